@@ -1,6 +1,6 @@
 public class Test {
     static double a;
-	public static void main() {
+	public static void main(String[] args) {
 		Test x = new Test();
         a = x.foo();
     }
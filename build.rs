@@ -0,0 +1,11 @@
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(asm_entry)");
+    println!("cargo::rerun-if-changed=build.rs");
+
+    // Methods are entered through sysv64 assembly using the internal calling convention on x86-64. All other
+    // targets (and builds with the portable-entry feature) call ordinary functions instead.
+    let arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    if arch == "x86_64" && std::env::var_os("CARGO_FEATURE_PORTABLE_ENTRY").is_none() {
+        println!("cargo::rustc-cfg=asm_entry");
+    }
+}
@@ -0,0 +1,62 @@
+package java.io;
+
+// The PrintStream of the VM-provided bootstrap classes, which writes directly to a file descriptor of the VM
+// (1 for System.out, 2 for System.err) through the native method write
+public class PrintStream {
+    private final int descriptor;
+
+    public PrintStream(int descriptor) {
+        this.descriptor = descriptor;
+    }
+
+    public void print(String string) {
+        write(descriptor, string);
+    }
+
+    public void print(int i) {
+        print(Integer.toString(i));
+    }
+
+    public void print(char c) {
+        print(String.valueOf(c));
+    }
+
+    public void print(boolean b) {
+        print(String.valueOf(b));
+    }
+
+    public void print(Object object) {
+        print(String.valueOf(object));
+    }
+
+    public void println() {
+        print("\n");
+    }
+
+    public void println(String string) {
+        print(string);
+        println();
+    }
+
+    public void println(int i) {
+        print(i);
+        println();
+    }
+
+    public void println(char c) {
+        print(c);
+        println();
+    }
+
+    public void println(boolean b) {
+        print(b);
+        println();
+    }
+
+    public void println(Object object) {
+        print(object);
+        println();
+    }
+
+    private static native void write(int descriptor, String string);
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The AbstractMethodError of the VM-provided bootstrap classes, thrown by the VM on calls of abstract methods
+public class AbstractMethodError extends IncompatibleClassChangeError {
+    public AbstractMethodError() {
+    }
+
+    public AbstractMethodError(String message) {
+        super(message);
+    }
+}
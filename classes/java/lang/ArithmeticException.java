@@ -0,0 +1,11 @@
+package java.lang;
+
+// The ArithmeticException of the VM-provided bootstrap classes, thrown by the VM on integer division by zero
+public class ArithmeticException extends RuntimeException {
+    public ArithmeticException() {
+    }
+
+    public ArithmeticException(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The ArrayIndexOutOfBoundsException of the VM-provided bootstrap classes, thrown by the VM on array accesses
+public class ArrayIndexOutOfBoundsException extends IndexOutOfBoundsException {
+    public ArrayIndexOutOfBoundsException() {
+    }
+
+    public ArrayIndexOutOfBoundsException(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The ArrayStoreException of the VM-provided bootstrap classes, thrown by the VM on stores into arrays of an incompatible type
+public class ArrayStoreException extends RuntimeException {
+    public ArrayStoreException() {
+    }
+
+    public ArrayStoreException(String message) {
+        super(message);
+    }
+}
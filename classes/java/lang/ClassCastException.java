@@ -0,0 +1,11 @@
+package java.lang;
+
+// The ClassCastException of the VM-provided bootstrap classes, thrown by the VM on failing checkcasts
+public class ClassCastException extends RuntimeException {
+    public ClassCastException() {
+    }
+
+    public ClassCastException(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The ClassCircularityError of the VM-provided bootstrap classes, thrown by the VM when a class is its own superclass
+public class ClassCircularityError extends LinkageError {
+    public ClassCircularityError() {
+    }
+
+    public ClassCircularityError(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,15 @@
+package java.lang;
+
+// The Error of the VM-provided bootstrap classes
+public class Error extends Throwable {
+    public Error() {
+    }
+
+    public Error(String message) {
+        super(message);
+    }
+
+    public Error(String message, Throwable cause) {
+        super(message, cause);
+    }
+}
@@ -0,0 +1,15 @@
+package java.lang;
+
+// The Exception of the VM-provided bootstrap classes
+public class Exception extends Throwable {
+    public Exception() {
+    }
+
+    public Exception(String message) {
+        super(message);
+    }
+
+    public Exception(String message, Throwable cause) {
+        super(message, cause);
+    }
+}
@@ -0,0 +1,20 @@
+package java.lang;
+
+// The ExceptionInInitializerError of the VM-provided bootstrap classes, thrown by the VM in place of an exception of a
+// static initializer that isn't an Error. The VM sets the cause without running a constructor.
+public class ExceptionInInitializerError extends LinkageError {
+    public ExceptionInInitializerError() {
+    }
+
+    public ExceptionInInitializerError(String message) {
+        super(message);
+    }
+
+    public ExceptionInInitializerError(Throwable thrown) {
+        super(null, thrown);
+    }
+
+    public Throwable getException() {
+        return getCause();
+    }
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The IllegalAccessError of the VM-provided bootstrap classes, thrown by the VM on accesses of inaccessible members
+public class IllegalAccessError extends IncompatibleClassChangeError {
+    public IllegalAccessError() {
+    }
+
+    public IllegalAccessError(String message) {
+        super(message);
+    }
+}
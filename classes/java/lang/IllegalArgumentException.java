@@ -0,0 +1,11 @@
+package java.lang;
+
+// The IllegalArgumentException of the VM-provided bootstrap classes
+public class IllegalArgumentException extends RuntimeException {
+    public IllegalArgumentException() {
+    }
+
+    public IllegalArgumentException(String message) {
+        super(message);
+    }
+}
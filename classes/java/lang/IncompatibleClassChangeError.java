@@ -0,0 +1,11 @@
+package java.lang;
+
+// The IncompatibleClassChangeError of the VM-provided bootstrap classes
+public class IncompatibleClassChangeError extends LinkageError {
+    public IncompatibleClassChangeError() {
+    }
+
+    public IncompatibleClassChangeError(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The IndexOutOfBoundsException of the VM-provided bootstrap classes
+public class IndexOutOfBoundsException extends RuntimeException {
+    public IndexOutOfBoundsException() {
+    }
+
+    public IndexOutOfBoundsException(String message) {
+        super(message);
+    }
+}
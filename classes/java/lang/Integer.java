@@ -0,0 +1,91 @@
+package java.lang;
+
+// The Integer of the VM-provided bootstrap classes. The value is held in the field value, like the VM's boxing
+// expects.
+public final class Integer {
+    private final int value;
+
+    public Integer(int value) {
+        this.value = value;
+    }
+
+    public static Integer valueOf(int i) {
+        return new Integer(i);
+    }
+
+    public int intValue() {
+        return value;
+    }
+
+    public String toString() {
+        return toString(value);
+    }
+
+    // The VM replaces the following methods by intrinsics, the bytecode is only kept for completeness
+
+    public static int compareUnsigned(int x, int y) {
+        x += 0x80000000;
+        y += 0x80000000;
+        return x < y ? -1 : (x == y ? 0 : 1);
+    }
+
+    public static int divideUnsigned(int dividend, int divisor) {
+        return (int) ((dividend & 0xffffffffL) / (divisor & 0xffffffffL));
+    }
+
+    public static int remainderUnsigned(int dividend, int divisor) {
+        return (int) ((dividend & 0xffffffffL) % (divisor & 0xffffffffL));
+    }
+
+    public static int numberOfLeadingZeros(int i) {
+        int n = 0;
+        while (n < 32 && i >= 0) {
+            n++;
+            i <<= 1;
+        }
+        return n;
+    }
+
+    public static int numberOfTrailingZeros(int i) {
+        int n = 0;
+        while (n < 32 && (i & 1) == 0) {
+            n++;
+            i >>>= 1;
+        }
+        return n;
+    }
+
+    public static int bitCount(int i) {
+        int n = 0;
+        while (i != 0) {
+            i &= i - 1;
+            n++;
+        }
+        return n;
+    }
+
+    public static int reverseBytes(int i) {
+        return (i << 24) | ((i & 0xff00) << 8) | ((i >>> 8) & 0xff00) | (i >>> 24);
+    }
+
+    public static String toString(int i) {
+        // The digits are taken from the negated value, because the negation of MIN_VALUE overflows
+        char[] buffer = new char[11];
+        int position = buffer.length;
+        boolean negative = i < 0;
+        if (!negative) {
+            i = -i;
+        }
+        do {
+            int quotient = i / 10;
+            position--;
+            buffer[position] = (char) ('0' + quotient * 10 - i);
+            i = quotient;
+        } while (i != 0);
+        if (negative) {
+            position--;
+            buffer[position] = '-';
+        }
+        return new String(buffer, position, buffer.length - position);
+    }
+}
@@ -0,0 +1,15 @@
+package java.lang;
+
+// The InternalError of the VM-provided bootstrap classes
+public class InternalError extends VirtualMachineError {
+    public InternalError() {
+    }
+
+    public InternalError(String message) {
+        super(message);
+    }
+
+    public InternalError(String message, Throwable cause) {
+        super(message, cause);
+    }
+}
@@ -0,0 +1,15 @@
+package java.lang;
+
+// The LinkageError of the VM-provided bootstrap classes
+public class LinkageError extends Error {
+    public LinkageError() {
+    }
+
+    public LinkageError(String message) {
+        super(message);
+    }
+
+    public LinkageError(String message, Throwable cause) {
+        super(message, cause);
+    }
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The NegativeArraySizeException of the VM-provided bootstrap classes, thrown by the VM on the creation of arrays
+public class NegativeArraySizeException extends RuntimeException {
+    public NegativeArraySizeException() {
+    }
+
+    public NegativeArraySizeException(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The NoClassDefFoundError of the VM-provided bootstrap classes, thrown by the VM when a class can't be loaded
+public class NoClassDefFoundError extends LinkageError {
+    public NoClassDefFoundError() {
+    }
+
+    public NoClassDefFoundError(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The NoSuchFieldError of the VM-provided bootstrap classes, thrown by the VM on the resolution of fields
+public class NoSuchFieldError extends IncompatibleClassChangeError {
+    public NoSuchFieldError() {
+    }
+
+    public NoSuchFieldError(String message) {
+        super(message);
+    }
+}
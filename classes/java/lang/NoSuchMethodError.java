@@ -0,0 +1,11 @@
+package java.lang;
+
+// The NoSuchMethodError of the VM-provided bootstrap classes, thrown by the VM on the resolution of methods
+public class NoSuchMethodError extends IncompatibleClassChangeError {
+    public NoSuchMethodError() {
+    }
+
+    public NoSuchMethodError(String message) {
+        super(message);
+    }
+}
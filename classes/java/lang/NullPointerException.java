@@ -0,0 +1,11 @@
+package java.lang;
+
+// The NullPointerException of the VM-provided bootstrap classes, thrown by the VM on the dereference of null
+public class NullPointerException extends RuntimeException {
+    public NullPointerException() {
+    }
+
+    public NullPointerException(String message) {
+        super(message);
+    }
+}
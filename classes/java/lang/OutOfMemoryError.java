@@ -0,0 +1,11 @@
+package java.lang;
+
+// The OutOfMemoryError of the VM-provided bootstrap classes, thrown by the VM when the heap is exhausted
+public class OutOfMemoryError extends VirtualMachineError {
+    public OutOfMemoryError() {
+    }
+
+    public OutOfMemoryError(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,15 @@
+package java.lang;
+
+// The RuntimeException of the VM-provided bootstrap classes
+public class RuntimeException extends Exception {
+    public RuntimeException() {
+    }
+
+    public RuntimeException(String message) {
+        super(message);
+    }
+
+    public RuntimeException(String message, Throwable cause) {
+        super(message, cause);
+    }
+}
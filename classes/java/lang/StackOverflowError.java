@@ -0,0 +1,11 @@
+package java.lang;
+
+// The StackOverflowError of the VM-provided bootstrap classes, thrown by the VM when the Java stack is exhausted
+public class StackOverflowError extends VirtualMachineError {
+    public StackOverflowError() {
+    }
+
+    public StackOverflowError(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,85 @@
+package java.lang;
+
+// The String of the VM-provided bootstrap classes. It only uses the features that the VM supports, e.g. no
+// System.arraycopy and no reference comparisons. The characters are stored in value, like in the JDK 8 class library,
+// which is the layout that the VM expects for string constants.
+public final class String {
+    private final char[] value;
+
+    public String() {
+        value = new char[0];
+    }
+
+    public String(char[] value) {
+        this(value, 0, value.length);
+    }
+
+    public String(char[] value, int offset, int count) {
+        char[] copy = new char[count];
+        for (int i = 0; i < count; i++) {
+            copy[i] = value[offset + i];
+        }
+        this.value = copy;
+    }
+
+    public int length() {
+        return value.length;
+    }
+
+    public boolean isEmpty() {
+        return value.length == 0;
+    }
+
+    public char charAt(int index) {
+        return value[index];
+    }
+
+    public String concat(String other) {
+        return new StringBuilder(this).append(other).toString();
+    }
+
+    public boolean equals(Object other) {
+        if (!(other instanceof String)) {
+            return false;
+        }
+        String string = (String) other;
+        if (string.value.length != value.length) {
+            return false;
+        }
+        for (int i = 0; i < value.length; i++) {
+            if (string.value[i] != value[i]) {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    public int hashCode() {
+        int hash = 0;
+        for (int i = 0; i < value.length; i++) {
+            hash = 31 * hash + value[i];
+        }
+        return hash;
+    }
+
+    public String toString() {
+        return this;
+    }
+
+    // Unlike in the JDK, null can't be converted, because the VM can't compare references with null yet
+    public static String valueOf(Object object) {
+        return object.toString();
+    }
+
+    public static String valueOf(int i) {
+        return Integer.toString(i);
+    }
+
+    public static String valueOf(char c) {
+        return new String(new char[] { c });
+    }
+
+    public static String valueOf(boolean b) {
+        return b ? "true" : "false";
+    }
+}
@@ -0,0 +1,74 @@
+package java.lang;
+
+// The StringBuilder of the VM-provided bootstrap classes, which javac uses for string concatenation when compiling
+// for Java 8 (--release 8 or -XDstringConcat=inline). Only ints, chars, booleans, strings and objects can be
+// appended so far.
+public final class StringBuilder {
+    private char[] value;
+    private int count;
+
+    public StringBuilder() {
+        this(16);
+    }
+
+    public StringBuilder(int capacity) {
+        value = new char[capacity];
+    }
+
+    public StringBuilder(String string) {
+        this(string.length() + 16);
+        append(string);
+    }
+
+    public int length() {
+        return count;
+    }
+
+    public char charAt(int index) {
+        return value[index];
+    }
+
+    public StringBuilder append(String string) {
+        int length = string.length();
+        ensureCapacity(count + length);
+        for (int i = 0; i < length; i++) {
+            value[count + i] = string.charAt(i);
+        }
+        count += length;
+        return this;
+    }
+
+    public StringBuilder append(char c) {
+        ensureCapacity(count + 1);
+        value[count] = c;
+        count++;
+        return this;
+    }
+
+    public StringBuilder append(int i) {
+        return append(Integer.toString(i));
+    }
+
+    public StringBuilder append(boolean b) {
+        return append(String.valueOf(b));
+    }
+
+    public StringBuilder append(Object object) {
+        return append(String.valueOf(object));
+    }
+
+    public String toString() {
+        return new String(value, 0, count);
+    }
+
+    private void ensureCapacity(int capacity) {
+        if (capacity <= value.length) {
+            return;
+        }
+        char[] grown = new char[capacity + value.length + 2];
+        for (int i = 0; i < count; i++) {
+            grown[i] = value[i];
+        }
+        value = grown;
+    }
+}
@@ -0,0 +1,41 @@
+package java.lang;
+
+// The Throwable of the VM-provided bootstrap classes. The message is held in the field detailMessage, where the VM
+// puts the messages of the exceptions it throws itself, and the stack trace is recorded by the VM.
+public class Throwable {
+    private String detailMessage;
+    private Throwable cause;
+
+    public Throwable() {
+        fillInStackTrace();
+    }
+
+    public Throwable(String message) {
+        fillInStackTrace();
+        detailMessage = message;
+    }
+
+    public Throwable(String message, Throwable cause) {
+        fillInStackTrace();
+        detailMessage = message;
+        this.cause = cause;
+    }
+
+    public String getMessage() {
+        return detailMessage;
+    }
+
+    public String getLocalizedMessage() {
+        return getMessage();
+    }
+
+    public Throwable getCause() {
+        return cause;
+    }
+
+    public Throwable fillInStackTrace() {
+        return fillInStackTrace(0);
+    }
+
+    private native Throwable fillInStackTrace(int dummy);
+}
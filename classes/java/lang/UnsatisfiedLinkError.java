@@ -0,0 +1,11 @@
+package java.lang;
+
+// The UnsatisfiedLinkError of the VM-provided bootstrap classes, thrown by the VM when a native method isn't registered
+public class UnsatisfiedLinkError extends LinkageError {
+    public UnsatisfiedLinkError() {
+    }
+
+    public UnsatisfiedLinkError(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,11 @@
+package java.lang;
+
+// The VerifyError of the VM-provided bootstrap classes, thrown by the VM when a method fails verification
+public class VerifyError extends LinkageError {
+    public VerifyError() {
+    }
+
+    public VerifyError(String message) {
+        super(message);
+    }
+}
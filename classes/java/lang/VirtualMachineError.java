@@ -0,0 +1,15 @@
+package java.lang;
+
+// The VirtualMachineError of the VM-provided bootstrap classes
+public abstract class VirtualMachineError extends Error {
+    public VirtualMachineError() {
+    }
+
+    public VirtualMachineError(String message) {
+        super(message);
+    }
+
+    public VirtualMachineError(String message, Throwable cause) {
+        super(message, cause);
+    }
+}
@@ -1,248 +1,617 @@
-pub const NOP: u8 = 0x00;
-
-pub const ACONST_NULL: u8 = 0x01;
-
-pub const ICONST_M1: u8 = 0x02;
-pub const ICONST_0: u8 = 0x03;
-pub const ICONST_1: u8 = 0x04;
-pub const ICONST_2: u8 = 0x05;
-pub const ICONST_3: u8 = 0x06;
-pub const ICONST_4: u8 = 0x07;
-pub const ICONST_5: u8 = 0x08;
-pub const LCONST_0: u8 = 0x09;
-pub const LCONST_1: u8 = 0x0A;
-pub const FCONST_0: u8 = 0x0B;
-pub const FCONST_1: u8 = 0x0C;
-pub const FCONST_2: u8 = 0x0D;
-pub const DCONST_0: u8 = 0x0E;
-pub const DCONST_1: u8 = 0x0F;
-
-pub const BIPUSH: u8 = 0x10;
-pub const SIPUSH: u8 = 0x11;
-
-pub const LDC: u8 = 0x12;
-pub const LDC_W: u8 = 0x13;
-pub const LDC2_W: u8 = 0x14;
-
-pub const ILOAD: u8 = 0x15;
-pub const LLOAD: u8 = 0x16;
-pub const FLOAD: u8 = 0x17;
-pub const DLOAD: u8 = 0x18;
-pub const ALOAD: u8 = 0x19;
-
-pub const ILOAD_0: u8 = 0x1A;
-pub const ILOAD_1: u8 = 0x1B;
-pub const ILOAD_2: u8 = 0x1C;
-pub const ILOAD_3: u8 = 0x1D;
-pub const LLOAD_0: u8 = 0x1E;
-pub const LLOAD_1: u8 = 0x1F;
-pub const LLOAD_2: u8 = 0x20;
-pub const LLOAD_3: u8 = 0x21;
-pub const FLOAD_0: u8 = 0x22;
-pub const FLOAD_1: u8 = 0x23;
-pub const FLOAD_2: u8 = 0x24;
-pub const FLOAD_3: u8 = 0x25;
-pub const DLOAD_0: u8 = 0x26;
-pub const DLOAD_1: u8 = 0x27;
-pub const DLOAD_2: u8 = 0x28;
-pub const DLOAD_3: u8 = 0x29;
-pub const ALOAD_0: u8 = 0x2A;
-pub const ALOAD_1: u8 = 0x2B;
-pub const ALOAD_2: u8 = 0x2C;
-pub const ALOAD_3: u8 = 0x2D;
-
-pub const IALOAD: u8 = 0x2E;
-pub const LALOAD: u8 = 0x2F;
-pub const FALOAD: u8 = 0x30;
-pub const DALOAD: u8 = 0x31;
-pub const AALOAD: u8 = 0x32;
-pub const BALOAD: u8 = 0x33;
-pub const CALOAD: u8 = 0x34;
-pub const SALOAD: u8 = 0x35;
-
-pub const ISTORE: u8 = 0x36;
-pub const LSTORE: u8 = 0x37;
-pub const FSTORE: u8 = 0x38;
-pub const DSTORE: u8 = 0x39;
-pub const ASTORE: u8 = 0x3A;
-
-pub const ISTORE_0: u8 = 0x3B;
-pub const ISTORE_1: u8 = 0x3C;
-pub const ISTORE_2: u8 = 0x3D;
-pub const ISTORE_3: u8 = 0x3E;
-pub const LSTORE_0: u8 = 0x3F;
-pub const LSTORE_1: u8 = 0x40;
-pub const LSTORE_2: u8 = 0x41;
-pub const LSTORE_3: u8 = 0x42;
-pub const FSTORE_0: u8 = 0x43;
-pub const FSTORE_1: u8 = 0x44;
-pub const FSTORE_2: u8 = 0x45;
-pub const FSTORE_3: u8 = 0x46;
-pub const DSTORE_0: u8 = 0x47;
-pub const DSTORE_1: u8 = 0x48;
-pub const DSTORE_2: u8 = 0x49;
-pub const DSTORE_3: u8 = 0x4A;
-pub const ASTORE_0: u8 = 0x4B;
-pub const ASTORE_1: u8 = 0x4C;
-pub const ASTORE_2: u8 = 0x4D;
-pub const ASTORE_3: u8 = 0x4E;
-
-pub const IASTORE: u8 = 0x4F;
-pub const LASTORE: u8 = 0x50;
-pub const FASTORE: u8 = 0x51;
-pub const DASTORE: u8 = 0x52;
-pub const AASTORE: u8 = 0x53;
-pub const BASTORE: u8 = 0x54;
-pub const CASTORE: u8 = 0x55;
-pub const SASTORE: u8 = 0x56;
-
-pub const POP: u8 = 0x57;
-pub const POP2: u8 = 0x58;
-
-pub const DUP: u8 = 0x59;
-pub const DUP_X1: u8 = 0x5A;
-pub const DUP_X2: u8 = 0x5B;
-pub const DUP2: u8 = 0x5C;
-pub const DUP2_X1: u8 = 0x5D;
-pub const DUP2_X2: u8 = 0x5E;
-
-pub const SWAP: u8 = 0x5F;
-
-pub const IADD: u8 = 0x60;
-pub const LADD: u8 = 0x61;
-pub const FADD: u8 = 0x62;
-pub const DADD: u8 = 0x63;
-
-pub const ISUB: u8 = 0x64;
-pub const LSUB: u8 = 0x65;
-pub const FSUB: u8 = 0x66;
-pub const DSUB: u8 = 0x67;
-
-pub const IMUL: u8 = 0x68;
-pub const LMUL: u8 = 0x69;
-pub const FMUL: u8 = 0x6A;
-pub const DMUL: u8 = 0x6B;
-
-pub const IDIV: u8 = 0x6C;
-pub const LDIV: u8 = 0x6D;
-pub const FDIV: u8 = 0x6E;
-pub const DDIV: u8 = 0x6F;
-
-pub const IREM: u8 = 0x70;
-pub const LREM: u8 = 0x71;
-pub const FREM: u8 = 0x72;
-pub const DREM: u8 = 0x73;
-
-pub const INEG: u8 = 0x74;
-pub const LNEG: u8 = 0x75;
-pub const FNEG: u8 = 0x76;
-pub const DNEG: u8 = 0x77;
-
-pub const ISHL: u8 = 0x78;
-pub const LSHL: u8 = 0x79;
-
-pub const ISHR: u8 = 0x7A;
-pub const LSHR: u8 = 0x7B;
-
-pub const IUSHR: u8 = 0x7C;
-pub const LUSHR: u8 = 0x7D;
-
-pub const IAND: u8 = 0x7E;
-pub const LAND: u8 = 0x7F;
-
-pub const IOR: u8 = 0x80;
-pub const LOR: u8 = 0x81;
-
-pub const IXOR: u8 = 0x82;
-pub const LXOR: u8 = 0x83;
-
-pub const IINC: u8 = 0x84;
-
-pub const I2L: u8 = 0x85;
-pub const I2F: u8 = 0x86;
-pub const I2D: u8 = 0x87;
-pub const L2I: u8 = 0x88;
-pub const L2F: u8 = 0x89;
-pub const L2D: u8 = 0x8A;
-pub const F2I: u8 = 0x8B;
-pub const F2L: u8 = 0x8C;
-pub const F2D: u8 = 0x8D;
-pub const D2I: u8 = 0x8E;
-pub const D2L: u8 = 0x8F;
-pub const D2F: u8 = 0x90;
-pub const I2B: u8 = 0x91;
-pub const I2C: u8 = 0x92;
-pub const I2S: u8 = 0x93;
-
-pub const LCMP: u8 = 0x94;
-pub const FCMPL: u8 = 0x95;
-pub const FCMPG: u8 = 0x96;
-pub const DCMPL: u8 = 0x97;
-pub const DCMPG: u8 = 0x98;
-
-pub const IFEQ: u8 = 0x99;
-pub const IFNE: u8 = 0x9A;
-pub const IFLT: u8 = 0x9B;
-pub const IFGE: u8 = 0x9C;
-pub const IFGT: u8 = 0x9D;
-pub const IFLE: u8 = 0x9E;
-pub const IF_ICMPEQ: u8 = 0x9F;
-pub const IF_ICMPNE: u8 = 0xA0;
-pub const IF_ICMPLT: u8 = 0xA1;
-pub const IF_ICMPGE: u8 = 0xA2;
-pub const IF_ICMPGT: u8 = 0xA3;
-pub const IF_ICMPLE: u8 = 0xA4;
-pub const IF_ACMPEQ: u8 = 0xA5;
-pub const IF_ACMPNE: u8 = 0xA6;
-
-pub const GOTO: u8 = 0xA7;
-
-pub const JSR: u8 = 0xA8; // Deprecated
-pub const RET: u8 = 0xA9; // Deprecated
-
-pub const TABLESWITCH: u8 = 0xAA;
-pub const LOOKUPSWITCH: u8 = 0xAB;
-
-pub const IRETURN: u8 = 0xAC;
-pub const LRETURN: u8 = 0xAD;
-pub const FRETURN: u8 = 0xAE;
-pub const DRETURN: u8 = 0xAF;
-pub const ARETURN: u8 = 0xB0;
-pub const RETURN: u8 = 0xB1;
-
-pub const GETSTATIC: u8 = 0xB2;
-pub const PUTSTATIC: u8 = 0xB3;
-pub const GETFIELD: u8 = 0xB4;
-pub const PUTFIELD: u8 = 0xB5;
-
-pub const INVOKEVIRTUAL: u8 = 0xB6;
-pub const INVOKESPECIAL: u8 = 0xB7;
-pub const INVOKESTATIC: u8 = 0xB8;
-pub const INVOKEINTERFACE: u8 = 0xB9;
-pub const INVOKEDYNAMIC: u8 = 0xBA;
-
-pub const NEW: u8 = 0xBB;
-
-pub const NEWARRAY: u8 = 0xBC;
-pub const ANEWARRAY: u8 = 0xBD;
-pub const ARRAYLENGTH: u8 = 0xBE;
-
-pub const ATHROW: u8 = 0xBF;
-
-pub const CHECKCAST: u8 = 0xC0;
-pub const INSTANCEOF: u8 = 0xC1;
-
-pub const MONITORENTER: u8 = 0xC2;
-pub const MONITOREXIT: u8 = 0xC3;
-
-pub const WIDE: u8 = 0xC4;
-
-pub const MULTIANEWARRAY: u8 = 0xC5;
-
-pub const IFNULL: u8 = 0xC6;
-pub const IFNONNULL: u8 = 0xC7;
-
-pub const GOTO_W: u8 = 0xC8;
-pub const JSR_W: u8 = 0xC9; // Deprecated
-
-pub const BREAKPOINT: u8 = 0xCA;
\ No newline at end of file
+/// Defines a constant for every opcode together with the table of all opcodes, the table of their lengths and the
+/// table of the tiers that support them. Instructions without operands have a length of 1 byte, the length of the
+/// other instructions is given after => (including the opcode), or is variable if it depends on the operands.
+/// The tiers in brackets are the ones that support the opcode: the interpreter, the JIT (jit_with_asm_entry only if
+/// compiled code can call the VM, see cfg asm_entry), and reserved marks the opcodes that may not appear in class
+/// files. The verifier accepts all other opcodes.
+macro_rules! opcodes {
+    (@length) => { 1 };
+    (@length variable) => { 0 };
+    (@length $length:literal) => { $length };
+
+    (@tier interpreter) => { INTERPRETER };
+    (@tier jit) => { JIT };
+    (@tier jit_with_asm_entry) => { JIT_WITH_ASM_ENTRY };
+    (@tier reserved) => { RESERVED };
+
+    ($($name:ident = $value:literal $(=> $length:tt)? $([$($tier:ident),*])?,)*) => {
+        $(pub const $name: u8 = $value;)*
+
+        /// All opcodes that are defined by the JVM specification as (opcode, mnemonic)
+        pub const OPCODES: &[(u8, &str)] = &[$(($value, stringify!($name))),*];
+
+        /// The lengths of the instructions, 0 if the opcode is undefined or its length is variable
+        const LENGTHS: [u8; 256] = {
+            let mut lengths = [0; 256];
+            $(lengths[$value as usize] = opcodes!(@length $($length)?);)*
+            lengths
+        };
+
+        /// The tiers that support the opcodes, 0 if the opcode is undefined
+        const TIERS: [u8; 256] = {
+            let mut tiers = [0; 256];
+            $(tiers[$value as usize] = DEFINED $($(| opcodes!(@tier $tier))*)?;)*
+            tiers
+        };
+    };
+}
+
+// The flags of TIERS
+const DEFINED: u8 = 1;
+const INTERPRETER: u8 = 1 << 1;
+const JIT: u8 = 1 << 2;
+const JIT_WITH_ASM_ENTRY: u8 = 1 << 3;
+const RESERVED: u8 = 1 << 4;
+
+opcodes! {
+    NOP = 0x00,
+
+    ACONST_NULL = 0x01 [interpreter],
+
+    ICONST_M1 = 0x02 [interpreter, jit],
+    ICONST_0 = 0x03 [interpreter, jit],
+    ICONST_1 = 0x04 [interpreter, jit],
+    ICONST_2 = 0x05 [interpreter, jit],
+    ICONST_3 = 0x06 [interpreter, jit],
+    ICONST_4 = 0x07 [interpreter, jit],
+    ICONST_5 = 0x08 [interpreter, jit],
+    LCONST_0 = 0x09 [interpreter, jit],
+    LCONST_1 = 0x0A [interpreter, jit],
+    FCONST_0 = 0x0B [interpreter, jit],
+    FCONST_1 = 0x0C [interpreter, jit],
+    FCONST_2 = 0x0D [interpreter, jit],
+    DCONST_0 = 0x0E [interpreter, jit],
+    DCONST_1 = 0x0F [interpreter, jit],
+
+    BIPUSH = 0x10 => 2 [interpreter, jit],
+    SIPUSH = 0x11 => 3 [interpreter, jit],
+
+    LDC = 0x12 => 2 [interpreter, jit],
+    LDC_W = 0x13 => 3 [interpreter, jit],
+    LDC2_W = 0x14 => 3 [interpreter, jit],
+
+    ILOAD = 0x15 => 2 [interpreter],
+    LLOAD = 0x16 => 2 [interpreter],
+    FLOAD = 0x17 => 2 [interpreter],
+    DLOAD = 0x18 => 2 [interpreter],
+    ALOAD = 0x19 => 2 [interpreter],
+
+    ILOAD_0 = 0x1A [interpreter],
+    ILOAD_1 = 0x1B [interpreter],
+    ILOAD_2 = 0x1C [interpreter],
+    ILOAD_3 = 0x1D [interpreter],
+    LLOAD_0 = 0x1E [interpreter],
+    LLOAD_1 = 0x1F [interpreter],
+    LLOAD_2 = 0x20 [interpreter],
+    LLOAD_3 = 0x21 [interpreter],
+    FLOAD_0 = 0x22 [interpreter],
+    FLOAD_1 = 0x23 [interpreter],
+    FLOAD_2 = 0x24 [interpreter],
+    FLOAD_3 = 0x25 [interpreter],
+    DLOAD_0 = 0x26 [interpreter],
+    DLOAD_1 = 0x27 [interpreter],
+    DLOAD_2 = 0x28 [interpreter],
+    DLOAD_3 = 0x29 [interpreter],
+    ALOAD_0 = 0x2A [interpreter],
+    ALOAD_1 = 0x2B [interpreter],
+    ALOAD_2 = 0x2C [interpreter],
+    ALOAD_3 = 0x2D [interpreter],
+
+    IALOAD = 0x2E [interpreter],
+    LALOAD = 0x2F [interpreter],
+    FALOAD = 0x30 [interpreter],
+    DALOAD = 0x31 [interpreter],
+    AALOAD = 0x32 [interpreter],
+    BALOAD = 0x33 [interpreter],
+    CALOAD = 0x34 [interpreter],
+    SALOAD = 0x35 [interpreter],
+
+    ISTORE = 0x36 => 2 [interpreter],
+    LSTORE = 0x37 => 2 [interpreter],
+    FSTORE = 0x38 => 2 [interpreter],
+    DSTORE = 0x39 => 2 [interpreter],
+    ASTORE = 0x3A => 2 [interpreter],
+
+    ISTORE_0 = 0x3B [interpreter],
+    ISTORE_1 = 0x3C [interpreter],
+    ISTORE_2 = 0x3D [interpreter],
+    ISTORE_3 = 0x3E [interpreter],
+    LSTORE_0 = 0x3F [interpreter],
+    LSTORE_1 = 0x40 [interpreter],
+    LSTORE_2 = 0x41 [interpreter],
+    LSTORE_3 = 0x42 [interpreter],
+    FSTORE_0 = 0x43 [interpreter],
+    FSTORE_1 = 0x44 [interpreter],
+    FSTORE_2 = 0x45 [interpreter],
+    FSTORE_3 = 0x46 [interpreter],
+    DSTORE_0 = 0x47 [interpreter],
+    DSTORE_1 = 0x48 [interpreter],
+    DSTORE_2 = 0x49 [interpreter],
+    DSTORE_3 = 0x4A [interpreter],
+    ASTORE_0 = 0x4B [interpreter],
+    ASTORE_1 = 0x4C [interpreter],
+    ASTORE_2 = 0x4D [interpreter],
+    ASTORE_3 = 0x4E [interpreter],
+
+    IASTORE = 0x4F [interpreter],
+    LASTORE = 0x50 [interpreter],
+    FASTORE = 0x51 [interpreter],
+    DASTORE = 0x52 [interpreter],
+    AASTORE = 0x53 [interpreter],
+    BASTORE = 0x54 [interpreter],
+    CASTORE = 0x55 [interpreter],
+    SASTORE = 0x56 [interpreter],
+
+    POP = 0x57 [interpreter],
+    POP2 = 0x58 [interpreter],
+
+    DUP = 0x59 [interpreter],
+    DUP_X1 = 0x5A [interpreter],
+    DUP_X2 = 0x5B [interpreter],
+    DUP2 = 0x5C [interpreter],
+    DUP2_X1 = 0x5D [interpreter],
+    DUP2_X2 = 0x5E [interpreter],
+
+    SWAP = 0x5F [interpreter],
+
+    IADD = 0x60 [interpreter],
+    LADD = 0x61 [interpreter],
+    FADD = 0x62 [interpreter],
+    DADD = 0x63 [interpreter],
+
+    ISUB = 0x64 [interpreter],
+    LSUB = 0x65 [interpreter],
+    FSUB = 0x66 [interpreter],
+    DSUB = 0x67 [interpreter],
+
+    IMUL = 0x68 [interpreter],
+    LMUL = 0x69 [interpreter],
+    FMUL = 0x6A [interpreter],
+    DMUL = 0x6B [interpreter],
+
+    IDIV = 0x6C [interpreter],
+    LDIV = 0x6D [interpreter],
+    FDIV = 0x6E [interpreter],
+    DDIV = 0x6F [interpreter],
+
+    IREM = 0x70 [interpreter],
+    LREM = 0x71 [interpreter],
+    FREM = 0x72 [interpreter],
+    DREM = 0x73 [interpreter],
+
+    INEG = 0x74 [interpreter],
+    LNEG = 0x75 [interpreter],
+    FNEG = 0x76 [interpreter],
+    DNEG = 0x77 [interpreter],
+
+    ISHL = 0x78,
+    LSHL = 0x79,
+
+    ISHR = 0x7A,
+    LSHR = 0x7B,
+
+    IUSHR = 0x7C,
+    LUSHR = 0x7D,
+
+    IAND = 0x7E [interpreter],
+    LAND = 0x7F [interpreter],
+
+    IOR = 0x80 [interpreter],
+    LOR = 0x81 [interpreter],
+
+    IXOR = 0x82 [interpreter],
+    LXOR = 0x83 [interpreter],
+
+    IINC = 0x84 => 3 [interpreter],
+
+    I2L = 0x85 [interpreter],
+    I2F = 0x86 [interpreter],
+    I2D = 0x87 [interpreter],
+    L2I = 0x88 [interpreter],
+    L2F = 0x89 [interpreter],
+    L2D = 0x8A [interpreter],
+    F2I = 0x8B [interpreter],
+    F2L = 0x8C [interpreter],
+    F2D = 0x8D [interpreter],
+    D2I = 0x8E [interpreter],
+    D2L = 0x8F [interpreter],
+    D2F = 0x90 [interpreter],
+    I2B = 0x91 [interpreter],
+    I2C = 0x92 [interpreter],
+    I2S = 0x93 [interpreter],
+
+    LCMP = 0x94 [interpreter],
+    FCMPL = 0x95 [interpreter],
+    FCMPG = 0x96 [interpreter],
+    DCMPL = 0x97 [interpreter],
+    DCMPG = 0x98 [interpreter],
+
+    IFEQ = 0x99 => 3 [interpreter, jit],
+    IFNE = 0x9A => 3 [interpreter, jit],
+    IFLT = 0x9B => 3 [interpreter, jit],
+    IFGE = 0x9C => 3 [interpreter, jit],
+    IFGT = 0x9D => 3 [interpreter, jit],
+    IFLE = 0x9E => 3 [interpreter, jit],
+    IF_ICMPEQ = 0x9F => 3 [interpreter, jit],
+    IF_ICMPNE = 0xA0 => 3 [interpreter, jit],
+    IF_ICMPLT = 0xA1 => 3 [interpreter, jit],
+    IF_ICMPGE = 0xA2 => 3 [interpreter, jit],
+    IF_ICMPGT = 0xA3 => 3 [interpreter, jit],
+    IF_ICMPLE = 0xA4 => 3 [interpreter, jit],
+    IF_ACMPEQ = 0xA5 => 3,
+    IF_ACMPNE = 0xA6 => 3,
+
+    GOTO = 0xA7 => 3 [interpreter, jit],
+
+    JSR = 0xA8 => 3, // Deprecated
+    RET = 0xA9 => 2, // Deprecated
+
+    TABLESWITCH = 0xAA => variable [interpreter],
+    LOOKUPSWITCH = 0xAB => variable [interpreter],
+
+    IRETURN = 0xAC [interpreter, jit],
+    LRETURN = 0xAD [interpreter],
+    FRETURN = 0xAE [interpreter],
+    DRETURN = 0xAF [interpreter],
+    ARETURN = 0xB0 [interpreter],
+    RETURN = 0xB1 [interpreter, jit],
+
+    GETSTATIC = 0xB2 => 3 [interpreter],
+    PUTSTATIC = 0xB3 => 3 [interpreter],
+    GETFIELD = 0xB4 => 3 [interpreter, jit_with_asm_entry],
+    PUTFIELD = 0xB5 => 3 [interpreter],
+
+    INVOKEVIRTUAL = 0xB6 => 3 [interpreter],
+    INVOKESPECIAL = 0xB7 => 3 [interpreter, jit_with_asm_entry],
+    INVOKESTATIC = 0xB8 => 3 [interpreter, jit_with_asm_entry],
+    INVOKEINTERFACE = 0xB9 => 5 [interpreter],
+    INVOKEDYNAMIC = 0xBA => 5 [interpreter], // Links no call sites without java.lang.invoke, see UnsupportedDynamic
+
+    NEW = 0xBB => 3 [interpreter, jit_with_asm_entry],
+
+    NEWARRAY = 0xBC => 2 [interpreter],
+    ANEWARRAY = 0xBD => 3 [interpreter],
+    ARRAYLENGTH = 0xBE [interpreter],
+
+    ATHROW = 0xBF [interpreter, jit_with_asm_entry],
+
+    CHECKCAST = 0xC0 => 3 [interpreter],
+    INSTANCEOF = 0xC1 => 3 [interpreter],
+
+    MONITORENTER = 0xC2,
+    MONITOREXIT = 0xC3,
+
+    WIDE = 0xC4 => variable [interpreter],
+
+    MULTIANEWARRAY = 0xC5 => 4,
+
+    IFNULL = 0xC6 => 3,
+    IFNONNULL = 0xC7 => 3,
+
+    GOTO_W = 0xC8 => 5 [interpreter],
+    JSR_W = 0xC9 => 5, // Deprecated
+
+    BREAKPOINT = 0xCA [reserved], // Only set by the debugger in the quickened code
+    IMPDEP1 = 0xFE [reserved],
+    IMPDEP2 = 0xFF [reserved],
+}
+
+// Quickened instructions, which only exist in the code that is executed by the interpreter (see interpreter::quickening).
+// Their operand is an index into the resolved operands of the method instead of a constant pool index.
+pub const GETSTATIC_QUICK: u8 = 0xCB;
+pub const PUTSTATIC_QUICK: u8 = 0xCC;
+pub const GETFIELD_QUICK: u8 = 0xCD;
+pub const PUTFIELD_QUICK: u8 = 0xCE;
+pub const INVOKEVIRTUAL_QUICK: u8 = 0xCF;
+pub const INVOKENONVIRTUAL_QUICK: u8 = 0xD0; // invokestatic, invokespecial and invokevirtual of private methods
+pub const INVOKESTATIC_TAIL_QUICK: u8 = 0xD4; // invokestatic in tail position (see interpreter::tail_call)
+
+// Superinstructions, which replace the first instruction of a frequent sequence in the code that is executed by the
+// interpreter (see interpreter::fusion). The following instructions of the sequence stay in place.
+pub const ALOAD_0_GETFIELD: u8 = 0xD1; // aload_0, getfield
+pub const ILOAD_ILOAD_IADD: u8 = 0xD2; // iload(_<n>), iload(_<n>), iadd
+pub const ICONST_ISTORE: u8 = 0xD3; // iconst_<i>, istore(_<n>)
+
+// invokestatic directly followed by a return, which is marked while the code is pre-decoded (see
+// interpreter::tail_call). It is quickened to INVOKESTATIC_TAIL_QUICK like an invokestatic.
+pub const INVOKESTATIC_TAIL: u8 = 0xD5;
+
+/// The length of the instruction at pc in bytes (including the opcode and any padding), or None if the code ends
+/// before the instruction
+pub fn instruction_length(code: &[u8], pc: usize) -> Option<usize> {
+    let read_int = |position: usize| -> Option<i32> {
+        let bytes = code.get(position..position + 4)?;
+        Some(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    let length = match *code.get(pc)? {
+        WIDE => match *code.get(pc + 1)? {
+            IINC => 6,
+            _ => 4,
+        },
+        // The operands of the switches are aligned to four bytes, relative to the start of the code
+        TABLESWITCH => {
+            let padding = 3 - pc % 4;
+            let low = read_int(pc + 1 + padding + 4)?;
+            let high = read_int(pc + 1 + padding + 8)?;
+            1 + padding + 12 + 4 * (high as i64 - low as i64 + 1).max(0) as usize
+        }
+        LOOKUPSWITCH => {
+            let padding = 3 - pc % 4;
+            let pairs = read_int(pc + 1 + padding + 4)?;
+            1 + padding + 8 + 8 * pairs.max(0) as usize
+        }
+        // Undefined opcodes are rejected by the verifier and the interpreter
+        opcode => fixed_length(opcode).unwrap_or(1),
+    };
+    if pc + length <= code.len() {
+        Some(length)
+    } else {
+        None
+    }
+}
+
+/// The length of the instructions with the opcode in bytes (including the opcode), None if the opcode is undefined or
+/// the length depends on the operands (tableswitch, lookupswitch and wide)
+pub fn fixed_length(opcode: u8) -> Option<usize> {
+    match LENGTHS[opcode as usize] {
+        0 => None,
+        length => Some(length as usize),
+    }
+}
+
+/// Whether the interpreter executes the opcode, the others throw an InternalError
+pub fn is_interpreted(opcode: u8) -> bool {
+    TIERS[opcode as usize] & INTERPRETER != 0
+}
+
+/// Whether the JIT can compile methods that contain the opcode, the others stay interpreted
+pub fn is_compiled(opcode: u8) -> bool {
+    let tiers = TIERS[opcode as usize];
+    tiers & JIT != 0 || cfg!(asm_entry) && tiers & JIT_WITH_ASM_ENTRY != 0
+}
+
+/// Whether the verifier accepts the opcode, i.e. every opcode of the specification except the reserved ones
+pub fn is_verifiable(opcode: u8) -> bool {
+    TIERS[opcode as usize] & (DEFINED | RESERVED) == DEFINED
+}
+
+/// Lists the opcodes that the interpreter or the JIT can't execute or that the verifier rejects, so that users can
+/// predict whether their class files will run (--list-unsupported)
+pub fn unsupported_opcodes_report() -> String {
+    let mut report = format!(
+        "{:<8}{:<18}{:<14}{:<6}{}\n",
+        "opcode", "mnemonic", "interpreter", "jit", "verifier"
+    );
+    let mut interpreted = 0;
+    let mut compiled = 0;
+    let mut verifiable = 0;
+    for (opcode, mnemonic) in OPCODES {
+        let tiers = [
+            is_interpreted(*opcode),
+            is_compiled(*opcode),
+            is_verifiable(*opcode),
+        ];
+        interpreted += tiers[0] as usize;
+        compiled += tiers[1] as usize;
+        verifiable += tiers[2] as usize;
+        if tiers.iter().all(|supported| *supported) {
+            continue;
+        }
+        let [interpreter, jit, verifier] =
+            tiers.map(|supported| if supported { "yes" } else { "no" });
+        report.push_str(&format!(
+            "{:#04x}    {:<18}{:<14}{:<6}{}\n",
+            opcode,
+            mnemonic.to_ascii_lowercase(),
+            interpreter,
+            jit,
+            verifier
+        ));
+    }
+    report.push_str(&format!(
+        "{} opcodes: {} implemented by the interpreter, {} by the JIT, {} accepted by the verifier (-Xverify:all)\n",
+        OPCODES.len(),
+        interpreted,
+        compiled,
+        verifiable
+    ));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        class_parser,
+        class_writer::{
+            ClassWriter, CodeDefinition, ConstantPoolBuilder, FieldDefinition, MethodDefinition,
+        },
+        interpreter::ExecutionError,
+        jit::{self, CompilationError},
+        jvm::Jvm,
+        model::class_library::ClassIndex,
+        options::{ExecutionMode, VmOptions},
+        testing,
+        verifier::{Verifier, VerifyError},
+    };
+
+    use super::*;
+
+    const CLASS_NAME: &str = "Opcodes";
+    const ACC_STATIC: u16 = 0x0008;
+
+    /// The constant pool entries that the operands of the instructions refer to
+    struct Constants {
+        int: u16,
+        long: u16,
+        field: u16,  // static int value
+        method: u16, // static void target()
+        interface_method: u16,
+        class: u16,
+    }
+
+    /// Pushes zeros (which are null as references), so that the instruction has operands to pop, then executes the
+    /// instruction and returns. The branches of the instruction lead to the return.
+    fn code(opcode: u8, constants: &Constants) -> Vec<u8> {
+        let mut code = vec![ICONST_0; 8];
+        let pc = code.len();
+        code.push(opcode);
+        match opcode {
+            LDC => code.push(constants.int as u8),
+            LDC_W => code.extend(constants.int.to_be_bytes()),
+            LDC2_W => code.extend(constants.long.to_be_bytes()),
+            IFEQ..=JSR | IFNULL | IFNONNULL => code.extend(3i16.to_be_bytes()),
+            GOTO_W | JSR_W => code.extend(5i32.to_be_bytes()),
+            TABLESWITCH => {
+                code.resize(code.len().next_multiple_of(4), 0);
+                let length = (code.len() + 16 - pc) as i32;
+                // default, low, high and the offset of the only case
+                for operand in [length, 0, 0, length] {
+                    code.extend(operand.to_be_bytes());
+                }
+            }
+            LOOKUPSWITCH => {
+                code.resize(code.len().next_multiple_of(4), 0);
+                let length = (code.len() + 8 - pc) as i32;
+                // default and no pairs
+                for operand in [length, 0] {
+                    code.extend(operand.to_be_bytes());
+                }
+            }
+            GETSTATIC..=PUTFIELD => code.extend(constants.field.to_be_bytes()),
+            INVOKEVIRTUAL..=INVOKESTATIC | INVOKEDYNAMIC => {
+                code.extend(constants.method.to_be_bytes());
+                code.resize(pc + fixed_length(opcode).unwrap(), 0);
+            }
+            INVOKEINTERFACE => {
+                code.extend(constants.interface_method.to_be_bytes());
+                code.extend([1, 0]);
+            }
+            NEW | ANEWARRAY | CHECKCAST | INSTANCEOF => code.extend(constants.class.to_be_bytes()),
+            MULTIANEWARRAY => {
+                code.extend(constants.class.to_be_bytes());
+                code.push(1);
+            }
+            NEWARRAY => code.push(10), // T_INT
+            WIDE => code.extend([ILOAD, 0, 0]),
+            // Local 0, a constant 0 and the like
+            _ => code.resize(pc + fixed_length(opcode).unwrap(), 0),
+        }
+        code.push(RETURN);
+        code
+    }
+
+    /// A class whose static method run executes the instruction, see code
+    fn class_file(opcode: u8) -> Vec<u8> {
+        let mut class = ClassWriter::new(
+            ConstantPoolBuilder::new(),
+            CLASS_NAME,
+            Some("classes/Object"),
+            0x0021, // ACC_PUBLIC | ACC_SUPER
+        )
+        .unwrap();
+        let constant_pool = class.constant_pool();
+        let constants = Constants {
+            int: constant_pool.integer(1).unwrap().as_u16(),
+            long: constant_pool.long(1).unwrap().as_u16(),
+            field: constant_pool
+                .field_reference(CLASS_NAME, "value", "I")
+                .unwrap()
+                .as_u16(),
+            method: constant_pool
+                .method_reference(CLASS_NAME, "target", "()V")
+                .unwrap()
+                .as_u16(),
+            interface_method: constant_pool
+                .interface_method_reference(CLASS_NAME, "target", "()V")
+                .unwrap()
+                .as_u16(),
+            class: constant_pool.class(CLASS_NAME).unwrap().as_u16(),
+        };
+        class.add_field(FieldDefinition {
+            access_flags: ACC_STATIC,
+            name: "value".to_string(),
+            descriptor: "I".to_string(),
+            constant_value: None,
+            signature: None,
+        });
+        for (name, code) in [("run", code(opcode, &constants)), ("target", vec![RETURN])] {
+            class.add_method(MethodDefinition {
+                access_flags: ACC_STATIC,
+                name: name.to_string(),
+                descriptor: "()V".to_string(),
+                code: Some(CodeDefinition {
+                    max_stack: 16,
+                    max_locals: 4,
+                    code,
+                    exception_table: Vec::new(),
+                    line_numbers: Vec::new(),
+                    local_variables: Vec::new(),
+                }),
+                signature: None,
+            });
+        }
+        class.finish().unwrap()
+    }
+
+    fn define(opcode: u8) -> (Jvm, ClassIndex) {
+        let mut jvm = testing::jvm_with(VmOptions {
+            execution_mode: ExecutionMode::Interpreted,
+            ..VmOptions::default()
+        });
+        let class = jvm.define_class(CLASS_NAME, class_file(opcode)).unwrap();
+        (jvm, class)
+    }
+
+    #[test]
+    fn the_interpreter_dispatches_the_opcodes_it_implements() {
+        for (opcode, mnemonic) in OPCODES {
+            // The reserved opcodes are left to the verifier, breakpoint would stop in the console debugger
+            if !is_verifiable(*opcode) {
+                continue;
+            }
+            let (mut jvm, _) = define(*opcode);
+            let unsupported = ExecutionError::UnsupportedOpcode(*opcode).to_string();
+            let dispatched = match jvm.call_static(CLASS_NAME, "run", "()V", &[]) {
+                Ok(_) => true,
+                Err(exception) => exception.message.as_deref() != Some(unsupported.as_str()),
+            };
+            assert_eq!(dispatched, is_interpreted(*opcode), "{}", mnemonic);
+        }
+    }
+
+    #[test]
+    fn the_jit_compiles_the_opcodes_it_supports() {
+        for (opcode, mnemonic) in OPCODES {
+            let (jvm, class) = define(*opcode);
+            let (method, _) = jvm
+                .classes()
+                .resolve(class)
+                .find_own_static_method("run", "()V")
+                .unwrap();
+            let compiled = !matches!(
+                jit::compile_method(method, jvm.classes(), jvm.methods()),
+                Err(CompilationError::UnsupportedOpcode(unsupported)) if unsupported == *opcode
+            );
+            assert_eq!(compiled, is_compiled(*opcode), "{}", mnemonic);
+        }
+    }
+
+    #[test]
+    fn the_verifier_rejects_only_the_reserved_opcodes() {
+        for (opcode, mnemonic) in OPCODES {
+            let class_file = class_file(*opcode);
+            let (_, data, _) = class_parser::parse(&class_file).unwrap();
+            let accepted = !matches!(
+                Verifier::new(None).verify_class(CLASS_NAME, &class_file, &data),
+                Err(VerifyError::IllegalOpcode { opcode: illegal, .. }) if illegal == *opcode
+            );
+            assert_eq!(accepted, is_verifiable(*opcode), "{}", mnemonic);
+        }
+        let report = unsupported_opcodes_report();
+        for reserved in ["breakpoint", "impdep1", "impdep2"] {
+            let line = report.lines().find(|line| line.contains(reserved)).unwrap();
+            assert!(line.ends_with("no"), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    model::{
+        class_library::{ClassIndex, ClassLibrary},
+        method::{MethodIndex, MethodTable},
+    },
+    options::VmOptions,
+};
+
+// Experimental warm-up snapshots: a checkpoint stores the class files of the classes that have been loaded during the
+// startup of the VM, the values of their static fields after initialization and the methods that have been compiled.
+// A run that restores the checkpoint loads these classes without searching the classpath, skips their static
+// initializers and compiles the methods right away.
+// The heap is not part of a checkpoint, so classes whose static fields reference objects are initialized again.
+
+const MAGIC: &[u8; 8] = b"JVMCKPT\0";
+const VERSION: u32 = 1;
+
+pub struct Checkpoint {
+    configuration: String, // The options that influence which class files are loaded
+    classes: HashMap<String, ClassSnapshot>,
+}
+
+pub struct ClassSnapshot {
+    pub class_file: Vec<u8>,
+    pub statics: Option<Vec<u8>>, // None if the static initializer has to run again
+    pub compiled_methods: Vec<(String, String)>, // (name, descriptor)
+}
+
+impl Checkpoint {
+    /// Captures the current state of all loaded classes. The class library must have recorded their class files.
+    pub fn capture(
+        classes: &ClassLibrary,
+        methods: &MethodTable,
+        options: &VmOptions,
+    ) -> Result<Self, CheckpointError> {
+        let mut snapshots = HashMap::new();
+        for index in 0..classes.class_count() {
+            let class = classes.resolve(ClassIndex(index));
+            let name = class
+                .name()
+                .map_err(|err| CheckpointError::InvalidClass(err.to_string()))?;
+            let class_file = classes
+                .recorded_class_file(name)
+                .ok_or_else(|| CheckpointError::MissingClassFile(name.to_string()))?;
+            snapshots.insert(
+                name.to_string(),
+                ClassSnapshot {
+                    class_file,
+                    statics: class.statics_snapshot(),
+                    compiled_methods: Vec::new(),
+                },
+            );
+        }
+
+        for index in 0..methods.method_count() {
+            let method_index = MethodIndex::from(index);
+            let data = methods.get_data(method_index);
+            if data.is_native || !methods.is_compiled(method_index) {
+                continue;
+            }
+            let class_name = classes
+                .resolve(data.owning_class)
+                .name()
+                .map_err(|err| CheckpointError::InvalidClass(err.to_string()))?;
+            if let Some(snapshot) = snapshots.get_mut(class_name) {
+                snapshot
+                    .compiled_methods
+                    .push((data.name.clone(), data.descriptor.clone()));
+            }
+        }
+
+        Ok(Self {
+            configuration: configuration(options),
+            classes: snapshots,
+        })
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<(), CheckpointError> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        write_bytes(&mut out, self.configuration.as_bytes());
+        write_length(&mut out, self.classes.len());
+        for (name, snapshot) in &self.classes {
+            write_bytes(&mut out, name.as_bytes());
+            write_bytes(&mut out, &snapshot.class_file);
+            match &snapshot.statics {
+                Some(statics) => {
+                    out.push(1);
+                    write_bytes(&mut out, statics);
+                }
+                None => out.push(0),
+            }
+            write_length(&mut out, snapshot.compiled_methods.len());
+            for (name, descriptor) in &snapshot.compiled_methods {
+                write_bytes(&mut out, name.as_bytes());
+                write_bytes(&mut out, descriptor.as_bytes());
+            }
+        }
+        fs::File::create(path)?.write_all(&out)?;
+        Ok(())
+    }
+
+    /// Fails if the file isn't a checkpoint or if it has been created with a different classpath
+    pub fn read_from(path: &Path, options: &VmOptions) -> Result<Self, CheckpointError> {
+        let mut input = io::BufReader::new(fs::File::open(path)?);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(&mut input)? != VERSION {
+            return Err(CheckpointError::NotACheckpoint);
+        }
+        let configuration = read_string(&mut input)?;
+        if configuration != self::configuration(options) {
+            return Err(CheckpointError::ConfigurationMismatch(configuration));
+        }
+
+        let class_count = read_u32(&mut input)?;
+        let mut classes = HashMap::new();
+        for _ in 0..class_count {
+            let name = read_string(&mut input)?;
+            let class_file = read_bytes(&mut input)?;
+            let mut has_statics = [0];
+            input.read_exact(&mut has_statics)?;
+            let statics = match has_statics[0] {
+                0 => None,
+                _ => Some(read_bytes(&mut input)?),
+            };
+            let method_count = read_u32(&mut input)?;
+            let mut compiled_methods = Vec::new();
+            for _ in 0..method_count {
+                compiled_methods.push((read_string(&mut input)?, read_string(&mut input)?));
+            }
+            classes.insert(
+                name,
+                ClassSnapshot {
+                    class_file,
+                    statics,
+                    compiled_methods,
+                },
+            );
+        }
+
+        Ok(Self {
+            configuration,
+            classes,
+        })
+    }
+
+    pub fn class_count(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn into_classes(self) -> HashMap<String, ClassSnapshot> {
+        self.classes
+    }
+}
+
+/// A checkpoint can only be restored with the same class files, i.e. with the same classpath
+fn configuration(options: &VmOptions) -> String {
+    format!(
+        "classpath={:?} boot_classpath_append={:?} module_patches={:?} boot_jdk={:?}",
+        options.classpath, options.boot_classpath_append, options.module_patches, options.boot_jdk
+    )
+}
+
+fn write_length(out: &mut Vec<u8>, length: usize) {
+    out.extend_from_slice(&(length as u32).to_le_bytes());
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_length(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_bytes<R: Read>(input: &mut R) -> io::Result<Vec<u8>> {
+    let length = read_u32(input)?;
+    let mut bytes = vec![0; length as usize];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string<R: Read>(input: &mut R) -> Result<String, CheckpointError> {
+    String::from_utf8(read_bytes(input)?).map_err(|_| CheckpointError::NotACheckpoint)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CheckpointError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("the file is not a checkpoint of this VM version")]
+    NotACheckpoint,
+
+    #[error("the checkpoint has been created with different options ({0})")]
+    ConfigurationMismatch(String),
+
+    #[error("the class file of {0} has not been recorded")]
+    MissingClassFile(String),
+
+    #[error("invalid class: {0}")]
+    InvalidClass(String),
+}
@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    sync::Mutex,
+};
+
+// Reads the classes of the jimage file (lib/modules) of an installed JDK, the format that jlink links the modules of
+// a runtime image into. It isn't specified, this follows jdk.internal.jimage.BasicImageReader of OpenJDK:
+// the file starts with an index of the resources (a header, a perfect hash table, the attributes of the locations and
+// their strings), followed by the contents of the resources. The index is read once, when the image is opened, and
+// the classes are indexed by their file name without the module, because the packages of the modules of a runtime
+// image are disjoint.
+
+const MAGIC: u32 = 0xCAFEDADA;
+const MAJOR_VERSION: u32 = 1;
+const HEADER_LENGTH: usize = 7 * 4;
+
+// The kinds of the attributes of a location
+const ATTRIBUTE_END: u8 = 0;
+const ATTRIBUTE_MODULE: u8 = 1;
+const ATTRIBUTE_PARENT: u8 = 2;
+const ATTRIBUTE_BASE: u8 = 3;
+const ATTRIBUTE_EXTENSION: u8 = 4;
+const ATTRIBUTE_OFFSET: u8 = 5;
+const ATTRIBUTE_COMPRESSED: u8 = 6;
+const ATTRIBUTE_UNCOMPRESSED: u8 = 7;
+
+pub(super) struct JImage {
+    file: Mutex<File>, // Reads seek, so they take turns like the reads of the entries of a jar
+    index_length: u64, // The contents of the resources start right after the index
+    classes: HashMap<String, Resource>, // By the file name of the class, e.g. java/lang/Object.class
+}
+
+#[derive(Clone, Copy)]
+struct Resource {
+    offset: u64,          // Relative to the end of the index
+    compressed_size: u64, // Zero if the resource isn't compressed
+    size: u64,
+}
+
+impl JImage {
+    pub(super) fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = [0; HEADER_LENGTH];
+        file.read_exact(&mut header)?;
+
+        // The image is written in the byte order of the platform that it has been linked for
+        let little_endian = u32::from_le_bytes(header[..4].try_into().unwrap()) == MAGIC;
+        if !little_endian && u32::from_be_bytes(header[..4].try_into().unwrap()) != MAGIC {
+            return Err(invalid_image("missing magic number"));
+        }
+        let u32_at = |bytes: &[u8], position: usize| -> io::Result<u32> {
+            let bytes = bytes
+                .get(position..position + 4)
+                .ok_or_else(|| invalid_image("truncated index"))?
+                .try_into()
+                .unwrap();
+            Ok(if little_endian {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            })
+        };
+        let version = u32_at(&header, 4)?;
+        if version >> 16 != MAJOR_VERSION {
+            return Err(invalid_image(&format!(
+                "unsupported version {}.{}",
+                version >> 16,
+                version & 0xFFFF
+            )));
+        }
+        let table_length = u32_at(&header, 16)? as usize;
+        let locations_size = u32_at(&header, 20)? as usize;
+        let strings_size = u32_at(&header, 24)? as usize;
+
+        // The redirect table of the hash isn't needed, because all locations are indexed
+        let mut index = vec![0; 8 * table_length + locations_size + strings_size];
+        file.read_exact(&mut index)?;
+        let offsets_start = 4 * table_length;
+        let locations_start = 8 * table_length;
+        let locations = &index[locations_start..locations_start + locations_size];
+        let strings = &index[locations_start + locations_size..];
+
+        let mut classes = HashMap::new();
+        for entry in 0..table_length {
+            let location = u32_at(&index, offsets_start + 4 * entry)? as usize;
+            let attributes = read_attributes(
+                locations
+                    .get(location..)
+                    .ok_or_else(|| invalid_image("location out of bounds"))?,
+            )?;
+            let string = |kind: u8| read_string(strings, attributes[kind as usize] as usize);
+            // The directories of the image (/modules and /packages) aren't classes
+            let module = string(ATTRIBUTE_MODULE)?;
+            if string(ATTRIBUTE_EXTENSION)? != "class"
+                || module == "modules"
+                || module == "packages"
+            {
+                continue;
+            }
+            let parent = string(ATTRIBUTE_PARENT)?;
+            let base = string(ATTRIBUTE_BASE)?;
+            let name = if parent.is_empty() {
+                format!("{}.class", base)
+            } else {
+                format!("{}/{}.class", parent, base)
+            };
+            classes.insert(
+                name,
+                Resource {
+                    offset: attributes[ATTRIBUTE_OFFSET as usize],
+                    compressed_size: attributes[ATTRIBUTE_COMPRESSED as usize],
+                    size: attributes[ATTRIBUTE_UNCOMPRESSED as usize],
+                },
+            );
+        }
+        log::debug!(
+            "Indexed {} classes of the image {}",
+            classes.len(),
+            path.display()
+        );
+
+        Ok(Self {
+            file: Mutex::new(file),
+            index_length: (HEADER_LENGTH + index.len()) as u64,
+            classes,
+        })
+    }
+
+    /// Returns None if the image doesn't contain the class file
+    pub(super) fn read(&self, file_name: &str) -> io::Result<Option<Vec<u8>>> {
+        let Some(resource) = self.classes.get(file_name) else {
+            return Ok(None);
+        };
+        // Images that have been linked with --compress need the decompressors of jlink, the JDK doesn't use them
+        if resource.compressed_size != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} is compressed in the image", file_name),
+            ));
+        }
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(self.index_length + resource.offset))?;
+        let mut bytes = vec![0; resource.size as usize];
+        file.read_exact(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+}
+
+/// The values of the attributes of a location by their kind. Every attribute is a byte with the kind (the upper five
+/// bits) and the length of the value minus one (the lower three bits), followed by the big-endian value.
+fn read_attributes(mut bytes: &[u8]) -> io::Result<[u64; 8]> {
+    let mut attributes = [0; 8];
+    loop {
+        let (&header, rest) = bytes
+            .split_first()
+            .ok_or_else(|| invalid_image("unterminated location"))?;
+        let kind = header >> 3;
+        if kind == ATTRIBUTE_END {
+            return Ok(attributes);
+        }
+        let length = (header & 0x7) as usize + 1;
+        let value = rest
+            .get(..length)
+            .ok_or_else(|| invalid_image("unterminated location"))?;
+        *attributes
+            .get_mut(kind as usize)
+            .ok_or_else(|| invalid_image("unknown location attribute"))? = value
+            .iter()
+            .fold(0, |value, byte| value << 8 | *byte as u64);
+        bytes = &rest[length..];
+    }
+}
+
+/// The strings are null-terminated and encoded in UTF-8
+fn read_string(strings: &[u8], offset: usize) -> io::Result<&str> {
+    let bytes = strings
+        .get(offset..)
+        .ok_or_else(|| invalid_image("string out of bounds"))?;
+    let end = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or_else(|| invalid_image("unterminated string"))?;
+    std::str::from_utf8(&bytes[..end]).map_err(|_| invalid_image("invalid string"))
+}
+
+fn invalid_image(reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid jimage file: {}", reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, path::PathBuf};
+
+    use super::{
+        JImage, ATTRIBUTE_BASE, ATTRIBUTE_COMPRESSED, ATTRIBUTE_EXTENSION, ATTRIBUTE_MODULE,
+        ATTRIBUTE_OFFSET, ATTRIBUTE_PARENT, ATTRIBUTE_UNCOMPRESSED, MAGIC, MAJOR_VERSION,
+    };
+
+    struct Location {
+        module: &'static str,
+        parent: &'static str,
+        base: &'static str,
+        extension: &'static str,
+        content: &'static [u8],
+        compressed_size: u32,
+    }
+
+    fn class(
+        module: &'static str,
+        parent: &'static str,
+        base: &'static str,
+        content: &'static [u8],
+    ) -> Location {
+        Location {
+            module,
+            parent,
+            base,
+            extension: "class",
+            content,
+            compressed_size: 0,
+        }
+    }
+
+    /// Links the locations into an image with the layout of jdk.internal.jimage.BasicImageReader. Every attribute
+    /// value is written with four bytes, and the hash table lists the locations in order.
+    fn link(locations: &[Location], big_endian: bool, version: u32) -> Vec<u8> {
+        let u32_bytes = |value: u32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let mut strings = vec![0]; // The empty string
+        let mut string = |value: &str| {
+            let offset = strings.len() as u32;
+            strings.extend_from_slice(value.as_bytes());
+            strings.push(0);
+            offset
+        };
+        let mut attributes = Vec::new();
+        let mut offsets = Vec::new();
+        let mut contents = Vec::new();
+        for location in locations {
+            offsets.push(attributes.len() as u32);
+            for (kind, value) in [
+                (ATTRIBUTE_MODULE, string(location.module)),
+                (ATTRIBUTE_PARENT, string(location.parent)),
+                (ATTRIBUTE_BASE, string(location.base)),
+                (ATTRIBUTE_EXTENSION, string(location.extension)),
+                (ATTRIBUTE_OFFSET, contents.len() as u32),
+                (ATTRIBUTE_COMPRESSED, location.compressed_size),
+                (ATTRIBUTE_UNCOMPRESSED, location.content.len() as u32),
+            ] {
+                attributes.push(kind << 3 | 3);
+                attributes.extend_from_slice(&value.to_be_bytes());
+            }
+            attributes.push(0);
+            contents.extend_from_slice(location.content);
+        }
+
+        let mut image = Vec::new();
+        for value in [
+            MAGIC,
+            version,
+            0, // Flags
+            locations.len() as u32,
+            locations.len() as u32,
+            attributes.len() as u32,
+            strings.len() as u32,
+        ] {
+            image.extend_from_slice(&u32_bytes(value));
+        }
+        for _ in locations {
+            image.extend_from_slice(&u32_bytes(0)); // Redirects
+        }
+        for offset in offsets {
+            image.extend_from_slice(&u32_bytes(offset));
+        }
+        image.extend_from_slice(&attributes);
+        image.extend_from_slice(&strings);
+        image.extend_from_slice(&contents);
+        image
+    }
+
+    fn open(name: &str, image: &[u8]) -> io::Result<JImage> {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("jvm-jimage-{}-{}", std::process::id(), name));
+        std::fs::write(&path, image).unwrap();
+        let opened = JImage::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        opened
+    }
+
+    fn locations() -> Vec<Location> {
+        vec![
+            class("java.base", "java/lang", "Object", b"object"),
+            class("app", "", "Main", b"main class"),
+            // The directories of the image and other resources aren't classes
+            Location {
+                module: "packages",
+                parent: "",
+                base: "java.lang",
+                extension: "",
+                content: b"",
+                compressed_size: 0,
+            },
+            Location {
+                module: "java.base",
+                parent: "META-INF",
+                base: "MANIFEST",
+                extension: "MF",
+                content: b"manifest",
+                compressed_size: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn reads_the_classes_by_their_file_name() {
+        for big_endian in [false, true] {
+            let image = open(
+                "classes",
+                &link(&locations(), big_endian, MAJOR_VERSION << 16),
+            )
+            .unwrap();
+            assert_eq!(image.classes.len(), 2);
+            assert_eq!(
+                image.read("java/lang/Object.class").unwrap().unwrap(),
+                b"object"
+            );
+            assert_eq!(image.read("Main.class").unwrap().unwrap(), b"main class");
+            assert_eq!(image.read("java/lang/String.class").unwrap(), None);
+            assert_eq!(image.read("META-INF/MANIFEST.class").unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn compressed_classes_are_unsupported() {
+        let mut locations = locations();
+        locations[0].compressed_size = 3;
+        let image = open("compressed", &link(&locations, false, MAJOR_VERSION << 16)).unwrap();
+        let err = image.read("java/lang/Object.class").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(image.read("Main.class").unwrap().unwrap(), b"main class");
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_images_of_a_supported_version() {
+        let mut image = link(&locations(), false, MAJOR_VERSION << 16);
+        image[0] ^= 0xFF;
+        let err = open("magic", &image).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let image = link(&locations(), false, (MAJOR_VERSION + 1) << 16);
+        let err = open("version", &image).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_truncated_indexes() {
+        let image = link(&locations(), false, MAJOR_VERSION << 16);
+        assert!(open("truncated", &image[..40]).is_err());
+    }
+}
@@ -0,0 +1,576 @@
+mod jimage;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use zip::{result::ZipError, ZipArchive};
+
+use crate::model::package::ClassLoaderId;
+
+use self::jimage::JImage;
+
+/// The bootstrap classes that are compiled into the binary, so that the VM doesn't need any files to start.
+/// Besides Object, they implement just enough of java.lang and java.io (strings, string concatenation of Java 8,
+/// System.out and the throwables that the VM throws itself) to run simple programs without the JDK class library. They are searched last, so a class library on
+/// the classpath, the appended boot classpath or the boot JDK replaces them, and -XX:-EmbeddedBootClasses leaves them
+/// out.
+const EMBEDDED_CLASSES: &[(&str, &[u8])] = &[
+    (
+        "classes/Object.class",
+        include_bytes!("../../classes/Object.class"),
+    ),
+    (
+        "java/lang/String.class",
+        include_bytes!("../../classes/java/lang/String.class"),
+    ),
+    (
+        "java/lang/StringBuilder.class",
+        include_bytes!("../../classes/java/lang/StringBuilder.class"),
+    ),
+    (
+        "java/lang/Integer.class",
+        include_bytes!("../../classes/java/lang/Integer.class"),
+    ),
+    (
+        "java/lang/System.class",
+        include_bytes!("../../classes/java/lang/System.class"),
+    ),
+    (
+        "java/io/PrintStream.class",
+        include_bytes!("../../classes/java/io/PrintStream.class"),
+    ),
+    (
+        "java/lang/Throwable.class",
+        include_bytes!("../../classes/java/lang/Throwable.class"),
+    ),
+    (
+        "java/lang/Exception.class",
+        include_bytes!("../../classes/java/lang/Exception.class"),
+    ),
+    (
+        "java/lang/RuntimeException.class",
+        include_bytes!("../../classes/java/lang/RuntimeException.class"),
+    ),
+    (
+        "java/lang/Error.class",
+        include_bytes!("../../classes/java/lang/Error.class"),
+    ),
+    (
+        "java/lang/NullPointerException.class",
+        include_bytes!("../../classes/java/lang/NullPointerException.class"),
+    ),
+    (
+        "java/lang/ArithmeticException.class",
+        include_bytes!("../../classes/java/lang/ArithmeticException.class"),
+    ),
+    (
+        "java/lang/IndexOutOfBoundsException.class",
+        include_bytes!("../../classes/java/lang/IndexOutOfBoundsException.class"),
+    ),
+    (
+        "java/lang/ArrayIndexOutOfBoundsException.class",
+        include_bytes!("../../classes/java/lang/ArrayIndexOutOfBoundsException.class"),
+    ),
+    (
+        "java/lang/ArrayStoreException.class",
+        include_bytes!("../../classes/java/lang/ArrayStoreException.class"),
+    ),
+    (
+        "java/lang/ClassCastException.class",
+        include_bytes!("../../classes/java/lang/ClassCastException.class"),
+    ),
+    (
+        "java/lang/NegativeArraySizeException.class",
+        include_bytes!("../../classes/java/lang/NegativeArraySizeException.class"),
+    ),
+    (
+        "java/lang/IllegalArgumentException.class",
+        include_bytes!("../../classes/java/lang/IllegalArgumentException.class"),
+    ),
+    (
+        "java/lang/VirtualMachineError.class",
+        include_bytes!("../../classes/java/lang/VirtualMachineError.class"),
+    ),
+    (
+        "java/lang/StackOverflowError.class",
+        include_bytes!("../../classes/java/lang/StackOverflowError.class"),
+    ),
+    (
+        "java/lang/OutOfMemoryError.class",
+        include_bytes!("../../classes/java/lang/OutOfMemoryError.class"),
+    ),
+    (
+        "java/lang/InternalError.class",
+        include_bytes!("../../classes/java/lang/InternalError.class"),
+    ),
+    (
+        "java/lang/LinkageError.class",
+        include_bytes!("../../classes/java/lang/LinkageError.class"),
+    ),
+    (
+        "java/lang/NoClassDefFoundError.class",
+        include_bytes!("../../classes/java/lang/NoClassDefFoundError.class"),
+    ),
+    (
+        "java/lang/ExceptionInInitializerError.class",
+        include_bytes!("../../classes/java/lang/ExceptionInInitializerError.class"),
+    ),
+    (
+        "java/lang/ClassCircularityError.class",
+        include_bytes!("../../classes/java/lang/ClassCircularityError.class"),
+    ),
+    (
+        "java/lang/UnsatisfiedLinkError.class",
+        include_bytes!("../../classes/java/lang/UnsatisfiedLinkError.class"),
+    ),
+    (
+        "java/lang/VerifyError.class",
+        include_bytes!("../../classes/java/lang/VerifyError.class"),
+    ),
+    (
+        "java/lang/IncompatibleClassChangeError.class",
+        include_bytes!("../../classes/java/lang/IncompatibleClassChangeError.class"),
+    ),
+    (
+        "java/lang/NoSuchMethodError.class",
+        include_bytes!("../../classes/java/lang/NoSuchMethodError.class"),
+    ),
+    (
+        "java/lang/NoSuchFieldError.class",
+        include_bytes!("../../classes/java/lang/NoSuchFieldError.class"),
+    ),
+    (
+        "java/lang/AbstractMethodError.class",
+        include_bytes!("../../classes/java/lang/AbstractMethodError.class"),
+    ),
+    (
+        "java/lang/IllegalAccessError.class",
+        include_bytes!("../../classes/java/lang/IllegalAccessError.class"),
+    ),
+];
+
+pub struct BootstrapClassLoader {
+    patches: Vec<(String, Vec<ClasspathEntry>)>, // (module, entries)
+    classpath: Vec<ClasspathEntry>,
+    boot_classpath_append: Vec<ClasspathEntry>,
+    skipped_entries: Vec<PathBuf>, // Entries that couldn't be opened, only reported when a class isn't found
+}
+
+/// Classes that replace or augment the classes of a module (--patch-module)
+#[derive(Debug, Clone)]
+pub struct ModulePatch {
+    pub module: String,
+    pub paths: Vec<PathBuf>,
+}
+
+impl BootstrapClassLoader {
+    /// Classes are searched in the patches (in the order of the patches), then on the classpath, then on the
+    /// appended boot classpath, then in the modules of the boot JDK (if given) and finally in the embedded classes
+    /// (if enabled). Entries that can't be opened are skipped like by the java launcher.
+    pub fn new(
+        classpath: Vec<PathBuf>,
+        boot_classpath_append: Vec<PathBuf>,
+        patches: Vec<ModulePatch>,
+        boot_jdk: Option<PathBuf>,
+        embedded_classes: bool,
+    ) -> Self {
+        let mut skipped_entries = Vec::new();
+        let mut boot_classpath_append = open_entries(boot_classpath_append, &mut skipped_entries);
+        if let Some(boot_jdk) = boot_jdk {
+            boot_classpath_append.extend(open_boot_jdk(boot_jdk, &mut skipped_entries));
+        }
+        if embedded_classes {
+            boot_classpath_append.push(ClasspathEntry::Embedded);
+        }
+        Self {
+            patches: patches
+                .into_iter()
+                .map(|patch| {
+                    (
+                        patch.module,
+                        open_entries(patch.paths, &mut skipped_entries),
+                    )
+                })
+                .collect(),
+            classpath: open_entries(classpath, &mut skipped_entries),
+            boot_classpath_append,
+            skipped_entries,
+        }
+    }
+
+    pub fn id(&self) -> ClassLoaderId {
+        ClassLoaderId::Bootstrap
+    }
+
+    /// The Main-Class of the manifest of the given jar on the classpath, in internal form
+    pub fn main_class(&self, jar: &Path) -> Option<String> {
+        self.classpath.iter().find_map(|entry| match entry {
+            ClasspathEntry::Jar { path, manifest, .. } if path == jar => manifest
+                .get("Main-Class")
+                .map(|class| class.replace('.', "/")),
+            _ => None,
+        })
+    }
+
+    /// Reads the class file of the class with the given internal name (e.g. java/lang/Object) from the first
+    /// entry that contains it. Only the patches of the modules for which is_patched returns true are searched.
+    pub fn load_class<F: Fn(&str) -> bool>(
+        &self,
+        name: String,
+        is_patched: F,
+    ) -> io::Result<Vec<u8>> {
+        log::debug!("Loading class {}", name);
+        let patches = self
+            .patches
+            .iter()
+            .filter(|(module, _)| is_patched(module))
+            .flat_map(|(_, entries)| entries);
+        let file_name = format!("{}.class", name);
+        let mut searched = Vec::new();
+        for entry in patches
+            .chain(&self.classpath)
+            .chain(&self.boot_classpath_append)
+        {
+            let bytes = entry.read(&file_name).map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!(
+                        "can't read {} from {}: {}",
+                        file_name,
+                        entry.path().display(),
+                        err
+                    ),
+                )
+            })?;
+            if let Some(bytes) = bytes {
+                log::debug!("Found {} in {}", name, entry.path().display());
+                return Ok(bytes);
+            }
+            searched.push(entry.path().display().to_string());
+        }
+        let mut message = if searched.is_empty() {
+            "the classpath is empty".to_string()
+        } else {
+            format!("not found in {}", searched.join(", "))
+        };
+        if !self.skipped_entries.is_empty() {
+            message.push_str(&format!(
+                " (skipped the entries that couldn't be opened: {})",
+                self.skipped_entries
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, message))
+    }
+
+    /// Finds the classpath entries that are exploded modules, i.e. directories with a module-info.class.
+    /// Modular jars are treated like plain jars, i.e. their classes are in the unnamed module.
+    pub fn find_modules(&self) -> io::Result<Vec<ExplodedModule>> {
+        let mut modules = Vec::new();
+        for entry in &self.classpath {
+            let directory = match entry {
+                ClasspathEntry::Directory(directory) => directory,
+                _ => continue,
+            };
+            let path = directory.join("module-info.class");
+            if !path.is_file() {
+                continue;
+            }
+            log::debug!("Found the module descriptor {}", path.display());
+            let mut packages = HashSet::new();
+            collect_packages(directory, "", &mut packages)?;
+            modules.push(ExplodedModule {
+                module_info: fs::read(path)?,
+                packages,
+            });
+        }
+        Ok(modules)
+    }
+}
+
+/// A module whose classes are stored in a directory
+pub struct ExplodedModule {
+    pub module_info: Vec<u8>,      // The content of module-info.class
+    pub packages: HashSet<String>, // All packages that contain classes, in internal form
+}
+
+enum ClasspathEntry {
+    Directory(PathBuf),
+    Jar {
+        path: PathBuf,
+        archive: Mutex<ZipArchive<File>>,
+        manifest: HashMap<String, String>, // The main section of META-INF/MANIFEST.MF
+    },
+    // The modules of the boot JDK, either linked into a jimage (lib/modules) or packaged as jmod files, which are
+    // zip files with a header and the classes in the directory classes/
+    Image {
+        path: PathBuf,
+        image: JImage,
+    },
+    JMod {
+        path: PathBuf,
+        archive: Mutex<ZipArchive<File>>,
+    },
+    Embedded, // The classes that are compiled into the binary (EMBEDDED_CLASSES)
+}
+
+impl ClasspathEntry {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        if path.is_dir() {
+            return Ok(Self::Directory(path));
+        }
+
+        let mut archive = ZipArchive::new(File::open(&path)?).map_err(zip_error)?;
+        let manifest = match archive.by_name("META-INF/MANIFEST.MF") {
+            Ok(mut file) => {
+                let mut content = String::new();
+                file.read_to_string(&mut content)?;
+                parse_manifest(&content)
+            }
+            Err(ZipError::FileNotFound) => HashMap::new(),
+            Err(err) => return Err(zip_error(err)),
+        };
+        // Signature files (META-INF/*.SF, *.RSA, ...) and the per-entry digests in the manifest are ignored,
+        // signed jars are loaded like unsigned ones
+        if archive.file_names().any(is_signature_file) {
+            log::debug!("Not verifying the signature of {}", path.display());
+        }
+        Ok(Self::Jar {
+            path,
+            archive: Mutex::new(archive),
+            manifest,
+        })
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Self::Directory(path)
+            | Self::Jar { path, .. }
+            | Self::Image { path, .. }
+            | Self::JMod { path, .. } => path,
+            Self::Embedded => Path::new("<embedded>"),
+        }
+    }
+
+    /// Returns None if the entry doesn't contain the file
+    fn read(&self, file_name: &str) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            Self::Directory(directory) => match File::open(directory.join(file_name)) {
+                Ok(mut file) => {
+                    let mut bytes = Vec::new();
+                    file.read_to_end(&mut bytes)?;
+                    Ok(Some(bytes))
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            },
+            Self::Jar { archive, .. } => read_zip_entry(archive, file_name),
+            Self::JMod { archive, .. } => {
+                read_zip_entry(archive, &format!("classes/{}", file_name))
+            }
+            Self::Image { image, .. } => image.read(file_name),
+            Self::Embedded => Ok(EMBEDDED_CLASSES
+                .iter()
+                .find(|(name, _)| *name == file_name)
+                .map(|(_, bytes)| bytes.to_vec())),
+        }
+    }
+
+    /// The entries that are referenced by the Class-Path attribute of a jar's manifest, relative to the jar
+    fn class_path_references(&self) -> Vec<PathBuf> {
+        match self {
+            Self::Jar { path, manifest, .. } => {
+                let base = path.parent().unwrap_or(Path::new(""));
+                manifest
+                    .get("Class-Path")
+                    .map(|class_path| {
+                        class_path
+                            .split_ascii_whitespace()
+                            .map(|url| base.join(url.strip_prefix("file:").unwrap_or(url)))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Opens the entries, followed by the entries they reference through the Class-Path attribute of their manifests.
+/// Every entry is only opened once, the entries that can't be opened are added to skipped.
+fn open_entries(paths: Vec<PathBuf>, skipped: &mut Vec<PathBuf>) -> Vec<ClasspathEntry> {
+    let mut entries = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = paths;
+    pending.reverse();
+    while let Some(path) = pending.pop() {
+        if !visited.insert(path.clone()) {
+            continue;
+        }
+        match ClasspathEntry::open(path.clone()) {
+            Ok(entry) => {
+                // The referenced entries are searched right after the jar that references them
+                pending.extend(entry.class_path_references().into_iter().rev());
+                entries.push(entry);
+            }
+            Err(err) => {
+                log::warn!("Skipping the classpath entry {}: {}", path.display(), err);
+                skipped.push(path);
+            }
+        }
+    }
+    entries
+}
+
+/// Opens the modules of an installed JDK (--boot-jdk), preferring the runtime image over the jmod files of a JDK
+/// that has them
+fn open_boot_jdk(jdk: PathBuf, skipped: &mut Vec<PathBuf>) -> Vec<ClasspathEntry> {
+    let image = jdk.join("lib").join("modules");
+    if image.is_file() {
+        return match JImage::open(&image) {
+            Ok(opened) => vec![ClasspathEntry::Image {
+                path: image,
+                image: opened,
+            }],
+            Err(err) => {
+                log::warn!("Skipping the boot JDK {}: {}", image.display(), err);
+                skipped.push(image);
+                Vec::new()
+            }
+        };
+    }
+
+    let jmods = jdk.join("jmods");
+    let mut paths = match fs::read_dir(&jmods) {
+        Ok(files) => files
+            .filter_map(|file| Some(file.ok()?.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "jmod")
+            })
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            log::warn!(
+                "Skipping the boot JDK {}, which has neither lib/modules nor jmods: {}",
+                jdk.display(),
+                err
+            );
+            skipped.push(jdk);
+            return Vec::new();
+        }
+    };
+    paths.sort();
+    let mut entries = Vec::new();
+    for path in paths {
+        // The zip reader finds the central directory from the end, so the header of the jmod doesn't matter
+        match File::open(&path).and_then(|file| ZipArchive::new(file).map_err(zip_error)) {
+            Ok(archive) => entries.push(ClasspathEntry::JMod {
+                path,
+                archive: Mutex::new(archive),
+            }),
+            Err(err) => {
+                log::warn!("Skipping the module {}: {}", path.display(), err);
+                skipped.push(path);
+            }
+        }
+    }
+    entries
+}
+
+/// Returns None if the archive doesn't contain the file
+fn read_zip_entry(
+    archive: &Mutex<ZipArchive<File>>,
+    file_name: &str,
+) -> io::Result<Option<Vec<u8>>> {
+    match archive.lock().unwrap().by_name(file_name) {
+        Ok(mut file) => {
+            let mut bytes = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut bytes)?;
+            Ok(Some(bytes))
+        }
+        Err(ZipError::FileNotFound) => Ok(None),
+        Err(err) => Err(zip_error(err)),
+    }
+}
+
+/// Parses the main section of a manifest (see the JAR file specification). Names are case-insensitive, so they are
+/// normalized to the usual capitalization of the attributes that are evaluated.
+fn parse_manifest(content: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut current: Option<(String, String)> = None;
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            // The main section ends at the first empty line, the following sections describe single entries
+            break;
+        }
+        if let Some(continuation) = line.strip_prefix(' ') {
+            if let Some((_, value)) = &mut current {
+                value.push_str(continuation);
+            }
+            continue;
+        }
+        if let Some((name, value)) = current.take() {
+            attributes.insert(name, value);
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = match name.to_ascii_lowercase().as_str() {
+                "main-class" => "Main-Class".to_string(),
+                "class-path" => "Class-Path".to_string(),
+                _ => name.to_string(),
+            };
+            current = Some((name, value.trim_start().to_string()));
+        }
+    }
+    if let Some((name, value)) = current {
+        attributes.insert(name, value);
+    }
+    attributes
+}
+
+fn is_signature_file(name: &str) -> bool {
+    match name.strip_prefix("META-INF/") {
+        Some(file) => [".SF", ".RSA", ".DSA", ".EC"]
+            .iter()
+            .any(|extension| file.to_ascii_uppercase().ends_with(extension)),
+        None => false,
+    }
+}
+
+fn zip_error(err: ZipError) -> io::Error {
+    match err {
+        ZipError::Io(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+/// Adds the package (and its subpackages) if it contains at least one class file
+fn collect_packages(
+    directory: &Path,
+    package: &str,
+    packages: &mut HashSet<String>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            let subpackage = if package.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", package, name)
+            };
+            collect_packages(&path, &subpackage, packages)?;
+        } else if !package.is_empty() && name.ends_with(".class") {
+            packages.insert(package.to_string());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,459 @@
+use crate::model::{
+    annotation::{
+        Annotation, Annotations, ElementValue, LocalVariableRange, TypeAnnotation,
+        TypeAnnotationTarget, TypePathStep,
+    },
+    constant_pool::{ConstantPool, ConstantPoolEntry, ConstantPoolIndex},
+};
+
+use super::{attribute, iterator::ClassFileIterator, ParsingError};
+
+/// Parses the attribute into the annotations if it is one of the annotation attributes, returns whether it has been
+/// parsed
+pub(super) fn parse_attribute(
+    name: &str,
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+    annotations: &mut Annotations,
+) -> Result<bool, ParsingError> {
+    match name {
+        attribute::RUNTIME_VISIBLE_ANNOTATIONS | attribute::RUNTIME_INVISIBLE_ANNOTATIONS => {
+            let visible = name == attribute::RUNTIME_VISIBLE_ANNOTATIONS;
+            let count = iter.u16()?;
+            for _ in 0..count {
+                annotations
+                    .declaration
+                    .push(parse_annotation(iter, constant_pool, visible)?);
+            }
+        }
+        attribute::RUNTIME_VISIBLE_PARAMETER_ANNOTATIONS
+        | attribute::RUNTIME_INVISIBLE_PARAMETER_ANNOTATIONS => {
+            // The visible and the invisible annotations of a parameter are stored in separate attributes
+            let visible = name == attribute::RUNTIME_VISIBLE_PARAMETER_ANNOTATIONS;
+            let parameter_count = iter.byte()? as usize;
+            if annotations.parameters.len() < parameter_count {
+                annotations.parameters.resize(parameter_count, Vec::new());
+            }
+            for parameter in 0..parameter_count {
+                let count = iter.u16()?;
+                for _ in 0..count {
+                    annotations.parameters[parameter].push(parse_annotation(
+                        iter,
+                        constant_pool,
+                        visible,
+                    )?);
+                }
+            }
+        }
+        attribute::RUNTIME_VISIBLE_TYPE_ANNOTATIONS
+        | attribute::RUNTIME_INVISIBLE_TYPE_ANNOTATIONS => {
+            let visible = name == attribute::RUNTIME_VISIBLE_TYPE_ANNOTATIONS;
+            let count = iter.u16()?;
+            for _ in 0..count {
+                annotations
+                    .types
+                    .push(parse_type_annotation(iter, constant_pool, visible)?);
+            }
+        }
+        attribute::ANNOTATION_DEFAULT => {
+            annotations.default = Some(parse_element_value(iter, constant_pool, true)?);
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Parses an annotation structure (JVMS §4.7.16)
+fn parse_annotation(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+    visible: bool,
+) -> Result<Annotation, ParsingError> {
+    let type_descriptor = constant_pool.get_utf8(iter.u16()?.into())?.to_string();
+    let count = iter.u16()?;
+    let mut elements = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name = constant_pool.get_utf8(iter.u16()?.into())?.to_string();
+        elements.push((name, parse_element_value(iter, constant_pool, visible)?));
+    }
+    Ok(Annotation {
+        type_descriptor,
+        elements,
+        visible,
+    })
+}
+
+/// Parses an element_value structure (JVMS §4.7.16.1), nested annotations have the visibility of the outer one
+fn parse_element_value(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+    visible: bool,
+) -> Result<ElementValue, ParsingError> {
+    let tag = iter.byte()?;
+    let value = match tag {
+        b'B' => ElementValue::Byte(int_constant(iter, constant_pool)? as i8),
+        b'C' => ElementValue::Char(int_constant(iter, constant_pool)? as u16),
+        b'I' => ElementValue::Int(int_constant(iter, constant_pool)?),
+        b'S' => ElementValue::Short(int_constant(iter, constant_pool)? as i16),
+        b'Z' => ElementValue::Boolean(int_constant(iter, constant_pool)? != 0),
+        b'D' | b'F' | b'J' => {
+            let index: ConstantPoolIndex = iter.u16()?.into();
+            match (tag, constant_pool.get(index)?) {
+                (b'D', ConstantPoolEntry::Double(value)) => ElementValue::Double(*value),
+                (b'F', ConstantPoolEntry::Float(value)) => ElementValue::Float(*value),
+                (b'J', ConstantPoolEntry::Long(value)) => ElementValue::Long(*value),
+                (_, constant) => {
+                    return Err(ParsingError::InvalidConstantValue(format!(
+                        "{:?}",
+                        constant
+                    )))
+                }
+            }
+        }
+        b's' => ElementValue::String(constant_pool.get_utf8(iter.u16()?.into())?.to_string()),
+        b'e' => ElementValue::Enum {
+            type_descriptor: constant_pool.get_utf8(iter.u16()?.into())?.to_string(),
+            constant: constant_pool.get_utf8(iter.u16()?.into())?.to_string(),
+        },
+        b'c' => ElementValue::Class(constant_pool.get_utf8(iter.u16()?.into())?.to_string()),
+        b'@' => ElementValue::Annotation(Box::new(parse_annotation(iter, constant_pool, visible)?)),
+        b'[' => {
+            let count = iter.u16()?;
+            let mut values = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                values.push(parse_element_value(iter, constant_pool, visible)?);
+            }
+            ElementValue::Array(values)
+        }
+        _ => return Err(ParsingError::InvalidElementValueTag(tag)),
+    };
+    Ok(value)
+}
+
+/// The CONSTANT_Integer that holds the value of a boolean, byte, char, short or int element
+fn int_constant(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+) -> Result<i32, ParsingError> {
+    match constant_pool.get(iter.u16()?.into())? {
+        ConstantPoolEntry::Integer(value) => Ok(*value),
+        constant => Err(ParsingError::InvalidConstantValue(format!(
+            "{:?}",
+            constant
+        ))),
+    }
+}
+
+/// Parses a type_annotation structure (JVMS §4.7.20)
+fn parse_type_annotation(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+    visible: bool,
+) -> Result<TypeAnnotation, ParsingError> {
+    let target_type = iter.byte()?;
+    let target = match target_type {
+        0x00 => TypeAnnotationTarget::ClassTypeParameter(iter.byte()?),
+        0x01 => TypeAnnotationTarget::MethodTypeParameter(iter.byte()?),
+        0x10 => TypeAnnotationTarget::Supertype(iter.u16()?),
+        0x11 => TypeAnnotationTarget::ClassTypeParameterBound {
+            type_parameter: iter.byte()?,
+            bound: iter.byte()?,
+        },
+        0x12 => TypeAnnotationTarget::MethodTypeParameterBound {
+            type_parameter: iter.byte()?,
+            bound: iter.byte()?,
+        },
+        0x13 => TypeAnnotationTarget::Field,
+        0x14 => TypeAnnotationTarget::Return,
+        0x15 => TypeAnnotationTarget::Receiver,
+        0x16 => TypeAnnotationTarget::FormalParameter(iter.byte()?),
+        0x17 => TypeAnnotationTarget::Throws(iter.u16()?),
+        0x40 | 0x41 => {
+            let count = iter.u16()?;
+            let mut ranges = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                ranges.push(LocalVariableRange {
+                    start_pc: iter.u16()?,
+                    length: iter.u16()?,
+                    index: iter.u16()?,
+                });
+            }
+            if target_type == 0x40 {
+                TypeAnnotationTarget::LocalVariable(ranges)
+            } else {
+                TypeAnnotationTarget::ResourceVariable(ranges)
+            }
+        }
+        0x42 => TypeAnnotationTarget::Catch(iter.u16()?),
+        0x43 => TypeAnnotationTarget::Instanceof(iter.u16()?),
+        0x44 => TypeAnnotationTarget::New(iter.u16()?),
+        0x45 => TypeAnnotationTarget::ConstructorReference(iter.u16()?),
+        0x46 => TypeAnnotationTarget::MethodReference(iter.u16()?),
+        0x47..=0x4B => {
+            let offset = iter.u16()?;
+            let type_argument = iter.byte()?;
+            match target_type {
+                0x47 => TypeAnnotationTarget::Cast {
+                    offset,
+                    type_argument,
+                },
+                0x48 => TypeAnnotationTarget::ConstructorInvocationTypeArgument {
+                    offset,
+                    type_argument,
+                },
+                0x49 => TypeAnnotationTarget::MethodInvocationTypeArgument {
+                    offset,
+                    type_argument,
+                },
+                0x4A => TypeAnnotationTarget::ConstructorReferenceTypeArgument {
+                    offset,
+                    type_argument,
+                },
+                _ => TypeAnnotationTarget::MethodReferenceTypeArgument {
+                    offset,
+                    type_argument,
+                },
+            }
+        }
+        _ => return Err(ParsingError::InvalidTypeAnnotationTarget(target_type)),
+    };
+
+    let length = iter.byte()?;
+    let mut path = Vec::with_capacity(length as usize);
+    for _ in 0..length {
+        let kind = iter.byte()?;
+        let argument = iter.byte()?;
+        path.push(match kind {
+            0 => TypePathStep::Array,
+            1 => TypePathStep::Nested,
+            2 => TypePathStep::WildcardBound,
+            3 => TypePathStep::TypeArgument(argument),
+            _ => return Err(ParsingError::InvalidTypePathKind(kind)),
+        });
+    }
+
+    Ok(TypeAnnotation {
+        target,
+        path,
+        annotation: parse_annotation(iter, constant_pool, visible)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        class_parser::{attribute, iterator::ClassFileIterator, ParsingError},
+        model::{
+            annotation::{Annotations, ElementValue, TypeAnnotationTarget, TypePathStep},
+            constant_pool::{ConstantPool, ConstantPoolEntry},
+        },
+    };
+
+    use super::parse_attribute;
+
+    fn constant_pool() -> ConstantPool {
+        let utf8 = |value: &str| ConstantPoolEntry::Utf8(value.to_string());
+        ConstantPool::new(vec![
+            utf8("Lp/Outer;"), // 1
+            utf8("b"),
+            utf8("c"),
+            utf8("i"),
+            utf8("s"),
+            utf8("z"),
+            utf8("j"),
+            utf8("d"),
+            utf8("f"),
+            utf8("str"), // 10
+            utf8("e"),
+            utf8("cls"),
+            utf8("nested"),
+            utf8("array"),
+            ConstantPoolEntry::Integer(65), // 15
+            ConstantPoolEntry::Long(8),
+            ConstantPoolEntry::Empty,
+            ConstantPoolEntry::Double(1.5),
+            ConstantPoolEntry::Empty,
+            ConstantPoolEntry::Float(2.5), // 20
+            utf8("text"),
+            utf8("Ljava/lang/annotation/RetentionPolicy;"),
+            utf8("RUNTIME"),
+            utf8("[Ljava/lang/String;"),
+            utf8("Lp/Inner;"), // 25
+            utf8("value"),
+        ])
+    }
+
+    /// An element_value with the tag and its constant pool indices
+    fn element_value(tag: u8, indices: &[u16]) -> Vec<u8> {
+        let mut bytes = vec![tag];
+        for index in indices {
+            bytes.extend(index.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// A RuntimeVisibleAnnotations attribute with an annotation that has an element of every kind
+    fn every_element_kind() -> Vec<u8> {
+        let mut bytes = vec![0, 1, 0, 1, 0, 13]; // One @p.Outer with 13 elements
+        let elements = [
+            (2, element_value(b'B', &[15])),
+            (3, element_value(b'C', &[15])),
+            (4, element_value(b'I', &[15])),
+            (5, element_value(b'S', &[15])),
+            (6, element_value(b'Z', &[15])),
+            (7, element_value(b'J', &[16])),
+            (8, element_value(b'D', &[18])),
+            (9, element_value(b'F', &[20])),
+            (10, element_value(b's', &[21])),
+            (11, element_value(b'e', &[22, 23])),
+            (12, element_value(b'c', &[24])),
+            // @p.Inner(65)
+            (
+                13,
+                [
+                    element_value(b'@', &[25, 1, 26]),
+                    element_value(b'I', &[15]),
+                ]
+                .concat(),
+            ),
+            (
+                14,
+                [
+                    element_value(b'[', &[2]),
+                    element_value(b'I', &[15]),
+                    element_value(b's', &[21]),
+                ]
+                .concat(),
+            ),
+        ];
+        for (name, value) in elements {
+            bytes.extend((name as u16).to_be_bytes());
+            bytes.extend(value);
+        }
+        bytes
+    }
+
+    fn parse(name: &str, bytes: &[u8]) -> Result<Annotations, ParsingError> {
+        let mut annotations = Annotations::default();
+        let mut iter = ClassFileIterator::new(bytes);
+        assert!(parse_attribute(
+            name,
+            &mut iter,
+            &constant_pool(),
+            &mut annotations
+        )?);
+        Ok(annotations)
+    }
+
+    #[test]
+    fn parses_every_kind_of_element_value() {
+        let annotations = parse(
+            attribute::RUNTIME_VISIBLE_ANNOTATIONS,
+            &every_element_kind(),
+        )
+        .unwrap();
+        assert_eq!(annotations.declaration.len(), 1);
+        assert_eq!(
+            annotations.declaration[0].to_string(),
+            "@p.Outer(b = (byte) 65, c = 'A', i = 65, s = (short) 65, z = true, j = 8L, d = 1.5, \
+             f = 2.5f, str = \"text\", e = java.lang.annotation.RetentionPolicy.RUNTIME, \
+             cls = java.lang.String[].class, nested = @p.Inner(65), array = {65, \"text\"})"
+        );
+    }
+
+    #[test]
+    fn nested_annotations_have_the_visibility_of_the_outer_one() {
+        let annotations = parse(
+            attribute::RUNTIME_INVISIBLE_ANNOTATIONS,
+            &every_element_kind(),
+        )
+        .unwrap();
+        let outer = &annotations.declaration[0];
+        assert!(!outer.visible);
+        let (_, nested) = &outer.elements[11];
+        assert!(matches!(nested, ElementValue::Annotation(inner) if !inner.visible));
+    }
+
+    #[test]
+    fn parses_parameter_and_type_annotations() {
+        // The second of two parameters is annotated with @p.Inner
+        let bytes = [2, 0, 0, 0, 1, 0, 25, 0, 0];
+        let annotations = parse(attribute::RUNTIME_VISIBLE_PARAMETER_ANNOTATIONS, &bytes).unwrap();
+        assert_eq!(annotations.parameters.len(), 2);
+        assert!(annotations.parameters[0].is_empty());
+        assert_eq!(annotations.parameters[1][0].to_string(), "@p.Inner");
+
+        // @p.Inner on the first type argument of local 1 in pc 0..5
+        let bytes = [0, 1, 0x40, 0, 1, 0, 0, 0, 5, 0, 1, 1, 3, 0, 0, 25, 0, 0];
+        let annotations = parse(attribute::RUNTIME_VISIBLE_TYPE_ANNOTATIONS, &bytes).unwrap();
+        let annotation = &annotations.types[0];
+        assert!(matches!(
+            &annotation.target,
+            TypeAnnotationTarget::LocalVariable(ranges)
+                if ranges.len() == 1 && ranges[0].index == 1 && ranges[0].length == 5
+        ));
+        assert!(matches!(
+            annotation.path[..],
+            [TypePathStep::TypeArgument(0)]
+        ));
+        assert_eq!(annotation.annotation.type_descriptor, "Lp/Inner;");
+    }
+
+    #[test]
+    fn parses_the_default_value_of_an_element() {
+        let bytes = element_value(b'e', &[22, 23]);
+        let annotations = parse(attribute::ANNOTATION_DEFAULT, &bytes).unwrap();
+        assert_eq!(
+            annotations.default.unwrap().to_string(),
+            "java.lang.annotation.RetentionPolicy.RUNTIME"
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_annotations() {
+        let bytes = every_element_kind();
+        for length in 0..bytes.len() {
+            let result = parse(attribute::RUNTIME_VISIBLE_ANNOTATIONS, &bytes[..length]);
+            assert!(
+                matches!(result, Err(ParsingError::UnexpectedEOF)),
+                "{} bytes: {:?}",
+                length,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_element_values() {
+        let annotation = |value: Vec<u8>| [vec![0, 1, 0, 1, 0, 1, 0, 2], value].concat();
+        let result = parse(
+            attribute::RUNTIME_VISIBLE_ANNOTATIONS,
+            &annotation(element_value(b'X', &[15])),
+        );
+        assert!(matches!(
+            result,
+            Err(ParsingError::InvalidElementValueTag(b'X'))
+        ));
+
+        // A long element whose constant is an Integer
+        let result = parse(
+            attribute::RUNTIME_VISIBLE_ANNOTATIONS,
+            &annotation(element_value(b'J', &[15])),
+        );
+        assert!(matches!(result, Err(ParsingError::InvalidConstantValue(_))));
+    }
+
+    #[test]
+    fn ignores_other_attributes() {
+        let mut annotations = Annotations::default();
+        let mut iter = ClassFileIterator::new(&[]);
+        let parsed = parse_attribute(
+            attribute::SIGNATURE,
+            &mut iter,
+            &constant_pool(),
+            &mut annotations,
+        );
+        assert!(!parsed.unwrap());
+        assert!(annotations.is_empty());
+    }
+}
@@ -1,36 +1,91 @@
-pub use attribute::*;
+#![allow(dead_code)]
 
-#[allow(dead_code)]
-pub mod attribute {
-    pub const CONSTANT_VALUE: &str = "ConstantValue";
-    pub const CODE: &str = "Code";
-    pub const STACK_MAP_TABLE: &str = "StackMapTable";
-    pub const BOOTSTRAP_METHODS: &str = "BootstrapMethods";
-    pub const NEST_HOST: &str = "NestHost";
-    pub const NEST_MEMBERS: &str = "NestMembers";
-    pub const PERMITTED_SUBCLASSES: &str = "PermittedSubclasses";
-    pub const EXCEPTIONS: &str = "Exceptions";
-    pub const INNER_CLASSES: &str = "InnerClasses";
-    pub const ENCLOSING_METHOD: &str = "EnclosingMethod";
-    pub const SYNTHETIC: &str = "Synthetic";
-    pub const SIGNATURE: &str = "Signature";
-    pub const RECORD: &str = "Record";
-    pub const SOURCE_FILE: &str = "SourceFile";
-    pub const LINE_NUMBER_TABLE: &str = "LineNumberTable";
-    pub const LOCAL_VARIABLE_TABLE: &str = "LocalVariableTable";
-    pub const LOCAL_VARIABLE_TYPE_TABLE: &str = "LocalVariableTypeTable";
-    pub const SOURCE_DEBUG_EXTENSIONS: &str = "SourceDebugExtensions";
-    pub const DEPRECATED: &str = "Deprecated";
-    pub const RUNTIME_VISIBLE_ANNOTATIONS: &str = "RuntimeVisibleAnnotations";
-    pub const RUNTIME_INVISIBLE_ANNOTATIONS: &str = "RuntimeInvisibleAnnotations";
-    pub const RUNTIME_VISIBLE_PARAMETER_ANNOTATIONS: &str = "RuntimeVisibleParameterAnnotations";
-    pub const RUNTIME_INVISIBLE_PARAMETER_ANNOTATIONS: &str =
-        "RuntimeInvisibleParameterAnnotations";
-    pub const RUNTIME_VISIBLE_TYPE_ANNOTATIONS: &str = "RuntimeVisibleTypeAnnotations";
-    pub const RUNTIME_INVISIBLE_TYPE_ANNOTATIONS: &str = "RuntimeInvisibleTypeAnnotations";
-    pub const ANNOTATION_DEFAULT: &str = "AnnotationDefault";
-    pub const METHOD_PARAMETERS: &str = "MethodParameters";
-    pub const MODULE: &str = "Module";
-    pub const MODULE_PACKAGES: &str = "ModulePackages";
-    pub const MODULE_MAIN_CLASS: &str = "ModuleMainClass";
+pub const CONSTANT_VALUE: &str = "ConstantValue";
+pub const CODE: &str = "Code";
+pub const STACK_MAP_TABLE: &str = "StackMapTable";
+pub const BOOTSTRAP_METHODS: &str = "BootstrapMethods";
+pub const NEST_HOST: &str = "NestHost";
+pub const NEST_MEMBERS: &str = "NestMembers";
+pub const PERMITTED_SUBCLASSES: &str = "PermittedSubclasses";
+pub const EXCEPTIONS: &str = "Exceptions";
+pub const INNER_CLASSES: &str = "InnerClasses";
+pub const ENCLOSING_METHOD: &str = "EnclosingMethod";
+pub const SYNTHETIC: &str = "Synthetic";
+pub const SIGNATURE: &str = "Signature";
+pub const RECORD: &str = "Record";
+pub const SOURCE_FILE: &str = "SourceFile";
+pub const LINE_NUMBER_TABLE: &str = "LineNumberTable";
+pub const LOCAL_VARIABLE_TABLE: &str = "LocalVariableTable";
+pub const LOCAL_VARIABLE_TYPE_TABLE: &str = "LocalVariableTypeTable";
+pub const SOURCE_DEBUG_EXTENSIONS: &str = "SourceDebugExtensions";
+pub const DEPRECATED: &str = "Deprecated";
+pub const RUNTIME_VISIBLE_ANNOTATIONS: &str = "RuntimeVisibleAnnotations";
+pub const RUNTIME_INVISIBLE_ANNOTATIONS: &str = "RuntimeInvisibleAnnotations";
+pub const RUNTIME_VISIBLE_PARAMETER_ANNOTATIONS: &str = "RuntimeVisibleParameterAnnotations";
+pub const RUNTIME_INVISIBLE_PARAMETER_ANNOTATIONS: &str = "RuntimeInvisibleParameterAnnotations";
+pub const RUNTIME_VISIBLE_TYPE_ANNOTATIONS: &str = "RuntimeVisibleTypeAnnotations";
+pub const RUNTIME_INVISIBLE_TYPE_ANNOTATIONS: &str = "RuntimeInvisibleTypeAnnotations";
+pub const ANNOTATION_DEFAULT: &str = "AnnotationDefault";
+pub const METHOD_PARAMETERS: &str = "MethodParameters";
+pub const MODULE: &str = "Module";
+pub const MODULE_PACKAGES: &str = "ModulePackages";
+pub const MODULE_MAIN_CLASS: &str = "ModuleMainClass";
+
+/// Whether a structure may have at most one attribute of the name (JVMS §4.7). The line number and local
+/// variable tables may be split into several attributes, unknown attributes are skipped anyway.
+pub fn is_unique(name: &str) -> bool {
+    matches!(
+        name,
+        CONSTANT_VALUE
+            | CODE
+            | STACK_MAP_TABLE
+            | BOOTSTRAP_METHODS
+            | NEST_HOST
+            | NEST_MEMBERS
+            | PERMITTED_SUBCLASSES
+            | EXCEPTIONS
+            | INNER_CLASSES
+            | ENCLOSING_METHOD
+            | SIGNATURE
+            | RECORD
+            | SOURCE_FILE
+            | SOURCE_DEBUG_EXTENSIONS
+            | RUNTIME_VISIBLE_ANNOTATIONS
+            | RUNTIME_INVISIBLE_ANNOTATIONS
+            | RUNTIME_VISIBLE_PARAMETER_ANNOTATIONS
+            | RUNTIME_INVISIBLE_PARAMETER_ANNOTATIONS
+            | RUNTIME_VISIBLE_TYPE_ANNOTATIONS
+            | RUNTIME_INVISIBLE_TYPE_ANNOTATIONS
+            | ANNOTATION_DEFAULT
+            | METHOD_PARAMETERS
+            | MODULE
+            | MODULE_PACKAGES
+            | MODULE_MAIN_CLASS
+    )
+}
+
+/// The first major version of the class file format that defines the attribute (JVMS §4.7). Class files of
+/// earlier versions may contain an attribute of the same name, which has to be ignored.
+pub fn introduced_in(name: &str) -> u16 {
+    match name {
+        ENCLOSING_METHOD
+        | SIGNATURE
+        | SOURCE_DEBUG_EXTENSIONS
+        | LOCAL_VARIABLE_TYPE_TABLE
+        | RUNTIME_VISIBLE_ANNOTATIONS
+        | RUNTIME_INVISIBLE_ANNOTATIONS
+        | RUNTIME_VISIBLE_PARAMETER_ANNOTATIONS
+        | RUNTIME_INVISIBLE_PARAMETER_ANNOTATIONS
+        | ANNOTATION_DEFAULT => 49,
+        STACK_MAP_TABLE => 50,
+        BOOTSTRAP_METHODS => 51,
+        METHOD_PARAMETERS
+        | RUNTIME_VISIBLE_TYPE_ANNOTATIONS
+        | RUNTIME_INVISIBLE_TYPE_ANNOTATIONS => 52,
+        MODULE | MODULE_PACKAGES | MODULE_MAIN_CLASS => 53,
+        NEST_HOST | NEST_MEMBERS => 55,
+        RECORD => 60,
+        PERMITTED_SUBCLASSES => 61,
+        _ => 45,
+    }
 }
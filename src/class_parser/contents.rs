@@ -0,0 +1,103 @@
+use crate::model::{
+    class_file::{
+        AttributeContent, AttributeInfo, ClassFileContents, CodeAttribute, ExceptionHandlerInfo,
+        MemberInfo,
+    },
+    constant_pool::{ConstantPool, ConstantPoolIndex},
+};
+
+use super::{attribute, iterator::ClassFileIterator, ParsingError};
+
+// Reads the part of a class file after the constant pool once more, but keeps it as it is instead of interpreting it.
+// The class file has already been parsed and validated by then, so only the structure is read here.
+
+pub(super) fn parse_contents(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+) -> Result<ClassFileContents, ParsingError> {
+    let access_flags = iter.u16()?;
+    let this_class = iter.u16()?.into();
+    let super_class = iter.u16()?.into();
+    let interfaces = parse_indices(iter)?;
+    let fields = parse_members(iter, constant_pool)?;
+    let methods = parse_members(iter, constant_pool)?;
+    let attributes = parse_attributes(iter, constant_pool)?;
+    Ok(ClassFileContents {
+        constant_pool: constant_pool.clone(),
+        access_flags,
+        this_class,
+        super_class,
+        interfaces,
+        fields,
+        methods,
+        attributes,
+    })
+}
+
+fn parse_indices(iter: &mut ClassFileIterator) -> Result<Vec<ConstantPoolIndex>, ParsingError> {
+    let count = iter.u16()?;
+    (0..count).map(|_| Ok(iter.u16()?.into())).collect()
+}
+
+fn parse_members(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+) -> Result<Vec<MemberInfo>, ParsingError> {
+    let count = iter.u16()?;
+    let mut members = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        members.push(MemberInfo {
+            access_flags: iter.u16()?,
+            name: iter.u16()?.into(),
+            descriptor: iter.u16()?.into(),
+            attributes: parse_attributes(iter, constant_pool)?,
+        });
+    }
+    Ok(members)
+}
+
+fn parse_attributes(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+) -> Result<Vec<AttributeInfo>, ParsingError> {
+    let count = iter.u16()?;
+    let mut attributes = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name: ConstantPoolIndex = iter.u16()?.into();
+        let length = iter.u32()? as usize;
+        let content = if constant_pool.get_utf8(name)? == attribute::CODE {
+            AttributeContent::Code(parse_code(iter, constant_pool)?)
+        } else {
+            AttributeContent::Raw(iter.take_bytes(length)?.to_vec())
+        };
+        attributes.push(AttributeInfo { name, content });
+    }
+    Ok(attributes)
+}
+
+fn parse_code(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+) -> Result<CodeAttribute, ParsingError> {
+    let max_stack = iter.u16()?;
+    let max_locals = iter.u16()?;
+    let code_length = iter.u32()? as usize;
+    let code = iter.take_bytes(code_length)?.to_vec();
+    let handler_count = iter.u16()?;
+    let mut exception_table = Vec::with_capacity(handler_count as usize);
+    for _ in 0..handler_count {
+        exception_table.push(ExceptionHandlerInfo {
+            start_pc: iter.u16()?,
+            end_pc: iter.u16()?,
+            handler_pc: iter.u16()?,
+            catch_type: iter.u16()?.into(),
+        });
+    }
+    Ok(CodeAttribute {
+        max_stack,
+        max_locals,
+        code,
+        exception_table,
+        attributes: parse_attributes(iter, constant_pool)?,
+    })
+}
@@ -1,13 +1,32 @@
+use crate::model::class_file::MAX_MAJOR_VERSION;
+
 use super::ParsingError;
 
 pub(super) struct ClassFileIterator<'b> {
     bytes: &'b [u8],
     offset: usize,
+    major_version: u16, // Of the class file, decides which attributes are recognized
 }
 
 impl<'b> ClassFileIterator<'b> {
     pub(super) fn new(bytes: &'b [u8]) -> Self {
-        Self { bytes, offset: 0 }
+        Self {
+            bytes,
+            offset: 0,
+            major_version: MAX_MAJOR_VERSION,
+        }
+    }
+
+    pub(super) fn major_version(&self) -> u16 {
+        self.major_version
+    }
+
+    pub(super) fn set_major_version(&mut self, major_version: u16) {
+        self.major_version = major_version;
+    }
+
+    pub(super) fn offset(&self) -> usize {
+        self.offset
     }
 
     pub(super) fn take_bytes(&mut self, count: usize) -> Result<&'_ [u8], ParsingError> {
@@ -94,4 +113,4 @@ impl<'b> ClassFileIterator<'b> {
             self.byte()?,
         ]))
     }
-}
\ No newline at end of file
+}
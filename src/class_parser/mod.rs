@@ -1,5 +1,8 @@
-mod attribute;
+mod annotation;
+pub mod attribute;
+mod contents;
 mod iterator;
+mod validation;
 
 use std::str::Utf8Error;
 
@@ -8,20 +11,39 @@ use unicode_segmentation::UnicodeSegmentation;
 use crate::{
     class_parser::iterator::ClassFileIterator,
     model::{
-        class_file::ClassFile,
+        access_flags::{ClassFlags, FieldFlags, MethodFlags},
+        annotation::Annotations,
+        class_file::{self, ClassFile, VersionError},
         constant_pool::{
-            ConstantPool, ConstantPoolEntry, ConstantPoolError, ConstantPoolIndex, FieldReference,
-            MethodReference,
+            BootstrapMethod, ConstantPool, ConstantPoolEntry, ConstantPoolError, ConstantPoolIndex,
+            FieldReference, InterfaceMethodReference, MethodReference, ReferenceKind,
         },
         field::FieldDescriptor,
-        method::{MethodCode, MethodDescriptor},
+        method::{ExceptionTableEntry, LineNumber, LocalVariable, MethodCode, MethodDescriptor},
+        module::{ModuleDescriptor, ModuleRequires, PackageGrant},
+        signature::{self, ClassSignature, SignatureError},
         types::JvmType,
         value::JvmValue,
         visibility::Visibility,
     },
 };
 
+const MAX_CODE_LENGTH: usize = 65535;
+const MAX_PARAMETER_SLOTS: usize = 255; // Including this (JVMS §4.3.3)
+
 pub fn parse(bytes: &[u8]) -> Result<(ClassFile, ClassData, ConstantPool), ParsingError> {
+    parse_class(bytes, false)
+}
+
+/// Parses the class file like parse, but keeps its complete structure in the ClassFile (see ClassFile::contents)
+pub fn parse_retaining(bytes: &[u8]) -> Result<(ClassFile, ClassData, ConstantPool), ParsingError> {
+    parse_class(bytes, true)
+}
+
+fn parse_class(
+    bytes: &[u8],
+    retain_contents: bool,
+) -> Result<(ClassFile, ClassData, ConstantPool), ParsingError> {
     let mut iter = ClassFileIterator::new(bytes);
 
     // Magic number
@@ -32,13 +54,20 @@ pub fn parse(bytes: &[u8]) -> Result<(ClassFile, ClassData, ConstantPool), Parsi
     // Version info
     let minor_version = iter.u16()?;
     let major_version = iter.u16()?;
+    class_file::check_version(major_version, minor_version)?;
+    iter.set_major_version(major_version);
 
     // Constant pool
-    let constant_pool = parse_constants(&mut iter).unwrap();
+    let constant_pool_start = iter.offset();
+    let constant_pool = parse_constants(&mut iter)?;
+    let constant_pool_length = iter.offset() - constant_pool_start;
+    validation::check_constant_pool(&constant_pool, major_version)?;
+    let contents_start = iter.offset();
 
     // Visibility
     let access_flags = iter.u16()?;
     let visibility = Visibility::from_access_flags(access_flags);
+    let flags = ClassFlags::from_bits_truncate(access_flags);
 
     // This
     let this_class: ConstantPoolIndex = iter.u16()?.into();
@@ -60,14 +89,93 @@ pub fn parse(bytes: &[u8]) -> Result<(ClassFile, ClassData, ConstantPool), Parsi
     let (static_methods, methods) = parse_methods(&mut iter, &constant_pool)?;
 
     // Attributes
-    parse_attributes(&mut iter, &&constant_pool, |_, _, _| Ok(false))?;
+    let mut module = None;
+    let mut module_packages = Vec::new();
+    let mut main_class = None;
+    let mut bootstrap_methods = Vec::new();
+    let mut nest_host = None;
+    let mut nest_members = Vec::new();
+    let mut permitted_subclasses = Vec::new();
+    let mut source_file = None;
+    let mut signature = None;
+    let mut annotations = Annotations::default();
+    parse_attributes(
+        &mut iter,
+        &constant_pool,
+        "the class",
+        |name, _, iter| match name {
+            attribute::MODULE => {
+                module = Some(parse_module(iter, &constant_pool)?);
+                Ok(true)
+            }
+            attribute::MODULE_PACKAGES => {
+                let count = iter.u16()?;
+                for _ in 0..count {
+                    module_packages.push(
+                        constant_pool
+                            .resolve_package(iter.u16()?.into())?
+                            .to_string(),
+                    );
+                }
+                Ok(true)
+            }
+            attribute::MODULE_MAIN_CLASS => {
+                main_class = Some(constant_pool.resolve_type(iter.u16()?.into())?.to_string());
+                Ok(true)
+            }
+            attribute::SIGNATURE => {
+                signature = Some(signature::parse_class(
+                    constant_pool.get_utf8(iter.u16()?.into())?,
+                )?);
+                Ok(true)
+            }
+            attribute::NEST_HOST => {
+                nest_host = Some(constant_pool.resolve_type(iter.u16()?.into())?.to_string());
+                Ok(true)
+            }
+            attribute::NEST_MEMBERS => {
+                let count = iter.u16()?;
+                for _ in 0..count {
+                    nest_members.push(constant_pool.resolve_type(iter.u16()?.into())?.to_string());
+                }
+                Ok(true)
+            }
+            attribute::PERMITTED_SUBCLASSES => {
+                let count = iter.u16()?;
+                for _ in 0..count {
+                    permitted_subclasses
+                        .push(constant_pool.resolve_type(iter.u16()?.into())?.to_string());
+                }
+                Ok(true)
+            }
+            attribute::BOOTSTRAP_METHODS => {
+                bootstrap_methods = parse_bootstrap_methods(iter, &constant_pool)?;
+                Ok(true)
+            }
+            attribute::SOURCE_FILE => {
+                source_file = Some(constant_pool.get_utf8(iter.u16()?.into())?.to_string());
+                Ok(true)
+            }
+            _ => annotation::parse_attribute(name, iter, &constant_pool, &mut annotations),
+        },
+    )?;
+    let module = if flags.contains(ClassFlags::MODULE) {
+        let mut module = module.ok_or(ParsingError::MissingModuleAttribute)?;
+        module.packages = module_packages;
+        module.main_class = main_class;
+        Some(module)
+    } else {
+        None
+    };
 
     // Create the actual class file
-    let class_file = ClassFile::new(minor_version, major_version);
+    let mut class_file = ClassFile::new(minor_version, major_version, constant_pool_length);
 
     // Create the actual class
     let class = ClassData {
         visibility,
+        flags,
+        is_interface: flags.contains(ClassFlags::INTERFACE),
         this_class,
         super_class,
         interfaces,
@@ -75,13 +183,33 @@ pub fn parse(bytes: &[u8]) -> Result<(ClassFile, ClassData, ConstantPool), Parsi
         fields,
         static_methods,
         methods,
+        module,
+        bootstrap_methods,
+        nest_host,
+        nest_members,
+        permitted_subclasses,
+        source_file,
+        signature,
+        annotations,
     };
+    validation::check_class(&class, &constant_pool)?;
+
+    if retain_contents {
+        let mut iter = ClassFileIterator::new(bytes);
+        iter.skip_bytes(contents_start)?;
+        iter.set_major_version(major_version);
+        class_file = class_file.with_contents(contents::parse_contents(&mut iter, &constant_pool)?);
+    }
 
     Ok((class_file, class, constant_pool))
 }
 
 fn parse_constants(iter: &mut ClassFileIterator) -> Result<ConstantPool, ParsingError> {
-    let count = iter.u16()? - 1; // For some obscure reason the number in the class file is the size of the constant pool plus one
+    // For some obscure reason the number in the class file is the size of the constant pool plus one
+    let count = iter
+        .u16()?
+        .checked_sub(1)
+        .ok_or(ParsingError::InvalidConstantPoolCount)?;
     let mut constants = Vec::with_capacity(count as usize);
 
     let mut i = 0; // We can't use for because some entries requires us to skip the next entry
@@ -121,6 +249,11 @@ fn parse_constants(iter: &mut ClassFileIterator) -> Result<ConstantPool, Parsing
                 name: iter.u16()?.into(),
             }),
 
+            // CONSTANT_String
+            8 => constants.push(ConstantPoolEntry::String {
+                value: iter.u16()?.into(),
+            }),
+
             // CONSTANT_Fieldref
             9 => constants.push(ConstantPoolEntry::FieldReference(
                 FieldReference::Unresolved {
@@ -138,10 +271,12 @@ fn parse_constants(iter: &mut ClassFileIterator) -> Result<ConstantPool, Parsing
             )),
 
             // CONSTANT_InterfaceMethodref
-            11 => constants.push(ConstantPoolEntry::InterfaceMethodReference {
-                class: iter.u16()?.into(),
-                name_and_type: iter.u16()?.into(),
-            }),
+            11 => constants.push(ConstantPoolEntry::InterfaceMethodReference(
+                InterfaceMethodReference::Unresolved {
+                    class: iter.u16()?.into(),
+                    name_and_type: iter.u16()?.into(),
+                },
+            )),
 
             // CONSTANT_NameAndType
             12 => constants.push(ConstantPoolEntry::NameAndType {
@@ -149,6 +284,43 @@ fn parse_constants(iter: &mut ClassFileIterator) -> Result<ConstantPool, Parsing
                 ty: iter.u16()?.into(),
             }),
 
+            // CONSTANT_MethodHandle
+            15 => {
+                let kind = iter.byte()?;
+                constants.push(ConstantPoolEntry::MethodHandle {
+                    kind: ReferenceKind::from_u8(kind)
+                        .ok_or(ParsingError::InvalidReferenceKind(kind))?,
+                    reference: iter.u16()?.into(),
+                })
+            }
+
+            // CONSTANT_MethodType
+            16 => constants.push(ConstantPoolEntry::MethodType {
+                descriptor: iter.u16()?.into(),
+            }),
+
+            // CONSTANT_Dynamic
+            17 => constants.push(ConstantPoolEntry::Dynamic {
+                bootstrap_method: iter.u16()?,
+                name_and_type: iter.u16()?.into(),
+            }),
+
+            // CONSTANT_InvokeDynamic
+            18 => constants.push(ConstantPoolEntry::InvokeDynamic {
+                bootstrap_method: iter.u16()?,
+                name_and_type: iter.u16()?.into(),
+            }),
+
+            // CONSTANT_Module
+            19 => constants.push(ConstantPoolEntry::Module {
+                name: iter.u16()?.into(),
+            }),
+
+            // CONSTANT_Package
+            20 => constants.push(ConstantPoolEntry::Package {
+                name: iter.u16()?.into(),
+            }),
+
             _ => return Err(ParsingError::UnknownConstantTag(tag)),
         }
         i += 1;
@@ -168,6 +340,7 @@ fn parse_fields(
     for _ in 0..field_count {
         let access_flags = iter.u16()?;
         let visibility = Visibility::from_access_flags(access_flags);
+        let flags = FieldFlags::from_bits_truncate(access_flags);
 
         let name_index = iter.u16()?;
         let name = constant_pool.get_utf8(name_index.into())?.to_string();
@@ -178,46 +351,75 @@ fn parse_fields(
             .ok_or(ParsingError::InvalidType(type_string.to_string()))?;
 
         let mut constant_value = None;
-
-        parse_attributes(iter, constant_pool, |attr_name, _, iter| {
-            match attr_name {
-                attribute::CONSTANT_VALUE => {
-                    let value_index = iter.u16()?;
-                    let constant = constant_pool.get(value_index.into())?;
-                    let value = match constant {
-                        ConstantPoolEntry::Integer(value) => JvmValue { int: *value },
-                        ConstantPoolEntry::Long(value) => JvmValue { long: *value },
-                        ConstantPoolEntry::Float(value) => JvmValue { float: *value },
-                        ConstantPoolEntry::Double(value) => JvmValue { double: *value },
-                        //TODO parse strings
-                        _ => {
-                            return Err(ParsingError::InvalidConstantValue(format!(
-                                "{:?}",
-                                constant
-                            )))
-                        }
-                    };
-                    constant_value = Some(value);
-                    Ok(true)
+        let mut constant_string = None;
+        let mut signature = None;
+        let mut annotations = Annotations::default();
+
+        parse_attributes(
+            iter,
+            constant_pool,
+            &format!("field {}", name),
+            |attr_name, _, iter| {
+                match attr_name {
+                    attribute::SIGNATURE => {
+                        signature = Some(signature::parse_field(
+                            constant_pool.get_utf8(iter.u16()?.into())?,
+                        )?);
+                        Ok(true)
+                    }
+                    attribute::CONSTANT_VALUE => {
+                        let value_index = iter.u16()?;
+                        let constant = constant_pool.get(value_index.into())?;
+                        let value = match constant {
+                            ConstantPoolEntry::Integer(value) => JvmValue { int: *value },
+                            ConstantPoolEntry::Long(value) => JvmValue { long: *value },
+                            ConstantPoolEntry::Float(value) => JvmValue { float: *value },
+                            ConstantPoolEntry::Double(value) => JvmValue { double: *value },
+                            ConstantPoolEntry::String { value } => {
+                                constant_string = Some(constant_pool.get_utf8(*value)?.to_string());
+                                return Ok(true);
+                            }
+                            _ => {
+                                return Err(ParsingError::InvalidConstantValue(format!(
+                                    "{:?}",
+                                    constant
+                                )))
+                            }
+                        };
+                        constant_value = Some(value);
+                        Ok(true)
+                    }
+                    _ => annotation::parse_attribute(
+                        attr_name,
+                        iter,
+                        constant_pool,
+                        &mut annotations,
+                    ),
                 }
-                _ => Ok(false),
-            }
-        })?;
+            },
+        )?;
 
-        if access_flags & 0x0008 != 0 {
-            // ACC_STATIC
+        if flags.contains(FieldFlags::STATIC) {
             static_fields.push(FieldDescriptor {
                 name,
                 visibility,
+                flags,
                 ty,
                 constant_value,
+                constant_string,
+                signature,
+                annotations,
             });
         } else {
             fields.push(FieldDescriptor {
                 name,
                 visibility,
+                flags,
                 ty,
                 constant_value,
+                constant_string,
+                signature,
+                annotations,
             });
         }
     }
@@ -225,6 +427,56 @@ fn parse_fields(
     Ok((static_fields, fields))
 }
 
+/// Checks the limits of the JVM specification that the interpreter and the JIT rely on (JVMS §4.7.3, §4.11)
+fn check_method_limits(
+    name: &str,
+    code: &MethodCode,
+    parameters: &[JvmType],
+    has_receiver: bool,
+    max_locals: usize,
+    exception_table: &[ExceptionTableEntry],
+) -> Result<(), ParsingError> {
+    let parameter_slots =
+        parameters.iter().map(|ty| ty.slots()).sum::<usize>() + has_receiver as usize;
+    if parameter_slots > MAX_PARAMETER_SLOTS {
+        return Err(ParsingError::TooManyParameters(
+            name.to_string(),
+            parameter_slots,
+        ));
+    }
+
+    let MethodCode::Bytecode(code) = code else {
+        return Ok(());
+    };
+    if code.is_empty() || code.len() > MAX_CODE_LENGTH {
+        return Err(ParsingError::InvalidCodeLength(
+            name.to_string(),
+            code.len(),
+        ));
+    }
+    if parameter_slots > max_locals {
+        return Err(ParsingError::TooFewLocals {
+            method: name.to_string(),
+            max_locals,
+            parameter_slots,
+        });
+    }
+    for entry in exception_table {
+        if entry.start_pc >= entry.end_pc
+            || entry.end_pc > code.len()
+            || entry.handler_pc >= code.len()
+        {
+            return Err(ParsingError::InvalidExceptionHandler(
+                name.to_string(),
+                entry.start_pc,
+                entry.end_pc,
+                entry.handler_pc,
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn parse_methods(
     iter: &mut ClassFileIterator,
     constant_pool: &ConstantPool,
@@ -236,6 +488,8 @@ fn parse_methods(
     for _ in 0..method_count {
         let access_flags = iter.u16()?;
         let visibility = Visibility::from_access_flags(access_flags);
+        let flags = MethodFlags::from_bits_truncate(access_flags);
+        let is_static = flags.contains(MethodFlags::STATIC);
 
         let name_index = iter.u16()?;
         let name = constant_pool.get_utf8(name_index.into())?.to_string();
@@ -246,53 +500,161 @@ fn parse_methods(
         let mut code = None;
         let mut max_stack = 0;
         let mut max_locals = 0;
-        parse_attributes(iter, constant_pool, |attribute_name, _, iter| {
-            match attribute_name {
-                attribute::CODE => {
-                    max_stack = iter.u16()? as usize;
-                    max_locals = iter.u16()? as usize;
-                    let code_length = iter.u32()?;
-                    code = Some(iter.take_bytes(code_length as usize)?.to_vec());
-                    let exception_table_length = iter.u16()?;
-                    //TODO
-                    // Skip the exception table for now
-                    iter.skip_bytes(exception_table_length as usize * 8)?;
-
-                    //TODO
-                    // Skip the attributes
-                    parse_attributes(iter, constant_pool, |_, _, _| Ok(false))?;
-
-                    Ok(true)
+        let mut exception_table = Vec::new();
+        let mut line_numbers = Vec::new();
+        let mut local_variables = Vec::new();
+        let mut local_variable_types = Vec::new();
+        let mut signature = None;
+        let mut annotations = Annotations::default();
+        parse_attributes(
+            iter,
+            constant_pool,
+            &format!("method {}", name),
+            |attribute_name, _, iter| {
+                match attribute_name {
+                    attribute::SIGNATURE => {
+                        signature = Some(signature::parse_method(
+                            constant_pool.get_utf8(iter.u16()?.into())?,
+                        )?);
+                        Ok(true)
+                    }
+                    attribute::CODE => {
+                        max_stack = iter.u16()? as usize;
+                        max_locals = iter.u16()? as usize;
+                        let code_length = iter.u32()?;
+                        code = Some(iter.take_bytes(code_length as usize)?.to_vec());
+                        let exception_table_length = iter.u16()?;
+                        for _ in 0..exception_table_length {
+                            exception_table.push(ExceptionTableEntry {
+                                start_pc: iter.u16()? as usize,
+                                end_pc: iter.u16()? as usize,
+                                handler_pc: iter.u16()? as usize,
+                                catch_type: iter.u16()?.into(),
+                            });
+                        }
+
+                        parse_attributes(
+                            iter,
+                            constant_pool,
+                            &format!("the code of method {}", name),
+                            |attribute_name, _, iter| {
+                                match attribute_name {
+                                    // A method may have multiple tables, which together form the mapping (JVMS §4.7.12)
+                                    attribute::LINE_NUMBER_TABLE => {
+                                        let length = iter.u16()?;
+                                        for _ in 0..length {
+                                            line_numbers.push(LineNumber {
+                                                start_pc: iter.u16()? as usize,
+                                                line: iter.u16()? as usize,
+                                            });
+                                        }
+                                        Ok(true)
+                                    }
+                                    // The type table only lists the variables of generic types, with their signature
+                                    attribute::LOCAL_VARIABLE_TABLE
+                                    | attribute::LOCAL_VARIABLE_TYPE_TABLE => {
+                                        let length = iter.u16()?;
+                                        for _ in 0..length {
+                                            let start_pc = iter.u16()? as usize;
+                                            let length = iter.u16()? as usize;
+                                            let name =
+                                                constant_pool.get_utf8(iter.u16()?.into())?;
+                                            let descriptor =
+                                                constant_pool.get_utf8(iter.u16()?.into())?;
+                                            let variable = LocalVariable {
+                                                start_pc,
+                                                length,
+                                                name: name.to_string(),
+                                                descriptor: descriptor.to_string(),
+                                                signature: None,
+                                                slot: iter.u16()? as usize,
+                                            };
+                                            if attribute_name == attribute::LOCAL_VARIABLE_TABLE {
+                                                local_variables.push(variable);
+                                            } else {
+                                                local_variable_types.push(variable);
+                                            }
+                                        }
+                                        Ok(true)
+                                    }
+                                    // Type annotations of local variables, casts, ... are stored in the Code attribute
+                                    _ => annotation::parse_attribute(
+                                        attribute_name,
+                                        iter,
+                                        constant_pool,
+                                        &mut annotations,
+                                    ),
+                                }
+                            },
+                        )?;
+
+                        Ok(true)
+                    }
+                    _ => annotation::parse_attribute(
+                        attribute_name,
+                        iter,
+                        constant_pool,
+                        &mut annotations,
+                    ),
                 }
-                _ => Ok(false),
-            }
-        })?;
+            },
+        )?;
 
         let code = if let Some(bytecode) = code {
             MethodCode::Bytecode(bytecode)
-        } else if is_native(access_flags) {
+        } else if flags.contains(MethodFlags::NATIVE) {
             log::info!("Encountered native method '{0}'", name);
             MethodCode::Native
-        } else if is_abstract(access_flags) {
+        } else if flags.contains(MethodFlags::ABSTRACT) {
             MethodCode::Abstract
         } else {
             return Err(ParsingError::MissingCode(name));
         };
 
         let (parameters, return_type) = parse_descriptor(&descriptor)?;
+        line_numbers.sort_by_key(|entry: &LineNumber| entry.start_pc);
+        for typed in local_variable_types {
+            // A variable is identified by its range and slot (JVMS §4.7.14)
+            if let Some(variable) =
+                local_variables
+                    .iter_mut()
+                    .find(|variable: &&mut LocalVariable| {
+                        variable.start_pc == typed.start_pc
+                            && variable.length == typed.length
+                            && variable.slot == typed.slot
+                    })
+            {
+                variable.signature = Some(typed.descriptor);
+            }
+        }
+        check_method_limits(
+            &name,
+            &code,
+            &parameters,
+            !is_static,
+            max_locals,
+            &exception_table,
+        )?;
 
         let method = MethodDescriptor {
             name,
+            descriptor,
             parameters,
             return_type,
             visibility,
+            flags,
             code,
             max_stack,
             max_locals,
-            is_virtual: !is_static(access_flags),
+            exception_table,
+            line_numbers,
+            local_variables,
+            signature,
+            annotations,
+            is_virtual: !is_static,
         };
 
-        if is_static(access_flags) {
+        if is_static {
             static_methods.push(method);
         } else {
             methods.push(method);
@@ -302,28 +664,188 @@ fn parse_methods(
     Ok((static_methods, methods))
 }
 
+/// Parses the content of a BootstrapMethods attribute (JVMS §4.7.23)
+fn parse_bootstrap_methods(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+) -> Result<Vec<BootstrapMethod>, ParsingError> {
+    let count = iter.u16()?;
+    let mut bootstrap_methods = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let method_handle = iter.u16()?.into();
+        constant_pool.get_method_handle(method_handle)?;
+        let argument_count = iter.u16()?;
+        let mut arguments = Vec::with_capacity(argument_count as usize);
+        for _ in 0..argument_count {
+            let argument = iter.u16()?.into();
+            match constant_pool.get(argument)? {
+                ConstantPoolEntry::Integer(_)
+                | ConstantPoolEntry::Float(_)
+                | ConstantPoolEntry::Long(_)
+                | ConstantPoolEntry::Double(_)
+                | ConstantPoolEntry::Class { .. }
+                | ConstantPoolEntry::String { .. }
+                | ConstantPoolEntry::MethodHandle { .. }
+                | ConstantPoolEntry::MethodType { .. }
+                | ConstantPoolEntry::Dynamic { .. } => arguments.push(argument),
+                _ => return Err(ConstantPoolError::NotLoadable(argument).into()),
+            }
+        }
+        bootstrap_methods.push(BootstrapMethod {
+            method_handle,
+            arguments,
+        });
+    }
+    Ok(bootstrap_methods)
+}
+
+/// Parses the content of a Module attribute (JVMS §4.7.25)
+fn parse_module(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+) -> Result<ModuleDescriptor, ParsingError> {
+    let name = constant_pool
+        .resolve_module(iter.u16()?.into())?
+        .to_string();
+    let flags = iter.u16()?;
+    let version = parse_optional_utf8(iter, constant_pool)?;
+
+    let requires_count = iter.u16()?;
+    let mut requires = Vec::with_capacity(requires_count as usize);
+    for _ in 0..requires_count {
+        requires.push(ModuleRequires {
+            module: constant_pool
+                .resolve_module(iter.u16()?.into())?
+                .to_string(),
+            flags: iter.u16()?,
+            version: parse_optional_utf8(iter, constant_pool)?,
+        });
+    }
+
+    let exports = parse_package_grants(iter, constant_pool)?;
+    let opens = parse_package_grants(iter, constant_pool)?;
+
+    let uses_count = iter.u16()?;
+    let mut uses = Vec::with_capacity(uses_count as usize);
+    for _ in 0..uses_count {
+        uses.push(constant_pool.resolve_type(iter.u16()?.into())?.to_string());
+    }
+
+    let provides_count = iter.u16()?;
+    let mut provides = Vec::with_capacity(provides_count as usize);
+    for _ in 0..provides_count {
+        let service = constant_pool.resolve_type(iter.u16()?.into())?.to_string();
+        let with_count = iter.u16()?;
+        let mut implementations = Vec::with_capacity(with_count as usize);
+        for _ in 0..with_count {
+            implementations.push(constant_pool.resolve_type(iter.u16()?.into())?.to_string());
+        }
+        provides.push((service, implementations));
+    }
+
+    Ok(ModuleDescriptor {
+        name,
+        flags,
+        version,
+        requires,
+        exports,
+        opens,
+        uses,
+        provides,
+        packages: Vec::new(),
+        main_class: None,
+    })
+}
+
+/// Parses the exports or opens table of a Module attribute, which have the same structure
+fn parse_package_grants(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+) -> Result<Vec<PackageGrant>, ParsingError> {
+    let count = iter.u16()?;
+    let mut grants = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let package = constant_pool
+            .resolve_package(iter.u16()?.into())?
+            .to_string();
+        let flags = iter.u16()?;
+        let to_count = iter.u16()?;
+        let mut to = Vec::with_capacity(to_count as usize);
+        for _ in 0..to_count {
+            to.push(
+                constant_pool
+                    .resolve_module(iter.u16()?.into())?
+                    .to_string(),
+            );
+        }
+        grants.push(PackageGrant { package, flags, to });
+    }
+    Ok(grants)
+}
+
+/// Reads a constant pool index that refers to an utf8 constant or is zero
+fn parse_optional_utf8(
+    iter: &mut ClassFileIterator,
+    constant_pool: &ConstantPool,
+) -> Result<Option<String>, ParsingError> {
+    let index: ConstantPoolIndex = iter.u16()?.into();
+    if index.is_valid() {
+        Ok(Some(constant_pool.get_utf8(index)?.to_string()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Calls the handler for every attribute, which returns whether it has read the attribute. The owner names the
+/// structure that has the attributes in errors.
 fn parse_attributes<H>(
     iter: &mut ClassFileIterator,
     constant_pool: &ConstantPool,
+    owner: &str,
     mut handler: H,
 ) -> Result<(), ParsingError>
 where
     H: FnMut(&str, usize, &mut ClassFileIterator) -> Result<bool, ParsingError>,
 {
     let count = iter.u16()?;
+    let mut unique_attributes = Vec::new();
     for _ in 0..count {
         let name_index = iter.u16()?;
         let name = constant_pool.get_utf8(name_index.into())?;
         let length = iter.u32()? as usize;
-        if !handler(name, length, iter)? {
+        if attribute::is_unique(name) {
+            if unique_attributes.contains(&name) {
+                return Err(ParsingError::DuplicateAttribute {
+                    owner: owner.to_string(),
+                    name: name.to_string(),
+                });
+            }
+            unique_attributes.push(name);
+        }
+        let start = iter.offset();
+        if attribute::introduced_in(name) > iter.major_version() {
+            log::info!(
+                "Skipping attribute '{}', which is not defined in class files of version {}",
+                name,
+                iter.major_version()
+            );
+            iter.take_bytes(length)?;
+        } else if !handler(name, length, iter)? {
             log::info!("Skipping attribute '{}'", name);
             iter.take_bytes(length)?;
+        } else if iter.offset() - start != length {
+            return Err(ParsingError::AttributeLength {
+                owner: owner.to_string(),
+                name: name.to_string(),
+                declared: length,
+                actual: iter.offset() - start,
+            });
         }
     }
     Ok(())
 }
 
-fn parse_descriptor(descriptor: &str) -> Result<(Vec<JvmType>, JvmType), ParsingError> {
+pub fn parse_descriptor(descriptor: &str) -> Result<(Vec<JvmType>, JvmType), ParsingError> {
     let mut graphemes = descriptor.graphemes(true).peekable();
     if let Some(char) = graphemes.next() {
         if char != "(" {
@@ -352,18 +874,10 @@ fn parse_descriptor(descriptor: &str) -> Result<(Vec<JvmType>, JvmType), Parsing
     Ok((parameters, return_type))
 }
 
-fn is_native(access_flags: u16) -> bool {
-    access_flags & 0x0100 != 0
-}
-fn is_static(access_flags: u16) -> bool {
-    access_flags & 0x0008 != 0
-}
-fn is_abstract(access_flags: u16) -> bool {
-    access_flags & 0x0400 != 0
-}
-
 pub struct ClassData {
     pub visibility: Visibility,
+    pub flags: ClassFlags,
+    pub is_interface: bool,
     pub this_class: ConstantPoolIndex,
     pub super_class: ConstantPoolIndex,
     pub interfaces: Vec<ConstantPoolIndex>,
@@ -371,6 +885,14 @@ pub struct ClassData {
     pub fields: Vec<FieldDescriptor>,
     pub static_methods: Vec<MethodDescriptor>,
     pub methods: Vec<MethodDescriptor>,
+    pub module: Option<ModuleDescriptor>, // Only present in module-info.class
+    pub bootstrap_methods: Vec<BootstrapMethod>, // Indexed by the Dynamic and InvokeDynamic entries
+    pub nest_host: Option<String>,        // None if the class is the host of its nest
+    pub nest_members: Vec<String>,        // Only listed by the nest host
+    pub permitted_subclasses: Vec<String>, // Only listed by sealed classes and interfaces
+    pub source_file: Option<String>,      // The file name without the directories, e.g. Foo.java
+    pub signature: Option<ClassSignature>, // Only present for generic classes and subtypes of parameterized types
+    pub annotations: Annotations,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -381,9 +903,49 @@ pub enum ParsingError {
     #[error("unexpected end of file")]
     UnexpectedEOF,
 
+    #[error(transparent)]
+    UnsupportedVersion(#[from] VersionError),
+
+    #[error("the constant pool count must be at least 1")]
+    InvalidConstantPoolCount,
+
+    #[error("constant pool entry {entry} refers to {index}, which is not a {expected} entry")]
+    InvalidConstantReference {
+        entry: u16,
+        index: u16,
+        expected: &'static str,
+    },
+
+    #[error("{referenced_by} refers to the constant pool entry {index}, which is not a {expected} entry")]
+    InvalidClassReference {
+        referenced_by: String,
+        index: u16,
+        expected: &'static str,
+    },
+
+    #[error("the class has no bootstrap method {0}")]
+    MissingBootstrapMethod(u16),
+
+    #[error("'{name}' is not a valid {kind} name")]
+    InvalidName { kind: &'static str, name: String },
+
+    #[error("the {name} attribute of {owner} declares a length of {declared} bytes, but has {actual} bytes")]
+    AttributeLength {
+        owner: String,
+        name: String,
+        declared: usize,
+        actual: usize,
+    },
+
+    #[error("{owner} has more than one {name} attribute")]
+    DuplicateAttribute { owner: String, name: String },
+
     #[error("unknown constant tag {0}")]
     UnknownConstantTag(u8),
 
+    #[error("invalid reference kind {0} of a method handle")]
+    InvalidReferenceKind(u8),
+
     #[error("invalid utf string at constant index {0}: {1}")]
     InvalidUtf8Constant(u16, Utf8Error),
 
@@ -399,12 +961,47 @@ pub enum ParsingError {
     #[error("no code attribute found for methode {0}")]
     MissingCode(String),
 
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+
+    #[error("invalid tag '{}' of an annotation element value", *.0 as char)]
+    InvalidElementValueTag(u8),
+
+    #[error("invalid target type {0:#04x} of a type annotation")]
+    InvalidTypeAnnotationTarget(u8),
+
+    #[error("invalid type path kind {0} of a type annotation")]
+    InvalidTypePathKind(u8),
+
     #[error("constant pool error")]
     ConstantPool {
         #[from]
         source: ConstantPoolError,
     },
 
+    #[error("the module-info class file has no Module attribute")]
+    MissingModuleAttribute,
+
     #[error("could not parse method descriptor '{0}'")]
     DescriptorParseError(String),
+
+    #[error(
+        "the code of method {0} has the invalid length {1} (must be between 1 and 65535 bytes)"
+    )]
+    InvalidCodeLength(String, usize),
+
+    #[error("the parameters of method {0} take {1} slots, but at most 255 are allowed")]
+    TooManyParameters(String, usize),
+
+    #[error("method {method} has {max_locals} local variables, but its parameters take {parameter_slots} slots")]
+    TooFewLocals {
+        method: String,
+        max_locals: usize,
+        parameter_slots: usize,
+    },
+
+    #[error(
+        "method {0} has an exception handler with the invalid range {1}..{2} or handler pc {3}"
+    )]
+    InvalidExceptionHandler(String, usize, usize, usize),
 }
@@ -0,0 +1,450 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::model::{
+    constant_pool::{
+        ConstantPool, ConstantPoolEntry, ConstantPoolIndex, FieldReference,
+        InterfaceMethodReference, MethodReference, ReferenceKind,
+    },
+    types::JvmType,
+};
+
+use super::{ClassData, ParsingError};
+
+// The format checks of JVMS §4.8 that aren't already made while reading the class file. The parser only looks up the
+// constant pool entries that it needs, so a malformed entry would otherwise surface as a failed resolution (or a
+// panic) long after the class has been loaded. The constant pool is checked right after it has been read, the rest of
+// the class once it has been parsed completely. The lengths of the attributes and their number are checked while
+// reading them (see parse_attributes), the limits of the code in check_method_limits.
+
+/// Checks that every entry of the constant pool refers to entries of the right kinds and that the names of classes
+/// are well-formed
+pub(super) fn check_constant_pool(
+    constant_pool: &ConstantPool,
+    major_version: u16,
+) -> Result<(), ParsingError> {
+    for (position, entry) in constant_pool.entries().iter().enumerate() {
+        let entry_index = position as u16 + 1;
+        let expect = |index: ConstantPoolIndex, expected: &'static str| {
+            if kind(constant_pool, index) == Some(expected) {
+                Ok(())
+            } else {
+                Err(ParsingError::InvalidConstantReference {
+                    entry: entry_index,
+                    index: index.as_u16(),
+                    expected,
+                })
+            }
+        };
+        match entry {
+            ConstantPoolEntry::String { value: name }
+            | ConstantPoolEntry::MethodType { descriptor: name }
+            | ConstantPoolEntry::Module { name }
+            | ConstantPoolEntry::Package { name } => expect(*name, UTF8)?,
+            ConstantPoolEntry::Class { name } => {
+                expect(*name, UTF8)?;
+                check_class_name(constant_pool.get_utf8(*name)?)?;
+            }
+            ConstantPoolEntry::FieldReference(FieldReference::Unresolved {
+                class,
+                name_and_type,
+            })
+            | ConstantPoolEntry::MethodReference(MethodReference::Unresolved {
+                class,
+                name_and_type,
+            })
+            | ConstantPoolEntry::InterfaceMethodReference(InterfaceMethodReference::Unresolved {
+                class,
+                name_and_type,
+            }) => {
+                expect(*class, CLASS)?;
+                expect(*name_and_type, NAME_AND_TYPE)?;
+            }
+            ConstantPoolEntry::NameAndType { name, ty } => {
+                expect(*name, UTF8)?;
+                expect(*ty, UTF8)?;
+            }
+            ConstantPoolEntry::MethodHandle {
+                kind: reference_kind,
+                reference,
+            } => {
+                let expected = match reference_kind {
+                    ReferenceKind::GetField
+                    | ReferenceKind::GetStatic
+                    | ReferenceKind::PutField
+                    | ReferenceKind::PutStatic => FIELD_REFERENCE,
+                    ReferenceKind::InvokeVirtual | ReferenceKind::NewInvokeSpecial => {
+                        METHOD_REFERENCE
+                    }
+                    // Static and special handles may also refer to interface methods since Java 8
+                    ReferenceKind::InvokeStatic | ReferenceKind::InvokeSpecial
+                        if major_version >= 52
+                            && kind(constant_pool, *reference)
+                                == Some(INTERFACE_METHOD_REFERENCE) =>
+                    {
+                        INTERFACE_METHOD_REFERENCE
+                    }
+                    ReferenceKind::InvokeStatic | ReferenceKind::InvokeSpecial => METHOD_REFERENCE,
+                    ReferenceKind::InvokeInterface => INTERFACE_METHOD_REFERENCE,
+                };
+                expect(*reference, expected)?;
+            }
+            ConstantPoolEntry::Dynamic { name_and_type, .. }
+            | ConstantPoolEntry::InvokeDynamic { name_and_type, .. } => {
+                expect(*name_and_type, NAME_AND_TYPE)?
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Checks the references of the class to the constant pool and the names of its members
+pub(super) fn check_class(
+    data: &ClassData,
+    constant_pool: &ConstantPool,
+) -> Result<(), ParsingError> {
+    let expect = |index: ConstantPoolIndex, expected: &'static str, referenced_by: &str| {
+        if kind(constant_pool, index) == Some(expected) {
+            Ok(())
+        } else {
+            Err(ParsingError::InvalidClassReference {
+                referenced_by: referenced_by.to_string(),
+                index: index.as_u16(),
+                expected,
+            })
+        }
+    };
+    expect(data.this_class, CLASS, "this_class")?;
+    if data.super_class.is_valid() {
+        expect(data.super_class, CLASS, "super_class")?;
+    }
+    for interface in &data.interfaces {
+        expect(*interface, CLASS, "interfaces")?;
+    }
+    for (index, bootstrap_method) in data.bootstrap_methods.iter().enumerate() {
+        expect(
+            bootstrap_method.method_handle,
+            METHOD_HANDLE,
+            &format!("bootstrap method {}", index),
+        )?;
+    }
+
+    // Dynamically-computed constants and call sites are resolved through the BootstrapMethods attribute
+    for entry in constant_pool.entries() {
+        if let ConstantPoolEntry::Dynamic {
+            bootstrap_method, ..
+        }
+        | ConstantPoolEntry::InvokeDynamic {
+            bootstrap_method, ..
+        } = entry
+        {
+            if *bootstrap_method as usize >= data.bootstrap_methods.len() {
+                return Err(ParsingError::MissingBootstrapMethod(*bootstrap_method));
+            }
+        }
+    }
+
+    for field in data.static_fields.iter().chain(&data.fields) {
+        check_unqualified_name(&field.name, "field")?;
+    }
+    for method in data.static_methods.iter().chain(&data.methods) {
+        if method.name != "<init>" && method.name != "<clinit>" {
+            check_unqualified_name(&method.name, "method")?;
+            if method.name.contains(['<', '>']) {
+                return Err(invalid_name(&method.name, "method"));
+            }
+        }
+    }
+    Ok(())
+}
+
+const UTF8: &str = "Utf8";
+const CLASS: &str = "Class";
+const NAME_AND_TYPE: &str = "NameAndType";
+const FIELD_REFERENCE: &str = "Fieldref";
+const METHOD_REFERENCE: &str = "Methodref";
+const INTERFACE_METHOD_REFERENCE: &str = "InterfaceMethodref";
+const METHOD_HANDLE: &str = "MethodHandle";
+
+/// The kind of the entry like in the names of the tags (JVMS §4.4), None if there is no entry at the index
+fn kind(constant_pool: &ConstantPool, index: ConstantPoolIndex) -> Option<&'static str> {
+    Some(match constant_pool.get(index).ok()? {
+        ConstantPoolEntry::Utf8(_) => UTF8,
+        ConstantPoolEntry::Class { .. } => CLASS,
+        ConstantPoolEntry::NameAndType { .. } => NAME_AND_TYPE,
+        ConstantPoolEntry::FieldReference(_) => FIELD_REFERENCE,
+        ConstantPoolEntry::MethodReference(_) => METHOD_REFERENCE,
+        ConstantPoolEntry::InterfaceMethodReference(_) => INTERFACE_METHOD_REFERENCE,
+        ConstantPoolEntry::MethodHandle { .. } => METHOD_HANDLE,
+        _ => "other",
+    })
+}
+
+/// Class names are binary names in internal form, array classes are named by their descriptor (JVMS §4.2.1)
+fn check_class_name(name: &str) -> Result<(), ParsingError> {
+    if name.starts_with('[') {
+        let mut graphemes = name.graphemes(true).peekable();
+        if JvmType::parse(&mut graphemes).is_some() && graphemes.next().is_none() {
+            return Ok(());
+        }
+        return Err(invalid_name(name, "class"));
+    }
+    for segment in name.split('/') {
+        if segment.is_empty() || segment.contains(['.', ';', '[']) {
+            return Err(invalid_name(name, "class"));
+        }
+    }
+    Ok(())
+}
+
+/// The names of fields and methods are unqualified (JVMS §4.2.2)
+fn check_unqualified_name(name: &str, kind: &'static str) -> Result<(), ParsingError> {
+    if name.is_empty() || name.contains(['.', ';', '[', '/']) {
+        return Err(invalid_name(name, kind));
+    }
+    Ok(())
+}
+
+fn invalid_name(name: &str, kind: &'static str) -> ParsingError {
+    ParsingError::InvalidName {
+        kind,
+        name: name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        class_parser::{self, ClassData, ParsingError},
+        model::constant_pool::{
+            BootstrapMethod, ConstantPool, ConstantPoolEntry, ConstantPoolIndex, FieldReference,
+            InterfaceMethodReference, MethodReference, ReferenceKind,
+        },
+    };
+
+    use super::{check_class, check_constant_pool};
+
+    fn utf8(value: &str) -> ConstantPoolEntry {
+        ConstantPoolEntry::Utf8(value.to_string())
+    }
+
+    fn index(index: u16) -> ConstantPoolIndex {
+        ConstantPoolIndex::from(index)
+    }
+
+    /// A constant pool with a class (1), a field (3) and a method reference (4) to which the entries are appended
+    fn constant_pool(entries: &[ConstantPoolEntry]) -> ConstantPool {
+        let mut pool = vec![
+            ConstantPoolEntry::Class { name: index(2) },
+            utf8("p/A"),
+            ConstantPoolEntry::FieldReference(FieldReference::Unresolved {
+                class: index(1),
+                name_and_type: index(5),
+            }),
+            ConstantPoolEntry::MethodReference(MethodReference::Unresolved {
+                class: index(1),
+                name_and_type: index(5),
+            }),
+            ConstantPoolEntry::NameAndType {
+                name: index(2),
+                ty: index(2),
+            },
+        ];
+        pool.extend_from_slice(entries);
+        ConstantPool::new(pool)
+    }
+
+    /// Checks the constant pool and returns the entry and index of the invalid reference
+    fn invalid_reference(entries: &[ConstantPoolEntry], major_version: u16) -> (u16, u16) {
+        match check_constant_pool(&constant_pool(entries), major_version) {
+            Err(ParsingError::InvalidConstantReference { entry, index, .. }) => (entry, index),
+            result => panic!("not rejected: {:?}", result),
+        }
+    }
+
+    fn assert_invalid_name(result: Result<(), ParsingError>, expected_kind: &str, expected: &str) {
+        match result {
+            Err(ParsingError::InvalidName { kind, name }) => {
+                assert_eq!(kind, expected_kind);
+                assert_eq!(name, expected);
+            }
+            result => panic!("'{}' not rejected: {:?}", expected, result),
+        }
+    }
+
+    #[test]
+    fn accepts_a_valid_constant_pool() {
+        check_constant_pool(&constant_pool(&[]), 52).unwrap();
+    }
+
+    #[test]
+    fn rejects_entries_referring_to_entries_of_the_wrong_kind() {
+        let cases = [
+            ConstantPoolEntry::String { value: index(1) },
+            ConstantPoolEntry::MethodType {
+                descriptor: index(1),
+            },
+            ConstantPoolEntry::Class { name: index(1) },
+            ConstantPoolEntry::NameAndType {
+                name: index(2),
+                ty: index(1),
+            },
+            ConstantPoolEntry::FieldReference(FieldReference::Unresolved {
+                class: index(2),
+                name_and_type: index(5),
+            }),
+            ConstantPoolEntry::MethodReference(MethodReference::Unresolved {
+                class: index(1),
+                name_and_type: index(1),
+            }),
+            ConstantPoolEntry::InvokeDynamic {
+                bootstrap_method: 0,
+                name_and_type: index(2),
+            },
+        ];
+        for entry in &cases {
+            let invalid = invalid_reference(std::slice::from_ref(entry), 52);
+            assert_eq!(invalid.0, 6, "{:?}", entry);
+        }
+        // References past the end of the constant pool
+        let invalid = invalid_reference(&[ConstantPoolEntry::String { value: index(7) }], 52);
+        assert_eq!(invalid, (6, 7));
+    }
+
+    #[test]
+    fn rejects_method_handles_referring_to_the_wrong_kind_of_member() {
+        let handle = |kind, reference| ConstantPoolEntry::MethodHandle {
+            kind,
+            reference: index(reference),
+        };
+        assert_eq!(
+            invalid_reference(&[handle(ReferenceKind::GetField, 4)], 52),
+            (6, 4)
+        );
+        assert_eq!(
+            invalid_reference(&[handle(ReferenceKind::InvokeVirtual, 3)], 52),
+            (6, 3)
+        );
+        assert_eq!(
+            invalid_reference(&[handle(ReferenceKind::InvokeInterface, 4)], 52),
+            (6, 4)
+        );
+
+        // Static handles only refer to interface methods since Java 8
+        let interface_method =
+            ConstantPoolEntry::InterfaceMethodReference(InterfaceMethodReference::Unresolved {
+                class: index(1),
+                name_and_type: index(5),
+            });
+        let entries = [interface_method, handle(ReferenceKind::InvokeStatic, 6)];
+        check_constant_pool(&constant_pool(&entries), 52).unwrap();
+        assert_eq!(invalid_reference(&entries, 51), (7, 6));
+    }
+
+    #[test]
+    fn rejects_malformed_class_names() {
+        for name in ["", "p//A", "/p/A", "p.A", "p/A;", "p[/A", "[", "[X", "[II"] {
+            let entries = [utf8(name), ConstantPoolEntry::Class { name: index(6) }];
+            assert_invalid_name(
+                check_constant_pool(&constant_pool(&entries), 52),
+                "class",
+                name,
+            );
+        }
+        let entries = [
+            utf8("[[Ljava/lang/String;"),
+            ConstantPoolEntry::Class { name: index(6) },
+        ];
+        check_constant_pool(&constant_pool(&entries), 52).unwrap();
+    }
+
+    /// Constants.class, which has a static field and methods
+    fn parse_constants() -> (ClassData, ConstantPool) {
+        let bytes = include_bytes!("../../tests/classes/Constants.class");
+        let (_, data, constant_pool) = class_parser::parse(bytes).unwrap();
+        (data, constant_pool)
+    }
+
+    /// Checks the class and returns what refers to the wrong kind of entry
+    fn invalid_class_reference(data: &ClassData, constant_pool: &ConstantPool) -> String {
+        match check_class(data, constant_pool) {
+            Err(ParsingError::InvalidClassReference { referenced_by, .. }) => referenced_by,
+            result => panic!("not rejected: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn rejects_class_references_to_entries_of_the_wrong_kind() {
+        let (data, constant_pool) = parse_constants();
+        check_class(&data, &constant_pool).unwrap();
+        // The name of the field, which isn't a Class entry
+        let not_a_class = constant_pool
+            .entries()
+            .iter()
+            .position(|entry| matches!(entry, ConstantPoolEntry::Utf8(value) if *value == data.static_fields[0].name))
+            .map(|position| index(position as u16 + 1))
+            .unwrap();
+
+        let mut invalid = parse_constants().0;
+        invalid.this_class = not_a_class;
+        assert_eq!(
+            invalid_class_reference(&invalid, &constant_pool),
+            "this_class"
+        );
+        let mut invalid = parse_constants().0;
+        invalid.super_class = not_a_class;
+        assert_eq!(
+            invalid_class_reference(&invalid, &constant_pool),
+            "super_class"
+        );
+        let mut invalid = parse_constants().0;
+        invalid.interfaces.push(not_a_class);
+        assert_eq!(
+            invalid_class_reference(&invalid, &constant_pool),
+            "interfaces"
+        );
+        let mut invalid = parse_constants().0;
+        invalid.bootstrap_methods.push(BootstrapMethod {
+            method_handle: data.this_class,
+            arguments: Vec::new(),
+        });
+        assert_eq!(
+            invalid_class_reference(&invalid, &constant_pool),
+            "bootstrap method 0"
+        );
+    }
+
+    #[test]
+    fn rejects_call_sites_without_bootstrap_method() {
+        let (data, constant_pool) = parse_constants();
+        let name_and_type = constant_pool
+            .entries()
+            .iter()
+            .position(|entry| matches!(entry, ConstantPoolEntry::NameAndType { .. }))
+            .unwrap();
+        let mut entries = constant_pool.entries().to_vec();
+        entries.push(ConstantPoolEntry::InvokeDynamic {
+            bootstrap_method: 0,
+            name_and_type: index(name_and_type as u16 + 1),
+        });
+        let result = check_class(&data, &ConstantPool::new(entries));
+        assert!(matches!(
+            result,
+            Err(ParsingError::MissingBootstrapMethod(0))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_member_names() {
+        for name in ["", "a.b", "a;b", "a[b", "a/b"] {
+            let (mut data, constant_pool) = parse_constants();
+            data.static_fields[0].name = name.to_string();
+            assert_invalid_name(check_class(&data, &constant_pool), "field", name);
+        }
+        for name in ["", "a.b", "a/b", "<cinit>", "a>"] {
+            let (mut data, constant_pool) = parse_constants();
+            data.static_methods[0].name = name.to_string();
+            assert_invalid_name(check_class(&data, &constant_pool), "method", name);
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+
+use crate::model::constant_pool::{
+    ConstantPool, ConstantPoolEntry, ConstantPoolIndex, FieldReference, InterfaceMethodReference,
+    MethodReference,
+};
+
+use super::{Output, WriteError};
+
+const MAX_ENTRIES: usize = u16::MAX as usize - 1; // The count in the class file is the number of entries plus one
+
+/// The identity of a constant, floating point values are compared by their bits
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Constant {
+    Utf8(String),
+    Integer(i32),
+    Float(u32),
+    Long(i64),
+    Double(u64),
+    Class(ConstantPoolIndex),
+    String(ConstantPoolIndex),
+    NameAndType(ConstantPoolIndex, ConstantPoolIndex),
+    Field(ConstantPoolIndex, ConstantPoolIndex),
+    Method(ConstantPoolIndex, ConstantPoolIndex),
+    InterfaceMethod(ConstantPoolIndex, ConstantPoolIndex),
+    MethodType(ConstantPoolIndex),
+}
+
+impl Constant {
+    /// The key of the entry, None for entries that are never shared (e.g. method handles)
+    fn of(entry: &ConstantPoolEntry) -> Option<Self> {
+        Some(match entry {
+            ConstantPoolEntry::Utf8(value) => Self::Utf8(value.clone()),
+            ConstantPoolEntry::Integer(value) => Self::Integer(*value),
+            ConstantPoolEntry::Float(value) => Self::Float(value.to_bits()),
+            ConstantPoolEntry::Long(value) => Self::Long(*value),
+            ConstantPoolEntry::Double(value) => Self::Double(value.to_bits()),
+            ConstantPoolEntry::Class { name } => Self::Class(*name),
+            ConstantPoolEntry::String { value } => Self::String(*value),
+            ConstantPoolEntry::NameAndType { name, ty } => Self::NameAndType(*name, *ty),
+            ConstantPoolEntry::FieldReference(FieldReference::Unresolved {
+                class,
+                name_and_type,
+            }) => Self::Field(*class, *name_and_type),
+            ConstantPoolEntry::MethodReference(MethodReference::Unresolved {
+                class,
+                name_and_type,
+            }) => Self::Method(*class, *name_and_type),
+            ConstantPoolEntry::InterfaceMethodReference(InterfaceMethodReference::Unresolved {
+                class,
+                name_and_type,
+            }) => Self::InterfaceMethod(*class, *name_and_type),
+            ConstantPoolEntry::MethodType { descriptor } => Self::MethodType(*descriptor),
+            _ => return None,
+        })
+    }
+}
+
+/// The constant pool of a class that is being written. Adding a constant that is already in the pool returns the
+/// existing entry.
+pub struct ConstantPoolBuilder {
+    entries: Vec<ConstantPoolEntry>,
+    indices: HashMap<Constant, ConstantPoolIndex>,
+}
+
+impl Default for ConstantPoolBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstantPoolBuilder {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Starts with the entries of a parsed constant pool, so that the code of its class keeps its meaning. The entries
+    /// keep their symbolic references when they are resolved, the check only guards against entries built by hand.
+    pub fn from_constant_pool(constant_pool: &ConstantPool) -> Result<Self, WriteError> {
+        let mut builder = Self::new();
+        for (position, entry) in constant_pool.entries().iter().enumerate() {
+            let index = ConstantPoolIndex::from(position as u16 + 1);
+            if is_resolved(entry) {
+                return Err(WriteError::ResolvedConstant(index));
+            }
+            if let Some(constant) = Constant::of(entry) {
+                builder.indices.entry(constant).or_insert(index);
+            }
+            builder.entries.push(entry.clone());
+        }
+        Ok(builder)
+    }
+
+    /// Adds the entry unless an equal one exists, longs and doubles take up two entries
+    pub fn add(&mut self, entry: ConstantPoolEntry) -> Result<ConstantPoolIndex, WriteError> {
+        let index = ConstantPoolIndex::from(self.entries.len() as u16 + 1);
+        if is_resolved(&entry) {
+            return Err(WriteError::ResolvedConstant(index));
+        }
+        let constant = Constant::of(&entry);
+        if let Some(index) = constant
+            .as_ref()
+            .and_then(|constant| self.indices.get(constant))
+        {
+            return Ok(*index);
+        }
+        let wide = matches!(
+            entry,
+            ConstantPoolEntry::Long(_) | ConstantPoolEntry::Double(_)
+        );
+        let length = self.entries.len() + 1 + wide as usize;
+        if length > MAX_ENTRIES {
+            return Err(WriteError::TooManyConstants(length));
+        }
+        self.entries.push(entry);
+        if wide {
+            self.entries.push(ConstantPoolEntry::Empty);
+        }
+        if let Some(constant) = constant {
+            self.indices.insert(constant, index);
+        }
+        Ok(index)
+    }
+
+    pub fn utf8(&mut self, value: &str) -> Result<ConstantPoolIndex, WriteError> {
+        self.add(ConstantPoolEntry::Utf8(value.to_string()))
+    }
+
+    pub fn integer(&mut self, value: i32) -> Result<ConstantPoolIndex, WriteError> {
+        self.add(ConstantPoolEntry::Integer(value))
+    }
+
+    pub fn float(&mut self, value: f32) -> Result<ConstantPoolIndex, WriteError> {
+        self.add(ConstantPoolEntry::Float(value))
+    }
+
+    pub fn long(&mut self, value: i64) -> Result<ConstantPoolIndex, WriteError> {
+        self.add(ConstantPoolEntry::Long(value))
+    }
+
+    pub fn double(&mut self, value: f64) -> Result<ConstantPoolIndex, WriteError> {
+        self.add(ConstantPoolEntry::Double(value))
+    }
+
+    /// The class (or array type) with the name in internal form
+    pub fn class(&mut self, name: &str) -> Result<ConstantPoolIndex, WriteError> {
+        let name = self.utf8(name)?;
+        self.add(ConstantPoolEntry::Class { name })
+    }
+
+    pub fn string(&mut self, value: &str) -> Result<ConstantPoolIndex, WriteError> {
+        let value = self.utf8(value)?;
+        self.add(ConstantPoolEntry::String { value })
+    }
+
+    pub fn name_and_type(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<ConstantPoolIndex, WriteError> {
+        let name = self.utf8(name)?;
+        let ty = self.utf8(descriptor)?;
+        self.add(ConstantPoolEntry::NameAndType { name, ty })
+    }
+
+    pub fn field_reference(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<ConstantPoolIndex, WriteError> {
+        let class = self.class(class)?;
+        let name_and_type = self.name_and_type(name, descriptor)?;
+        self.add(ConstantPoolEntry::FieldReference(
+            FieldReference::Unresolved {
+                class,
+                name_and_type,
+            },
+        ))
+    }
+
+    pub fn method_reference(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<ConstantPoolIndex, WriteError> {
+        let class = self.class(class)?;
+        let name_and_type = self.name_and_type(name, descriptor)?;
+        self.add(ConstantPoolEntry::MethodReference(
+            MethodReference::Unresolved {
+                class,
+                name_and_type,
+            },
+        ))
+    }
+
+    pub fn interface_method_reference(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<ConstantPoolIndex, WriteError> {
+        let class = self.class(class)?;
+        let name_and_type = self.name_and_type(name, descriptor)?;
+        self.add(ConstantPoolEntry::InterfaceMethodReference(
+            InterfaceMethodReference::Unresolved {
+                class,
+                name_and_type,
+            },
+        ))
+    }
+
+    pub fn method_type(&mut self, descriptor: &str) -> Result<ConstantPoolIndex, WriteError> {
+        let descriptor = self.utf8(descriptor)?;
+        self.add(ConstantPoolEntry::MethodType { descriptor })
+    }
+
+    /// Writes constant_pool_count and the entries (JVMS §4.4)
+    pub(super) fn write(&self, output: &mut Output) {
+        output.u16(self.entries.len() as u16 + 1);
+        for entry in &self.entries {
+            match entry {
+                ConstantPoolEntry::Utf8(value) => {
+                    let bytes = modified_utf8(value);
+                    output.u8(1);
+                    output.u16(bytes.len() as u16);
+                    output.bytes(&bytes);
+                }
+                ConstantPoolEntry::Integer(value) => {
+                    output.u8(3);
+                    output.bytes(&value.to_be_bytes());
+                }
+                ConstantPoolEntry::Float(value) => {
+                    output.u8(4);
+                    output.bytes(&value.to_be_bytes());
+                }
+                ConstantPoolEntry::Long(value) => {
+                    output.u8(5);
+                    output.bytes(&value.to_be_bytes());
+                }
+                ConstantPoolEntry::Double(value) => {
+                    output.u8(6);
+                    output.bytes(&value.to_be_bytes());
+                }
+                ConstantPoolEntry::Class { name } => {
+                    output.u8(7);
+                    output.index(*name);
+                }
+                ConstantPoolEntry::String { value } => {
+                    output.u8(8);
+                    output.index(*value);
+                }
+                ConstantPoolEntry::FieldReference(reference) => {
+                    let FieldReference::Unresolved {
+                        class,
+                        name_and_type,
+                    } = reference
+                    else {
+                        unreachable!("resolved references are never added")
+                    };
+                    output.u8(9);
+                    output.index(*class);
+                    output.index(*name_and_type);
+                }
+                ConstantPoolEntry::MethodReference(reference) => {
+                    let MethodReference::Unresolved {
+                        class,
+                        name_and_type,
+                    } = reference
+                    else {
+                        unreachable!("resolved references are never added")
+                    };
+                    output.u8(10);
+                    output.index(*class);
+                    output.index(*name_and_type);
+                }
+                ConstantPoolEntry::InterfaceMethodReference(reference) => {
+                    let InterfaceMethodReference::Unresolved {
+                        class,
+                        name_and_type,
+                    } = reference
+                    else {
+                        unreachable!("resolved references are never added")
+                    };
+                    output.u8(11);
+                    output.index(*class);
+                    output.index(*name_and_type);
+                }
+                ConstantPoolEntry::NameAndType { name, ty } => {
+                    output.u8(12);
+                    output.index(*name);
+                    output.index(*ty);
+                }
+                ConstantPoolEntry::MethodHandle { kind, reference } => {
+                    output.u8(15);
+                    output.u8(kind.to_u8());
+                    output.index(*reference);
+                }
+                ConstantPoolEntry::MethodType { descriptor } => {
+                    output.u8(16);
+                    output.index(*descriptor);
+                }
+                ConstantPoolEntry::Dynamic {
+                    bootstrap_method,
+                    name_and_type,
+                } => {
+                    output.u8(17);
+                    output.u16(*bootstrap_method);
+                    output.index(*name_and_type);
+                }
+                ConstantPoolEntry::InvokeDynamic {
+                    bootstrap_method,
+                    name_and_type,
+                } => {
+                    output.u8(18);
+                    output.u16(*bootstrap_method);
+                    output.index(*name_and_type);
+                }
+                ConstantPoolEntry::Module { name } => {
+                    output.u8(19);
+                    output.index(*name);
+                }
+                ConstantPoolEntry::Package { name } => {
+                    output.u8(20);
+                    output.index(*name);
+                }
+                // The second slot of a long or double isn't written
+                ConstantPoolEntry::Empty => {}
+            }
+        }
+    }
+}
+
+fn is_resolved(entry: &ConstantPoolEntry) -> bool {
+    matches!(
+        entry,
+        ConstantPoolEntry::FieldReference(FieldReference::Resolved { .. })
+            | ConstantPoolEntry::MethodReference(
+                MethodReference::ResolvedStatic { .. } | MethodReference::ResolvedVirtual { .. }
+            )
+            | ConstantPoolEntry::InterfaceMethodReference(
+                InterfaceMethodReference::ResolvedInterface { .. }
+                    | InterfaceMethodReference::ResolvedVirtual { .. }
+            )
+    )
+}
+
+/// Encodes the string in the modified UTF-8 of class files (JVMS §4.4.7): the null character takes two bytes and
+/// supplementary characters are encoded as surrogate pairs
+fn modified_utf8(value: &str) -> Vec<u8> {
+    if !value.chars().any(|c| c == '\0' || c as u32 > 0xFFFF) {
+        return value.as_bytes().to_vec();
+    }
+    let mut bytes = Vec::with_capacity(value.len() + 8);
+    for unit in value.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    bytes
+}
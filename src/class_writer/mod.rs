@@ -0,0 +1,558 @@
+mod constant_pool;
+
+pub use constant_pool::ConstantPoolBuilder;
+
+use crate::{
+    class_parser::attribute,
+    model::{
+        class_file::{AttributeContent, AttributeInfo, ClassFile, CodeAttribute, MemberInfo},
+        constant_pool::ConstantPoolIndex,
+        method::{ExceptionTableEntry, LineNumber, LocalVariable, MethodCode, MethodDescriptor},
+    },
+};
+
+// Serializes classes into class files (JVMS §4), the counterpart of the class parser for code that generates classes
+// at runtime (e.g. proxies, adapters of lambdas and transformed classes) and defines them like loaded ones.
+// The parsed model drops most of the attributes and can't be written back as a whole, but a class can be rewritten
+// by starting from its constant pool, so that its code keeps referring to the same entries, and by adding the parsed
+// methods with MethodDefinition::from_descriptor. A class file that has been parsed with
+// class_parser::parse_retaining can be written back unchanged with write_class_file instead. The writer doesn't compute max_stack, max_locals or stack map
+// frames, the code has to come with them.
+
+const DEFAULT_MAJOR_VERSION: u16 = 52; // Java 8, the last version that doesn't require stack map frames
+
+pub struct FieldDefinition {
+    pub access_flags: u16,
+    pub name: String,
+    pub descriptor: String,
+    pub constant_value: Option<ConstantPoolIndex>, // The initial value of a static field
+    pub signature: Option<String>,
+}
+
+pub struct MethodDefinition {
+    pub access_flags: u16,
+    pub name: String,
+    pub descriptor: String,
+    pub code: Option<CodeDefinition>, // None for native and abstract methods
+    pub signature: Option<String>,
+}
+
+/// The content of a Code attribute, the pcs and constant pool indices refer to the class that is being written
+pub struct CodeDefinition {
+    pub max_stack: usize,
+    pub max_locals: usize,
+    pub code: Vec<u8>,
+    pub exception_table: Vec<ExceptionTableEntry>,
+    pub line_numbers: Vec<LineNumber>,
+    pub local_variables: Vec<LocalVariable>, // Variables with a signature are also written to the type table
+}
+
+impl MethodDefinition {
+    /// The definition of a parsed method, whose code refers to the constant pool of its class. The model only keeps
+    /// the visibility, static, native and abstract flags and doesn't keep the generic signature.
+    pub fn from_descriptor(method: &MethodDescriptor) -> Self {
+        let code = match &method.code {
+            MethodCode::Bytecode(code) => Some(CodeDefinition {
+                max_stack: method.max_stack,
+                max_locals: method.max_locals,
+                code: code.clone(),
+                exception_table: method.exception_table.clone(),
+                line_numbers: method.line_numbers.clone(),
+                local_variables: method.local_variables.clone(),
+            }),
+            MethodCode::Native | MethodCode::Abstract => None,
+        };
+        Self {
+            access_flags: method.flags.bits(),
+            name: method.name.clone(),
+            descriptor: method.descriptor.clone(),
+            code,
+            signature: None,
+        }
+    }
+}
+
+pub struct ClassWriter {
+    minor_version: u16,
+    major_version: u16,
+    constant_pool: ConstantPoolBuilder,
+    access_flags: u16,
+    this_class: ConstantPoolIndex,
+    super_class: ConstantPoolIndex, // Zero for java/lang/Object
+    interfaces: Vec<ConstantPoolIndex>,
+    fields: Vec<FieldDefinition>,
+    methods: Vec<MethodDefinition>,
+    attributes: Vec<(String, Vec<u8>)>, // Further attributes of the class as (name, content)
+}
+
+impl ClassWriter {
+    /// Starts a class file (version 52.0) with the name and superclass in internal form. The classes are added to
+    /// the constant pool first.
+    pub fn new(
+        mut constant_pool: ConstantPoolBuilder,
+        name: &str,
+        super_class: Option<&str>,
+        access_flags: u16,
+    ) -> Result<Self, WriteError> {
+        let this_class = constant_pool.class(name)?;
+        let super_class = match super_class {
+            Some(super_class) => constant_pool.class(super_class)?,
+            None => ConstantPoolIndex::from(0),
+        };
+        Ok(Self {
+            minor_version: 0,
+            major_version: DEFAULT_MAJOR_VERSION,
+            constant_pool,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+        })
+    }
+
+    pub fn set_version(&mut self, minor_version: u16, major_version: u16) {
+        self.minor_version = minor_version;
+        self.major_version = major_version;
+    }
+
+    /// The constant pool, e.g. to add the constants that the code of the methods refers to
+    pub fn constant_pool(&mut self) -> &mut ConstantPoolBuilder {
+        &mut self.constant_pool
+    }
+
+    pub fn add_interface(&mut self, name: &str) -> Result<(), WriteError> {
+        let interface = self.constant_pool.class(name)?;
+        self.interfaces.push(interface);
+        Ok(())
+    }
+
+    pub fn add_field(&mut self, field: FieldDefinition) {
+        self.fields.push(field);
+    }
+
+    pub fn add_method(&mut self, method: MethodDefinition) {
+        self.methods.push(method);
+    }
+
+    /// Adds an attribute of the class that the writer doesn't model, e.g. SourceFile
+    pub fn add_attribute(&mut self, name: &str, content: Vec<u8>) {
+        self.attributes.push((name.to_string(), content));
+    }
+
+    /// Writes the class file. The names of the attributes are added to the constant pool on the way.
+    pub fn finish(mut self) -> Result<Vec<u8>, WriteError> {
+        // The members go first, because writing them completes the constant pool
+        let mut members = Output::new();
+        self.write_members(&mut members)?;
+
+        let mut output = Output::new();
+        output.u32(0xCAFEBABE);
+        output.u16(self.minor_version);
+        output.u16(self.major_version);
+        self.constant_pool.write(&mut output);
+        output.u16(self.access_flags);
+        output.index(self.this_class);
+        output.index(self.super_class);
+        output.count(self.interfaces.len(), "the number of interfaces")?;
+        for interface in &self.interfaces {
+            output.index(*interface);
+        }
+        output.bytes(&members.0);
+        Ok(output.0)
+    }
+
+    fn write_members(&mut self, output: &mut Output) -> Result<(), WriteError> {
+        output.count(self.fields.len(), "the number of fields")?;
+        for field in &self.fields {
+            output.u16(field.access_flags);
+            output.index(self.constant_pool.utf8(&field.name)?);
+            output.index(self.constant_pool.utf8(&field.descriptor)?);
+            let mut attributes = Attributes::new();
+            if let Some(constant_value) = field.constant_value {
+                let mut content = Output::new();
+                content.index(constant_value);
+                attributes.add(attribute::CONSTANT_VALUE, content, &mut self.constant_pool)?;
+            }
+            if let Some(signature) = &field.signature {
+                attributes.signature(signature, &mut self.constant_pool)?;
+            }
+            attributes.write(output)?;
+        }
+
+        output.count(self.methods.len(), "the number of methods")?;
+        for method in &self.methods {
+            output.u16(method.access_flags);
+            output.index(self.constant_pool.utf8(&method.name)?);
+            output.index(self.constant_pool.utf8(&method.descriptor)?);
+            let mut attributes = Attributes::new();
+            if let Some(code) = &method.code {
+                let content = write_code(&method.name, code, &mut self.constant_pool)?;
+                attributes.add(attribute::CODE, content, &mut self.constant_pool)?;
+            }
+            if let Some(signature) = &method.signature {
+                attributes.signature(signature, &mut self.constant_pool)?;
+            }
+            attributes.write(output)?;
+        }
+
+        let mut attributes = Attributes::new();
+        for (name, content) in &self.attributes {
+            attributes.add(name, Output(content.clone()), &mut self.constant_pool)?;
+        }
+        attributes.write(output)
+    }
+}
+
+/// Writes a class file from its retained contents (see class_parser::parse_retaining), byte for byte like the parsed
+/// one unless the contents have been modified
+pub fn write_class_file(file: &ClassFile) -> Result<Vec<u8>, WriteError> {
+    let contents = file.contents().ok_or(WriteError::NotRetained)?;
+    let mut output = Output::new();
+    output.u32(0xCAFEBABE);
+    output.u16(file.minor_version());
+    output.u16(file.major_version());
+    ConstantPoolBuilder::from_constant_pool(&contents.constant_pool)?.write(&mut output);
+    output.u16(contents.access_flags);
+    output.index(contents.this_class);
+    output.index(contents.super_class);
+    output.count(contents.interfaces.len(), "the number of interfaces")?;
+    for interface in &contents.interfaces {
+        output.index(*interface);
+    }
+    write_member_infos(&contents.fields, "the number of fields", &mut output)?;
+    write_member_infos(&contents.methods, "the number of methods", &mut output)?;
+    write_attribute_infos(&contents.attributes, &mut output)?;
+    Ok(output.0)
+}
+
+fn write_member_infos(
+    members: &[MemberInfo],
+    what: &'static str,
+    output: &mut Output,
+) -> Result<(), WriteError> {
+    output.count(members.len(), what)?;
+    for member in members {
+        output.u16(member.access_flags);
+        output.index(member.name);
+        output.index(member.descriptor);
+        write_attribute_infos(&member.attributes, output)?;
+    }
+    Ok(())
+}
+
+fn write_attribute_infos(
+    attributes: &[AttributeInfo],
+    output: &mut Output,
+) -> Result<(), WriteError> {
+    output.count(attributes.len(), "the number of attributes")?;
+    for attribute in attributes {
+        let content = match &attribute.content {
+            AttributeContent::Code(code) => write_code_attribute(code)?,
+            AttributeContent::Raw(info) => Output(info.clone()),
+        };
+        let length = u32::try_from(content.0.len()).map_err(|_| {
+            WriteError::LimitExceeded("the length of an attribute", content.0.len())
+        })?;
+        output.index(attribute.name);
+        output.u32(length);
+        output.bytes(&content.0);
+    }
+    Ok(())
+}
+
+fn write_code_attribute(code: &CodeAttribute) -> Result<Output, WriteError> {
+    let mut output = Output::new();
+    output.u16(code.max_stack);
+    output.u16(code.max_locals);
+    output.u32(code.code.len() as u32);
+    output.bytes(&code.code);
+    output.count(
+        code.exception_table.len(),
+        "the length of the exception table",
+    )?;
+    for handler in &code.exception_table {
+        output.u16(handler.start_pc);
+        output.u16(handler.end_pc);
+        output.u16(handler.handler_pc);
+        output.index(handler.catch_type);
+    }
+    write_attribute_infos(&code.attributes, &mut output)?;
+    Ok(output)
+}
+
+/// Writes the content of the Code attribute (JVMS §4.7.3)
+fn write_code(
+    method: &str,
+    code: &CodeDefinition,
+    constant_pool: &mut ConstantPoolBuilder,
+) -> Result<Output, WriteError> {
+    if code.code.is_empty() || code.code.len() > u16::MAX as usize {
+        return Err(WriteError::InvalidCodeLength(
+            method.to_string(),
+            code.code.len(),
+        ));
+    }
+    let mut output = Output::new();
+    output.u16(limit(code.max_stack, "max_stack")?);
+    output.u16(limit(code.max_locals, "max_locals")?);
+    output.u32(code.code.len() as u32);
+    output.bytes(&code.code);
+    output.count(
+        code.exception_table.len(),
+        "the length of the exception table",
+    )?;
+    for entry in &code.exception_table {
+        output.u16(limit(entry.start_pc, "a pc")?);
+        output.u16(limit(entry.end_pc, "a pc")?);
+        output.u16(limit(entry.handler_pc, "a pc")?);
+        output.index(entry.catch_type);
+    }
+
+    let mut attributes = Attributes::new();
+    if !code.line_numbers.is_empty() {
+        let mut content = Output::new();
+        content.count(code.line_numbers.len(), "the number of line numbers")?;
+        for entry in &code.line_numbers {
+            content.u16(limit(entry.start_pc, "a pc")?);
+            content.u16(limit(entry.line, "a line number")?);
+        }
+        attributes.add(attribute::LINE_NUMBER_TABLE, content, constant_pool)?;
+    }
+    if !code.local_variables.is_empty() {
+        let variables = code.local_variables.iter().collect::<Vec<_>>();
+        let content = write_local_variables(&variables, false, constant_pool)?;
+        attributes.add(attribute::LOCAL_VARIABLE_TABLE, content, constant_pool)?;
+    }
+    let generic_variables = code
+        .local_variables
+        .iter()
+        .filter(|variable| variable.signature.is_some())
+        .collect::<Vec<_>>();
+    if !generic_variables.is_empty() {
+        let content = write_local_variables(&generic_variables, true, constant_pool)?;
+        attributes.add(attribute::LOCAL_VARIABLE_TYPE_TABLE, content, constant_pool)?;
+    }
+    attributes.write(&mut output)?;
+    Ok(output)
+}
+
+/// Writes a LocalVariableTable or, with the signatures instead of the descriptors, a LocalVariableTypeTable
+fn write_local_variables(
+    variables: &[&LocalVariable],
+    signatures: bool,
+    constant_pool: &mut ConstantPoolBuilder,
+) -> Result<Output, WriteError> {
+    let mut output = Output::new();
+    output.count(variables.len(), "the number of local variables")?;
+    for variable in variables {
+        output.u16(limit(variable.start_pc, "a pc")?);
+        output.u16(limit(variable.length, "the length of a local variable")?);
+        output.index(constant_pool.utf8(&variable.name)?);
+        let ty = match (&variable.signature, signatures) {
+            (Some(signature), true) => signature,
+            _ => &variable.descriptor,
+        };
+        output.index(constant_pool.utf8(ty)?);
+        output.u16(limit(variable.slot, "the slot of a local variable")?);
+    }
+    Ok(output)
+}
+
+fn limit(value: usize, what: &'static str) -> Result<u16, WriteError> {
+    u16::try_from(value).map_err(|_| WriteError::LimitExceeded(what, value))
+}
+
+/// The attributes of a class, field, method or code
+struct Attributes {
+    count: usize,
+    output: Output,
+}
+
+impl Attributes {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            output: Output::new(),
+        }
+    }
+
+    fn add(
+        &mut self,
+        name: &str,
+        content: Output,
+        constant_pool: &mut ConstantPoolBuilder,
+    ) -> Result<(), WriteError> {
+        let length = u32::try_from(content.0.len()).map_err(|_| {
+            WriteError::LimitExceeded("the length of an attribute", content.0.len())
+        })?;
+        self.output.index(constant_pool.utf8(name)?);
+        self.output.u32(length);
+        self.output.bytes(&content.0);
+        self.count += 1;
+        Ok(())
+    }
+
+    fn signature(
+        &mut self,
+        signature: &str,
+        constant_pool: &mut ConstantPoolBuilder,
+    ) -> Result<(), WriteError> {
+        let mut content = Output::new();
+        content.index(constant_pool.utf8(signature)?);
+        self.add(attribute::SIGNATURE, content, constant_pool)
+    }
+
+    fn write(self, output: &mut Output) -> Result<(), WriteError> {
+        output.count(self.count, "the number of attributes")?;
+        output.bytes(&self.output.0);
+        Ok(())
+    }
+}
+
+/// The bytes that have been written so far, in the big-endian order of class files
+struct Output(Vec<u8>);
+
+impl Output {
+    fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend(value.to_be_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend(value.to_be_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend(bytes);
+    }
+
+    fn index(&mut self, index: ConstantPoolIndex) {
+        self.u16(index.as_u16());
+    }
+
+    /// Writes the length of a table as a u16
+    fn count(&mut self, count: usize, what: &'static str) -> Result<(), WriteError> {
+        self.u16(limit(count, what)?);
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum WriteError {
+    #[error("the constant pool would have {0} entries, but a class file can store at most 65534")]
+    TooManyConstants(usize),
+
+    #[error(
+        "the constant pool entry {0} has already been resolved and lost its symbolic reference"
+    )]
+    ResolvedConstant(ConstantPoolIndex),
+
+    #[error("invalid code length of method {0}: {1} bytes")]
+    InvalidCodeLength(String, usize),
+
+    #[error("{0} is {1}, but a class file can store at most 65535")]
+    LimitExceeded(&'static str, usize),
+
+    #[error("the contents of the class file have not been retained while parsing it")]
+    NotRetained,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{write_class_file, ClassWriter, ConstantPoolBuilder, MethodDefinition};
+    use crate::{class_parser, model::method::MethodDescriptor};
+
+    fn fixtures() -> Vec<(PathBuf, Vec<u8>)> {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/classes");
+        let mut fixtures: Vec<_> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "class")
+            })
+            .map(|path| {
+                let bytes = fs::read(&path).unwrap();
+                (path, bytes)
+            })
+            .collect();
+        fixtures.sort();
+        assert!(!fixtures.is_empty());
+        fixtures
+    }
+
+    fn method_summary(method: &MethodDescriptor) -> String {
+        format!(
+            "{} {} {:?} {:?} {} {} {:?} {:?} {:?}",
+            method.name,
+            method.descriptor,
+            method.flags,
+            method.code,
+            method.max_stack,
+            method.max_locals,
+            method.exception_table,
+            method.line_numbers,
+            method.local_variables
+        )
+    }
+
+    #[test]
+    fn retained_class_files_are_written_back_unchanged() {
+        for (path, bytes) in fixtures() {
+            let (file, _, _) = class_parser::parse_retaining(&bytes).unwrap();
+            let written = write_class_file(&file).unwrap();
+            assert!(written == bytes, "{} changed", path.display());
+        }
+    }
+
+    #[test]
+    fn rewritten_classes_parse_back_to_the_same_methods() {
+        for (path, bytes) in fixtures() {
+            let (_, data, constant_pool) = class_parser::parse(&bytes).unwrap();
+            let name = constant_pool.resolve_type(data.this_class).unwrap();
+            let super_class = data
+                .super_class
+                .is_valid()
+                .then(|| constant_pool.resolve_type(data.super_class).unwrap());
+            let constants = ConstantPoolBuilder::from_constant_pool(&constant_pool).unwrap();
+            let mut writer =
+                ClassWriter::new(constants, name, super_class, data.flags.bits()).unwrap();
+            for interface in &data.interfaces {
+                let interface = constant_pool.resolve_type(*interface).unwrap();
+                writer.add_interface(interface).unwrap();
+            }
+            let methods = || data.static_methods.iter().chain(&data.methods);
+            for method in methods() {
+                writer.add_method(MethodDefinition::from_descriptor(method));
+            }
+
+            let (_, rewritten, rewritten_pool) =
+                class_parser::parse(&writer.finish().unwrap()).unwrap();
+            assert_eq!(
+                rewritten_pool.resolve_type(rewritten.this_class).unwrap(),
+                name
+            );
+            assert_eq!(rewritten.interfaces.len(), data.interfaces.len());
+            let rewritten_methods: Vec<_> = rewritten
+                .static_methods
+                .iter()
+                .chain(&rewritten.methods)
+                .map(method_summary)
+                .collect();
+            let methods: Vec<_> = methods().map(method_summary).collect();
+            assert_eq!(rewritten_methods, methods, "{}", path.display());
+        }
+    }
+}
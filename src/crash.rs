@@ -0,0 +1,219 @@
+use std::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+use crate::{
+    interpreter::{frame, thread::ThreadState},
+    model::{class_library::ClassLibrary, heap::Heap, method::MethodTable},
+};
+
+// The VM state that is dumped on a crash. The pointers are only read while the process is going down.
+static HEAP: AtomicPtr<Heap> = AtomicPtr::new(std::ptr::null_mut());
+static CLASSES: AtomicPtr<ClassLibrary> = AtomicPtr::new(std::ptr::null_mut());
+static METHODS: AtomicPtr<MethodTable> = AtomicPtr::new(std::ptr::null_mut());
+
+static DUMPING: AtomicBool = AtomicBool::new(false);
+
+const FATAL_SIGNALS: [libc::c_int; 4] = [libc::SIGSEGV, libc::SIGBUS, libc::SIGILL, libc::SIGFPE];
+
+// The handlers run on their own stack, so that an overflow of the native stack can be reported as well
+const SIGNAL_STACK_SIZE: usize = 64 * 1024;
+
+/// Installs a panic hook and handlers for fatal signals that dump the state of the VM before aborting.
+/// The given VM structures must outlive all Java code that is executed afterwards.
+pub fn install_handler(heap: &Heap, classes: &ClassLibrary, methods: &MethodTable) {
+    HEAP.store(heap as *const Heap as *mut Heap, Ordering::Release);
+    CLASSES.store(
+        classes as *const ClassLibrary as *mut ClassLibrary,
+        Ordering::Release,
+    );
+    METHODS.store(
+        methods as *const MethodTable as *mut MethodTable,
+        Ordering::Release,
+    );
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        dump_vm_state();
+        std::process::abort();
+    }));
+
+    unsafe {
+        // The stack is never freed, the handlers stay installed until the process exits
+        let signal_stack = libc::stack_t {
+            ss_sp: Box::leak(vec![0u8; SIGNAL_STACK_SIZE].into_boxed_slice()).as_mut_ptr()
+                as *mut libc::c_void,
+            ss_flags: 0,
+            ss_size: SIGNAL_STACK_SIZE,
+        };
+        if libc::sigaltstack(&signal_stack, std::ptr::null_mut()) != 0 {
+            log::warn!("Can't install the signal stack, native stack overflows won't be reported");
+        }
+
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_signal as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESETHAND | libc::SA_ONSTACK;
+        for signal in FATAL_SIGNALS {
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+/// Forgets the VM structures before they are freed, crashes afterwards only report the signal or panic
+pub fn release_vm_state() {
+    HEAP.store(std::ptr::null_mut(), Ordering::Release);
+    CLASSES.store(std::ptr::null_mut(), Ordering::Release);
+    METHODS.store(std::ptr::null_mut(), Ordering::Release);
+}
+
+extern "C" fn handle_signal(signal: libc::c_int, _: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    // Only async-signal-safe functions may be called here: the signal may have interrupted malloc or a lock of stderr,
+    // so the output is formatted into a buffer on the stack and written with write(2)
+    let mut out = RawStderr::new();
+    let _ = writeln!(out, "VM crashed: received signal {}", signal);
+    out.flush();
+    dump_vm_state();
+    unsafe { libc::abort() };
+}
+
+/// Prints the Java stack of the current thread, the last executed bytecode and statistics about the heap
+/// and the loaded classes to stderr. It neither allocates nor locks, so that it can run in a signal handler.
+pub fn dump_vm_state() {
+    // A crash while dumping must not recurse
+    if DUMPING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let mut out = RawStderr::new();
+    let _ = write_vm_state(&mut out);
+    out.flush();
+}
+
+fn write_vm_state(out: &mut RawStderr) -> fmt::Result {
+    let classes = unsafe { CLASSES.load(Ordering::Acquire).as_ref() };
+    let methods = unsafe { METHODS.load(Ordering::Acquire).as_ref() };
+    let heap = unsafe { HEAP.load(Ordering::Acquire).as_ref() };
+
+    writeln!(out, "================ VM state dump ================")?;
+
+    // (class, method, pc, opcode) of the newest frame
+    let mut last_executed = None;
+    writeln!(
+        out,
+        "Java stack of the current thread ({}, newest first):",
+        ThreadState::current()
+    )?;
+    let mut result = Ok(());
+    frame::walk_frames(|record| {
+        if result.is_err() {
+            return;
+        }
+        result = match (classes, methods) {
+            (Some(classes), Some(methods)) => {
+                let data = methods.get_data(record.method());
+                let class_name = classes
+                    .resolve(data.owning_class)
+                    .name()
+                    .unwrap_or("<unknown>");
+                if last_executed.is_none() {
+                    last_executed = Some((
+                        class_name,
+                        data.name.as_str(),
+                        record.pc(),
+                        data.code.get(record.pc()).copied().unwrap_or_default(),
+                    ));
+                }
+                writeln!(
+                    out,
+                    "    at {}.{} (pc {})",
+                    class_name,
+                    data.name,
+                    record.pc()
+                )
+            }
+            _ => writeln!(
+                out,
+                "    at method #{} (pc {})",
+                usize::from(record.method()),
+                record.pc()
+            ),
+        };
+    });
+    result?;
+
+    match last_executed {
+        Some((class_name, method_name, pc, opcode)) => writeln!(
+            out,
+            "Last executed bytecode: {}.{} at pc {} (opcode {:#04x})",
+            class_name, method_name, pc, opcode
+        )?,
+        None => writeln!(out, "Last executed bytecode: <no interpreter frame active>")?,
+    }
+
+    if let Some(heap) = heap {
+        writeln!(
+            out,
+            "Heap: {} of {} bytes used",
+            heap.used(),
+            heap.capacity()
+        )?;
+    }
+    if let Some(classes) = classes {
+        writeln!(out, "Loaded classes: {}", classes.class_count())?;
+    }
+    if let Some(methods) = methods {
+        writeln!(out, "Loaded methods: {}", methods.method_count())?;
+    }
+
+    writeln!(out, "===============================================")
+}
+
+/// Writes to stderr with write(2) through a buffer on the stack, which is flushed at the end of every line
+struct RawStderr {
+    buffer: [u8; 256],
+    length: usize,
+}
+
+impl RawStderr {
+    fn new() -> Self {
+        Self {
+            buffer: [0; 256],
+            length: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        let mut written = 0;
+        while written < self.length {
+            let result = unsafe {
+                libc::write(
+                    libc::STDERR_FILENO,
+                    self.buffer[written..].as_ptr() as *const libc::c_void,
+                    self.length - written,
+                )
+            };
+            if result <= 0 {
+                break;
+            }
+            written += result as usize;
+        }
+        self.length = 0;
+    }
+}
+
+impl fmt::Write for RawStderr {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            if self.length == self.buffer.len() {
+                self.flush();
+            }
+            self.buffer[self.length] = byte;
+            self.length += 1;
+            if byte == b'\n' {
+                self.flush();
+            }
+        }
+        Ok(())
+    }
+}
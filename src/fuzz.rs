@@ -0,0 +1,196 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::{
+    class_parser::{attribute, parse_descriptor},
+    class_writer::{
+        ClassWriter, CodeDefinition, ConstantPoolBuilder, FieldDefinition, MethodDefinition,
+        WriteError,
+    },
+    interpreter::budget::ExecutionBudget,
+    jvm::{GuestException, Jvm},
+    model::{class_library::ClassLimits, stack, types::JvmType, value::JvmValue},
+    options::{ExecutionMode, VmOptions},
+    verifier::types,
+};
+
+// The entry point for fuzzers (cargo build --features fuzzing), which feed arbitrary bytes through the class parser,
+// the verifier and the interpreter. The input is the code of a static method, prefixed by three bytes for its
+// max_stack, its max_locals and its descriptor (see DESCRIPTORS). The harness wraps the code into a synthetic class
+// and defines it in a fresh VM, which runs the method in the checked interpreter (-XX:+CheckStackTags) with an
+// instruction and time budget, a small heap and a cap on the loaded classes.
+// The structural checks of the verifier don't cover the types of the operand stack, which the interpreter trusts, so
+// code that e.g. pops from an empty stack would crash the VM instead of revealing a bug. The harness therefore only
+// runs code that passes the type inference of the verifier (see verifier::types), which rejects everything that
+// depends on the class library. Any panic or crash in a run that hasn't been rejected (e.g. a stack tag mismatch) is
+// a bug of the VM.
+// -XX:FuzzMethod=<file> runs the harness once on the content of the file, e.g. to reproduce a crash.
+
+const CLASS_NAME: &str = "Fuzz";
+const METHOD_NAME: &str = "fuzz";
+const DESCRIPTORS: &[&str] = &["()V", "()I", "(I)I", "(II)I", "(J)J", "(FD)D", "(IJFD)F"];
+const HEADER_LENGTH: usize = 3;
+
+// The constant pool of the synthetic class, the code may refer to these entries
+const INT_CONSTANT: u16 = 8;
+const FLOAT_CONSTANT: u16 = 9;
+const LONG_CONSTANT: u16 = 10; // Takes two entries
+const DOUBLE_CONSTANT: u16 = 12; // Takes two entries
+const FIELD_REFERENCE: u16 = 17; // static int value
+
+const BUDGET: ExecutionBudget = ExecutionBudget {
+    instructions: Some(100_000),
+    time: Some(Duration::from_secs(1)),
+};
+const HEAP_SIZE: usize = 64 * 1024;
+const MAX_LOADED_CLASSES: usize = 16;
+
+/// What happened to an input
+#[derive(Debug)]
+pub enum FuzzOutcome {
+    Rejected(String), // The input isn't valid code or uses instructions the harness doesn't model
+    Returned(JvmValue, JvmType),
+    Threw(GuestException),
+}
+
+impl Display for FuzzOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "rejected: {}", reason),
+            Self::Returned(value, ty) => match ty {
+                JvmType::Void => write!(f, "returned"),
+                JvmType::Long => write!(f, "returned {}", value.long().0),
+                JvmType::Float => write!(f, "returned {}", value.float().0),
+                JvmType::Double => write!(f, "returned {}", value.double().0),
+                _ => write!(f, "returned {}", value.int().0),
+            },
+            Self::Threw(exception) => write!(f, "threw {}", exception),
+        }
+    }
+}
+
+/// Runs the input (see the module description) in a fresh VM
+pub fn fuzz_method(bytes: &[u8]) -> FuzzOutcome {
+    let Some((header, code)) = bytes.split_first_chunk::<HEADER_LENGTH>() else {
+        return FuzzOutcome::Rejected("the input is shorter than its header".to_string());
+    };
+    let [max_stack, max_locals, descriptor] = *header;
+    let descriptor = DESCRIPTORS[descriptor as usize % DESCRIPTORS.len()];
+    let (parameters, return_type) = parse_descriptor(descriptor).unwrap();
+
+    // The parser and the verifier see the code first, the type inference only has to handle verified code
+    let options = VmOptions {
+        execution_mode: ExecutionMode::Interpreted,
+        verify: true,
+        classpath: Vec::new(),
+        heap_size: HEAP_SIZE,
+        class_limits: ClassLimits {
+            classes: Some(MAX_LOADED_CLASSES),
+            constant_pool_bytes: None,
+        },
+        ..VmOptions::default()
+    };
+    stack::enable_tags();
+    let mut jvm = Jvm::new(options);
+    let class_file = match synthetic_class(descriptor, max_stack, max_locals, code) {
+        Ok(class_file) => class_file,
+        Err(err) => return FuzzOutcome::Rejected(err.to_string()),
+    };
+    let class = match jvm.define_class(CLASS_NAME, class_file) {
+        Ok(class) => jvm.classes().resolve(class),
+        Err(err) => return FuzzOutcome::Rejected(err.to_string()),
+    };
+    let (method, _) = class
+        .find_own_static_method(METHOD_NAME, descriptor)
+        .unwrap();
+    if let Err(err) = types::infer_types(class, jvm.methods().get_data(method)) {
+        return FuzzOutcome::Rejected(err.to_string());
+    }
+
+    let args = parameters
+        .iter()
+        .map(|ty| argument(*ty))
+        .collect::<Vec<_>>();
+    match jvm.call_static_with_budget(CLASS_NAME, METHOD_NAME, descriptor, &args, BUDGET) {
+        Ok(value) => FuzzOutcome::Returned(value, return_type),
+        Err(exception) => FuzzOutcome::Threw(exception),
+    }
+}
+
+/// The value that is passed for a parameter of the type
+fn argument(ty: JvmType) -> JvmValue {
+    match ty {
+        JvmType::Long => JvmValue::from(-3_000_000_000i64),
+        JvmType::Float => JvmValue::from(1.5f32),
+        JvmType::Double => JvmValue::from(-0.25f64),
+        _ => JvmValue::from(7),
+    }
+}
+
+/// A class file (version 52) with the static int field value and the static method with the code, fails if the
+/// code is empty
+fn synthetic_class(
+    descriptor: &str,
+    max_stack: u8,
+    max_locals: u8,
+    code: &[u8],
+) -> Result<Vec<u8>, WriteError> {
+    const ACC_PUBLIC_STATIC: u16 = 0x0009;
+    let mut class = ClassWriter::new(
+        ConstantPoolBuilder::new(),
+        CLASS_NAME,
+        Some("classes/Object"), // The root of the class hierarchy of this VM
+        0x0021,                 // ACC_PUBLIC | ACC_SUPER
+    )?;
+
+    // The entries that the code may refer to have fixed indices, so that inputs keep their meaning
+    let constant_pool = class.constant_pool();
+    constant_pool.utf8(METHOD_NAME).unwrap();
+    constant_pool.utf8(descriptor).unwrap();
+    constant_pool.utf8(attribute::CODE).unwrap();
+    let constants = [
+        constant_pool.integer(0x1234_5678),
+        constant_pool.float(-2.5),
+        constant_pool.long(i64::MIN),
+        constant_pool.double(f64::MAX),
+        constant_pool.field_reference(CLASS_NAME, "value", "I"),
+    ];
+    debug_assert_eq!(
+        constants.map(|index| index.unwrap().as_u16()),
+        [
+            INT_CONSTANT,
+            FLOAT_CONSTANT,
+            LONG_CONSTANT,
+            DOUBLE_CONSTANT,
+            FIELD_REFERENCE
+        ]
+    );
+    // The method itself
+    constant_pool
+        .method_reference(CLASS_NAME, METHOD_NAME, descriptor)
+        .unwrap();
+
+    class.add_field(FieldDefinition {
+        access_flags: ACC_PUBLIC_STATIC,
+        name: "value".to_string(),
+        descriptor: "I".to_string(),
+        constant_value: None,
+        signature: None,
+    });
+    // The code is truncated to the maximum length, so that the class can always be written
+    let code = &code[..code.len().min(u16::MAX as usize)];
+    class.add_method(MethodDefinition {
+        access_flags: ACC_PUBLIC_STATIC,
+        name: METHOD_NAME.to_string(),
+        descriptor: descriptor.to_string(),
+        code: Some(CodeDefinition {
+            max_stack: max_stack as usize,
+            max_locals: max_locals as usize,
+            code: code.to_vec(),
+            exception_table: Vec::new(),
+            line_numbers: Vec::new(),
+            local_variables: Vec::new(),
+        }),
+        signature: None,
+    });
+    class.finish()
+}
@@ -0,0 +1,85 @@
+use std::cell::Cell;
+
+use crate::bytecode;
+
+// The interpreter counts how often each conditional branch is taken, so that the JIT can place the successor that is
+// executed more often directly behind the branch (see jit::compile_method). The counts are kept next to the
+// quickened code: the pcs of the conditional branches are found once when the code is decoded, and each branch gets
+// two 16-bit counters. When a counter saturates, both counters of the branch are halved, which keeps their ratio and
+// lets the profile follow phase changes of the program.
+
+const MIN_SAMPLES: u32 = 32;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BranchCounts {
+    taken: u16,
+    not_taken: u16,
+}
+
+/// The taken/not-taken counts of the conditional branches of a method
+pub struct BranchProfile {
+    pcs: Box<[u32]>, // Sorted
+    counts: Box<[Cell<BranchCounts>]>,
+}
+
+impl BranchProfile {
+    pub fn new(code: &[u8]) -> Self {
+        let mut pcs = Vec::new();
+        let mut pc = 0;
+        while let Some(length) = bytecode::instruction_length(code, pc) {
+            if is_conditional_branch(code[pc]) {
+                pcs.push(pc as u32);
+            }
+            pc += length;
+        }
+        Self {
+            counts: pcs.iter().map(|_| Cell::default()).collect(),
+            pcs: pcs.into_boxed_slice(),
+        }
+    }
+
+    /// Counts an execution of the conditional branch at pc
+    pub fn record(&self, pc: usize, taken: bool) {
+        let Some(counts) = self.counts_at(pc) else {
+            return;
+        };
+        let mut value = counts.get();
+        let counter = if taken {
+            &mut value.taken
+        } else {
+            &mut value.not_taken
+        };
+        if *counter == u16::MAX {
+            value.taken /= 2;
+            value.not_taken /= 2;
+        }
+        if taken {
+            value.taken += 1;
+        } else {
+            value.not_taken += 1;
+        }
+        counts.set(value);
+    }
+
+    /// Whether the conditional branch at pc is taken more often than not, None if it hasn't been executed often
+    /// enough to tell
+    pub fn is_likely_taken(&self, pc: usize) -> Option<bool> {
+        let counts = self.counts_at(pc)?.get();
+        if (counts.taken as u32 + counts.not_taken as u32) < MIN_SAMPLES {
+            return None;
+        }
+        Some(counts.taken > counts.not_taken)
+    }
+
+    fn counts_at(&self, pc: usize) -> Option<&Cell<BranchCounts>> {
+        let index = self.pcs.binary_search(&(pc as u32)).ok()?;
+        Some(&self.counts[index])
+    }
+}
+
+pub fn is_conditional_branch(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        bytecode::IFEQ..=bytecode::IF_ACMPNE | bytecode::IFNULL | bytecode::IFNONNULL
+    )
+}
@@ -0,0 +1,334 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    io::{self, BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{
+    bytecode,
+    model::{
+        class_library::ClassLibrary,
+        method::{MethodData, MethodIndex, MethodTable},
+        stack::StackFrame,
+    },
+    verifier,
+};
+
+use super::frame;
+
+// Breakpoints stop the interpreter before it executes an instruction and hand control to the debugger
+// (-XX:Breakpoint=<class>.<method>[<descriptor>][@<pc>], the descriptor selects an overload and the pc defaults to the
+// start of the method). Setting a breakpoint patches the opcode of the instruction in the quickened code to the
+// reserved breakpoint opcode. The interpreter traps into the debugger when it dispatches the breakpoint and executes
+// the saved instruction once the debugger resumes, so the breakpoint stays set. Stepping sets temporary breakpoints at
+// the successors of the instruction, which are removed again by the next breakpoint that is hit in the method.
+// The breakpoints of a method are set when the interpreter enters it for the first time. Compiled code can't stop, so
+// the JIT doesn't compile methods with breakpoints, but a breakpoint in a method that has already been compiled is
+// never hit. Steps don't follow calls and returns.
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static BREAKPOINTS: Mutex<Vec<Breakpoint>> = Mutex::new(Vec::new());
+
+// The methods whose breakpoints have been set
+static APPLIED: Mutex<Option<HashSet<MethodIndex>>> = Mutex::new(None);
+
+thread_local! {
+    static DEBUGGER: RefCell<Option<Box<dyn Debugger>>> = RefCell::new(None);
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    class: String, // Internal form
+    method: String,
+    descriptor: Option<String>,
+    pc: usize,
+}
+
+impl Breakpoint {
+    /// Parses the value of -XX:Breakpoint, e.g. "java/lang/String.charAt(I)C@4"
+    pub fn parse(value: &str) -> Option<Self> {
+        let (method, pc) = match value.rsplit_once('@') {
+            Some((method, pc)) => (method, pc.parse().ok()?),
+            None => (value, 0),
+        };
+        let (method, descriptor) = match method.find('(') {
+            Some(start) => (&method[..start], Some(method[start..].to_string())),
+            None => (method, None),
+        };
+        let (class, method) = method.rsplit_once('.')?;
+        if class.is_empty() || method.is_empty() {
+            return None;
+        }
+        Some(Self {
+            class: class.replace('.', "/"),
+            method: method.to_string(),
+            descriptor,
+            pc,
+        })
+    }
+
+    fn matches(&self, method: &MethodData, classes: &ClassLibrary) -> bool {
+        self.method == method.name
+            && self
+                .descriptor
+                .as_ref()
+                .is_none_or(|descriptor| *descriptor == method.descriptor)
+            && classes.resolve(method.owning_class).name().ok() == Some(self.class.as_str())
+    }
+}
+
+/// What the interpreter does after the debugger returns from a breakpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resumption {
+    Continue,
+    Step, // Stops again at the next instruction of the method
+}
+
+/// The interpreter frame that has stopped at a breakpoint, the instruction at pc hasn't been executed yet
+pub struct Suspension<'a> {
+    pub index: MethodIndex,
+    pub method: &'a MethodData,
+    pub pc: usize,
+    pub stack: &'a StackFrame,
+    pub classes: &'a ClassLibrary,
+    pub methods: &'a MethodTable,
+}
+
+/// Gets control whenever the interpreter hits a breakpoint. The callbacks must not call back into the VM.
+pub trait Debugger {
+    fn breakpoint_hit(&mut self, suspension: &Suspension) -> Resumption;
+}
+
+pub fn install(breakpoints: Vec<Breakpoint>) {
+    ENABLED.store(!breakpoints.is_empty(), Ordering::Release);
+    *BREAKPOINTS.lock().unwrap() = breakpoints;
+}
+
+/// Adds a breakpoint while the program is running, it is set in all methods that haven't been compiled
+pub fn add(breakpoint: Breakpoint) {
+    BREAKPOINTS.lock().unwrap().push(breakpoint);
+    // The breakpoints of the methods that have already been entered are set again on their next invocation
+    APPLIED.lock().unwrap().take();
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Installs the debugger for the interpreter of the current thread (the console debugger by default) and returns the
+/// previously installed one
+pub fn install_debugger(debugger: Box<dyn Debugger>) -> Option<Box<dyn Debugger>> {
+    DEBUGGER.with(|current| current.borrow_mut().replace(debugger))
+}
+
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Whether the method stops at a breakpoint, i.e. must not be compiled
+pub fn has_breakpoints(method: &MethodData, classes: &ClassLibrary) -> bool {
+    method.quickened_code.has_breakpoints()
+        || BREAKPOINTS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|breakpoint| breakpoint.matches(method, classes))
+}
+
+/// Sets the breakpoints of the method if it is entered for the first time
+pub(super) fn apply(
+    index: MethodIndex,
+    method: &MethodData,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) {
+    if !APPLIED
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashSet::new)
+        .insert(index)
+    {
+        return;
+    }
+    for breakpoint in BREAKPOINTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|breakpoint| breakpoint.matches(method, classes))
+    {
+        if is_instruction_start(&method.code, breakpoint.pc) {
+            method
+                .quickened_code
+                .set_breakpoint(breakpoint.pc, &method.code, false);
+        } else {
+            log::warn!(
+                "Ignoring the breakpoint at pc {} of {}, which is not the start of an instruction",
+                breakpoint.pc,
+                frame::location(index, 0, classes, methods)
+            );
+        }
+    }
+}
+
+/// Called by the interpreter when it dispatches a breakpoint, before the instruction at pc is executed
+pub(super) fn hit(
+    index: MethodIndex,
+    method: &MethodData,
+    pc: usize,
+    stack: &StackFrame,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) {
+    method.quickened_code.clear_temporary_breakpoints();
+    let suspension = Suspension {
+        index,
+        method,
+        pc,
+        stack,
+        classes,
+        methods,
+    };
+    let resumption = DEBUGGER.with(|debugger| {
+        debugger
+            .borrow_mut()
+            .get_or_insert_with(|| Box::new(ConsoleDebugger))
+            .breakpoint_hit(&suspension)
+    });
+    if resumption == Resumption::Step {
+        let code = &method.code;
+        let mut successors = verifier::branch_targets(code, pc);
+        if verifier::falls_through(code[pc]) {
+            successors.push((pc + bytecode::instruction_length(code, pc).unwrap()) as i64);
+        }
+        for successor in successors {
+            if (successor as usize) < code.len() {
+                method
+                    .quickened_code
+                    .set_breakpoint(successor as usize, code, true);
+            }
+        }
+    }
+}
+
+fn is_instruction_start(code: &[u8], pc: usize) -> bool {
+    let mut start = 0;
+    while start < pc {
+        match bytecode::instruction_length(code, start) {
+            Some(length) => start += length,
+            None => return false,
+        }
+    }
+    start == pc && pc < code.len()
+}
+
+/// Reads commands from stdin while the interpreter is stopped
+pub struct ConsoleDebugger;
+
+impl ConsoleDebugger {
+    const HELP: &'static str = "Commands:
+    c, continue                 resume the program
+    s, step                     stop at the next instruction of the method
+    w, where                    print the Java stack
+    o, operands                 print the operand stack (bottom to top)
+    l, locals                   print the local variables
+    b, break <method>[@<pc>]    set a breakpoint, e.g. b java/lang/String.charAt(I)C@4
+    d, delete                   remove the breakpoint at the current instruction
+    q, quit                     terminate the VM
+    h, help                     print this help";
+
+    fn print_locals(suspension: &Suspension) {
+        let method = suspension.method;
+        for slot in 0..method.max_locals {
+            let value = suspension.stack.get_local(slot).as_int().0;
+            match method.local_variable(slot, suspension.pc) {
+                Some(variable) => eprintln!(
+                    "    {}: {} {} = {}",
+                    slot, variable.descriptor, variable.name, value
+                ),
+                None => eprintln!("    {}: {}", slot, value),
+            }
+        }
+    }
+
+    fn delete(suspension: &Suspension) {
+        let Suspension {
+            method,
+            pc,
+            classes,
+            ..
+        } = suspension;
+        method.quickened_code.clear_breakpoint(*pc);
+        // Otherwise the breakpoint would be set again in methods that are entered later on
+        BREAKPOINTS
+            .lock()
+            .unwrap()
+            .retain(|breakpoint| breakpoint.pc != *pc || !breakpoint.matches(method, classes));
+    }
+}
+
+impl Debugger for ConsoleDebugger {
+    fn breakpoint_hit(&mut self, suspension: &Suspension) -> Resumption {
+        let opcode = suspension.method.code[suspension.pc];
+        eprintln!(
+            "Breakpoint: {} ({})",
+            frame::location(
+                suspension.index,
+                suspension.pc,
+                suspension.classes,
+                suspension.methods
+            ),
+            bytecode::OPCODES
+                .iter()
+                .find(|(defined, _)| *defined == opcode)
+                .map_or_else(
+                    || format!("{:#04x}", opcode),
+                    |(_, name)| name.to_lowercase()
+                )
+        );
+        let stdin = io::stdin();
+        let mut line = String::new();
+        loop {
+            eprint!("(jvm) ");
+            io::stderr().flush().unwrap();
+            line.clear();
+            // The program just continues if there is no one to debug it
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                return Resumption::Continue;
+            }
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("c" | "continue"), _) => return Resumption::Continue,
+                (Some("s" | "step"), _) => return Resumption::Step,
+                (Some("w" | "where"), _) => {
+                    frame::print_stack_trace(suspension.classes, suspension.methods)
+                }
+                (Some("o" | "operands"), _) => {
+                    for value in suspension.stack.operands() {
+                        eprintln!("    {}", value.as_int().0);
+                    }
+                }
+                (Some("l" | "locals"), _) => Self::print_locals(suspension),
+                (Some("b" | "break"), Some(spec)) => match Breakpoint::parse(spec) {
+                    Some(breakpoint) => {
+                        add(breakpoint);
+                        // The current method is still running, so its breakpoints are set right away
+                        apply(
+                            suspension.index,
+                            suspension.method,
+                            suspension.classes,
+                            suspension.methods,
+                        );
+                    }
+                    None => eprintln!("Invalid breakpoint {}", spec),
+                },
+                (Some("d" | "delete"), _) => Self::delete(suspension),
+                (Some("q" | "quit"), _) => std::process::exit(1),
+                (Some("h" | "help"), _) => eprintln!("{}", Self::HELP),
+                (None, _) => {}
+                (Some(command), _) => eprintln!("Unknown command {}, try help", command),
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+// Limits of the execution of guest code that the embedder sets for a call (see Jvm::call_static_with_budget), e.g. to
+// run untrusted or buggy code. The interpreter charges every instruction it executes against the active budget and
+// stops with a VM error once it is exhausted. The error is not a Java exception, so neither handlers nor finally
+// blocks of the guest can intercept it.
+// Reading the clock is expensive, so the deadline is only checked every DEADLINE_CHECK_INTERVAL instructions.
+// Compiled code is only charged once per loop iteration at its safepoints (see jit::runtime), native methods are not
+// charged.
+
+const DEADLINE_CHECK_INTERVAL: u32 = 1024;
+
+thread_local! {
+    static ACTIVE: Cell<Option<ActiveBudget>> = const { Cell::new(None) };
+}
+
+/// The limits of a call. A limit that is None is unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecutionBudget {
+    pub instructions: Option<u64>, // Interpreted instructions
+    pub time: Option<Duration>,    // Wall-clock time
+}
+
+#[derive(Clone, Copy)]
+struct ActiveBudget {
+    budget: ExecutionBudget,
+    remaining_instructions: Option<u64>,
+    deadline: Option<Instant>,
+    until_deadline_check: u32,
+}
+
+impl ExecutionBudget {
+    /// Starts charging the instructions of the current thread against this budget until the returned guard is dropped
+    pub fn enter(self) -> BudgetGuard {
+        let active = ActiveBudget {
+            budget: self,
+            remaining_instructions: self.instructions,
+            deadline: self.time.map(|time| Instant::now() + time),
+            until_deadline_check: DEADLINE_CHECK_INTERVAL,
+        };
+        BudgetGuard {
+            previous: ACTIVE.with(|budget| budget.replace(Some(active))),
+        }
+    }
+}
+
+/// Restores the previous budget (usually none) when dropped
+pub struct BudgetGuard {
+    previous: Option<ActiveBudget>,
+}
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        ACTIVE.with(|budget| budget.set(self.previous));
+    }
+}
+
+/// Whether instructions are currently charged
+pub fn is_active() -> bool {
+    ACTIVE.with(|budget| budget.get().is_some())
+}
+
+/// Charges one instruction against the active budget
+pub fn charge() -> Result<(), BudgetExhausted> {
+    ACTIVE.with(|active| {
+        let Some(mut budget) = active.get() else {
+            return Ok(());
+        };
+        if let Some(remaining) = &mut budget.remaining_instructions {
+            if *remaining == 0 {
+                return Err(BudgetExhausted::Instructions(
+                    budget.budget.instructions.unwrap_or_default(),
+                ));
+            }
+            *remaining -= 1;
+        }
+        if let Some(deadline) = budget.deadline {
+            budget.until_deadline_check -= 1;
+            if budget.until_deadline_check == 0 {
+                budget.until_deadline_check = DEADLINE_CHECK_INTERVAL;
+                if Instant::now() >= deadline {
+                    // Every later instruction fails immediately
+                    budget.until_deadline_check = 1;
+                    active.set(Some(budget));
+                    return Err(BudgetExhausted::Time(
+                        budget.budget.time.unwrap_or_default(),
+                    ));
+                }
+            }
+        }
+        active.set(Some(budget));
+        Ok(())
+    })
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BudgetExhausted {
+    #[error("the budget of {0} instructions has been exhausted")]
+    Instructions(u64),
+
+    #[error("the time budget of {0:?} has been exhausted")]
+    Time(Duration),
+}
@@ -0,0 +1,232 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    bytecode,
+    jit::{self, CompilationError},
+    model::{
+        class_library::ClassLibrary,
+        heap::Heap,
+        method::{MethodImplementation, MethodIndex, MethodTable},
+        stack::StackPointer,
+        types::JvmType,
+        value::JvmValue,
+    },
+};
+
+thread_local! {
+    // The compiled versions of the checked methods, or None if the method can't be compiled or checked
+    static COMPILED_METHODS: RefCell<HashMap<MethodIndex, Option<MethodImplementation>>> =
+        RefCell::new(HashMap::new());
+}
+
+static CHECKED_CALLS: AtomicUsize = AtomicUsize::new(0);
+static DIVERGENCES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of checked calls and the number of calls for which the tiers diverged
+pub fn statistics() -> (usize, usize) {
+    (
+        CHECKED_CALLS.load(Ordering::Relaxed),
+        DIVERGENCES.load(Ordering::Relaxed),
+    )
+}
+
+/// Executes the call with both the JIT and the interpreter and reports any divergence between the two.
+/// The compiled code runs first on a copy of the arguments, then the interpreter executes the actual call. Only
+/// methods without side effects are checked (see has_side_effects), so that running them twice can't be observed.
+/// Returns None if the method isn't checked; the caller has to interpret it as usual in this case.
+pub fn check_call(
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> Option<JvmValue> {
+    let compiled = compiled_code(method_index, classes, methods)?;
+    let method = methods.get_data(method_index);
+
+    // Copy the arguments above the current stack pointer, so that the interpreter still finds the original ones
+    let arguments = method.argument_count;
+    let copied_stack = unsafe {
+        let top = stack.into_raw();
+        std::ptr::copy_nonoverlapping(top.offset(-(arguments as isize)), top, arguments);
+        stack.reserve_slots(arguments)
+    };
+
+    let compiled_value = unsafe {
+        super::call_address(compiled, method_index, copied_stack, heap, classes, methods)
+    };
+    let compiled_error = super::take_pending_error();
+
+    let interpreted_value = super::run_interpreted(method_index, stack, heap, classes, methods);
+    let interpreted_error = super::take_pending_error();
+
+    CHECKED_CALLS.fetch_add(1, Ordering::Relaxed);
+    let method_name = format!(
+        "{}.{}",
+        classes
+            .resolve(method.owning_class)
+            .name()
+            .unwrap_or("<unknown>"),
+        method.name
+    );
+    let compiled_result = normalize(compiled_value, method.return_type);
+    let interpreted_result = normalize(interpreted_value, method.return_type);
+    if compiled_error.is_some() || interpreted_error.is_some() {
+        if compiled_error.is_some() != interpreted_error.is_some() {
+            report_divergence(
+                &method_name,
+                &format!(
+                    "compiled code failed with {:?}, but the interpreter failed with {:?}",
+                    compiled_error, interpreted_error
+                ),
+            );
+        }
+    } else if compiled_result != interpreted_result {
+        report_divergence(
+            &method_name,
+            &format!(
+                "compiled code returned {:#x}, but the interpreter returned {:#x}",
+                compiled_result, interpreted_result
+            ),
+        );
+    }
+    if let Some(err) = interpreted_error {
+        super::set_pending_error(err);
+    }
+    Some(interpreted_value)
+}
+
+/// Normalizes a value of the given type to its significant bits
+pub fn normalize(value: JvmValue, ty: JvmType) -> i64 {
+    match ty {
+        JvmType::Void => 0,
+        JvmType::Byte | JvmType::Char | JvmType::Integer | JvmType::Short | JvmType::Boolean => {
+            value.int().0 as i64
+        }
+        JvmType::Float => value.float().0.to_bits() as i64,
+        JvmType::Long => value.long().0,
+        JvmType::Double => value.double().0.to_bits() as i64,
+        JvmType::Reference => value.reference().to_heap_index().as_u32() as i64,
+    }
+}
+
+fn compiled_code(
+    method_index: MethodIndex,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> Option<u64> {
+    let cached = COMPILED_METHODS.with(|compiled| {
+        compiled
+            .borrow()
+            .get(&method_index)
+            .map(|implementation| implementation.as_ref().map(code_address))
+    });
+    if let Some(address) = cached {
+        return address;
+    }
+
+    let code = &methods.get_data(method_index).code;
+    let implementation = if has_side_effects(code) {
+        None
+    } else {
+        compile(method_index, classes, methods)
+    };
+    let address = implementation.as_ref().map(code_address);
+    COMPILED_METHODS.with(|compiled| compiled.borrow_mut().insert(method_index, implementation));
+    address
+}
+
+/// Whether the code may change the state of the VM or of the world, so that executing it twice would be observable.
+/// Calls and allocations are counted as side effects, because the callee or the initializer of the class could have
+/// them. Throwing an exception isn't, because the interpreter records the stack trace of the exception again.
+fn has_side_effects(code: &[u8]) -> bool {
+    let mut pc = 0;
+    while pc < code.len() {
+        if matches!(
+            code[pc],
+            bytecode::IASTORE..=bytecode::SASTORE
+                | bytecode::PUTSTATIC
+                | bytecode::PUTFIELD
+                | bytecode::GETSTATIC // Initializes the class of the field
+                | bytecode::INVOKEVIRTUAL..=bytecode::NEWARRAY
+                | bytecode::ANEWARRAY
+                | bytecode::MONITORENTER
+                | bytecode::MONITOREXIT
+                | bytecode::MULTIANEWARRAY
+        ) {
+            return true;
+        }
+        match bytecode::instruction_length(code, pc) {
+            Some(length) => pc += length,
+            None => return true,
+        }
+    }
+    false
+}
+
+fn compile(
+    method_index: MethodIndex,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> Option<MethodImplementation> {
+    match jit::compile_method(method_index, classes, methods) {
+        Ok(implementation) => Some(implementation),
+        Err(CompilationError::UnsupportedOpcode(_)) => None,
+        Err(err) => {
+            log::warn!(
+                "Not checking method {}, compilation failed: {}",
+                methods.get_data(method_index).name,
+                err
+            );
+            None
+        }
+    }
+}
+
+fn code_address(implementation: &MethodImplementation) -> u64 {
+    match implementation {
+        MethodImplementation::Native(code, _) => **code as usize as u64,
+        MethodImplementation::Interpreted => {
+            unreachable!("the JIT never returns interpreted methods")
+        }
+    }
+}
+
+fn report_divergence(method_name: &str, description: &str) {
+    DIVERGENCES.fetch_add(1, Ordering::Relaxed);
+    log::error!("Tier divergence in {}: {}", method_name, description);
+}
+
+#[cfg(all(test, asm_entry))]
+mod tests {
+    use crate::{
+        options::{ExecutionMode, VmOptions},
+        testing,
+    };
+
+    fn jvm() -> crate::jvm::Jvm {
+        testing::jvm_with(VmOptions {
+            execution_mode: ExecutionMode::CrossCheck,
+            ..VmOptions::default()
+        })
+    }
+
+    #[test]
+    fn checks_methods_without_side_effects() {
+        let mut jvm = jvm();
+        let (checked, _) = super::statistics();
+        assert_eq!(testing::call_int(&mut jvm, "CrossChecks", "answer"), 42);
+        assert!(super::statistics().0 > checked);
+    }
+
+    #[test]
+    fn executes_side_effects_once() {
+        let mut jvm = jvm();
+        assert_eq!(testing::call_int(&mut jvm, "CrossChecks", "callIncrement"), 1);
+        assert_eq!(testing::call_int(&mut jvm, "CrossChecks", "callIncrement"), 2);
+    }
+}
@@ -0,0 +1,159 @@
+use std::{cell::Cell, fmt::Display, ptr};
+
+use crate::model::{class_library::ClassLibrary, method::MethodIndex, method::MethodTable};
+
+thread_local! {
+    static TOP_FRAME: Cell<*const FrameRecord> = const { Cell::new(ptr::null()) };
+}
+
+/// Bookkeeping for an active interpreter frame. The records form a linked list through the native stack
+/// (newest first), so that the Java stack of the current thread can be inspected at any time.
+pub struct FrameRecord {
+    method: MethodIndex,
+    pc: Cell<usize>,
+    caller: Cell<*const FrameRecord>,
+}
+
+impl FrameRecord {
+    pub fn new(method: MethodIndex) -> Self {
+        Self {
+            method,
+            pc: Cell::new(0),
+            caller: Cell::new(ptr::null()),
+        }
+    }
+
+    /// Makes this record the top frame of the current thread until the returned guard is dropped
+    pub fn enter(&self) -> ActiveFrame<'_> {
+        self.caller
+            .set(TOP_FRAME.with(|top| top.replace(self as *const FrameRecord)));
+        ActiveFrame { record: self }
+    }
+
+    #[inline(always)]
+    pub fn set_pc(&self, pc: usize) {
+        self.pc.set(pc);
+    }
+
+    pub fn method(&self) -> MethodIndex {
+        self.method
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc.get()
+    }
+}
+
+pub struct ActiveFrame<'a> {
+    record: &'a FrameRecord,
+}
+
+impl Drop for ActiveFrame<'_> {
+    fn drop(&mut self) {
+        TOP_FRAME.with(|top| top.set(self.record.caller.get()));
+    }
+}
+
+/// Calls the visitor for every active interpreter frame of the current thread, starting with the newest frame
+pub fn walk_frames<F: FnMut(&FrameRecord)>(mut visitor: F) {
+    let mut frame = TOP_FRAME.with(|top| top.get());
+    while !frame.is_null() {
+        // Safety: Records are only reachable while their ActiveFrame guard (which borrows them) is alive
+        let record = unsafe { &*frame };
+        visitor(record);
+        frame = record.caller.get();
+    }
+}
+
+/// The method and pc of the newest active interpreter frame of the current thread
+pub fn newest_frame() -> Option<(MethodIndex, usize)> {
+    let frame = TOP_FRAME.with(|top| top.get());
+    // Safety: see walk_frames
+    unsafe { frame.as_ref() }.map(|record| (record.method(), record.pc()))
+}
+
+/// A single frame of a captured Java stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackTraceElement {
+    pub class_name: String, // Internal form
+    pub method_name: String,
+    pub file_name: Option<String>, // None if the class has no SourceFile attribute
+    pub pc: usize,
+    pub line: Option<usize>, // None if the method has no LineNumberTable
+    pub is_native: bool,
+}
+
+/// Formatted like the frames of Java stack traces, e.g. "java.lang.String.charAt(String.java:1555)". The pc takes
+/// the place of the line if the line isn't known, native methods have neither.
+impl Display for StackTraceElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let class_name = self.class_name.replace('/', ".");
+        if self.is_native {
+            return write!(f, "{}.{}(Native Method)", class_name, self.method_name);
+        }
+        write!(
+            f,
+            "{}.{}({}",
+            class_name,
+            self.method_name,
+            self.file_name.as_deref().unwrap_or("Unknown Source")
+        )?;
+        match self.line {
+            Some(line) => write!(f, ":{})", line),
+            None => write!(f, ", pc {})", self.pc),
+        }
+    }
+}
+
+/// Captures the active interpreter frames of the current thread (newest first)
+pub fn capture_stack_trace(
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> Vec<StackTraceElement> {
+    let mut trace = Vec::new();
+    walk_frames(|record| {
+        let data = methods.get_data(record.method());
+        let class = classes.resolve(data.owning_class);
+        let line = data.line_for_pc(record.pc());
+        trace.push(StackTraceElement {
+            class_name: class.name().unwrap_or("<unknown>").to_string(),
+            method_name: data.name.clone(),
+            file_name: class.source_file().map(str::to_string),
+            pc: record.pc(),
+            line,
+            is_native: data.is_native,
+        });
+    });
+    trace
+}
+
+/// Describes the instruction at pc as "<class>.<method><descriptor> at pc <pc> (line <line>)"
+pub fn location(
+    method: MethodIndex,
+    pc: usize,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> String {
+    let data = methods.get_data(method);
+    format!(
+        "{}.{}{} at pc {}{}",
+        classes
+            .resolve(data.owning_class)
+            .name()
+            .unwrap_or("<unknown>"),
+        data.name,
+        data.descriptor,
+        pc,
+        data.line_for_pc(pc)
+            .map(|line| format!(" (line {})", line))
+            .unwrap_or_default()
+    )
+}
+
+/// Prints the Java stack of the current thread to stderr, e.g. from a native method while debugging
+pub fn print_stack_trace(classes: &ClassLibrary, methods: &MethodTable) {
+    eprintln!("Java stack of the current thread (newest first):");
+    for element in capture_stack_trace(classes, methods) {
+        eprintln!("    at {}", element);
+    }
+}
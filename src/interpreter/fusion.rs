@@ -0,0 +1,157 @@
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::bytecode;
+
+// Frequent instruction sequences are fused into superinstructions, so that the interpreter dispatches once per
+// sequence instead of once per instruction. Only the opcode of the first instruction is replaced, the following
+// instructions stay in place, so branches into the middle of a sequence still work. The fused handlers decode the
+// operands from the original code.
+// The counters show how often each superinstruction is created and executed, i.e. whether a fusion pays off.
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static STATISTICS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+static FUSED_SITES: [AtomicUsize; Fusion::COUNT] = [const { AtomicUsize::new(0) }; Fusion::COUNT];
+static EXECUTIONS: [AtomicUsize; Fusion::COUNT] = [const { AtomicUsize::new(0) }; Fusion::COUNT];
+static DEFERRALS: [AtomicUsize; Fusion::COUNT] = [const { AtomicUsize::new(0) }; Fusion::COUNT];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fusion {
+    LoadFieldOfThis, // aload_0, getfield
+    AddLocals,       // iload, iload, iadd
+    StoreConstant,   // iconst, istore
+}
+
+impl Fusion {
+    const COUNT: usize = 3;
+    const ALL: [Self; Self::COUNT] = [Self::LoadFieldOfThis, Self::AddLocals, Self::StoreConstant];
+
+    pub fn opcode(self) -> u8 {
+        match self {
+            Self::LoadFieldOfThis => bytecode::ALOAD_0_GETFIELD,
+            Self::AddLocals => bytecode::ILOAD_ILOAD_IADD,
+            Self::StoreConstant => bytecode::ICONST_ISTORE,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::LoadFieldOfThis => "aload_0 getfield",
+            Self::AddLocals => "iload iload iadd",
+            Self::StoreConstant => "iconst istore",
+        }
+    }
+
+    /// Checks whether the sequence starts at pc (which must be the start of an instruction)
+    fn matches(self, code: &[u8], pc: usize) -> bool {
+        match self {
+            Self::LoadFieldOfThis => {
+                code[pc] == bytecode::ALOAD_0 && code.get(pc + 1) == Some(&bytecode::GETFIELD)
+            }
+            Self::AddLocals => int_load(code, pc)
+                .and_then(|(_, first)| int_load(code, pc + first).map(|(_, second)| first + second))
+                .is_some_and(|length| code.get(pc + length) == Some(&bytecode::IADD)),
+            Self::StoreConstant => {
+                (bytecode::ICONST_M1..=bytecode::ICONST_5).contains(&code[pc])
+                    && int_store(code, pc + 1).is_some()
+            }
+        }
+    }
+}
+
+/// Disables the fusion of instructions for all methods that are loaded afterwards (-XX:-FuseInstructions)
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Counts the executions of the superinstructions (-XX:+PrintFusionStatistics)
+pub fn enable_statistics() {
+    STATISTICS_ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Replaces the first instruction of every fusable sequence in code by the corresponding superinstruction.
+/// The original code is used to find the sequences, so that superinstructions don't hide each other.
+pub fn fuse(original: &[u8], code: &[Cell<u8>]) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mut pc = 0;
+    while pc < original.len() {
+        let length = match bytecode::instruction_length(original, pc) {
+            Some(length) => length,
+            // Truncated code is reported by the interpreter once it gets there
+            None => return,
+        };
+        if let Some(fusion) = Fusion::ALL
+            .iter()
+            .find(|fusion| fusion.matches(original, pc))
+        {
+            code[pc].set(fusion.opcode());
+            FUSED_SITES[*fusion as usize].fetch_add(1, Ordering::Relaxed);
+        }
+        pc += length;
+    }
+}
+
+pub fn is_fused(opcode: u8) -> bool {
+    Fusion::ALL.iter().any(|fusion| fusion.opcode() == opcode)
+}
+
+/// Counts an execution of the superinstruction
+#[inline(always)]
+pub fn record_execution(fusion: Fusion) {
+    if STATISTICS_ENABLED.load(Ordering::Relaxed) {
+        EXECUTIONS[fusion as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts an execution of the superinstruction that had to fall back to executing its first instruction on its own,
+/// e.g. because a field hasn't been resolved yet
+#[inline(always)]
+pub fn record_deferral(fusion: Fusion) {
+    if STATISTICS_ENABLED.load(Ordering::Relaxed) {
+        DEFERRALS[fusion as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns (fusion, fused sites, executions, deferred executions) for every kind of superinstruction
+pub fn statistics() -> Vec<(Fusion, usize, usize, usize)> {
+    Fusion::ALL
+        .iter()
+        .map(|fusion| {
+            let index = *fusion as usize;
+            (
+                *fusion,
+                FUSED_SITES[index].load(Ordering::Relaxed),
+                EXECUTIONS[index].load(Ordering::Relaxed),
+                DEFERRALS[index].load(Ordering::Relaxed),
+            )
+        })
+        .collect()
+}
+
+/// Returns (local, instruction length) if the instruction at pc is iload or iload_<n>
+pub fn int_load(code: &[u8], pc: usize) -> Option<(usize, usize)> {
+    match *code.get(pc)? {
+        bytecode::ILOAD => Some((*code.get(pc + 1)? as usize, 2)),
+        opcode @ bytecode::ILOAD_0..=bytecode::ILOAD_3 => {
+            Some(((opcode - bytecode::ILOAD_0) as usize, 1))
+        }
+        _ => None,
+    }
+}
+
+/// Returns (local, instruction length) if the instruction at pc is istore or istore_<n>
+pub fn int_store(code: &[u8], pc: usize) -> Option<(usize, usize)> {
+    match *code.get(pc)? {
+        bytecode::ISTORE => Some((*code.get(pc + 1)? as usize, 2)),
+        opcode @ bytecode::ISTORE_0..=bytecode::ISTORE_3 => {
+            Some(((opcode - bytecode::ISTORE_0) as usize, 1))
+        }
+        _ => None,
+    }
+}
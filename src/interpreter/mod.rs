@@ -1,78 +1,146 @@
+pub mod branch_profile;
+pub mod breakpoint;
+pub mod budget;
+pub mod cross_check;
+pub mod frame;
+pub mod fusion;
+pub mod observer;
+pub mod quickening;
+pub mod tail_call;
+pub mod thread;
+pub mod watchpoint;
+
+#[cfg(test)]
+mod tests;
+
 use crate::{
     bytecode,
+    jit::{self, call_graph, CompilationError},
     model::{
-        class::{FieldError, MethodError},
-        class_library::ClassLibrary,
-        constant_pool::{ConstantPoolError, ConstantPoolIndex},
-        heap::Heap,
+        access_flags::MethodFlags,
+        array::{Array, ArrayType},
+        class::{Class, FieldError, LoadableConstant, MethodError, VirtualCall},
+        class_library::{ClassIndex, ClassLibrary, ClassResolveError},
+        constant_pool::{ConstantPoolError, ConstantPoolIndex, InterfaceMethodReference},
+        heap::{Heap, HeapError, HeapIndex, NULL_POINTER},
         method::{MethodData, MethodIndex, MethodTable},
-        stack::{StackFrame, StackPointer, StackValue, StackValueWide},
-        types::TypeError,
+        mirror::{self, MirrorError},
+        package::ClassLoaderId,
+        resolution_statistics::SymbolicReference,
+        stack::{SlotTag, StackError, StackFrame, StackPointer},
+        string::{self, StringError},
+        types::{JvmType, TypeError},
         value::{
             JvmDouble, JvmFloat, JvmInt, JvmLong, JvmReference, JvmValue, JVM_EQUAL, JVM_GREATER,
             JVM_LESS,
         },
     },
+    options::ExecutionMode,
+};
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::HashMap,
+};
+
+use self::{
+    budget::BudgetExhausted,
+    frame::{FrameRecord, StackTraceElement},
+    fusion::Fusion,
+    quickening::QuickOperand,
+    watchpoint::Access,
 };
-use std::arch::{asm, global_asm};
-
-global_asm!(
-    ".global interpreter_trampoline",
-    "interpreter_trampoline:",
-    // Stack alignment (8B return address to caller of interpreter_trampoline; 16B required => 8B padding)
-    "sub rsp, 8", 
-    // Move the global variables to the sysv64 parameter registers. 
-    // The method_index is already placed in rdi.
-    // r12-r15 and rbx are preserved by sysv64 and therefore we don't have to save them on the stack
-    "mov rsi, r12",
-    "mov rdx, r13",
-    "mov rcx, r14",
-    "mov r8, r15",
-    "call interpret_method",
-    "add rsp, 8",
-    "ret"
-);
-
-extern "sysv64" {
-    /// Never call this directly! This uses the internal calling convention and not the acutal
-    /// sysv64 calling convention. This function does the translation between them.
-    pub fn interpreter_trampoline(method_index: MethodIndex);
-}
-
-pub extern "sysv64" fn call_method(
+
+thread_local! {
+    // The error (e.g. an uncaught Java exception) that terminated the last called method. Errors can't be
+    // returned through the internal calling convention, so the callee stores them here and call_method picks them up.
+    static PENDING_ERROR: RefCell<Option<ExecutionError>> = const { RefCell::new(None) };
+
+    // The Java stack at the point where an exception has been created or thrown (for the first time)
+    static EXCEPTION_STACK_TRACES: RefCell<HashMap<HeapIndex, Vec<StackTraceElement>>> =
+        RefCell::new(HashMap::new());
+
+    // The Java stack at the point where the pending VM error (i.e. not a Java exception) has been raised
+    static ERROR_STACK_TRACE: RefCell<Option<Vec<StackTraceElement>>> = const { RefCell::new(None) };
+}
+
+#[cfg(asm_entry)]
+pub use crate::jit::convention::interpreter_trampoline;
+
+/// Without the assembly entry, the call table points to this function for interpreted methods
+///
+/// # Safety
+/// The stack pointer must point behind the arguments of the method, and the pointers must refer to the VM that owns it
+#[cfg(not(asm_entry))]
+pub unsafe extern "C" fn interpreter_trampoline(
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+) -> i64 {
+    enter_interpreter(method_index, stack, heap, classes, methods)
+}
+
+/// Whether methods are entered through the x86-64 assembly, which the JIT requires
+pub const ASM_ENTRY: bool = cfg!(asm_entry);
+
+/// Calls the method via the method table. If the callee is terminated by an error (including uncaught Java
+/// exceptions), the error is returned instead of the return value.
+pub fn call_method(
     method_index: MethodIndex,
     stack: StackPointer,
     heap: &mut Heap,
     classes: &ClassLibrary,
     methods: &MethodTable,
-) -> JvmValue {
-    unsafe {
+) -> Result<JvmValue, ExecutionError> {
+    let return_value = unsafe {
         let target = methods.resolve(method_index);
-        let heap = heap as *mut Heap;
-        let classes = classes as *const ClassLibrary;
-        let methods = methods as *const MethodTable;
-        let method_index = method_index.into_raw();
-        let stack = stack.into_raw();
-
-        let return_value: i64;
-        asm!(
-            "sub rsp, 8",
-            "push rbx",
-            "call {0}",
-            "pop rbx",
-            "add rsp, 8",
-            in(reg) target,
-            in("rdi") method_index,
-            in("r12") stack,
-            in("r13") heap,
-            in("r14") classes,
-            in("r15") methods,
-            lateout("rax") return_value,
-        );
-        JvmValue::from_native(return_value)
+        call_address(target, method_index, stack, heap, classes, methods)
+    };
+    match take_pending_error() {
+        Some(err) => Err(err),
+        None => Ok(return_value),
     }
 }
 
+/// Calls the code at the given address using the internal calling convention
+#[cfg(asm_entry)]
+unsafe fn call_address(
+    target: u64,
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> JvmValue {
+    JvmValue::from_native(jit::convention::call(
+        target,
+        method_index,
+        stack,
+        heap,
+        classes,
+        methods,
+    ))
+}
+
+/// Calls the entry function at the given address
+#[cfg(not(asm_entry))]
+unsafe fn call_address(
+    target: u64,
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> JvmValue {
+    let entry: crate::model::method::NativeMethod = std::mem::transmute(target);
+    JvmValue::from_native(entry(method_index, stack, heap, classes, methods))
+}
+
+/// # Safety
+/// The stack pointer must point behind the arguments of the method, and the pointers must refer to the VM that owns it
+#[cfg(asm_entry)]
 #[no_mangle]
 pub unsafe extern "sysv64" fn interpret_method(
     method_index: MethodIndex,
@@ -80,16 +148,229 @@ pub unsafe extern "sysv64" fn interpret_method(
     heap: *mut Heap,
     classes: *const ClassLibrary,
     methods: *const MethodTable,
+) -> i64 {
+    if cfg!(debug_assertions) {
+        jit::convention::check_context(method_index, stack, heap, classes, methods);
+    }
+    enter_interpreter(method_index, stack, heap, classes, methods)
+}
+
+unsafe fn enter_interpreter(
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
 ) -> i64 {
     let heap = &mut *heap;
     let classes = &*classes;
     let methods = &*methods;
 
+    let method = methods.get_data(method_index);
+    if method.is_native {
+        // Bound native methods never reach the interpreter
+        let record = FrameRecord::new(method_index);
+        let _active_frame = record.enter();
+        raise_error(
+            ExecutionError::UnsatisfiedLink(format!(
+                "{}.{}",
+                classes
+                    .resolve(method.owning_class)
+                    .name()
+                    .unwrap_or("<unknown>"),
+                method.name
+            )),
+            classes,
+            methods,
+        );
+        return JvmValue::VOID.to_native();
+    }
+
+    // In the mixed mode, methods are compiled once they have been invoked often enough
+    if (methods.execution_mode() == ExecutionMode::Compiled
+        || methods.count_invocation(method_index))
+        && methods.should_attempt_compilation(method_index)
+    {
+        if let Some(return_value) = compile_and_call(method_index, stack, heap, classes, methods) {
+            return return_value.to_native();
+        }
+    }
+    if methods.execution_mode() == ExecutionMode::CrossCheck {
+        if let Some(return_value) =
+            cross_check::check_call(method_index, stack, heap, classes, methods)
+        {
+            return return_value.to_native();
+        }
+    }
+
+    run_interpreted(method_index, stack, heap, classes, methods).to_native()
+}
+
+fn run_interpreted(
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> JvmValue {
     let method = &methods.get_data(method_index);
-    let mut stack_frame = StackFrame::prepare(stack, method.argument_count, method.max_locals);
-    let return_value = interpret(method, heap, classes, methods, &mut stack_frame).unwrap();
-    stack_frame.clear();
-    return_value.to_native()
+    let record = FrameRecord::new(method_index);
+    let _active_frame = record.enter();
+    let mut stack_frame = match StackFrame::prepare(
+        stack,
+        method.argument_count,
+        method.max_locals,
+        method.max_stack,
+    ) {
+        Ok(stack_frame) => stack_frame,
+        Err(err) => {
+            raise_error(err.into(), classes, methods);
+            return JvmValue::VOID;
+        }
+    };
+    stack_frame.tag_parameters(method.argument_count, &method.parameters);
+    match interpret(method, heap, classes, methods, &mut stack_frame, &record) {
+        Ok(value) => value,
+        Err(err) => {
+            raise_error(err, classes, methods);
+            JvmValue::VOID
+        }
+    }
+}
+
+/// Replaces the implementation of the method with compiled code and calls it, if the JIT supports the method
+fn compile_and_call(
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> Option<JvmValue> {
+    if !compile(method_index, classes, methods) {
+        return None;
+    }
+    // The arguments are still in place, so the compiled code can take over this call.
+    // Errors stay in the error slot for our caller.
+    Some(unsafe {
+        call_address(
+            methods.resolve(method_index),
+            method_index,
+            stack,
+            heap,
+            classes,
+            methods,
+        )
+    })
+}
+
+/// Replaces the implementation of the method with compiled code, if the JIT supports the method
+fn compile(method_index: MethodIndex, classes: &ClassLibrary, methods: &MethodTable) -> bool {
+    match jit::compile_method(method_index, classes, methods) {
+        Ok(implementation) => {
+            methods.update_method(method_index, implementation);
+            true
+        }
+        Err(CompilationError::UnsupportedOpcode(opcode)) => {
+            log::debug!(
+                "Interpreting method {} (opcode {:#04x} is not supported by the JIT)",
+                methods.get_data(method_index).name,
+                opcode
+            );
+            false
+        }
+        Err(CompilationError::ExceptionHandlers) => {
+            log::debug!(
+                "Interpreting method {} (the JIT doesn't support exception handlers)",
+                methods.get_data(method_index).name
+            );
+            false
+        }
+        Err(CompilationError::Breakpoints) => {
+            log::debug!(
+                "Interpreting method {} (compiled code can't stop at breakpoints)",
+                methods.get_data(method_index).name
+            );
+            false
+        }
+        Err(CompilationError::WatchedFields) => {
+            log::debug!(
+                "Interpreting method {} (compiled code doesn't check watchpoints)",
+                methods.get_data(method_index).name
+            );
+            false
+        }
+        Err(err) => panic!(
+            "Failed to compile method {}: {}",
+            methods.get_data(method_index).name,
+            err
+        ),
+    }
+}
+
+pub fn raise_error(err: ExecutionError, classes: &ClassLibrary, methods: &MethodTable) {
+    if !matches!(err, ExecutionError::JavaException(_)) {
+        // Only the innermost frame records the trace
+        ERROR_STACK_TRACE.with(|trace| {
+            trace
+                .borrow_mut()
+                .get_or_insert_with(|| frame::capture_stack_trace(classes, methods));
+        });
+    }
+    // Errors can't cross the native call boundary and are picked up by call_method in the caller
+    set_pending_error(err);
+}
+
+/// Returns (and clears) the error that terminated the last called method, if any
+fn take_pending_error() -> Option<ExecutionError> {
+    PENDING_ERROR.with(|pending| pending.borrow_mut().take())
+}
+
+/// Called by compiled code after every call, the caller returns immediately if the callee has raised an error
+#[cfg(asm_entry)]
+pub extern "sysv64" fn has_pending_error() -> bool {
+    PENDING_ERROR.with(|pending| pending.borrow().is_some())
+}
+
+fn set_pending_error(err: ExecutionError) {
+    PENDING_ERROR.with(|pending| *pending.borrow_mut() = Some(err));
+}
+
+/// Records the current Java stack as the stack trace of the throwable (Throwable.fillInStackTrace), which replaces
+/// the trace of its first throw. Like in HotSpot, the trace starts at the code that created the throwable, i.e.
+/// without fillInStackTrace itself and the constructors of the throwable.
+pub fn fill_in_stack_trace(
+    throwable: HeapIndex,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) {
+    let mut throwable_classes = Vec::new();
+    let mut class = Some(heap.resolve(throwable).class());
+    while let Some(index) = class {
+        let resolved = classes.resolve(index);
+        throwable_classes.push(resolved.name().unwrap_or("<unknown>"));
+        class = resolved.super_class();
+    }
+
+    let trace = frame::capture_stack_trace(classes, methods)
+        .into_iter()
+        .skip_while(|element| element.method_name == "fillInStackTrace")
+        .skip_while(|element| {
+            element.method_name == "<init>"
+                && throwable_classes.contains(&element.class_name.as_str())
+        })
+        .collect();
+    EXCEPTION_STACK_TRACES.with(|traces| traces.borrow_mut().insert(throwable, trace));
+}
+
+/// Returns the Java stack at the point where the exception has been thrown for the first time
+pub fn exception_stack_trace(exception: HeapIndex) -> Option<Vec<StackTraceElement>> {
+    EXCEPTION_STACK_TRACES.with(|traces| traces.borrow().get(&exception).cloned())
+}
+
+/// Returns (and clears) the Java stack at the point where the last VM error has been raised
+pub fn take_error_stack_trace() -> Option<Vec<StackTraceElement>> {
+    ERROR_STACK_TRACE.with(|trace| trace.borrow_mut().take())
 }
 
 fn interpret(
@@ -98,803 +379,985 @@ fn interpret(
     classes: &ClassLibrary,
     methods: &MethodTable,
     stack: &mut StackFrame,
+    frame: &FrameRecord,
 ) -> Result<JvmValue, ExecutionError> {
-    let callee_class = classes.resolve(method.owning_class);
-    println!(
-        "========= Entered method {0} of type {1}",
-        &method.name,
-        callee_class.name().unwrap()
-    );
+    let observed = observer::is_installed();
+    if observed {
+        observer::notify(|observer| observer.method_entered(frame.method(), method, classes));
+    }
+    if breakpoint::is_enabled() {
+        breakpoint::apply(frame.method(), method, classes, methods);
+    }
     let mut pc = 0;
-    let code = &method.code;
     let return_value = loop {
+        let mut result = execute(method, heap, classes, methods, stack, frame, pc, observed);
+        if let Err(err) = result {
+            // VM errors become Java exceptions, so that handlers in this frame and its callers can catch them.
+            // A stack overflow becomes an exception in the first frame that has enough stack left to load the
+            // exception class.
+            result = Err(match err.java_exception_class() {
+                Some(class_name) => {
+                    throw_vm_exception(class_name, err, heap, classes, methods, stack)
+                }
+                None => err,
+            });
+        }
+        match result {
+            Err(ExecutionError::JavaException(exception)) => {
+                // The frame record still points to the instruction that threw the exception
+                match find_exception_handler(
+                    method,
+                    frame.pc(),
+                    exception,
+                    heap,
+                    classes,
+                    methods,
+                    stack,
+                )? {
+                    Some(handler_pc) => {
+                        stack.clear_operands();
+                        stack.push_reference(JvmReference::from_heap_index(exception));
+                        pc = handler_pc;
+                    }
+                    None => break Err(ExecutionError::JavaException(exception)),
+                }
+            }
+            result => break result,
+        }
+    };
+    if observed {
+        observer::notify(|observer| {
+            observer.method_exited(frame.method(), method, classes, &return_value)
+        });
+    }
+    return_value
+}
+
+fn find_exception_handler(
+    method: &MethodData,
+    pc: usize,
+    exception: HeapIndex,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    stack: &mut StackFrame,
+) -> Result<Option<usize>, ExecutionError> {
+    let callee_class = classes.resolve(method.owning_class);
+    let exception_class = classes.resolve(heap.resolve(exception).class());
+    for entry in &method.exception_table {
+        if !entry.covers(pc) {
+            continue;
+        }
+        if !entry.catch_type.is_valid() {
+            // finally blocks catch everything
+            return Ok(Some(entry.handler_pc));
+        }
+        let catch_type = callee_class.resolve_type(entry.catch_type)?;
+        let catch_type = classes
+            .try_resolve_in(
+                callee_class.loader(),
+                catch_type,
+                methods,
+                heap,
+                stack.get_stack_for_call(),
+            )?
+            .index();
+        if exception_class.is_subclass_of(catch_type, classes) {
+            return Ok(Some(entry.handler_pc));
+        }
+    }
+    Ok(None)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute(
+    method: &MethodData,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    stack: &mut StackFrame,
+    frame: &FrameRecord,
+    start_pc: usize,
+    observed: bool,
+) -> Result<JvmValue, ExecutionError> {
+    let callee_class = classes.resolve(method.owning_class);
+    let mut pc = start_pc;
+    let code = method.quickened_code.code();
+    let budgeted = budget::is_active();
+    loop {
         if pc >= code.len() {
             break Err(ExecutionError::MissingReturn);
         }
+        // Instructions that don't transfer control continue behind their operands, the length is taken from the
+        // original code because superinstructions replace the opcode of the first instruction of their sequence
+        let Some(length) = bytecode::instruction_length(&method.code, pc) else {
+            break Err(ExecutionError::MissingReturn);
+        };
+        let next_pc = pc + length;
+
+        if stack.has_overflowed() {
+            // The previous instruction pushed more values than the method declared
+            break Err(StackError::OperandStackOverflow(method.max_stack).into());
+        }
 
-        let opcode = code[pc];
-        //println!("{:#04x}", opcode);
+        frame.set_pc(pc);
+        if budgeted {
+            budget::charge()?;
+        }
+        let mut opcode = code[pc].get();
+        if opcode == bytecode::BREAKPOINT {
+            // The debugger has replaced the instruction, which is executed once the debugger resumes
+            breakpoint::hit(frame.method(), method, pc, stack, classes, methods);
+            opcode = method.quickened_code.opcode_at(pc);
+        }
+        if observed {
+            // Observers see the instructions of the class file instead of the quickened ones
+            observer::notify(|observer| {
+                observer.instruction(
+                    frame.method(),
+                    method,
+                    pc,
+                    method.code[pc],
+                    stack.operands(),
+                )
+            });
+        }
+        if observed && fusion::is_fused(opcode) {
+            // Superinstructions would hide the instructions they replace from the observers
+            opcode = method.code[pc];
+        }
+        if quickening::is_quickenable(opcode) {
+            // The constant pool entry is only resolved on the first execution, afterwards the quickened instruction
+            // is executed directly
+            let operand = resolve_quick_operand(opcode, pc, method, heap, classes, methods, stack)?;
+            opcode = method.quickened_code.quicken(pc, operand);
+        }
         match opcode {
+            bytecode::ACONST_NULL => {
+                stack.push_reference(JvmReference::from_heap_index(NULL_POINTER));
+                pc = next_pc;
+            }
             bytecode::ICONST_M1 => {
-                stack.push(StackValue::from_int(JvmInt(-1)));
-                pc += 1;
+                stack.push_int(JvmInt(-1));
+                pc = next_pc;
             }
             bytecode::ICONST_0 => {
-                stack.push(StackValue::from_int(JvmInt(0)));
-                pc += 1;
+                stack.push_int(JvmInt(0));
+                pc = next_pc;
             }
             bytecode::ICONST_1 => {
-                stack.push(StackValue::from_int(JvmInt(1)));
-                pc += 1;
+                stack.push_int(JvmInt(1));
+                pc = next_pc;
             }
             bytecode::ICONST_2 => {
-                stack.push(StackValue::from_int(JvmInt(2)));
-                pc += 1;
+                stack.push_int(JvmInt(2));
+                pc = next_pc;
             }
             bytecode::ICONST_3 => {
-                stack.push(StackValue::from_int(JvmInt(3)));
-                pc += 1;
+                stack.push_int(JvmInt(3));
+                pc = next_pc;
             }
             bytecode::ICONST_4 => {
-                stack.push(StackValue::from_int(JvmInt(4)));
-                pc += 1;
+                stack.push_int(JvmInt(4));
+                pc = next_pc;
             }
             bytecode::ICONST_5 => {
-                stack.push(StackValue::from_int(JvmInt(5)));
-                pc += 1;
+                stack.push_int(JvmInt(5));
+                pc = next_pc;
             }
             bytecode::LCONST_0 => {
-                stack.push_wide(StackValue::from_long(JvmLong(0)));
-                pc += 1;
+                stack.push_long(JvmLong(0));
+                pc = next_pc;
             }
             bytecode::LCONST_1 => {
-                stack.push_wide(StackValue::from_long(JvmLong(1)));
-                pc += 1;
+                stack.push_long(JvmLong(1));
+                pc = next_pc;
             }
             bytecode::FCONST_0 => {
-                stack.push(StackValue::from_float(JvmFloat(0.0f32)));
-                pc += 1;
+                stack.push_float(JvmFloat(0.0f32));
+                pc = next_pc;
             }
             bytecode::FCONST_1 => {
-                stack.push(StackValue::from_float(JvmFloat(1.0f32)));
-                pc += 1;
+                stack.push_float(JvmFloat(1.0f32));
+                pc = next_pc;
             }
             bytecode::FCONST_2 => {
-                stack.push(StackValue::from_float(JvmFloat(2.0f32)));
-                pc += 1;
+                stack.push_float(JvmFloat(2.0f32));
+                pc = next_pc;
             }
             bytecode::DCONST_0 => {
-                stack.push_wide(StackValue::from_double(JvmDouble(0.0)));
-                pc += 1;
+                stack.push_double(JvmDouble(0.0));
+                pc = next_pc;
             }
             bytecode::DCONST_1 => {
-                stack.push_wide(StackValue::from_double(JvmDouble(1.0)));
-                pc += 1;
+                stack.push_double(JvmDouble(1.0));
+                pc = next_pc;
             }
 
             bytecode::BIPUSH => {
-                stack.push(StackValue::from_int(JvmInt(
-                    i8::from_be_bytes([code[pc + 1]]) as i32,
-                )));
-                pc += 2;
+                stack.push_int(JvmInt(i8::from_be_bytes([code[pc + 1].get()]) as i32));
+                pc = next_pc;
             }
             bytecode::SIPUSH => {
-                stack.push(StackValue::from_int(JvmInt(i16::from_be_bytes([
-                    code[pc + 1],
-                    code[pc + 2],
-                ]) as i32)));
-                pc += 3;
+                stack.push_int(JvmInt(
+                    i16::from_be_bytes([code[pc + 1].get(), code[pc + 2].get()]) as i32,
+                ));
+                pc = next_pc;
             }
 
             bytecode::LDC => {
-                let index = ConstantPoolIndex::from(code[pc + 1] as u16);
-                let (ty, value) = callee_class.get_loadable(index)?;
-                stack.push_value(value, ty);
-                pc += 2;
+                let index = ConstantPoolIndex::from(code[pc + 1].get() as u16);
+                load_constant(index, callee_class, stack, heap, classes, methods)?;
+                pc = next_pc;
             }
             bytecode::LDC_W | bytecode::LDC2_W => {
-                let index =
-                    ConstantPoolIndex::from(u16::from_be_bytes([code[pc + 1], code[pc + 2]]));
-                let (ty, value) = callee_class.get_loadable(index)?;
-                stack.push_value(value, ty);
-                pc += 3;
+                let index = ConstantPoolIndex::from(u16::from_be_bytes([
+                    code[pc + 1].get(),
+                    code[pc + 2].get(),
+                ]));
+                load_constant(index, callee_class, stack, heap, classes, methods)?;
+                pc = next_pc;
             }
 
             bytecode::ILOAD | bytecode::FLOAD | bytecode::ALOAD => {
-                let index = code[pc + 1];
-                stack.push(stack.get_local(index as usize));
-                pc += 2;
+                let index = code[pc + 1].get();
+                stack.load_local(index as usize, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LLOAD | bytecode::DLOAD => {
-                let index = code[pc + 1] as usize;
-                stack.push_wide((stack.get_local(index), stack.get_local(index + 1)));
-                pc += 1;
+                let index = code[pc + 1].get() as usize;
+                stack.load_local_wide(index, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::ILOAD_0 | bytecode::FLOAD_0 | bytecode::ALOAD_0 => {
-                stack.push(stack.get_local(0));
-                pc += 1;
+                stack.load_local(0, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LLOAD_0 | bytecode::DLOAD_0 => {
-                stack.push_wide((stack.get_local(0), stack.get_local(1)));
-                pc += 1;
+                stack.load_local_wide(0, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::ILOAD_1 | bytecode::FLOAD_1 | bytecode::ALOAD_1 => {
-                stack.push(stack.get_local(1));
-                pc += 1;
+                stack.load_local(1, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LLOAD_1 | bytecode::DLOAD_1 => {
-                stack.push_wide((stack.get_local(1), stack.get_local(2)));
-                pc += 1;
+                stack.load_local_wide(1, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::ILOAD_2 | bytecode::FLOAD_2 | bytecode::ALOAD_2 => {
-                stack.push(stack.get_local(2));
-                pc += 1;
+                stack.load_local(2, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LLOAD_2 | bytecode::DLOAD_2 => {
-                stack.push_wide((stack.get_local(2), stack.get_local(3)));
-                pc += 1;
+                stack.load_local_wide(2, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::ILOAD_3 | bytecode::FLOAD_3 | bytecode::ALOAD_3 => {
-                stack.push(stack.get_local(3));
-                pc += 1;
+                stack.load_local(3, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LLOAD_3 | bytecode::DLOAD_3 => {
-                stack.push_wide((stack.get_local(3), stack.get_local(4)));
-                pc += 1;
+                stack.load_local_wide(3, local_tag(opcode));
+                pc = next_pc;
             }
 
             // + array loads
             bytecode::ISTORE | bytecode::FSTORE | bytecode::ASTORE => {
-                let index = code[pc + 1];
-                let value = stack.pop();
-                stack.set_local(index as usize, value);
-                pc += 2;
+                let index = code[pc + 1].get();
+                stack.store_local(index as usize, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LSTORE | bytecode::DSTORE => {
-                let index = code[pc + 1] as usize;
-                let top = stack.pop();
-                let second = stack.pop();
-                stack.set_local(index, second);
-                stack.set_local(index + 1, top);
-                pc += 2;
+                let index = code[pc + 1].get() as usize;
+                stack.store_local_wide(index, local_tag(opcode));
+                pc = next_pc;
             }
 
             bytecode::ISTORE_0 | bytecode::FSTORE_0 | bytecode::ASTORE_0 => {
-                let value = stack.pop();
-                stack.set_local(0, value);
-                pc += 1;
+                stack.store_local(0, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LSTORE_0 | bytecode::DSTORE_0 => {
-                let top = stack.pop();
-                let second = stack.pop();
-                stack.set_local(0, second);
-                stack.set_local(1, top);
-                pc += 1;
+                stack.store_local_wide(0, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::ISTORE_1 | bytecode::FSTORE_1 | bytecode::ASTORE_1 => {
-                let value = stack.pop();
-                stack.set_local(1, value);
-                pc += 1;
+                stack.store_local(1, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LSTORE_1 | bytecode::DSTORE_1 => {
-                let top = stack.pop();
-                let second = stack.pop();
-                stack.set_local(1, second);
-                stack.set_local(2, top);
-                pc += 1;
+                stack.store_local_wide(1, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::ISTORE_2 | bytecode::FSTORE_2 | bytecode::ASTORE_2 => {
-                let value = stack.pop();
-                stack.set_local(2, value);
-                pc += 1;
+                stack.store_local(2, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LSTORE_2 | bytecode::DSTORE_2 => {
-                let top = stack.pop();
-                let second = stack.pop();
-                stack.set_local(2, second);
-                stack.set_local(3, top);
-                pc += 1;
+                stack.store_local_wide(2, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::ISTORE_3 | bytecode::FSTORE_3 | bytecode::ASTORE_3 => {
-                let value = stack.pop();
-                stack.set_local(3, value);
-                pc += 1;
+                stack.store_local(3, local_tag(opcode));
+                pc = next_pc;
             }
             bytecode::LSTORE_3 | bytecode::DSTORE_3 => {
-                let top = stack.pop();
-                let second = stack.pop();
-                stack.set_local(3, second);
-                stack.set_local(4, top);
-                pc += 1;
+                stack.store_local_wide(3, local_tag(opcode));
+                pc = next_pc;
             }
 
             // + array stores
             bytecode::POP => {
                 stack.pop();
-                pc += 1;
+                pc = next_pc;
             }
             bytecode::POP2 => {
                 stack.pop_wide();
-                pc += 1;
+                pc = next_pc;
             }
 
+            // Category 2 values (long and double) occupy two slots on the stack. Therefore all forms of
+            // the instructions below (JVMS §6.5) are the same permutation of slots, e.g. dup2_x1 on an int
+            // and a long ([int][long high][long low]) moves the slots exactly like dup2_x1 on three ints.
             bytecode::DUP => {
-                let tos = stack.pop();
-                stack.push(tos);
-                stack.push(tos);
-                pc += 1;
+                let tos = stack.pop_slot();
+                stack.push_slot(tos);
+                stack.push_slot(tos);
+                pc = next_pc;
             }
             bytecode::DUP_X1 => {
-                let top = stack.pop();
-                let second = stack.pop();
-                stack.push(top);
-                stack.push(second);
-                stack.push(top);
-                pc += 1;
+                let top = stack.pop_slot();
+                let second = stack.pop_slot();
+                stack.push_slot(top);
+                stack.push_slot(second);
+                stack.push_slot(top);
+                pc = next_pc;
             }
             bytecode::DUP_X2 => {
-                let top = stack.pop();
-                let second = stack.pop();
-                let third = stack.pop();
-                stack.push(top);
-                stack.push(third);
-                stack.push(second);
-                stack.push(top);
-                pc += 1;
+                let top = stack.pop_slot();
+                let second = stack.pop_slot();
+                let third = stack.pop_slot();
+                stack.push_slot(top);
+                stack.push_slot(third);
+                stack.push_slot(second);
+                stack.push_slot(top);
+                pc = next_pc;
             }
             bytecode::DUP2 => {
-                let top = stack.pop();
-                let second = stack.pop();
-                stack.push(second);
-                stack.push(top);
-                stack.push(second);
-                stack.push(top);
-                pc += 1;
+                let top = stack.pop_slot();
+                let second = stack.pop_slot();
+                stack.push_slot(second);
+                stack.push_slot(top);
+                stack.push_slot(second);
+                stack.push_slot(top);
+                pc = next_pc;
+            }
+            bytecode::DUP2_X1 => {
+                let top = stack.pop_slot();
+                let second = stack.pop_slot();
+                let third = stack.pop_slot();
+                stack.push_slot(second);
+                stack.push_slot(top);
+                stack.push_slot(third);
+                stack.push_slot(second);
+                stack.push_slot(top);
+                pc = next_pc;
+            }
+            bytecode::DUP2_X2 => {
+                let top = stack.pop_slot();
+                let second = stack.pop_slot();
+                let third = stack.pop_slot();
+                let fourth = stack.pop_slot();
+                stack.push_slot(second);
+                stack.push_slot(top);
+                stack.push_slot(fourth);
+                stack.push_slot(third);
+                stack.push_slot(second);
+                stack.push_slot(top);
+                pc = next_pc;
             }
 
             bytecode::SWAP => {
-                let top = stack.pop();
-                let second = stack.pop();
-                stack.push(top);
-                stack.push(second);
-                pc += 1;
+                let top = stack.pop_slot();
+                let second = stack.pop_slot();
+                stack.push_slot(top);
+                stack.push_slot(second);
+                pc = next_pc;
             }
 
             bytecode::IADD => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                stack.push(StackValue::from_int(JvmInt(op1.0.wrapping_add(op2.0))));
-                pc += 1;
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                stack.push_int(JvmInt(op1.0.wrapping_add(op2.0)));
+                pc = next_pc;
             }
             bytecode::LADD => {
-                let op2 = stack.pop_wide().as_long();
-                let op1 = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_long(JvmLong(op1.0.wrapping_add(op2.0))));
-                pc += 1;
+                let op2 = stack.pop_long();
+                let op1 = stack.pop_long();
+                stack.push_long(JvmLong(op1.0.wrapping_add(op2.0)));
+                pc = next_pc;
             }
             bytecode::FADD => {
-                let op2 = stack.pop().as_float();
-                let op1 = stack.pop().as_float();
-                stack.push(StackValue::from_float(JvmFloat(op1.0 + op2.0)));
-                pc += 1;
+                let op2 = stack.pop_float();
+                let op1 = stack.pop_float();
+                stack.push_float(JvmFloat(op1.0 + op2.0));
+                pc = next_pc;
             }
             bytecode::DADD => {
-                let op2 = stack.pop_wide().as_double();
-                let op1 = stack.pop_wide().as_double();
-                stack.push_wide(StackValue::from_double(JvmDouble(op1.0 + op2.0)));
-                pc += 1;
+                let op2 = stack.pop_double();
+                let op1 = stack.pop_double();
+                stack.push_double(JvmDouble(op1.0 + op2.0));
+                pc = next_pc;
             }
             bytecode::ISUB => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                stack.push(StackValue::from_int(JvmInt(op1.0.wrapping_sub(op2.0))));
-                pc += 1;
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                stack.push_int(JvmInt(op1.0.wrapping_sub(op2.0)));
+                pc = next_pc;
             }
             bytecode::LSUB => {
-                let op2 = stack.pop_wide().as_long();
-                let op1 = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_long(JvmLong(op1.0.wrapping_sub(op2.0))));
-                pc += 1;
+                let op2 = stack.pop_long();
+                let op1 = stack.pop_long();
+                stack.push_long(JvmLong(op1.0.wrapping_sub(op2.0)));
+                pc = next_pc;
             }
             bytecode::FSUB => {
-                let op2 = stack.pop().as_float();
-                let op1 = stack.pop().as_float();
-                stack.push(StackValue::from_float(JvmFloat(op1.0 - op2.0)));
-                pc += 1;
+                let op2 = stack.pop_float();
+                let op1 = stack.pop_float();
+                stack.push_float(JvmFloat(op1.0 - op2.0));
+                pc = next_pc;
             }
             bytecode::DSUB => {
-                let op2 = stack.pop_wide().as_double();
-                let op1 = stack.pop_wide().as_double();
-                stack.push_wide(StackValue::from_double(JvmDouble(op1.0 - op2.0)));
-                pc += 1;
+                let op2 = stack.pop_double();
+                let op1 = stack.pop_double();
+                stack.push_double(JvmDouble(op1.0 - op2.0));
+                pc = next_pc;
             }
             bytecode::IMUL => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                stack.push(StackValue::from_int(JvmInt(op1.0.wrapping_mul(op2.0))));
-                pc += 1;
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                stack.push_int(JvmInt(op1.0.wrapping_mul(op2.0)));
+                pc = next_pc;
             }
             bytecode::LMUL => {
-                let op2 = stack.pop_wide().as_long();
-                let op1 = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_long(JvmLong(op1.0.wrapping_mul(op2.0))));
-                pc += 1;
+                let op2 = stack.pop_long();
+                let op1 = stack.pop_long();
+                stack.push_long(JvmLong(op1.0.wrapping_mul(op2.0)));
+                pc = next_pc;
             }
             bytecode::FMUL => {
-                let op2 = stack.pop().as_float();
-                let op1 = stack.pop().as_float();
-                stack.push(StackValue::from_float(JvmFloat(op1.0 * op2.0)));
-                pc += 1;
+                let op2 = stack.pop_float();
+                let op1 = stack.pop_float();
+                stack.push_float(JvmFloat(op1.0 * op2.0));
+                pc = next_pc;
             }
             bytecode::DMUL => {
-                let op2 = stack.pop_wide().as_double();
-                let op1 = stack.pop_wide().as_double();
-                stack.push_wide(StackValue::from_double(JvmDouble(op1.0 * op2.0)));
-                pc += 1;
+                let op2 = stack.pop_double();
+                let op1 = stack.pop_double();
+                stack.push_double(JvmDouble(op1.0 * op2.0));
+                pc = next_pc;
             }
             bytecode::IDIV => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                stack.push(StackValue::from_int(JvmInt(op1.0.wrapping_div(op2.0))));
-                pc += 1;
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                if op2.0 == 0 {
+                    break Err(ExecutionError::DivisionByZero);
+                }
+                stack.push_int(JvmInt(op1.0.wrapping_div(op2.0)));
+                pc = next_pc;
             }
             bytecode::LDIV => {
-                let op2 = stack.pop_wide().as_long();
-                let op1 = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_long(JvmLong(op1.0.wrapping_div(op2.0))));
-                pc += 1;
+                let op2 = stack.pop_long();
+                let op1 = stack.pop_long();
+                if op2.0 == 0 {
+                    break Err(ExecutionError::DivisionByZero);
+                }
+                stack.push_long(JvmLong(op1.0.wrapping_div(op2.0)));
+                pc = next_pc;
             }
             bytecode::FDIV => {
-                let op2 = stack.pop().as_float();
-                let op1 = stack.pop().as_float();
-                stack.push(StackValue::from_float(JvmFloat(op1.0 / op2.0)));
-                pc += 1;
+                let op2 = stack.pop_float();
+                let op1 = stack.pop_float();
+                stack.push_float(JvmFloat(op1.0 / op2.0));
+                pc = next_pc;
             }
             bytecode::DDIV => {
-                let op2 = stack.pop_wide().as_double();
-                let op1 = stack.pop_wide().as_double();
-                stack.push_wide(StackValue::from_double(JvmDouble(op1.0 / op2.0)));
-                pc += 1;
+                let op2 = stack.pop_double();
+                let op1 = stack.pop_double();
+                stack.push_double(JvmDouble(op1.0 / op2.0));
+                pc = next_pc;
             }
             bytecode::IREM => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                stack.push(StackValue::from_int(JvmInt(op1.0 % op2.0)));
-                pc += 1;
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                if op2.0 == 0 {
+                    break Err(ExecutionError::DivisionByZero);
+                }
+                stack.push_int(JvmInt(op1.0.wrapping_rem(op2.0)));
+                pc = next_pc;
             }
             bytecode::LREM => {
-                let op2 = stack.pop_wide().as_long();
-                let op1 = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_long(JvmLong(op1.0 % op2.0)));
-                pc += 1;
+                let op2 = stack.pop_long();
+                let op1 = stack.pop_long();
+                if op2.0 == 0 {
+                    break Err(ExecutionError::DivisionByZero);
+                }
+                stack.push_long(JvmLong(op1.0.wrapping_rem(op2.0)));
+                pc = next_pc;
             }
             bytecode::FREM => {
-                let op2 = stack.pop().as_float();
-                let op1 = stack.pop().as_float();
-                stack.push(StackValue::from_float(JvmFloat(op1.0 % op2.0)));
-                pc += 1;
+                let op2 = stack.pop_float();
+                let op1 = stack.pop_float();
+                stack.push_float(op1.remainder(op2));
+                pc = next_pc;
             }
             bytecode::DREM => {
-                let op2 = stack.pop_wide().as_double();
-                let op1 = stack.pop_wide().as_double();
-                stack.push_wide(StackValue::from_double(JvmDouble(op1.0 % op2.0)));
-                pc += 1;
+                let op2 = stack.pop_double();
+                let op1 = stack.pop_double();
+                stack.push_double(op1.remainder(op2));
+                pc = next_pc;
             }
             bytecode::INEG => {
-                let op1 = stack.pop().as_int();
-                stack.push(StackValue::from_int(JvmInt(-op1.0)));
-                pc += 1;
+                let op1 = stack.pop_int();
+                stack.push_int(JvmInt(op1.0.wrapping_neg()));
+                pc = next_pc;
             }
             bytecode::LNEG => {
-                let op1 = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_long(JvmLong(-op1.0)));
-                pc += 1;
+                let op1 = stack.pop_long();
+                stack.push_long(JvmLong(op1.0.wrapping_neg()));
+                pc = next_pc;
             }
             bytecode::FNEG => {
-                let op1 = stack.pop().as_float();
-                stack.push(StackValue::from_float(JvmFloat(-op1.0)));
-                pc += 1;
+                let op1 = stack.pop_float();
+                stack.push_float(JvmFloat(-op1.0));
+                pc = next_pc;
             }
             bytecode::DNEG => {
-                let op1 = stack.pop_wide().as_double();
-                stack.push_wide(StackValue::from_double(JvmDouble(-op1.0)));
-                pc += 1;
+                let op1 = stack.pop_double();
+                stack.push_double(JvmDouble(-op1.0));
+                pc = next_pc;
             }
 
             // + Shifts
             bytecode::IAND => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                stack.push(StackValue::from_int(JvmInt(op1.0 & op2.0)));
-                pc += 1;
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                stack.push_int(JvmInt(op1.0 & op2.0));
+                pc = next_pc;
             }
             bytecode::LAND => {
-                let op2 = stack.pop_wide().as_long();
-                let op1 = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_long(JvmLong(op1.0 & op2.0)));
-                pc += 1;
+                let op2 = stack.pop_long();
+                let op1 = stack.pop_long();
+                stack.push_long(JvmLong(op1.0 & op2.0));
+                pc = next_pc;
             }
             bytecode::IOR => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                stack.push(StackValue::from_int(JvmInt(op1.0 | op2.0)));
-                pc += 1;
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                stack.push_int(JvmInt(op1.0 | op2.0));
+                pc = next_pc;
             }
             bytecode::LOR => {
-                let op2 = stack.pop_wide().as_long();
-                let op1 = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_long(JvmLong(op1.0 | op2.0)));
-                pc += 1;
+                let op2 = stack.pop_long();
+                let op1 = stack.pop_long();
+                stack.push_long(JvmLong(op1.0 | op2.0));
+                pc = next_pc;
             }
             bytecode::IXOR => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                stack.push(StackValue::from_int(JvmInt(op1.0 ^ op2.0)));
-                pc += 1;
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                stack.push_int(JvmInt(op1.0 ^ op2.0));
+                pc = next_pc;
             }
             bytecode::LXOR => {
-                let op2 = stack.pop_wide().as_long();
-                let op1 = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_long(JvmLong(op1.0 ^ op2.0)));
-                pc += 1;
+                let op2 = stack.pop_long();
+                let op1 = stack.pop_long();
+                stack.push_long(JvmLong(op1.0 ^ op2.0));
+                pc = next_pc;
             }
 
             bytecode::IINC => {
-                let index = code[pc + 1] as usize;
-                let increment = unsafe { std::mem::transmute::<u8, i8>(code[pc + 2]) } as i32;
-                stack.set_local(
+                let index = code[pc + 1].get() as usize;
+                let increment = code[pc + 2].get() as i8 as i32;
+                stack.set_local_int(
                     index,
-                    StackValue::from_int(JvmInt(stack.get_local(index).as_int().0 + increment)),
+                    JvmInt(stack.get_local_int(index).0.wrapping_add(increment)),
                 );
-                pc += 3;
+                pc = next_pc;
             }
 
             bytecode::I2L => {
-                let value = stack.pop().as_int();
-                stack.push_wide(StackValue::from_long(JvmLong(value.0 as i64)));
-                pc += 1;
+                let value = stack.pop_int();
+                stack.push_long(JvmLong(value.0 as i64));
+                pc = next_pc;
             }
             bytecode::I2F => {
-                let value = stack.pop().as_int();
-                stack.push(StackValue::from_float(JvmFloat(value.0 as f32)));
-                pc += 1;
+                let value = stack.pop_int();
+                stack.push_float(JvmFloat(value.0 as f32));
+                pc = next_pc;
             }
             bytecode::I2D => {
-                let value = stack.pop().as_int();
-                stack.push_wide(StackValue::from_double(JvmDouble(value.0 as f64)));
-                pc += 1;
+                let value = stack.pop_int();
+                stack.push_double(JvmDouble(value.0 as f64));
+                pc = next_pc;
             }
             bytecode::L2I => {
-                let value = stack.pop_wide().as_long();
-                stack.push(StackValue::from_int(JvmInt(value.0 as i32)));
-                pc += 1;
+                let value = stack.pop_long();
+                stack.push_int(JvmInt(value.0 as i32));
+                pc = next_pc;
             }
             bytecode::L2F => {
-                let value = stack.pop_wide().as_long();
-                stack.push(StackValue::from_float(JvmFloat(value.0 as f32)));
-                pc += 1;
+                let value = stack.pop_long();
+                stack.push_float(JvmFloat(value.0 as f32));
+                pc = next_pc;
             }
             bytecode::L2D => {
-                let value = stack.pop_wide().as_long();
-                stack.push_wide(StackValue::from_double(JvmDouble(value.0 as f64)));
-                pc += 1;
+                let value = stack.pop_long();
+                stack.push_double(JvmDouble(value.0 as f64));
+                pc = next_pc;
             }
             bytecode::F2I => {
-                let value = stack.pop().as_float();
-                stack.push(StackValue::from_int(JvmInt(value.0 as i32)));
-                pc += 1;
+                let value = stack.pop_float();
+                stack.push_int(JvmInt(value.0 as i32));
+                pc = next_pc;
             }
             bytecode::F2L => {
-                let value = stack.pop().as_float();
-                stack.push_wide(StackValue::from_long(JvmLong(value.0 as i64)));
-                pc += 1;
+                let value = stack.pop_float();
+                stack.push_long(JvmLong(value.0 as i64));
+                pc = next_pc;
             }
             bytecode::F2D => {
-                let value = stack.pop().as_float();
-                stack.push_wide(StackValue::from_double(JvmDouble(value.0 as f64)));
-                pc += 1;
+                let value = stack.pop_float();
+                stack.push_double(JvmDouble(value.0 as f64));
+                pc = next_pc;
             }
             bytecode::D2I => {
-                let value = stack.pop_wide().as_double();
-                stack.push(StackValue::from_int(JvmInt(value.0 as i32)));
-                pc += 1;
+                let value = stack.pop_double();
+                stack.push_int(JvmInt(value.0 as i32));
+                pc = next_pc;
             }
             bytecode::D2L => {
-                let value = stack.pop_wide().as_double();
-                stack.push_wide(StackValue::from_long(JvmLong(value.0 as i64)));
-                pc += 1;
+                let value = stack.pop_double();
+                stack.push_long(JvmLong(value.0 as i64));
+                pc = next_pc;
             }
             bytecode::D2F => {
-                let value = stack.pop_wide().as_double();
-                stack.push(StackValue::from_float(JvmFloat(value.0 as f32)));
-                pc += 1;
+                let value = stack.pop_double();
+                stack.push_float(JvmFloat(value.0 as f32));
+                pc = next_pc;
             }
             bytecode::I2B => {
-                let value = stack.pop().as_int().0 as i8;
-                stack.push(StackValue::from_int(JvmInt(value as i32))); // This does sign-extension
-                pc += 1;
+                let value = stack.pop_int().0 as i8;
+                stack.push_int(JvmInt(value as i32)); // This does sign-extension
+                pc = next_pc;
             }
             bytecode::I2C => {
-                let value = stack.pop().as_int().0 as u8;
-                stack.push(StackValue::from_int(JvmInt(value as i32))); //TODO not sure if this does sign-extension (it shouldn't do)
-                pc += 1;
+                // Keeps the low 16 bits and zero-extends them (JVMS §6.5.i2c)
+                let value = stack.pop_int().0 as u16;
+                stack.push_int(value.into());
+                pc = next_pc;
             }
             bytecode::I2S => {
-                let value = stack.pop().as_int().0 as i16;
-                stack.push(StackValue::from_int(JvmInt(value as i32))); // This does sign-extension
-                pc += 1;
+                let value = stack.pop_int().0 as i16;
+                stack.push_int(JvmInt(value as i32)); // This does sign-extension
+                pc = next_pc;
             }
 
             bytecode::LCMP => {
-                let op2 = stack.pop_wide().as_long();
-                let op1 = stack.pop_wide().as_long();
+                let op2 = stack.pop_long();
+                let op1 = stack.pop_long();
                 if op1 > op2 {
-                    stack.push(StackValue::from_int(JVM_GREATER));
+                    stack.push_int(JVM_GREATER);
                 } else if op1 == op2 {
-                    stack.push(StackValue::from_int(JVM_EQUAL));
+                    stack.push_int(JVM_EQUAL);
                 } else {
-                    stack.push(StackValue::from_int(JVM_LESS));
+                    stack.push_int(JVM_LESS);
                 }
-                pc += 3;
+                pc = next_pc;
             }
             bytecode::FCMPG => {
-                let op2 = stack.pop().as_float();
-                let op1 = stack.pop().as_float();
-                if op1.0.is_nan() || op2.0.is_nan() {
-                    stack.push(StackValue::from_int(JVM_GREATER));
-                } else if op1 > op2 {
-                    stack.push(StackValue::from_int(JVM_GREATER));
+                let op2 = stack.pop_float();
+                let op1 = stack.pop_float();
+                if op1.0.is_nan() || op2.0.is_nan() || op1 > op2 {
+                    stack.push_int(JVM_GREATER);
                 } else if op1 == op2 {
-                    stack.push(StackValue::from_int(JVM_EQUAL));
+                    stack.push_int(JVM_EQUAL);
                 } else {
-                    stack.push(StackValue::from_int(JVM_LESS));
+                    stack.push_int(JVM_LESS);
                 }
-                pc += 3;
+                pc = next_pc;
             }
             bytecode::FCMPL => {
-                let op2 = stack.pop().as_float();
-                let op1 = stack.pop().as_float();
+                let op2 = stack.pop_float();
+                let op1 = stack.pop_float();
                 if op1.0.is_nan() || op2.0.is_nan() {
-                    stack.push(StackValue::from_int(JVM_LESS));
+                    stack.push_int(JVM_LESS);
                 } else if op1 > op2 {
-                    stack.push(StackValue::from_int(JVM_GREATER));
+                    stack.push_int(JVM_GREATER);
                 } else if op1 == op2 {
-                    stack.push(StackValue::from_int(JVM_LESS));
+                    stack.push_int(JVM_EQUAL);
                 } else {
-                    stack.push(StackValue::from_int(JVM_EQUAL));
+                    stack.push_int(JVM_LESS);
                 }
-                pc += 3;
+                pc = next_pc;
             }
             bytecode::DCMPG => {
-                let op2 = stack.pop_wide().as_double();
-                let op1 = stack.pop_wide().as_double();
-                if op1.0.is_nan() || op2.0.is_nan() {
-                    stack.push(StackValue::from_int(JVM_GREATER));
-                } else if op1 > op2 {
-                    stack.push(StackValue::from_int(JVM_GREATER));
+                let op2 = stack.pop_double();
+                let op1 = stack.pop_double();
+                if op1.0.is_nan() || op2.0.is_nan() || op1 > op2 {
+                    stack.push_int(JVM_GREATER);
                 } else if op1 == op2 {
-                    stack.push(StackValue::from_int(JVM_EQUAL));
+                    stack.push_int(JVM_EQUAL);
                 } else {
-                    stack.push(StackValue::from_int(JVM_LESS));
+                    stack.push_int(JVM_LESS);
                 }
-                pc += 3;
+                pc = next_pc;
             }
             bytecode::DCMPL => {
-                let op2 = stack.pop_wide().as_double();
-                let op1 = stack.pop_wide().as_double();
+                let op2 = stack.pop_double();
+                let op1 = stack.pop_double();
                 if op1.0.is_nan() || op2.0.is_nan() {
-                    stack.push(StackValue::from_int(JVM_LESS));
+                    stack.push_int(JVM_LESS);
                 } else if op1 > op2 {
-                    stack.push(StackValue::from_int(JVM_GREATER));
+                    stack.push_int(JVM_GREATER);
                 } else if op1 == op2 {
-                    stack.push(StackValue::from_int(JVM_EQUAL));
+                    stack.push_int(JVM_EQUAL);
                 } else {
-                    stack.push(StackValue::from_int(JVM_LESS));
+                    stack.push_int(JVM_LESS);
                 }
-                pc += 3;
+                pc = next_pc;
             }
 
             bytecode::IFEQ => {
-                let op = stack.pop().as_int();
-                if op.0 == 0 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 2;
-                }
+                let op = stack.pop_int();
+                pc = branch(op.0 == 0, pc, method, frame.method(), classes, methods);
             }
             bytecode::IFNE => {
-                let op = stack.pop().as_int();
-                if op.0 != 0 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 2;
-                }
+                let op = stack.pop_int();
+                pc = branch(op.0 != 0, pc, method, frame.method(), classes, methods);
             }
             bytecode::IFLT => {
-                let op = stack.pop().as_int();
-                if op.0 < 0 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 2;
-                }
+                let op = stack.pop_int();
+                pc = branch(op.0 < 0, pc, method, frame.method(), classes, methods);
             }
             bytecode::IFGE => {
-                let op = stack.pop().as_int();
-                if op.0 >= 0 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 2;
-                }
+                let op = stack.pop_int();
+                pc = branch(op.0 >= 0, pc, method, frame.method(), classes, methods);
             }
             bytecode::IFGT => {
-                let op = stack.pop().as_int();
-                if op.0 > 0 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 2;
-                }
+                let op = stack.pop_int();
+                pc = branch(op.0 > 0, pc, method, frame.method(), classes, methods);
             }
             bytecode::IFLE => {
-                let op = stack.pop().as_int();
-                if op.0 <= 0 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 2;
-                }
+                let op = stack.pop_int();
+                pc = branch(op.0 <= 0, pc, method, frame.method(), classes, methods);
             }
             bytecode::IF_ICMPEQ => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                if op1 == op2 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 3;
-                }
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                pc = branch(op1 == op2, pc, method, frame.method(), classes, methods);
             }
             bytecode::IF_ICMPNE => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                if op1 != op2 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 3;
-                }
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                pc = branch(op1 != op2, pc, method, frame.method(), classes, methods);
             }
             bytecode::IF_ICMPLT => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                if op1 < op2 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 3;
-                }
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                pc = branch(op1 < op2, pc, method, frame.method(), classes, methods);
             }
             bytecode::IF_ICMPGE => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                if op1 >= op2 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 3;
-                }
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                pc = branch(op1 >= op2, pc, method, frame.method(), classes, methods);
             }
             bytecode::IF_ICMPGT => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                if op1 > op2 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 3;
-                }
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                pc = branch(op1 > op2, pc, method, frame.method(), classes, methods);
             }
             bytecode::IF_ICMPLE => {
-                let op2 = stack.pop().as_int();
-                let op1 = stack.pop().as_int();
-                if op1 <= op2 {
-                    pc = offset(pc, code[pc + 1], code[pc + 2]);
-                } else {
-                    pc += 3;
-                }
+                let op2 = stack.pop_int();
+                let op1 = stack.pop_int();
+                pc = branch(op1 <= op2, pc, method, frame.method(), classes, methods);
             }
 
             // + IF_ACMPEQ, IF_ACMPNE
             bytecode::GOTO => {
-                pc = offset(pc, code[pc + 1], code[pc + 2]);
+                pc = take_branch(pc, code, frame.method(), classes, methods);
             }
 
-            // + JSR, RET (maybe)
+            bytecode::GOTO_W => {
+                let target = wide_offset(pc, read_i32(code, pc + 1));
+                pc = jump(pc, target, frame.method(), classes, methods);
+            }
+            bytecode::TABLESWITCH | bytecode::LOOKUPSWITCH => {
+                let key = stack.pop_int().0;
+                let target = switch_target(pc, code, key);
+                pc = jump(pc, target, frame.method(), classes, methods);
+            }
 
-            // + tableswitch, lookupswitch
+            // + JSR, RET (maybe)
             bytecode::IRETURN => {
+                check_return_type(opcode, method)?;
                 break Ok(JvmValue {
-                    int: stack.pop().as_int().into(),
-                })
+                    int: stack.pop_int().into(),
+                });
             }
             bytecode::LRETURN => {
+                check_return_type(opcode, method)?;
                 break Ok(JvmValue {
-                    long: stack.pop_wide().as_long().into(),
-                })
+                    long: stack.pop_long().into(),
+                });
             }
             bytecode::FRETURN => {
+                check_return_type(opcode, method)?;
                 break Ok(JvmValue {
-                    float: stack.pop().as_float().into(),
-                })
+                    float: stack.pop_float().into(),
+                });
             }
             bytecode::DRETURN => {
+                check_return_type(opcode, method)?;
                 break Ok(JvmValue {
-                    double: stack.pop_wide().as_double().into(),
-                })
+                    double: stack.pop_double().into(),
+                });
             }
             bytecode::ARETURN => {
+                check_return_type(opcode, method)?;
                 break Ok(JvmValue {
-                    reference: stack.pop().as_reference().to_heap_index(),
-                })
+                    reference: stack.pop_reference().to_heap_index(),
+                });
+            }
+            bytecode::RETURN => {
+                check_return_type(opcode, method)?;
+                break Ok(JvmValue::VOID);
             }
-            bytecode::RETURN => break Ok(JvmValue::VOID),
 
-            bytecode::GETSTATIC => {
-                let (class, field) = callee_class.resolve_static_field(
-                    index(code[pc + 1], code[pc + 2]),
-                    classes,
-                    heap,
-                    methods,
-                    stack.get_stack_for_call(),
-                )?;
+            bytecode::GETSTATIC_QUICK => {
+                callee_class
+                    .resolution_statistics()
+                    .record_hit(SymbolicReference::Field);
+                let (class, field) = method.quickened_code.static_field(pc);
                 let value = classes.resolve(class).get_static_field(field);
+                if watchpoint::is_enabled() {
+                    watchpoint::static_field_access(
+                        Access::Read,
+                        class,
+                        field,
+                        value,
+                        classes,
+                        methods,
+                    );
+                }
                 stack.push_value(value, field.ty);
-                pc += 3;
+                pc = next_pc;
             }
-            bytecode::PUTSTATIC => {
-                let (class, field) = callee_class.resolve_static_field(
-                    index(code[pc + 1], code[pc + 2]),
-                    classes,
-                    heap,
-                    methods,
-                    stack.get_stack_for_call(),
-                )?;
+            bytecode::PUTSTATIC_QUICK => {
+                callee_class
+                    .resolution_statistics()
+                    .record_hit(SymbolicReference::Field);
+                let (class, field) = method.quickened_code.static_field(pc);
                 let value = stack.pop_type(field.ty);
+                if watchpoint::is_enabled() {
+                    watchpoint::static_field_access(
+                        Access::Write,
+                        class,
+                        field,
+                        value,
+                        classes,
+                        methods,
+                    );
+                }
                 classes.resolve(class).set_static_field(field, value);
-                pc += 3;
-            }
-            bytecode::GETFIELD => {
-                let field = callee_class.resolve_instance_field(
-                    index(code[pc + 1], code[pc + 2]),
-                    classes,
-                    heap,
-                    methods,
-                    stack.get_stack_for_call(),
-                )?;
-                let objectref = stack.pop().as_reference();
-                let value = heap.resolve(objectref.to_heap_index()).get_field(field);
+                pc = next_pc;
+            }
+            bytecode::GETFIELD_QUICK => {
+                callee_class
+                    .resolution_statistics()
+                    .record_hit(SymbolicReference::Field);
+                let field = method.quickened_code.instance_field(pc);
+                let objectref = non_null(stack.pop_reference())?;
+                let object = heap.resolve(objectref);
+                let value = object.get_field(field);
+                if watchpoint::is_enabled() {
+                    watchpoint::instance_field_access(
+                        Access::Read,
+                        object.class(),
+                        field,
+                        value,
+                        classes,
+                        methods,
+                    );
+                }
                 stack.push_value(value, field.ty);
-                pc += 3;
+                pc = next_pc;
             }
-            bytecode::PUTFIELD => {
-                let field = callee_class.resolve_instance_field(
-                    index(code[pc + 1], code[pc + 2]),
-                    classes,
-                    heap,
-                    methods,
-                    stack.get_stack_for_call(),
-                )?;
+            bytecode::PUTFIELD_QUICK => {
+                callee_class
+                    .resolution_statistics()
+                    .record_hit(SymbolicReference::Field);
+                let field = method.quickened_code.instance_field(pc);
                 let value = stack.pop_type(field.ty);
-                let objectref = stack.pop().as_reference();
-                heap.resolve(objectref.to_heap_index())
-                    .set_field(field, value);
-                pc += 3;
+                let objectref = non_null(stack.pop_reference())?;
+                if watchpoint::is_enabled() {
+                    watchpoint::instance_field_access(
+                        Access::Write,
+                        heap.resolve(objectref).class(),
+                        field,
+                        value,
+                        classes,
+                        methods,
+                    );
+                }
+                heap.resolve(objectref).set_field(field, value);
+                pc = next_pc;
             }
 
-            bytecode::INVOKESPECIAL => {
-                let cp_index = index(code[pc + 1], code[pc + 2]);
-                //TODO match the signature
-                let (method_index, _) = callee_class.resolve_virtual_method_statically(
-                    cp_index,
-                    classes,
+            bytecode::INVOKESTATIC_TAIL_QUICK
+                if !observed && method.quickened_code.method(pc).0 == frame.method() =>
+            {
+                // The method calls itself as its last action, so its frame can be reused for the call
+                let (_, parameter_count) = method.quickened_code.method(pc);
+                stack.replace_locals(parameter_count);
+                pc = 0;
+            }
+            bytecode::INVOKENONVIRTUAL_QUICK | bytecode::INVOKESTATIC_TAIL_QUICK => {
+                callee_class
+                    .resolution_statistics()
+                    .record_hit(SymbolicReference::Method);
+                let (method_index, parameter_count) = method.quickened_code.method(pc);
+                let callee = methods.get_data(method_index);
+                if !callee.flags.contains(MethodFlags::STATIC) {
+                    // invokespecial and invokevirtual of methods that can't be overridden
+                    non_null(stack.peek(parameter_count - 1).as_reference())?;
+                }
+                let return_type = callee.return_type;
+                call_graph::record_call(frame.method(), pc, method_index);
+                let return_value = call_method(
+                    method_index,
+                    stack.get_stack_for_call(),
                     heap,
+                    classes,
                     methods,
-                    stack.get_stack_for_call(),
-                )?;
+                );
+                stack.discard(parameter_count);
+                stack.push_value(return_value?, return_type);
+                pc = next_pc;
+            }
+            bytecode::INVOKEVIRTUAL_QUICK => {
+                callee_class
+                    .resolution_statistics()
+                    .record_hit(SymbolicReference::Method);
+                let (virtual_index, parameter_count) = method.quickened_code.virtual_method(pc);
+                let instance = non_null(stack.peek(parameter_count - 1).as_reference())?;
+                let method_index = heap
+                    .resolve(instance)
+                    .dispatch_virtual(virtual_index, classes);
+
                 let return_type = methods.get_data(method_index).return_type;
+                call_graph::record_call(frame.method(), pc, method_index);
                 let return_value = call_method(
                     method_index,
                     stack.get_stack_for_call(),
@@ -902,19 +1365,57 @@ fn interpret(
                     classes,
                     methods,
                 );
-                stack.push_value(return_value, return_type);
-                pc += 3;
-            }
-            bytecode::INVOKESTATIC => {
-                let cp_index = index(code[pc + 1], code[pc + 2]);
-                let (method_index, _) = callee_class.resolve_static_method(
+                stack.discard(parameter_count);
+                stack.push_value(return_value?, return_type);
+                pc = next_pc;
+            }
+            bytecode::INVOKEINTERFACE => {
+                let cp_index = index(code[pc + 1].get(), code[pc + 2].get());
+                // code[pc + 3] (count) is redundant to the descriptor and code[pc + 4] is always zero
+                let reference = callee_class.resolve_interface_method(
                     cp_index,
                     classes,
                     heap,
                     methods,
                     stack.get_stack_for_call(),
                 )?;
+                let (method_index, parameter_count) = match reference {
+                    InterfaceMethodReference::ResolvedInterface {
+                        interface,
+                        method_index,
+                        parameter_count,
+                    } => {
+                        let instance = non_null(stack.peek(parameter_count - 1).as_reference())?;
+                        let instance_class = classes.resolve(heap.resolve(instance).class());
+                        let method_index = instance_class
+                            .dispatch_interface_call(interface, method_index)
+                            .ok_or_else(|| {
+                                MethodError::MissingInterfaceImplementation(
+                                    instance_class.name().unwrap_or_default().to_string(),
+                                    classes
+                                        .resolve(interface)
+                                        .name()
+                                        .unwrap_or_default()
+                                        .to_string(),
+                                )
+                            })?;
+                        (method_index, parameter_count)
+                    }
+                    InterfaceMethodReference::ResolvedVirtual {
+                        virtual_index,
+                        parameter_count,
+                    } => {
+                        let instance = non_null(stack.peek(parameter_count - 1).as_reference())?;
+                        let method_index = heap
+                            .resolve(instance)
+                            .dispatch_virtual(virtual_index, classes);
+                        (method_index, parameter_count)
+                    }
+                    InterfaceMethodReference::Unresolved { .. } => unreachable!(),
+                };
+
                 let return_type = methods.get_data(method_index).return_type;
+                call_graph::record_call(frame.method(), pc, method_index);
                 let return_value = call_method(
                     method_index,
                     stack.get_stack_for_call(),
@@ -922,62 +1423,525 @@ fn interpret(
                     classes,
                     methods,
                 );
-                stack.push_value(return_value, return_type);
-                pc += 3;
+                stack.discard(parameter_count);
+                stack.push_value(return_value?, return_type);
+                pc = next_pc;
             }
-            bytecode::INVOKEVIRTUAL => {
-                let cp_index = index(code[pc + 1], code[pc + 2]);
-                //TODO match the signature
-                let (virtual_index, paramter_count) = callee_class.resolve_virtual_method(
-                    cp_index,
-                    classes,
-                    heap,
+            // + invokedynamic
+            bytecode::NEW => {
+                let class_name =
+                    callee_class.resolve_type(index(code[pc + 1].get(), code[pc + 2].get()))?;
+                callee_class
+                    .resolution_statistics()
+                    .record_class_lookup(class_name, classes);
+                let class = classes.try_resolve_in(
+                    callee_class.loader(),
+                    class_name,
                     methods,
+                    heap,
                     stack.get_stack_for_call(),
                 )?;
-                let instance = stack
-                    .peek(paramter_count - 1)
-                    .as_reference()
-                    .to_heap_index();
-                let method_index = heap
-                    .resolve(instance)
-                    .dispatch_virtual(virtual_index, classes);
+                classes.initialize(class.index(), methods, heap, stack.get_stack_for_call())?;
+                let instance = heap.instantiate(class)?;
+                stack.push_reference(JvmReference::from_heap_index(instance));
+                pc = next_pc;
+            }
 
-                let return_type = methods.get_data(method_index).return_type;
-                let return_value = call_method(
-                    method_index,
-                    stack.get_stack_for_call(),
+            bytecode::CHECKCAST => {
+                let reference = stack.peek(0).as_reference().to_heap_index();
+                // null can be cast to every type
+                if reference != NULL_POINTER {
+                    let target_name =
+                        callee_class.resolve_type(index(code[pc + 1].get(), code[pc + 2].get()))?;
+                    callee_class
+                        .resolution_statistics()
+                        .record_class_lookup(target_name, classes);
+                    if !is_instance_of(
+                        reference,
+                        target_name,
+                        callee_class.loader(),
+                        heap,
+                        classes,
+                        methods,
+                        stack,
+                    )? {
+                        let source = heap.resolve(reference).class();
+                        break Err(ExecutionError::ClassCast {
+                            class: classes.resolve(source).name()?.to_string(),
+                            target: target_name.to_string(),
+                        });
+                    }
+                }
+                pc = next_pc;
+            }
+            bytecode::INSTANCEOF => {
+                let reference = stack.pop_reference().to_heap_index();
+                let result = if reference == NULL_POINTER {
+                    false
+                } else {
+                    let target_name =
+                        callee_class.resolve_type(index(code[pc + 1].get(), code[pc + 2].get()))?;
+                    callee_class
+                        .resolution_statistics()
+                        .record_class_lookup(target_name, classes);
+                    is_instance_of(
+                        reference,
+                        target_name,
+                        callee_class.loader(),
+                        heap,
+                        classes,
+                        methods,
+                        stack,
+                    )?
+                };
+                stack.push_int(JvmInt(result as i32));
+                pc = next_pc;
+            }
+
+            bytecode::NEWARRAY => {
+                let atype = code[pc + 1].get();
+                let ty =
+                    ArrayType::from_atype(atype).ok_or(ExecutionError::InvalidArrayType(atype))?;
+                let count = stack.pop_int().0;
+                let array = new_array(ty, count, None, heap, classes, methods, stack)?;
+                stack.push_reference(JvmReference::from_heap_index(array));
+                pc = next_pc;
+            }
+            bytecode::ANEWARRAY => {
+                let component_name =
+                    callee_class.resolve_type(index(code[pc + 1].get(), code[pc + 2].get()))?;
+                callee_class
+                    .resolution_statistics()
+                    .record_class_lookup(component_name, classes);
+                // Array classes don't exist as Class, so arrays of arrays have no component class
+                let component_class = if component_name.starts_with('[') {
+                    None
+                } else {
+                    Some(
+                        classes
+                            .try_resolve_in(
+                                callee_class.loader(),
+                                component_name,
+                                methods,
+                                heap,
+                                stack.get_stack_for_call(),
+                            )?
+                            .index(),
+                    )
+                };
+                let count = stack.pop_int().0;
+                let array = new_array(
+                    ArrayType::Reference,
+                    count,
+                    component_class,
                     heap,
                     classes,
                     methods,
-                );
-                stack.push_value(return_value, return_type);
-                pc += 3;
+                    stack,
+                )?;
+                stack.push_reference(JvmReference::from_heap_index(array));
+                pc = next_pc;
             }
-            // + invokeinterface, invokedynamic
-            bytecode::NEW => {
-                let class_name = callee_class.resolve_type(index(code[pc + 1], code[pc + 2]))?;
-                let class =
-                    classes.resolve_by_name(class_name, methods, heap, stack.get_stack_for_call());
-                let instance = heap.instantiate(class);
-                stack.push(StackValue::from_reference(JvmReference::from_heap_index(
-                    instance,
+            bytecode::ARRAYLENGTH => {
+                let arrayref = stack.pop_reference().to_heap_index();
+                if arrayref == NULL_POINTER {
+                    break Err(ExecutionError::NullPointer);
+                }
+                let array = heap
+                    .resolve_array(arrayref)
+                    .ok_or(ExecutionError::NotAnArray)?;
+                stack.push_int(JvmInt(array.length() as i32));
+                pc = next_pc;
+            }
+            bytecode::IALOAD
+            | bytecode::LALOAD
+            | bytecode::FALOAD
+            | bytecode::DALOAD
+            | bytecode::AALOAD
+            | bytecode::BALOAD
+            | bytecode::CALOAD
+            | bytecode::SALOAD => {
+                let index = stack.pop_int().0;
+                let arrayref = stack.pop_reference();
+                let array = access_array(arrayref, index, heap, classes, methods, stack)?;
+                stack.push_value(array.get(index as usize), array.ty().stack_type());
+                pc = next_pc;
+            }
+            bytecode::IASTORE
+            | bytecode::LASTORE
+            | bytecode::FASTORE
+            | bytecode::DASTORE
+            | bytecode::AASTORE
+            | bytecode::BASTORE
+            | bytecode::CASTORE
+            | bytecode::SASTORE => {
+                let ty = match opcode {
+                    bytecode::LASTORE => JvmType::Long,
+                    bytecode::FASTORE => JvmType::Float,
+                    bytecode::DASTORE => JvmType::Double,
+                    bytecode::AASTORE => JvmType::Reference,
+                    _ => JvmType::Integer,
+                };
+                let value = stack.pop_type(ty);
+                let index = stack.pop_int().0;
+                let arrayref = stack.pop_reference();
+                let mut array = access_array(arrayref, index, heap, classes, methods, stack)?;
+                if opcode == bytecode::AASTORE {
+                    check_array_store(&array, value, heap, classes, methods, stack)?;
+                }
+                array.set(index as usize, value);
+                pc = next_pc;
+            }
+
+            bytecode::ATHROW => {
+                let exception = stack.pop_reference().to_heap_index();
+                if exception == NULL_POINTER {
+                    break Err(ExecutionError::NullPointer);
+                }
+                record_exception_stack_trace(exception, classes, methods);
+                break Err(ExecutionError::JavaException(exception));
+            }
+
+            bytecode::ALOAD_0_GETFIELD => {
+                // The superinstruction is only executed as a whole once the field has been resolved by the getfield
+                if code[pc + 1].get() == bytecode::GETFIELD_QUICK {
+                    fusion::record_execution(Fusion::LoadFieldOfThis);
+                    callee_class
+                        .resolution_statistics()
+                        .record_hit(SymbolicReference::Field);
+                    let field = method.quickened_code.instance_field(pc + 1);
+                    let objectref = non_null(stack.get_local_reference(0))?;
+                    let object = heap.resolve(objectref);
+                    let value = object.get_field(field);
+                    if watchpoint::is_enabled() {
+                        watchpoint::instance_field_access(
+                            Access::Read,
+                            object.class(),
+                            field,
+                            value,
+                            classes,
+                            methods,
+                        );
+                    }
+                    stack.push_value(value, field.ty);
+                    pc += 4;
+                } else {
+                    fusion::record_deferral(Fusion::LoadFieldOfThis);
+                    stack.load_local(0, SlotTag::Reference);
+                    pc = next_pc;
+                }
+            }
+            bytecode::ILOAD_ILOAD_IADD => {
+                fusion::record_execution(Fusion::AddLocals);
+                let (first, first_length) = fusion::int_load(&method.code, pc).unwrap();
+                let (second, second_length) =
+                    fusion::int_load(&method.code, pc + first_length).unwrap();
+                let op1 = stack.get_local_int(first);
+                let op2 = stack.get_local_int(second);
+                stack.push_int(JvmInt(op1.0.wrapping_add(op2.0)));
+                pc += first_length + second_length + 1;
+            }
+            bytecode::ICONST_ISTORE => {
+                fusion::record_execution(Fusion::StoreConstant);
+                let value = method.code[pc] as i32 - bytecode::ICONST_0 as i32;
+                let (local, length) = fusion::int_store(&method.code, pc + 1).unwrap();
+                stack.set_local_int(local, JvmInt(value));
+                pc += 1 + length;
+            }
+
+            bytecode::WIDE => {
+                let modified_opcode = code[pc + 1].get();
+                let index = u16::from_be_bytes([code[pc + 2].get(), code[pc + 3].get()]) as usize;
+                match modified_opcode {
+                    bytecode::ILOAD | bytecode::FLOAD | bytecode::ALOAD => {
+                        stack.load_local(index, local_tag(modified_opcode));
+                        pc = next_pc;
+                    }
+                    bytecode::LLOAD | bytecode::DLOAD => {
+                        stack.load_local_wide(index, local_tag(modified_opcode));
+                        pc = next_pc;
+                    }
+                    bytecode::ISTORE | bytecode::FSTORE | bytecode::ASTORE => {
+                        stack.store_local(index, local_tag(modified_opcode));
+                        pc = next_pc;
+                    }
+                    bytecode::LSTORE | bytecode::DSTORE => {
+                        stack.store_local_wide(index, local_tag(modified_opcode));
+                        pc = next_pc;
+                    }
+                    bytecode::IINC => {
+                        let increment =
+                            i16::from_be_bytes([code[pc + 4].get(), code[pc + 5].get()]) as i32;
+                        stack.set_local_int(
+                            index,
+                            JvmInt(stack.get_local_int(index).0.wrapping_add(increment)),
+                        );
+                        pc = next_pc;
+                    }
+                    // + RET (maybe)
+                    _ => break Err(ExecutionError::InvalidWideOpcode(modified_opcode)),
+                }
+            }
+
+            bytecode::INVOKEDYNAMIC => {
+                let (bootstrap_method, call_site) = callee_class
+                    .get_invoke_dynamic(index(code[pc + 1].get(), code[pc + 2].get()))?;
+                break Err(ExecutionError::UnsupportedDynamic(format!(
+                    "the call site {} of the bootstrap method {}",
+                    call_site, bootstrap_method
                 )));
-                pc += 3;
             }
 
-            _ => todo!("Unimplemented opcode {:#04x}", opcode),
+            // Valid code may still contain opcodes that aren't implemented yet, which fail like a VM error
+            _ => break Err(ExecutionError::UnsupportedOpcode(opcode)),
         }
+        debug_assert!(
+            pc == next_pc || transfers_control(opcode) || fusion::is_fused(opcode),
+            "the handler of {:#04x} didn't continue at pc {}, but at {}",
+            opcode,
+            next_pc,
+            pc
+        );
+    }
+}
+
+/// The type of the local that is accessed by a load or store instruction, for the checked mode
+#[inline(always)]
+fn local_tag(opcode: u8) -> SlotTag {
+    match opcode {
+        bytecode::ILOAD
+        | bytecode::ILOAD_0..=bytecode::ILOAD_3
+        | bytecode::ISTORE
+        | bytecode::ISTORE_0..=bytecode::ISTORE_3 => SlotTag::Int,
+        bytecode::LLOAD
+        | bytecode::LLOAD_0..=bytecode::LLOAD_3
+        | bytecode::LSTORE
+        | bytecode::LSTORE_0..=bytecode::LSTORE_3 => SlotTag::LongHigh,
+        bytecode::FLOAD
+        | bytecode::FLOAD_0..=bytecode::FLOAD_3
+        | bytecode::FSTORE
+        | bytecode::FSTORE_0..=bytecode::FSTORE_3 => SlotTag::Float,
+        bytecode::DLOAD
+        | bytecode::DLOAD_0..=bytecode::DLOAD_3
+        | bytecode::DSTORE
+        | bytecode::DSTORE_0..=bytecode::DSTORE_3 => SlotTag::DoubleHigh,
+        _ => SlotTag::Reference,
+    }
+}
+
+/// Whether the instruction may continue somewhere else than at the next instruction
+fn transfers_control(opcode: u8) -> bool {
+    branch_profile::is_conditional_branch(opcode)
+        || matches!(
+            opcode,
+            bytecode::GOTO
+                | bytecode::GOTO_W
+                | bytecode::JSR
+                | bytecode::JSR_W
+                | bytecode::RET
+                | bytecode::TABLESWITCH
+                | bytecode::LOOKUPSWITCH
+                | bytecode::INVOKESTATIC_TAIL_QUICK
+        )
+}
+
+/// The object that the reference points to, which must be checked before it is resolved on the heap. Fails with a
+/// NullPointerException if the reference is null.
+#[inline(always)]
+fn non_null(reference: JvmReference) -> Result<HeapIndex, ExecutionError> {
+    let reference = reference.to_heap_index();
+    if reference == NULL_POINTER {
+        Err(ExecutionError::NullPointer)
+    } else {
+        Ok(reference)
+    }
+}
+
+#[inline(always)]
+/// Resolves the constant pool entry that is referenced by the (not yet quickened) instruction at pc
+fn resolve_quick_operand(
+    opcode: u8,
+    pc: usize,
+    method: &MethodData,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    stack: &mut StackFrame,
+) -> Result<QuickOperand, ExecutionError> {
+    let callee_class = classes.resolve(method.owning_class);
+    // The code from the class file still holds the constant pool index
+    let cp_index = index(method.code[pc + 1], method.code[pc + 2]);
+    let stack = stack.get_stack_for_call();
+    let operand = match opcode {
+        bytecode::GETSTATIC | bytecode::PUTSTATIC => {
+            let (class, field) =
+                callee_class.resolve_static_field(cp_index, classes, heap, methods, stack)?;
+            // The declaring class of the field is initialized, not the class that the reference names
+            classes.initialize(class, methods, heap, stack)?;
+            QuickOperand::StaticField(class, field)
+        }
+        bytecode::GETFIELD | bytecode::PUTFIELD => QuickOperand::InstanceField(
+            callee_class.resolve_instance_field(cp_index, classes, heap, methods, stack)?,
+        ),
+        bytecode::INVOKESPECIAL => {
+            let (method_index, parameter_count) =
+                callee_class.resolve_special_method(cp_index, classes, heap, methods, stack)?;
+            QuickOperand::Method(method_index, parameter_count)
+        }
+        bytecode::INVOKESTATIC | bytecode::INVOKESTATIC_TAIL => {
+            let (method_index, parameter_count) =
+                callee_class.resolve_static_method(cp_index, classes, heap, methods, stack)?;
+            let declaring_class = methods.get_data(method_index).owning_class;
+            classes.initialize(declaring_class, methods, heap, stack)?;
+            QuickOperand::Method(method_index, parameter_count)
+        }
+        bytecode::INVOKEVIRTUAL => {
+            match callee_class.resolve_virtual_method(cp_index, classes, heap, methods, stack)? {
+                (VirtualCall::Dispatched(virtual_index), parameter_count) => {
+                    QuickOperand::VirtualMethod(virtual_index, parameter_count)
+                }
+                (VirtualCall::Direct(method_index), parameter_count) => {
+                    QuickOperand::Method(method_index, parameter_count)
+                }
+            }
+        }
+        _ => unreachable!("opcode {:#04x} can't be quickened", opcode),
     };
-    println!(
-        "========= Exited method {0} of type {1}",
-        &method.name,
-        callee_class.name().unwrap()
-    );
-    return_value
+    Ok(operand)
+}
+
+/// Whether the return instruction matches the return type of a method
+pub fn is_return_for(opcode: u8, return_type: JvmType) -> bool {
+    match return_type {
+        JvmType::Void => opcode == bytecode::RETURN,
+        JvmType::Boolean | JvmType::Byte | JvmType::Char | JvmType::Short | JvmType::Integer => {
+            opcode == bytecode::IRETURN
+        }
+        JvmType::Long => opcode == bytecode::LRETURN,
+        JvmType::Float => opcode == bytecode::FRETURN,
+        JvmType::Double => opcode == bytecode::DRETURN,
+        JvmType::Reference => opcode == bytecode::ARETURN,
+    }
+}
+
+/// Checks that the return instruction matches the return type of the method. Otherwise the value on the stack would
+/// be reinterpreted as the return type silently.
+fn check_return_type(opcode: u8, method: &MethodData) -> Result<(), ExecutionError> {
+    if is_return_for(opcode, method.return_type) {
+        Ok(())
+    } else {
+        Err(ExecutionError::ReturnTypeMismatch {
+            opcode,
+            method: format!("{}{}", method.name, method.descriptor),
+        })
+    }
+}
+
+/// Continues at the target of the conditional branch at pc if it is taken, otherwise at the next instruction
+#[inline(always)]
+fn branch(
+    taken: bool,
+    pc: usize,
+    method: &MethodData,
+    method_index: MethodIndex,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> usize {
+    method.quickened_code.branches().record(pc, taken);
+    if taken {
+        take_branch(
+            pc,
+            method.quickened_code.code(),
+            method_index,
+            classes,
+            methods,
+        )
+    } else {
+        pc + bytecode::fixed_length(method.code[pc]).unwrap()
+    }
 }
 
+/// Returns the target of the branch instruction at pc. Backward branches count towards the back-edge threshold of the
+/// method, a method that reaches it is compiled. The running invocation stays in the interpreter, because there is no
+/// on-stack replacement, but all later invocations execute the compiled code.
 #[inline(always)]
+fn take_branch(
+    pc: usize,
+    code: &[Cell<u8>],
+    method_index: MethodIndex,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> usize {
+    let target = offset(pc, code[pc + 1].get(), code[pc + 2].get());
+    jump(pc, target, method_index, classes, methods)
+}
+
+/// Continues at the target of a branch, compiling the method if the branch is a hot back edge
+fn jump(
+    pc: usize,
+    target: usize,
+    method_index: MethodIndex,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> usize {
+    if target <= pc
+        && methods.count_back_edge(method_index)
+        && methods.should_attempt_compilation(method_index)
+    {
+        compile(method_index, classes, methods);
+    }
+    target
+}
+
+/// Returns the target of the tableswitch or lookupswitch at pc for the key
+fn switch_target(pc: usize, code: &[Cell<u8>], key: i32) -> usize {
+    // The operands are aligned to four bytes, relative to the start of the code
+    let operands = pc + 1 + (3 - pc % 4);
+    let default = read_i32(code, operands);
+    let offset = if code[pc].get() == bytecode::TABLESWITCH {
+        let low = read_i32(code, operands + 4);
+        let high = read_i32(code, operands + 8);
+        if key < low || key > high {
+            default
+        } else {
+            read_i32(code, operands + 12 + 4 * (key as i64 - low as i64) as usize)
+        }
+    } else {
+        // The match-offset pairs are sorted by their match
+        let pairs = read_i32(code, operands + 4).max(0) as usize;
+        let (mut start, mut end) = (0, pairs);
+        let mut offset = default;
+        while start < end {
+            let middle = start + (end - start) / 2;
+            let pair = operands + 8 + 8 * middle;
+            match read_i32(code, pair).cmp(&key) {
+                Ordering::Less => start = middle + 1,
+                Ordering::Greater => end = middle,
+                Ordering::Equal => {
+                    offset = read_i32(code, pair + 4);
+                    break;
+                }
+            }
+        }
+        offset
+    };
+    wide_offset(pc, offset)
+}
+
+fn read_i32(code: &[Cell<u8>], index: usize) -> i32 {
+    i32::from_be_bytes([
+        code[index].get(),
+        code[index + 1].get(),
+        code[index + 2].get(),
+        code[index + 3].get(),
+    ])
+}
+
+/// The target of a branch with a 32 bit offset (goto_w and the switches)
+fn wide_offset(pc: usize, offset: i32) -> usize {
+    pc.wrapping_add(offset as isize as usize)
+}
+
 fn offset(pc: usize, byte1: u8, byte2: u8) -> usize {
     //hack
     // Should work because of the two complement's representation of i16 and the wrapping add
@@ -986,6 +1950,305 @@ fn offset(pc: usize, byte1: u8, byte2: u8) -> usize {
 }
 
 #[inline]
+/// Records the current Java stack for the exception. A rethrown exception keeps the stack trace of its first throw.
+pub fn record_exception_stack_trace(
+    exception: HeapIndex,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) {
+    EXCEPTION_STACK_TRACES.with(|traces| {
+        traces
+            .borrow_mut()
+            .entry(exception)
+            .or_insert_with(|| frame::capture_stack_trace(classes, methods));
+    });
+}
+
+/// Creates an exception that is raised by the VM itself (e.g. java/lang/ArrayIndexOutOfBoundsException).
+/// The constructor of the exception is not run, its message is the detail message of the fallback error.
+/// Returns the fallback error if the exception class can't be loaded.
+fn throw_vm_exception(
+    class_name: &str,
+    fallback: ExecutionError,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    stack: &mut StackFrame,
+) -> ExecutionError {
+    let class =
+        match classes.try_resolve_by_name(class_name, methods, heap, stack.get_stack_for_call()) {
+            Ok(class) => class,
+            Err(err) => {
+                log::debug!("Can't throw {}: {}", class_name, err);
+                return fallback;
+            }
+        };
+    let exception = match heap.instantiate(class) {
+        Ok(exception) => exception,
+        Err(err) => return err.into(),
+    };
+    if let Ok(message_field) = class.field_layout().resolve("detailMessage") {
+        let message = fallback.detail_message();
+        match string::new_string(&message, classes, heap, methods, stack.get_stack_for_call()) {
+            Ok(message) => heap
+                .resolve(exception)
+                .set_field(message_field, JvmValue { reference: message }),
+            Err(err) => log::debug!("The {} has no message: {}", class_name, err),
+        }
+    }
+    record_exception_stack_trace(exception, classes, methods);
+    ExecutionError::JavaException(exception)
+}
+
+/// Wraps an exception that a static initializer has thrown in an ExceptionInInitializerError, unless it is an Error
+/// itself (JVMS §5.5, step 11). VM errors without a Java exception are passed on as they are, like the exception if
+/// the ExceptionInInitializerError can't be created.
+pub fn exception_in_initializer(
+    err: ExecutionError,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> ExecutionError {
+    let ExecutionError::JavaException(exception) = err else {
+        return err;
+    };
+    let class = heap.resolve(exception).class();
+    // No instance of an Error can exist if the class hasn't been loaded
+    if classes
+        .find_loaded("java/lang/Error")
+        .is_some_and(|error| classes.is_subtype_of(class, error))
+    {
+        return err;
+    }
+    let wrapper = match classes.try_resolve_by_name(
+        "java/lang/ExceptionInInitializerError",
+        methods,
+        heap,
+        stack,
+    ) {
+        Ok(wrapper) => wrapper,
+        Err(resolve_err) => {
+            log::debug!("Can't throw ExceptionInInitializerError: {}", resolve_err);
+            return err;
+        }
+    };
+    let error = match heap.instantiate(wrapper) {
+        Ok(error) => error,
+        Err(heap_err) => return heap_err.into(),
+    };
+    if let Ok(cause_field) = wrapper.field_layout().resolve("cause") {
+        heap.resolve(error).set_field(
+            cause_field,
+            JvmValue {
+                reference: exception,
+            },
+        );
+    }
+    record_exception_stack_trace(error, classes, methods);
+    ExecutionError::JavaException(error)
+}
+
+/// Pushes the constant at the index (ldc). String constants are interned and mirrors are created on first use.
+fn load_constant(
+    index: ConstantPoolIndex,
+    class: &Class,
+    stack: &mut StackFrame,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> Result<(), ExecutionError> {
+    match class.get_loadable(index)? {
+        LoadableConstant::Value(ty, value) => stack.push_value(value, ty),
+        LoadableConstant::String(value) => {
+            let string = string::intern(value, classes, heap, methods, stack.get_stack_for_call())?;
+            stack.push_reference(JvmReference::from_heap_index(string));
+        }
+        LoadableConstant::Class(name) => {
+            let mirror = mirror::mirror(name, classes, heap, methods, stack.get_stack_for_call())?;
+            stack.push_reference(JvmReference::from_heap_index(mirror));
+        }
+        // These need java.lang.invoke, which the VM doesn't support yet
+        LoadableConstant::MethodHandle(kind, reference) => {
+            return Err(ExecutionError::UnsupportedDynamic(format!(
+                "the method handle {:?} of {}",
+                kind, reference
+            )))
+        }
+        LoadableConstant::MethodType(descriptor) => {
+            return Err(ExecutionError::UnsupportedDynamic(format!(
+                "the method type {}",
+                descriptor
+            )))
+        }
+        LoadableConstant::Dynamic(bootstrap_method, key) => {
+            return Err(ExecutionError::UnsupportedDynamic(format!(
+                "the dynamic constant {} of the bootstrap method {}",
+                key, bootstrap_method
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn new_array(
+    ty: ArrayType,
+    count: i32,
+    component_class: Option<ClassIndex>,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    stack: &mut StackFrame,
+) -> Result<HeapIndex, ExecutionError> {
+    if count < 0 {
+        return Err(throw_vm_exception(
+            "java/lang/NegativeArraySizeException",
+            ExecutionError::NegativeArraySize(count),
+            heap,
+            classes,
+            methods,
+            stack,
+        ));
+    }
+    let object_class = classes
+        .try_resolve_by_name(
+            "java/lang/Object",
+            methods,
+            heap,
+            stack.get_stack_for_call(),
+        )?
+        .index();
+    Ok(heap.instantiate_array(ty, count as usize, component_class, object_class)?)
+}
+
+/// Resolves the array and checks that the index is within its bounds
+fn access_array(
+    arrayref: JvmReference,
+    index: i32,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    stack: &mut StackFrame,
+) -> Result<Array, ExecutionError> {
+    let arrayref = arrayref.to_heap_index();
+    if arrayref == NULL_POINTER {
+        return Err(ExecutionError::NullPointer);
+    }
+    let array = heap
+        .resolve_array(arrayref)
+        .ok_or(ExecutionError::NotAnArray)?;
+    if index < 0 || index as usize >= array.length() {
+        return Err(throw_vm_exception(
+            "java/lang/ArrayIndexOutOfBoundsException",
+            ExecutionError::ArrayIndexOutOfBounds {
+                index,
+                length: array.length(),
+            },
+            heap,
+            classes,
+            methods,
+            stack,
+        ));
+    }
+    Ok(array)
+}
+
+/// Checks that the value can be stored in the reference array (JVMS §6.5.aastore)
+fn check_array_store(
+    array: &Array,
+    value: JvmValue,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    stack: &mut StackFrame,
+) -> Result<(), ExecutionError> {
+    let value = value.reference().to_heap_index();
+    if let Some(component_class) = array.component_class() {
+        if value != NULL_POINTER && !heap.is_array(value) {
+            let class = heap.resolve(value).class();
+            if !classes.is_subtype_of(class, component_class) {
+                return Err(throw_vm_exception(
+                    "java/lang/ArrayStoreException",
+                    ExecutionError::ArrayStore {
+                        class: classes.resolve(class).name()?.to_string(),
+                        component: classes.resolve(component_class).name()?.to_string(),
+                    },
+                    heap,
+                    classes,
+                    methods,
+                    stack,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether the (non-null) object is an instance of the type with the given name, which may be an array type
+fn is_instance_of(
+    reference: HeapIndex,
+    target_name: &str,
+    loader: ClassLoaderId, // Of the class whose constant pool names the target
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    stack: &mut StackFrame,
+) -> Result<bool, ExecutionError> {
+    if let Some(target_type) = ArrayType::from_class_name(target_name) {
+        let array = match heap.resolve_array(reference) {
+            Some(array) => array,
+            None => return Ok(false),
+        };
+        if array.ty() != target_type {
+            return Ok(false);
+        }
+        if target_type != ArrayType::Reference {
+            return Ok(true);
+        }
+        let target_component = &target_name[1..];
+        return match (
+            array.component_class(),
+            target_component
+                .strip_prefix('L')
+                .and_then(|name| name.strip_suffix(';')),
+        ) {
+            (Some(component_class), Some(target_component)) => {
+                let target_component = classes
+                    .try_resolve_in(
+                        loader,
+                        target_component,
+                        methods,
+                        heap,
+                        stack.get_stack_for_call(),
+                    )?
+                    .index();
+                Ok(classes.is_subtype_of(component_class, target_component))
+            }
+            // Arrays of arrays are only distinguished from arrays of objects, not by their dimension
+            (None, None) => Ok(true),
+            _ => Ok(false),
+        };
+    }
+
+    if heap.is_array(reference) {
+        // Arrays are only instances of Object and the interfaces that all arrays implement (JLS §10.8)
+        return Ok(matches!(
+            target_name,
+            "java/lang/Object" | "java/lang/Cloneable" | "java/io/Serializable"
+        ));
+    }
+    let target = classes
+        .try_resolve_in(
+            loader,
+            target_name,
+            methods,
+            heap,
+            stack.get_stack_for_call(),
+        )?
+        .index();
+    Ok(classes.is_subtype_of(heap.resolve(reference).class(), target))
+}
+
 fn index(byte1: u8, byte2: u8) -> ConstantPoolIndex {
     u16::from_be_bytes([byte1, byte2]).into()
 }
@@ -995,6 +2258,51 @@ pub enum ExecutionError {
     #[error("last instruction was not a return instruction")]
     MissingReturn,
 
+    #[error("a Java exception was thrown and not caught")]
+    JavaException(HeapIndex),
+
+    #[error("tried to dereference null")]
+    NullPointer,
+
+    #[error("/ by zero")]
+    DivisionByZero,
+
+    #[error("an instance of {class} cannot be cast to {target}")]
+    ClassCast { class: String, target: String },
+
+    #[error("index {index} is out of bounds for an array of length {length}")]
+    ArrayIndexOutOfBounds { index: i32, length: usize },
+
+    #[error("tried to create an array with negative size {0}")]
+    NegativeArraySize(i32),
+
+    #[error("an instance of {class} cannot be stored in an array of {component}")]
+    ArrayStore { class: String, component: String },
+
+    #[error("{0} is not a valid primitive array type")]
+    InvalidArrayType(u8),
+
+    #[error("tried to access an object that is not an array as an array")]
+    NotAnArray,
+
+    #[error("no implementation has been registered for the native method {0}")]
+    UnsatisfiedLink(String),
+
+    #[error("could not initialize class {0}")]
+    ErroneousClass(String),
+
+    #[error("the return instruction {opcode:#04x} doesn't match the return type of {method}")]
+    ReturnTypeMismatch { opcode: u8, method: String },
+
+    #[error("{0} can't be linked, because the VM doesn't support java.lang.invoke yet")]
+    UnsupportedDynamic(String),
+
+    #[error("the interpreter doesn't support the opcode {0:#04x} yet")]
+    UnsupportedOpcode(u8),
+
+    #[error("the opcode {0:#04x} cannot be modified by the wide prefix")]
+    InvalidWideOpcode(u8),
+
     #[error("method error")]
     MethodError {
         #[from]
@@ -1018,4 +2326,116 @@ pub enum ExecutionError {
         #[from]
         value: FieldError,
     },
+
+    #[error("heap error")]
+    HeapError {
+        #[from]
+        value: HeapError,
+    },
+
+    #[error("mirror error")]
+    MirrorError {
+        #[from]
+        value: MirrorError,
+    },
+
+    // Boxed, because the failed initialization of a class contains an ExecutionError
+    #[error("class resolve error")]
+    ClassResolveError { value: Box<ClassResolveError> },
+
+    #[error("stack error")]
+    StackError {
+        #[from]
+        value: StackError,
+    },
+
+    #[error("string error")]
+    StringError {
+        #[from]
+        value: StringError,
+    },
+
+    #[error("the VM is exiting with status {0}")]
+    Exit(i32),
+
+    #[error("execution budget error")]
+    BudgetExhausted {
+        #[from]
+        value: BudgetExhausted,
+    },
+}
+
+impl From<ClassResolveError> for ExecutionError {
+    fn from(err: ClassResolveError) -> Self {
+        Self::ClassResolveError {
+            value: Box::new(err),
+        }
+    }
+}
+
+impl ExecutionError {
+    /// The Java exception that a complete VM would throw in place of this error, None for errors that indicate a
+    /// broken class file or a bug of the VM
+    pub fn java_exception_class(&self) -> Option<&'static str> {
+        match self {
+            Self::NullPointer => Some("java/lang/NullPointerException"),
+            Self::DivisionByZero => Some("java/lang/ArithmeticException"),
+            Self::ClassCast { .. } => Some("java/lang/ClassCastException"),
+            Self::ArrayIndexOutOfBounds { .. } => Some("java/lang/ArrayIndexOutOfBoundsException"),
+            Self::NegativeArraySize(_) => Some("java/lang/NegativeArraySizeException"),
+            Self::ArrayStore { .. } => Some("java/lang/ArrayStoreException"),
+            Self::UnsatisfiedLink(_) => Some("java/lang/UnsatisfiedLinkError"),
+            Self::ErroneousClass(_) => Some("java/lang/NoClassDefFoundError"),
+            Self::MethodError { value } => match value {
+                MethodError::UnknownVirtual(_)
+                | MethodError::UnknownInterface(_)
+                | MethodError::UnknownStatic(_) => Some("java/lang/NoSuchMethodError"),
+                MethodError::MissingInterfaceImplementation(..) => {
+                    Some("java/lang/AbstractMethodError")
+                }
+                MethodError::NotVirtual(_)
+                | MethodError::NotStatic(_)
+                | MethodError::NotAnInterface(_) => Some("java/lang/IncompatibleClassChangeError"),
+                MethodError::IllegalAccess { .. } => Some("java/lang/IllegalAccessError"),
+                MethodError::ClassResolve(err) => err.java_exception_class(),
+                MethodError::InvalidDescriptor(_) | MethodError::ConstantPool(_) => None,
+            },
+            Self::FieldError { value } => match value {
+                FieldError::FieldNotResolvable(_) | FieldError::StaticFieldNotFound(_) => {
+                    Some("java/lang/NoSuchFieldError")
+                }
+                FieldError::IllegalAccess { .. } => Some("java/lang/IllegalAccessError"),
+                FieldError::ClassResolve(err) => err.java_exception_class(),
+                FieldError::InvalidDescriptor(_) | FieldError::ConstantPool(_) => None,
+            },
+            Self::ClassResolveError { value } => value.java_exception_class(),
+            Self::MirrorError {
+                value: MirrorError::ClassResolve(err),
+            } => err.java_exception_class(),
+            Self::HeapError { .. } => Some("java/lang/OutOfMemoryError"),
+            Self::StackError {
+                value: StackError::StackOverflow | StackError::NativeStackOverflow,
+            } => Some("java/lang/StackOverflowError"),
+            // The verifier of a complete VM would have rejected the method
+            Self::StackError {
+                value: StackError::OperandStackOverflow(_),
+            } => Some("java/lang/VerifyError"),
+            Self::ReturnTypeMismatch { .. } => Some("java/lang/VerifyError"),
+            Self::UnsupportedOpcode(_) => Some("java/lang/InternalError"),
+            _ => None,
+        }
+    }
+
+    /// The message of the Java exception that replaces this error
+    pub fn detail_message(&self) -> String {
+        match self {
+            Self::MethodError { value } => value.to_string(),
+            Self::FieldError { value } => value.to_string(),
+            Self::ClassResolveError { value } => value.to_string(),
+            Self::HeapError { value } => value.to_string(),
+            Self::StackError { value } => value.to_string(),
+            Self::BudgetExhausted { value } => value.to_string(),
+            _ => self.to_string(),
+        }
+    }
 }
@@ -0,0 +1,133 @@
+use std::cell::{Cell, RefCell};
+
+use crate::model::{
+    class_library::ClassLibrary,
+    method::{MethodData, MethodIndex},
+    stack::StackValue,
+    value::JvmValue,
+};
+
+use super::ExecutionError;
+
+thread_local! {
+    static OBSERVER: RefCell<Option<Box<dyn InterpreterObserver>>> = RefCell::new(None);
+
+    // Checked once per interpreted method, so that the interpreter loop doesn't pay for observers if none is installed
+    static INSTALLED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Gets notified about the execution of interpreted methods, e.g. for tracing, coverage or profiling.
+/// Compiled code is not observed. The callbacks must not call back into the VM.
+pub trait InterpreterObserver {
+    fn method_entered(
+        &mut self,
+        _index: MethodIndex,
+        _method: &MethodData,
+        _classes: &ClassLibrary,
+    ) {
+    }
+
+    /// The result is the return value or the error (including uncaught Java exceptions) that terminated the method
+    fn method_exited(
+        &mut self,
+        _index: MethodIndex,
+        _method: &MethodData,
+        _classes: &ClassLibrary,
+        _result: &Result<JvmValue, ExecutionError>,
+    ) {
+    }
+
+    /// Called before the instruction at pc is executed. The operand stack is given bottom to top.
+    fn instruction(
+        &mut self,
+        _index: MethodIndex,
+        _method: &MethodData,
+        _pc: usize,
+        _opcode: u8,
+        _operands: &[StackValue],
+    ) {
+    }
+}
+
+/// Installs the observer for the interpreter of the current thread and returns the previously installed one
+pub fn install(observer: Box<dyn InterpreterObserver>) -> Option<Box<dyn InterpreterObserver>> {
+    INSTALLED.with(|installed| installed.set(true));
+    OBSERVER.with(|current| current.borrow_mut().replace(observer))
+}
+
+pub fn remove() -> Option<Box<dyn InterpreterObserver>> {
+    INSTALLED.with(|installed| installed.set(false));
+    OBSERVER.with(|current| current.borrow_mut().take())
+}
+
+#[inline(always)]
+pub(super) fn is_installed() -> bool {
+    INSTALLED.with(|installed| installed.get())
+}
+
+pub(super) fn notify<F: FnOnce(&mut dyn InterpreterObserver)>(callback: F) {
+    OBSERVER.with(|current| {
+        if let Some(observer) = current.borrow_mut().as_mut() {
+            callback(observer.as_mut());
+        }
+    });
+}
+
+/// Prints every entered and exited method (-XX:+TraceMethods)
+pub struct MethodTracer;
+
+impl InterpreterObserver for MethodTracer {
+    fn method_entered(&mut self, _index: MethodIndex, method: &MethodData, classes: &ClassLibrary) {
+        println!(
+            "========= Entered method {0} of type {1}{2}",
+            method.name,
+            classes
+                .resolve(method.owning_class)
+                .name()
+                .unwrap_or("<unknown>"),
+            parameter_names(method)
+                .map(|names| format!(" with parameters {}", names))
+                .unwrap_or_default()
+        );
+    }
+
+    fn method_exited(
+        &mut self,
+        _index: MethodIndex,
+        method: &MethodData,
+        classes: &ClassLibrary,
+        _result: &Result<JvmValue, ExecutionError>,
+    ) {
+        println!(
+            "========= Exited method {0} of type {1}",
+            method.name,
+            classes
+                .resolve(method.owning_class)
+                .name()
+                .unwrap_or("<unknown>")
+        );
+    }
+}
+
+/// The names and types of the parameters as "name: type, ...", None if the method has no parameters or no
+/// LocalVariableTable
+fn parameter_names(method: &MethodData) -> Option<String> {
+    if method.parameters.is_empty() || method.local_variables.is_empty() {
+        return None;
+    }
+    let parameter_slots = method.parameters.iter().map(|ty| ty.slots()).sum::<usize>();
+    let mut slot = method.argument_count - parameter_slots; // Skips this
+    let mut names = Vec::with_capacity(method.parameters.len());
+    for ty in &method.parameters {
+        names.push(match method.local_variable(slot, 0) {
+            Some(variable) => format!(
+                "{}: {}",
+                variable.name,
+                variable.signature.as_ref().unwrap_or(&variable.descriptor)
+            ),
+            None => "<unknown>".to_string(),
+        });
+        slot += ty.slots();
+    }
+    Some(names.join(", "))
+}
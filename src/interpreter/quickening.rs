@@ -0,0 +1,227 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use appendlist::AppendList;
+
+use crate::{
+    bytecode,
+    interpreter::{branch_profile::BranchProfile, fusion, tail_call},
+    model::{
+        class::VirtualMethodIndex,
+        class_library::ClassIndex,
+        field::FieldInfo,
+        method::{ExceptionTableEntry, MethodIndex},
+    },
+};
+
+// Instructions that refer to the constant pool are rewritten to quickened variants once their constant pool entry has
+// been resolved, so that later executions skip the resolution. The quickened instruction has the same length as the
+// original one and stores the index of its resolved operand in place of the constant pool index.
+// Only the interpreter sees the quickened code, the JIT and everything else work on the code from the class file.
+// Breakpoints replace the opcode of an instruction by BREAKPOINT and keep the replaced opcode aside, an instruction that
+// is quickened while a breakpoint is set gets its quickened opcode stored there instead.
+
+/// The resolved operand of a quickened instruction
+#[derive(Debug, Clone, Copy)]
+pub enum QuickOperand {
+    StaticField(ClassIndex, FieldInfo),
+    InstanceField(FieldInfo),
+    Method(MethodIndex, usize), // The parameter count includes this
+    VirtualMethod(VirtualMethodIndex, usize), // The parameter count includes this
+}
+
+/// The bytecode of a method as executed by the interpreter
+pub struct QuickenedCode {
+    code: Box<[Cell<u8>]>, // Cells because instructions are rewritten while the method may be running
+    operands: AppendList<QuickOperand>,
+    branches: BranchProfile,
+    breakpoints: RefCell<HashMap<usize, PatchedInstruction>>,
+}
+
+/// The instruction that a breakpoint has replaced
+#[derive(Debug, Clone, Copy)]
+struct PatchedInstruction {
+    opcode: u8,
+    temporary: bool, // Set by the debugger to step and removed by the next hit in the method
+}
+
+impl QuickenedCode {
+    pub fn new(code: &[u8], exception_table: &[ExceptionTableEntry]) -> Self {
+        let quickened_code = Self {
+            code: code.iter().copied().map(Cell::new).collect(),
+            operands: AppendList::new(),
+            branches: BranchProfile::new(code),
+            breakpoints: RefCell::new(HashMap::new()),
+        };
+        fusion::fuse(code, &quickened_code.code);
+        tail_call::mark(code, &quickened_code.code, exception_table);
+        quickened_code
+    }
+
+    pub fn code(&self) -> &[Cell<u8>] {
+        &self.code
+    }
+
+    /// The taken/not-taken counts of the conditional branches, which the JIT uses to lay out the code
+    pub fn branches(&self) -> &BranchProfile {
+        &self.branches
+    }
+
+    /// Replaces the instruction at pc by its quickened variant and returns the opcode of the quickened instruction
+    pub fn quicken(&self, pc: usize, operand: QuickOperand) -> u8 {
+        let opcode = self.opcode_at(pc);
+        let quickened_opcode = match (opcode, operand) {
+            (bytecode::GETSTATIC, QuickOperand::StaticField(..)) => bytecode::GETSTATIC_QUICK,
+            (bytecode::PUTSTATIC, QuickOperand::StaticField(..)) => bytecode::PUTSTATIC_QUICK,
+            (bytecode::GETFIELD, QuickOperand::InstanceField(..)) => bytecode::GETFIELD_QUICK,
+            (bytecode::PUTFIELD, QuickOperand::InstanceField(..)) => bytecode::PUTFIELD_QUICK,
+            (bytecode::INVOKEVIRTUAL, QuickOperand::VirtualMethod(..)) => {
+                bytecode::INVOKEVIRTUAL_QUICK
+            }
+            (
+                bytecode::INVOKEVIRTUAL | bytecode::INVOKESPECIAL | bytecode::INVOKESTATIC,
+                QuickOperand::Method(..),
+            ) => bytecode::INVOKENONVIRTUAL_QUICK,
+            (bytecode::INVOKESTATIC_TAIL, QuickOperand::Method(..)) => {
+                bytecode::INVOKESTATIC_TAIL_QUICK
+            }
+            // The instruction has already been quickened while its operand was being resolved, e.g. by a static
+            // initializer that calls this method
+            _ => return opcode,
+        };
+
+        // Every quickened instruction is at least three bytes long, so the index always fits into the operand
+        let index = self.operands.len() as u16;
+        self.operands.push(operand);
+        let [high, low] = index.to_be_bytes();
+        self.code[pc + 1].set(high);
+        self.code[pc + 2].set(low);
+        match self.breakpoints.borrow_mut().get_mut(&pc) {
+            Some(patched) => patched.opcode = quickened_opcode,
+            None => self.code[pc].set(quickened_opcode),
+        }
+        quickened_opcode
+    }
+
+    /// The opcode of the instruction at pc, which is the replaced opcode if a breakpoint is set at pc
+    pub fn opcode_at(&self, pc: usize) -> u8 {
+        match self.breakpoints.borrow().get(&pc) {
+            Some(patched) => patched.opcode,
+            None => self.code[pc].get(),
+        }
+    }
+
+    /// Sets a breakpoint at the instruction at pc (which must be the start of an instruction in the original code).
+    /// A temporary breakpoint doesn't replace a breakpoint that is already set at pc.
+    /// The superinstructions of the method are split up again, because they would execute the instructions of
+    /// their sequence without dispatching to them.
+    pub fn set_breakpoint(&self, pc: usize, original: &[u8], temporary: bool) {
+        let mut start = 0;
+        while let Some(length) = bytecode::instruction_length(original, start) {
+            if fusion::is_fused(self.code[start].get()) {
+                self.code[start].set(original[start]);
+            }
+            start += length;
+        }
+
+        let mut breakpoints = self.breakpoints.borrow_mut();
+        if let Some(patched) = breakpoints.get_mut(&pc) {
+            patched.temporary &= temporary;
+            return;
+        }
+        breakpoints.insert(
+            pc,
+            PatchedInstruction {
+                opcode: self.code[pc].get(),
+                temporary,
+            },
+        );
+        self.code[pc].set(bytecode::BREAKPOINT);
+    }
+
+    /// Restores the instruction at pc, returns whether a breakpoint has been set
+    pub fn clear_breakpoint(&self, pc: usize) -> bool {
+        match self.breakpoints.borrow_mut().remove(&pc) {
+            Some(patched) => {
+                self.code[pc].set(patched.opcode);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Restores the instructions of all temporary breakpoints
+    pub fn clear_temporary_breakpoints(&self) {
+        self.breakpoints.borrow_mut().retain(|pc, patched| {
+            if patched.temporary {
+                self.code[*pc].set(patched.opcode);
+            }
+            !patched.temporary
+        });
+    }
+
+    pub fn has_breakpoints(&self) -> bool {
+        !self.breakpoints.borrow().is_empty()
+    }
+
+    pub fn static_field(&self, pc: usize) -> (ClassIndex, FieldInfo) {
+        match self.operand(pc) {
+            QuickOperand::StaticField(class, field) => (class, field),
+            operand => panic!(
+                "Expected a static field at pc {}, but got {:?}",
+                pc, operand
+            ),
+        }
+    }
+
+    pub fn instance_field(&self, pc: usize) -> FieldInfo {
+        match self.operand(pc) {
+            QuickOperand::InstanceField(field) => field,
+            operand => panic!(
+                "Expected an instance field at pc {}, but got {:?}",
+                pc, operand
+            ),
+        }
+    }
+
+    /// Returns (method, parameter count)
+    pub fn method(&self, pc: usize) -> (MethodIndex, usize) {
+        match self.operand(pc) {
+            QuickOperand::Method(method, parameter_count) => (method, parameter_count),
+            operand => panic!("Expected a method at pc {}, but got {:?}", pc, operand),
+        }
+    }
+
+    /// Returns (virtual method, parameter count)
+    pub fn virtual_method(&self, pc: usize) -> (VirtualMethodIndex, usize) {
+        match self.operand(pc) {
+            QuickOperand::VirtualMethod(method, parameter_count) => (method, parameter_count),
+            operand => panic!(
+                "Expected a virtual method at pc {}, but got {:?}",
+                pc, operand
+            ),
+        }
+    }
+
+    fn operand(&self, pc: usize) -> QuickOperand {
+        let index = u16::from_be_bytes([self.code[pc + 1].get(), self.code[pc + 2].get()]);
+        self.operands[index as usize]
+    }
+}
+
+/// Whether the instruction is replaced by a quickened instruction on its first execution
+pub fn is_quickenable(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        bytecode::GETSTATIC
+            | bytecode::PUTSTATIC
+            | bytecode::GETFIELD
+            | bytecode::PUTFIELD
+            | bytecode::INVOKEVIRTUAL
+            | bytecode::INVOKESPECIAL
+            | bytecode::INVOKESTATIC
+            | bytecode::INVOKESTATIC_TAIL
+    )
+}
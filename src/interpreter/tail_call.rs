@@ -0,0 +1,45 @@
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{bytecode, model::method::ExceptionTableEntry};
+
+// Recursive code in a functional style (e.g. loops written as static methods that call themselves as their last
+// action) exhausts the fixed size stack quickly, because every call needs a new frame. An invokestatic that is
+// directly followed by a return is marked as a tail call while the code is pre-decoded. Once the call has been
+// resolved, a tail call of the method itself reuses the frame of the caller: the arguments replace the locals and the
+// interpreter continues at the start of the method. Calls of other methods are executed as usual.
+// Tail calls inside of exception handler ranges aren't marked, because the handlers of the caller would see the
+// exceptions of the callee at a different pc. Eliminated calls don't show up in stack traces or observers.
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Disables the elimination of tail calls for all methods that are loaded afterwards (-XX:-EliminateTailCalls)
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Replaces every invokestatic in tail position in code by INVOKESTATIC_TAIL
+pub fn mark(original: &[u8], code: &[Cell<u8>], exception_table: &[ExceptionTableEntry]) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mut pc = 0;
+    while pc < original.len() {
+        let Some(length) = bytecode::instruction_length(original, pc) else {
+            // Truncated code is reported by the interpreter once it gets there
+            return;
+        };
+        if original[pc] == bytecode::INVOKESTATIC
+            && original
+                .get(pc + length)
+                .is_some_and(|next| (bytecode::IRETURN..=bytecode::RETURN).contains(next))
+            && !exception_table.iter().any(|entry| entry.covers(pc))
+        {
+            code[pc].set(bytecode::INVOKESTATIC_TAIL);
+        }
+        pc += length;
+    }
+}
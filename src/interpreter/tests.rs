@@ -0,0 +1,270 @@
+use crate::{jvm::Jvm, model::value::JvmValue, testing};
+
+/// Each method of NullChecks dereferences null with one instruction and returns 1 if it caught the
+/// NullPointerException. The methods are called twice, because instructions are quickened on their first execution.
+fn assert_throws_null_pointer(method: &str) {
+    let mut jvm = testing::jvm();
+    for _ in 0..2 {
+        assert_eq!(
+            testing::call_int(&mut jvm, "NullChecks", method),
+            1,
+            "{}",
+            method
+        );
+    }
+}
+
+#[test]
+fn getfield_of_null_throws() {
+    assert_throws_null_pointer("getField");
+}
+
+#[test]
+fn putfield_of_null_throws() {
+    assert_throws_null_pointer("putField");
+}
+
+#[test]
+fn fused_getfield_of_null_throws() {
+    assert_throws_null_pointer("loadFieldOfLocal");
+}
+
+#[test]
+fn invokevirtual_of_null_throws() {
+    assert_throws_null_pointer("invokeVirtual");
+}
+
+#[test]
+fn direct_invokevirtual_of_null_throws() {
+    assert_throws_null_pointer("invokeFinal");
+}
+
+#[test]
+fn invokespecial_of_null_throws() {
+    assert_throws_null_pointer("invokeSpecial");
+}
+
+#[test]
+fn invokeinterface_of_null_throws() {
+    assert_throws_null_pointer("invokeInterface");
+}
+
+#[test]
+fn arraylength_of_null_throws() {
+    assert_throws_null_pointer("arrayLength");
+}
+
+#[test]
+fn array_load_of_null_throws() {
+    assert_throws_null_pointer("arrayLoad");
+}
+
+#[test]
+fn array_store_of_null_throws() {
+    assert_throws_null_pointer("arrayStore");
+}
+
+#[test]
+fn athrow_of_null_throws() {
+    assert_throws_null_pointer("throwNull");
+}
+
+#[test]
+fn deep_recursion_throws_stack_overflow_error() {
+    let mut jvm = testing::jvm();
+    assert!(testing::call_int(&mut jvm, "Recursion", "overflow") > 10);
+    // The stack is usable again after the error has been caught
+    assert!(testing::call_int(&mut jvm, "Recursion", "overflow") > 10);
+}
+
+fn drem(jvm: &mut Jvm, dividend: f64, divisor: f64) -> f64 {
+    let args = [JvmValue::from(dividend), JvmValue::from(divisor)];
+    let result = jvm.call_static("Arithmetic", "drem", "(DD)D", &args);
+    unsafe { result.unwrap().double }
+}
+
+fn frem(jvm: &mut Jvm, dividend: f32, divisor: f32) -> f32 {
+    let args = [JvmValue::from(dividend), JvmValue::from(divisor)];
+    let result = jvm.call_static("Arithmetic", "frem", "(FF)F", &args);
+    unsafe { result.unwrap().float }
+}
+
+/// The dividend, the divisor and the remainder as computed by HotSpot. The quotient is truncated, so the remainder
+/// has the sign of the dividend, which also holds for zero remainders.
+const REMAINDERS: &[(f64, f64, f64)] = &[
+    (5.5, 2.0, 1.5),
+    (-5.5, 2.0, -1.5),
+    (5.5, -2.0, 1.5),
+    (-5.5, -2.0, -1.5),
+    (5.0, 3.0, 2.0), // IEEE 754 remainder would round the quotient and result in -1
+    (-5.0, 3.0, -2.0),
+    (1099511627776.0, 3.0, 1.0), // 2^40, whose quotient needs more bits than a float has
+    (4.0, -2.0, 0.0),
+    (-4.0, 2.0, -0.0),
+    (0.0, 3.0, 0.0),
+    (-0.0, 3.0, -0.0),
+    (3.0, f64::INFINITY, 3.0),
+    (-3.0, f64::NEG_INFINITY, -3.0),
+    (-0.0, f64::INFINITY, -0.0),
+    (f64::INFINITY, 2.0, f64::NAN),
+    (f64::NEG_INFINITY, 2.0, f64::NAN),
+    (f64::INFINITY, f64::INFINITY, f64::NAN),
+    (3.0, 0.0, f64::NAN),
+    (3.0, -0.0, f64::NAN),
+    (0.0, 0.0, f64::NAN),
+    (f64::NAN, 1.0, f64::NAN),
+    (1.0, f64::NAN, f64::NAN),
+];
+
+#[test]
+fn drem_follows_the_remainder_rules_of_the_specification() {
+    let mut jvm = testing::jvm();
+    for &(dividend, divisor, expected) in REMAINDERS {
+        let remainder = drem(&mut jvm, dividend, divisor);
+        assert!(
+            remainder.to_bits() == expected.to_bits() || remainder.is_nan() && expected.is_nan(),
+            "{} % {} = {}, expected {}",
+            dividend,
+            divisor,
+            remainder,
+            expected
+        );
+    }
+    assert_eq!(drem(&mut jvm, f64::MAX, f64::MIN_POSITIVE), 0.0);
+    assert_eq!(drem(&mut jvm, 3.0 * 5e-324, 2.0 * 5e-324), 5e-324); // Subnormal operands
+}
+
+#[test]
+fn frem_follows_the_remainder_rules_of_the_specification() {
+    let mut jvm = testing::jvm();
+    for &(dividend, divisor, expected) in REMAINDERS {
+        let (dividend, divisor, expected) = (dividend as f32, divisor as f32, expected as f32);
+        let remainder = frem(&mut jvm, dividend, divisor);
+        assert!(
+            remainder.to_bits() == expected.to_bits() || remainder.is_nan() && expected.is_nan(),
+            "{} % {} = {}, expected {}",
+            dividend,
+            divisor,
+            remainder,
+            expected
+        );
+    }
+    assert_eq!(frem(&mut jvm, f32::MAX, f32::MIN_POSITIVE), 0.0);
+}
+
+fn call_char(jvm: &mut Jvm, method: &str, value: i32) -> i32 {
+    let result = jvm.call_static("Arithmetic", method, "(I)I", &[JvmValue::from(value)]);
+    unsafe { result.unwrap().int }
+}
+
+#[test]
+fn i2c_zero_extends_the_low_16_bits() {
+    let mut jvm = testing::jvm();
+    for (value, expected) in [
+        (0x41, 0x41),
+        (0xff, 0xff),
+        (0x100, 0x100),   // Not truncated to a byte
+        (0x8000, 0x8000), // Not sign-extended
+        (0xffff, 0xffff),
+        (0x1_0041, 0x41),
+        (-1, 0xffff),
+        (i32::MIN, 0),
+        (0xd800, 0xd800), // The first high surrogate
+        (0xdbff, 0xdbff),
+        (0xdc00, 0xdc00), // The first low surrogate
+        (0xdfff, 0xdfff),
+        (-0x2800, 0xd800),
+        (0x10_dfff, 0xdfff),
+    ] {
+        assert_eq!(
+            call_char(&mut jvm, "i2c", value),
+            expected,
+            "(char) {:#x}",
+            value
+        );
+        // char arrays keep the zero-extended value
+        assert_eq!(
+            call_char(&mut jvm, "storeChar", value),
+            expected,
+            "{:#x}",
+            value
+        );
+    }
+}
+
+#[test]
+fn surrogates_of_supplementary_code_points() {
+    let mut jvm = testing::jvm();
+    for code_point in ['\u{10000}', '\u{1f600}', '\u{10ffff}'] {
+        let mut expected = [0; 2];
+        code_point.encode_utf16(&mut expected);
+        let code_point = code_point as i32;
+        assert_eq!(
+            call_char(&mut jvm, "highSurrogate", code_point),
+            expected[0].into()
+        );
+        assert_eq!(
+            call_char(&mut jvm, "lowSurrogate", code_point),
+            expected[1].into()
+        );
+    }
+}
+
+#[test]
+fn failed_static_initializer_makes_the_class_unusable() {
+    let mut jvm = testing::jvm();
+    let error = jvm
+        .call_static("FailingInitializer", "value", "()I", &[])
+        .unwrap_err();
+    assert_eq!(error.class_name, "java/lang/ExceptionInInitializerError");
+    let cause = jvm.get_field(error.throwable.unwrap(), "cause").unwrap();
+    let cause = jvm.get_field(unsafe { cause.reference }, "detailMessage");
+    assert_eq!(
+        jvm.read_string(unsafe { cause.unwrap().reference })
+            .unwrap(),
+        "/ by zero"
+    );
+    // The class is erroneous now, the static initializer doesn't run again
+    let error = jvm
+        .call_static("FailingInitializer", "value", "()I", &[])
+        .unwrap_err();
+    assert_eq!(error.class_name, "java/lang/NoClassDefFoundError");
+}
+
+#[test]
+fn unsupported_opcode_throws_internal_error() {
+    let mut jvm = testing::jvm();
+    let error = jvm
+        .call_static(
+            "UnsupportedOpcodes",
+            "shiftLeft",
+            "(I)I",
+            &[JvmValue::from(1)],
+        )
+        .unwrap_err();
+    assert_eq!(error.class_name, "java/lang/InternalError");
+    assert_eq!(
+        error.message.as_deref(),
+        Some("the interpreter doesn't support the opcode 0x78 yet")
+    );
+    // Like other VM errors, it can be caught
+    assert_eq!(
+        testing::call_int(&mut jvm, "UnsupportedOpcodes", "catchInternalError"),
+        -1
+    );
+}
+
+#[test]
+fn native_frames_are_marked_in_stack_traces() {
+    // The native method isn't bound, so it throws an UnsatisfiedLinkError from its own frame
+    let mut jvm = testing::jvm();
+    let error = jvm
+        .call_static("NativeFrames", "unbound", "()I", &[])
+        .unwrap_err();
+    assert_eq!(error.class_name, "java/lang/UnsatisfiedLinkError");
+    assert!(error.stack_trace[0].is_native);
+    assert_eq!(
+        error.stack_trace[0].to_string(),
+        "NativeFrames.unbound(Native Method)"
+    );
+}
@@ -0,0 +1,82 @@
+use std::{cell::Cell, fmt::Display};
+
+// The state of the current thread as reported by Thread.getState and the thread dump. Java code only runs on one
+// thread, which is RUNNABLE unless it sleeps, waits or blocks in one of the VM's implementations of these operations.
+// These enter their state with ThreadState::enter for as long as they don't return to Java code.
+
+thread_local! {
+    static STATE: Cell<ThreadState> = const { Cell::new(ThreadState::Runnable) };
+}
+
+/// The states of java.lang.Thread.State
+#[allow(dead_code)] // Only sleeping is implemented so far, the VM has no monitors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    New,
+    Runnable,
+    Blocked,      // Waiting for a monitor
+    Waiting,      // Object.wait, Thread.join or LockSupport.park without a timeout
+    TimedWaiting, // Thread.sleep and the above with a timeout
+    Terminated,
+}
+
+impl ThreadState {
+    /// The state of the current thread
+    pub fn current() -> Self {
+        STATE.with(|state| state.get())
+    }
+
+    /// Puts the current thread into this state until the returned guard is dropped
+    pub fn enter(self) -> StateGuard {
+        StateGuard {
+            previous: STATE.with(|state| state.replace(self)),
+        }
+    }
+
+    /// The value of the field threadStatus of java.lang.Thread, from which Thread.getState derives the state
+    /// (JVMTI thread state bits, see jdk.internal.misc.VM.toThreadState)
+    pub fn thread_status(self) -> i32 {
+        const ALIVE: i32 = 0x0001;
+        const TERMINATED: i32 = 0x0002;
+        const RUNNABLE: i32 = 0x0004;
+        const WAITING_INDEFINITELY: i32 = 0x0010;
+        const WAITING_WITH_TIMEOUT: i32 = 0x0020;
+        const WAITING: i32 = 0x0080;
+        const BLOCKED_ON_MONITOR_ENTER: i32 = 0x0400;
+        match self {
+            Self::New => 0,
+            Self::Runnable => ALIVE | RUNNABLE,
+            Self::Blocked => ALIVE | BLOCKED_ON_MONITOR_ENTER,
+            Self::Waiting => ALIVE | WAITING | WAITING_INDEFINITELY,
+            Self::TimedWaiting => ALIVE | WAITING | WAITING_WITH_TIMEOUT,
+            Self::Terminated => TERMINATED,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::New => "NEW",
+            Self::Runnable => "RUNNABLE",
+            Self::Blocked => "BLOCKED",
+            Self::Waiting => "WAITING",
+            Self::TimedWaiting => "TIMED_WAITING",
+            Self::Terminated => "TERMINATED",
+        }
+    }
+}
+
+impl Display for ThreadState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub struct StateGuard {
+    previous: ThreadState,
+}
+
+impl Drop for StateGuard {
+    fn drop(&mut self) {
+        STATE.with(|state| state.set(self.previous));
+    }
+}
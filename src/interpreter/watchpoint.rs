@@ -0,0 +1,181 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use crate::model::{
+    class_library::{ClassIndex, ClassLibrary},
+    field::FieldInfo,
+    heap::NULL_POINTER,
+    method::MethodTable,
+    types::JvmType,
+    value::JvmValue,
+};
+
+use super::frame;
+
+// Watchpoints report every read or write of a field by the interpreter (-XX:WatchField=<class>.<field>[:<option>]*),
+// e.g. to find the code that mutates a static of the guest. The options are
+// - read or write to only watch one kind of access (both are watched by default),
+// - break to stop in the native debugger (SIGTRAP) after the access has been reported with the Java stack.
+// Compiled code doesn't check the watchpoints, so the JIT doesn't compile methods that access fields while watchpoints
+// are set and they keep running in the interpreter. Instance fields are also watched in the subclasses of the class.
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static WATCHPOINTS: Mutex<Vec<Watchpoint>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    class: String, // Internal form
+    field: String,
+    reads: bool,
+    writes: bool,
+    break_on_access: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Watchpoint {
+    /// Parses the value of -XX:WatchField, e.g. "java/lang/System.out:write:break"
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(':');
+        let (class, field) = parts.next()?.rsplit_once('.')?;
+        if class.is_empty() || field.is_empty() {
+            return None;
+        }
+        let mut watchpoint = Self {
+            class: class.replace('.', "/"),
+            field: field.to_string(),
+            reads: true,
+            writes: true,
+            break_on_access: false,
+        };
+        for option in parts {
+            match option {
+                "read" => watchpoint.writes = false,
+                "write" => watchpoint.reads = false,
+                "break" => watchpoint.break_on_access = true,
+                _ => return None,
+            }
+        }
+        if !watchpoint.reads && !watchpoint.writes {
+            return None;
+        }
+        Some(watchpoint)
+    }
+
+    fn watches(&self, access: Access) -> bool {
+        match access {
+            Access::Read => self.reads,
+            Access::Write => self.writes,
+        }
+    }
+
+    /// The watched field as (class, field), None as long as the class hasn't been loaded
+    fn resolve(&self, is_static: bool, classes: &ClassLibrary) -> Option<(ClassIndex, FieldInfo)> {
+        let class = classes.resolve(classes.find_loaded(&self.class)?);
+        class.find_field(&self.field, is_static, classes)
+    }
+}
+
+pub fn install(watchpoints: Vec<Watchpoint>) {
+    ENABLED.store(!watchpoints.is_empty(), Ordering::Release);
+    *WATCHPOINTS.lock().unwrap() = watchpoints;
+}
+
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Reports the access of the static field of the class by the instruction of the newest interpreter frame
+pub fn static_field_access(
+    access: Access,
+    class: ClassIndex,
+    field: FieldInfo,
+    value: JvmValue,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) {
+    report(access, true, value, classes, methods, |watched, _| {
+        watched == (class, field.offset)
+    });
+}
+
+/// Reports the access of the field of an instance of the class by the instruction of the newest interpreter frame
+pub fn instance_field_access(
+    access: Access,
+    class: ClassIndex,
+    field: FieldInfo,
+    value: JvmValue,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) {
+    report(
+        access,
+        false,
+        value,
+        classes,
+        methods,
+        |watched, classes| {
+            watched.1 == field.offset && classes.resolve(class).is_subclass_of(watched.0, classes)
+        },
+    );
+}
+
+fn report<F: Fn((ClassIndex, usize), &ClassLibrary) -> bool>(
+    access: Access,
+    is_static: bool,
+    value: JvmValue,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    matches: F,
+) {
+    let watchpoints = WATCHPOINTS.lock().unwrap();
+    for watchpoint in watchpoints
+        .iter()
+        .filter(|watchpoint| watchpoint.watches(access))
+    {
+        let Some((class, field)) = watchpoint.resolve(is_static, classes) else {
+            continue;
+        };
+        if !matches((class, field.offset), classes) {
+            continue;
+        }
+        log::info!(
+            "Watchpoint: {} {} {}.{} = {}",
+            frame::newest_frame()
+                .map(|(method, pc)| frame::location(method, pc, classes, methods))
+                .unwrap_or_else(|| "the VM".to_string()),
+            match access {
+                Access::Read => "reads",
+                Access::Write => "writes",
+            },
+            watchpoint.class,
+            watchpoint.field,
+            format_value(value, field.ty)
+        );
+        if watchpoint.break_on_access {
+            frame::print_stack_trace(classes, methods);
+            unsafe { libc::raise(libc::SIGTRAP) };
+        }
+    }
+}
+
+fn format_value(value: JvmValue, ty: JvmType) -> String {
+    match ty {
+        JvmType::Long => value.long().0.to_string(),
+        JvmType::Float => value.float().0.to_string(),
+        JvmType::Double => value.double().0.to_string(),
+        JvmType::Reference if value.reference().to_heap_index() == NULL_POINTER => {
+            "null".to_string()
+        }
+        JvmType::Reference => format!("@{}", value.reference().to_heap_index().as_u32()),
+        JvmType::Boolean => (value.int().0 != 0).to_string(),
+        _ => value.int().0.to_string(),
+    }
+}
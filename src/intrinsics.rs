@@ -0,0 +1,92 @@
+use std::rc::Rc;
+
+use crate::{interpreter::ExecutionError, model::value::JvmValue, native::NativeFunction};
+
+// Intrinsics replace the bytecode of small, hot methods of the class library by a single Rust operation, e.g. the
+// unsigned arithmetic and bit twiddling of Integer and Long that hashing and parsing code calls in its inner loops.
+// An intrinsified method is bound like a native method when its class is loaded, so the interpreter and compiled
+// callers call it through the call table as usual. The intrinsics must behave exactly like the bytecode they
+// replace, including the exceptions they throw.
+
+type Intrinsic = fn(&[JvmValue]) -> Result<JvmValue, ExecutionError>;
+
+/// (class name, method name and descriptor, implementation)
+const INTRINSICS: &[(&str, &str, Intrinsic)] = &[
+    ("java/lang/Integer", "compareUnsigned(II)I", |args| {
+        Ok(JvmValue::from(int(args[0]).cmp(&int(args[1])) as i32))
+    }),
+    ("java/lang/Integer", "divideUnsigned(II)I", |args| {
+        Ok(JvmValue::from(
+            int(args[0])
+                .checked_div(int(args[1]))
+                .ok_or(ExecutionError::DivisionByZero)? as i32,
+        ))
+    }),
+    ("java/lang/Integer", "remainderUnsigned(II)I", |args| {
+        Ok(JvmValue::from(
+            int(args[0])
+                .checked_rem(int(args[1]))
+                .ok_or(ExecutionError::DivisionByZero)? as i32,
+        ))
+    }),
+    ("java/lang/Integer", "numberOfLeadingZeros(I)I", |args| {
+        Ok(JvmValue::from(int(args[0]).leading_zeros() as i32))
+    }),
+    ("java/lang/Integer", "numberOfTrailingZeros(I)I", |args| {
+        Ok(JvmValue::from(int(args[0]).trailing_zeros() as i32))
+    }),
+    ("java/lang/Integer", "bitCount(I)I", |args| {
+        Ok(JvmValue::from(int(args[0]).count_ones() as i32))
+    }),
+    ("java/lang/Integer", "reverseBytes(I)I", |args| {
+        Ok(JvmValue::from(int(args[0]).swap_bytes() as i32))
+    }),
+    ("java/lang/Long", "compareUnsigned(JJ)I", |args| {
+        Ok(JvmValue::from(long(args[0]).cmp(&long(args[1])) as i32))
+    }),
+    ("java/lang/Long", "divideUnsigned(JJ)J", |args| {
+        Ok(JvmValue::from(
+            long(args[0])
+                .checked_div(long(args[1]))
+                .ok_or(ExecutionError::DivisionByZero)? as i64,
+        ))
+    }),
+    ("java/lang/Long", "remainderUnsigned(JJ)J", |args| {
+        Ok(JvmValue::from(
+            long(args[0])
+                .checked_rem(long(args[1]))
+                .ok_or(ExecutionError::DivisionByZero)? as i64,
+        ))
+    }),
+    ("java/lang/Long", "numberOfLeadingZeros(J)I", |args| {
+        Ok(JvmValue::from(long(args[0]).leading_zeros() as i32))
+    }),
+    ("java/lang/Long", "numberOfTrailingZeros(J)I", |args| {
+        Ok(JvmValue::from(long(args[0]).trailing_zeros() as i32))
+    }),
+    ("java/lang/Long", "bitCount(J)I", |args| {
+        Ok(JvmValue::from(long(args[0]).count_ones() as i32))
+    }),
+    ("java/lang/Long", "reverseBytes(J)J", |args| {
+        Ok(JvmValue::from(long(args[0]).swap_bytes() as i64))
+    }),
+];
+
+/// The argument as an unsigned int
+fn int(value: JvmValue) -> u32 {
+    i32::from(value.int()) as u32
+}
+
+/// The argument as an unsigned long
+fn long(value: JvmValue) -> u64 {
+    i64::from(value.long()) as u64
+}
+
+/// Returns the intrinsic that replaces the bytecode of the method, if there is one
+pub fn lookup(class_name: &str, method_name: &str, descriptor: &str) -> Option<Rc<NativeFunction>> {
+    let (_, _, intrinsic) = INTRINSICS.iter().find(|(class, method, _)| {
+        *class == class_name && method.strip_prefix(method_name) == Some(descriptor)
+    })?;
+    let intrinsic = *intrinsic;
+    Some(Rc::new(move |_, args| intrinsic(args)))
+}
@@ -0,0 +1,562 @@
+use std::fmt::Write;
+
+use crate::{
+    bytecode::{self, OPCODES},
+    class_parser::{self, attribute, ParsingError},
+    model::{
+        access_flags::{ClassFlags, FieldFlags, MethodFlags},
+        class_file::{AttributeContent, AttributeInfo, ClassFile, CodeAttribute, MemberInfo},
+        constant_pool::{
+            ConstantPool, ConstantPoolEntry, ConstantPoolIndex, FieldReference,
+            InterfaceMethodReference, MethodReference,
+        },
+    },
+};
+
+// Prints a class file like javap -v (-XX:PrintClassFile), from the contents that the parser retains on request. Unlike
+// javap, the class file has to pass the checks of the parser, and the attributes that the VM doesn't interpret are
+// only listed with their length.
+
+/// Parses the class file and describes its complete structure
+pub fn print_class_file(bytes: &[u8]) -> Result<String, ParsingError> {
+    let (file, _, _) = class_parser::parse_retaining(bytes)?;
+    Ok(describe(&file))
+}
+
+fn describe(file: &ClassFile) -> String {
+    let contents = file
+        .contents()
+        .expect("The contents of the class file have been retained");
+    let constant_pool = &contents.constant_pool;
+    let mut output = String::new();
+    let out = &mut output;
+
+    writeln!(
+        out,
+        "Classfile version {}.{}",
+        file.major_version(),
+        file.minor_version()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  flags: (0x{:04x}) {}",
+        contents.access_flags,
+        flag_names(format!(
+            "{:?}",
+            ClassFlags::from_bits_truncate(contents.access_flags)
+        ))
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  this_class: {}",
+        reference(constant_pool, contents.this_class)
+    )
+    .unwrap();
+    if contents.super_class.is_valid() {
+        writeln!(
+            out,
+            "  super_class: {}",
+            reference(constant_pool, contents.super_class)
+        )
+        .unwrap();
+    }
+    for interface in &contents.interfaces {
+        writeln!(out, "  interface: {}", reference(constant_pool, *interface)).unwrap();
+    }
+    writeln!(
+        out,
+        "  interfaces: {}, fields: {}, methods: {}, attributes: {}",
+        contents.interfaces.len(),
+        contents.fields.len(),
+        contents.methods.len(),
+        contents.attributes.len()
+    )
+    .unwrap();
+
+    writeln!(out, "Constant pool:").unwrap();
+    for (position, entry) in constant_pool.entries().iter().enumerate() {
+        if matches!(entry, ConstantPoolEntry::Empty) {
+            continue;
+        }
+        let index = ConstantPoolIndex::from(position as u16 + 1);
+        writeln!(
+            out,
+            "{:>6} = {:<18} {}",
+            format!("#{}", index.as_u16()),
+            tag(entry),
+            symbolic(constant_pool, index)
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "{{").unwrap();
+    for field in &contents.fields {
+        let flags = format!("{:?}", FieldFlags::from_bits_truncate(field.access_flags));
+        describe_member(out, constant_pool, field, flags);
+    }
+    for method in &contents.methods {
+        let flags = format!("{:?}", MethodFlags::from_bits_truncate(method.access_flags));
+        describe_member(out, constant_pool, method, flags);
+    }
+    writeln!(out, "}}").unwrap();
+    describe_attributes(out, constant_pool, &contents.attributes, "");
+    output
+}
+
+fn describe_member(
+    out: &mut String,
+    constant_pool: &ConstantPool,
+    member: &MemberInfo,
+    flags: String,
+) {
+    writeln!(
+        out,
+        "  {}",
+        constant_pool.get_utf8(member.name).unwrap_or("<invalid>")
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    descriptor: {}",
+        constant_pool
+            .get_utf8(member.descriptor)
+            .unwrap_or("<invalid>")
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    flags: (0x{:04x}) {}",
+        member.access_flags,
+        flag_names(flags)
+    )
+    .unwrap();
+    describe_attributes(out, constant_pool, &member.attributes, "    ");
+    writeln!(out).unwrap();
+}
+
+fn describe_attributes(
+    out: &mut String,
+    constant_pool: &ConstantPool,
+    attributes: &[AttributeInfo],
+    indent: &str,
+) {
+    for attribute in attributes {
+        let name = constant_pool
+            .get_utf8(attribute.name)
+            .unwrap_or("<invalid>");
+        match &attribute.content {
+            AttributeContent::Code(code) => describe_code(out, constant_pool, code, indent),
+            // Attributes that only consist of a constant pool index
+            AttributeContent::Raw(info)
+                if info.len() == 2
+                    && matches!(
+                        name,
+                        attribute::SOURCE_FILE
+                            | attribute::SIGNATURE
+                            | attribute::CONSTANT_VALUE
+                            | attribute::NEST_HOST
+                            | attribute::MODULE_MAIN_CLASS
+                    ) =>
+            {
+                let index = ConstantPoolIndex::from(u16::from_be_bytes([info[0], info[1]]));
+                writeln!(
+                    out,
+                    "{}{}: {}",
+                    indent,
+                    name,
+                    reference(constant_pool, index)
+                )
+                .unwrap();
+            }
+            AttributeContent::Raw(info) => {
+                writeln!(out, "{}{}: {} bytes", indent, name, info.len()).unwrap()
+            }
+        }
+    }
+}
+
+fn describe_code(
+    out: &mut String,
+    constant_pool: &ConstantPool,
+    code: &CodeAttribute,
+    indent: &str,
+) {
+    writeln!(out, "{}Code:", indent).unwrap();
+    writeln!(
+        out,
+        "{}  stack={}, locals={}, length={}",
+        indent,
+        code.max_stack,
+        code.max_locals,
+        code.code.len()
+    )
+    .unwrap();
+    let mut pc = 0;
+    while pc < code.code.len() {
+        let Some(length) = bytecode::instruction_length(&code.code, pc) else {
+            writeln!(out, "{}  {:>5}: <truncated>", indent, pc).unwrap();
+            break;
+        };
+        writeln!(
+            out,
+            "{}  {:>5}: {}",
+            indent,
+            pc,
+            instruction(constant_pool, &code.code, pc)
+        )
+        .unwrap();
+        pc += length;
+    }
+    if !code.exception_table.is_empty() {
+        writeln!(out, "{}  Exception table:", indent).unwrap();
+        writeln!(out, "{}     from    to  target type", indent).unwrap();
+        for handler in &code.exception_table {
+            let catch_type = if handler.catch_type.is_valid() {
+                reference(constant_pool, handler.catch_type)
+            } else {
+                "any".to_string()
+            };
+            writeln!(
+                out,
+                "{}    {:>5} {:>5} {:>7} {}",
+                indent, handler.start_pc, handler.end_pc, handler.handler_pc, catch_type
+            )
+            .unwrap();
+        }
+    }
+    describe_attributes(
+        out,
+        constant_pool,
+        &code.attributes,
+        &format!("{}  ", indent),
+    );
+}
+
+/// The mnemonic and the operands of the instruction at pc, which is complete (see bytecode::instruction_length)
+fn instruction(constant_pool: &ConstantPool, code: &[u8], pc: usize) -> String {
+    let opcode = code[pc];
+    let mnemonic = mnemonic(opcode);
+    let u8_at = |offset: usize| code[pc + offset];
+    let u16_at = |offset: usize| u16::from_be_bytes([code[pc + offset], code[pc + offset + 1]]);
+    let i32_at = |position: usize| {
+        i32::from_be_bytes([
+            code[position],
+            code[position + 1],
+            code[position + 2],
+            code[position + 3],
+        ])
+    };
+    let target = |offset: i64| pc as i64 + offset;
+
+    match opcode {
+        bytecode::LDC => format!(
+            "{} {}",
+            mnemonic,
+            reference(constant_pool, (u8_at(1) as u16).into())
+        ),
+        bytecode::LDC_W
+        | bytecode::LDC2_W
+        | bytecode::GETSTATIC
+        | bytecode::PUTSTATIC
+        | bytecode::GETFIELD
+        | bytecode::PUTFIELD
+        | bytecode::INVOKEVIRTUAL
+        | bytecode::INVOKESPECIAL
+        | bytecode::INVOKESTATIC
+        | bytecode::INVOKEINTERFACE
+        | bytecode::INVOKEDYNAMIC
+        | bytecode::NEW
+        | bytecode::ANEWARRAY
+        | bytecode::CHECKCAST
+        | bytecode::INSTANCEOF => {
+            format!(
+                "{} {}",
+                mnemonic,
+                reference(constant_pool, u16_at(1).into())
+            )
+        }
+        bytecode::MULTIANEWARRAY => format!(
+            "{} {}, {}",
+            mnemonic,
+            reference(constant_pool, u16_at(1).into()),
+            u8_at(3)
+        ),
+        bytecode::IFEQ..=bytecode::JSR | bytecode::IFNULL | bytecode::IFNONNULL => {
+            format!("{} {}", mnemonic, target(u16_at(1) as i16 as i64))
+        }
+        bytecode::GOTO_W | bytecode::JSR_W => {
+            format!("{} {}", mnemonic, target(i32_at(pc + 1) as i64))
+        }
+        bytecode::BIPUSH => format!("{} {}", mnemonic, u8_at(1) as i8),
+        bytecode::SIPUSH => format!("{} {}", mnemonic, u16_at(1) as i16),
+        bytecode::IINC => format!("{} {}, {}", mnemonic, u8_at(1), u8_at(2) as i8),
+        bytecode::WIDE if u8_at(1) == bytecode::IINC => {
+            format!("{} iinc {}, {}", mnemonic, u16_at(2), u16_at(4) as i16)
+        }
+        bytecode::WIDE => format!("{} {} {}", mnemonic, self::mnemonic(u8_at(1)), u16_at(2)),
+        bytecode::TABLESWITCH => {
+            let operands = pc + 1 + (3 - pc % 4);
+            let (low, high) = (i32_at(operands + 4), i32_at(operands + 8));
+            let mut description = format!(
+                "{} {{ default: {}",
+                mnemonic,
+                target(i32_at(operands) as i64)
+            );
+            for (position, key) in (low..=high).enumerate() {
+                let offset = i32_at(operands + 12 + 4 * position);
+                write!(description, ", {}: {}", key, target(offset as i64)).unwrap();
+            }
+            description + " }"
+        }
+        bytecode::LOOKUPSWITCH => {
+            let operands = pc + 1 + (3 - pc % 4);
+            let mut description = format!(
+                "{} {{ default: {}",
+                mnemonic,
+                target(i32_at(operands) as i64)
+            );
+            for pair in 0..i32_at(operands + 4).max(0) as usize {
+                let key = i32_at(operands + 8 + 8 * pair);
+                let offset = i32_at(operands + 12 + 8 * pair);
+                write!(description, ", {}: {}", key, target(offset as i64)).unwrap();
+            }
+            description + " }"
+        }
+        // The index of a local variable or the type of newarray
+        _ if bytecode::fixed_length(opcode) == Some(2) => format!("{} {}", mnemonic, u8_at(1)),
+        _ => mnemonic,
+    }
+}
+
+fn mnemonic(opcode: u8) -> String {
+    OPCODES
+        .iter()
+        .find(|(candidate, _)| *candidate == opcode)
+        .map_or_else(
+            || format!("<undefined 0x{:02x}>", opcode),
+            |(_, mnemonic)| mnemonic.to_ascii_lowercase(),
+        )
+}
+
+/// The index of a constant together with the constant itself, like in the comments of javap
+fn reference(constant_pool: &ConstantPool, index: ConstantPoolIndex) -> String {
+    format!("#{} // {}", index.as_u16(), resolve(constant_pool, index))
+}
+
+/// The operands of the entry, followed by what they refer to
+fn symbolic(constant_pool: &ConstantPool, index: ConstantPoolIndex) -> String {
+    let indices = |indices: &[ConstantPoolIndex]| {
+        indices
+            .iter()
+            .map(|index| format!("#{}", index.as_u16()))
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+    let operands = match constant_pool.get(index) {
+        Ok(ConstantPoolEntry::String { value: operand })
+        | Ok(ConstantPoolEntry::Class { name: operand })
+        | Ok(ConstantPoolEntry::MethodType {
+            descriptor: operand,
+        })
+        | Ok(ConstantPoolEntry::Module { name: operand })
+        | Ok(ConstantPoolEntry::Package { name: operand }) => indices(&[*operand]),
+        Ok(ConstantPoolEntry::NameAndType { name, ty }) => indices(&[*name, *ty]),
+        Ok(ConstantPoolEntry::MethodHandle { kind, reference }) => {
+            format!("{:?}:{}", kind, indices(&[*reference]))
+        }
+        Ok(
+            ConstantPoolEntry::Dynamic {
+                bootstrap_method,
+                name_and_type,
+            }
+            | ConstantPoolEntry::InvokeDynamic {
+                bootstrap_method,
+                name_and_type,
+            },
+        ) => format!("#{}:{}", bootstrap_method, indices(&[*name_and_type])),
+        Ok(entry) => match member_reference(entry) {
+            Some((class, name_and_type)) => indices(&[class, name_and_type]),
+            // Constants are shown as their value
+            None => return resolve(constant_pool, index),
+        },
+        Err(_) => return "<invalid>".to_string(),
+    };
+    format!("{:<14} // {}", operands, resolve(constant_pool, index))
+}
+
+/// The value of the entry, with the entries that it refers to resolved
+fn resolve(constant_pool: &ConstantPool, index: ConstantPoolIndex) -> String {
+    let utf8 = |index: ConstantPoolIndex| constant_pool.get_utf8(index).unwrap_or("<invalid>");
+    match constant_pool.get(index) {
+        Ok(ConstantPoolEntry::Utf8(value)) => value.escape_debug().to_string(),
+        Ok(ConstantPoolEntry::Integer(value)) => value.to_string(),
+        Ok(ConstantPoolEntry::Long(value)) => format!("{}l", value),
+        Ok(ConstantPoolEntry::Float(value)) => format!("{}f", value),
+        Ok(ConstantPoolEntry::Double(value)) => format!("{}d", value),
+        Ok(ConstantPoolEntry::String { value }) => format!("\"{}\"", utf8(*value).escape_debug()),
+        Ok(ConstantPoolEntry::Class { name: operand })
+        | Ok(ConstantPoolEntry::MethodType {
+            descriptor: operand,
+        })
+        | Ok(ConstantPoolEntry::Module { name: operand })
+        | Ok(ConstantPoolEntry::Package { name: operand }) => utf8(*operand).to_string(),
+        Ok(ConstantPoolEntry::NameAndType { name, ty }) => {
+            format!("{}:{}", utf8(*name), utf8(*ty))
+        }
+        Ok(ConstantPoolEntry::MethodHandle { kind, reference }) => {
+            format!("{:?} {}", kind, resolve(constant_pool, *reference))
+        }
+        Ok(
+            ConstantPoolEntry::Dynamic {
+                bootstrap_method,
+                name_and_type,
+            }
+            | ConstantPoolEntry::InvokeDynamic {
+                bootstrap_method,
+                name_and_type,
+            },
+        ) => format!(
+            "#{}:{}",
+            bootstrap_method,
+            resolve(constant_pool, *name_and_type)
+        ),
+        Ok(entry) => match member_reference(entry) {
+            Some((class, name_and_type)) => format!(
+                "{}.{}",
+                resolve(constant_pool, class),
+                resolve(constant_pool, name_and_type)
+            ),
+            None => String::new(),
+        },
+        Err(_) => "<invalid>".to_string(),
+    }
+}
+
+/// The class and the name and type of a field, method or interface method reference
+fn member_reference(entry: &ConstantPoolEntry) -> Option<(ConstantPoolIndex, ConstantPoolIndex)> {
+    match entry {
+        ConstantPoolEntry::FieldReference(FieldReference::Unresolved {
+            class,
+            name_and_type,
+        })
+        | ConstantPoolEntry::MethodReference(MethodReference::Unresolved {
+            class,
+            name_and_type,
+        })
+        | ConstantPoolEntry::InterfaceMethodReference(InterfaceMethodReference::Unresolved {
+            class,
+            name_and_type,
+        }) => Some((*class, *name_and_type)),
+        _ => None,
+    }
+}
+
+/// The name of the tag of the entry (JVMS §4.4)
+fn tag(entry: &ConstantPoolEntry) -> &'static str {
+    match entry {
+        ConstantPoolEntry::Utf8(_) => "Utf8",
+        ConstantPoolEntry::Integer(_) => "Integer",
+        ConstantPoolEntry::Long(_) => "Long",
+        ConstantPoolEntry::Float(_) => "Float",
+        ConstantPoolEntry::Double(_) => "Double",
+        ConstantPoolEntry::String { .. } => "String",
+        ConstantPoolEntry::Class { .. } => "Class",
+        ConstantPoolEntry::FieldReference(_) => "Fieldref",
+        ConstantPoolEntry::MethodReference(_) => "Methodref",
+        ConstantPoolEntry::InterfaceMethodReference(_) => "InterfaceMethodref",
+        ConstantPoolEntry::NameAndType { .. } => "NameAndType",
+        ConstantPoolEntry::MethodHandle { .. } => "MethodHandle",
+        ConstantPoolEntry::MethodType { .. } => "MethodType",
+        ConstantPoolEntry::Dynamic { .. } => "Dynamic",
+        ConstantPoolEntry::InvokeDynamic { .. } => "InvokeDynamic",
+        ConstantPoolEntry::Module { .. } => "Module",
+        ConstantPoolEntry::Package { .. } => "Package",
+        ConstantPoolEntry::Empty => "",
+    }
+}
+
+/// Turns the Debug output of access flags (e.g. "PUBLIC | SUPER") into the names of javap
+fn flag_names(flags: String) -> String {
+    if flags == "(empty)" {
+        return String::new();
+    }
+    flags
+        .split(" | ")
+        .map(|flag| format!("ACC_{}", flag))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::class_parser::ParsingError;
+
+    use super::print_class_file;
+
+    const RECURSION: &[u8] = include_bytes!("../tests/classes/Recursion.class");
+
+    #[test]
+    fn prints_the_header_and_the_constant_pool() {
+        let output = print_class_file(RECURSION).unwrap();
+        assert!(output.starts_with("Classfile version 52.0\n"), "{}", output);
+        for line in [
+            "  flags: (0x0021) ACC_PUBLIC, ACC_SUPER",
+            "  this_class: #8 // Recursion",
+            "  super_class: #2 // java/lang/Object",
+            "  interfaces: 0, fields: 1, methods: 3, attributes: 1",
+            "    #1 = Methodref          #2.#3          // java/lang/Object.<init>:()V",
+            "    #7 = Fieldref           #8.#9          // Recursion.depth:I",
+            "   #25 = Utf8               Recursion.java",
+        ] {
+            assert!(
+                output.lines().any(|l| l == line),
+                "missing {:?}:\n{}",
+                line,
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn prints_the_members_and_their_code() {
+        let output = print_class_file(RECURSION).unwrap();
+        let overflow = &output[output.find("\n  overflow\n").unwrap() + 1..];
+        let expected = [
+            "  overflow",
+            "    descriptor: ()I",
+            "    flags: (0x0008) ACC_STATIC",
+            "    Code:",
+            "      stack=1, locals=1, length=10",
+            "          0: iconst_0",
+            "          1: invokestatic #13 // Recursion.recurse:(I)I",
+            "          4: ireturn",
+            "          5: astore_0",
+            "          6: getstatic #7 // Recursion.depth:I",
+            "          9: ireturn",
+            "      Exception table:",
+            "         from    to  target type",
+            "            0     4       5 #17 // java/lang/StackOverflowError",
+            "      LineNumberTable: 14 bytes",
+            "      StackMapTable: 6 bytes",
+        ];
+        assert_eq!(
+            overflow.lines().take(expected.len()).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_class_files() {
+        let truncated = &RECURSION[..RECURSION.len() / 2];
+        assert!(matches!(
+            print_class_file(truncated),
+            Err(ParsingError::UnexpectedEOF)
+        ));
+        assert!(matches!(
+            print_class_file(&[0xCA, 0xFE, 0xBA, 0xBF]),
+            Err(ParsingError::MissingMagicNumber)
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::model::{class_library::ClassLibrary, method::MethodIndex, method::MethodTable};
+
+// The dynamic call graph as observed by the interpreter, with the methods that have been compiled. The JIT doesn't
+// inline yet, so every call site of compiled code stays a call and there are no inlining decisions to dump.
+// Recording is disabled by default, because every executed call has to update the edge counters.
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static CALL_GRAPH: Mutex<Option<CallGraph>> = Mutex::new(None);
+
+#[derive(Default)]
+struct CallGraph {
+    edges: BTreeMap<CallEdge, u64>, // Edge -> number of calls
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct CallEdge {
+    caller: usize, // Method index
+    pc: usize,
+    callee: usize, // Method index
+}
+
+pub enum DumpFormat {
+    Dot,
+    Json,
+}
+
+impl DumpFormat {
+    /// Files ending in .json are written as JSON, everything else as DOT
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::Json,
+            _ => Self::Dot,
+        }
+    }
+}
+
+pub fn enable() {
+    *CALL_GRAPH.lock().unwrap() = Some(CallGraph::default());
+    ENABLED.store(true, Ordering::Release);
+}
+
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Counts a call from the call site at pc in caller to callee
+pub fn record_call(caller: MethodIndex, pc: usize, callee: MethodIndex) {
+    if !is_enabled() {
+        return;
+    }
+
+    if let Some(graph) = CALL_GRAPH.lock().unwrap().as_mut() {
+        let edge = CallEdge {
+            caller: caller.into(),
+            pc,
+            callee: callee.into(),
+        };
+        *graph.edges.entry(edge).or_insert(0) += 1;
+    }
+}
+
+/// Writes the recorded call graph to the given file
+pub fn dump(path: &Path, classes: &ClassLibrary, methods: &MethodTable) {
+    let graph = CALL_GRAPH.lock().unwrap();
+    let graph = match graph.as_ref() {
+        Some(graph) => graph,
+        None => return,
+    };
+
+    let output = match DumpFormat::from_path(path) {
+        DumpFormat::Dot => to_dot(graph, classes, methods),
+        DumpFormat::Json => to_json(graph, classes, methods),
+    };
+    match fs::write(path, output) {
+        Ok(()) => log::info!("Wrote the call graph to {}", path.display()),
+        Err(err) => log::warn!(
+            "Failed to write the call graph to {}: {}",
+            path.display(),
+            err
+        ),
+    }
+}
+
+fn method_name(method: usize, classes: &ClassLibrary, methods: &MethodTable) -> String {
+    let data = methods.get_data(method.into());
+    format!(
+        "{}.{}",
+        classes
+            .resolve(data.owning_class)
+            .name()
+            .unwrap_or("<unknown>"),
+        data.name
+    )
+}
+
+/// All methods that appear in the graph, either as a caller or as a callee
+fn nodes(graph: &CallGraph) -> BTreeSet<usize> {
+    graph
+        .edges
+        .keys()
+        .flat_map(|edge| [edge.caller, edge.callee])
+        .collect()
+}
+
+fn to_dot(graph: &CallGraph, classes: &ClassLibrary, methods: &MethodTable) -> String {
+    let mut dot = String::from("digraph calls {\n    node [shape=box];\n");
+    for method in nodes(graph) {
+        let style = if methods.is_compiled(method.into()) {
+            ", style=filled, fillcolor=lightblue"
+        } else {
+            ""
+        };
+        writeln!(
+            dot,
+            "    m{} [label=\"{}\"{}];",
+            method,
+            escape(&method_name(method, classes, methods)),
+            style
+        )
+        .unwrap();
+    }
+    for (edge, count) in &graph.edges {
+        writeln!(
+            dot,
+            "    m{} -> m{} [label=\"pc {}: {} calls\"];",
+            edge.caller, edge.callee, edge.pc, count
+        )
+        .unwrap();
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn to_json(graph: &CallGraph, classes: &ClassLibrary, methods: &MethodTable) -> String {
+    let methods_json = nodes(graph)
+        .into_iter()
+        .map(|method| {
+            format!(
+                "    {{\"id\": {}, \"name\": \"{}\", \"compiled\": {}}}",
+                method,
+                escape(&method_name(method, classes, methods)),
+                methods.is_compiled(method.into())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    let edges_json = graph
+        .edges
+        .iter()
+        .map(|(edge, count)| {
+            format!(
+                "    {{\"caller\": {}, \"pc\": {}, \"callee\": {}, \"count\": {}}}",
+                edge.caller, edge.pc, edge.callee, count
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    format!(
+        "{{\n\"methods\": [\n{}\n],\n\"edges\": [\n{}\n]\n}}\n",
+        methods_json, edges_json
+    )
+}
+
+/// Escapes a string for use inside double quotes (valid for both DOT and JSON)
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape;
+
+    #[test]
+    fn escapes_quotes_and_backslashes() {
+        assert_eq!(escape(r#"say "hi""#), r#"say \"hi\""#);
+        assert_eq!(escape(r"C:\classes"), r"C:\\classes");
+    }
+
+    #[test]
+    fn escapes_control_characters() {
+        assert_eq!(escape("a\nb"), r"a\nb");
+        assert_eq!(escape("a\tb\u{1}"), r"a\u0009b\u0001");
+    }
+}
@@ -0,0 +1,264 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use dynasmrt::ExecutableBuffer;
+
+#[cfg(asm_entry)]
+use dynasmrt::{dynasm, relocations::Relocation, Assembler, AssemblyOffset, DynasmApi};
+
+use crate::model::method::MethodIndex;
+#[cfg(asm_entry)]
+use crate::{
+    bytecode,
+    interpreter::{self, ExecutionError},
+    model::{
+        class_library::ClassLibrary, constant_pool::ConstantPoolIndex, heap::Heap,
+        method::MethodTable, stack::StackPointer,
+    },
+};
+
+#[cfg(asm_entry)]
+use super::convention;
+use super::CodeBuffer;
+
+// Compiled static and special calls don't go through the call table. Every call site loads the method index and the
+// target as immediates. Until the callee has been resolved, the target is a stub at the end of the compiled method
+// that resolves the callee (which may load and initialize its class) and patches both immediates, so that later
+// calls jump directly to the callee.
+// Patching is safe without stopping the world, because Java code only runs on one thread and call sites are only
+// patched while that thread is inside the VM, i.e. at a safepoint. If the implementation of a callee changes (e.g.
+// because it has been compiled), all call sites that are bound to it are patched again.
+
+thread_local! {
+    // The bound call sites by their callee
+    static BOUND_SITES: RefCell<HashMap<MethodIndex, Vec<*const CallSite>>> =
+        RefCell::new(HashMap::new());
+}
+
+#[cfg_attr(not(asm_entry), allow(dead_code))] // Only the x86-64 method entry can resolve call sites
+pub struct CallSite {
+    caller: MethodIndex,
+    pc: usize,
+    opcode: u8,
+    method_index: *mut u8, // The imm32 operand that is passed as the method index
+    target: *mut u8,       // The imm64 operand with the address that is called
+}
+
+/// The code of a compiled method together with its call sites
+pub struct CompiledCode {
+    buffer: ExecutableBuffer,
+    // The stubs refer to the call sites by address, so they must not move
+    #[allow(clippy::vec_box)]
+    call_sites: Vec<Box<CallSite>>,
+}
+
+impl CompiledCode {
+    pub fn new(buffer: ExecutableBuffer, call_sites: Vec<Box<CallSite>>) -> Self {
+        Self { buffer, call_sites }
+    }
+}
+
+impl CodeBuffer for CompiledCode {
+    fn code_size(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+impl Drop for CompiledCode {
+    fn drop(&mut self) {
+        // The call sites are freed together with the code, so they must not be patched anymore. Code that is owned
+        // by a thread local (e.g. of the cross-checker) may be dropped after the registry, which is gone then anyway.
+        let _ = BOUND_SITES.try_with(|sites| {
+            for bound in sites.borrow_mut().values_mut() {
+                bound.retain(|site| {
+                    !self
+                        .call_sites
+                        .iter()
+                        .any(|owned| std::ptr::eq(&**owned, *site))
+                });
+            }
+        });
+    }
+}
+
+/// A call site whose code has been emitted, but not yet finalized
+#[cfg(asm_entry)]
+pub struct PendingCallSite {
+    site: Box<CallSite>,
+    method_index_end: AssemblyOffset, // End of the `mov edi, imm32` instruction
+    target_end: AssemblyOffset,       // End of the `mov rax, imm64` instruction
+    stub: Option<AssemblyOffset>,
+}
+
+/// Emits the call of the method that is referenced by the invokestatic or invokespecial instruction at pc.
+/// The arguments have been pushed onto the Java stack, rax holds the return value afterwards.
+#[cfg(asm_entry)]
+pub fn emit_call<R: Relocation>(
+    ops: &mut Assembler<R>,
+    caller: MethodIndex,
+    pc: usize,
+    opcode: u8,
+) -> PendingCallSite {
+    dynasm!(ops
+        ; .arch x64
+        ; mov edi, DWORD 0
+    );
+    let method_index_end = ops.offset();
+    dynasm!(ops
+        ; .arch x64
+        ; mov rax, QWORD 0
+    );
+    let target_end = ops.offset();
+    dynasm!(ops
+        ; .arch x64
+        ; call rax
+    );
+    PendingCallSite {
+        site: Box::new(CallSite {
+            caller,
+            pc,
+            opcode,
+            method_index: std::ptr::null_mut(),
+            target: std::ptr::null_mut(),
+        }),
+        method_index_end,
+        target_end,
+        stub: None,
+    }
+}
+
+/// Emits the resolution stub of the call site. The stub is entered like the callee, i.e. with the return address
+/// of the call site on top of the native stack.
+#[cfg(asm_entry)]
+pub fn emit_stub<R: Relocation>(ops: &mut Assembler<R>, pending: &mut PendingCallSite) {
+    pending.stub = Some(ops.offset());
+    dynasm!(ops
+        ; .arch x64
+        ; push rbp  // Aligns the stack to 16B
+        ; mov rbp, rsp
+        ; mov rdi, QWORD &*pending.site as *const CallSite as i64
+    );
+    convention::emit_pass_context(ops);
+    dynasm!(ops
+        ; .arch x64
+        ; mov rax, QWORD resolve_call_site as *const () as i64
+        ; call rax
+        ; pop rbp
+        ; mov rdi, rdx  // The method index of the callee
+        ; jmp rax       // Continue as if the call site had called the target directly
+    );
+}
+
+/// Points the call site at its stub once the code has been finalized
+#[cfg(asm_entry)]
+pub fn finalize(pending: PendingCallSite, buffer: &ExecutableBuffer) -> Box<CallSite> {
+    let mut site = pending.site;
+    site.method_index = unsafe { buffer.ptr(pending.method_index_end).sub(4) as *mut u8 };
+    site.target = unsafe { buffer.ptr(pending.target_end).sub(8) as *mut u8 };
+    let stub = buffer.ptr(
+        pending
+            .stub
+            .expect("The stub of the call site hasn't been emitted"),
+    );
+    unsafe { patch(site.target, &(stub as u64).to_le_bytes()) };
+    site
+}
+
+/// Patches all call sites that are bound to the method, e.g. after the method has been compiled
+pub fn rebind(callee: MethodIndex, target: u64) {
+    BOUND_SITES.with(|sites| {
+        if let Some(bound) = sites.borrow().get(&callee) {
+            for site in bound {
+                unsafe { patch((**site).target, &target.to_le_bytes()) };
+            }
+        }
+    });
+}
+
+#[cfg(asm_entry)]
+#[repr(C)]
+struct Resolution {
+    target: u64,       // Returned in rax
+    method_index: u64, // Returned in rdx
+}
+
+#[cfg(asm_entry)]
+extern "sysv64" fn resolve_call_site(
+    site: *const CallSite,
+    stack: StackPointer,
+    heap: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+) -> Resolution {
+    let (site, heap, classes, methods) = unsafe { (&*site, &mut *heap, &*classes, &*methods) };
+    let caller = methods.get_data(site.caller);
+    let class = classes.resolve(caller.owning_class);
+    let index = ConstantPoolIndex::from(u16::from_be_bytes([
+        caller.code[site.pc + 1],
+        caller.code[site.pc + 2],
+    ]));
+    let resolved = match site.opcode {
+        bytecode::INVOKESTATIC => class
+            .resolve_static_method(index, classes, heap, methods, stack)
+            .map_err(ExecutionError::from)
+            .and_then(|(callee, parameter_count)| {
+                // The site is only bound once the declaring class has been initialized
+                let declaring_class = methods.get_data(callee).owning_class;
+                classes.initialize(declaring_class, methods, heap, stack)?;
+                Ok((callee, parameter_count))
+            }),
+        _ => class
+            .resolve_special_method(index, classes, heap, methods, stack)
+            .map_err(ExecutionError::from),
+    };
+    match resolved {
+        Ok((callee, _)) => {
+            let target = unsafe { methods.resolve(callee) };
+            unsafe {
+                patch(site.method_index, &callee.into_raw().to_le_bytes());
+                patch(site.target, &target.to_le_bytes());
+            }
+            BOUND_SITES.with(|sites| {
+                sites
+                    .borrow_mut()
+                    .entry(callee)
+                    .or_default()
+                    .push(site as *const CallSite)
+            });
+            Resolution {
+                target,
+                method_index: unsafe { callee.into_raw() } as u64,
+            }
+        }
+        Err(err) => {
+            // The site stays unbound, the caller returns because of the pending error
+            interpreter::raise_error(err, classes, methods);
+            Resolution {
+                target: resolution_failed as *const () as u64,
+                method_index: 0,
+            }
+        }
+    }
+}
+
+#[cfg(asm_entry)]
+extern "sysv64" fn resolution_failed() -> i64 {
+    0
+}
+
+/// Overwrites the operand of an instruction in the (otherwise read-only) code
+unsafe fn patch(address: *mut u8, bytes: &[u8]) {
+    let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+    let start = address as usize & !(page_size - 1);
+    let length = address as usize + bytes.len() - start;
+    libc::mprotect(
+        start as *mut libc::c_void,
+        length,
+        libc::PROT_READ | libc::PROT_WRITE,
+    );
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), address, bytes.len());
+    libc::mprotect(
+        start as *mut libc::c_void,
+        length,
+        libc::PROT_READ | libc::PROT_EXEC,
+    );
+}
@@ -0,0 +1,121 @@
+// Restricts which methods are handed to the JIT (-XX:CompileCommand=<command>,<pattern>), e.g. to bisect a
+// miscompilation to a single method. A pattern has the form <class>.<method>[<descriptor>] (<class>::<method> is
+// accepted as well) and may contain * as a wildcard in every part. Classes can be named in internal form or with dots.
+
+#[derive(Debug, Clone)]
+pub struct CompileCommand {
+    kind: CompileCommandKind,
+    pattern: MethodPattern,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompileCommandKind {
+    Exclude,     // Never compile the matching methods
+    CompileOnly, // Only compile the methods that match one of the compileonly commands
+}
+
+#[derive(Debug, Clone)]
+struct MethodPattern {
+    class: String, // Internal form
+    method: String,
+    descriptor: Option<String>, // Matches every descriptor if not given
+}
+
+impl CompileCommand {
+    /// Parses the value of -XX:CompileCommand, e.g. "exclude,java/lang/String.indexOf"
+    pub fn parse(value: &str) -> Option<Self> {
+        let (kind, pattern) = value.split_once(',')?;
+        let kind = match kind {
+            "exclude" => CompileCommandKind::Exclude,
+            "compileonly" => CompileCommandKind::CompileOnly,
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            pattern: MethodPattern::parse(pattern)?,
+        })
+    }
+
+    fn matches(&self, class: &str, method: &str, descriptor: &str) -> bool {
+        glob_matches(&self.pattern.class, class)
+            && glob_matches(&self.pattern.method, method)
+            && self
+                .pattern
+                .descriptor
+                .as_ref()
+                .is_none_or(|pattern| glob_matches(pattern, descriptor))
+    }
+}
+
+impl MethodPattern {
+    fn parse(pattern: &str) -> Option<Self> {
+        let (class, method) = match pattern.split_once("::") {
+            Some(parts) => parts,
+            None => pattern.rsplit_once('.')?,
+        };
+        let (method, descriptor) = match method.find('(') {
+            Some(start) => (&method[..start], Some(method[start..].to_string())),
+            None => (method, None),
+        };
+        if class.is_empty() || method.is_empty() {
+            return None;
+        }
+        Some(Self {
+            class: class.replace('.', "/"),
+            method: method.to_string(),
+            descriptor,
+        })
+    }
+}
+
+/// Decides for every method whether the JIT may compile it
+#[derive(Debug, Clone, Default)]
+pub struct CompilationFilter {
+    commands: Vec<CompileCommand>,
+}
+
+impl CompilationFilter {
+    pub fn new(commands: Vec<CompileCommand>) -> Self {
+        Self { commands }
+    }
+
+    /// Exclusions take precedence over compileonly commands
+    pub fn allows(&self, class: &str, method: &str, descriptor: &str) -> bool {
+        let mut has_compile_only = false;
+        let mut compile_only = false;
+        for command in &self.commands {
+            let matches = command.matches(class, method, descriptor);
+            match command.kind {
+                CompileCommandKind::Exclude if matches => return false,
+                CompileCommandKind::Exclude => {}
+                CompileCommandKind::CompileOnly => {
+                    has_compile_only = true;
+                    compile_only |= matches;
+                }
+            }
+        }
+        !has_compile_only || compile_only
+    }
+}
+
+/// Matches text against a pattern in which * stands for any (possibly empty) sequence of characters
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
@@ -0,0 +1,254 @@
+use std::cell::Cell;
+
+use dynasmrt::{dynasm, relocations::Relocation, Assembler, DynasmApi};
+
+use crate::model::{
+    class_library::ClassLibrary,
+    heap::Heap,
+    method::{MethodIndex, MethodTable},
+    stack::StackPointer,
+};
+
+// The internal calling convention, which the interpreter, compiled methods, native trampolines and the resolution
+// stubs of call sites use to call each other (only with the x86-64 method entry):
+// - rdi holds the method index of the callee (like the first argument in sysv64)
+// - r12 holds the Java stack pointer. The caller has pushed the arguments below it, the callee starts its frame at it
+// - r13, r14 and r15 hold the VM context: the heap, the class library and the method table
+// - The value is returned in rax. Longs and doubles are returned as a whole in rax (the high half in the upper
+//   32 bits), all other values in eax. Errors are left in the pending error slot of the interpreter instead.
+// - Like in sysv64, the callee preserves rbx, rbp and r12-r15. Compiled methods use rbx as the base of their frame
+//   on the Java stack and reset r12 to it when they return, so r12 is restored as well.
+// The registers of the VM context are listed once in context_registers, from which the translations into and out of
+// sysv64 are generated: the assembly below, and the code that is emitted at runtime by the JIT, the native
+// trampolines and the call sites. In debug builds, compiled methods and the interpreter check on entry that the
+// registers hold the context of the running VM, and compiled methods check it again when a call returns to them.
+
+/// Expands the macro with the registers of the VM context as (internal register, sysv64 argument register), in the
+/// order of the arguments of the interpreter entry
+macro_rules! context_registers {
+    ($expand:ident) => {
+        $expand!(
+            ("r12", "rsi"), // Java stack pointer
+            ("r13", "rdx"), // *mut Heap
+            ("r14", "rcx"), // *const ClassLibrary
+            ("r15", "r8")   // *const MethodTable
+        )
+    };
+}
+
+/// Assembly that moves the context into the argument registers of sysv64
+#[cfg(asm_entry)]
+macro_rules! pass_context {
+    ($(($register:literal, $argument:literal)),*) => {
+        concat!($("mov ", $argument, ", ", $register, "\n"),*)
+    };
+}
+
+/// Assembly that moves the context from the argument registers of sysv64 into the internal registers
+#[cfg(asm_entry)]
+macro_rules! take_context {
+    ($(($register:literal, $argument:literal)),*) => {
+        concat!($("mov ", $register, ", ", $argument, "\n"),*)
+    };
+}
+
+macro_rules! register_numbers {
+    ($(($register:literal, $argument:literal)),*) => {
+        [$((register_number($register), register_number($argument))),*]
+    };
+}
+
+/// The context registers as (internal register, sysv64 argument register) numbers, for the code emitted at runtime
+const CONTEXT_REGISTERS: [(u8, u8); 4] = context_registers!(register_numbers);
+const STACK_POINTER: u8 = CONTEXT_REGISTERS[0].0;
+const FRAME_BASE: u8 = register_number("rbx");
+
+const fn register_number(name: &str) -> u8 {
+    match name.as_bytes() {
+        b"rax" => 0,
+        b"rcx" => 1,
+        b"rdx" => 2,
+        b"rbx" => 3,
+        b"rsp" => 4,
+        b"rbp" => 5,
+        b"rsi" => 6,
+        b"rdi" => 7,
+        [b'r', digit] => *digit - b'0',
+        [b'r', b'1', digit] => 10 + *digit - b'0',
+        _ => panic!("unknown register"),
+    }
+}
+
+#[cfg(asm_entry)]
+std::arch::global_asm!(
+    // Calls the interpreter from the internal calling convention
+    ".global interpreter_trampoline",
+    "interpreter_trampoline:",
+    // Stack alignment (8B return address to caller of interpreter_trampoline; 16B required => 8B padding)
+    // The padding slot holds the caller's frame pointer so that native unwinders can walk through JIT frames
+    "push rbp",
+    "mov rbp, rsp",
+    // The method index is already placed in rdi
+    context_registers!(pass_context),
+    "call interpret_method",
+    "pop rbp",
+    "ret",
+    // Calls the target (the last argument) with the internal calling convention from sysv64
+    ".global call_internal",
+    "call_internal:",
+    "push rbp",
+    "mov rbp, rsp",
+    "push rbx",
+    "push r12",
+    "push r13",
+    "push r14",
+    "push r15",
+    "sub rsp, 8", // Align the stack to 16B
+    context_registers!(take_context),
+    "call r9",
+    "add rsp, 8",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop rbx",
+    "pop rbp",
+    "ret",
+);
+
+#[cfg(asm_entry)]
+extern "sysv64" {
+    /// Never call this directly! This uses the internal calling convention and not the acutal
+    /// sysv64 calling convention. This function does the translation between them.
+    pub fn interpreter_trampoline(method_index: MethodIndex);
+
+    // The context is passed as untyped pointers, because the types aren't FFI-safe
+    fn call_internal(
+        method_index: MethodIndex,
+        stack: StackPointer,
+        heap: *mut (),
+        classes: *const (),
+        methods: *const (),
+        target: u64,
+    ) -> i64;
+}
+
+thread_local! {
+    // The VM context of the last call into the internal calling convention from Rust (checked in debug builds)
+    static EXPECTED_CONTEXT: Cell<(usize, usize, usize)> = const { Cell::new((0, 0, 0)) };
+}
+
+/// Calls the code at the given address with the internal calling convention and returns the raw return value
+///
+/// # Safety
+/// The target must be the entry of a method that expects the arguments on the given stack, and the pointers must
+/// refer to the VM that owns the method
+#[cfg(asm_entry)]
+pub unsafe fn call(
+    target: u64,
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+) -> i64 {
+    if cfg!(debug_assertions) {
+        EXPECTED_CONTEXT
+            .with(|context| context.set((heap as usize, classes as usize, methods as usize)));
+    }
+    call_internal(
+        method_index,
+        stack,
+        heap as *mut (),
+        classes as *const (),
+        methods as *const (),
+        target,
+    )
+}
+
+/// Panics if the registers don't hold the VM context of the running call. The arguments are passed like by the
+/// interpreter_trampoline.
+pub extern "sysv64" fn check_context(
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+) {
+    let expected = EXPECTED_CONTEXT.with(|context| context.get());
+    assert!(
+        expected == (heap as usize, classes as usize, methods as usize),
+        "The context registers of {:?} hold (heap {:p}, classes {:p}, methods {:p}) instead of {:x?}",
+        method_index,
+        heap,
+        classes,
+        methods,
+        expected
+    );
+    assert!(
+        stack.is_on_stack(),
+        "The stack pointer register of {:?} ({:p}) doesn't point into the Java stack",
+        method_index,
+        stack.into_raw()
+    );
+}
+
+/// Emits the code that moves the context into the argument registers of sysv64, like the interpreter_trampoline
+pub fn emit_pass_context<R: Relocation>(ops: &mut Assembler<R>) {
+    for (register, argument) in CONTEXT_REGISTERS {
+        dynasm!(ops
+            ; .arch x64
+            ; mov Rq(argument), Rq(register)
+        );
+    }
+}
+
+/// Emits the entry of a compiled method
+pub fn emit_prologue<R: Relocation>(ops: &mut Assembler<R>, method_index: MethodIndex) {
+    // rbp is maintained as a conventional frame pointer so that native unwinders (gdb, perf)
+    // can walk through JIT frames into the frames of the interpreter
+    dynasm!(ops
+        ; .arch x64
+        ; push rbp  // Save the native frame pointer (also aligns the stack to 16B)
+        ; mov rbp, rsp  // Link this frame into the native frame chain
+    );
+    emit_context_check(ops, method_index);
+    dynasm!(ops
+        ; .arch x64
+        ; push Rq(FRAME_BASE)  // Save the base pointer
+        ; mov Rq(FRAME_BASE), Rq(STACK_POINTER)  // Update the base pointer to the current stack pointer
+    );
+}
+
+/// Emits the return of a compiled method to its caller, the return value is already in rax
+pub fn emit_epilogue<R: Relocation>(ops: &mut Assembler<R>) {
+    dynasm!(ops
+        ; .arch x64
+        ; mov Rq(STACK_POINTER), Rq(FRAME_BASE)  // Restore the old stack pointer
+        ; pop Rq(FRAME_BASE)  // Restore the old base pointer
+        ; pop rbp  // Restore the caller's native frame pointer
+        ; ret  // Return to caller
+    );
+}
+
+/// Emits a call of check_context in debug builds. The native stack must be aligned to 16B, rax and rdi are
+/// preserved, but the other caller-saved registers are not.
+pub fn emit_context_check<R: Relocation>(ops: &mut Assembler<R>, method_index: MethodIndex) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    dynasm!(ops
+        ; .arch x64
+        ; push rax
+        ; push rdi
+        ; mov edi, DWORD unsafe { method_index.into_raw() } as i32
+    );
+    emit_pass_context(ops);
+    dynasm!(ops
+        ; .arch x64
+        ; mov rax, QWORD check_context as *const () as i64
+        ; call rax
+        ; pop rdi
+        ; pop rax
+    );
+}
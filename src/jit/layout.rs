@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+
+use crate::{
+    bytecode,
+    interpreter::branch_profile::{self, BranchProfile},
+};
+
+use super::CompilationError;
+
+// The JIT emits the basic blocks of a method in an order that follows the hot path: behind each block comes its more
+// frequently executed successor (if it hasn't been placed yet), so that the hot path mostly falls through and stays
+// in consecutive cache lines. Which successor of a conditional branch is hot is taken from the branch profile of the
+// interpreter. Branches without enough samples keep their fall-through successor, like in the bytecode.
+// The remaining blocks follow in bytecode order.
+
+/// A basic block of the bytecode
+#[derive(Debug, Clone, Copy)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize, // Exclusive
+    pub exit: BlockExit,
+}
+
+/// How control leaves a basic block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockExit {
+    FallThrough,   // Continues at the end of the block
+    Branch(usize), // Conditional branch to the target, otherwise continues at the end of the block
+    Goto(usize),   // Unconditional branch to the target
+    Return,        // Returns or throws
+}
+
+/// Splits the code into basic blocks and returns them in the order in which they should be emitted
+pub fn layout(code: &[u8], profile: &BranchProfile) -> Result<Vec<Block>, CompilationError> {
+    let blocks = basic_blocks(code)?;
+    let index_of = |pc: usize| blocks.binary_search_by_key(&pc, |block| block.start).ok();
+
+    let mut placed = vec![false; blocks.len()];
+    let mut order = Vec::with_capacity(blocks.len());
+    let mut next = Some(0);
+    while order.len() < blocks.len() {
+        let index = next
+            .filter(|&index| !placed[index])
+            .or_else(|| placed.iter().position(|placed| !placed))
+            .unwrap();
+        placed[index] = true;
+        order.push(blocks[index]);
+
+        let block = &blocks[index];
+        next = match block.exit {
+            BlockExit::FallThrough => index_of(block.end),
+            BlockExit::Branch(target) => {
+                let branch_pc = block.end - 3;
+                if profile.is_likely_taken(branch_pc) == Some(true) {
+                    index_of(target)
+                } else {
+                    index_of(block.end)
+                }
+            }
+            BlockExit::Goto(target) => index_of(target),
+            BlockExit::Return => None,
+        };
+    }
+    Ok(order)
+}
+
+/// The starts of the blocks that a backward branch jumps to, i.e. the headers of the loops
+#[cfg_attr(not(asm_entry), allow(dead_code))]
+pub fn loop_headers(blocks: &[Block]) -> HashSet<usize> {
+    blocks
+        .iter()
+        .filter_map(|block| match block.exit {
+            BlockExit::Branch(target) | BlockExit::Goto(target) if target < block.end => {
+                Some(target)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn basic_blocks(code: &[u8]) -> Result<Vec<Block>, CompilationError> {
+    if code.is_empty() {
+        return Err(CompilationError::MissingReturn);
+    }
+
+    // Find the first instruction of every block (and the exit of the block that ends before it)
+    let mut starts = vec![false; code.len() + 1];
+    let mut exits = vec![None; code.len() + 1];
+    starts[0] = true;
+    let mut pc = 0;
+    while pc < code.len() {
+        let length =
+            bytecode::instruction_length(code, pc).ok_or(CompilationError::MissingReturn)?;
+        let next = pc + length;
+        let exit = match code[pc] {
+            opcode if branch_profile::is_conditional_branch(opcode) => {
+                Some(BlockExit::Branch(branch_target(code, pc)?))
+            }
+            bytecode::GOTO => Some(BlockExit::Goto(branch_target(code, pc)?)),
+            bytecode::IRETURN..=bytecode::RETURN | bytecode::ATHROW => Some(BlockExit::Return),
+            _ => None,
+        };
+        if let Some(exit) = exit {
+            if let BlockExit::Branch(target) | BlockExit::Goto(target) = exit {
+                starts[target] = true;
+            }
+            starts[next] = true;
+            exits[next] = Some(exit);
+        }
+        pc = next;
+    }
+
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    for end in 1..=code.len() {
+        if starts[end] || end == code.len() {
+            blocks.push(Block {
+                start,
+                end,
+                exit: exits[end].unwrap_or(BlockExit::FallThrough),
+            });
+            start = end;
+        }
+    }
+    Ok(blocks)
+}
+
+fn branch_target(code: &[u8], pc: usize) -> Result<usize, CompilationError> {
+    let offset = i16::from_be_bytes([code[pc + 1], code[pc + 2]]);
+    let target = pc as isize + offset as isize;
+    if target >= 0 && (target as usize) < code.len() {
+        Ok(target as usize)
+    } else {
+        Err(CompilationError::InvalidBranchTarget(pc))
+    }
+}
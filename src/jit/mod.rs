@@ -1,13 +1,29 @@
+pub mod call_graph;
+pub mod call_site;
+pub mod compile_command;
+pub mod convention;
 mod disassemble;
+mod layout;
+pub mod perf_map;
+pub mod runtime;
 
-use dynasmrt::{dynasm, relocations::Relocation, Assembler, DynasmApi};
+use std::collections::HashMap;
+
+use dynasmrt::{
+    dynasm, relocations::Relocation, Assembler, DynamicLabel, DynasmApi, DynasmLabelApi,
+};
 
 use crate::{
     bytecode,
+    interpreter::breakpoint,
+    jit::{
+        call_site::CompiledCode,
+        layout::{Block, BlockExit},
+    },
     model::{
+        class::{FieldError, LoadableConstant, MethodError},
         class_library::ClassLibrary,
         constant_pool::{ConstantPoolError, ConstantPoolIndex},
-        heap::{Heap, HeapIndex},
         method::{MethodImplementation, MethodIndex, MethodTable, NativeMethod},
         stack::StackValue,
         types::JvmType,
@@ -15,6 +31,11 @@ use crate::{
     },
 };
 
+#[cfg(asm_entry)]
+use self::runtime::RuntimeCall;
+#[cfg(asm_entry)]
+use crate::interpreter::watchpoint;
+
 pub fn compile_method(
     method_index: MethodIndex,
     classes: &ClassLibrary,
@@ -22,158 +43,432 @@ pub fn compile_method(
 ) -> Result<MethodImplementation, CompilationError> {
     let method = methods.get_data(method_index);
     let owning_class = classes.resolve(method.owning_class);
+    if !method.exception_table.is_empty() {
+        return Err(CompilationError::ExceptionHandlers);
+    }
+    if breakpoint::is_enabled() && breakpoint::has_breakpoints(method, classes) {
+        return Err(CompilationError::Breakpoints);
+    }
 
     let mut ops = dynasmrt::x64::Assembler::new().unwrap();
 
     let start_offset = ops.offset();
 
-    // Prologue
-    dynasm!(ops
-        ; .arch x64
-        ; sub rsp, 8    // Stack alignment (8B padding + 8B saved base pointer)
-        ; push rbx      // Save the base pointer
-        ; mov rbx, r12  // Update the base pointer to the current stack pointer
-    );
+    convention::emit_prologue(&mut ops, method_index);
 
     let mut offsets = Vec::with_capacity(method.code.len());
+    #[cfg(asm_entry)]
+    let mut call_sites = Vec::new();
+
+    // The blocks are emitted in the order of the hot path, see layout
+    let blocks = layout::layout(&method.code, method.quickened_code.branches())?;
+    let labels = blocks
+        .iter()
+        .map(|block| (block.start, ops.new_dynamic_label()))
+        .collect::<HashMap<_, _>>();
+    #[cfg(asm_entry)]
+    let loop_headers = layout::loop_headers(&blocks);
 
-    let mut code_index = 0;
-    loop {
-        offsets.push(ops.offset());
-        if code_index >= method.code.len() {
-            return Err(CompilationError::MissingReturn);
+    for (position, block) in blocks.iter().enumerate() {
+        let next_block = blocks.get(position + 1).map(|block| block.start);
+        dynasm!(ops
+            ; .arch x64
+            ; =>labels[&block.start]
+        );
+        #[cfg(asm_entry)]
+        if loop_headers.contains(&block.start) {
+            runtime::emit_call(&mut ops, RuntimeCall::Safepoint, 0, method_index);
         }
-        let opcode = method.code[code_index];
 
-        match opcode {
-            bytecode::ICONST_M1 => {
-                push_constant(&mut ops, StackValue::from_int(JvmInt(-1)));
-                code_index += 1;
-            }
-            bytecode::ICONST_0 => {
-                push_constant(&mut ops, StackValue::from_int(JvmInt(0)));
-                code_index += 1;
-            }
-            bytecode::ICONST_1 => {
-                push_constant(&mut ops, StackValue::from_int(JvmInt(1)));
-                code_index += 1;
-            }
-            bytecode::ICONST_2 => {
-                push_constant(&mut ops, StackValue::from_int(JvmInt(2)));
-                code_index += 1;
-            }
-            bytecode::ICONST_3 => {
-                push_constant(&mut ops, StackValue::from_int(JvmInt(3)));
-                code_index += 1;
-            }
-            bytecode::ICONST_4 => {
-                push_constant(&mut ops, StackValue::from_int(JvmInt(4)));
-                code_index += 1;
-            }
-            bytecode::ICONST_5 => {
-                push_constant(&mut ops, StackValue::from_int(JvmInt(5)));
-                code_index += 1;
-            }
-            bytecode::LCONST_0 => {
-                push_wide_constant(&mut ops, StackValue::from_long(JvmLong(0)));
-                code_index += 1;
-            }
-            bytecode::LCONST_1 => {
-                push_wide_constant(&mut ops, StackValue::from_long(JvmLong(1)));
-                code_index += 1;
-            }
-            bytecode::FCONST_0 => {
-                push_constant(&mut ops, StackValue::from_float(JvmFloat(0.0f32)));
-                code_index += 1;
-            }
-            bytecode::FCONST_1 => {
-                push_constant(&mut ops, StackValue::from_float(JvmFloat(1.0f32)));
-                code_index += 1;
-            }
-            bytecode::FCONST_2 => {
-                push_constant(&mut ops, StackValue::from_float(JvmFloat(2.0f32)));
-                code_index += 1;
-            }
-            bytecode::DCONST_0 => {
-                push_wide_constant(&mut ops, StackValue::from_double(JvmDouble(0.0)));
-                code_index += 1;
-            }
-            bytecode::DCONST_1 => {
-                push_wide_constant(&mut ops, StackValue::from_double(JvmDouble(1.0)));
-                code_index += 1;
-            }
+        let mut code_index = block.start;
+        while code_index < block.end {
+            offsets.push(ops.offset());
+            let opcode = method.code[code_index];
 
-            bytecode::BIPUSH => {
-                push_constant(
-                    &mut ops,
-                    StackValue::from_int(JvmInt(
-                        i8::from_be_bytes([method.code[code_index + 1]]) as i32
-                    )),
-                );
-                code_index += 2;
-            }
-            bytecode::SIPUSH => {
-                push_constant(
-                    &mut ops,
-                    StackValue::from_int(JvmInt(i16::from_be_bytes([
+            match opcode {
+                bytecode::ICONST_M1 => {
+                    push_constant(&mut ops, StackValue::from_int(JvmInt(-1)));
+                }
+                bytecode::ICONST_0 => {
+                    push_constant(&mut ops, StackValue::from_int(JvmInt(0)));
+                }
+                bytecode::ICONST_1 => {
+                    push_constant(&mut ops, StackValue::from_int(JvmInt(1)));
+                }
+                bytecode::ICONST_2 => {
+                    push_constant(&mut ops, StackValue::from_int(JvmInt(2)));
+                }
+                bytecode::ICONST_3 => {
+                    push_constant(&mut ops, StackValue::from_int(JvmInt(3)));
+                }
+                bytecode::ICONST_4 => {
+                    push_constant(&mut ops, StackValue::from_int(JvmInt(4)));
+                }
+                bytecode::ICONST_5 => {
+                    push_constant(&mut ops, StackValue::from_int(JvmInt(5)));
+                }
+                bytecode::LCONST_0 => {
+                    push_wide_constant(&mut ops, StackValue::from_long(JvmLong(0)));
+                }
+                bytecode::LCONST_1 => {
+                    push_wide_constant(&mut ops, StackValue::from_long(JvmLong(1)));
+                }
+                bytecode::FCONST_0 => {
+                    push_constant(&mut ops, StackValue::from_float(JvmFloat(0.0f32)));
+                }
+                bytecode::FCONST_1 => {
+                    push_constant(&mut ops, StackValue::from_float(JvmFloat(1.0f32)));
+                }
+                bytecode::FCONST_2 => {
+                    push_constant(&mut ops, StackValue::from_float(JvmFloat(2.0f32)));
+                }
+                bytecode::DCONST_0 => {
+                    push_wide_constant(&mut ops, StackValue::from_double(JvmDouble(0.0)));
+                }
+                bytecode::DCONST_1 => {
+                    push_wide_constant(&mut ops, StackValue::from_double(JvmDouble(1.0)));
+                }
+
+                bytecode::BIPUSH => {
+                    push_constant(
+                        &mut ops,
+                        StackValue::from_int(JvmInt(
+                            i8::from_be_bytes([method.code[code_index + 1]]) as i32
+                        )),
+                    );
+                }
+                bytecode::SIPUSH => {
+                    push_constant(
+                        &mut ops,
+                        StackValue::from_int(JvmInt(i16::from_be_bytes([
+                            method.code[code_index + 1],
+                            method.code[code_index + 2],
+                        ]) as i32)),
+                    );
+                }
+
+                bytecode::LDC => {
+                    let index = ConstantPoolIndex::from(method.code[code_index + 1] as u16);
+                    match owning_class.get_loadable(index)? {
+                        LoadableConstant::Value(ty, value) => push_constant_type(&mut ops, value, ty),
+                        // Interning and mirrors need the heap, which isn't available during compilation
+                        LoadableConstant::String(_) | LoadableConstant::Class(_) => {
+                            return Err(CompilationError::UnsupportedOpcode(opcode))
+                        }
+                        // The interpreter doesn't support them either
+                        LoadableConstant::MethodHandle(..)
+                        | LoadableConstant::MethodType(_)
+                        | LoadableConstant::Dynamic(..) => {
+                            return Err(CompilationError::UnsupportedOpcode(opcode))
+                        }
+                    }
+                }
+                bytecode::LDC_W | bytecode::LDC2_W => {
+                    let index = ConstantPoolIndex::from(u16::from_be_bytes([
                         method.code[code_index + 1],
                         method.code[code_index + 2],
-                    ]) as i32)),
-                );
-                code_index += 3;
-            }
+                    ]));
+                    match owning_class.get_loadable(index)? {
+                        LoadableConstant::Value(ty, value) => push_constant_type(&mut ops, value, ty),
+                        // Interning and mirrors need the heap, which isn't available during compilation
+                        LoadableConstant::String(_) | LoadableConstant::Class(_) => {
+                            return Err(CompilationError::UnsupportedOpcode(opcode))
+                        }
+                        // The interpreter doesn't support them either
+                        LoadableConstant::MethodHandle(..)
+                        | LoadableConstant::MethodType(_)
+                        | LoadableConstant::Dynamic(..) => {
+                            return Err(CompilationError::UnsupportedOpcode(opcode))
+                        }
+                    }
+                }
 
-            bytecode::LDC => {
-                let index = ConstantPoolIndex::from(method.code[code_index + 1] as u16);
-                let (ty, value) = owning_class.get_loadable(index)?;
-                push_constant_type(&mut ops, value, ty);
-                code_index += 2;
-            }
-            bytecode::LDC_W | bytecode::LDC2_W => {
-                let index = ConstantPoolIndex::from(u16::from_be_bytes([
-                    method.code[code_index + 1],
-                    method.code[code_index + 2],
-                ]));
-                let (ty, value) = owning_class.get_loadable(index)?;
-                push_constant_type(&mut ops, value, ty);
-                code_index += 3;
-            }
+                bytecode::RETURN => {
+                    check_return_type(opcode, method.return_type)?;
+                    return_to_caller(&mut ops, next_block);
+                }
 
-            bytecode::RETURN => {
-                break;
-            }
+                bytecode::IRETURN => {
+                    // We can use pop, because rax is used for the return value
+                    check_return_type(opcode, method.return_type)?;
+                    pop(&mut ops);
+                    return_to_caller(&mut ops, next_block);
+                }
+
+                bytecode::IFEQ..=bytecode::IFLE => {
+                    dynasm!(ops
+                        ; .arch x64
+                        ; sub r12, 4
+                        ; cmp DWORD [r12], 0
+                    );
+                    emit_branch(&mut ops, Condition::of(opcode), block, next_block, &labels);
+                }
+
+                bytecode::IF_ICMPEQ..=bytecode::IF_ICMPLE => {
+                    dynasm!(ops
+                        ; .arch x64
+                        ; sub r12, 8
+                        ; mov eax, [r12]
+                        ; cmp eax, [r12 + 4]
+                    );
+                    emit_branch(&mut ops, Condition::of(opcode), block, next_block, &labels);
+                }
+
+                bytecode::GOTO => {
+                    if let BlockExit::Goto(target) = block.exit {
+                        if next_block != Some(target) {
+                            dynasm!(ops
+                                ; .arch x64
+                                ; jmp =>labels[&target]
+                            );
+                        }
+                    }
+                }
+
+                #[cfg(asm_entry)]
+                bytecode::INVOKESTATIC | bytecode::INVOKESPECIAL => {
+                    let index = ConstantPoolIndex::from(u16::from_be_bytes([
+                        method.code[code_index + 1],
+                        method.code[code_index + 2],
+                    ]));
+                    let (argument_slots, return_type) = owning_class.method_reference_signature(
+                        index,
+                        opcode == bytecode::INVOKESPECIAL,
+                        methods,
+                    )?;
+                    if opcode == bytecode::INVOKESPECIAL {
+                        // The receiver is the first argument, the runtime is only called if it is null
+                        let receiver_offset = -((argument_slots * 4) as i32);
+                        dynasm!(ops
+                            ; .arch x64
+                            ; cmp DWORD [r12 + receiver_offset], 0  // NULL_POINTER
+                            ; jne >receiver_checked
+                        );
+                        runtime::emit_call(&mut ops, RuntimeCall::NullPointer, 0, method_index);
+                        dynasm!(ops
+                            ; .arch x64
+                            ; receiver_checked:
+                        );
+                    }
+                    dynasm!(ops
+                        ; .arch x64
+                        ; sub rsp, 8  // Align the stack to 16B
+                    );
+                    call_sites.push(call_site::emit_call(&mut ops, method_index, code_index, opcode));
+                    dynasm!(ops
+                        ; .arch x64
+                        ; mov [rsp], rax  // Keep the return value in the padding slot
+                    );
+                    convention::emit_context_check(&mut ops, method_index);
+                    dynasm!(ops
+                        ; .arch x64
+                        ; mov rax, QWORD crate::interpreter::has_pending_error as *const () as i64
+                        ; call rax
+                        ; test al, al
+                        ; mov rax, [rsp]
+                        ; lea rsp, [rsp + 8]  // Doesn't change the flags
+                        ; jnz ->exit  // The error stays pending for our caller
+                        ; sub r12, (argument_slots * 4) as i32  // Discard the arguments
+                    );
+                    push_return_value(&mut ops, return_type);
+                }
 
-            bytecode::IRETURN => {
-                // We can use pop/pop_wide, because rax is used for the return value
-                pop(&mut ops);
-                break;
+                #[cfg(asm_entry)]
+                bytecode::NEW => {
+                    let index = u16::from_be_bytes([
+                        method.code[code_index + 1],
+                        method.code[code_index + 2],
+                    ]);
+                    runtime::emit_call(
+                        &mut ops,
+                        RuntimeCall::NewObject,
+                        index as u32,
+                        method_index,
+                    );
+                    push(&mut ops);
+                }
+
+                #[cfg(asm_entry)]
+                bytecode::GETFIELD => {
+                    if watchpoint::is_enabled() {
+                        return Err(CompilationError::WatchedFields);
+                    }
+                    let index = u16::from_be_bytes([
+                        method.code[code_index + 1],
+                        method.code[code_index + 2],
+                    ]);
+                    let ty = owning_class.field_reference_type(ConstantPoolIndex::from(index))?;
+                    runtime::emit_call(&mut ops, RuntimeCall::GetField, index as u32, method_index);
+                    dynasm!(ops
+                        ; .arch x64
+                        ; sub r12, 4  // Pop the object
+                    );
+                    push_return_value(&mut ops, ty);
+                }
+
+                #[cfg(asm_entry)]
+                bytecode::ATHROW => {
+                    // Never returns normally, the exception is left pending for the caller
+                    runtime::emit_call(&mut ops, RuntimeCall::Throw, 0, method_index);
+                    return_to_caller(&mut ops, next_block);
+                }
+
+                _ => return Err(CompilationError::UnsupportedOpcode(opcode)),
             }
+            code_index += bytecode::fixed_length(opcode)
+                .ok_or(CompilationError::UnsupportedOpcode(opcode))?;
+        }
 
-            _ => todo!("Unimplemented opcode {:#04x}", opcode),
+        if block.exit == BlockExit::FallThrough {
+            if block.end >= method.code.len() {
+                return Err(CompilationError::MissingReturn);
+            }
+            if next_block != Some(block.end) {
+                dynasm!(ops
+                    ; .arch x64
+                    ; jmp =>labels[&block.end]
+                );
+            }
         }
     }
 
-    // Epilogue
     dynasm!(ops
         ; .arch x64
-        ; mov r12, rbx  // Restore the old stack pointer
-        ; pop rbx       // Restore the old base pointer
-        ; add rsp, 8    // Revert padding
-        ; ret           // Return to caller
+        ; ->exit:
     );
+    convention::emit_epilogue(&mut ops);
+
+    // The resolution stubs of the call sites are placed behind the code of the method
+    #[cfg(asm_entry)]
+    for call_site in &mut call_sites {
+        call_site::emit_stub(&mut ops, call_site);
+    }
 
     // Create the function
     ops.commit()?;
     let buf = ops.finalize().expect("Failed to create the executable buffer");
+    #[cfg(asm_entry)]
+    let call_sites = call_sites
+        .into_iter()
+        .map(|call_site| call_site::finalize(call_site, &buf))
+        .collect();
+    #[cfg(not(asm_entry))]
+    let call_sites = Vec::new();
 
-    println!("============== Compilation output of {0} ==============", method.name);
-    println!("{}", disassemble::disassemble(&buf));
-    println!("========== End of compilation output of {0} ===========", method.name);
+    // Disassembling is only worth it if the output is shown
+    if log::log_enabled!(log::Level::Trace) {
+        log::trace!(
+            "Compilation output of {}:\n{}",
+            method.name,
+            disassemble::disassemble(&buf)
+        );
+    }
+
+    perf_map::register_code(
+        buf.ptr(start_offset) as usize,
+        buf.len() - start_offset.0,
+        &format!("{}::{}", owning_class.name()?, method.name),
+    );
 
     let function: NativeMethod = unsafe {
         std::mem::transmute(buf.ptr(start_offset))
     };
-    return Ok(MethodImplementation::Native(Box::new(function), Box::new(buf)))
+    Ok(MethodImplementation::Native(
+        Box::new(function),
+        Box::new(CompiledCode::new(buf, call_sites)),
+    ))
+}
+
+/// The interpreter reports the mismatch when it executes the method
+fn check_return_type(opcode: u8, return_type: JvmType) -> Result<(), CompilationError> {
+    if crate::interpreter::is_return_for(opcode, return_type) {
+        Ok(())
+    } else {
+        Err(CompilationError::ReturnTypeMismatch(opcode))
+    }
+}
+
+/// Returns from the method, the epilogue directly follows the last block
+fn return_to_caller(ops: &mut dynasmrt::x64::Assembler, next_block: Option<usize>) {
+    if next_block.is_some() {
+        dynasm!(ops
+            ; .arch x64
+            ; jmp ->exit
+        );
+    }
+}
+
+/// Emits the jumps of the conditional branch that ends the block, the flags must already be set by a comparison.
+/// The successor that is placed right behind the block is reached by falling through.
+fn emit_branch(
+    ops: &mut dynasmrt::x64::Assembler,
+    condition: Condition,
+    block: &Block,
+    next_block: Option<usize>,
+    labels: &HashMap<usize, DynamicLabel>,
+) {
+    let BlockExit::Branch(target) = block.exit else {
+        unreachable!("the block doesn't end with a conditional branch");
+    };
+    if next_block == Some(target) {
+        // The branch is usually taken
+        jump_if(ops, condition.negate(), labels[&block.end]);
+    } else {
+        jump_if(ops, condition, labels[&target]);
+        if next_block != Some(block.end) {
+            dynasm!(ops
+                ; .arch x64
+                ; jmp =>labels[&block.end]
+            );
+        }
+    }
+}
+
+/// The condition of a conditional branch, comparing the (first) operand with zero or the second operand
+#[derive(Debug, Clone, Copy)]
+enum Condition {
+    Equal,
+    NotEqual,
+    Less,
+    GreaterOrEqual,
+    Greater,
+    LessOrEqual,
+}
+
+impl Condition {
+    fn of(opcode: u8) -> Self {
+        // ifeq..ifle and if_icmpeq..if_icmple list the conditions in the same order
+        match (opcode - bytecode::IFEQ) % 6 {
+            0 => Self::Equal,
+            1 => Self::NotEqual,
+            2 => Self::Less,
+            3 => Self::GreaterOrEqual,
+            4 => Self::Greater,
+            _ => Self::LessOrEqual,
+        }
+    }
+
+    fn negate(self) -> Self {
+        match self {
+            Self::Equal => Self::NotEqual,
+            Self::NotEqual => Self::Equal,
+            Self::Less => Self::GreaterOrEqual,
+            Self::GreaterOrEqual => Self::Less,
+            Self::Greater => Self::LessOrEqual,
+            Self::LessOrEqual => Self::Greater,
+        }
+    }
+}
+
+fn jump_if(ops: &mut dynasmrt::x64::Assembler, condition: Condition, label: DynamicLabel) {
+    match condition {
+        Condition::Equal => dynasm!(ops ; .arch x64 ; je =>label),
+        Condition::NotEqual => dynasm!(ops ; .arch x64 ; jne =>label),
+        Condition::Less => dynasm!(ops ; .arch x64 ; jl =>label),
+        Condition::GreaterOrEqual => dynasm!(ops ; .arch x64 ; jge =>label),
+        Condition::Greater => dynasm!(ops ; .arch x64 ; jg =>label),
+        Condition::LessOrEqual => dynasm!(ops ; .arch x64 ; jle =>label),
+    }
 }
 
 fn push_constant<R: Relocation>(ops: &mut Assembler<R>, value: StackValue) {
@@ -188,7 +483,7 @@ fn push_wide_constant<R: Relocation>(ops: &mut Assembler<R>, value: (StackValue,
     dynasm!(ops
         ; .arch x64
         ; mov DWORD [r12], value.0.to_raw()
-        ; mov DWORD [r12 + 4], value.0.to_raw()
+        ; mov DWORD [r12 + 4], value.1.to_raw()
         ; add r12, 8
     );
 }
@@ -204,6 +499,7 @@ fn push_constant_type<R: Relocation>(ops: &mut Assembler<R>, value: JvmValue, ty
     }
 }
 
+#[cfg_attr(not(asm_entry), allow(dead_code))] // Calls are only compiled with the x86-64 method entry
 fn push<R: Relocation>(ops: &mut Assembler<R>) {
     dynasm!(ops
         ; .arch x64
@@ -212,6 +508,22 @@ fn push<R: Relocation>(ops: &mut Assembler<R>) {
     );
 }
 
+/// Pushes the value that a call has returned in rax
+#[cfg_attr(not(asm_entry), allow(dead_code))] // Calls are only compiled with the x86-64 method entry
+fn push_return_value<R: Relocation>(ops: &mut Assembler<R>, ty: JvmType) {
+    match ty {
+        JvmType::Void => {}
+        JvmType::Long | JvmType::Double => dynasm!(ops
+            ; .arch x64
+            ; mov [r12 + 4], eax  // Low half
+            ; shr rax, 32
+            ; mov [r12], eax      // High half
+            ; add r12, 8
+        ),
+        _ => push(ops),
+    }
+}
+
 fn pop<R: Relocation>(ops: &mut Assembler<R>) {
     dynasm!(ops
         ; .arch x64
@@ -220,28 +532,71 @@ fn pop<R: Relocation>(ops: &mut Assembler<R>) {
     );
 }
 
-fn pop_wide<R: Relocation>(ops: &mut Assembler<R>) {
-    dynasm!(ops
-        ; .arch x64
-        ; sub r12, 8
-        ; mov rax, [r12]
-    );
+/// Owns the machine code of a method implementation
+pub trait CodeBuffer {
+    /// The size of the machine code in bytes, which is charged against the code cache
+    fn code_size(&self) -> usize {
+        0
+    }
 }
 
-fn load_local<R: Relocation>(ops: &mut Assembler<R>, index: usize) {}
-
-pub trait CodeBuffer {}
-
-impl CodeBuffer for dynasmrt::ExecutableBuffer {}
+impl CodeBuffer for dynasmrt::ExecutableBuffer {
+    fn code_size(&self) -> usize {
+        self.len()
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum CompilationError {
     #[error("The end of the bytecode was reached but no return instruction has been found")]
     MissingReturn,
 
+    #[error("The branch at pc {0} jumps outside of the code")]
+    InvalidBranchTarget(usize),
+
+    #[error("The return instruction {0:#04x} doesn't match the return type of the method")]
+    ReturnTypeMismatch(u8),
+
+    #[error("The opcode {0:#04x} is not supported by the JIT yet")]
+    UnsupportedOpcode(u8),
+
     #[error(transparent)]
     ConstantPoolError(#[from] ConstantPoolError),
 
+    #[error("The JIT doesn't support exception handlers yet")]
+    ExceptionHandlers,
+
+    #[error("Compiled code doesn't check the watchpoints of fields")]
+    WatchedFields,
+
+    #[error("Compiled code can't stop at breakpoints")]
+    Breakpoints,
+
+    #[error(transparent)]
+    MethodError(#[from] MethodError),
+
+    #[error(transparent)]
+    FieldError(#[from] FieldError),
+
     #[error(transparent)]
     DynasmError(#[from] dynasmrt::DynasmError)
 }
+
+#[cfg(all(test, asm_entry))]
+mod tests {
+    use crate::testing;
+
+    #[test]
+    fn compiled_invokespecial_of_null_throws() {
+        let mut jvm = testing::jvm();
+        let class = jvm.load_class("NullChecks").unwrap();
+        let (method, _) = jvm
+            .classes()
+            .resolve(class)
+            .find_own_static_method("compiledInvokeSpecial", "()I")
+            .unwrap();
+        let code = super::compile_method(method, jvm.classes(), jvm.methods()).unwrap();
+        jvm.methods().update_method(method, code);
+        assert_eq!(testing::call_int(&mut jvm, "NullChecks", "invokeCompiled"), 1);
+    }
+}
@@ -0,0 +1,43 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+// Code regions are published in the format expected by perf (/tmp/perf-<pid>.map, one
+// "<start> <size> <name>" line per region) so that samples in JIT code can be symbolized
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static MAP_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Starts writing the perf map. Regions registered before this call are not recorded.
+pub fn enable() {
+    let path = format!("/tmp/perf-{}.map", std::process::id());
+    match OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(file) => {
+            *MAP_FILE.lock().unwrap() = Some(file);
+            ENABLED.store(true, Ordering::Release);
+            log::info!("Writing the perf map to {}", path);
+        }
+        Err(err) => log::warn!("Failed to open the perf map {}: {}", path, err),
+    }
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+pub fn register_code(start: usize, size: usize, name: &str) {
+    if !is_enabled() {
+        return;
+    }
+
+    if let Some(file) = MAP_FILE.lock().unwrap().as_mut() {
+        if let Err(err) = writeln!(file, "{:x} {:x} {}", start, size, name) {
+            log::warn!("Failed to write to the perf map: {}", err);
+        }
+    }
+}
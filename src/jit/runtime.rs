@@ -0,0 +1,221 @@
+use std::mem::offset_of;
+
+#[cfg(asm_entry)]
+use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi};
+
+use crate::{
+    interpreter::{self, budget, ExecutionError},
+    model::{
+        class_library::ClassLibrary,
+        constant_pool::ConstantPoolIndex,
+        heap::{Heap, NULL_POINTER},
+        method::{MethodIndex, MethodTable},
+        stack::StackPointer,
+        value::JvmValue,
+    },
+};
+#[cfg(asm_entry)]
+use crate::{jit::convention, model::method::RUNTIME_FUNCTIONS_OFFSET};
+
+// Compiled code calls back into the VM for the operations that are too complex to emit inline: allocating objects,
+// resolving fields, throwing exceptions and polling safepoints. The runtime functions are called through a fixed
+// table of function pointers, which the method table (r15) points to, so the compiled code doesn't embed their
+// addresses. A runtime function is called like a sysv64 function with
+// - the operand of the instruction (e.g. a constant pool index) in rdi,
+// - the VM context in rsi-r8, like the interpreter (see convention),
+// - the index of the compiled method in r9, whose constant pool the operand refers to.
+// The result is returned in rax. If the function fails, it leaves the error in the pending error slot like a callee,
+// and the compiled code returns to its caller immediately.
+// Safepoints are polled at the start of every loop of a compiled method, which charges the iteration against the
+// execution budget of the embedder (see interpreter::budget).
+
+/// A function of the VM that compiled code can call
+pub type RuntimeFunction = extern "sysv64" fn(
+    u32,
+    StackPointer,
+    *mut Heap,
+    *const ClassLibrary,
+    *const MethodTable,
+    MethodIndex,
+) -> u64;
+
+#[repr(C)]
+pub struct RuntimeFunctions {
+    new_object: RuntimeFunction,
+    get_field: RuntimeFunction,
+    throw: RuntimeFunction,
+    null_pointer: RuntimeFunction,
+    safepoint: RuntimeFunction,
+}
+
+pub static RUNTIME_FUNCTIONS: RuntimeFunctions = RuntimeFunctions {
+    new_object,
+    get_field,
+    throw,
+    null_pointer,
+    safepoint,
+};
+
+/// The entries of the table of runtime functions
+#[cfg_attr(not(asm_entry), allow(dead_code))]
+#[derive(Debug, Clone, Copy)]
+pub enum RuntimeCall {
+    NewObject, // The class is the operand, returns the new instance
+    GetField, // The field is the operand, the object is on top of the Java stack (and isn't popped)
+    Throw,    // The exception is on top of the Java stack, always fails
+    NullPointer, // Always fails with a NullPointerException, called if compiled code finds a null reference
+    Safepoint, // Fails if the execution budget is exhausted
+}
+
+impl RuntimeCall {
+    #[cfg_attr(not(asm_entry), allow(dead_code))]
+    fn offset(self) -> usize {
+        match self {
+            Self::NewObject => offset_of!(RuntimeFunctions, new_object),
+            Self::GetField => offset_of!(RuntimeFunctions, get_field),
+            Self::Throw => offset_of!(RuntimeFunctions, throw),
+            Self::NullPointer => offset_of!(RuntimeFunctions, null_pointer),
+            Self::Safepoint => offset_of!(RuntimeFunctions, safepoint),
+        }
+    }
+}
+
+/// Emits the call of the runtime function. Afterwards rax holds the result, or the compiled code has returned to its
+/// caller if the function failed.
+#[cfg(asm_entry)]
+pub fn emit_call(
+    ops: &mut dynasmrt::x64::Assembler,
+    call: RuntimeCall,
+    operand: u32,
+    method_index: MethodIndex,
+) {
+    dynasm!(ops
+        ; .arch x64
+        ; sub rsp, 8  // Align the stack to 16B
+        ; mov edi, DWORD operand as i32
+    );
+    convention::emit_pass_context(ops);
+    dynasm!(ops
+        ; .arch x64
+        ; mov r9d, DWORD unsafe { method_index.into_raw() } as i32
+        ; mov rax, [r15 + RUNTIME_FUNCTIONS_OFFSET as i32]
+        ; call QWORD [rax + call.offset() as i32]
+        ; mov [rsp], rax  // Keep the result in the padding slot
+        ; mov rax, QWORD interpreter::has_pending_error as *const () as i64
+        ; call rax
+        ; test al, al
+        ; mov rax, [rsp]
+        ; lea rsp, [rsp + 8]  // Doesn't change the flags
+        ; jnz ->exit  // The error stays pending for our caller
+    );
+}
+
+extern "sysv64" fn new_object(
+    index: u32,
+    stack: StackPointer,
+    heap: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+    method: MethodIndex,
+) -> u64 {
+    let (heap, classes, methods) = unsafe { (&mut *heap, &*classes, &*methods) };
+    let result = (|| {
+        let caller = classes.resolve(methods.get_data(method).owning_class);
+        let name = caller.resolve_type(ConstantPoolIndex::from(index as u16))?;
+        let class = classes.try_resolve_in(caller.loader(), name, methods, heap, stack)?;
+        classes.initialize(class.index(), methods, heap, stack)?;
+        let instance = heap.instantiate(class)?;
+        Ok(JvmValue {
+            reference: instance,
+        })
+    })();
+    finish(result, classes, methods)
+}
+
+extern "sysv64" fn get_field(
+    index: u32,
+    stack: StackPointer,
+    heap: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+    method: MethodIndex,
+) -> u64 {
+    let (heap, classes, methods) = unsafe { (&mut *heap, &*classes, &*methods) };
+    let result = (|| {
+        let objectref = stack.peek(0).as_reference().to_heap_index();
+        if objectref == NULL_POINTER {
+            return Err(ExecutionError::NullPointer);
+        }
+        let caller = classes.resolve(methods.get_data(method).owning_class);
+        let field = caller.resolve_instance_field(
+            ConstantPoolIndex::from(index as u16),
+            classes,
+            heap,
+            methods,
+            stack,
+        )?;
+        Ok(heap.resolve(objectref).get_field(field))
+    })();
+    finish(result, classes, methods)
+}
+
+extern "sysv64" fn throw(
+    _: u32,
+    stack: StackPointer,
+    _: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+    _: MethodIndex,
+) -> u64 {
+    let (classes, methods) = unsafe { (&*classes, &*methods) };
+    let exception = stack.peek(0).as_reference().to_heap_index();
+    let err = if exception == NULL_POINTER {
+        ExecutionError::NullPointer
+    } else {
+        interpreter::record_exception_stack_trace(exception, classes, methods);
+        ExecutionError::JavaException(exception)
+    };
+    finish(Err(err), classes, methods)
+}
+
+extern "sysv64" fn null_pointer(
+    _: u32,
+    _: StackPointer,
+    _: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+    _: MethodIndex,
+) -> u64 {
+    let (classes, methods) = unsafe { (&*classes, &*methods) };
+    finish(Err(ExecutionError::NullPointer), classes, methods)
+}
+
+extern "sysv64" fn safepoint(
+    _: u32,
+    _: StackPointer,
+    _: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+    _: MethodIndex,
+) -> u64 {
+    let (classes, methods) = unsafe { (&*classes, &*methods) };
+    let result = budget::charge()
+        .map(|_| JvmValue::VOID)
+        .map_err(ExecutionError::from);
+    finish(result, classes, methods)
+}
+
+/// Returns the value to the compiled code, or raises the error
+fn finish(
+    result: Result<JvmValue, ExecutionError>,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> u64 {
+    match result {
+        Ok(value) => unsafe { value.to_native() as u64 },
+        Err(err) => {
+            interpreter::raise_error(err, classes, methods);
+            0
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::string::FromUtf16Error;
+
+use crate::model::{
+    array::ArrayType,
+    boxing::{self, BoxingError},
+    class_library::{ClassIndex, ClassResolveError},
+    constant_pool::ConstantPoolError,
+    field::FieldError,
+    heap::{HeapError, HeapIndex, NULL_POINTER},
+    string::{self, StringError, STRING_CLASS, STRING_VALUE_FIELD},
+    types::JvmType,
+    value::JvmValue,
+};
+
+use super::Jvm;
+
+// Conversions between Rust values and guest values. Primitive values convert without the VM (see the From
+// implementations of JvmValue), strings and arrays are created on or read from the heap of the VM.
+// Strings use the layout of model::string.
+// Options of primitives convert to and from their wrapper objects (see model::boxing).
+
+/// A Rust value that can be passed to the guest
+pub trait ToGuest {
+    fn to_guest(&self, jvm: &mut Jvm) -> Result<JvmValue, ConversionError>;
+}
+
+/// A Rust value that can be created from a guest value. Values are untyped, so primitives are read
+/// as the requested type without any check; references are checked against their class.
+pub trait FromGuest: Sized {
+    fn from_guest(value: JvmValue, jvm: &mut Jvm) -> Result<Self, ConversionError>;
+}
+
+/// A type that can be the element type of a guest array, i.e. Vec<T> converts to and from T[]
+pub trait ArrayElement: ToGuest + FromGuest {
+    const ARRAY_TYPE: ArrayType;
+
+    /// The class of the elements of reference arrays (None for primitives and arrays of arrays)
+    const COMPONENT_CLASS: Option<&'static str> = None;
+}
+
+macro_rules! primitive_conversion {
+    ($ty:ty, $field:ident, $array_type:expr) => {
+        impl ToGuest for $ty {
+            fn to_guest(&self, _jvm: &mut Jvm) -> Result<JvmValue, ConversionError> {
+                Ok((*self).into())
+            }
+        }
+
+        impl FromGuest for $ty {
+            fn from_guest(value: JvmValue, _jvm: &mut Jvm) -> Result<Self, ConversionError> {
+                Ok(unsafe { value.$field } as $ty)
+            }
+        }
+
+        impl ArrayElement for $ty {
+            const ARRAY_TYPE: ArrayType = $array_type;
+        }
+    };
+}
+
+primitive_conversion!(i32, int, ArrayType::Int);
+primitive_conversion!(i64, long, ArrayType::Long);
+primitive_conversion!(f32, float, ArrayType::Float);
+primitive_conversion!(f64, double, ArrayType::Double);
+primitive_conversion!(i8, int, ArrayType::Byte);
+primitive_conversion!(i16, int, ArrayType::Short);
+primitive_conversion!(u16, int, ArrayType::Char); // Java's char
+
+impl ToGuest for bool {
+    fn to_guest(&self, _jvm: &mut Jvm) -> Result<JvmValue, ConversionError> {
+        Ok((*self).into())
+    }
+}
+
+impl FromGuest for bool {
+    fn from_guest(value: JvmValue, _jvm: &mut Jvm) -> Result<Self, ConversionError> {
+        Ok(value.int().0 != 0)
+    }
+}
+
+impl ArrayElement for bool {
+    const ARRAY_TYPE: ArrayType = ArrayType::Boolean;
+}
+
+macro_rules! boxed_conversion {
+    ($ty:ty, $jvm_type:expr, $wrapper:literal) => {
+        impl ToGuest for Option<$ty> {
+            fn to_guest(&self, jvm: &mut Jvm) -> Result<JvmValue, ConversionError> {
+                let object = match self {
+                    Some(value) => boxing::box_value(
+                        (*value).into(),
+                        $jvm_type,
+                        &jvm.classes,
+                        &mut jvm.heap,
+                        &jvm.methods,
+                        jvm.stack,
+                    )?,
+                    None => NULL_POINTER,
+                };
+                Ok(JvmValue { reference: object })
+            }
+        }
+
+        impl FromGuest for Option<$ty> {
+            fn from_guest(value: JvmValue, jvm: &mut Jvm) -> Result<Self, ConversionError> {
+                let object = value.reference().to_heap_index();
+                if object == NULL_POINTER {
+                    return Ok(None);
+                }
+                let value = boxing::unbox_value(object, $jvm_type, &jvm.classes, &mut jvm.heap)?;
+                Ok(Some(<$ty>::from_guest(value, jvm)?))
+            }
+        }
+
+        impl ArrayElement for Option<$ty> {
+            const ARRAY_TYPE: ArrayType = ArrayType::Reference;
+            const COMPONENT_CLASS: Option<&'static str> = Some($wrapper);
+        }
+    };
+}
+
+// Wrapper objects, None is null
+boxed_conversion!(i32, JvmType::Integer, "java/lang/Integer");
+boxed_conversion!(i64, JvmType::Long, "java/lang/Long");
+boxed_conversion!(f32, JvmType::Float, "java/lang/Float");
+boxed_conversion!(f64, JvmType::Double, "java/lang/Double");
+boxed_conversion!(i8, JvmType::Byte, "java/lang/Byte");
+boxed_conversion!(i16, JvmType::Short, "java/lang/Short");
+boxed_conversion!(u16, JvmType::Char, "java/lang/Character");
+boxed_conversion!(bool, JvmType::Boolean, "java/lang/Boolean");
+
+impl ToGuest for str {
+    fn to_guest(&self, jvm: &mut Jvm) -> Result<JvmValue, ConversionError> {
+        Ok(JvmValue {
+            reference: jvm.new_string(self)?,
+        })
+    }
+}
+
+impl ToGuest for String {
+    fn to_guest(&self, jvm: &mut Jvm) -> Result<JvmValue, ConversionError> {
+        self.as_str().to_guest(jvm)
+    }
+}
+
+impl FromGuest for String {
+    fn from_guest(value: JvmValue, jvm: &mut Jvm) -> Result<Self, ConversionError> {
+        jvm.read_string(value.reference().to_heap_index())
+    }
+}
+
+impl ArrayElement for String {
+    const ARRAY_TYPE: ArrayType = ArrayType::Reference;
+    const COMPONENT_CLASS: Option<&'static str> = Some(STRING_CLASS);
+}
+
+impl<T: ArrayElement> ToGuest for [T] {
+    fn to_guest(&self, jvm: &mut Jvm) -> Result<JvmValue, ConversionError> {
+        let component_class = match T::COMPONENT_CLASS {
+            Some(name) => Some(jvm.resolve_class(name)?),
+            None => None,
+        };
+        let object_class = jvm.resolve_class("java/lang/Object")?;
+        let array =
+            jvm.heap
+                .instantiate_array(T::ARRAY_TYPE, self.len(), component_class, object_class)?;
+        for (index, element) in self.iter().enumerate() {
+            // Converting the element may allocate, so the array is resolved again for every element
+            let value = element.to_guest(jvm)?;
+            jvm.heap.resolve_array(array).unwrap().set(index, value);
+        }
+        Ok(JvmValue { reference: array })
+    }
+}
+
+impl<T: ArrayElement> ToGuest for Vec<T> {
+    fn to_guest(&self, jvm: &mut Jvm) -> Result<JvmValue, ConversionError> {
+        self.as_slice().to_guest(jvm)
+    }
+}
+
+impl<T: ArrayElement> FromGuest for Vec<T> {
+    fn from_guest(value: JvmValue, jvm: &mut Jvm) -> Result<Self, ConversionError> {
+        let array = value.reference().to_heap_index();
+        if array == NULL_POINTER {
+            return Err(ConversionError::Null);
+        }
+        let length = match jvm.heap.resolve_array(array) {
+            Some(resolved) if resolved.ty() == T::ARRAY_TYPE => resolved.length(),
+            _ => return Err(jvm.type_mismatch(array, format!("{:?}[]", T::ARRAY_TYPE))),
+        };
+        (0..length)
+            .map(|index| {
+                let element = jvm.heap.resolve_array(array).unwrap().get(index);
+                T::from_guest(element, jvm)
+            })
+            .collect()
+    }
+}
+
+impl<T: ArrayElement> ArrayElement for Vec<T> {
+    const ARRAY_TYPE: ArrayType = ArrayType::Reference;
+}
+
+impl Jvm {
+    /// Converts a Rust value to a guest value, creating guest objects for strings and arrays
+    pub fn to_guest<T: ToGuest + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<JvmValue, ConversionError> {
+        value.to_guest(self)
+    }
+
+    /// Converts a guest value (e.g. the return value of call_static) to a Rust value
+    pub fn from_guest<T: FromGuest>(&mut self, value: JvmValue) -> Result<T, ConversionError> {
+        T::from_guest(value, self)
+    }
+
+    /// Creates a java/lang/String with the given content
+    pub fn new_string(&mut self, value: &str) -> Result<HeapIndex, ConversionError> {
+        Ok(string::new_string(
+            value,
+            &self.classes,
+            &mut self.heap,
+            &self.methods,
+            self.stack,
+        )?)
+    }
+
+    /// Reads the content of a java/lang/String
+    pub fn read_string(&mut self, string: HeapIndex) -> Result<String, ConversionError> {
+        if string == NULL_POINTER {
+            return Err(ConversionError::Null);
+        }
+        if self.heap.is_array(string)
+            || self
+                .classes
+                .resolve(self.heap.resolve(string).class())
+                .name()?
+                != STRING_CLASS
+        {
+            return Err(self.type_mismatch(string, STRING_CLASS.to_string()));
+        }
+
+        let class = self.classes.resolve(self.heap.resolve(string).class());
+        let value_field = class.field_layout().resolve(STRING_VALUE_FIELD)?;
+        let characters = self.heap.resolve(string).get_field(value_field);
+        let characters: Vec<u16> = self.from_guest(characters)?;
+        Ok(String::from_utf16(&characters)?)
+    }
+
+    fn resolve_class(&mut self, name: &str) -> Result<ClassIndex, ConversionError> {
+        Ok(self
+            .classes
+            .try_resolve_by_name(name, &self.methods, &mut self.heap, self.stack)?
+            .index())
+    }
+
+    fn type_mismatch(&mut self, object: HeapIndex, expected: String) -> ConversionError {
+        let actual = match self.heap.resolve_array(object) {
+            Some(array) => format!("{:?}[]", array.ty()),
+            None => self
+                .classes
+                .resolve(self.heap.resolve(object).class())
+                .name()
+                .unwrap_or("<unknown>")
+                .to_string(),
+        };
+        ConversionError::TypeMismatch { expected, actual }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConversionError {
+    #[error("expected an object, but got null")]
+    Null,
+
+    #[error("expected an instance of {expected}, but got an instance of {actual}")]
+    TypeMismatch { expected: String, actual: String },
+
+    #[error(transparent)]
+    InvalidString(#[from] FromUtf16Error),
+
+    #[error(transparent)]
+    ClassResolve(#[from] ClassResolveError),
+
+    #[error(transparent)]
+    ConstantPool(#[from] ConstantPoolError),
+
+    #[error(transparent)]
+    Field(#[from] FieldError),
+
+    #[error(transparent)]
+    Heap(#[from] HeapError),
+
+    #[error(transparent)]
+    Boxing(#[from] BoxingError),
+
+    #[error(transparent)]
+    String(#[from] StringError),
+}
@@ -0,0 +1,240 @@
+use crate::{
+    class_parser,
+    interpreter::ExecutionError,
+    model::{
+        class_library::{ClassIndex, ClassResolveError},
+        heap::{HeapIndex, NULL_POINTER},
+        mirror,
+        package::ClassLoaderId,
+        string,
+        value::JvmValue,
+    },
+    native::NativeContext,
+};
+
+// The natives of java.lang.ClassLoader that user-defined class loaders are built on. A loader written in Java
+// overrides findClass (or loadClass), reads the class file itself and hands it to defineClass, which ends in
+// defineClass1. The VM keys its classes by the defining loader and the name, so the same name may be defined by
+// several loaders. When a class of a user-defined loader refers to another class, the VM asks that loader with
+// loadClass (see ClassLibrary::try_resolve_in), which usually delegates to its parent first.
+// The ProtectionDomain and the source of defineClass1 are ignored.
+
+pub(super) const CLASS_LOADER_CLASS: &str = "java/lang/ClassLoader";
+
+/// ClassLoader.defineClass1: static with the loader as first argument since JDK 9, an instance method before, so the
+/// loader is the first argument either way
+pub(super) fn define_class(
+    context: &mut NativeContext,
+    args: &[JvmValue],
+) -> Result<JvmValue, ExecutionError> {
+    let loader = args[0].reference().to_heap_index();
+    let bytes = args[2].reference().to_heap_index();
+    if loader == NULL_POINTER || bytes == NULL_POINTER {
+        return Err(ExecutionError::NullPointer);
+    }
+    let (offset, length) = (args[3].int().0, args[4].int().0);
+    let array = context.heap.resolve_array(bytes).unwrap();
+    if offset < 0 || length < 0 || offset as usize + length as usize > array.length() {
+        return Err(ExecutionError::ArrayIndexOutOfBounds {
+            index: offset.saturating_add(length),
+            length: array.length(),
+        });
+    }
+    let class_file = (offset as usize..(offset + length) as usize)
+        .map(|index| array.get(index).int().0 as u8)
+        .collect::<Vec<_>>();
+
+    // The name may be null, then it is taken from the class file
+    let name = match class_name(args[1].reference().to_heap_index(), context)? {
+        Some(name) => name,
+        None => name_in_class_file(&class_file)?,
+    };
+    let loader = context.classes.loaders().write().unwrap().id_of(loader);
+    log::debug!("Defining {} by the class loader {:?}", name, loader);
+    let class = context.classes.define(
+        loader,
+        &name,
+        class_file,
+        context.heap,
+        context.methods,
+        context.stack,
+    )?;
+    let mirror = mirror::class_mirror(
+        class,
+        context.classes,
+        context.heap,
+        context.methods,
+        context.stack,
+    )?;
+    Ok(JvmValue { reference: mirror })
+}
+
+/// ClassLoader.findLoadedClass0(Ljava/lang/String;)Ljava/lang/Class;, null if the loader hasn't defined the class and
+/// hasn't been asked for it by the VM yet
+pub(super) fn find_loaded_class(
+    context: &mut NativeContext,
+    args: &[JvmValue],
+) -> Result<JvmValue, ExecutionError> {
+    let Some(name) = class_name(args[1].reference().to_heap_index(), context)? else {
+        return Ok(JvmValue {
+            reference: NULL_POINTER,
+        });
+    };
+    let loader = context
+        .classes
+        .loaders()
+        .write()
+        .unwrap()
+        .id_of(args[0].reference().to_heap_index());
+    let class = context.classes.find_loaded_by(loader, &name);
+    class_or_null(class, context)
+}
+
+/// ClassLoader.findBootstrapClass(Ljava/lang/String;)Ljava/lang/Class;, which is static since JDK 9. Returns null if
+/// the bootstrap loader can't find the class, so that the parent delegation continues with findClass.
+pub(super) fn find_bootstrap_class(
+    context: &mut NativeContext,
+    args: &[JvmValue],
+) -> Result<JvmValue, ExecutionError> {
+    let Some(name) = class_name(args[args.len() - 1].reference().to_heap_index(), context)? else {
+        return Ok(JvmValue {
+            reference: NULL_POINTER,
+        });
+    };
+    let class = match context.classes.try_resolve_in(
+        ClassLoaderId::Bootstrap,
+        &name,
+        context.methods,
+        context.heap,
+        context.stack,
+    ) {
+        Ok(class) => Some(class.index()),
+        Err(ClassResolveError::ClassNotFound(..)) => None,
+        Err(err) => return Err(err.into()),
+    };
+    class_or_null(class, context)
+}
+
+/// The binary name (e.g. java.lang.Object) in internal form, None for null
+fn class_name(
+    name: HeapIndex,
+    context: &mut NativeContext,
+) -> Result<Option<String>, ExecutionError> {
+    if name == NULL_POINTER {
+        return Ok(None);
+    }
+    let name = string::read_string(name, context.classes, context.heap)?;
+    Ok(Some(name.replace('.', "/")))
+}
+
+fn name_in_class_file(class_file: &[u8]) -> Result<String, ExecutionError> {
+    let (_, data, constant_pool) =
+        class_parser::parse(class_file).map_err(ClassResolveError::from)?;
+    let name = constant_pool
+        .resolve_type(data.this_class)
+        .map_err(ClassResolveError::from)?;
+    Ok(name.to_string())
+}
+
+fn class_or_null(
+    class: Option<ClassIndex>,
+    context: &mut NativeContext,
+) -> Result<JvmValue, ExecutionError> {
+    let reference = match class {
+        Some(class) => mirror::class_mirror(
+            class,
+            context.classes,
+            context.heap,
+            context.methods,
+            context.stack,
+        )?,
+        None => NULL_POINTER,
+    };
+    Ok(JvmValue { reference })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        jvm::{convert::ToGuest, Jvm},
+        model::{heap::HeapIndex, package::ClassLoaderId},
+        testing::{self, call_int},
+    };
+
+    /// Creates a Loaders that defines Square itself
+    fn square_loader(jvm: &mut Jvm) -> HeapIndex {
+        let class_file = include_bytes!("../../tests/classes/Square.class")
+            .iter()
+            .map(|byte| *byte as i8)
+            .collect::<Vec<_>>();
+        let name = "Square".to_guest(jvm).unwrap();
+        let class_file = class_file.to_guest(jvm).unwrap();
+        let loader = jvm
+            .call_static(
+                "Loaders",
+                "create",
+                "(Ljava/lang/String;[B)LLoaders;",
+                &[name, class_file],
+            )
+            .unwrap();
+        loader.reference().to_heap_index()
+    }
+
+    fn load_class(jvm: &mut Jvm, loader: HeapIndex, name: &str) -> HeapIndex {
+        let name = name.to_guest(jvm).unwrap();
+        let class = jvm
+            .call_virtual(
+                loader,
+                "loadClass",
+                "(Ljava/lang/String;)Ljava/lang/Class;",
+                &[name],
+            )
+            .unwrap();
+        class.reference().to_heap_index()
+    }
+
+    #[test]
+    fn loader_written_in_java_defines_its_own_version_of_a_class() {
+        let mut jvm = testing::jvm();
+        let loader = square_loader(&mut jvm);
+        let mirror = load_class(&mut jvm, loader, "Square");
+        let name = jvm.get_field(mirror, "name").unwrap();
+        assert_eq!(
+            jvm.read_string(name.reference().to_heap_index()).unwrap(),
+            "Square"
+        );
+        let class_loader = jvm.get_field(mirror, "classLoader").unwrap();
+        assert!(jvm.is_same_object(class_loader.reference().to_heap_index(), loader));
+        // The second request finds the class with findLoadedClass0
+        let again = load_class(&mut jvm, loader, "Square");
+        assert!(jvm.is_same_object(mirror, again));
+
+        // The class is keyed by its defining loader, so the bootstrap loader has its own Square
+        let id = ClassLoaderId::User(0);
+        let defined = jvm.classes().find_loaded_by(id, "Square").unwrap();
+        assert_eq!(jvm.classes().resolve(defined).loader(), id);
+        assert_ne!(jvm.load_class("Square").unwrap(), defined);
+    }
+
+    #[test]
+    fn superclass_is_resolved_through_the_loader_of_the_class() {
+        let mut jvm = testing::jvm();
+        let loader = square_loader(&mut jvm);
+        load_class(&mut jvm, loader, "Square");
+        // Defining Square asked the loader for its superclass Shape, which it left to the bootstrap loader
+        let id = ClassLoaderId::User(0);
+        let shape = jvm.classes().find_loaded_by(id, "Shape").unwrap();
+        assert_eq!(
+            jvm.classes().resolve(shape).loader(),
+            ClassLoaderId::Bootstrap
+        );
+        assert_eq!(jvm.classes().find_loaded("Shape"), Some(shape));
+        // The loader is recorded as an initiating loader of Shape and isn't asked again
+        let requests = call_int(&mut jvm, "Loaders", "requests");
+        assert_eq!(requests, 2);
+        jvm.classes
+            .try_resolve_in(id, "Shape", &jvm.methods, &mut jvm.heap, jvm.stack)
+            .unwrap();
+        assert_eq!(call_int(&mut jvm, "Loaders", "requests"), requests);
+    }
+}
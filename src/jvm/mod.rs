@@ -0,0 +1,641 @@
+pub mod convert;
+mod loaders;
+pub mod shutdown;
+pub mod version;
+
+use std::{fmt::Display, path::Path, rc::Rc, time::Duration};
+
+use crate::{
+    checkpoint::{Checkpoint, CheckpointError},
+    class_loader::BootstrapClassLoader,
+    interpreter::{
+        self, budget::ExecutionBudget, frame::StackTraceElement, thread::ThreadState,
+        ExecutionError,
+    },
+    jit::compile_command::CompilationFilter,
+    model::{
+        class_library::{ClassIndex, ClassLibrary, ClassResolveError},
+        class_list, cleaner,
+        heap::{Heap, HeapIndex, NULL_POINTER},
+        heap_dump,
+        method::{MethodIndex, MethodTable},
+        mirror,
+        package::ClassLoaderId,
+        stack::{StackFrame, StackPointer},
+        string,
+        types::JvmType,
+        value::JvmValue,
+    },
+    native::NativeContext,
+    options::{ExecutionMode, VmOptions},
+    verifier::{cache::VerificationCache, Verifier},
+};
+
+// Like the lists of the ClassLibrary, the call table is reserved large enough for the methods of java.base
+const CALL_TABLE_LENGTH: usize = 1 << 20;
+
+/// The embedding API: a complete VM instance that Rust hosts can load classes into and call methods on
+pub struct Jvm {
+    options: VmOptions,
+    classes: ClassLibrary,
+    heap: Heap,
+    methods: MethodTable,
+    stack: StackPointer,
+    shutdown_hooks: Vec<Box<dyn FnOnce(i32)>>,
+}
+
+impl Jvm {
+    pub fn new(mut options: VmOptions) -> Self {
+        if !interpreter::ASM_ENTRY && options.execution_mode != ExecutionMode::Interpreted {
+            log::warn!("The JIT requires the x86-64 method entry, all methods are interpreted");
+            options.execution_mode = ExecutionMode::Interpreted;
+        }
+        let class_loader = BootstrapClassLoader::new(
+            options.classpath.clone(),
+            options.boot_classpath_append.clone(),
+            options.module_patches.clone(),
+            options.boot_jdk.clone(),
+            options.embedded_boot_classes,
+        );
+        let classes = ClassLibrary::new(
+            class_loader,
+            options.check_module_access,
+            options.enable_preview,
+            options.class_limits,
+            options.verify.then(|| {
+                Verifier::new(
+                    options
+                        .verification_cache
+                        .as_deref()
+                        .map(VerificationCache::open),
+                )
+            }),
+        );
+        if let Err(err) = classes.define_modules() {
+            log::error!("Failed to define the modules on the classpath: {}", err);
+        }
+        if options.checkpoint_to.is_some() {
+            classes.record_class_files();
+        }
+        if let Some(path) = &options.restore_from {
+            match Checkpoint::read_from(path, &options) {
+                Ok(checkpoint) => {
+                    log::info!(
+                        "Restoring {} classes from the checkpoint {}",
+                        checkpoint.class_count(),
+                        path.display()
+                    );
+                    classes.restore(checkpoint);
+                }
+                Err(err) => log::warn!("Ignoring the checkpoint {}: {}", path.display(), err),
+            }
+        }
+        let mut jvm = Self {
+            classes,
+            heap: Heap::new(options.heap_size, options.gc),
+            methods: MethodTable::new(
+                CALL_TABLE_LENGTH,
+                options.execution_mode,
+                options.compilation_thresholds,
+                CompilationFilter::new(options.compile_commands.clone()),
+                options.code_cache_size,
+            ),
+            stack: StackPointer::with_size(20000),
+            shutdown_hooks: Vec::new(),
+            options,
+        };
+        // An explicit GC request runs the cleaner, because no collector would ever discover phantom reachable objects
+        jvm.register_native("java/lang/Runtime", "gc()V", |context, _| {
+            cleaner::run(
+                context.classes,
+                context.heap,
+                context.methods,
+                context.stack,
+            );
+            Ok(JvmValue::VOID)
+        });
+        // Throwables record the Java stack when they are created
+        jvm.register_native(
+            "java/lang/Throwable",
+            "fillInStackTrace(I)Ljava/lang/Throwable;",
+            |context, args| {
+                let throwable = args[0].reference().to_heap_index();
+                interpreter::fill_in_stack_trace(
+                    throwable,
+                    context.heap,
+                    context.classes,
+                    context.methods,
+                );
+                Ok(JvmValue {
+                    reference: throwable,
+                })
+            },
+        );
+        // Reflection hands out the cached mirrors, so that every call returns the same Field and Method objects
+        jvm.register_native(
+            "java/lang/Class",
+            "getDeclaredFields0(Z)[Ljava/lang/reflect/Field;",
+            |context, args| {
+                let class = context
+                    .classes
+                    .mirrors()
+                    .read()
+                    .unwrap()
+                    .reflected_class(args[0].reference().to_heap_index());
+                let fields = mirror::declared_fields(
+                    class,
+                    args[1].int().0 != 0,
+                    context.classes,
+                    context.heap,
+                    context.methods,
+                    context.stack,
+                )?;
+                Ok(JvmValue { reference: fields })
+            },
+        );
+        jvm.register_native(
+            "java/lang/Class",
+            "getDeclaredMethods0(Z)[Ljava/lang/reflect/Method;",
+            |context, args| {
+                let class = context
+                    .classes
+                    .mirrors()
+                    .read()
+                    .unwrap()
+                    .reflected_class(args[0].reference().to_heap_index());
+                let methods = mirror::declared_methods(
+                    class,
+                    args[1].int().0 != 0,
+                    context.classes,
+                    context.heap,
+                    context.methods,
+                    context.stack,
+                )?;
+                Ok(JvmValue { reference: methods })
+            },
+        );
+        // Sleeping is the only blocking operation so far, the thread state makes it visible in thread dumps
+        jvm.register_native("java/lang/Thread", "sleep(J)V", |_, args| {
+            sleep(Duration::from_millis(args[0].long().0.max(0) as u64));
+            Ok(JvmValue::VOID)
+        });
+        jvm.register_native("java/lang/Thread", "sleep0(J)V", |_, args| {
+            sleep(Duration::from_nanos(args[0].long().0.max(0) as u64));
+            Ok(JvmValue::VOID)
+        });
+        // System.exit ends with Shutdown.halt0 once the Java shutdown hooks have run (see shutdown)
+        jvm.register_native("java/lang/Shutdown", "halt0(I)V", shutdown::halt);
+        jvm.register_native("java/lang/Shutdown", "beforeHalt()V", |_, _| {
+            Ok(JvmValue::VOID)
+        });
+        jvm.register_native("java/lang/System", "exit(I)V", shutdown::exit);
+        // User-defined class loaders define their classes through the VM (see loaders)
+        jvm.register_native(
+            loaders::CLASS_LOADER_CLASS,
+            "defineClass1",
+            loaders::define_class,
+        );
+        jvm.register_native(
+            loaders::CLASS_LOADER_CLASS,
+            "findLoadedClass0(Ljava/lang/String;)Ljava/lang/Class;",
+            loaders::find_loaded_class,
+        );
+        jvm.register_native(
+            loaders::CLASS_LOADER_CLASS,
+            "findBootstrapClass(Ljava/lang/String;)Ljava/lang/Class;",
+            loaders::find_bootstrap_class,
+        );
+        // The PrintStream of the embedded bootstrap classes writes to the standard streams of the VM
+        jvm.register_native(
+            "java/io/PrintStream",
+            "write(ILjava/lang/String;)V",
+            |context, args| {
+                let string = args[1].reference().to_heap_index();
+                let content = if string == NULL_POINTER {
+                    "null".to_string()
+                } else {
+                    string::read_string(string, context.classes, context.heap)?
+                };
+                match args[0].int().0 {
+                    2 => eprint!("{}", content),
+                    _ => print!("{}", content),
+                }
+                Ok(JvmValue::VOID)
+            },
+        );
+        jvm
+    }
+
+    /// The version and the capabilities of this build of the VM
+    pub fn version() -> version::VersionInfo {
+        version::VersionInfo::current()
+    }
+
+    /// Loads the class if it hasn't been loaded yet. It is initialized on its first active use, e.g. by call_static.
+    pub fn load_class(&mut self, name: &str) -> Result<ClassIndex, ClassResolveError> {
+        Ok(self
+            .classes
+            .try_resolve_by_name(name, &self.methods, &mut self.heap, self.stack)?
+            .index())
+    }
+
+    /// Loads the class from the class file instead of searching it on the classpath, e.g. a class that the host has
+    /// generated. Fails if a class with the name has already been loaded.
+    #[cfg_attr(not(feature = "fuzzing"), allow(dead_code))]
+    pub fn define_class(
+        &mut self,
+        name: &str,
+        class_file: Vec<u8>,
+    ) -> Result<ClassIndex, ClassResolveError> {
+        self.classes.define(
+            ClassLoaderId::Bootstrap,
+            name,
+            class_file,
+            &mut self.heap,
+            &self.methods,
+            self.stack,
+        )
+    }
+
+    /// Implements the native method `method` of `class` (e.g. "java/lang/System", "nanoTime") with a Rust closure.
+    /// A descriptor after the name (e.g. "sleep(J)V") restricts the closure to one overload, otherwise it implements
+    /// all native overloads of the method. The method can be registered before or after its class has been loaded.
+    pub fn register_native<F>(&mut self, class: &str, method: &str, function: F)
+    where
+        F: Fn(&mut NativeContext, &[JvmValue]) -> Result<JvmValue, ExecutionError> + 'static,
+    {
+        self.methods
+            .register_native_function(class, method, Rc::new(function));
+
+        for index in 0..self.methods.method_count() {
+            let data = self.methods.get_data(index.into());
+            if data.is_native
+                && (data.name == method || format!("{}{}", data.name, data.descriptor) == method)
+                && self.classes.resolve(data.owning_class).name().ok() == Some(class)
+            {
+                self.methods.bind_native_method(index.into(), class);
+            }
+        }
+    }
+
+    /// Calls the static method with the given name and descriptor (e.g. "(IJ)V") with the given arguments
+    /// (which must match the parameter types of the method).
+    /// Exceptions that are not caught by the guest code and errors of the VM itself are returned as GuestException.
+    pub fn call_static(
+        &mut self,
+        class: &str,
+        method: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+    ) -> Result<JvmValue, GuestException> {
+        let class = self
+            .load_class(class)
+            .map_err(|err| self.guest_exception(err.into()))?;
+        let class = self.classes.resolve(class);
+        let (method_index, _) = class
+            .find_own_static_method(method, descriptor)
+            .ok_or_else(|| {
+                GuestException::from_vm_error(
+                    "java/lang/NoSuchMethodError",
+                    format!(
+                        "{}.{}{}",
+                        class.name().unwrap_or("<unknown>"),
+                        method,
+                        descriptor
+                    ),
+                    Vec::new(),
+                )
+            })?;
+        // Calling a static method is an active use of the class
+        let declaring_class = self.methods.get_data(method_index).owning_class;
+        self.classes
+            .initialize(declaring_class, &self.methods, &mut self.heap, self.stack)
+            .map_err(|err| self.guest_exception(err))?;
+
+        self.invoke(method_index, None, args)
+    }
+
+    /// Calls the instance method with the given name and descriptor on the object, dispatched on the class of the
+    /// object like invokevirtual. The arguments don't include the receiver.
+    pub fn call_virtual(
+        &mut self,
+        object: HeapIndex,
+        method: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+    ) -> Result<JvmValue, GuestException> {
+        let class = self.object_class(object)?;
+        let class = self.classes.resolve(class);
+        let method_index = class
+            .find_own_method(method, descriptor)
+            .filter(|_| class.find_own_static_method(method, descriptor).is_none())
+            .ok_or_else(|| {
+                GuestException::from_vm_error(
+                    "java/lang/NoSuchMethodError",
+                    format!(
+                        "{}.{}{}",
+                        class.name().unwrap_or("<unknown>"),
+                        method,
+                        descriptor
+                    ),
+                    Vec::new(),
+                )
+            })?;
+        self.invoke(method_index, Some(object), args)
+    }
+
+    /// Reads the field with the given name (declared by the class of the object or one of its superclasses)
+    pub fn get_field(
+        &mut self,
+        object: HeapIndex,
+        field: &str,
+    ) -> Result<JvmValue, GuestException> {
+        let class = self.object_class(object)?;
+        let class = self.classes.resolve(class);
+        let info = class.field_layout().resolve(field).map_err(|_| {
+            GuestException::from_vm_error(
+                "java/lang/NoSuchFieldError",
+                format!("{}.{}", class.name().unwrap_or("<unknown>"), field),
+                Vec::new(),
+            )
+        })?;
+        Ok(self.heap.resolve(object).get_field(info))
+    }
+
+    /// Whether both references denote the same object (the == operator of Java), null is only the same as null
+    pub fn is_same_object(&self, first: HeapIndex, second: HeapIndex) -> bool {
+        first == second
+    }
+
+    /// The class of an instance (arrays have no fields or methods of their own)
+    fn object_class(&mut self, object: HeapIndex) -> Result<ClassIndex, GuestException> {
+        if object == NULL_POINTER {
+            return Err(GuestException::from_vm_error(
+                "java/lang/NullPointerException",
+                "the object is null".to_string(),
+                Vec::new(),
+            ));
+        }
+        if self.heap.is_array(object) {
+            return Err(GuestException::from_vm_error(
+                "java/lang/IllegalArgumentException",
+                "the object is an array".to_string(),
+                Vec::new(),
+            ));
+        }
+        Ok(self.heap.resolve(object).class())
+    }
+
+    /// Pushes the receiver (if any) and the arguments and calls the method
+    fn invoke(
+        &mut self,
+        method_index: MethodIndex,
+        receiver: Option<HeapIndex>,
+        args: &[JvmValue],
+    ) -> Result<JvmValue, GuestException> {
+        let parameters = &self.methods.get_data(method_index).parameters;
+        if parameters.len() != args.len() {
+            return Err(GuestException::from_vm_error(
+                "java/lang/IllegalArgumentException",
+                format!(
+                    "expected {} arguments, got {}",
+                    parameters.len(),
+                    args.len()
+                ),
+                Vec::new(),
+            ));
+        }
+        let argument_slots = receiver.iter().count()
+            + parameters
+                .iter()
+                .map(|parameter| parameter.slots())
+                .sum::<usize>();
+        let mut arguments =
+            StackFrame::prepare(self.stack, 0, 0, argument_slots).map_err(|err| {
+                GuestException::from_vm_error(
+                    "java/lang/StackOverflowError",
+                    err.to_string(),
+                    Vec::new(),
+                )
+            })?;
+        if let Some(receiver) = receiver {
+            arguments.push_value(receiver.into(), JvmType::Reference);
+        }
+        for (arg, ty) in args.iter().zip(parameters) {
+            arguments.push_value(*arg, *ty);
+        }
+
+        // Discard the trace of an earlier failed call
+        interpreter::take_error_stack_trace();
+        interpreter::call_method(
+            method_index,
+            arguments.get_stack_for_call(),
+            &mut self.heap,
+            &self.classes,
+            &self.methods,
+        )
+        .map_err(|err| self.guest_exception(err))
+    }
+
+    /// Like call_static, but stops the execution with an InternalError once the interpreter has used up the budget
+    /// (see interpreter::budget). Guest code can't catch this error.
+    pub fn call_static_with_budget(
+        &mut self,
+        class: &str,
+        method: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+        budget: ExecutionBudget,
+    ) -> Result<JvmValue, GuestException> {
+        let _budget = budget.enter();
+        self.call_static(class, method, descriptor, args)
+    }
+
+    fn guest_exception(&mut self, err: ExecutionError) -> GuestException {
+        let stack_trace = interpreter::take_error_stack_trace().unwrap_or_default();
+        match err {
+            ExecutionError::JavaException(throwable) => {
+                let class = self.classes.resolve(self.heap.resolve(throwable).class());
+                GuestException {
+                    class_name: class.name().unwrap_or("<unknown>").to_string(),
+                    message: self.exception_message(throwable),
+                    stack_trace: interpreter::exception_stack_trace(throwable).unwrap_or_default(),
+                    throwable: Some(throwable),
+                }
+            }
+            // VM errors are reported as the Java errors a complete VM would throw in their place
+            _ => GuestException::from_vm_error(
+                err.java_exception_class()
+                    .unwrap_or("java/lang/InternalError"),
+                err.detail_message(),
+                stack_trace,
+            ),
+        }
+    }
+
+    /// Reads the detail message of a throwable (the field detailMessage of java/lang/Throwable)
+    fn exception_message(&mut self, throwable: HeapIndex) -> Option<String> {
+        let class = self.classes.resolve(self.heap.resolve(throwable).class());
+        let message_field = class.field_layout().resolve("detailMessage").ok()?;
+        let message = self.heap.resolve(throwable).get_field(message_field);
+        self.read_string(message.reference().to_heap_index()).ok()
+    }
+
+    /// Runs the action of the host once the object has become phantom reachable, e.g. to close a file handle that
+    /// belongs to it. The action must not reference the object itself.
+    pub fn register_cleanup<F>(&mut self, object: HeapIndex, action: F)
+    where
+        F: FnOnce() + 'static,
+    {
+        self.heap.register_cleanup(object, Box::new(action));
+    }
+
+    /// Runs the cleaning actions of all objects that have become phantom reachable (see model::cleaner).
+    /// Returns the number of actions that have been run.
+    pub fn run_cleaner(&mut self) -> usize {
+        cleaner::run(&self.classes, &mut self.heap, &self.methods, self.stack)
+    }
+
+    /// Runs the hook with the exit status when the VM shuts down, after the shutdown hooks of the guest
+    pub fn add_shutdown_hook<F>(&mut self, hook: F)
+    where
+        F: FnOnce(i32) + 'static,
+    {
+        self.shutdown_hooks.push(Box::new(hook));
+    }
+
+    /// The exit status if guest code has called System.exit. Calls into the guest that were terminated by the exit
+    /// return an InternalError.
+    pub fn exit_status(&self) -> Option<i32> {
+        shutdown::exit_status()
+    }
+
+    /// Runs the shutdown sequence (see shutdown), e.g. after the main method has returned. Returns the exit status,
+    /// which is the status of System.exit if the guest has called it and the given status otherwise.
+    pub fn shutdown(&mut self, status: i32) -> i32 {
+        // Like DestroyJavaVM, the Java hooks only run if the class library has used java.lang.Shutdown
+        if shutdown::java_hooks_pending()
+            && self.classes.find_loaded(shutdown::SHUTDOWN_CLASS).is_some()
+        {
+            if let Err(exception) =
+                self.call_static(shutdown::SHUTDOWN_CLASS, "shutdown", "()V", &[])
+            {
+                if self.exit_status().is_none() {
+                    log::warn!("The shutdown hooks failed: {}", exception);
+                }
+            }
+        }
+        let status = self.exit_status().unwrap_or(status);
+        self.classes.save_verification_cache();
+        for hook in std::mem::take(&mut self.shutdown_hooks) {
+            hook(status);
+        }
+        log::info!(
+            "Shutting down with status {} ({} of {} heap bytes and {} bytes of compiled code used)",
+            status,
+            self.heap.used(),
+            self.heap.capacity(),
+            self.methods.code_cache_used()
+        );
+        status
+    }
+
+    /// Stores the loaded classes, their static fields and the compiled methods (see checkpoint)
+    pub fn write_checkpoint(&self, path: &Path) -> Result<(), CheckpointError> {
+        Checkpoint::capture(&self.classes, &self.methods, &self.options)?.write_to(path)
+    }
+
+    /// Writes the names of the loaded classes in the order of their indices (see model::class_list)
+    pub fn write_class_list(&self, path: &Path) -> std::io::Result<()> {
+        class_list::write(path, &self.classes)
+    }
+
+    /// Loads the classes of a list that has been written by write_class_list in its order, returns the number of
+    /// classes that have been loaded. Classes that can't be loaded are skipped, which shifts the indices of the
+    /// following classes.
+    pub fn replay_class_list(&mut self, path: &Path) -> std::io::Result<usize> {
+        let mut loaded = 0;
+        for name in class_list::read(path)? {
+            if self.classes.find_loaded(&name).is_some() {
+                continue;
+            }
+            match self
+                .classes
+                .try_resolve_by_name(&name, &self.methods, &mut self.heap, self.stack)
+            {
+                Ok(_) => loaded += 1,
+                Err(err) => log::warn!(
+                    "Skipping {} of the class list, the following classes get different indices: {}",
+                    name,
+                    err
+                ),
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Writes the object graph of the heap as text (see model::heap_dump)
+    pub fn write_heap_dump(&mut self, path: &Path) -> std::io::Result<()> {
+        heap_dump::write(path, &mut self.heap, &self.classes)
+    }
+
+    pub fn options(&self) -> &VmOptions {
+        &self.options
+    }
+
+    pub fn classes(&self) -> &ClassLibrary {
+        &self.classes
+    }
+
+    pub fn methods(&self) -> &MethodTable {
+        &self.methods
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+}
+
+fn sleep(duration: Duration) {
+    let _state = ThreadState::TimedWaiting.enter();
+    std::thread::sleep(duration);
+}
+
+/// A throwable that terminated a call from the host into the guest
+#[derive(Debug, Clone)]
+pub struct GuestException {
+    pub class_name: String, // Internal form, e.g. java/lang/RuntimeException
+    pub message: Option<String>,
+    pub stack_trace: Vec<StackTraceElement>, // Newest frame first
+    pub throwable: Option<HeapIndex>, // None if the exception has been raised by the VM and has no guest object
+}
+
+impl GuestException {
+    fn from_vm_error(
+        class_name: &str,
+        message: String,
+        stack_trace: Vec<StackTraceElement>,
+    ) -> Self {
+        Self {
+            class_name: class_name.to_string(),
+            message: Some(message),
+            stack_trace,
+            throwable: None,
+        }
+    }
+}
+
+impl Display for GuestException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.class_name.replace('/', "."))?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        for element in &self.stack_trace {
+            write!(f, "\n\tat {}", element)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GuestException {}
@@ -0,0 +1,49 @@
+use std::cell::Cell;
+
+use crate::{interpreter::ExecutionError, model::value::JvmValue, native::NativeContext};
+
+// The VM terminates like HotSpot: either when the main method returns (the launcher then calls Jvm::shutdown) or when
+// Java code calls System.exit. In the JDK, System.exit runs the shutdown hooks in Java (java.lang.Shutdown) and then
+// halts the VM via the native Shutdown.halt0. Halting unwinds all Java frames with ExecutionError::Exit, which is no
+// Java exception, so neither handlers nor finally blocks run. Class libraries without java.lang.Shutdown may
+// implement System.exit as a native method instead, in which case the VM runs the hooks itself.
+// The shutdown sequence (Jvm::shutdown) runs the Java shutdown hooks (if they haven't run yet), then the hooks of
+// the host in the order of their registration. The heap and the compiled code are freed when the Jvm is dropped.
+
+pub(super) const SHUTDOWN_CLASS: &str = "java/lang/Shutdown";
+
+thread_local! {
+    // The status of a requested exit and whether the Java shutdown hooks have already run
+    static EXIT: Cell<Option<(i32, bool)>> = const { Cell::new(None) };
+}
+
+/// The exit status that Java code has requested, None if it hasn't called System.exit (or Runtime.halt)
+pub fn exit_status() -> Option<i32> {
+    EXIT.with(|exit| exit.get()).map(|(status, _)| status)
+}
+
+/// Whether the shutdown hooks of java.lang.Shutdown still have to run before the VM terminates
+pub(super) fn java_hooks_pending() -> bool {
+    !matches!(EXIT.with(|exit| exit.get()), Some((_, true)))
+}
+
+/// Shutdown.halt0(I)V, called after the Java shutdown hooks have run
+pub(super) fn halt(_: &mut NativeContext, args: &[JvmValue]) -> Result<JvmValue, ExecutionError> {
+    request_exit(args[0].int().0, true)
+}
+
+/// System.exit(I)V of class libraries that implement it natively
+pub(super) fn exit(_: &mut NativeContext, args: &[JvmValue]) -> Result<JvmValue, ExecutionError> {
+    request_exit(args[0].int().0, false)
+}
+
+fn request_exit(status: i32, hooks_done: bool) -> Result<JvmValue, ExecutionError> {
+    // A nested exit (e.g. from a shutdown hook) keeps the status of the first one
+    let (status, _) = EXIT.with(|exit| {
+        let request = exit.get().unwrap_or((status, hooks_done));
+        exit.set(Some(request));
+        request
+    });
+    log::info!("Java code requested the VM to exit with status {}", status);
+    Err(ExecutionError::Exit(status))
+}
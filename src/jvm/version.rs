@@ -0,0 +1,64 @@
+use std::fmt::Display;
+
+use crate::model::{
+    class_file::{MAX_MAJOR_VERSION, MIN_MAJOR_VERSION},
+    gc::GarbageCollector,
+};
+
+/// What this build of the VM supports (jvm --version), so that bug reports and tools can tell builds apart
+#[derive(Debug, Clone)]
+pub struct VersionInfo {
+    pub vm_version: &'static str,
+    pub min_class_file_version: u16, // Major versions, the preview features of the latest one can be enabled
+    pub max_class_file_version: u16,
+    pub jit: bool, // The JIT needs the assembly entry, i.e. x86-64 without the feature portable-entry
+    pub garbage_collectors: &'static [GarbageCollector],
+    pub threads: bool, // Whether Java threads can run, the VM only executes the main thread so far
+    pub fuzzing: bool, // The fuzzing harness has been built in (-XX:FuzzMethod)
+    pub target_arch: &'static str,
+    pub target_os: &'static str,
+}
+
+impl VersionInfo {
+    pub(super) fn current() -> Self {
+        Self {
+            vm_version: env!("CARGO_PKG_VERSION"),
+            min_class_file_version: MIN_MAJOR_VERSION,
+            max_class_file_version: MAX_MAJOR_VERSION,
+            jit: cfg!(asm_entry),
+            garbage_collectors: GarbageCollector::ALL,
+            threads: false,
+            fuzzing: cfg!(feature = "fuzzing"),
+            target_arch: std::env::consts::ARCH,
+            target_os: std::env::consts::OS,
+        }
+    }
+}
+
+impl Display for VersionInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let enabled = |enabled| if enabled { "enabled" } else { "disabled" };
+        writeln!(
+            f,
+            "jvm {} ({}-{})",
+            self.vm_version, self.target_arch, self.target_os
+        )?;
+        writeln!(
+            f,
+            "Class file versions: {}.0 to {}.0 (preview features of {})",
+            self.min_class_file_version, self.max_class_file_version, self.max_class_file_version
+        )?;
+        writeln!(f, "JIT compiler: {}", enabled(self.jit))?;
+        writeln!(
+            f,
+            "Garbage collectors: {}",
+            self.garbage_collectors
+                .iter()
+                .map(GarbageCollector::name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        writeln!(f, "Threads: {}", enabled(self.threads))?;
+        write!(f, "Fuzzing harness: {}", enabled(self.fuzzing))
+    }
+}
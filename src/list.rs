@@ -1,30 +1,71 @@
-use std::{alloc::Layout, sync::atomic::{AtomicUsize, Ordering}};
+use std::{
+    alloc::Layout,
+    ops::Index,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+};
 
 #[repr(C)]
 pub struct NativeList<T: Copy> {
     list: *mut T,
-    layout: Layout
+    layout: Layout,
+    capacity: usize,
 }
 
+// The elements are only accessed through the unsafe get and set, whose callers must not access the same element from
+// several threads at once
+unsafe impl<T: Copy + Send> Send for NativeList<T> {}
+unsafe impl<T: Copy + Send> Sync for NativeList<T> {}
+
 impl<T: Copy> NativeList<T> {
     pub fn alloc(capacity: usize, align: usize) -> Self {
         let layout = Layout::from_size_align(capacity * std::mem::size_of::<T>(), align).unwrap();
-        let list = unsafe { std::alloc::alloc(layout) as *mut T };
+        // Zeroed, because the static fields of a class start with their default values
+        let list = unsafe { std::alloc::alloc_zeroed(layout) as *mut T };
         Self {
             list,
-            layout
+            layout,
+            capacity,
         }
     }
 
+    /// The number of elements, the list never grows
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Panics if the index is out of bounds
+    ///
+    /// # Safety
+    /// No other thread may write the element at the same time
     pub unsafe fn get(&self, index: usize) -> T {
-        *self.list.offset(index as isize)
+        assert!(
+            index < self.capacity,
+            "index {} out of bounds ({} elements)",
+            index,
+            self.capacity
+        );
+        *self.list.add(index)
     }
 
-    /// We don't require mutable access as we will never reallocate
+    /// We don't require mutable access as we will never reallocate. Panics if the index is out of bounds.
+    ///
+    /// # Safety
+    /// No other thread may access the element at the same time
     pub unsafe fn set(&self, index: usize, value: T) {
-        *self.list.offset(index as isize) = value;
+        assert!(
+            index < self.capacity,
+            "index {} out of bounds ({} elements)",
+            index,
+            self.capacity
+        );
+        *self.list.add(index) = value;
     }
 
+    /// # Safety
+    /// Writes through the pointer must follow the same rules as set
     pub unsafe fn get_pointer(&self) -> *mut T {
         self.list
     }
@@ -44,3 +85,80 @@ impl<T: Copy> Drop for NativeList<T> {
         }
     }
 }
+
+const FIRST_CHUNK_LENGTH: usize = 16;
+const CHUNK_COUNT: usize = usize::BITS as usize - 4; // Enough chunks to hold usize::MAX elements
+
+/// An append-only list like appendlist::AppendList, whose elements never move, but that can be read by other threads
+/// while it grows. The elements live in chunks that double in size, so a chunk is never reallocated. An element is
+/// published by storing the length after it has been written, so every index below len refers to a complete element.
+pub(crate) struct PublishedList<T> {
+    chunks: [OnceLock<Box<[OnceLock<T>]>>; CHUNK_COUNT],
+    len: AtomicUsize,
+    append: Mutex<()>, // Appends are serialized, reads don't take it
+}
+
+impl<T> PublishedList<T> {
+    pub fn new() -> Self {
+        Self {
+            chunks: std::array::from_fn(|_| OnceLock::new()),
+            len: AtomicUsize::new(0),
+            append: Mutex::new(()),
+        }
+    }
+
+    /// Appends the element and returns its index
+    pub fn push(&self, value: T) -> usize {
+        let _append = self.append.lock().unwrap();
+        let index = self.len.load(Ordering::Relaxed);
+        let (chunk, offset) = Self::position(index);
+        let chunk = self.chunks[chunk].get_or_init(|| {
+            (0..FIRST_CHUNK_LENGTH << chunk)
+                .map(|_| OnceLock::new())
+                .collect()
+        });
+        if chunk[offset].set(value).is_err() {
+            unreachable!("the element {} has already been published", index);
+        }
+        self.len.store(index + 1, Ordering::Release);
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len() {
+            return None;
+        }
+        let (chunk, offset) = Self::position(index);
+        self.chunks[chunk].get()?[offset].get()
+    }
+
+    /// The elements that have been published when the iteration starts
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        (0..self.len()).map(|index| &self[index])
+    }
+
+    /// The chunk of the element and its offset in the chunk
+    fn position(index: usize) -> (usize, usize) {
+        let chunk = (index / FIRST_CHUNK_LENGTH + 1).ilog2() as usize;
+        (chunk, index - FIRST_CHUNK_LENGTH * ((1 << chunk) - 1))
+    }
+}
+
+impl<T> Default for PublishedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<usize> for PublishedList<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index)
+            .unwrap_or_else(|| panic!("index {} out of bounds ({} elements)", index, self.len()))
+    }
+}
@@ -1,54 +1,254 @@
-#![feature(asm_sym)]
-#![feature(naked_functions)]
-#![feature(int_roundings)]
-
 pub mod bytecode;
+pub mod checkpoint;
 pub mod class_loader;
 pub mod class_parser;
+#[allow(dead_code)] // Only the fuzzing harness generates classes so far
+pub mod class_writer;
+pub mod crash;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
 pub mod interpreter;
+pub mod intrinsics;
+pub mod javap;
 pub mod jit;
+pub mod jvm;
 pub mod model;
 pub mod list;
-
-use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi};
-use std::arch::{asm, global_asm};
+pub mod native;
+pub mod options;
+#[cfg(test)]
+mod testing;
+pub mod verifier;
 
 use crate::{
-    class_loader::BootstrapClassLoader,
-    model::{
-        class_library::ClassLibrary,
-        heap::Heap,
-        method::MethodTable,
-        stack::StackPointer,
-    },
+    jvm::Jvm,
+    options::{ExecutionMode, VmOptions},
 };
 
+#[cfg(feature = "fuzzing")]
+fn run_fuzz_input(path: &std::path::Path) {
+    match std::fs::read(path) {
+        Ok(input) => println!("{}", fuzz::fuzz_method(&input)),
+        Err(err) => {
+            eprintln!("Error: can't read {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "fuzzing"))]
+fn run_fuzz_input(_path: &std::path::Path) {
+    eprintln!("Error: -XX:FuzzMethod requires a build with the feature fuzzing");
+    std::process::exit(1);
+}
+
 fn main() {
     env_logger::builder()
         .filter_level(log::LevelFilter::Info)
         .init();
 
-    let class_loader = BootstrapClassLoader::new();
-    let classes = ClassLibrary::new(class_loader);
-    let mut heap = Heap::new(20000);
-    let methods = MethodTable::new(100);
-    let stack = StackPointer::with_size(20000);
+    if std::env::var_os("JVM_PERF_MAP").is_some() {
+        jit::perf_map::enable();
+    }
+
+    let options = match VmOptions::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if options.print_version {
+        println!("{}", Jvm::version());
+        return;
+    }
+
+    if options.list_unsupported {
+        print!("{}", bytecode::unsupported_opcodes_report());
+        return;
+    }
+
+    if let Some(path) = &options.print_class_file {
+        let description = std::fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| javap::print_class_file(&bytes).map_err(|err| err.to_string()));
+        match description {
+            Ok(description) => print!("{}", description),
+            Err(err) => {
+                eprintln!("Error: can't print {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = &options.fuzz_method {
+        run_fuzz_input(path);
+        return;
+    }
+
+    if options.call_graph_dump.is_some() {
+        jit::call_graph::enable();
+    }
+    if options.class_graph_dump.is_some() {
+        model::class_graph::enable();
+    }
+
+    if options.trace_methods {
+        interpreter::observer::install(Box::new(interpreter::observer::MethodTracer));
+    }
+    interpreter::watchpoint::install(options.watchpoints.clone());
+    interpreter::breakpoint::install(options.breakpoints.clone());
+
+    if !options.fuse_instructions {
+        interpreter::fusion::disable();
+    }
+    if options.print_fusion_statistics {
+        interpreter::fusion::enable_statistics();
+    }
+    if options.print_resolution_statistics {
+        model::resolution_statistics::enable();
+    }
+    if !options.eliminate_tail_calls {
+        interpreter::tail_call::disable();
+    }
+    if options.check_stack_tags {
+        model::stack::enable_tags();
+    }
 
-    classes.resolve_by_name("classes/Object", &methods, &mut heap, stack);
+    let mut jvm = Jvm::new(options.clone());
 
-    let class = classes.resolve_by_name("Test", &methods, &mut heap, stack).index();
+    crash::install_handler(jvm.heap(), jvm.classes(), jvm.methods());
+
+    if let Err(err) = jvm.load_class("classes/Object") {
+        eprintln!("Error: can't load classes/Object: {}", err);
+        std::process::exit(1);
+    }
+
+    // Object is always loaded first, the other classes of the list are loaded before the main class
+    if let Some(path) = &options.replay_loaded_class_list {
+        match jvm.replay_class_list(path) {
+            Ok(count) => log::info!(
+                "Loaded {} classes of the class list {}",
+                count,
+                path.display()
+            ),
+            Err(err) => log::error!("Failed to read the class list {}: {}", path.display(), err),
+        }
+    }
+
+    // Like the java launcher, the main class of a jar is named by its manifest
+    let main_class = match &options.jar {
+        Some(jar) => jvm
+            .classes()
+            .class_loader()
+            .main_class(jar)
+            .unwrap_or_else(|| {
+                eprintln!("Error: no main manifest attribute, in {}", jar.display());
+                std::process::exit(1);
+            }),
+        None => options.main_class.clone().unwrap_or_else(|| {
+            eprintln!("Error: no main class given");
+            eprintln!("Usage: jvm [options] <main class> [args...]");
+            eprintln!("   or  jvm [options] -jar <jar file> [args...]");
+            std::process::exit(1);
+        }),
+    };
+    if let Err(err) = jvm.load_class(&main_class) {
+        eprintln!(
+            "Error: Could not find or load main class {}",
+            main_class.replace('/', ".")
+        );
+        eprintln!("Caused by: {}", err);
+        std::process::exit(1);
+    }
+    let arguments = match jvm.to_guest(&options.arguments) {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            eprintln!(
+                "Error: can't create the arguments of the main method: {}",
+                err
+            );
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(path) = &options.checkpoint_to {
+        match jvm.write_checkpoint(path) {
+            Ok(()) => log::info!("Wrote the checkpoint {}", path.display()),
+            Err(err) => log::error!("Failed to write the checkpoint {}: {}", path.display(), err),
+        }
+    }
+
+    // Like the java launcher, an uncaught exception exits with status 1
+    let main = jvm.call_static(&main_class, "main", "([Ljava/lang/String;)V", &[arguments]);
+    let status = match main {
+        Ok(_) => 0,
+        Err(_) if jvm.exit_status().is_some() => 0,
+        Err(exception) => {
+            log::error!("Uncaught exception in main: {}", exception);
+            1
+        }
+    };
+
+    if let Some(path) = &options.call_graph_dump {
+        jit::call_graph::dump(path, jvm.classes(), jvm.methods());
+    }
+    if let Some(path) = &options.class_graph_dump {
+        model::class_graph::dump(path, jvm.classes(), jvm.methods());
+    }
+
+    if let Some(path) = &options.dump_loaded_class_list {
+        match jvm.write_class_list(path) {
+            Ok(()) => log::info!("Wrote the class list {}", path.display()),
+            Err(err) => log::error!("Failed to write the class list {}: {}", path.display(), err),
+        }
+    }
+
+    if let Some(path) = &options.heap_dump {
+        match jvm.write_heap_dump(path) {
+            Ok(()) => log::info!("Wrote the heap dump {}", path.display()),
+            Err(err) => log::error!("Failed to write the heap dump {}: {}", path.display(), err),
+        }
+    }
+
+    if options.execution_mode == ExecutionMode::CrossCheck {
+        let (checked, divergences) = interpreter::cross_check::statistics();
+        log::info!(
+            "Cross-checked {} calls between the interpreter and the JIT, {} divergences",
+            checked,
+            divergences
+        );
+    }
+
+    if options.print_fusion_statistics {
+        for (fusion, sites, executions, deferrals) in interpreter::fusion::statistics() {
+            log::info!(
+                "Superinstruction '{}': {} sites, {} executions, {} deferred executions",
+                fusion.name(),
+                sites,
+                executions,
+                deferrals
+            );
+        }
+    }
 
-    let (foo_idx, _) = classes.resolve(class).resolve_own_virtual_method_by_name("foo");
-    methods.update_method(foo_idx, jit::compile_method(foo_idx, &classes, &methods).unwrap());
+    if options.print_resolution_statistics {
+        model::resolution_statistics::print(jvm.classes());
+    }
 
-    let (main, _) = classes
-        .resolve(class)
-        .resolve_own_static_method_by_name("main");
-    interpreter::call_method(main, stack, &mut heap, &classes, &methods);
+    if options.print_generic_signatures {
+        model::signature::print_loaded(jvm.classes());
+    }
+    if options.print_annotations {
+        model::annotation::print_loaded(jvm.classes());
+    }
 
-    dbg!(&classes
-        .resolve_by_name("Test", &methods, &mut heap, stack)
-        .get_static_field_by_name("a", &classes).unwrap().double());
+    let status = jvm.shutdown(status);
+    crash::release_vm_state();
+    drop(jvm);
+    std::process::exit(status);
 
     /*
     let mut ops = dynasmrt::x64::Assembler::new().unwrap();
@@ -0,0 +1,48 @@
+// The access_flags of classes, fields and methods (JVMS §4.1, §4.5, §4.6) as they are stored in the class file.
+// The visibility is also kept as a Visibility, because access checks only care about that. Flags that aren't
+// defined for the kind of item are dropped while parsing, like the JVM ignores them.
+
+bitflags::bitflags! {
+    pub struct ClassFlags: u16 {
+        const PUBLIC = 0x0001;
+        const FINAL = 0x0010;
+        const SUPER = 0x0020;
+        const INTERFACE = 0x0200;
+        const ABSTRACT = 0x0400;
+        const SYNTHETIC = 0x1000;
+        const ANNOTATION = 0x2000;
+        const ENUM = 0x4000;
+        const MODULE = 0x8000;
+    }
+}
+
+bitflags::bitflags! {
+    pub struct FieldFlags: u16 {
+        const PUBLIC = 0x0001;
+        const PRIVATE = 0x0002;
+        const PROTECTED = 0x0004;
+        const STATIC = 0x0008;
+        const FINAL = 0x0010;
+        const VOLATILE = 0x0040;
+        const TRANSIENT = 0x0080;
+        const SYNTHETIC = 0x1000;
+        const ENUM = 0x4000;
+    }
+}
+
+bitflags::bitflags! {
+    pub struct MethodFlags: u16 {
+        const PUBLIC = 0x0001;
+        const PRIVATE = 0x0002;
+        const PROTECTED = 0x0004;
+        const STATIC = 0x0008;
+        const FINAL = 0x0010;
+        const SYNCHRONIZED = 0x0020;
+        const BRIDGE = 0x0040;
+        const VARARGS = 0x0080;
+        const NATIVE = 0x0100;
+        const ABSTRACT = 0x0400;
+        const STRICT = 0x0800;
+        const SYNTHETIC = 0x1000;
+    }
+}
@@ -0,0 +1,354 @@
+use std::fmt::{self, Display};
+
+use super::class_library::{ClassIndex, ClassLibrary};
+
+// The annotations of classes, fields, methods and method parameters as stored in the Runtime(In)VisibleAnnotations,
+// Runtime(In)VisibleParameterAnnotations, Runtime(In)VisibleTypeAnnotations and AnnotationDefault attributes
+// (JVMS §4.7.16 - §4.7.22). Visible annotations are retained for reflection (RetentionPolicy.RUNTIME), invisible ones
+// are only recorded in the class file (RetentionPolicy.CLASS). The constants of the element values are resolved while
+// the class is parsed, so the annotations don't refer to the constant pool.
+// -XX:+PrintAnnotations prints the annotations of the loaded classes like they are written in Java.
+
+/// An annotation, e.g. @Deprecated(since = "9")
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub type_descriptor: String, // e.g. Ljava/lang/Deprecated;
+    pub elements: Vec<(String, ElementValue)>,
+    pub visible: bool,
+}
+
+/// The value of an element of an annotation (JVMS §4.7.16.1)
+#[derive(Debug, Clone)]
+pub enum ElementValue {
+    Byte(i8),
+    Char(u16),
+    Double(f64),
+    Float(f32),
+    Int(i32),
+    Long(i64),
+    Short(i16),
+    Boolean(bool),
+    String(String),
+    Enum {
+        type_descriptor: String,
+        constant: String,
+    },
+    Class(String), // A return descriptor, e.g. Ljava/lang/String; or V
+    Annotation(Box<Annotation>),
+    Array(Vec<ElementValue>),
+}
+
+/// An annotation of a type that is used in a declaration or expression (JVMS §4.7.20)
+#[derive(Debug, Clone)]
+pub struct TypeAnnotation {
+    pub target: TypeAnnotationTarget,
+    pub path: Vec<TypePathStep>, // The part of the type that is annotated, empty for the type itself
+    pub annotation: Annotation,
+}
+
+/// Where the annotated type is used, determined by the target_type and target_info items (JVMS §4.7.20.1)
+#[derive(Debug, Clone)]
+pub enum TypeAnnotationTarget {
+    ClassTypeParameter(u8),
+    MethodTypeParameter(u8),
+    Supertype(u16), // The index of the interface, u16::MAX for the super class
+    ClassTypeParameterBound { type_parameter: u8, bound: u8 },
+    MethodTypeParameterBound { type_parameter: u8, bound: u8 },
+    Field,
+    Return,
+    Receiver,
+    FormalParameter(u8),
+    Throws(u16), // The index into the exceptions of the method
+    LocalVariable(Vec<LocalVariableRange>),
+    ResourceVariable(Vec<LocalVariableRange>),
+    Catch(u16), // The index into the exception table
+    Instanceof(u16),
+    New(u16),
+    ConstructorReference(u16),
+    MethodReference(u16),
+    Cast { offset: u16, type_argument: u8 },
+    ConstructorInvocationTypeArgument { offset: u16, type_argument: u8 },
+    MethodInvocationTypeArgument { offset: u16, type_argument: u8 },
+    ConstructorReferenceTypeArgument { offset: u16, type_argument: u8 },
+    MethodReferenceTypeArgument { offset: u16, type_argument: u8 },
+}
+
+/// The code range in which a local variable with an annotated type is live
+#[derive(Debug, Clone, Copy)]
+pub struct LocalVariableRange {
+    pub start_pc: u16,
+    pub length: u16,
+    pub index: u16,
+}
+
+/// A step from a type to one of its parts (JVMS §4.7.20.2)
+#[derive(Debug, Clone, Copy)]
+pub enum TypePathStep {
+    Array,            // The component type of an array type
+    Nested,           // The nested type of a type
+    WildcardBound,    // The bound of a wildcard type argument
+    TypeArgument(u8), // The type argument of a parameterized type
+}
+
+/// All annotations of a class, field or method
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    pub declaration: Vec<Annotation>,
+    pub parameters: Vec<Vec<Annotation>>, // Only present for methods, indexed by the parameter
+    pub types: Vec<TypeAnnotation>,
+    pub default: Option<ElementValue>, // The default value of an element of an annotation interface
+}
+
+impl Annotations {
+    pub fn is_empty(&self) -> bool {
+        self.declaration.is_empty()
+            && self.parameters.iter().all(|parameter| parameter.is_empty())
+            && self.types.is_empty()
+            && self.default.is_none()
+    }
+}
+
+/// The Java name of the type of a field or return descriptor, e.g. java.lang.String[] for [Ljava/lang/String;
+fn java_type(descriptor: &str) -> String {
+    let dimensions = descriptor.chars().take_while(|c| *c == '[').count();
+    let element = &descriptor[dimensions..];
+    let name = match element {
+        "B" => "byte".to_string(),
+        "C" => "char".to_string(),
+        "D" => "double".to_string(),
+        "F" => "float".to_string(),
+        "I" => "int".to_string(),
+        "J" => "long".to_string(),
+        "S" => "short".to_string(),
+        "Z" => "boolean".to_string(),
+        "V" => "void".to_string(),
+        _ => element
+            .strip_prefix('L')
+            .and_then(|name| name.strip_suffix(';'))
+            .unwrap_or(element)
+            .replace('/', "."),
+    };
+    name + &"[]".repeat(dimensions)
+}
+
+impl Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}", java_type(&self.type_descriptor))?;
+        match self.elements.as_slice() {
+            [] => {}
+            // The element of single-element annotations is called value by convention, e.g. @Retention(RUNTIME)
+            [(name, value)] if name == "value" => write!(f, "({})", value)?,
+            elements => {
+                let elements = elements
+                    .iter()
+                    .map(|(name, value)| format!("{} = {}", name, value))
+                    .collect::<Vec<_>>();
+                write!(f, "({})", elements.join(", "))?;
+            }
+        }
+        if !self.visible {
+            write!(f, " /* class file only */")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ElementValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Byte(value) => write!(f, "(byte) {}", value),
+            Self::Char(value) => match char::from_u32(*value as u32) {
+                Some(c) => write!(f, "{:?}", c),
+                None => write!(f, "'\\u{:04x}'", value),
+            },
+            Self::Double(value) => write!(f, "{:?}", value),
+            Self::Float(value) => write!(f, "{:?}f", value),
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Long(value) => write!(f, "{}L", value),
+            Self::Short(value) => write!(f, "(short) {}", value),
+            Self::Boolean(value) => write!(f, "{}", value),
+            Self::String(value) => write!(f, "{:?}", value),
+            Self::Enum {
+                type_descriptor,
+                constant,
+            } => write!(f, "{}.{}", java_type(type_descriptor), constant),
+            Self::Class(descriptor) => write!(f, "{}.class", java_type(descriptor)),
+            Self::Annotation(annotation) => write!(f, "{}", annotation),
+            Self::Array(values) => {
+                let values = values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", values.join(", "))
+            }
+        }
+    }
+}
+
+impl Display for TypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on {}", self.annotation, self.target)?;
+        if !self.path.is_empty() {
+            let path = self
+                .path
+                .iter()
+                .map(|step| step.to_string())
+                .collect::<Vec<_>>();
+            write!(f, " at {}", path.join(""))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for TypeAnnotationTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges = |ranges: &[LocalVariableRange]| {
+            ranges
+                .iter()
+                .map(|range| {
+                    format!(
+                        "local {} in pc {}..{}",
+                        range.index,
+                        range.start_pc,
+                        range.start_pc as usize + range.length as usize
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Self::ClassTypeParameter(index) => write!(f, "class type parameter {}", index),
+            Self::MethodTypeParameter(index) => write!(f, "method type parameter {}", index),
+            Self::Supertype(u16::MAX) => write!(f, "super class"),
+            Self::Supertype(index) => write!(f, "interface {}", index),
+            Self::ClassTypeParameterBound {
+                type_parameter,
+                bound,
+            } => write!(
+                f,
+                "bound {} of class type parameter {}",
+                bound, type_parameter
+            ),
+            Self::MethodTypeParameterBound {
+                type_parameter,
+                bound,
+            } => write!(
+                f,
+                "bound {} of method type parameter {}",
+                bound, type_parameter
+            ),
+            Self::Field => write!(f, "field type"),
+            Self::Return => write!(f, "return type"),
+            Self::Receiver => write!(f, "receiver type"),
+            Self::FormalParameter(index) => write!(f, "parameter {}", index),
+            Self::Throws(index) => write!(f, "thrown type {}", index),
+            Self::LocalVariable(variables) => write!(f, "{}", ranges(variables)),
+            Self::ResourceVariable(variables) => write!(f, "resource {}", ranges(variables)),
+            Self::Catch(index) => write!(f, "exception handler {}", index),
+            Self::Instanceof(offset) => write!(f, "instanceof at pc {}", offset),
+            Self::New(offset) => write!(f, "new at pc {}", offset),
+            Self::ConstructorReference(offset) => {
+                write!(f, "constructor reference at pc {}", offset)
+            }
+            Self::MethodReference(offset) => write!(f, "method reference at pc {}", offset),
+            Self::Cast {
+                offset,
+                type_argument,
+            } => write!(f, "cast {} at pc {}", type_argument, offset),
+            Self::ConstructorInvocationTypeArgument {
+                offset,
+                type_argument,
+            } => write!(
+                f,
+                "type argument {} of the constructor call at pc {}",
+                type_argument, offset
+            ),
+            Self::MethodInvocationTypeArgument {
+                offset,
+                type_argument,
+            } => write!(
+                f,
+                "type argument {} of the method call at pc {}",
+                type_argument, offset
+            ),
+            Self::ConstructorReferenceTypeArgument {
+                offset,
+                type_argument,
+            } => write!(
+                f,
+                "type argument {} of the constructor reference at pc {}",
+                type_argument, offset
+            ),
+            Self::MethodReferenceTypeArgument {
+                offset,
+                type_argument,
+            } => write!(
+                f,
+                "type argument {} of the method reference at pc {}",
+                type_argument, offset
+            ),
+        }
+    }
+}
+
+impl Display for TypePathStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The notation of javap
+        match self {
+            Self::Array => write!(f, "["),
+            Self::Nested => write!(f, "."),
+            Self::WildcardBound => write!(f, "*"),
+            Self::TypeArgument(index) => write!(f, "{};", index),
+        }
+    }
+}
+
+/// Prints the annotations of the loaded classes and their members (-XX:+PrintAnnotations)
+pub fn print_loaded(classes: &ClassLibrary) {
+    for index in 0..classes.class_count() {
+        let class = classes.resolve(ClassIndex(index));
+        let fields = class
+            .declared_fields()
+            .filter(|field| !field.annotations.is_empty())
+            .map(|field| (&field.name, &field.annotations))
+            .collect::<Vec<_>>();
+        let methods = class
+            .declared_methods()
+            .filter(|method| !method.annotations.is_empty())
+            .map(|method| (&method.name, &method.annotations))
+            .collect::<Vec<_>>();
+        if class.annotations().is_empty() && fields.is_empty() && methods.is_empty() {
+            continue;
+        }
+        println!(
+            "class {}",
+            class.name().unwrap_or("<unknown>").replace('/', ".")
+        );
+        print_annotations("  ", class.annotations());
+        for (name, annotations) in fields {
+            println!("  field {}", name);
+            print_annotations("    ", annotations);
+        }
+        for (name, annotations) in methods {
+            println!("  method {}", name);
+            print_annotations("    ", annotations);
+        }
+    }
+}
+
+fn print_annotations(indent: &str, annotations: &Annotations) {
+    for annotation in &annotations.declaration {
+        println!("{}{}", indent, annotation);
+    }
+    for (index, parameter) in annotations.parameters.iter().enumerate() {
+        for annotation in parameter {
+            println!("{}{} on parameter {}", indent, annotation, index);
+        }
+    }
+    for annotation in &annotations.types {
+        println!("{}{}", indent, annotation);
+    }
+    if let Some(default) = &annotations.default {
+        println!("{}default {}", indent, default);
+    }
+}
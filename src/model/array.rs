@@ -0,0 +1,144 @@
+use super::{class_library::ClassIndex, field::Fields, types::JvmType, value::JvmValue};
+
+/// The element type of an array. Primitive types are numbered like the atype operand of newarray (JVMS §6.5.newarray).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ArrayType {
+    Boolean = 4,
+    Char = 5,
+    Float = 6,
+    Double = 7,
+    Byte = 8,
+    Short = 9,
+    Int = 10,
+    Long = 11,
+    Reference = 12,
+}
+
+impl ArrayType {
+    /// Decodes the atype operand of newarray, which can't describe reference arrays
+    pub fn from_atype(atype: u8) -> Option<Self> {
+        match Self::from_raw(atype) {
+            Some(Self::Reference) => None,
+            ty => ty,
+        }
+    }
+
+    pub(super) fn from_raw(value: u8) -> Option<Self> {
+        match value {
+            4 => Some(Self::Boolean),
+            5 => Some(Self::Char),
+            6 => Some(Self::Float),
+            7 => Some(Self::Double),
+            8 => Some(Self::Byte),
+            9 => Some(Self::Short),
+            10 => Some(Self::Int),
+            11 => Some(Self::Long),
+            12 => Some(Self::Reference),
+            _ => None,
+        }
+    }
+
+    /// Determines the element type of an array class given by its name, e.g. "[I" or "[Ljava/lang/Object;"
+    pub fn from_class_name(name: &str) -> Option<Self> {
+        match name.strip_prefix('[')?.chars().next()? {
+            'Z' => Some(Self::Boolean),
+            'C' => Some(Self::Char),
+            'F' => Some(Self::Float),
+            'D' => Some(Self::Double),
+            'B' => Some(Self::Byte),
+            'S' => Some(Self::Short),
+            'I' => Some(Self::Int),
+            'J' => Some(Self::Long),
+            'L' | '[' => Some(Self::Reference),
+            _ => None,
+        }
+    }
+
+    /// The type of the values that are stored in the elements (for the types that fields can store)
+    fn value_type(self) -> JvmType {
+        match self {
+            Self::Float => JvmType::Float,
+            Self::Double => JvmType::Double,
+            Self::Int => JvmType::Integer,
+            Self::Long => JvmType::Long,
+            Self::Reference => JvmType::Reference,
+            Self::Boolean => JvmType::Boolean,
+            Self::Char => JvmType::Char,
+            Self::Byte => JvmType::Byte,
+            Self::Short => JvmType::Short,
+        }
+    }
+
+    /// The type of the elements on the operand stack, where narrow integral types are widened to int
+    pub fn stack_type(self) -> JvmType {
+        match self {
+            Self::Boolean | Self::Char | Self::Byte | Self::Short | Self::Int => JvmType::Integer,
+            ty => ty.value_type(),
+        }
+    }
+
+    /// In bytes
+    pub fn element_size(self) -> usize {
+        match self {
+            Self::Boolean | Self::Byte => 1,
+            Self::Char | Self::Short => 2,
+            Self::Float | Self::Int => 4,
+            // References are stored with 8 bytes, like in fields
+            Self::Double | Self::Long | Self::Reference => 8,
+        }
+    }
+}
+
+/// An array on the heap. Its memory layout is [8 bytes header][4 bytes length][4 bytes component class][elements].
+pub struct Array {
+    ty: ArrayType,
+    length: usize,
+    component_class: Option<ClassIndex>,
+    elements: Fields,
+}
+
+impl Array {
+    pub(super) unsafe fn at(
+        ty: ArrayType,
+        length: usize,
+        component_class: Option<ClassIndex>,
+        elements: *mut u8,
+    ) -> Self {
+        Self {
+            ty,
+            length,
+            component_class,
+            elements: Fields::at(elements),
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn ty(&self) -> ArrayType {
+        self.ty
+    }
+
+    /// The class of the elements of a reference array, or None for primitive arrays and arrays of arrays
+    pub fn component_class(&self) -> Option<ClassIndex> {
+        self.component_class
+    }
+
+    /// Returns the element as it is pushed onto the operand stack, i.e. narrow integral types are widened to int.
+    /// The index must have been checked against the length.
+    pub fn get(&self, index: usize) -> JvmValue {
+        debug_assert!(index < self.length);
+        // Booleans are loaded like bytes (JVMS §6.5.baload), which is how fields widen them too
+        self.elements
+            .get_value(index * self.ty.element_size(), self.ty.value_type())
+    }
+
+    /// Stores the value, narrow integral types are truncated. The index must have been checked against the length.
+    pub fn set(&mut self, index: usize, value: JvmValue) {
+        debug_assert!(index < self.length);
+        self.elements
+            .set_value(index * self.ty.element_size(), self.ty.value_type(), value);
+    }
+}
@@ -0,0 +1,172 @@
+use super::{
+    class_library::{ClassLibrary, ClassResolveError},
+    constant_pool::ConstantPoolError,
+    field::FieldError,
+    heap::{Heap, HeapError, HeapIndex, NULL_POINTER},
+    method::MethodTable,
+    stack::StackPointer,
+    types::JvmType,
+    value::JvmValue,
+};
+
+// Adapters between primitive values and their wrapper objects (java/lang/Integer etc.), shared by every path that
+// passes primitives where objects are expected: the arguments and the return value of Method.invoke, MethodHandle
+// asType and the adaptation of lambdas to their functional interface.
+// Wrappers are instantiated directly (i.e. without the cache of valueOf) and hold the primitive in their field value,
+// like the wrappers of the JDK class library.
+
+const VALUE_FIELD: &str = "value";
+
+/// The wrapper class of a primitive type
+pub fn wrapper_class(ty: JvmType) -> Option<&'static str> {
+    match ty {
+        JvmType::Boolean => Some("java/lang/Boolean"),
+        JvmType::Byte => Some("java/lang/Byte"),
+        JvmType::Char => Some("java/lang/Character"),
+        JvmType::Short => Some("java/lang/Short"),
+        JvmType::Integer => Some("java/lang/Integer"),
+        JvmType::Long => Some("java/lang/Long"),
+        JvmType::Float => Some("java/lang/Float"),
+        JvmType::Double => Some("java/lang/Double"),
+        JvmType::Void | JvmType::Reference => None,
+    }
+}
+
+/// The primitive type that is wrapped by instances of the class
+pub fn primitive_type(wrapper_class: &str) -> Option<JvmType> {
+    match wrapper_class {
+        "java/lang/Boolean" => Some(JvmType::Boolean),
+        "java/lang/Byte" => Some(JvmType::Byte),
+        "java/lang/Character" => Some(JvmType::Char),
+        "java/lang/Short" => Some(JvmType::Short),
+        "java/lang/Integer" => Some(JvmType::Integer),
+        "java/lang/Long" => Some(JvmType::Long),
+        "java/lang/Float" => Some(JvmType::Float),
+        "java/lang/Double" => Some(JvmType::Double),
+        _ => None,
+    }
+}
+
+/// Wraps the primitive value of type ty in a new instance of its wrapper class
+pub fn box_value(
+    value: JvmValue,
+    ty: JvmType,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, BoxingError> {
+    let name = wrapper_class(ty).ok_or_else(|| BoxingError::NotPrimitive(format!("{:?}", ty)))?;
+    let class = classes.try_resolve_by_name(name, methods, heap, stack)?;
+    let value_field = class.field_layout().resolve(VALUE_FIELD)?;
+    let object = heap.instantiate(class)?;
+    heap.resolve(object).set_field(value_field, value);
+    Ok(object)
+}
+
+/// Reads the primitive value of a wrapper object as type ty. Like Method.invoke, this accepts every wrapper whose
+/// primitive widens to ty (e.g. an Integer for a long).
+pub fn unbox_value(
+    object: HeapIndex,
+    ty: JvmType,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+) -> Result<JvmValue, BoxingError> {
+    if object == NULL_POINTER {
+        return Err(BoxingError::Null);
+    }
+    if heap.is_array(object) {
+        return Err(BoxingError::NotAWrapper("an array".to_string()));
+    }
+    let class = classes.resolve(heap.resolve(object).class());
+    let name = class.name()?;
+    let wrapped = primitive_type(name).ok_or_else(|| BoxingError::NotAWrapper(name.to_string()))?;
+    let value_field = class.field_layout().resolve(VALUE_FIELD)?;
+    let value = heap.resolve(object).get_field(value_field);
+    widen(value, wrapped, ty).ok_or_else(|| BoxingError::Incompatible {
+        from: name.to_string(),
+        to: format!("{:?}", ty),
+    })
+}
+
+/// Converts a value from one type to another by boxing, unboxing or widening (JLS §5.1.2), e.g. the return value
+/// of a method to the return type of the method handle or functional interface that called it.
+/// Void adapts to every type (references become null), every value adapts to void.
+pub fn adapt(
+    value: JvmValue,
+    from: JvmType,
+    to: JvmType,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<JvmValue, BoxingError> {
+    match (from, to) {
+        (_, JvmType::Void) => Ok(JvmValue::VOID),
+        (JvmType::Void, JvmType::Reference) => Ok(NULL_POINTER.into()),
+        (JvmType::Void, _) => Ok(JvmValue::default()),
+        (JvmType::Reference, JvmType::Reference) => Ok(value),
+        (_, JvmType::Reference) => {
+            Ok(box_value(value, from, classes, heap, methods, stack)?.into())
+        }
+        (JvmType::Reference, _) => {
+            unbox_value(value.reference().to_heap_index(), to, classes, heap)
+        }
+        _ => widen(value, from, to).ok_or_else(|| BoxingError::Incompatible {
+            from: format!("{:?}", from),
+            to: format!("{:?}", to),
+        }),
+    }
+}
+
+/// Applies the widening primitive conversion from one primitive type to another (JLS §5.1.2). Returns None if
+/// the conversion would narrow the value. Values of types that are no wider than int are stored as int.
+pub fn widen(value: JvmValue, from: JvmType, to: JvmType) -> Option<JvmValue> {
+    if from.matches(&to) {
+        return Some(value);
+    }
+    match (from, to) {
+        (JvmType::Byte, JvmType::Short)
+        | (JvmType::Byte | JvmType::Short | JvmType::Char, JvmType::Integer) => Some(value),
+        (JvmType::Byte | JvmType::Short | JvmType::Char | JvmType::Integer, JvmType::Long) => {
+            Some((value.int().0 as i64).into())
+        }
+        (JvmType::Byte | JvmType::Short | JvmType::Char | JvmType::Integer, JvmType::Float) => {
+            Some((value.int().0 as f32).into())
+        }
+        (JvmType::Byte | JvmType::Short | JvmType::Char | JvmType::Integer, JvmType::Double) => {
+            Some((value.int().0 as f64).into())
+        }
+        (JvmType::Long, JvmType::Float) => Some((value.long().0 as f32).into()),
+        (JvmType::Long, JvmType::Double) => Some((value.long().0 as f64).into()),
+        (JvmType::Float, JvmType::Double) => Some((value.float().0 as f64).into()),
+        _ => None,
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BoxingError {
+    #[error("cannot unbox null")]
+    Null,
+
+    #[error("{0} is not a primitive type")]
+    NotPrimitive(String),
+
+    #[error("{0} is not a wrapper of a primitive type")]
+    NotAWrapper(String),
+
+    #[error("cannot convert {from} to {to}")]
+    Incompatible { from: String, to: String },
+
+    #[error(transparent)]
+    ClassResolve(#[from] ClassResolveError),
+
+    #[error(transparent)]
+    ConstantPool(#[from] ConstantPoolError),
+
+    #[error(transparent)]
+    Field(#[from] FieldError),
+
+    #[error(transparent)]
+    Heap(#[from] HeapError),
+}
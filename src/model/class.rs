@@ -1,60 +1,179 @@
-use std::{borrow::BorrowMut, cell::RefCell, collections::HashMap};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
-    class_parser::ClassData,
+    class_parser::{self, ClassData},
     interpreter::{self, ExecutionError},
-    jit,
     model::constant_pool::{ConstantPoolEntry, ConstantPoolError},
 };
 
 use super::{
-    class_library::{ClassIndex, ClassLibrary},
-    constant_pool::{ConstantPool, ConstantPoolIndex, FieldReference, MethodReference},
-    field::{self, FieldDescriptor, FieldInfo, FieldLayout, Fields},
-    heap::Heap,
-    method::{MethodCode, MethodData, MethodImplementation, MethodIndex, MethodTable},
+    access_flags::ClassFlags,
+    annotation::Annotations,
+    class_library::{ClassIndex, ClassLibrary, ClassResolveError},
+    constant_pool::{
+        BootstrapMethod, ConstantPool, ConstantPoolIndex, FieldReference, InterfaceMethodReference,
+        MethodReference, ReferenceKind,
+    },
+    field::{self, FieldDescriptor, FieldInfo, FieldLayout, Fields, ReferenceMap},
+    heap::{Heap, HeapIndex, NULL_POINTER},
+    method::{MethodCode, MethodData, MethodDescriptor, MethodIndex, MethodKey, MethodTable},
+    module::Module,
+    package::{ClassLoaderId, RuntimePackage},
+    resolution_statistics::{ResolutionStatistics, SymbolicReference},
+    signature::ClassSignature,
     stack::StackPointer,
+    string,
     types::JvmType,
     value::JvmValue,
+    visibility::Visibility,
 };
 
 pub struct Class {
     index: ClassIndex,
     super_class: Option<ClassIndex>,
+    package: RuntimePackage,
+    module: Option<Arc<Module>>, // None for the unnamed module
     data: ClassData,
     constant_pool: ConstantPool,
 
     static_field_layout: FieldLayout,
-    static_fields: RefCell<Fields>,
+    static_reference_map: ReferenceMap,
+    static_fields: Mutex<Fields>,
 
     field_layout: FieldLayout,
+    reference_map: ReferenceMap,
 
-    static_methods: HashMap<String, (MethodIndex, usize)>, // second tuple element is the parameter count
-    virtual_methods: HashMap<String, (MethodIndex, VirtualMethodIndex, usize)>, // The MethodIndex is used for static dispatch (i.e. invokespecial)
-    dispatch_table: *const MethodIndex,
+    static_methods: HashMap<MethodKey, (MethodIndex, usize)>, // second tuple element is the parameter count
+    virtual_methods: HashMap<MethodKey, (MethodIndex, VirtualMethodIndex, usize)>, // The MethodIndex is used for static dispatch (i.e. invokespecial)
+    direct_methods: HashMap<MethodKey, (MethodIndex, usize)>, // Private methods and constructors, which neither override nor get overridden
+    dispatch_table: DispatchTable,
     dispatch_table_length: usize,
+
+    interfaces: Vec<ClassIndex>, // The directly implemented (or, for interfaces, extended) interfaces
+    interface_methods: Vec<(MethodKey, usize)>, // Only populated for interfaces; the position in the list is the InterfaceMethodIndex
+    itable: HashMap<ClassIndex, Vec<Option<MethodIndex>>>, // For every (transitively) implemented interface: the implementation of each of its methods
+
+    resolution_statistics: ResolutionStatistics, // Of the references in the constant pool of this class
+    init_state: AtomicU8,                        // An InitState
 }
 
+/// The state of a class in the initialization procedure (JVMS §5.5), see ClassLibrary::initialize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InitState {
+    Loaded,       // Created from the class file
+    Linked,       // Verified and prepared, the static fields have their default values
+    Initializing, // The static initializer (or that of a supertype) is running
+    Initialized,
+    Erroneous, // The initialization has failed, the class can't be used anymore
+}
+
+impl InitState {
+    // By their discriminant
+    const ALL: [InitState; 5] = [
+        Self::Loaded,
+        Self::Linked,
+        Self::Initializing,
+        Self::Initialized,
+        Self::Erroneous,
+    ];
+}
+
+/// The position of the dispatch table of a class in the dispatch tables of the ClassLibrary. Class::new writes the
+/// table before the class is published, afterwards it is only read, and the tables live as long as the library.
+#[derive(Clone, Copy)]
+struct DispatchTable(*const MethodIndex);
+
+unsafe impl Send for DispatchTable {}
+unsafe impl Sync for DispatchTable {}
+
 impl Class {
-    /// Returns (class, statics_length in bytes, dispatch_table_length in dwords)
-    pub fn new(
+    /// Returns (class, statics_length in bytes, dispatch_table_length in dwords). Fails with TableFull before anything
+    /// is written if the static fields, the dispatch table or the methods don't fit into the space that is left.
+    ///
+    /// # Safety
+    /// The static fields and the dispatch table are written to the given positions, which must be valid for their
+    /// lengths
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn new(
         data: ClassData,
         constant_pool: ConstantPool,
         index: ClassIndex,
+        loader: ClassLoaderId,
         super_class: Option<&Class>,
+        interfaces: Vec<ClassIndex>,
+        classes: &ClassLibrary,
         methods: &MethodTable,
-        static_fields_position: *mut u8,
-        dispatch_table_position: *mut MethodIndex,
+        static_fields_position: *mut [u8],
+        dispatch_table_position: *mut [MethodIndex],
     ) -> Result<(Self, usize, usize), ClassCreationError> {
         let static_field_layout = field::layout_fields(&FieldLayout::empty(), &data.static_fields);
+        let statics_length = static_field_layout.byte_length();
+
+        // Collect all interfaces that are implemented by this class, including the ones inherited
+        // from the super class and the super interfaces of the implemented interfaces
+        let mut implemented_interfaces = Vec::new();
+        let inherited_interfaces = super_class
+            .into_iter()
+            .flat_map(|super_class| super_class.itable.keys())
+            .chain(
+                interfaces
+                    .iter()
+                    .flat_map(|interface| classes.resolve(*interface).itable.keys()),
+            );
+        for interface in inherited_interfaces {
+            if !implemented_interfaces.contains(interface) {
+                implemented_interfaces.push(*interface);
+            }
+        }
+        let default_methods = select_default_methods(&implemented_interfaces, classes, methods);
+
+        // Every method and every inherited default method gets at most one new slot in the dispatch table
+        let dispatch_table_bound = super_class.map_or(0, |class| class.dispatch_table_length)
+            + data.methods.len()
+            + default_methods.len();
+        let method_count = data.methods.len() + data.static_methods.len();
+        if statics_length > static_fields_position.len() {
+            return Err(ClassCreationError::TableFull {
+                resource: "bytes of static fields",
+                capacity: static_fields_position.len(),
+            });
+        }
+        if dispatch_table_bound > dispatch_table_position.len() {
+            return Err(ClassCreationError::TableFull {
+                resource: "dispatch table entries",
+                capacity: dispatch_table_position.len(),
+            });
+        }
+        if methods.method_count() + method_count > methods.capacity() {
+            return Err(ClassCreationError::TableFull {
+                resource: "methods",
+                capacity: methods.capacity(),
+            });
+        }
+
         let static_fields = unsafe {
             Fields::init_from_layout_at(
-                static_fields_position,
+                static_fields_position as *mut u8,
                 &static_field_layout,
                 &data.static_fields,
             )
         };
-        let statics_length = static_field_layout.byte_length();
+        let name = constant_pool.resolve_type(data.this_class)?;
+        let package = RuntimePackage::of_class(name, loader);
+        // Only the classes of the bootstrap loader belong to named modules
+        let module = match loader {
+            ClassLoaderId::Bootstrap => classes.module_of_package(package.name()),
+            ClassLoaderId::User(_) => None,
+        };
 
         let field_layout = if let Some(super_class) = super_class {
             let super_field_layout = &super_class.field_layout;
@@ -70,20 +189,13 @@ impl Class {
         };
         for desc in &data.static_methods {
             match &desc.code {
-                MethodCode::Bytecode(_) => {
-                    let method_index = methods.add_method(
-                        MethodImplementation::Interpreted,
-                        MethodData::from_bytecode_descriptor(desc, index).unwrap(),
-                    );
-                    static_methods.insert(
-                        desc.name.to_string(),
-                        (method_index, desc.parameter_count()),
-                    );
+                MethodCode::Bytecode(_) | MethodCode::Native => {
+                    let method_index = add_method(desc, name, index, methods);
+                    static_methods.insert(desc.key(), (method_index, desc.parameter_count()));
                 }
                 MethodCode::Abstract => {
                     panic!("Abstract static method")
                 }
-                MethodCode::Native => {} // TODO
             }
         }
 
@@ -92,33 +204,40 @@ impl Class {
         } else {
             HashMap::new()
         };
+        let mut direct_methods = HashMap::new();
         let mut dispatch_table = Vec::new();
         if let Some(super_class) = super_class {
             unsafe {
                 dispatch_table.extend_from_slice(std::slice::from_raw_parts(
-                    super_class.dispatch_table,
+                    super_class.dispatch_table.0,
                     super_class.dispatch_table_length,
                 ));
             }
         }
         for desc in &data.methods {
             match &desc.code {
-                MethodCode::Bytecode(_) => {
-                    let method_index = methods.add_method(
-                        MethodImplementation::Interpreted,
-                        MethodData::from_bytecode_descriptor(desc, index).unwrap(),
-                    );
+                MethodCode::Bytecode(_) | MethodCode::Native => {
+                    let method_index = add_method(desc, name, index, methods);
 
-                    if let Some((old_method_index, virtual_index, _)) =
-                        virtual_methods.get_mut(&desc.name)
-                    {
+                    // Overriding is determined by name and descriptor (JVMS §5.4.5). Overloads therefore get their
+                    // own slots, and covariant overrides replace the slot through the bridge method generated by javac.
+                    // Package-private methods can only be overridden from within their runtime package, a method
+                    // with the same signature in another package gets a new slot instead.
+                    let overridden = virtual_methods.get(&desc.key()).filter(|(old, _, _)| {
+                        let old = methods.get_data(*old);
+                        old.visibility != Visibility::PackagePrivate
+                            || classes.resolve(old.owning_class).package == package
+                    });
+                    if desc.is_direct() {
+                        direct_methods.insert(desc.key(), (method_index, desc.parameter_count()));
+                    } else if let Some((_, virtual_index, _)) = overridden.copied() {
                         dispatch_table[virtual_index.0] = method_index;
-                        *old_method_index = method_index;
+                        virtual_methods.get_mut(&desc.key()).unwrap().0 = method_index;
                     } else {
                         let virtual_index = dispatch_table.len();
                         dispatch_table.push(method_index);
                         virtual_methods.insert(
-                            desc.name.to_string(),
+                            desc.key(),
                             (
                                 method_index,
                                 VirtualMethodIndex(virtual_index),
@@ -128,30 +247,95 @@ impl Class {
                     }
                 }
                 MethodCode::Abstract => {} // Abstract method, don't do anything
-                MethodCode::Native => {}   // TODO
             }
         }
+        // Default methods are inherited unless the class or one of its superclasses declares the method (JVMS §5.4.6).
+        // A default method that a superclass has inherited is replaced if a more specific one is implemented.
+        for (key, method_index, parameter_count) in default_methods {
+            if data.methods.iter().any(|desc| desc.key() == key) {
+                continue;
+            }
+            match virtual_methods.get(&key).copied() {
+                Some((current, virtual_index, _)) => {
+                    let owner = methods.get_data(current).owning_class;
+                    if classes.resolve(owner).is_interface() {
+                        dispatch_table[virtual_index.0] = method_index;
+                        virtual_methods.get_mut(&key).unwrap().0 = method_index;
+                    }
+                }
+                None => {
+                    let virtual_index = dispatch_table.len();
+                    dispatch_table.push(method_index);
+                    virtual_methods.insert(
+                        key,
+                        (
+                            method_index,
+                            VirtualMethodIndex(virtual_index),
+                            parameter_count,
+                        ),
+                    );
+                }
+            }
+        }
+
         unsafe {
             std::ptr::copy_nonoverlapping(
                 dispatch_table.as_ptr(),
-                dispatch_table_position,
+                dispatch_table_position as *mut MethodIndex,
                 dispatch_table.len(),
             );
         }
 
+        let interface_methods = if data.is_interface {
+            data.methods
+                .iter()
+                .map(|desc| (desc.key(), desc.parameter_count()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut itable = HashMap::new();
+        for interface in implemented_interfaces {
+            let implementations = classes
+                .resolve(interface)
+                .interface_methods
+                .iter()
+                .map(|(key, _)| virtual_methods.get(key).map(|(method, _, _)| *method))
+                .collect();
+            itable.insert(interface, implementations);
+        }
+        if data.is_interface {
+            let implementations = interface_methods
+                .iter()
+                .map(|(key, _)| virtual_methods.get(key).map(|(method, _, _)| *method))
+                .collect();
+            itable.insert(index, implementations);
+        }
+
         Ok((
             Self {
                 index,
                 data,
                 super_class: super_class.map(|class| class.index()),
+                package,
+                module,
                 constant_pool,
+                static_reference_map: ReferenceMap::of(&static_field_layout),
                 static_field_layout,
-                static_fields: RefCell::new(static_fields),
+                static_fields: Mutex::new(static_fields),
+                reference_map: ReferenceMap::of(&field_layout),
                 field_layout,
                 static_methods,
                 virtual_methods,
-                dispatch_table: dispatch_table_position,
+                direct_methods,
+                dispatch_table: DispatchTable(dispatch_table_position as *const MethodIndex),
                 dispatch_table_length: dispatch_table.len(),
+                interfaces,
+                interface_methods,
+                itable,
+                resolution_statistics: ResolutionStatistics::default(),
+                init_state: AtomicU8::new(InitState::Loaded as u8),
             },
             statics_length,
             dispatch_table.len(),
@@ -162,6 +346,8 @@ impl Class {
         self.index = index;
     }
 
+    /// Assigns the String constants of the static fields and runs the static initializer, see
+    /// ClassLibrary::initialize. The other constants have been assigned when the class was created.
     pub fn bootstrap(
         &self,
         methods: &MethodTable,
@@ -169,8 +355,15 @@ impl Class {
         heap: &mut Heap,
         stack: StackPointer,
     ) -> Result<(), ExecutionError> {
-        if let Some((clinit, _)) = self.static_methods.get("<clinit>") {
-            let _return_value = interpreter::call_method(*clinit, stack, heap, classes, methods);
+        for field in &self.data.static_fields {
+            if let Some(value) = &field.constant_string {
+                let string = string::intern(value, classes, heap, methods, stack)?;
+                let info = self.static_field_layout.resolve(&field.name).unwrap();
+                self.set_static_field(info, JvmValue { reference: string });
+            }
+        }
+        if let Some((clinit, _)) = self.static_methods.get(&MethodKey::new("<clinit>", "()V")) {
+            interpreter::call_method(*clinit, stack, heap, classes, methods)?;
         }
         Ok(())
     }
@@ -183,34 +376,58 @@ impl Class {
         methods: &MethodTable,
         stack: StackPointer,
     ) -> Result<FieldInfo, FieldError> {
-        match self.constant_pool.get(index)? {
-            ConstantPoolEntry::FieldReference(reference) => match reference {
-                FieldReference::Resolved { info, .. } => Ok(*info),
-                FieldReference::Unresolved {
-                    name_and_type,
-                    class,
-                } => {
-                    let (name, ty) = self.constant_pool.get_name_and_type(*name_and_type)?;
-                    //let ty_str = self.constant_pool.get_utf8(ty)?;
-                    let name = self.constant_pool.get_utf8(name)?;
+        match self.constant_pool.get_field(index)? {
+            FieldReference::Resolved { info, .. } => {
+                self.resolution_statistics
+                    .record_hit(SymbolicReference::Field);
+                Ok(info)
+            }
+            FieldReference::Unresolved {
+                name_and_type,
+                class,
+            } => {
+                let (name, _ty) = self.constant_pool.get_name_and_type(name_and_type)?;
+                //let ty_str = self.constant_pool.get_utf8(ty)?;
+                let name = self.constant_pool.get_utf8(name)?;
 
-                    let callee_class_name = self
-                        .constant_pool
-                        .get_utf8(self.constant_pool.get_class(*class)?)?;
-                    let callee_class =
-                        classes.resolve_by_name(callee_class_name, methods, heap, stack);
+                let callee_class_name = self
+                    .constant_pool
+                    .get_utf8(self.constant_pool.get_class(class)?)?;
+                let callee_class = classes.try_resolve_in(
+                    self.loader(),
+                    callee_class_name,
+                    methods,
+                    heap,
+                    stack,
+                )?;
 
-                    let info = callee_class.field_layout.resolve(name)?;
+                let info = callee_class.field_layout.resolve(name)?;
+                if let Some((declaring_class, visibility)) =
+                    callee_class.find_field_declaration(name, false, classes)
+                {
+                    self.check_field_access(declaring_class, visibility, name, classes)?;
+                }
 
-                    self.constant_pool
-                        .update_resolved_field(index, info, callee_class.index());
+                self.constant_pool
+                    .update_resolved_field(index, info, callee_class.index());
+                self.resolution_statistics
+                    .record_resolution(SymbolicReference::Field);
 
-                    Ok(info)
-                }
-            },
-            _ => Err(FieldError::ConstantPool(
-                ConstantPoolError::FieldNotResolvable(index),
-            )),
+                Ok(info)
+            }
+        }
+    }
+
+    /// The type of the referenced field, without resolving it (e.g. for the JIT)
+    pub fn field_reference_type(&self, index: ConstantPoolIndex) -> Result<JvmType, FieldError> {
+        match self.constant_pool.get_field(index)? {
+            FieldReference::Resolved { info, .. } => Ok(info.ty),
+            FieldReference::Unresolved { name_and_type, .. } => {
+                let (_, ty) = self.constant_pool.get_name_and_type(name_and_type)?;
+                let descriptor = self.constant_pool.get_utf8(ty)?;
+                JvmType::parse(&mut descriptor.graphemes(true).peekable())
+                    .ok_or_else(|| FieldError::InvalidDescriptor(descriptor.to_string()))
+            }
         }
     }
 
@@ -222,35 +439,58 @@ impl Class {
         methods: &MethodTable,
         stack: StackPointer,
     ) -> Result<(ClassIndex, FieldInfo), FieldError> {
-        match self.constant_pool.get(index)? {
+        match self.constant_pool.get_field(index)? {
             //TODO use the class
-            ConstantPoolEntry::FieldReference(reference) => match reference {
-                FieldReference::Resolved { info, class } => Ok((*class, *info)),
-                FieldReference::Unresolved {
-                    name_and_type,
-                    class,
-                } => {
-                    let (name, ty) = self.constant_pool.get_name_and_type(*name_and_type)?;
-                    //let ty_str = self.constant_pool.get_utf8(ty)?;
-                    let name = self.constant_pool.get_utf8(name)?;
+            FieldReference::Resolved { info, class } => {
+                self.resolution_statistics
+                    .record_hit(SymbolicReference::Field);
+                Ok((class, info))
+            }
+            FieldReference::Unresolved {
+                name_and_type,
+                class,
+            } => {
+                let (name, _ty) = self.constant_pool.get_name_and_type(name_and_type)?;
+                //let ty_str = self.constant_pool.get_utf8(ty)?;
+                let name = self.constant_pool.get_utf8(name)?;
 
-                    let callee_class_name = self
-                        .constant_pool
-                        .get_utf8(self.constant_pool.get_class(*class)?)?;
+                let callee_class_name = self
+                    .constant_pool
+                    .get_utf8(self.constant_pool.get_class(class)?)?;
 
-                    let (owning_class, info) = classes
-                        .resolve_by_name(callee_class_name, methods, heap, stack)
-                        .resolve_own_static_field(name, classes)?;
+                let (owning_class, info) = classes
+                    .try_resolve_in(self.loader(), callee_class_name, methods, heap, stack)?
+                    .resolve_own_static_field(name, classes)?;
+                if let Some((declaring_class, visibility)) = classes
+                    .resolve(owning_class)
+                    .find_field_declaration(name, true, classes)
+                {
+                    self.check_field_access(declaring_class, visibility, name, classes)?;
+                }
 
-                    self.constant_pool
-                        .update_resolved_field(index, info, owning_class);
+                self.constant_pool
+                    .update_resolved_field(index, info, owning_class);
+                self.resolution_statistics
+                    .record_resolution(SymbolicReference::Field);
 
-                    Ok((owning_class, info))
-                }
-            },
-            _ => Err(FieldError::ConstantPool(
-                ConstantPoolError::FieldNotResolvable(index),
-            )),
+                Ok((owning_class, info))
+            }
+        }
+    }
+
+    /// The field with the name as (class, field), where the class is the declaring class of a static field and this
+    /// class for an instance field (whose layout includes the fields of the super classes)
+    pub fn find_field(
+        &self,
+        name: &str,
+        is_static: bool,
+        classes: &ClassLibrary,
+    ) -> Option<(ClassIndex, FieldInfo)> {
+        if is_static {
+            self.resolve_own_static_field(name, classes).ok()
+        } else {
+            let info = self.field_layout.resolve(name).ok()?;
+            Some((self.index(), info))
         }
     }
 
@@ -282,23 +522,29 @@ impl Class {
             MethodReference::ResolvedStatic {
                 index,
                 parameter_count,
-            } => Ok((index, parameter_count)),
+            } => {
+                self.resolution_statistics
+                    .record_hit(SymbolicReference::Method);
+                Ok((index, parameter_count))
+            }
             MethodReference::Unresolved {
                 class,
                 name_and_type,
             } => {
-                let (name, ty) = self.constant_pool.get_name_and_type(name_and_type)?;
+                let key = self.method_key(name_and_type)?;
                 let callee_class = self.constant_pool.resolve_type(class)?;
-                let name = self.constant_pool.get_utf8(name)?;
 
                 let method = classes
-                    .resolve_by_name(callee_class, methods, heap, stack)
+                    .try_resolve_in(self.loader(), callee_class, methods, heap, stack)?
                     .static_methods
-                    .get(name)
-                    .ok_or_else(|| MethodError::UnknownStatic(name.to_string()))?;
+                    .get(&key)
+                    .ok_or_else(|| MethodError::UnknownStatic(key.to_string()))?;
+                self.check_method_access(method.0, classes, methods)?;
 
                 self.constant_pool
                     .update_resolved_static_method(index, method.0, method.1);
+                self.resolution_statistics
+                    .record_resolution(SymbolicReference::Method);
 
                 Ok(*method)
             }
@@ -306,14 +552,102 @@ impl Class {
         }
     }
 
-    pub fn resolve_own_static_method_by_name(&self, name: &str) -> (MethodIndex, usize) {
-        *self.static_methods.get(name).unwrap()
+    pub fn resolve_own_static_method(&self, name: &str, descriptor: &str) -> (MethodIndex, usize) {
+        *self
+            .static_methods
+            .get(&MethodKey::new(name, descriptor))
+            .unwrap()
+    }
+
+    /// Finds a static, virtual or direct method of this class (or, for virtual methods, of a superclass)
+    pub fn find_own_method(&self, name: &str, descriptor: &str) -> Option<MethodIndex> {
+        let key = MethodKey::new(name, descriptor);
+        self.static_methods
+            .get(&key)
+            .or_else(|| self.direct_methods.get(&key))
+            .map(|(index, _)| *index)
+            .or_else(|| self.virtual_methods.get(&key).map(|(index, _, _)| *index))
+    }
+
+    pub fn find_own_static_method(
+        &self,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<(MethodIndex, usize)> {
+        self.static_methods
+            .get(&MethodKey::new(name, descriptor))
+            .copied()
+    }
+
+    /// This does not perform dynamic dispatch! None if neither this class nor a superclass has the virtual method.
+    pub fn resolve_own_virtual_method(
+        &self,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<(MethodIndex, usize)> {
+        self.virtual_methods
+            .get(&MethodKey::new(name, descriptor))
+            .map(|(index, _, parameter_count)| (*index, *parameter_count))
+    }
+
+    /// The number of argument slots (including the receiver of instance methods) and the return type of the method
+    /// that is referenced by the constant pool entry. Unlike the resolution, this never loads the class of the method.
+    pub fn method_reference_signature(
+        &self,
+        index: ConstantPoolIndex,
+        has_receiver: bool,
+        methods: &MethodTable,
+    ) -> Result<(usize, JvmType), MethodError> {
+        match self.constant_pool.get_method(index)? {
+            MethodReference::ResolvedStatic {
+                index,
+                parameter_count,
+            } => Ok((parameter_count, methods.get_data(index).return_type)),
+            MethodReference::ResolvedVirtual {
+                method_index,
+                parameter_count,
+                ..
+            } => Ok((parameter_count, methods.get_data(method_index).return_type)),
+            MethodReference::Unresolved { name_and_type, .. } => {
+                let key = self.method_key(name_and_type)?;
+                let (parameters, return_type) = class_parser::parse_descriptor(&key.descriptor)
+                    .map_err(|_| MethodError::InvalidDescriptor(key.descriptor.clone()))?;
+                let slots = parameters
+                    .iter()
+                    .map(|parameter| parameter.slots())
+                    .sum::<usize>();
+                Ok((slots + has_receiver as usize, return_type))
+            }
+        }
+    }
+
+    /// Returns (bootstrap method, name and descriptor) of the call site of an invokedynamic
+    pub fn get_invoke_dynamic(
+        &self,
+        index: ConstantPoolIndex,
+    ) -> Result<(&BootstrapMethod, MethodKey), ConstantPoolError> {
+        let (bootstrap_method, name_and_type) = self.constant_pool.get_invoke_dynamic(index)?;
+        Ok((
+            self.bootstrap_method(bootstrap_method)?,
+            self.method_key(name_and_type)?,
+        ))
+    }
+
+    /// The entry of the BootstrapMethods attribute at the index of a Dynamic or InvokeDynamic entry
+    pub fn bootstrap_method(&self, index: u16) -> Result<&BootstrapMethod, ConstantPoolError> {
+        self.data
+            .bootstrap_methods
+            .get(index as usize)
+            .ok_or(ConstantPoolError::MissingBootstrapMethod(index))
     }
 
-    /// This does not perform dynamic dispatch!
-    pub fn resolve_own_virtual_method_by_name(&self, name: &str) -> (MethodIndex, usize) {
-        let (index, _, parameter_count) = *self.virtual_methods.get(name).unwrap();
-        return (index, parameter_count)
+    /// The key of the method that is referenced by the NameAndType entry
+    fn method_key(&self, name_and_type: ConstantPoolIndex) -> Result<MethodKey, ConstantPoolError> {
+        let (name, ty) = self.constant_pool.get_name_and_type(name_and_type)?;
+        Ok(MethodKey::new(
+            self.constant_pool.get_utf8(name)?,
+            self.constant_pool.get_utf8(ty)?,
+        ))
     }
 
     pub fn resolve_virtual_method_statically(
@@ -328,20 +662,47 @@ impl Class {
             MethodReference::ResolvedStatic {
                 index,
                 parameter_count,
-            } => Ok((index, parameter_count)),
+            } => {
+                self.resolution_statistics
+                    .record_hit(SymbolicReference::Method);
+                Ok((index, parameter_count))
+            }
+            MethodReference::ResolvedVirtual {
+                method_index,
+                parameter_count,
+                ..
+            } => {
+                self.resolution_statistics
+                    .record_hit(SymbolicReference::Method);
+                Ok((method_index, parameter_count))
+            }
             MethodReference::Unresolved {
                 class,
                 name_and_type,
             } => {
-                let (name, ty) = self.constant_pool.get_name_and_type(name_and_type)?;
+                let key = self.method_key(name_and_type)?;
                 let callee_class = self.constant_pool.resolve_type(class)?;
-                let name = self.constant_pool.get_utf8(name)?;
+                let callee_class =
+                    classes.try_resolve_in(self.loader(), callee_class, methods, heap, stack)?;
 
-                let (method_index, virtual_index, parameter_count) = *classes
-                    .resolve_by_name(callee_class, methods, heap, stack)
+                if let Some((method_index, parameter_count)) = callee_class.direct_methods.get(&key)
+                {
+                    self.check_method_access(*method_index, classes, methods)?;
+                    self.constant_pool.update_resolved_static_method(
+                        index,
+                        *method_index,
+                        *parameter_count,
+                    );
+                    self.resolution_statistics
+                        .record_resolution(SymbolicReference::Method);
+                    return Ok((*method_index, *parameter_count));
+                }
+
+                let (method_index, virtual_index, parameter_count) = *callee_class
                     .virtual_methods
-                    .get(name)
-                    .ok_or_else(|| MethodError::UnknownStatic(name.to_string()))?;
+                    .get(&key)
+                    .ok_or_else(|| MethodError::UnknownStatic(key.to_string()))?;
+                self.check_method_access(method_index, classes, methods)?;
 
                 self.constant_pool.update_resolved_virtual_method(
                     index,
@@ -349,13 +710,86 @@ impl Class {
                     virtual_index,
                     parameter_count,
                 );
+                self.resolution_statistics
+                    .record_resolution(SymbolicReference::Method);
 
                 Ok((method_index, parameter_count))
             }
-            _ => Err(MethodError::NotStatic(index)),
         }
     }
 
+    /// Resolves the method that is invoked by invokespecial and selects the implementation (JVMS §6.5 invokespecial).
+    /// Instance initializers and private methods are invoked as resolved. A method that is resolved in a superclass
+    /// of this class is selected from the direct superclass instead (super.m()), so that a call compiled against an
+    /// older hierarchy still reaches the closest override. A method of a direct superinterface (I.super.m()) is
+    /// selected from that interface.
+    pub fn resolve_special_method(
+        &self,
+        index: ConstantPoolIndex,
+        classes: &ClassLibrary,
+        heap: &mut Heap,
+        methods: &MethodTable,
+        stack: StackPointer,
+    ) -> Result<(MethodIndex, usize), MethodError> {
+        if self.constant_pool.get_interface_method(index).is_ok() {
+            return match self.resolve_interface_method(index, classes, heap, methods, stack)? {
+                InterfaceMethodReference::ResolvedInterface {
+                    interface,
+                    method_index,
+                    parameter_count,
+                } => {
+                    let interface = classes.resolve(interface);
+                    let selected = interface
+                        .dispatch_interface_call(interface.index, method_index)
+                        .ok_or_else(|| {
+                            MethodError::MissingInterfaceImplementation(
+                                interface.name().unwrap_or("<unknown>").to_string(),
+                                interface.name().unwrap_or("<unknown>").to_string(),
+                            )
+                        })?;
+                    Ok((selected, parameter_count))
+                }
+                // A public method of java/lang/Object
+                InterfaceMethodReference::ResolvedVirtual {
+                    virtual_index,
+                    parameter_count,
+                } => Ok((
+                    classes
+                        .try_resolve_by_name("java/lang/Object", methods, heap, stack)?
+                        .dispatch_virtual_call(virtual_index),
+                    parameter_count,
+                )),
+                InterfaceMethodReference::Unresolved { .. } => {
+                    unreachable!("the interface method has just been resolved")
+                }
+            };
+        }
+
+        let (method_index, parameter_count) =
+            self.resolve_virtual_method_statically(index, classes, heap, methods, stack)?;
+        let resolved = methods.get_data(method_index);
+        if resolved.name == "<init>" || matches!(resolved.visibility, Visibility::Private) {
+            return Ok((method_index, parameter_count));
+        }
+        let declaring_class = classes.resolve(resolved.owning_class);
+        if declaring_class.index == self.index
+            || declaring_class.is_interface()
+            || !self.is_subclass_of(declaring_class.index, classes)
+        {
+            return Ok((method_index, parameter_count));
+        }
+        let selected = self
+            .super_class
+            .map(|super_class| classes.resolve(super_class))
+            .and_then(|super_class| {
+                super_class
+                    .virtual_methods
+                    .get(&MethodKey::new(&resolved.name, &resolved.descriptor))
+            })
+            .map_or(method_index, |(selected, _, _)| *selected);
+        Ok((selected, parameter_count))
+    }
+
     pub fn resolve_virtual_method(
         &self,
         index: ConstantPoolIndex,
@@ -363,26 +797,54 @@ impl Class {
         heap: &mut Heap,
         methods: &MethodTable,
         stack: StackPointer,
-    ) -> Result<(VirtualMethodIndex, usize), MethodError> {
+    ) -> Result<(VirtualCall, usize), MethodError> {
         match self.constant_pool.get_method(index)? {
             MethodReference::ResolvedVirtual {
                 virtual_index,
                 parameter_count,
                 ..
-            } => Ok((virtual_index, parameter_count)),
+            } => {
+                self.resolution_statistics
+                    .record_hit(SymbolicReference::Method);
+                Ok((VirtualCall::Dispatched(virtual_index), parameter_count))
+            }
+            // Only private methods are resolved statically for invokevirtual
+            MethodReference::ResolvedStatic {
+                index,
+                parameter_count,
+            } => {
+                self.resolution_statistics
+                    .record_hit(SymbolicReference::Method);
+                Ok((VirtualCall::Direct(index), parameter_count))
+            }
             MethodReference::Unresolved {
                 class,
                 name_and_type,
             } => {
-                let (name, ty) = self.constant_pool.get_name_and_type(name_and_type)?;
+                let key = self.method_key(name_and_type)?;
                 let callee_class = self.constant_pool.resolve_type(class)?;
-                let name = self.constant_pool.get_utf8(name)?;
+                let callee_class =
+                    classes.try_resolve_in(self.loader(), callee_class, methods, heap, stack)?;
 
-                let (method_index, virtual_index, parameter_count) = *classes
-                    .resolve_by_name(callee_class, methods, heap, stack)
+                // Since Java 11, javac uses invokevirtual for private methods of the same nest
+                if let Some((method_index, parameter_count)) = callee_class.direct_methods.get(&key)
+                {
+                    self.check_method_access(*method_index, classes, methods)?;
+                    self.constant_pool.update_resolved_static_method(
+                        index,
+                        *method_index,
+                        *parameter_count,
+                    );
+                    self.resolution_statistics
+                        .record_resolution(SymbolicReference::Method);
+                    return Ok((VirtualCall::Direct(*method_index), *parameter_count));
+                }
+
+                let (method_index, virtual_index, parameter_count) = *callee_class
                     .virtual_methods
-                    .get(name)
-                    .ok_or_else(|| MethodError::UnknownVirtual(name.to_string()))?;
+                    .get(&key)
+                    .ok_or_else(|| MethodError::UnknownVirtual(key.to_string()))?;
+                self.check_method_access(method_index, classes, methods)?;
 
                 self.constant_pool.update_resolved_virtual_method(
                     index,
@@ -390,15 +852,237 @@ impl Class {
                     virtual_index,
                     parameter_count,
                 );
+                self.resolution_statistics
+                    .record_resolution(SymbolicReference::Method);
+
+                Ok((VirtualCall::Dispatched(virtual_index), parameter_count))
+            }
+        }
+    }
+
+    pub fn resolve_interface_method(
+        &self,
+        index: ConstantPoolIndex,
+        classes: &ClassLibrary,
+        heap: &mut Heap,
+        methods: &MethodTable,
+        stack: StackPointer,
+    ) -> Result<InterfaceMethodReference, MethodError> {
+        match self.constant_pool.get_interface_method(index)? {
+            InterfaceMethodReference::Unresolved {
+                class,
+                name_and_type,
+            } => {
+                let key = self.method_key(name_and_type)?;
+                let interface = self.constant_pool.resolve_type(class)?;
+
+                let interface =
+                    classes.try_resolve_in(self.loader(), interface, methods, heap, stack)?;
+                if !interface.is_interface() {
+                    return Err(MethodError::NotAnInterface(interface.name()?.to_string()));
+                }
+
+                // JVMS §5.4.3.4: Look in the interface, then in java/lang/Object, then in the super interfaces
+                let resolved = if let Some(method_index) = interface.find_interface_method(&key) {
+                    InterfaceMethodReference::ResolvedInterface {
+                        interface: interface.index(),
+                        method_index,
+                        parameter_count: interface.interface_methods[method_index.0].1,
+                    }
+                } else if let Some((_, virtual_index, parameter_count)) = classes
+                    .try_resolve_by_name("java/lang/Object", methods, heap, stack)?
+                    .virtual_methods
+                    .get(&key)
+                {
+                    InterfaceMethodReference::ResolvedVirtual {
+                        virtual_index: *virtual_index,
+                        parameter_count: *parameter_count,
+                    }
+                } else {
+                    interface
+                        .itable
+                        .keys()
+                        .map(|super_interface| classes.resolve(*super_interface))
+                        .find_map(|super_interface| {
+                            super_interface
+                                .find_interface_method(&key)
+                                .map(|method_index| InterfaceMethodReference::ResolvedInterface {
+                                    interface: super_interface.index(),
+                                    method_index,
+                                    parameter_count: super_interface.interface_methods
+                                        [method_index.0]
+                                        .1,
+                                })
+                        })
+                        .ok_or_else(|| MethodError::UnknownInterface(key.to_string()))?
+                };
+
+                self.constant_pool
+                    .update_resolved_interface_method(index, resolved.clone());
+                self.resolution_statistics
+                    .record_resolution(SymbolicReference::Method);
+
+                Ok(resolved)
+            }
+            resolved => {
+                self.resolution_statistics
+                    .record_hit(SymbolicReference::Method);
+                Ok(resolved)
+            }
+        }
+    }
+
+    fn find_interface_method(&self, key: &MethodKey) -> Option<InterfaceMethodIndex> {
+        self.interface_methods
+            .iter()
+            .position(|(method_key, _)| method_key == key)
+            .map(InterfaceMethodIndex)
+    }
+
+    /// Whether this class may access a member with the given visibility that is declared by the given class (JVMS §5.4.4)
+    fn can_access(
+        &self,
+        declaring_class: &Class,
+        visibility: Visibility,
+        classes: &ClassLibrary,
+    ) -> bool {
+        if classes.checks_module_access() && !self.can_access_module_of(declaring_class) {
+            return false;
+        }
+        match visibility {
+            Visibility::Public => true,
+            Visibility::PackagePrivate => self.package == declaring_class.package,
+            Visibility::Protected => {
+                self.package == declaring_class.package
+                    || self.is_subclass_of(declaring_class.index, classes)
+            }
+            // Nested classes access each other's private members directly since Java 11
+            Visibility::Private => self.is_nestmate_of(declaring_class, classes),
+        }
+    }
+
+    /// Whether both classes belong to the same nest (JVMS §5.4.4). The nest host is only validated if it has been
+    /// loaded already, because access checks can't load classes. An unloaded host is trusted if it is in the
+    /// same package.
+    fn is_nestmate_of(&self, other: &Class, classes: &ClassLibrary) -> bool {
+        if self.index == other.index {
+            return true;
+        }
+        let (Ok(host), Ok(other_host)) = (self.nest_host(classes), other.nest_host(classes)) else {
+            return false;
+        };
+        host == other_host
+    }
+
+    /// The name of the host of the nest of this class, or an error if the NestHost attribute doesn't name a valid
+    /// host (the class is the host of its own nest then, see JVMS §5.4.4)
+    fn nest_host(&self, classes: &ClassLibrary) -> Result<&str, ConstantPoolError> {
+        let name = self.name()?;
+        let Some(host) = &self.data.nest_host else {
+            return Ok(name);
+        };
+        let is_member = match classes.find_loaded(host) {
+            Some(index) => {
+                let host_class = classes.resolve(index);
+                host_class.package == self.package
+                    && host_class
+                        .data
+                        .nest_members
+                        .iter()
+                        .any(|member| member == name)
+            }
+            None => RuntimePackage::of_class(host, self.package.loader()) == self.package,
+        };
+        if is_member {
+            Ok(host)
+        } else {
+            log::warn!("{} names the invalid nest host {}", name, host);
+            Ok(name)
+        }
+    }
 
-                Ok((virtual_index, parameter_count))
+    /// Whether the module of this class reads the module of the declaring class and the package of the declaring
+    /// class is exported to it
+    fn can_access_module_of(&self, declaring_class: &Class) -> bool {
+        let package = declaring_class.package.name();
+        match (&self.module, &declaring_class.module) {
+            // The class library isn't modular, so classes in the unnamed module are accessible from all modules
+            (_, None) => true,
+            (None, Some(target)) => target.exports(package, None),
+            (Some(module), Some(target)) => {
+                Arc::ptr_eq(module, target)
+                    || (module.reads(target.name()) && target.exports(package, Some(module.name())))
             }
-            _ => Err(MethodError::NotVirtual(index)),
+        }
+    }
+
+    fn check_method_access(
+        &self,
+        method: MethodIndex,
+        classes: &ClassLibrary,
+        methods: &MethodTable,
+    ) -> Result<(), MethodError> {
+        let method = methods.get_data(method);
+        let declaring_class = classes.resolve(method.owning_class);
+        if self.can_access(declaring_class, method.visibility, classes) {
+            Ok(())
+        } else {
+            Err(MethodError::IllegalAccess {
+                method: format!(
+                    "{}.{}{}",
+                    declaring_class.name()?,
+                    method.name,
+                    method.descriptor
+                ),
+                class: self.name()?.to_string(),
+            })
+        }
+    }
+
+    fn check_field_access(
+        &self,
+        declaring_class: ClassIndex,
+        visibility: Visibility,
+        name: &str,
+        classes: &ClassLibrary,
+    ) -> Result<(), FieldError> {
+        let declaring_class = classes.resolve(declaring_class);
+        if self.can_access(declaring_class, visibility, classes) {
+            Ok(())
+        } else {
+            Err(FieldError::IllegalAccess {
+                field: format!("{}.{}", declaring_class.name()?, name),
+                class: self.name()?.to_string(),
+            })
+        }
+    }
+
+    /// Finds the class that declares the field (this class or one of its superclasses) and the field's visibility
+    fn find_field_declaration(
+        &self,
+        name: &str,
+        is_static: bool,
+        classes: &ClassLibrary,
+    ) -> Option<(ClassIndex, Visibility)> {
+        let fields = if is_static {
+            &self.data.static_fields
+        } else {
+            &self.data.fields
+        };
+        if let Some(field) = fields.iter().find(|field| field.name == name) {
+            Some((self.index, field.visibility))
+        } else {
+            classes
+                .resolve(self.super_class?)
+                .find_field_declaration(name, is_static, classes)
         }
     }
 
     pub fn get_static_field(&self, info: FieldInfo) -> JvmValue {
-        self.static_fields.borrow().get_value(info.offset, info.ty)
+        self.static_fields
+            .lock()
+            .unwrap()
+            .get_value(info.offset, info.ty)
     }
 
     pub fn get_static_field_by_name(
@@ -412,43 +1096,75 @@ impl Class {
 
     pub fn set_static_field(&self, info: FieldInfo, value: JvmValue) {
         self.static_fields
-            .borrow_mut()
+            .lock()
+            .unwrap()
             .set_value(info.offset, info.ty, value);
     }
 
+    /// The raw values of all static fields, or None if a static field references an object, because the heap is not
+    /// part of a checkpoint (see checkpoint), or if the class hasn't been initialized yet
+    pub fn statics_snapshot(&self) -> Option<Vec<u8>> {
+        if self.init_state() != InitState::Initialized || !self.static_references().is_empty() {
+            return None;
+        }
+        let length = self.static_field_layout.byte_length();
+        Some(unsafe { self.static_fields.lock().unwrap().bytes(0, length) }.to_vec())
+    }
+
+    /// Initializes the static fields from a snapshot instead of running the static initializer.
+    /// Returns false if the snapshot doesn't match the layout of the static fields.
+    pub fn restore_statics(&self, snapshot: &[u8]) -> bool {
+        if snapshot.len() != self.static_field_layout.byte_length() {
+            return false;
+        }
+        unsafe {
+            self.static_fields.lock().unwrap().set_bytes(0, snapshot);
+        }
+        true
+    }
+
     pub fn get_loadable(
         &self,
         index: ConstantPoolIndex,
-    ) -> Result<(JvmType, JvmValue), ConstantPoolError> {
+    ) -> Result<LoadableConstant<'_>, ConstantPoolError> {
         let value = self.constant_pool.get(index)?;
         match value {
-            ConstantPoolEntry::Integer(value) => Ok((
+            ConstantPoolEntry::Integer(value) => Ok(LoadableConstant::Value(
                 JvmType::Integer,
-                JvmValue {
-                    int: (*value).into(),
-                },
+                JvmValue { int: *value },
             )),
-            ConstantPoolEntry::Long(value) => Ok((
+            ConstantPoolEntry::Long(value) => Ok(LoadableConstant::Value(
                 JvmType::Long,
-                JvmValue {
-                    long: (*value).into(),
-                },
+                JvmValue { long: *value },
             )),
-            ConstantPoolEntry::Float(value) => Ok((
+            ConstantPoolEntry::Float(value) => Ok(LoadableConstant::Value(
                 JvmType::Float,
-                JvmValue {
-                    float: (*value).into(),
-                },
+                JvmValue { float: *value },
             )),
-            ConstantPoolEntry::Double(value) => Ok((
+            ConstantPoolEntry::Double(value) => Ok(LoadableConstant::Value(
                 JvmType::Double,
-                JvmValue {
-                    double: (*value).into(),
-                },
+                JvmValue { double: *value },
+            )),
+            ConstantPoolEntry::String { value } => Ok(LoadableConstant::String(
+                self.constant_pool.get_utf8(*value)?,
+            )),
+            ConstantPoolEntry::Class { .. } => Ok(LoadableConstant::Class(
+                self.constant_pool.resolve_type(index)?,
             )),
-            ConstantPoolEntry::String(_) => todo!(),
-            ConstantPoolEntry::Class { .. } => todo!(),
-            // + MethodHandle, MethodType, Dynamic
+            ConstantPoolEntry::MethodHandle { .. } => {
+                let (kind, reference) = self.constant_pool.get_method_handle(index)?;
+                Ok(LoadableConstant::MethodHandle(kind, reference))
+            }
+            ConstantPoolEntry::MethodType { .. } => Ok(LoadableConstant::MethodType(
+                self.constant_pool.get_method_type(index)?,
+            )),
+            ConstantPoolEntry::Dynamic { .. } => {
+                let (bootstrap_method, name_and_type) = self.constant_pool.get_dynamic(index)?;
+                Ok(LoadableConstant::Dynamic(
+                    self.bootstrap_method(bootstrap_method)?,
+                    self.method_key(name_and_type)?,
+                ))
+            }
             _ => Err(ConstantPoolError::NotLoadable(index)),
         }
     }
@@ -457,10 +1173,30 @@ impl Class {
         &self.field_layout
     }
 
+    /// The reference-typed fields of the instances of this class
+    pub fn reference_map(&self) -> &ReferenceMap {
+        &self.reference_map
+    }
+
+    /// The objects that are referenced by the static fields of this class
+    pub fn static_references(&self) -> Vec<HeapIndex> {
+        let statics = self.static_fields.lock().unwrap();
+        self.static_reference_map
+            .offsets()
+            .iter()
+            .map(|offset| statics.get_reference(*offset).to_heap_index())
+            .filter(|object| *object != NULL_POINTER)
+            .collect()
+    }
+
     pub fn field_descriptors(&self) -> &[FieldDescriptor] {
         &self.data.fields
     }
 
+    pub fn resolution_statistics(&self) -> &ResolutionStatistics {
+        &self.resolution_statistics
+    }
+
     pub fn name(&self) -> Result<&str, ConstantPoolError> {
         self.constant_pool.resolve_type(self.data.this_class)
     }
@@ -469,12 +1205,118 @@ impl Class {
         self.index
     }
 
+    /// The generic signature from the Signature attribute, None if the class isn't generic
+    pub fn generic_signature(&self) -> Option<&ClassSignature> {
+        self.data.signature.as_ref()
+    }
+
+    /// The annotations of the class itself, the annotations of its members are part of their descriptors
+    pub fn annotations(&self) -> &Annotations {
+        &self.data.annotations
+    }
+
+    /// The static and instance fields that are declared by this class
+    pub fn declared_fields(&self) -> impl Iterator<Item = &FieldDescriptor> {
+        self.data.static_fields.iter().chain(&self.data.fields)
+    }
+
+    /// The static and instance methods that are declared by this class
+    pub fn declared_methods(&self) -> impl Iterator<Item = &MethodDescriptor> {
+        self.data.static_methods.iter().chain(&self.data.methods)
+    }
+
     pub fn resolve_type(&self, index: ConstantPoolIndex) -> Result<&str, ConstantPoolError> {
         self.constant_pool.resolve_type(index)
     }
 
     pub fn dispatch_virtual_call(&self, method: VirtualMethodIndex) -> MethodIndex {
-        unsafe { *self.dispatch_table.offset(method.0 as isize) }
+        unsafe { *self.dispatch_table.0.add(method.0) }
+    }
+
+    /// Returns None if this class doesn't implement the interface or the method is abstract
+    pub fn dispatch_interface_call(
+        &self,
+        interface: ClassIndex,
+        method: InterfaceMethodIndex,
+    ) -> Option<MethodIndex> {
+        self.itable
+            .get(&interface)
+            .and_then(|implementations| implementations[method.0])
+    }
+
+    pub fn package(&self) -> &RuntimePackage {
+        &self.package
+    }
+
+    /// The defining loader of the class
+    pub fn loader(&self) -> ClassLoaderId {
+        self.package.loader()
+    }
+
+    /// Returns None if the class is in the unnamed module
+    pub fn module(&self) -> Option<&Arc<Module>> {
+        self.module.as_ref()
+    }
+
+    /// The name of the package in binary form, like Class.getPackageName (e.g. java.lang)
+    pub fn package_name(&self) -> String {
+        self.package.binary_name()
+    }
+
+    /// The direct subclasses (or subinterfaces and implementations) that a sealed class or interface permits, empty
+    /// if the class isn't sealed
+    pub fn permitted_subclasses(&self) -> &[String] {
+        &self.data.permitted_subclasses
+    }
+
+    /// The name of the source file from the SourceFile attribute, e.g. Foo.java
+    pub fn source_file(&self) -> Option<&str> {
+        self.data.source_file.as_deref()
+    }
+
+    pub fn super_class(&self) -> Option<ClassIndex> {
+        self.super_class
+    }
+
+    /// Whether this class is the given class or one of its (transitive) subclasses
+    pub fn is_subclass_of(&self, other: ClassIndex, classes: &ClassLibrary) -> bool {
+        let mut current = Some(self.index);
+        while let Some(class) = current {
+            if class == other {
+                return true;
+            }
+            current = classes.resolve(class).super_class;
+        }
+        false
+    }
+
+    pub fn flags(&self) -> ClassFlags {
+        self.data.flags
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.data.is_interface
+    }
+
+    pub fn interfaces(&self) -> &[ClassIndex] {
+        &self.interfaces
+    }
+
+    pub fn init_state(&self) -> InitState {
+        InitState::ALL[self.init_state.load(Ordering::Acquire) as usize]
+    }
+
+    pub fn set_init_state(&self, state: InitState) {
+        self.init_state.store(state as u8, Ordering::Release);
+    }
+
+    /// Whether the interface declares a default (or private) instance method. Initializing a class initializes the
+    /// superinterfaces that do, because their methods may run on instances of the class (JVMS §5.5).
+    pub fn declares_default_methods(&self) -> bool {
+        self.data
+            .methods
+            .iter()
+            .any(|method| !matches!(method.code, MethodCode::Abstract))
     }
 }
 
@@ -482,6 +1324,79 @@ impl Class {
 #[repr(transparent)]
 pub struct VirtualMethodIndex(usize);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct InterfaceMethodIndex(usize);
+
+/// Selects the maximally-specific superinterface method for every method that the interfaces declare
+/// (JVMS §5.4.3.3). Returns (key, method, parameter count) of the methods for which exactly one of the maximally
+/// specific declarations is a default method; abstract or conflicting declarations don't provide an implementation.
+fn select_default_methods(
+    interfaces: &[ClassIndex],
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+) -> Vec<(MethodKey, MethodIndex, usize)> {
+    let mut keys: Vec<&MethodKey> = Vec::new();
+    for interface in interfaces {
+        for (key, _) in &classes.resolve(*interface).interface_methods {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+
+    let mut selected = Vec::new();
+    for key in keys {
+        // The interfaces that declare the method, together with the default method (None if it is abstract)
+        let declarations: Vec<(ClassIndex, Option<(MethodIndex, usize)>)> = interfaces
+            .iter()
+            .map(|interface| (*interface, classes.resolve(*interface)))
+            .filter(|(_, class)| {
+                class
+                    .interface_methods
+                    .iter()
+                    .any(|(other, _)| other == key)
+                    && !class.direct_methods.contains_key(key)
+            })
+            .map(|(interface, class)| {
+                let default = class
+                    .virtual_methods
+                    .get(key)
+                    .filter(|(method, _, _)| methods.get_data(*method).owning_class == interface)
+                    .map(|(method, _, parameter_count)| (*method, *parameter_count));
+                (interface, default)
+            })
+            .collect();
+
+        let mut defaults = declarations
+            .iter()
+            .filter(|(interface, _)| {
+                !declarations.iter().any(|(other, _)| {
+                    other != interface && classes.is_subtype_of(*other, *interface)
+                })
+            })
+            .filter_map(|(_, default)| *default);
+        match (defaults.next(), defaults.next()) {
+            (Some((method, parameter_count)), None) => {
+                selected.push((key.clone(), method, parameter_count))
+            }
+            (Some(_), Some(_)) => log::warn!("Conflicting default methods for {}", key),
+            _ => {}
+        }
+    }
+    selected
+}
+
+/// A constant that can be loaded by ldc (JVMS §4.4, table 4.4-C)
+pub enum LoadableConstant<'a> {
+    Value(JvmType, JvmValue),
+    String(&'a str), // The caller interns the string, see model::string
+    Class(&'a str),  // The caller loads the mirror of the class, see model::mirror
+    MethodHandle(ReferenceKind, ConstantPoolIndex),
+    MethodType(&'a str),                     // The method descriptor
+    Dynamic(&'a BootstrapMethod, MethodKey), // (bootstrap method, name and field descriptor), computed by the bootstrap method
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum MethodError {
     #[error("Unknown instance method '{0}'")]
@@ -492,20 +1407,80 @@ pub enum MethodError {
     )]
     NotVirtual(ConstantPoolIndex),
 
+    #[error("Unknown interface method '{0}'")]
+    UnknownInterface(String),
+
+    #[error("'{0}' was expected to be an interface, but is a class")]
+    NotAnInterface(String),
+
+    #[error("The class {0} does not provide an implementation for the called method of the interface {1}")]
+    MissingInterfaceImplementation(String, String),
+
+    #[error("{class} cannot access the method {method}")]
+    IllegalAccess { method: String, class: String },
+
     #[error("Unknown static method '{0}'")]
     UnknownStatic(String),
 
     #[error("The method at constant pool index {0} was expected to be static, but is not static")]
     NotStatic(ConstantPoolIndex),
 
+    #[error("Invalid method descriptor '{0}'")]
+    InvalidDescriptor(String),
+
+    // Boxed, because the failed initialization of a class contains an ExecutionError, which may be a MethodError
+    #[error(transparent)]
+    ClassResolve(Box<ClassResolveError>),
+
     #[error(transparent)]
     ConstantPool(#[from] ConstantPoolError),
 }
 
+impl From<ClassResolveError> for MethodError {
+    fn from(err: ClassResolveError) -> Self {
+        Self::ClassResolve(Box::new(err))
+    }
+}
+
+/// Adds a method with an implementation (i.e. bytecode or a native method) to the method table
+fn add_method(
+    desc: &MethodDescriptor,
+    class_name: &str,
+    class: ClassIndex,
+    methods: &MethodTable,
+) -> MethodIndex {
+    if let MethodCode::Native = desc.code {
+        methods.add_method(
+            methods.native_implementation(class_name, &desc.name, &desc.descriptor),
+            MethodData::from_native_descriptor(desc, class).unwrap(),
+            class_name,
+        )
+    } else {
+        methods.add_method(
+            methods.bytecode_implementation(class_name, &desc.name, &desc.descriptor),
+            MethodData::from_bytecode_descriptor(desc, class).unwrap(),
+            class_name,
+        )
+    }
+}
+
+/// The target of an invokevirtual instruction
+#[derive(Debug, Clone, Copy)]
+pub enum VirtualCall {
+    Dispatched(VirtualMethodIndex), // Looked up in the dispatch table of the receiver
+    Direct(MethodIndex),            // Private methods can't be overridden
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ClassCreationError {
     #[error("Failed to resolve the super class")]
     SuperclassResolutionFailed(#[from] ConstantPoolError),
+
+    #[error("The class doesn't fit into the {capacity} {resource} of the VM")]
+    TableFull {
+        resource: &'static str,
+        capacity: usize,
+    },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -516,22 +1491,84 @@ pub enum FieldError {
     #[error("The static field {0} cannot be resolved")]
     StaticFieldNotFound(String),
 
+    #[error("{class} cannot access the field {field}")]
+    IllegalAccess { field: String, class: String },
+
+    #[error("Invalid field descriptor '{0}'")]
+    InvalidDescriptor(String),
+
+    #[error(transparent)]
+    ClassResolve(Box<ClassResolveError>),
+
     #[error(transparent)]
     ConstantPool(#[from] ConstantPoolError),
 }
 
-/*
-bitflags::bitflags! {
-    pub struct ClassAccessModifiers: u16 {
-        const PUBLIC = 0x0001;
-        const FINAL = 0x0010;
-        const SUPER = 0x0020;
-        const INTERFACE = 0x0200;
-        const ABSTRACT = 0x0400;
-        const SYNTHETIC = 0x1000;
-        const ANNOTATION = 0x2000;
-        const ENUM = 0x4000;
-        const MODULE = 0x8000;
+impl From<ClassResolveError> for FieldError {
+    fn from(err: ClassResolveError) -> Self {
+        Self::ClassResolve(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing;
+
+    /// Calls the method of Dispatch twice, because the invocations are quickened on their first execution
+    fn call_twice(method: &str) -> i32 {
+        let mut jvm = testing::jvm();
+        let first = testing::call_int(&mut jvm, "Dispatch", method);
+        let second = testing::call_int(&mut jvm, "Dispatch", method);
+        assert_eq!(first, second, "{}", method);
+        first
+    }
+
+    #[test]
+    fn overrides_only_the_overload_with_the_same_descriptor() {
+        assert_eq!(call_twice("overloadedOverride"), 32);
+    }
+
+    #[test]
+    fn inherits_the_overloads_that_are_not_overridden() {
+        assert_eq!(call_twice("inheritedOverload"), 2);
+    }
+
+    #[test]
+    fn covariant_override_replaces_the_slot_through_the_bridge_method() {
+        assert_eq!(call_twice("covariantOverride"), 44);
+    }
+
+    #[test]
+    fn private_methods_are_not_overridden() {
+        assert_eq!(call_twice("privateMethodNotOverridden"), 15);
+    }
+
+    #[test]
+    fn method_with_a_different_return_type_is_not_found() {
+        assert_eq!(call_twice("staleReturnType"), -1);
+    }
+
+    #[test]
+    fn method_with_different_parameter_types_is_not_found() {
+        assert_eq!(call_twice("staleParameterType"), -1);
+    }
+
+    #[test]
+    fn string_constants_are_interned_when_the_class_is_initialized() {
+        let mut jvm = testing::jvm();
+        let literal = unsafe {
+            jvm.call_static("Constants", "greeting", "()Ljava/lang/String;", &[])
+                .unwrap()
+                .reference
+        };
+        let class = jvm.load_class("Constants").unwrap();
+        let value = jvm
+            .classes()
+            .resolve(class)
+            .get_static_field_by_name("GREETING", jvm.classes())
+            .unwrap();
+        let constant = unsafe { value.reference };
+        assert!(jvm.is_same_object(constant, literal));
+        assert_eq!(jvm.read_string(constant).unwrap(), "Hello");
     }
 }
-*/
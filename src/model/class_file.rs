@@ -1,17 +1,58 @@
+// The versions of the class file format that the VM can load (JVMS §4.1). Class files of a major version from Java 12
+// (56) on have the minor version 0, or 65535 if they depend on the preview features of their release. Preview features
+// are only supported for the latest major version and must be enabled explicitly (--enable-preview).
+// The parser consumes the class file into a ClassData and drops what the VM doesn't need. Tools that need the class
+// file as it is (e.g. -XX:PrintClassFile or rewriting a class) parse it with class_parser::parse_retaining, which
+// additionally keeps its complete structure as ClassFileContents.
+
+use super::constant_pool::{ConstantPool, ConstantPoolIndex};
+
+pub const MIN_MAJOR_VERSION: u16 = 45; // JDK 1.0.2
+pub const MAX_MAJOR_VERSION: u16 = 65; // Java 21
+pub const PREVIEW_MINOR_VERSION: u16 = 0xFFFF;
+const FIRST_PREVIEW_MAJOR_VERSION: u16 = 56; // Java 12
+
+/// Checks whether the VM supports the version of a class file
+pub fn check_version(major_version: u16, minor_version: u16) -> Result<(), VersionError> {
+    if !(MIN_MAJOR_VERSION..=MAX_MAJOR_VERSION).contains(&major_version)
+        || (major_version >= FIRST_PREVIEW_MAJOR_VERSION
+            && minor_version != 0
+            && minor_version != PREVIEW_MINOR_VERSION)
+    {
+        return Err(VersionError::Unsupported(major_version, minor_version));
+    }
+    if major_version >= FIRST_PREVIEW_MAJOR_VERSION
+        && minor_version == PREVIEW_MINOR_VERSION
+        && major_version != MAX_MAJOR_VERSION
+    {
+        return Err(VersionError::UnsupportedPreview(major_version));
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct ClassFile {
     minor_version: u16,
     major_version: u16,
+    constant_pool_length: usize, // In bytes, as stored in the class file
+    contents: Option<Box<ClassFileContents>>, // Only retained on request
 }
 
 impl ClassFile {
-    pub fn new(minor_version: u16, major_version: u16) -> Self {
+    pub fn new(minor_version: u16, major_version: u16, constant_pool_length: usize) -> Self {
         Self {
             minor_version,
             major_version,
+            constant_pool_length,
+            contents: None,
         }
     }
 
+    pub fn with_contents(mut self, contents: ClassFileContents) -> Self {
+        self.contents = Some(Box::new(contents));
+        self
+    }
+
     pub fn minor_version(&self) -> u16 {
         self.minor_version
     }
@@ -19,4 +60,150 @@ impl ClassFile {
     pub fn major_version(&self) -> u16 {
         self.major_version
     }
+
+    pub fn constant_pool_length(&self) -> usize {
+        self.constant_pool_length
+    }
+
+    /// The complete structure of the class file, if it has been parsed with class_parser::parse_retaining
+    pub fn contents(&self) -> Option<&ClassFileContents> {
+        self.contents.as_deref()
+    }
+
+    /// Whether the class depends on the preview features of the release of its major version
+    pub fn uses_preview_features(&self) -> bool {
+        self.major_version >= FIRST_PREVIEW_MAJOR_VERSION
+            && self.minor_version == PREVIEW_MINOR_VERSION
+    }
+}
+
+/// Everything after the version of a class file (JVMS §4.1), with the indices into the constant pool and the raw
+/// access flags as they are stored. Attributes are kept as bytes, except for Code whose attributes are kept as well.
+#[derive(Debug, Clone)]
+pub struct ClassFileContents {
+    pub constant_pool: ConstantPool, // Unresolved
+    pub access_flags: u16,
+    pub this_class: ConstantPoolIndex,
+    pub super_class: ConstantPoolIndex, // Zero for java/lang/Object and module-info
+    pub interfaces: Vec<ConstantPoolIndex>,
+    pub fields: Vec<MemberInfo>,
+    pub methods: Vec<MemberInfo>,
+    pub attributes: Vec<AttributeInfo>,
+}
+
+/// A field_info or method_info structure (JVMS §4.5, §4.6)
+#[derive(Debug, Clone)]
+pub struct MemberInfo {
+    pub access_flags: u16,
+    pub name: ConstantPoolIndex,
+    pub descriptor: ConstantPoolIndex,
+    pub attributes: Vec<AttributeInfo>,
+}
+
+impl MemberInfo {
+    /// The content of the Code attribute of a method, None for native and abstract methods and for fields
+    pub fn code(&self) -> Option<&CodeAttribute> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.content {
+                AttributeContent::Code(code) => Some(code),
+                AttributeContent::Raw(_) => None,
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AttributeInfo {
+    pub name: ConstantPoolIndex, // Utf8
+    pub content: AttributeContent,
+}
+
+#[derive(Debug, Clone)]
+pub enum AttributeContent {
+    Code(CodeAttribute),
+    Raw(Vec<u8>), // The info bytes of every other attribute, also of the ones that the VM doesn't know
+}
+
+/// The Code attribute (JVMS §4.7.3)
+#[derive(Debug, Clone)]
+pub struct CodeAttribute {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: Vec<u8>,
+    pub exception_table: Vec<ExceptionHandlerInfo>,
+    pub attributes: Vec<AttributeInfo>, // E.g. LineNumberTable and StackMapTable
+}
+
+/// An entry of the exception table of a Code attribute, as opposed to method::ExceptionTableEntry the pcs aren't
+/// checked and the catch type isn't resolved
+#[derive(Debug, Clone, Copy)]
+pub struct ExceptionHandlerInfo {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    pub catch_type: ConstantPoolIndex, // Zero for handlers of any exception
+}
+
+/// The class file has a version that the VM doesn't support, which is an UnsupportedClassVersionError in Java
+#[derive(thiserror::Error, Debug)]
+pub enum VersionError {
+    #[error(
+        "class file version {0}.{1} is not supported, only versions {}.0 to {}.0 are recognized",
+        MIN_MAJOR_VERSION,
+        MAX_MAJOR_VERSION
+    )]
+    Unsupported(u16, u16),
+
+    #[error(
+        "class file version {0}.65535 depends on preview features that are unsupported, only the preview features of version {}.65535 are recognized",
+        MAX_MAJOR_VERSION
+    )]
+    UnsupportedPreview(u16),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_version, ClassFile, VersionError, MAX_MAJOR_VERSION, PREVIEW_MINOR_VERSION};
+
+    #[test]
+    fn accepts_the_supported_versions() {
+        for (major, minor) in [
+            (45, 0),
+            (45, 3),
+            (52, 0),
+            (55, 1), // Minor versions are only restricted from Java 12 on
+            (56, 0),
+            (MAX_MAJOR_VERSION, 0),
+            (MAX_MAJOR_VERSION, PREVIEW_MINOR_VERSION),
+        ] {
+            assert!(check_version(major, minor).is_ok(), "{}.{}", major, minor);
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        for (major, minor) in [(44, 0), (MAX_MAJOR_VERSION + 1, 0), (56, 1), (60, 0xFFFE)] {
+            assert!(
+                matches!(
+                    check_version(major, minor),
+                    Err(VersionError::Unsupported(..))
+                ),
+                "{}.{}",
+                major,
+                minor
+            );
+        }
+        // Only the preview features of the latest release are supported
+        assert!(matches!(
+            check_version(MAX_MAJOR_VERSION - 1, PREVIEW_MINOR_VERSION),
+            Err(VersionError::UnsupportedPreview(major)) if major == MAX_MAJOR_VERSION - 1
+        ));
+    }
+
+    #[test]
+    fn only_classes_of_java_12_and_later_use_preview_features() {
+        assert!(ClassFile::new(PREVIEW_MINOR_VERSION, MAX_MAJOR_VERSION, 0).uses_preview_features());
+        assert!(!ClassFile::new(0, MAX_MAJOR_VERSION, 0).uses_preview_features());
+        assert!(!ClassFile::new(PREVIEW_MINOR_VERSION, 55, 0).uses_preview_features());
+    }
 }
@@ -0,0 +1,322 @@
+use std::{
+    cell::RefCell,
+    fmt::Write as _,
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{
+    interpreter::frame,
+    jit::call_graph::{escape, DumpFormat},
+};
+
+use super::{
+    class_library::{ClassIndex, ClassLibrary},
+    method::{MethodIndex, MethodTable},
+};
+
+// The loaded class hierarchy together with what triggered the loading of every class (-XX:ClassGraphDump), to debug
+// the order in which classes are loaded and initialized. Classes are loaded lazily on first use and initialized on
+// their first active use (see ClassLibrary::initialize), which may be much later.
+// A class is loaded because of
+// - the class that is being loaded, if it is its super class or one of its interfaces,
+// - the instruction of the newest interpreter frame, e.g. a new or getstatic (which may run in a static initializer),
+// - the initialization of a class without running Java code, e.g. when the VM creates a string constant,
+// - the VM itself, e.g. the main class.
+// Recording is disabled by default.
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static CLASS_GRAPH: Mutex<Option<ClassGraph>> = Mutex::new(None);
+
+thread_local! {
+    // The classes that are being loaded (innermost last)
+    static LOADING: RefCell<Vec<Loading>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Default)]
+struct ClassGraph {
+    loads: Vec<ClassLoad>, // In the order in which the loading of the classes has started
+}
+
+struct ClassLoad {
+    name: String,
+    class: Option<ClassIndex>, // None until the class has been loaded successfully
+    trigger: Trigger,
+}
+
+#[derive(Debug, Clone)]
+enum Trigger {
+    Vm,
+    Hierarchy(usize), // The load of the class whose super class or interface is loaded
+    Initialization(usize), // The load of the class that is being initialized
+    Code { method: usize, pc: usize },
+}
+
+struct Loading {
+    load: usize,                         // The index in ClassGraph::loads
+    initializing: bool,                  // Pushed by start_initialization instead of start_loading
+    frame: Option<(MethodIndex, usize)>, // The newest interpreter frame when the loading has started
+}
+
+pub fn enable() {
+    *CLASS_GRAPH.lock().unwrap() = Some(ClassGraph::default());
+    ENABLED.store(true, Ordering::Release);
+}
+
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Records that the loading of the class starts. Must be paired with finish_loading.
+pub fn start_loading(class: &str) {
+    if !is_enabled() {
+        return;
+    }
+
+    let frame = frame::newest_frame();
+    let trigger = LOADING.with(|loading| match loading.borrow().last() {
+        Some(parent) if !parent.initializing => Trigger::Hierarchy(parent.load),
+        // Nothing has been executed since the initialization has started
+        Some(parent) if parent.frame == frame => Trigger::Initialization(parent.load),
+        _ => match frame {
+            Some((method, pc)) => Trigger::Code {
+                method: method.into(),
+                pc,
+            },
+            None => Trigger::Vm,
+        },
+    });
+    log::debug!("Loading of {} triggered by {:?}", class, trigger);
+    let mut graph = CLASS_GRAPH.lock().unwrap();
+    let Some(graph) = graph.as_mut() else {
+        return;
+    };
+    LOADING.with(|loading| {
+        loading.borrow_mut().push(Loading {
+            load: graph.loads.len(),
+            initializing: false,
+            frame,
+        })
+    });
+    graph.loads.push(ClassLoad {
+        name: class.to_string(),
+        class: None,
+        trigger,
+    });
+}
+
+/// Records that the initialization of the loaded class starts. Must be paired with finish_initialization.
+pub fn start_initialization(class: ClassIndex) {
+    if !is_enabled() {
+        return;
+    }
+
+    let graph = CLASS_GRAPH.lock().unwrap();
+    // Classes that have been loaded before the recording has been enabled aren't recorded
+    let Some(load) = graph.as_ref().and_then(|graph| {
+        graph
+            .loads
+            .iter()
+            .position(|load| load.class == Some(class))
+    }) else {
+        return;
+    };
+    LOADING.with(|loading| {
+        loading.borrow_mut().push(Loading {
+            load,
+            initializing: true,
+            frame: frame::newest_frame(),
+        })
+    });
+}
+
+/// Records that the initialization of the class has ended
+pub fn finish_initialization(class: ClassIndex) {
+    if !is_enabled() {
+        return;
+    }
+
+    let graph = CLASS_GRAPH.lock().unwrap();
+    let Some(graph) = graph.as_ref() else {
+        return;
+    };
+    LOADING.with(|loading| {
+        let mut loading = loading.borrow_mut();
+        let initializing = loading.last().filter(|current| current.initializing);
+        if initializing.is_some_and(|current| graph.loads[current.load].class == Some(class)) {
+            loading.pop();
+        }
+    });
+}
+
+/// Records that the loading of the innermost class has ended, the class is None if the loading has failed
+pub fn finish_loading(class: Option<ClassIndex>) {
+    if !is_enabled() {
+        return;
+    }
+
+    let Some(loading) = LOADING.with(|loading| loading.borrow_mut().pop()) else {
+        return;
+    };
+    if let Some(graph) = CLASS_GRAPH.lock().unwrap().as_mut() {
+        graph.loads[loading.load].class = class;
+    }
+}
+
+/// Writes the hierarchy of the loaded classes and the triggers of their loading to the given file
+pub fn dump(path: &Path, classes: &ClassLibrary, methods: &MethodTable) {
+    let graph = CLASS_GRAPH.lock().unwrap();
+    let graph = match graph.as_ref() {
+        Some(graph) => graph,
+        None => return,
+    };
+
+    let output = match DumpFormat::from_path(path) {
+        DumpFormat::Dot => to_dot(graph, classes, methods),
+        DumpFormat::Json => to_json(graph, classes, methods),
+    };
+    match fs::write(path, output) {
+        Ok(()) => log::info!("Wrote the class graph to {}", path.display()),
+        Err(err) => log::warn!(
+            "Failed to write the class graph to {}: {}",
+            path.display(),
+            err
+        ),
+    }
+}
+
+fn class_name(class: ClassIndex, classes: &ClassLibrary) -> &str {
+    classes.resolve(class).name().unwrap_or("<unknown>")
+}
+
+/// The position of the class in the load order and what has triggered its loading, None if the class has been
+/// loaded before the recording has been enabled. Failed loads don't count.
+fn load_of(graph: &ClassGraph, class: ClassIndex) -> Option<(usize, &Trigger)> {
+    graph
+        .loads
+        .iter()
+        .filter(|load| load.class.is_some())
+        .enumerate()
+        .find(|(_, load)| load.class == Some(class))
+        .map(|(order, load)| (order, &load.trigger))
+}
+
+fn method_name(method: usize, methods: &MethodTable) -> String {
+    let data = methods.get_data(method.into());
+    format!("{}{}", data.name, data.descriptor)
+}
+
+fn to_dot(graph: &ClassGraph, classes: &ClassLibrary, methods: &MethodTable) -> String {
+    let mut dot = String::from("digraph classes {\n    node [shape=box];\n");
+    for index in 0..classes.class_count() {
+        let name = class_name(ClassIndex(index), classes);
+        let label = match load_of(graph, ClassIndex(index)) {
+            Some((order, _)) => format!("{}\\n#{}", escape(name), order),
+            None => escape(name),
+        };
+        writeln!(dot, "    c{} [label=\"{}\"];", index, label).unwrap();
+    }
+    for index in 0..classes.class_count() {
+        let class = classes.resolve(ClassIndex(index));
+        if let Some(super_class) = class.super_class() {
+            writeln!(
+                dot,
+                "    c{} -> c{} [arrowhead=empty];",
+                index, super_class.0
+            )
+            .unwrap();
+        }
+        for interface in class.interfaces() {
+            writeln!(
+                dot,
+                "    c{} -> c{} [arrowhead=empty, style=dashed];",
+                index, interface.0
+            )
+            .unwrap();
+        }
+        // The super class and the interfaces are already connected by the hierarchy
+        let (source, label) = match load_of(graph, ClassIndex(index)) {
+            Some((_, Trigger::Initialization(parent))) => (
+                graph.loads[*parent].class.map(|class| class.0),
+                "initialization".to_string(),
+            ),
+            Some((_, Trigger::Code { method, pc })) => (
+                Some(methods.get_data((*method).into()).owning_class.0),
+                format!("{} pc {}", method_name(*method, methods), pc),
+            ),
+            _ => (None, String::new()),
+        };
+        if let Some(source) = source {
+            writeln!(
+                dot,
+                "    c{} -> c{} [label=\"{}\", color=blue, fontcolor=blue];",
+                source,
+                index,
+                escape(&label)
+            )
+            .unwrap();
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn to_json(graph: &ClassGraph, classes: &ClassLibrary, methods: &MethodTable) -> String {
+    let classes_json = (0..classes.class_count())
+        .map(|index| {
+            let class = classes.resolve(ClassIndex(index));
+            let interfaces = class
+                .interfaces()
+                .iter()
+                .map(|interface| interface.0.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let (order, trigger) = match load_of(graph, ClassIndex(index)) {
+                Some((order, trigger)) => (order.to_string(), trigger_json(graph, trigger, methods)),
+                None => ("null".to_string(), "null".to_string()),
+            };
+            format!(
+                "    {{\"id\": {}, \"name\": \"{}\", \"super_class\": {}, \"interfaces\": [{}], \"load_order\": {}, \"trigger\": {}}}",
+                index,
+                escape(class_name(ClassIndex(index), classes)),
+                class
+                    .super_class()
+                    .map_or("null".to_string(), |super_class| super_class.0.to_string()),
+                interfaces,
+                order,
+                trigger
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    format!("{{\n\"classes\": [\n{}\n]\n}}\n", classes_json)
+}
+
+fn trigger_json(graph: &ClassGraph, trigger: &Trigger, methods: &MethodTable) -> String {
+    match trigger {
+        Trigger::Vm => "{\"kind\": \"vm\"}".to_string(),
+        Trigger::Hierarchy(parent) => format!(
+            "{{\"kind\": \"hierarchy\", \"class\": \"{}\"}}",
+            escape(&graph.loads[*parent].name)
+        ),
+        Trigger::Initialization(parent) => format!(
+            "{{\"kind\": \"initialization\", \"class\": \"{}\"}}",
+            escape(&graph.loads[*parent].name)
+        ),
+        Trigger::Code { method, pc } => {
+            let data = methods.get_data((*method).into());
+            format!(
+                "{{\"kind\": \"code\", \"method\": \"{}\", \"owning_class\": {}, \"pc\": {}}}",
+                escape(&method_name(*method, methods)),
+                data.owning_class.0,
+                pc
+            )
+        }
+    }
+}
@@ -1,135 +1,1251 @@
-use std::{cell::RefCell, collections::HashMap};
-
-use appendlist::AppendList;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, RwLock,
+    },
+    thread::ThreadId,
+};
 
 use crate::{
+    checkpoint::{Checkpoint, ClassSnapshot},
     class_loader::BootstrapClassLoader,
     class_parser::{self, ParsingError},
-    interpreter::ExecutionError,
-    list::NativeList,
+    interpreter::{self, ExecutionError},
+    jit,
+    list::{NativeList, PublishedList},
+    options::ExecutionMode,
+    verifier::{Verifier, VerifyError},
 };
 
 use super::{
-    class::{Class, ClassCreationError},
+    class::{Class, ClassCreationError, InitState},
+    class_file::ClassFile,
+    class_graph,
     constant_pool::ConstantPoolError,
-    heap::Heap,
+    heap::{Heap, HeapIndex},
     method::{MethodIndex, MethodTable},
-    stack::StackPointer,
+    mirror::MirrorTable,
+    module::Module,
+    package::{ClassLoaderId, LoaderTable, RuntimePackage},
+    stack::{StackFrame, StackPointer},
+    string,
+    types::JvmType,
+    visibility::Visibility,
 };
 
+// The ClassLibrary is shared by threads (it is Sync), but only one thread loads classes at a time, because loading
+// needs the heap exclusively (&mut Heap). The other threads may resolve the classes that have been published meanwhile.
+// - Every class is loaded once per defining loader. The classes that are being loaded are kept in a set, loading a
+//   class that is already in it means that the class is its own supertype (JVMS §5.3.5).
+// - The index, the static fields and the dispatch table of a class are reserved at the tails of the shared lists
+//   under one lock, which is held until the class has been published.
+// - A class is published by appending it to the list of classes and then mapping its name to it (name_mappings), so
+//   a name never maps to a class that isn't complete. Classes are never removed or replaced afterwards, so the &Class
+//   that resolve hands out stay valid without holding a lock.
+
+const LOAD_CLASS_METHOD: (&str, &str) = ("loadClass", "(Ljava/lang/String;)Ljava/lang/Class;");
+
+// The dispatch tables and the static fields of all classes live in two lists that are never reallocated, because
+// classes and compiled code point into them. They are reserved large enough for the classes of java.base, the memory
+// is only committed once it is used. A class that doesn't fit anymore fails to load with LimitExceeded.
+const DISPATCH_TABLES_LENGTH: usize = 1 << 20; // In dwords
+const STATICS_LENGTH: usize = 1 << 22; // In bytes
+
 #[repr(C)]
 pub struct ClassLibrary {
     dispatch_tables: NativeList<MethodIndex>,
     static_attributes: NativeList<u8>,
-    dispatch_table_tail: RefCell<usize>, // In dwords (u32), i.e. size_of<MethodIndex>()
-    statics_tail: RefCell<usize>,        // In bytes
-    classes: AppendList<Class>,
-    name_mappings: RefCell<HashMap<String, usize>>,
+    tails: Mutex<Tails>, // Held while a class is created and published
+    classes: PublishedList<Class>,
+    name_mappings: RwLock<HashMap<(ClassLoaderId, String), usize>>, // By a defining or initiating loader
+    subtype_cache: RwLock<HashSet<(ClassIndex, ClassIndex)>>, // (source, target) pairs for which the subtype check succeeded
     class_loader: BootstrapClassLoader,
+    modules: RwLock<HashMap<String, Arc<Module>>>, // Named modules by their name
+    package_modules: RwLock<HashMap<String, Arc<Module>>>, // Packages of the named modules (in internal form) to their module
+    check_module_access: bool,
+    enable_preview: bool, // Classes may depend on the preview features of the latest supported release
+    recorded_class_files: Mutex<Option<HashMap<String, Vec<u8>>>>, // Only recorded if a checkpoint will be taken
+    restored_classes: Mutex<HashMap<String, ClassSnapshot>>, // Classes of a restored checkpoint that haven't been loaded yet
+    defined_classes: Mutex<HashMap<(ClassLoaderId, String), Vec<u8>>>, // Class files of define that are being loaded
+    loading: Mutex<HashSet<(ClassLoaderId, String)>>, // The classes that are being loaded, see ClassLibrary::load
+    initializing: Mutex<HashMap<ClassIndex, ThreadId>>, // The classes that are being initialized by their thread
+    initialized: Condvar, // Notified when a thread has finished the initialization of a class
+    limits: ClassLimits,
+    constant_pool_bytes: AtomicUsize,  // Of all loaded classes
+    verifier: Option<Mutex<Verifier>>, // None if classes aren't verified
+    mirrors: RwLock<MirrorTable>,
+    loaders: RwLock<LoaderTable>,
+}
+
+// Checks that the class library can be shared by threads
+const _: () = {
+    const fn assert_sync<T: Sync + Send>() {}
+    assert_sync::<ClassLibrary>()
+};
+
+/// The ends of the used parts of the native lists of the ClassLibrary
+#[derive(Default)]
+struct Tails {
+    dispatch_tables: usize, // In dwords (u32), i.e. size_of<MethodIndex>()
+    statics: usize,         // In bytes
+}
+
+/// Caps on the metadata of the loaded classes, which protect embedders from classpath bombs and runaway generation
+/// of classes. Loading a class that would exceed a limit fails. A limit that is None is unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassLimits {
+    pub classes: Option<usize>,             // -XX:MaxLoadedClasses
+    pub constant_pool_bytes: Option<usize>, // -XX:MaxConstantPoolSize, as stored in the class files
 }
 
 impl ClassLibrary {
-    pub fn new(class_loader: BootstrapClassLoader) -> Self {
+    pub fn new(
+        class_loader: BootstrapClassLoader,
+        check_module_access: bool,
+        enable_preview: bool,
+        limits: ClassLimits,
+        verifier: Option<Verifier>,
+    ) -> Self {
         Self {
-            dispatch_tables: NativeList::alloc(1000, 8),
-            static_attributes: NativeList::alloc(4000, 8),
-            classes: AppendList::new(),
-            name_mappings: RefCell::new(HashMap::new()),
+            dispatch_tables: NativeList::alloc(DISPATCH_TABLES_LENGTH, 8),
+            static_attributes: NativeList::alloc(STATICS_LENGTH, 8),
+            tails: Mutex::new(Tails::default()),
+            classes: PublishedList::new(),
+            name_mappings: RwLock::new(HashMap::new()),
+            subtype_cache: RwLock::new(HashSet::new()),
             class_loader,
-            dispatch_table_tail: RefCell::new(0),
-            statics_tail: RefCell::new(0),
+            modules: RwLock::new(HashMap::new()),
+            package_modules: RwLock::new(HashMap::new()),
+            check_module_access,
+            enable_preview,
+            recorded_class_files: Mutex::new(None),
+            restored_classes: Mutex::new(HashMap::new()),
+            defined_classes: Mutex::new(HashMap::new()),
+            loading: Mutex::new(HashSet::new()),
+            initializing: Mutex::new(HashMap::new()),
+            initialized: Condvar::new(),
+            limits,
+            constant_pool_bytes: AtomicUsize::new(0),
+            verifier: verifier.map(Mutex::new),
+            mirrors: RwLock::new(MirrorTable::default()),
+            loaders: RwLock::new(LoaderTable::default()),
         }
     }
 
-    pub fn resolve_by_name(
+    pub fn try_resolve_by_name(
         &self,
         name: &str,
         methods: &MethodTable,
         heap: &mut Heap,
         stack: StackPointer,
-    ) -> &Class {
-        let index = self.name_mappings.borrow().get(name).map(|i| *i);
-        if let Some(index) = index {
-            &self.classes[index]
-        } else {
-            let index = self.load(name, heap, methods, stack).unwrap();
-            self.resolve(index)
+    ) -> Result<&Class, ClassResolveError> {
+        self.try_resolve_in(ClassLoaderId::Bootstrap, name, methods, heap, stack)
+    }
+
+    /// Resolves a class name that occurs in a class defined by the loader (JVMS §5.3). The bootstrap loader loads
+    /// the class itself, a user-defined loader is asked with loadClass unless it has already loaded the class.
+    pub fn try_resolve_in(
+        &self,
+        loader: ClassLoaderId,
+        name: &str,
+        methods: &MethodTable,
+        heap: &mut Heap,
+        stack: StackPointer,
+    ) -> Result<&Class, ClassResolveError> {
+        if let Some(index) = self.find_loaded_by(loader, name) {
+            return Ok(self.resolve(index));
         }
+        let object = self.loaders.read().unwrap().object(loader);
+        let index = match object {
+            None => self.load(ClassLoaderId::Bootstrap, name, heap, methods, stack)?,
+            Some(object) => {
+                let index = self.call_load_class(object, name, heap, methods, stack)?;
+                // The loader is an initiating loader of the class now, so it isn't asked again
+                self.name_mappings
+                    .write()
+                    .unwrap()
+                    .insert((loader, name.to_string()), index.0);
+                index
+            }
+        };
+        Ok(self.resolve(index))
+    }
+
+    /// Returns the class without loading it, i.e. None if the bootstrap loader hasn't loaded it yet
+    pub fn find_loaded(&self, name: &str) -> Option<ClassIndex> {
+        self.find_loaded_by(ClassLoaderId::Bootstrap, name)
+    }
+
+    /// Returns the class that the loader has defined or initiated the loading of (ClassLoader.findLoadedClass)
+    pub fn find_loaded_by(&self, loader: ClassLoaderId, name: &str) -> Option<ClassIndex> {
+        self.name_mappings
+            .read()
+            .unwrap()
+            .get(&(loader, name.to_string()))
+            .map(|index| ClassIndex(*index))
+    }
+
+    /// The user-defined class loaders, see ClassLoaderId
+    pub fn loaders(&self) -> &RwLock<LoaderTable> {
+        &self.loaders
     }
 
     pub fn resolve(&self, index: ClassIndex) -> &Class {
         &self.classes[index.0]
     }
 
-    /// This function should only be called by a class parser
+    /// The instances of java/lang/Class and java/lang/reflect that have been handed out, see model::mirror
+    pub fn mirrors(&self) -> &RwLock<MirrorTable> {
+        &self.mirrors
+    }
+
+    pub fn class_loader(&self) -> &BootstrapClassLoader {
+        &self.class_loader
+    }
+
+    /// Defines the modules on the classpath. Classes of packages that don't belong to one of them are in the unnamed
+    /// module. Must be called before any class of the modules is loaded.
+    pub fn define_modules(&self) -> Result<(), ClassResolveError> {
+        let exploded_modules = self
+            .class_loader
+            .find_modules()
+            .map_err(|err| ClassResolveError::ClassNotFound("module-info".to_string(), err))?;
+        for exploded_module in exploded_modules {
+            let (_file, data, _constant_pool) = class_parser::parse(&exploded_module.module_info)?;
+            let descriptor = data
+                .module
+                .ok_or_else(|| ClassResolveError::NotAModule("module-info".to_string()))?;
+            let mut packages = exploded_module.packages;
+            packages.extend(descriptor.packages.iter().cloned());
+
+            log::info!("Defining module {}", descriptor.name);
+            let module = Arc::new(Module::new(descriptor, packages));
+            for package in module.packages() {
+                self.package_modules
+                    .write()
+                    .unwrap()
+                    .insert(package.clone(), module.clone());
+            }
+            self.modules
+                .write()
+                .unwrap()
+                .insert(module.name().to_string(), module);
+        }
+        Ok(())
+    }
+
+    pub fn resolve_module(&self, name: &str) -> Option<Arc<Module>> {
+        self.modules.read().unwrap().get(name).cloned()
+    }
+
+    /// Returns None for packages in the unnamed module
+    pub fn module_of_package(&self, package: &str) -> Option<Arc<Module>> {
+        self.package_modules.read().unwrap().get(package).cloned()
+    }
+
+    /// Whether readability and exports of modules are enforced when resolving members
+    pub fn checks_module_access(&self) -> bool {
+        self.check_module_access
+    }
+
+    /// Keeps the class files of all classes that are loaded afterwards, so that they can be stored in a checkpoint
+    pub fn record_class_files(&self) {
+        *self.recorded_class_files.lock().unwrap() = Some(HashMap::new());
+    }
+
+    pub fn recorded_class_file(&self, name: &str) -> Option<Vec<u8>> {
+        self.recorded_class_files
+            .lock()
+            .unwrap()
+            .as_ref()?
+            .get(name)
+            .cloned()
+    }
+
+    /// Classes of the checkpoint are loaded from the checkpoint instead of the classpath. Must be called before any
+    /// class is loaded.
+    pub fn restore(&self, checkpoint: Checkpoint) {
+        *self.restored_classes.lock().unwrap() = checkpoint.into_classes();
+    }
+
+    /// The total size of the constant pools of the loaded classes in bytes
+    pub fn constant_pool_bytes(&self) -> usize {
+        self.constant_pool_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Writes the verification cache, see verifier::cache
+    pub fn save_verification_cache(&self) {
+        if let Some(verifier) = &self.verifier {
+            verifier.lock().unwrap().save_cache();
+        }
+    }
+
+    pub fn class_count(&self) -> usize {
+        self.classes.len()
+    }
+
+    /// The objects that are referenced by static fields. The statics of all classes live in one region outside of
+    /// the heap, which is scanned with the reference maps of the classes.
+    pub fn static_roots(&self) -> Vec<HeapIndex> {
+        self.classes
+            .iter()
+            .flat_map(|class| class.static_references())
+            .collect()
+    }
+
+    /// Checks whether source is assignable to target, i.e. whether target is source itself, one of its superclasses
+    /// or one of the interfaces it implements (directly or indirectly)
+    pub fn is_subtype_of(&self, source: ClassIndex, target: ClassIndex) -> bool {
+        if source == target
+            || self
+                .subtype_cache
+                .read()
+                .unwrap()
+                .contains(&(source, target))
+        {
+            return true;
+        }
+
+        let mut pending = vec![source];
+        let mut visited = HashSet::new();
+        while let Some(current) = pending.pop() {
+            if current == target {
+                // Only positive results are cached, the hierarchy of a class never changes after it has been loaded
+                self.subtype_cache.write().unwrap().insert((source, target));
+                return true;
+            }
+            if !visited.insert(current) {
+                continue;
+            }
+            let class = self.resolve(current);
+            pending.extend(class.super_class());
+            pending.extend_from_slice(class.interfaces());
+        }
+        false
+    }
+
+    /// Loads the class from the given class file instead of searching it with the class loader. The loader becomes
+    /// the defining loader of the class (ClassLoader.defineClass).
+    pub fn define(
+        &self,
+        loader: ClassLoaderId,
+        name: &str,
+        class_file: Vec<u8>,
+        heap: &mut Heap,
+        methods: &MethodTable,
+        stack: StackPointer,
+    ) -> Result<ClassIndex, ClassResolveError> {
+        // Like in HotSpot, only the bootstrap loader may define the classes of the platform
+        if loader != ClassLoaderId::Bootstrap && name.starts_with("java/") {
+            return Err(ClassResolveError::ProhibitedPackage(name.to_string()));
+        }
+        // The class file stays in defined_classes until the class has been published, so that a concurrent definition
+        // of the same class fails either here or with the check of the loaded classes
+        let key = (loader, name.to_string());
+        {
+            let mut defined_classes = self.defined_classes.lock().unwrap();
+            if self.find_loaded_by(loader, name).is_some() || defined_classes.contains_key(&key) {
+                return Err(ClassResolveError::AlreadyDefined(name.to_string()));
+            }
+            defined_classes.insert(key.clone(), class_file);
+        }
+        let result = self.load(loader, name, heap, methods, stack);
+        self.defined_classes.lock().unwrap().remove(&key);
+        result
+    }
+
+    /// This function should only be called by a class parser. Classes of user-defined loaders can only be defined.
+    /// Returns the class if it has been published since the caller looked it up.
     pub fn load(
         &self,
+        loader: ClassLoaderId,
+        name: &str,
+        heap: &mut Heap,
+        methods: &MethodTable,
+        stack: StackPointer,
+    ) -> Result<ClassIndex, ClassResolveError> {
+        let key = (loader, name.to_string());
+        {
+            let mut loading = self.loading.lock().unwrap();
+            if let Some(index) = self.find_loaded_by(loader, name) {
+                return Ok(index);
+            }
+            // A class that is loaded again while it is being loaded is its own supertype (JVMS §5.3.5)
+            if !loading.insert(key.clone()) {
+                return Err(ClassResolveError::ClassCircularity(name.to_string()));
+            }
+        }
+
+        class_graph::start_loading(name);
+        let result = self.load_and_link(loader, name, heap, methods, stack);
+        class_graph::finish_loading(result.as_ref().ok().copied());
+        self.loading.lock().unwrap().remove(&key);
+        result
+    }
+
+    fn load_and_link(
+        &self,
+        loader: ClassLoaderId,
         name: &str,
         heap: &mut Heap,
         methods: &MethodTable,
         stack: StackPointer,
     ) -> Result<ClassIndex, ClassResolveError> {
         log::info!("Loading class {}", name);
-        let bytes = self.class_loader.load_class(name.to_string());
-        let (_file, data, constant_pool) = class_parser::parse(&bytes)?;
+        let module = match loader {
+            ClassLoaderId::Bootstrap => {
+                self.module_of_package(RuntimePackage::of_class(name, loader).name())
+            }
+            // The modules on the classpath belong to the bootstrap loader
+            ClassLoaderId::User(_) => None,
+        };
+        let snapshot = match loader {
+            ClassLoaderId::Bootstrap => self.restored_classes.lock().unwrap().remove(name),
+            ClassLoaderId::User(_) => None,
+        };
+        let (bytes, statics, compiled_methods) = match snapshot {
+            Some(snapshot) => (
+                snapshot.class_file,
+                snapshot.statics,
+                snapshot.compiled_methods,
+            ),
+            None => match self
+                .defined_classes
+                .lock()
+                .unwrap()
+                .get(&(loader, name.to_string()))
+                .cloned()
+            {
+                Some(class_file) => (class_file, None, Vec::new()),
+                None if loader != ClassLoaderId::Bootstrap => {
+                    return Err(ClassResolveError::ClassNotFound(
+                        name.to_string(),
+                        std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            "user-defined class loaders can only define classes",
+                        ),
+                    ))
+                }
+                None => (
+                    self.class_loader
+                        .load_class(name.to_string(), |patched_module| match &module {
+                            Some(module) => module.name() == patched_module,
+                            // The class library isn't modular, so patches of modules that aren't defined (e.g.
+                            // java.base) apply to the classes of the unnamed module
+                            None => !self.modules.read().unwrap().contains_key(patched_module),
+                        })
+                        .map_err(|err| ClassResolveError::ClassNotFound(name.to_string(), err))?,
+                    None,
+                    Vec::new(),
+                ),
+            },
+        };
+        let (file, data, constant_pool) = class_parser::parse(&bytes)?;
+        if data.module.is_some() {
+            // module-info.class only describes a module, see define_modules
+            return Err(ClassResolveError::NotAClass(name.to_string()));
+        }
+        if file.uses_preview_features() && !self.enable_preview {
+            return Err(ClassResolveError::PreviewNotEnabled {
+                class: name.to_string(),
+                major_version: file.major_version(),
+            });
+        }
+        let reservation = self.check_limits(name, &file)?;
+        if let Some(verifier) = &self.verifier {
+            verifier.lock().unwrap().verify_class(name, &bytes, &data)?;
+        }
 
+        let this_class = constant_pool.resolve_type(data.this_class)?.to_string();
+        // The bootstrap loader loads Object by the name of its file (classes/Object)
+        if loader != ClassLoaderId::Bootstrap && this_class != name {
+            return Err(ClassResolveError::WrongName {
+                class: name.to_string(),
+                actual: this_class,
+            });
+        }
+
+        // The supertypes are resolved by the defining loader of the class
         let super_class = if data.super_class.is_valid() {
             let name = constant_pool.resolve_type(data.super_class)?;
-            Some(self.resolve_by_name(name, methods, heap, stack))
+            Some(self.try_resolve_in(loader, name, methods, heap, stack)?)
         } else {
             None
         };
 
-        // The following code for creating and updating the class must not be interrupted by an access to the ClassLibrary
-        // or the indices will be wrong
+        let mut interfaces = Vec::with_capacity(data.interfaces.len());
+        for interface in &data.interfaces {
+            let name = constant_pool.resolve_type(*interface)?;
+            interfaces.push(
+                self.try_resolve_in(loader, name, methods, heap, stack)?
+                    .index(),
+            );
+        }
+
+        let package = RuntimePackage::of_class(&this_class, loader);
+        let is_public = data.visibility == Visibility::Public;
+        for supertype in super_class
+            .iter()
+            .map(|class| class.index())
+            .chain(interfaces.iter().copied())
+        {
+            self.check_permitted_subclass(&this_class, is_public, &package, &module, supertype)?;
+        }
+
+        // The index, the static fields and the dispatch table are reserved under the lock of the tails, which is held
+        // until the class has been published. Class::new must not load classes, which would take the lock again.
+        let mut tails = self.tails.lock().unwrap();
         let index = self.classes.len();
         let statics_position = unsafe {
-            self.static_attributes
-                .get_pointer()
-                .offset(*self.statics_tail.borrow() as isize)
+            std::ptr::slice_from_raw_parts_mut(
+                self.static_attributes.get_pointer().add(tails.statics),
+                self.static_attributes.capacity() - tails.statics,
+            )
         };
         let dispatch_table_position = unsafe {
-            self.dispatch_tables
-                .get_pointer()
-                .offset(*self.dispatch_table_tail.borrow() as isize)
+            std::ptr::slice_from_raw_parts_mut(
+                self.dispatch_tables
+                    .get_pointer()
+                    .add(tails.dispatch_tables),
+                self.dispatch_tables.capacity() - tails.dispatch_tables,
+            )
         };
-        let (class, statics_length, dispatch_table_length) = Class::new(
-            data,
-            constant_pool,
-            ClassIndex(index),
-            super_class,
-            methods,
-            statics_position,
-            dispatch_table_position,
-        )?;
-        *self.statics_tail.borrow_mut() += statics_length;
-        *self.dispatch_table_tail.borrow_mut() += dispatch_table_length;
+        let (class, statics_length, dispatch_table_length) = unsafe {
+            Class::new(
+                data,
+                constant_pool,
+                ClassIndex(index),
+                loader,
+                super_class,
+                interfaces,
+                self,
+                methods,
+                statics_position,
+                dispatch_table_position,
+            )
+        }
+        .map_err(|err| match err {
+            ClassCreationError::TableFull { resource, capacity } => {
+                log::warn!("Refusing to load {}: the {} are full", name, resource);
+                ClassResolveError::LimitExceeded {
+                    class: name.to_string(),
+                    resource,
+                    limit: capacity,
+                }
+            }
+            err => err.into(),
+        })?;
+        tails.statics += statics_length;
+        tails.dispatch_tables += dispatch_table_length;
+        let class_name = class.name()?.to_string();
+        if let Some(class_files) = self.recorded_class_files.lock().unwrap().as_mut() {
+            class_files.insert(class_name.clone(), bytes);
+        }
+        // The name is published last, so that it never maps to a class that isn't in the list yet
+        let published = self.classes.push(class);
+        debug_assert_eq!(published, index);
         self.name_mappings
-            .borrow_mut()
-            .insert(class.name()?.to_string(), index);
-        self.classes.push(class);
+            .write()
+            .unwrap()
+            .insert((loader, class_name), index);
+        drop(tails);
+        reservation.keep();
+        // The class file has been verified before the class was created and Class::new has prepared the static fields
+        self.classes[index].set_init_state(InitState::Linked);
 
-        self.classes[index].bootstrap(methods, self, heap, stack)?;
+        // The static fields of a checkpoint hold the values after the initialization
+        if let Some(statics) = &statics {
+            if self.classes[index].restore_statics(statics) {
+                self.classes[index].set_init_state(InitState::Initialized);
+            }
+        }
+        for (name, descriptor) in compiled_methods {
+            self.compile_restored_method(ClassIndex(index), &name, &descriptor, methods);
+        }
 
         Ok(ClassIndex(index))
     }
+
+    /// Initializes the class on its first active use (JVMS §5.5): the execution of new, getstatic, putstatic or
+    /// invokestatic, or a call of one of its static methods by the host. The superclass and the superinterfaces with
+    /// default methods are initialized first. A class that is being initialized by the current thread (e.g. its static
+    /// initializer uses the class itself) is used right away, one that another thread initializes is waited for. An
+    /// exception of the static initializer is wrapped in an ExceptionInInitializerError unless it is an Error, later
+    /// uses of the class fail with NoClassDefFoundError.
+    pub fn initialize(
+        &self,
+        class: ClassIndex,
+        methods: &MethodTable,
+        heap: &mut Heap,
+        stack: StackPointer,
+    ) -> Result<(), ExecutionError> {
+        if !self.begin_initialization(class)? {
+            return Ok(());
+        }
+        let data = self.resolve(class);
+        log::debug!("Initializing class {}", data.name()?);
+        let result = self
+            .initialize_supertypes(data, methods, heap, stack)
+            .and_then(|()| {
+                class_graph::start_initialization(class);
+                let result = data.bootstrap(methods, self, heap, stack).map_err(|err| {
+                    interpreter::exception_in_initializer(err, heap, self, methods, stack)
+                });
+                class_graph::finish_initialization(class);
+                result
+            });
+        self.finish_initialization(class, result.is_ok());
+        result
+    }
+
+    /// Claims the initialization of the class for the current thread (JVMS §5.5, steps 1-6). Returns false if there
+    /// is nothing left to do, because the class has been initialized or is being initialized by the current thread.
+    /// Waits while another thread initializes the class.
+    fn begin_initialization(&self, class: ClassIndex) -> Result<bool, ExecutionError> {
+        let data = self.resolve(class);
+        if data.init_state() == InitState::Initialized {
+            return Ok(false);
+        }
+        let thread = std::thread::current().id();
+        let mut initializing = self.initializing.lock().unwrap();
+        loop {
+            match data.init_state() {
+                InitState::Initialized => return Ok(false),
+                InitState::Erroneous => {
+                    return Err(ExecutionError::ErroneousClass(data.name()?.to_string()))
+                }
+                InitState::Initializing if initializing.get(&class) == Some(&thread) => {
+                    return Ok(false)
+                }
+                InitState::Initializing => {
+                    initializing = self.initialized.wait(initializing).unwrap();
+                }
+                InitState::Loaded | InitState::Linked => {
+                    data.set_init_state(InitState::Initializing);
+                    initializing.insert(class, thread);
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    /// Marks the class as initialized or erroneous and wakes up the threads that wait for it (JVMS §5.5, steps 10-12)
+    fn finish_initialization(&self, class: ClassIndex, succeeded: bool) {
+        let mut initializing = self.initializing.lock().unwrap();
+        self.resolve(class).set_init_state(if succeeded {
+            InitState::Initialized
+        } else {
+            InitState::Erroneous
+        });
+        initializing.remove(&class);
+        self.initialized.notify_all();
+    }
+
+    /// Interfaces don't initialize their superinterfaces, classes initialize their superclass and every superinterface
+    /// that declares default methods, each interface after its own superinterfaces (JVMS §5.5, step 7)
+    fn initialize_supertypes(
+        &self,
+        class: &Class,
+        methods: &MethodTable,
+        heap: &mut Heap,
+        stack: StackPointer,
+    ) -> Result<(), ExecutionError> {
+        if class.is_interface() {
+            return Ok(());
+        }
+        if let Some(super_class) = class.super_class() {
+            self.initialize(super_class, methods, heap, stack)?;
+        }
+        for interface in class.interfaces() {
+            self.initialize_interface_with_defaults(*interface, methods, heap, stack)?;
+        }
+        Ok(())
+    }
+
+    fn initialize_interface_with_defaults(
+        &self,
+        interface: ClassIndex,
+        methods: &MethodTable,
+        heap: &mut Heap,
+        stack: StackPointer,
+    ) -> Result<(), ExecutionError> {
+        let data = self.resolve(interface);
+        for super_interface in data.interfaces() {
+            self.initialize_interface_with_defaults(*super_interface, methods, heap, stack)?;
+        }
+        if data.declares_default_methods() {
+            self.initialize(interface, methods, heap, stack)?;
+        }
+        Ok(())
+    }
+
+    /// Asks a user-defined class loader for a class (ClassLoader.loadClass), which usually delegates to its parent
+    /// first and defines the class itself otherwise
+    fn call_load_class(
+        &self,
+        loader: HeapIndex,
+        name: &str,
+        heap: &mut Heap,
+        methods: &MethodTable,
+        stack: StackPointer,
+    ) -> Result<ClassIndex, ClassResolveError> {
+        let loader_failed = |error: ExecutionError| ClassResolveError::LoaderFailed {
+            class: name.to_string(),
+            error,
+        };
+        let (method_name, descriptor) = LOAD_CLASS_METHOD;
+        let method = self
+            .resolve(heap.resolve(loader).class())
+            .find_own_method(method_name, descriptor)
+            .ok_or_else(|| ClassResolveError::NotAClassLoader(name.to_string()))?;
+        // loadClass takes the binary name
+        let binary_name = string::new_string(&name.replace('/', "."), self, heap, methods, stack)
+            .map_err(|err| loader_failed(err.into()))?;
+        let mut arguments =
+            StackFrame::prepare(stack, 0, 0, 2).map_err(|err| loader_failed(err.into()))?;
+        arguments.push_value(loader.into(), JvmType::Reference);
+        arguments.push_value(binary_name.into(), JvmType::Reference);
+        let mirror =
+            interpreter::call_method(method, arguments.get_stack_for_call(), heap, self, methods)
+                .map_err(loader_failed)?;
+        let class = self
+            .mirrors
+            .read()
+            .unwrap()
+            .reflected_class(mirror.reference().to_heap_index())
+            .ok_or_else(|| ClassResolveError::NoClassReturned(name.to_string()))?;
+        // The loader may have delegated to another loader, but it must return the requested class
+        let actual = self.resolve(class).name()?;
+        if actual != name {
+            return Err(ClassResolveError::WrongName {
+                class: name.to_string(),
+                actual: actual.to_string(),
+            });
+        }
+        Ok(class)
+    }
+
+    /// Fails if the direct supertype is sealed and doesn't permit the class as its subclass (JVMS §5.3.5). A sealed
+    /// class can only be extended from its own module, and from its own package if the subclass isn't public.
+    fn check_permitted_subclass(
+        &self,
+        name: &str,
+        is_public: bool,
+        package: &RuntimePackage,
+        module: &Option<Arc<Module>>,
+        supertype: ClassIndex,
+    ) -> Result<(), ClassResolveError> {
+        let supertype = self.resolve(supertype);
+        let permitted = supertype.permitted_subclasses();
+        if permitted.is_empty() {
+            return Ok(());
+        }
+        let same_module = match (module, supertype.module()) {
+            (Some(module), Some(other)) => Arc::ptr_eq(module, other),
+            (None, None) => true,
+            _ => false,
+        };
+        if !same_module
+            || (!is_public && supertype.package() != package)
+            || !permitted.iter().any(|permitted| permitted == name)
+        {
+            return Err(ClassResolveError::NotPermittedSubclass {
+                class: name.to_string(),
+                sealed: supertype.name()?.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Reserves the metadata of the class, fails if it would exceed one of the limits. The reservation is released
+    /// unless it is kept once the class has been published.
+    fn check_limits(
+        &self,
+        name: &str,
+        file: &ClassFile,
+    ) -> Result<ConstantPoolReservation<'_>, ClassResolveError> {
+        let exceeded = |resource, limit, used| {
+            log::warn!(
+                "Refusing to load {}: the limit of {} {} has been reached ({} used)",
+                name,
+                limit,
+                resource,
+                used
+            );
+            ClassResolveError::LimitExceeded {
+                class: name.to_string(),
+                resource,
+                limit,
+            }
+        };
+        if let Some(limit) = self.limits.classes {
+            if self.class_count() >= limit {
+                return Err(exceeded("loaded classes", limit, self.class_count()));
+            }
+        }
+        let length = file.constant_pool_length();
+        let reserved =
+            self.constant_pool_bytes
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                    match self.limits.constant_pool_bytes {
+                        Some(limit) if used + length > limit => None,
+                        _ => Some(used + length),
+                    }
+                });
+        match (reserved, self.limits.constant_pool_bytes) {
+            (Err(used), Some(limit)) => Err(exceeded("constant pool bytes", limit, used)),
+            _ => Ok(ConstantPoolReservation {
+                used: &self.constant_pool_bytes,
+                length,
+            }),
+        }
+    }
+
+    /// Compiles a method that had already been compiled when the restored checkpoint was taken
+    fn compile_restored_method(
+        &self,
+        class: ClassIndex,
+        name: &str,
+        descriptor: &str,
+        methods: &MethodTable,
+    ) {
+        if !matches!(
+            methods.execution_mode(),
+            ExecutionMode::Mixed | ExecutionMode::Compiled
+        ) {
+            return;
+        }
+        let method = match self.resolve(class).find_own_method(name, descriptor) {
+            Some(method) => method,
+            None => return,
+        };
+        if methods.should_attempt_compilation(method) {
+            match jit::compile_method(method, self, methods) {
+                Ok(implementation) => methods.update_method(method, implementation),
+                Err(err) => log::warn!("Failed to compile the restored method {}: {}", name, err),
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The constant pool bytes of a class that is being loaded, which are released again if the loading fails
+struct ConstantPoolReservation<'a> {
+    used: &'a AtomicUsize,
+    length: usize,
+}
+
+impl ConstantPoolReservation<'_> {
+    /// Keeps the bytes reserved, because the class has been loaded
+    fn keep(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for ConstantPoolReservation<'_> {
+    fn drop(&mut self) {
+        self.used.fetch_sub(self.length, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 pub struct ClassIndex(pub usize);
 
 #[derive(thiserror::Error, Debug)]
 pub enum ClassResolveError {
+    #[error("class {0} not found: {1}")]
+    ClassNotFound(String, std::io::Error),
+
+    #[error("{0} describes a module and is not a class")]
+    NotAClass(String),
+
+    #[error("class {0} has already been loaded")]
+    AlreadyDefined(String),
+
+    #[error("class {0} is its own superclass or superinterface")]
+    ClassCircularity(String),
+
+    #[error("{0} does not describe a module")]
+    NotAModule(String),
+
+    #[error("prohibited package name of {0}, only the bootstrap class loader may define classes in java/")]
+    ProhibitedPackage(String),
+
+    #[error("{class} (wrong name: {actual})")]
+    WrongName { class: String, actual: String },
+
+    #[error("the class loader of {0} is not a java/lang/ClassLoader")]
+    NotAClassLoader(String),
+
+    #[error("the class loader returned no class for {0}")]
+    NoClassReturned(String),
+
+    #[error("the class loader failed to load {class}: {error}")]
+    LoaderFailed {
+        class: String,
+        error: ExecutionError,
+    },
+
+    #[error("class {class} cannot inherit from the sealed class {sealed}")]
+    NotPermittedSubclass { class: String, sealed: String },
+
+    #[error("{class} (class file version {major_version}.65535) depends on preview features, which are not enabled (try --enable-preview)")]
+    PreviewNotEnabled { class: String, major_version: u16 },
+
+    #[error("loading {class} would exceed the limit of {limit} {resource}")]
+    LimitExceeded {
+        class: String,
+        resource: &'static str,
+        limit: usize,
+    },
+
     #[error(transparent)]
     ConstantPool(#[from] ConstantPoolError),
 
     #[error(transparent)]
     ClassParsing(#[from] ParsingError),
 
+    #[error(transparent)]
+    Verification(#[from] VerifyError),
+
     #[error(transparent)]
     ClassCreation(#[from] ClassCreationError),
 
     #[error(transparent)]
     ClassInitialization(#[from] ExecutionError),
 }
+
+impl ClassResolveError {
+    /// The Java error that a complete VM would throw in place of this error, None if there is no specific one
+    pub fn java_exception_class(&self) -> Option<&'static str> {
+        match self {
+            Self::ClassCircularity(_) => Some("java/lang/ClassCircularityError"),
+            // The class loader has failed to find the class (JVMS §5.3.1 and §5.3.2)
+            Self::ClassNotFound(..)
+            | Self::WrongName { .. }
+            | Self::NoClassReturned(_)
+            | Self::LoaderFailed { .. } => Some("java/lang/NoClassDefFoundError"),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        class_loader::BootstrapClassLoader,
+        class_parser::ParsingError,
+        jit::compile_command::CompilationFilter,
+        model::{
+            class::InitState,
+            class_file::{MAX_MAJOR_VERSION, PREVIEW_MINOR_VERSION},
+            heap::Heap,
+            method::MethodTable,
+            package::ClassLoaderId,
+            stack::StackPointer,
+        },
+        options::{ExecutionMode, VmOptions},
+        testing,
+    };
+
+    use super::{ClassIndex, ClassLibrary, ClassResolveError};
+
+    /// The parts of a VM like Jvm::new creates them, so that the class library can be shared with other threads
+    fn parts() -> (ClassLibrary, Heap, MethodTable, StackPointer) {
+        parts_with_call_table(100)
+    }
+
+    fn parts_with_call_table(length: usize) -> (ClassLibrary, Heap, MethodTable, StackPointer) {
+        let options = VmOptions::default();
+        let class_loader = BootstrapClassLoader::new(
+            vec![PathBuf::from(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/classes"
+            ))],
+            Vec::new(),
+            Vec::new(),
+            None,
+            true,
+        );
+        let classes = ClassLibrary::new(class_loader, false, false, options.class_limits, None);
+        let heap = Heap::new(options.heap_size, options.gc);
+        let methods = MethodTable::new(
+            length,
+            ExecutionMode::Interpreted,
+            options.compilation_thresholds,
+            CompilationFilter::new(Vec::new()),
+            None,
+        );
+        (classes, heap, methods, StackPointer::with_size(20000))
+    }
+
+    #[test]
+    fn other_threads_only_see_published_classes() {
+        let (classes, mut heap, methods, stack) = parts();
+        let loaded = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let reader = scope.spawn(|| loop {
+                let finished = loaded.load(Ordering::Acquire);
+                for index in 0..classes.class_count() {
+                    assert!(classes.resolve(ClassIndex(index)).name().is_ok());
+                }
+                if let Some(square) = classes.find_loaded("Square") {
+                    // The superclass is published before its subclass
+                    let shape = classes.find_loaded("Shape").unwrap();
+                    assert!(classes.is_subtype_of(square, shape));
+                    return;
+                }
+                assert!(!finished, "Square has been loaded, but isn't visible");
+            });
+            let result = classes
+                .load(
+                    ClassLoaderId::Bootstrap,
+                    "classes/Object",
+                    &mut heap,
+                    &methods,
+                    stack,
+                )
+                .and_then(|_| classes.try_resolve_by_name("Square", &methods, &mut heap, stack));
+            // Set before unwrapping, so that the reader stops if the loading failed
+            loaded.store(true, Ordering::Release);
+            result.unwrap();
+            reader.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn loading_a_published_class_again_returns_it() {
+        let (classes, mut heap, methods, stack) = parts();
+        classes
+            .load(
+                ClassLoaderId::Bootstrap,
+                "classes/Object",
+                &mut heap,
+                &methods,
+                stack,
+            )
+            .unwrap();
+        let first = classes
+            .load(
+                ClassLoaderId::Bootstrap,
+                "Square",
+                &mut heap,
+                &methods,
+                stack,
+            )
+            .unwrap();
+        let class_count = classes.class_count();
+        let second = classes
+            .load(
+                ClassLoaderId::Bootstrap,
+                "Square",
+                &mut heap,
+                &methods,
+                stack,
+            )
+            .unwrap();
+        assert_eq!(second, first);
+        assert_eq!(classes.class_count(), class_count);
+    }
+
+    #[test]
+    fn class_that_is_its_own_superclass_is_rejected() {
+        let (classes, mut heap, methods, stack) = parts();
+        classes
+            .load(
+                ClassLoaderId::Bootstrap,
+                "classes/Object",
+                &mut heap,
+                &methods,
+                stack,
+            )
+            .unwrap();
+        let constant_pool_bytes = classes.constant_pool_bytes();
+        // The failed loads are no longer in the set of the classes being loaded, so the second attempt fails the same way
+        for _ in 0..2 {
+            match classes.try_resolve_by_name("Circular", &methods, &mut heap, stack) {
+                Err(ClassResolveError::ClassCircularity(name)) => assert_eq!(name, "Circular"),
+                Err(err) => panic!("unexpected error {}", err),
+                Ok(_) => panic!("Circular has been loaded"),
+            }
+        }
+        assert_eq!(classes.find_loaded("Circular"), None);
+        assert_eq!(classes.find_loaded("CircularBase"), None);
+        // The constant pools of the failed loads have been released
+        assert_eq!(classes.constant_pool_bytes(), constant_pool_bytes);
+    }
+
+    #[test]
+    fn class_that_does_not_fit_into_the_call_table_is_rejected() {
+        let (classes, mut heap, methods, stack) = parts_with_call_table(16);
+        classes
+            .load(
+                ClassLoaderId::Bootstrap,
+                "classes/Object",
+                &mut heap,
+                &methods,
+                stack,
+            )
+            .unwrap();
+        let method_count = methods.method_count();
+        let constant_pool_bytes = classes.constant_pool_bytes();
+        match classes.try_resolve_by_name("Dispatch", &methods, &mut heap, stack) {
+            Err(ClassResolveError::LimitExceeded {
+                class,
+                resource,
+                limit,
+            }) => {
+                assert_eq!(class, "Dispatch");
+                assert_eq!(resource, "methods");
+                assert_eq!(limit, 16);
+            }
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("Dispatch has been loaded"),
+        }
+        // Nothing of the rejected class has been added
+        assert_eq!(methods.method_count(), method_count);
+        assert_eq!(classes.constant_pool_bytes(), constant_pool_bytes);
+        assert_eq!(classes.find_loaded("Dispatch"), None);
+    }
+
+    /// Loads Square, which has no static initializer, so that its initialization can be driven by the test
+    fn load_square() -> (ClassLibrary, ClassIndex) {
+        let (classes, mut heap, methods, stack) = parts();
+        classes
+            .load(
+                ClassLoaderId::Bootstrap,
+                "classes/Object",
+                &mut heap,
+                &methods,
+                stack,
+            )
+            .unwrap();
+        let square = classes
+            .try_resolve_by_name("Square", &methods, &mut heap, stack)
+            .unwrap()
+            .index();
+        (classes, square)
+    }
+
+    #[test]
+    fn other_threads_wait_until_the_class_has_been_initialized() {
+        let (classes, square) = load_square();
+        assert_eq!(classes.resolve(square).init_state(), InitState::Linked);
+        assert!(classes.begin_initialization(square).unwrap());
+        assert_eq!(
+            classes.resolve(square).init_state(),
+            InitState::Initializing
+        );
+        // The initializing thread uses the class right away, e.g. in the static initializer
+        assert!(!classes.begin_initialization(square).unwrap());
+
+        let finished = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                let initialize = classes.begin_initialization(square).unwrap();
+                (initialize, finished.load(Ordering::Acquire))
+            });
+            // Gives the other thread the time to start waiting
+            thread::sleep(Duration::from_millis(50));
+            finished.store(true, Ordering::Release);
+            classes.finish_initialization(square, true);
+            let (initialize, finished) = waiter.join().unwrap();
+            assert!(!initialize);
+            assert!(
+                finished,
+                "the other thread hasn't waited for the initialization"
+            );
+        });
+        assert_eq!(classes.resolve(square).init_state(), InitState::Initialized);
+    }
+
+    #[test]
+    fn class_whose_initialization_has_failed_is_erroneous() {
+        let (classes, square) = load_square();
+        assert!(classes.begin_initialization(square).unwrap());
+        classes.finish_initialization(square, false);
+        assert_eq!(classes.resolve(square).init_state(), InitState::Erroneous);
+        // Every later use fails, in other threads as well
+        let result = thread::scope(|scope| {
+            scope
+                .spawn(|| classes.begin_initialization(square))
+                .join()
+                .unwrap()
+        });
+        match result {
+            Err(err) => assert_eq!(
+                err.java_exception_class(),
+                Some("java/lang/NoClassDefFoundError")
+            ),
+            Ok(_) => panic!("the erroneous class has been initialized again"),
+        }
+        assert!(classes.begin_initialization(square).is_err());
+    }
+
+    /// Square.class with another version
+    fn square_with_version(major_version: u16, minor_version: u16) -> Vec<u8> {
+        let mut class_file = include_bytes!("../../tests/classes/Square.class").to_vec();
+        class_file[4..6].copy_from_slice(&minor_version.to_be_bytes());
+        class_file[6..8].copy_from_slice(&major_version.to_be_bytes());
+        class_file
+    }
+
+    #[test]
+    fn classes_using_preview_features_are_only_loaded_if_enabled() {
+        let preview = square_with_version(MAX_MAJOR_VERSION, PREVIEW_MINOR_VERSION);
+        let mut jvm = testing::jvm();
+        match jvm.define_class("Square", preview.clone()) {
+            Err(ClassResolveError::PreviewNotEnabled {
+                class,
+                major_version,
+            }) => {
+                assert_eq!(class, "Square");
+                assert_eq!(major_version, MAX_MAJOR_VERSION);
+            }
+            result => panic!(
+                "the preview class has been loaded: {:?}",
+                result.map(|_| ())
+            ),
+        }
+
+        let mut jvm = testing::jvm_with(VmOptions {
+            enable_preview: true,
+            ..VmOptions::default()
+        });
+        jvm.define_class("Square", preview).unwrap();
+    }
+
+    #[test]
+    fn classes_of_unsupported_versions_are_not_loaded() {
+        let mut jvm = testing::jvm_with(VmOptions {
+            enable_preview: true,
+            ..VmOptions::default()
+        });
+        for (major_version, minor_version) in [
+            (MAX_MAJOR_VERSION + 1, 0),
+            (MAX_MAJOR_VERSION - 1, PREVIEW_MINOR_VERSION),
+        ] {
+            let class_file = square_with_version(major_version, minor_version);
+            let result = jvm.define_class("Square", class_file);
+            assert!(
+                matches!(
+                    result,
+                    Err(ClassResolveError::ClassParsing(
+                        ParsingError::UnsupportedVersion(_)
+                    ))
+                ),
+                "{}.{}",
+                major_version,
+                minor_version
+            );
+        }
+    }
+}
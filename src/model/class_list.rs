@@ -0,0 +1,33 @@
+use std::{fmt::Write, fs, io, path::Path};
+
+use super::class_library::{ClassIndex, ClassLibrary};
+
+// The class indices are assigned in the order in which the classes are loaded, which depends on the order in which the
+// program first touches them. Heap dumps, traces and the call graph refer to classes by their index, so two runs that
+// load the same classes in a slightly different order can't be compared line by line.
+// -XX:DumpLoadedClassList=<file> writes the names of the loaded classes in the order of their indices when the VM
+// exits. -XX:ReplayLoadedClassList=<file> loads the classes of such a list right after the start of the VM, so that
+// they get the same indices as in the recorded run. Classes that the list doesn't contain are loaded on demand
+// afterwards. Loading a class also initializes it in this VM, so the static initializers of the listed classes run
+// before the main method.
+
+/// Writes the names of the loaded classes in the order of their indices, one per line
+pub fn write(path: &Path, classes: &ClassLibrary) -> io::Result<()> {
+    let mut output = String::from("# Classes in the order in which they have been loaded\n");
+    for index in 0..classes.class_count() {
+        if let Ok(name) = classes.resolve(ClassIndex(index)).name() {
+            writeln!(output, "{}", name).unwrap();
+        }
+    }
+    fs::write(path, output)
+}
+
+/// Reads the class names of a list, skipping empty lines and comments
+pub fn read(path: &Path) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
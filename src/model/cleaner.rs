@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use crate::interpreter::{self, ExecutionError};
+
+use super::{
+    class_library::{ClassIndex, ClassLibrary},
+    heap::{Heap, HeapIndex, NULL_POINTER},
+    method::{MethodIndex, MethodTable},
+    stack::{StackFrame, StackPointer},
+    types::JvmType,
+    value::JvmValue,
+};
+
+// Cleanup of objects that have become phantom reachable, which java.lang.ref.Cleaner (and the older
+// jdk.internal.ref.Cleaner of direct buffers) use instead of finalize to release native resources.
+// Epsilon never reclaims memory, so reachability is determined by a separate marking pass whenever the cleaner runs.
+// The roots are the static fields, the interned strings, the mirrors and the Java stack. The stack is scanned
+// conservatively, i.e. every slot whose value is the index of an object keeps the object alive. The referents of
+// phantom references are not traced.
+// Java code only runs on one thread, so the cleaner thread is run by the VM itself: on every explicit GC request
+// (Runtime.gc) and whenever the embedder calls Jvm::run_cleaner. It clears the phantom references whose referent is
+// unreachable and cleans them if they are cleanables (i.e. runs their cleaning action like the cleaner thread of
+// the class library would) or enqueues them otherwise. Cleaning actions of the host (Jvm::register_cleanup) run in
+// the same pass.
+
+const PHANTOM_REFERENCE_CLASS: &str = "java/lang/ref/PhantomReference";
+const REFERENT_FIELD: &str = "referent";
+const CLEANABLE_CLASSES: [&str; 2] = [
+    "jdk/internal/ref/PhantomCleanable",
+    "jdk/internal/ref/Cleaner",
+];
+
+/// Runs the cleaning actions of all objects that have become phantom reachable. Returns the number of actions.
+/// Exceptions thrown by cleaning actions are logged and ignored, like by the cleaner thread of the class library.
+pub fn run(
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> usize {
+    let phantom_class = classes.find_loaded(PHANTOM_REFERENCE_CLASS);
+    let reachable = mark(classes, heap, stack, phantom_class);
+    let mut actions = 0;
+
+    // Phantom references are only processed while they are reachable themselves, like by the reference handler
+    if let Some(phantom_class) = phantom_class {
+        let referent_field = classes
+            .resolve(phantom_class)
+            .field_layout()
+            .resolve(REFERENT_FIELD)
+            .expect("PhantomReference has no referent field");
+        let cleanable_classes = CLEANABLE_CLASSES
+            .iter()
+            .filter_map(|name| classes.find_loaded(name))
+            .collect::<Vec<_>>();
+        let mut pending = Vec::new();
+        for &reference in &reachable {
+            if heap.is_array(reference) {
+                continue;
+            }
+            let mut instance = heap.resolve(reference);
+            let class = classes.resolve(instance.class());
+            if !class.is_subclass_of(phantom_class, classes) {
+                continue;
+            }
+            let referent = instance
+                .get_field(referent_field)
+                .reference()
+                .to_heap_index();
+            if referent != NULL_POINTER && !reachable.contains(&referent) {
+                // Phantom references are cleared when they are enqueued (since Java 9)
+                instance.set_field(referent_field, NULL_POINTER.into());
+                let is_cleanable = cleanable_classes
+                    .iter()
+                    .any(|cleanable| class.is_subclass_of(*cleanable, classes));
+                pending.push((reference, is_cleanable));
+            }
+        }
+
+        for (reference, is_cleanable) in pending {
+            let (name, descriptor) = if is_cleanable {
+                ("clean", "()V")
+            } else {
+                ("enqueue", "()Z")
+            };
+            let class = classes.resolve(heap.resolve(reference).class());
+            let Some(method) = class.find_own_method(name, descriptor) else {
+                log::warn!(
+                    "{} has no method {}{}, its referent is not cleaned up",
+                    class.name().unwrap_or("<unknown>"),
+                    name,
+                    descriptor
+                );
+                continue;
+            };
+            if let Err(err) = call_method(method, reference, classes, heap, methods, stack) {
+                log::warn!("The cleanup of a phantom reachable object failed: {}", err);
+            }
+            actions += 1;
+        }
+    }
+
+    let (unreachable, alive): (Vec<_>, Vec<_>) = heap
+        .take_cleanups()
+        .into_iter()
+        .partition(|(object, _)| !reachable.contains(object));
+    for (object, action) in alive {
+        heap.register_cleanup(object, action);
+    }
+    for (_, action) in unreachable {
+        action();
+        actions += 1;
+    }
+
+    if actions > 0 {
+        log::debug!("The cleaner ran {} cleaning actions", actions);
+    }
+    actions
+}
+
+/// Finds all objects that are strongly reachable from the roots
+fn mark(
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    stack: StackPointer,
+    phantom_class: Option<ClassIndex>,
+) -> HashSet<HeapIndex> {
+    let objects = heap.objects(classes).into_iter().collect::<HashSet<_>>();
+
+    let mut worklist = classes.static_roots();
+    worklist.extend(heap.interned_strings());
+    worklist.extend(classes.mirrors().read().unwrap().roots());
+    worklist.extend(classes.loaders().read().unwrap().roots());
+    for slot in stack.used_slots() {
+        let candidate = HeapIndex::from_u32(*slot);
+        if objects.contains(&candidate) {
+            worklist.push(candidate);
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    while let Some(object) = worklist.pop() {
+        if object == NULL_POINTER || !reachable.insert(object) {
+            continue;
+        }
+        let referent_offset = if heap.is_array(object) {
+            None
+        } else {
+            let class = classes.resolve(heap.resolve(object).class());
+            phantom_class
+                .filter(|phantom_class| class.is_subclass_of(*phantom_class, classes))
+                .and_then(|_| class.field_layout().resolve(REFERENT_FIELD).ok())
+                .map(|referent| referent.offset)
+        };
+        heap.for_each_reference(object, classes, |offset, target| {
+            if offset.is_none() || offset != referent_offset {
+                worklist.push(target);
+            }
+        });
+    }
+    reachable
+}
+
+fn call_method(
+    method: MethodIndex,
+    receiver: HeapIndex,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<JvmValue, ExecutionError> {
+    let mut arguments = StackFrame::prepare(stack, 0, 0, 1)?;
+    arguments.push_value(receiver.into(), JvmType::Reference);
+    interpreter::call_method(
+        method,
+        arguments.get_stack_for_call(),
+        heap,
+        classes,
+        methods,
+    )
+}
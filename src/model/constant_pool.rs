@@ -1,25 +1,49 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::RwLock};
 
 use super::{
-    class::VirtualMethodIndex, class_library::ClassIndex, field::FieldInfo, method::MethodIndex,
+    class::{InterfaceMethodIndex, VirtualMethodIndex},
+    class_library::ClassIndex,
+    field::FieldInfo,
+    method::MethodIndex,
 };
 
 #[derive(Debug)]
 pub struct ConstantPool {
     entries: Vec<ConstantPoolEntry>,
+    // The resolved field, method and interface method references by the position of their entry. The entries keep
+    // the symbolic references of the class file, because get hands out references to them. Resolving an entry twice
+    // results in the same entry, so threads that resolve it concurrently don't need to agree on who stores it.
+    resolved: RwLock<Vec<Option<ConstantPoolEntry>>>,
+}
+
+impl Clone for ConstantPool {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            resolved: RwLock::new(self.resolved.read().unwrap().clone()),
+        }
+    }
 }
 
 impl ConstantPool {
     pub fn new(entries: Vec<ConstantPoolEntry>) -> Self {
-        Self { entries }
+        let resolved = RwLock::new(vec![None; entries.len()]);
+        Self { entries, resolved }
+    }
+
+    /// The entries in the order of their indices, starting with index 1
+    pub fn entries(&self) -> &[ConstantPoolEntry] {
+        &self.entries
     }
 
     pub fn get(
         &self,
         index: ConstantPoolIndex,
     ) -> Result<&'_ ConstantPoolEntry, ConstantPoolError> {
-        self.entries
-            .get((index.0 - 1) as usize)
+        // The entries start at 1
+        (index.0 as usize)
+            .checked_sub(1)
+            .and_then(|position| self.entries.get(position))
             .ok_or(ConstantPoolError::MissingEntry(index))
     }
 
@@ -53,31 +77,58 @@ impl ConstantPool {
         }
     }
 
+    /// The field reference, resolved if it has already been resolved
+    pub fn get_field(&self, index: ConstantPoolIndex) -> Result<FieldReference, ConstantPoolError> {
+        match self.get_resolved(index)? {
+            ConstantPoolEntry::FieldReference(reference) => Ok(reference),
+            _ => Err(ConstantPoolError::FieldNotResolvable(index)),
+        }
+    }
+
     pub fn get_method(
         &self,
         index: ConstantPoolIndex,
     ) -> Result<MethodReference, ConstantPoolError> {
-        let value = self.get(index)?;
-        match value {
-            ConstantPoolEntry::MethodReference(reference) => Ok(reference.clone()),
+        match self.get_resolved(index)? {
+            ConstantPoolEntry::MethodReference(reference) => Ok(reference),
             _ => Err(ConstantPoolError::MethodNotResolvable(index)),
         }
     }
 
+    pub fn get_interface_method(
+        &self,
+        index: ConstantPoolIndex,
+    ) -> Result<InterfaceMethodReference, ConstantPoolError> {
+        match self.get_resolved(index)? {
+            ConstantPoolEntry::InterfaceMethodReference(reference) => Ok(reference),
+            _ => Err(ConstantPoolError::InterfaceMethodNotResolvable(index)),
+        }
+    }
+
+    /// The resolution of the entry if it has been resolved, otherwise the entry itself
+    fn get_resolved(
+        &self,
+        index: ConstantPoolIndex,
+    ) -> Result<ConstantPoolEntry, ConstantPoolError> {
+        let entry = self.get(index)?;
+        let resolved = self.resolved.read().unwrap()[(index.0 - 1) as usize].clone();
+        Ok(resolved.unwrap_or_else(|| entry.clone()))
+    }
+
+    fn set_resolved(&self, index: ConstantPoolIndex, entry: ConstantPoolEntry) {
+        self.resolved.write().unwrap()[(index.0 - 1) as usize] = Some(entry);
+    }
+
     pub fn update_resolved_field(
         &self,
         index: ConstantPoolIndex,
         info: FieldInfo,
         class: ClassIndex,
     ) {
-        let entry = &self.entries[(index.0 - 1) as usize];
-        let field = ConstantPoolEntry::FieldReference(FieldReference::Resolved { info, class });
-
-        //Safety: This is a cache; nothing is changed here really and the cached value is constant
-        unsafe {
-            let entry = (entry as *const ConstantPoolEntry) as *mut ConstantPoolEntry;
-            *entry = field;
-        }
+        self.set_resolved(
+            index,
+            ConstantPoolEntry::FieldReference(FieldReference::Resolved { info, class }),
+        );
     }
 
     pub fn update_resolved_static_method(
@@ -86,17 +137,13 @@ impl ConstantPool {
         method: MethodIndex,
         parameter_count: usize,
     ) {
-        let entry = &self.entries[(index.0 - 1) as usize];
-        let field = ConstantPoolEntry::MethodReference(MethodReference::ResolvedStatic {
-            index: method,
-            parameter_count,
-        });
-
-        //Safety: This is a cache; nothing is changed here really and the cached value is constant
-        unsafe {
-            let entry = (entry as *const ConstantPoolEntry) as *mut ConstantPoolEntry;
-            *entry = field;
-        }
+        self.set_resolved(
+            index,
+            ConstantPoolEntry::MethodReference(MethodReference::ResolvedStatic {
+                index: method,
+                parameter_count,
+            }),
+        );
     }
 
     pub fn update_resolved_virtual_method(
@@ -106,17 +153,91 @@ impl ConstantPool {
         virtual_index: VirtualMethodIndex,
         parameter_count: usize,
     ) {
-        let entry = &self.entries[(index.0 - 1) as usize];
-        let field = ConstantPoolEntry::MethodReference(MethodReference::ResolvedVirtual {
-            method_index,
-            virtual_index,
-            parameter_count,
-        });
+        self.set_resolved(
+            index,
+            ConstantPoolEntry::MethodReference(MethodReference::ResolvedVirtual {
+                method_index,
+                virtual_index,
+                parameter_count,
+            }),
+        );
+    }
+
+    pub fn update_resolved_interface_method(
+        &self,
+        index: ConstantPoolIndex,
+        reference: InterfaceMethodReference,
+    ) {
+        self.set_resolved(
+            index,
+            ConstantPoolEntry::InterfaceMethodReference(reference),
+        );
+    }
 
-        //Safety: This is a cache; nothing is changed here really and the cached value is constant
-        unsafe {
-            let entry = (entry as *const ConstantPoolEntry) as *mut ConstantPoolEntry;
-            *entry = field;
+    /// Returns (kind, index of the field or method reference)
+    pub fn get_method_handle(
+        &self,
+        index: ConstantPoolIndex,
+    ) -> Result<(ReferenceKind, ConstantPoolIndex), ConstantPoolError> {
+        let value = self.get(index)?;
+        match value {
+            ConstantPoolEntry::MethodHandle { kind, reference } => Ok((*kind, *reference)),
+            _ => Err(ConstantPoolError::NotAMethodHandle(index, value.clone())),
+        }
+    }
+
+    /// Returns the method descriptor of the method type
+    pub fn get_method_type(&self, index: ConstantPoolIndex) -> Result<&str, ConstantPoolError> {
+        let value = self.get(index)?;
+        match value {
+            ConstantPoolEntry::MethodType { descriptor } => self.get_utf8(*descriptor),
+            _ => Err(ConstantPoolError::NotAMethodType(index, value.clone())),
+        }
+    }
+
+    /// Returns (index into the BootstrapMethods attribute, NameAndType of the constant)
+    pub fn get_dynamic(
+        &self,
+        index: ConstantPoolIndex,
+    ) -> Result<(u16, ConstantPoolIndex), ConstantPoolError> {
+        let value = self.get(index)?;
+        match value {
+            ConstantPoolEntry::Dynamic {
+                bootstrap_method,
+                name_and_type,
+            } => Ok((*bootstrap_method, *name_and_type)),
+            _ => Err(ConstantPoolError::NotADynamicConstant(index, value.clone())),
+        }
+    }
+
+    /// Returns (index into the BootstrapMethods attribute, NameAndType of the call site)
+    pub fn get_invoke_dynamic(
+        &self,
+        index: ConstantPoolIndex,
+    ) -> Result<(u16, ConstantPoolIndex), ConstantPoolError> {
+        let value = self.get(index)?;
+        match value {
+            ConstantPoolEntry::InvokeDynamic {
+                bootstrap_method,
+                name_and_type,
+            } => Ok((*bootstrap_method, *name_and_type)),
+            _ => Err(ConstantPoolError::NotAnInvokeDynamic(index, value.clone())),
+        }
+    }
+
+    pub fn resolve_module(&self, index: ConstantPoolIndex) -> Result<&str, ConstantPoolError> {
+        let value = self.get(index)?;
+        match value {
+            ConstantPoolEntry::Module { name } => self.get_utf8(*name),
+            _ => Err(ConstantPoolError::NotAModule(index, value.clone())),
+        }
+    }
+
+    pub fn resolve_package(&self, index: ConstantPoolIndex) -> Result<&str, ConstantPoolError> {
+        let value = self.get(index)?;
+        match value {
+            ConstantPoolEntry::Package { name } => self.get_utf8(*name),
+            _ => Err(ConstantPoolError::NotAPackage(index, value.clone())),
         }
     }
 
@@ -136,6 +257,10 @@ impl ConstantPoolIndex {
     pub fn is_valid(&self) -> bool {
         self.0 != 0
     }
+
+    pub fn as_u16(&self) -> u16 {
+        self.0
+    }
 }
 
 impl From<u16> for ConstantPoolIndex {
@@ -157,20 +282,42 @@ pub enum ConstantPoolEntry {
     Long(i64),
     Float(f32),
     Double(f64),
-    String(String),
+    String {
+        value: ConstantPoolIndex, // Utf8
+    },
     Class {
         name: ConstantPoolIndex,
     },
     FieldReference(FieldReference),
     MethodReference(MethodReference),
-    InterfaceMethodReference {
-        class: ConstantPoolIndex,
-        name_and_type: ConstantPoolIndex,
-    },
+    InterfaceMethodReference(InterfaceMethodReference),
     NameAndType {
         name: ConstantPoolIndex,
         ty: ConstantPoolIndex,
     },
+    MethodHandle {
+        kind: ReferenceKind,
+        reference: ConstantPoolIndex, // A field, method or interface method reference, depending on the kind
+    },
+    MethodType {
+        descriptor: ConstantPoolIndex, // Utf8
+    },
+    // The bootstrap method is an index into the BootstrapMethods attribute of the class
+    Dynamic {
+        bootstrap_method: u16,
+        name_and_type: ConstantPoolIndex,
+    },
+    InvokeDynamic {
+        bootstrap_method: u16,
+        name_and_type: ConstantPoolIndex,
+    },
+    // Only appear in module-info.class
+    Module {
+        name: ConstantPoolIndex,
+    },
+    Package {
+        name: ConstantPoolIndex,
+    },
     Empty, // To reserve the slot after longs and doubles
 }
 
@@ -180,6 +327,63 @@ impl Display for ConstantPoolEntry {
     }
 }
 
+/// An entry of the BootstrapMethods attribute (JVMS §4.7.23), which the Dynamic and InvokeDynamic entries refer to
+#[derive(Debug, Clone)]
+pub struct BootstrapMethod {
+    pub method_handle: ConstantPoolIndex,  // MethodHandle
+    pub arguments: Vec<ConstantPoolIndex>, // Loadable constants
+}
+
+impl Display for BootstrapMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.method_handle)?;
+        if !self.arguments.is_empty() {
+            let arguments = self
+                .arguments
+                .iter()
+                .map(|argument| argument.to_string())
+                .collect::<Vec<_>>();
+            write!(f, " with the static arguments {}", arguments.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The behavior of a method handle (JVMS §5.4.3.5), in the order of the reference_kind values 1 to 9
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    GetField,
+    GetStatic,
+    PutField,
+    PutStatic,
+    InvokeVirtual,
+    InvokeStatic,
+    InvokeSpecial,
+    NewInvokeSpecial,
+    InvokeInterface,
+}
+
+impl ReferenceKind {
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(Self::GetField),
+            2 => Some(Self::GetStatic),
+            3 => Some(Self::PutField),
+            4 => Some(Self::PutStatic),
+            5 => Some(Self::InvokeVirtual),
+            6 => Some(Self::InvokeStatic),
+            7 => Some(Self::InvokeSpecial),
+            8 => Some(Self::NewInvokeSpecial),
+            9 => Some(Self::InvokeInterface),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        self as u8 + 1
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum FieldReference {
     Unresolved {
@@ -209,6 +413,24 @@ pub enum MethodReference {
     },
 }
 
+#[derive(Debug, Clone)]
+pub enum InterfaceMethodReference {
+    Unresolved {
+        class: ConstantPoolIndex,
+        name_and_type: ConstantPoolIndex,
+    },
+    ResolvedInterface {
+        interface: ClassIndex,
+        method_index: InterfaceMethodIndex,
+        parameter_count: usize,
+    },
+    // Interface method references may also refer to the public methods of java/lang/Object (JVMS §5.4.3.4)
+    ResolvedVirtual {
+        virtual_index: VirtualMethodIndex,
+        parameter_count: usize,
+    },
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ConstantPoolError {
     #[error("there is no constant pool entry at {0}")]
@@ -229,6 +451,9 @@ pub enum ConstantPoolError {
     #[error("the value at index {0} is not resolvable to a method reference")]
     MethodNotResolvable(ConstantPoolIndex),
 
+    #[error("the value at index {0} is not resolvable to an interface method reference")]
+    InterfaceMethodNotResolvable(ConstantPoolIndex),
+
     #[error("The constant pool entry at #{0} is expected to be of type UTF8, but is actually {1}")]
     NotAnUtf8String(ConstantPoolIndex, ConstantPoolEntry),
 
@@ -239,4 +464,37 @@ pub enum ConstantPoolError {
         "The constant pool entry at {0} is expected to be of type NameAndType, but is actually {1}"
     )]
     NotNameAndType(ConstantPoolIndex, ConstantPoolEntry),
+
+    #[error(
+        "The constant pool entry at {0} is expected to be of type Module, but is actually {1}"
+    )]
+    NotAModule(ConstantPoolIndex, ConstantPoolEntry),
+
+    #[error(
+        "The constant pool entry at {0} is expected to be of type Package, but is actually {1}"
+    )]
+    NotAPackage(ConstantPoolIndex, ConstantPoolEntry),
+
+    #[error(
+        "The constant pool entry at {0} is expected to be of type MethodHandle, but is actually {1}"
+    )]
+    NotAMethodHandle(ConstantPoolIndex, ConstantPoolEntry),
+
+    #[error(
+        "The constant pool entry at {0} is expected to be of type MethodType, but is actually {1}"
+    )]
+    NotAMethodType(ConstantPoolIndex, ConstantPoolEntry),
+
+    #[error(
+        "The constant pool entry at {0} is expected to be of type Dynamic, but is actually {1}"
+    )]
+    NotADynamicConstant(ConstantPoolIndex, ConstantPoolEntry),
+
+    #[error(
+        "The constant pool entry at {0} is expected to be of type InvokeDynamic, but is actually {1}"
+    )]
+    NotAnInvokeDynamic(ConstantPoolIndex, ConstantPoolEntry),
+
+    #[error("there is no entry at index {0} of the BootstrapMethods attribute")]
+    MissingBootstrapMethod(u16),
 }
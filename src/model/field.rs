@@ -3,7 +3,10 @@ use std::collections::HashMap;
 use crate::model::value::JvmValue;
 
 use super::{
+    access_flags::FieldFlags,
+    annotation::Annotations,
     heap::HeapIndex,
+    signature::ReferenceTypeSignature,
     types::JvmType,
     value::{JvmDouble, JvmFloat, JvmInt, JvmLong, JvmReference},
     visibility::Visibility,
@@ -13,8 +16,12 @@ use super::{
 pub struct FieldDescriptor {
     pub name: String,
     pub visibility: Visibility,
+    pub flags: FieldFlags,
     pub ty: JvmType,
     pub constant_value: Option<JvmValue>,
+    pub constant_string: Option<String>, // A String constant, which is interned when the class is initialized
+    pub signature: Option<ReferenceTypeSignature>, // Only present for fields of generic types
+    pub annotations: Annotations,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -53,13 +60,42 @@ impl FieldLayout {
     pub fn byte_length(&self) -> usize {
         self.length
     }
+
+    pub fn fields(&self) -> impl Iterator<Item = FieldInfo> + '_ {
+        self.fields.values().map(|(offset, ty)| FieldInfo {
+            offset: *offset,
+            ty: *ty,
+        })
+    }
+}
+
+/// The offsets of the reference-typed fields of a layout, i.e. the slots that the garbage collector has to scan in
+/// every instance (or, for a layout of static fields, in the statics of the class)
+#[derive(Clone, Debug, Default)]
+pub struct ReferenceMap {
+    offsets: Vec<usize>, // Ascending
+}
+
+impl ReferenceMap {
+    pub fn of(layout: &FieldLayout) -> Self {
+        let mut offsets = layout
+            .fields()
+            .filter(|field| matches!(field.ty, JvmType::Reference))
+            .map(|field| field.offset)
+            .collect::<Vec<_>>();
+        offsets.sort_unstable();
+        Self { offsets }
+    }
+
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
 }
 
 pub fn layout_fields(parent_layout: &FieldLayout, fields: &Vec<FieldDescriptor>) -> FieldLayout {
-    // Sort descending by an inverted comparison
+    // Sort descending by size
     let mut fields_to_place = (*fields).clone();
-    fields_to_place
-        .sort_by(|first_field, second_field| second_field.ty.size().cmp(&first_field.ty.size()));
+    fields_to_place.sort_by_key(|field| std::cmp::Reverse(field.ty.size()));
 
     let mut field_mappings = parent_layout.fields.clone();
 
@@ -80,7 +116,7 @@ pub fn layout_fields(parent_layout: &FieldLayout, fields: &Vec<FieldDescriptor>)
         }
 
         // If we are here, no matching empty space has been found and the field will be layouted after all other fields
-        let alignment_space = if length % field.ty.alignment() == 0 {
+        let alignment_space = if length.is_multiple_of(field.ty.alignment()) {
             0
         } else {
             let space = field.ty.alignment() - (length % field.ty.alignment());
@@ -113,13 +149,19 @@ pub struct Fields {
     fields: *mut u8,
 }
 
+// The static fields of a class are accessed through the lock of the class, instance fields through the heap
+unsafe impl Send for Fields {}
+
 impl Fields {
+    /// # Safety
+    /// The position must point to fields that have been laid out and initialized, and must outlive the result
     pub unsafe fn at(position: *mut u8) -> Self {
-        Self {
-            fields: position,
-        }
+        Self { fields: position }
     }
 
+    /// # Safety
+    /// The position must point to zeroed memory of at least the byte length of the layout, which must outlive the
+    /// result
     pub unsafe fn init_from_layout_at(
         position: *mut u8,
         layout: &FieldLayout,
@@ -138,6 +180,18 @@ impl Fields {
         fields
     }
 
+    /// # Safety
+    /// The range must lie within the fields
+    pub unsafe fn bytes(&self, offset: usize, length: usize) -> &[u8] {
+        std::slice::from_raw_parts(self.fields.add(offset), length)
+    }
+
+    /// # Safety
+    /// The range must lie within the fields
+    pub unsafe fn set_bytes(&mut self, offset: usize, bytes: &[u8]) {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.fields.add(offset), bytes.len());
+    }
+
     pub fn set_value(&mut self, offset: usize, ty: JvmType, value: JvmValue) {
         match ty {
             JvmType::Void => {}
@@ -146,79 +200,86 @@ impl Fields {
             JvmType::Float => self.set_float(offset, value.float()),
             JvmType::Double => self.set_double(offset, value.double()),
             JvmType::Reference => self.set_reference(offset, value.reference()),
-            _ => todo!(),
+            // Narrow integral types arrive as int and are truncated, booleans keep only their lowest bit (JVMS §6.5.putfield)
+            JvmType::Boolean => unsafe { self.set_bytes(offset, &[(value.int().0 & 1) as u8]) },
+            JvmType::Byte => unsafe {
+                self.set_bytes(offset, &(value.int().0 as i8).to_be_bytes())
+            },
+            JvmType::Char | JvmType::Short => unsafe {
+                self.set_bytes(offset, &(value.int().0 as u16).to_be_bytes())
+            },
         }
     }
 
     pub fn set_int(&mut self, offset: usize, value: JvmInt) {
         let bytes = value.0.to_be_bytes();
         unsafe {
-            *self.fields.offset(offset as isize + 0) = bytes[0];
-            *self.fields.offset(offset as isize + 1) = bytes[1];
-            *self.fields.offset(offset as isize + 2) = bytes[2];
-            *self.fields.offset(offset as isize + 3) = bytes[3];
+            *self.fields.add(offset) = bytes[0];
+            *self.fields.add(offset + 1) = bytes[1];
+            *self.fields.add(offset + 2) = bytes[2];
+            *self.fields.add(offset + 3) = bytes[3];
         }
     }
 
     pub fn set_float(&mut self, offset: usize, value: JvmFloat) {
         let bytes = value.0.to_be_bytes();
         unsafe {
-            *self.fields.offset(offset as isize + 0) = bytes[0];
-            *self.fields.offset(offset as isize + 1) = bytes[1];
-            *self.fields.offset(offset as isize + 2) = bytes[2];
-            *self.fields.offset(offset as isize + 3) = bytes[3];
+            *self.fields.add(offset) = bytes[0];
+            *self.fields.add(offset + 1) = bytes[1];
+            *self.fields.add(offset + 2) = bytes[2];
+            *self.fields.add(offset + 3) = bytes[3];
         }
     }
 
     pub fn set_long(&mut self, offset: usize, value: JvmLong) {
         let bytes = value.0.to_be_bytes();
         unsafe {
-            *self.fields.offset(offset as isize + 0) = bytes[0];
-            *self.fields.offset(offset as isize + 1) = bytes[1];
-            *self.fields.offset(offset as isize + 2) = bytes[2];
-            *self.fields.offset(offset as isize + 3) = bytes[3];
-            *self.fields.offset(offset as isize + 4) = bytes[4];
-            *self.fields.offset(offset as isize + 5) = bytes[5];
-            *self.fields.offset(offset as isize + 6) = bytes[6];
-            *self.fields.offset(offset as isize + 7) = bytes[7];
+            *self.fields.add(offset) = bytes[0];
+            *self.fields.add(offset + 1) = bytes[1];
+            *self.fields.add(offset + 2) = bytes[2];
+            *self.fields.add(offset + 3) = bytes[3];
+            *self.fields.add(offset + 4) = bytes[4];
+            *self.fields.add(offset + 5) = bytes[5];
+            *self.fields.add(offset + 6) = bytes[6];
+            *self.fields.add(offset + 7) = bytes[7];
         }
     }
 
     pub fn set_double(&mut self, offset: usize, value: JvmDouble) {
         let bytes = value.0.to_be_bytes();
         unsafe {
-            *self.fields.offset(offset as isize + 0) = bytes[0];
-            *self.fields.offset(offset as isize + 1) = bytes[1];
-            *self.fields.offset(offset as isize + 2) = bytes[2];
-            *self.fields.offset(offset as isize + 3) = bytes[3];
-            *self.fields.offset(offset as isize + 4) = bytes[4];
-            *self.fields.offset(offset as isize + 5) = bytes[5];
-            *self.fields.offset(offset as isize + 6) = bytes[6];
-            *self.fields.offset(offset as isize + 7) = bytes[7];
+            *self.fields.add(offset) = bytes[0];
+            *self.fields.add(offset + 1) = bytes[1];
+            *self.fields.add(offset + 2) = bytes[2];
+            *self.fields.add(offset + 3) = bytes[3];
+            *self.fields.add(offset + 4) = bytes[4];
+            *self.fields.add(offset + 5) = bytes[5];
+            *self.fields.add(offset + 6) = bytes[6];
+            *self.fields.add(offset + 7) = bytes[7];
         }
     }
 
     pub fn set_reference(&mut self, offset: usize, value: JvmReference) {
         let bytes = unsafe { value.0.into_raw().to_be_bytes() };
         unsafe {
-            *self.fields.offset(offset as isize + 0) = bytes[0];
-            *self.fields.offset(offset as isize + 1) = bytes[1];
-            *self.fields.offset(offset as isize + 2) = bytes[2];
-            *self.fields.offset(offset as isize + 3) = bytes[3];
-            *self.fields.offset(offset as isize + 4) = bytes[4];
-            *self.fields.offset(offset as isize + 5) = bytes[5];
-            *self.fields.offset(offset as isize + 6) = bytes[6];
-            *self.fields.offset(offset as isize + 7) = bytes[7];
+            *self.fields.add(offset) = bytes[0];
+            *self.fields.add(offset + 1) = bytes[1];
+            *self.fields.add(offset + 2) = bytes[2];
+            *self.fields.add(offset + 3) = bytes[3];
+            *self.fields.add(offset + 4) = bytes[4];
+            *self.fields.add(offset + 5) = bytes[5];
+            *self.fields.add(offset + 6) = bytes[6];
+            *self.fields.add(offset + 7) = bytes[7];
         }
     }
 
     pub fn get_int(&self, offset: usize) -> JvmInt {
         unsafe {
             JvmInt(i32::from_be_bytes([
-                *self.fields.offset(offset as isize + 0),
-                *self.fields.offset(offset as isize + 1),
-                *self.fields.offset(offset as isize + 2),
-                *self.fields.offset(offset as isize + 3),
+                *self.fields.add(offset),
+                *self.fields.add(offset + 1),
+                *self.fields.add(offset + 2),
+                *self.fields.add(offset + 3),
             ]))
         }
     }
@@ -226,14 +287,14 @@ impl Fields {
     pub fn get_long(&self, offset: usize) -> JvmLong {
         unsafe {
             JvmLong(i64::from_be_bytes([
-                *self.fields.offset(offset as isize + 0),
-                *self.fields.offset(offset as isize + 1),
-                *self.fields.offset(offset as isize + 2),
-                *self.fields.offset(offset as isize + 3),
-                *self.fields.offset(offset as isize + 4),
-                *self.fields.offset(offset as isize + 5),
-                *self.fields.offset(offset as isize + 6),
-                *self.fields.offset(offset as isize + 7),
+                *self.fields.add(offset),
+                *self.fields.add(offset + 1),
+                *self.fields.add(offset + 2),
+                *self.fields.add(offset + 3),
+                *self.fields.add(offset + 4),
+                *self.fields.add(offset + 5),
+                *self.fields.add(offset + 6),
+                *self.fields.add(offset + 7),
             ]))
         }
     }
@@ -241,10 +302,10 @@ impl Fields {
     pub fn get_float(&self, offset: usize) -> JvmFloat {
         unsafe {
             JvmFloat(f32::from_be_bytes([
-                *self.fields.offset(offset as isize + 0),
-                *self.fields.offset(offset as isize + 1),
-                *self.fields.offset(offset as isize + 2),
-                *self.fields.offset(offset as isize + 3),
+                *self.fields.add(offset),
+                *self.fields.add(offset + 1),
+                *self.fields.add(offset + 2),
+                *self.fields.add(offset + 3),
             ]))
         }
     }
@@ -252,14 +313,14 @@ impl Fields {
     pub fn get_double(&self, offset: usize) -> JvmDouble {
         unsafe {
             JvmDouble(f64::from_be_bytes([
-                *self.fields.offset(offset as isize + 0),
-                *self.fields.offset(offset as isize + 1),
-                *self.fields.offset(offset as isize + 2),
-                *self.fields.offset(offset as isize + 3),
-                *self.fields.offset(offset as isize + 4),
-                *self.fields.offset(offset as isize + 5),
-                *self.fields.offset(offset as isize + 6),
-                *self.fields.offset(offset as isize + 7),
+                *self.fields.add(offset),
+                *self.fields.add(offset + 1),
+                *self.fields.add(offset + 2),
+                *self.fields.add(offset + 3),
+                *self.fields.add(offset + 4),
+                *self.fields.add(offset + 5),
+                *self.fields.add(offset + 6),
+                *self.fields.add(offset + 7),
             ]))
         }
     }
@@ -267,14 +328,14 @@ impl Fields {
     pub fn get_reference(&self, offset: usize) -> JvmReference {
         unsafe {
             JvmReference(HeapIndex::from_raw(u64::from_be_bytes([
-                *self.fields.offset(offset as isize + 0),
-                *self.fields.offset(offset as isize + 1),
-                *self.fields.offset(offset as isize + 2),
-                *self.fields.offset(offset as isize + 3),
-                *self.fields.offset(offset as isize + 4),
-                *self.fields.offset(offset as isize + 5),
-                *self.fields.offset(offset as isize + 6),
-                *self.fields.offset(offset as isize + 7),
+                *self.fields.add(offset),
+                *self.fields.add(offset + 1),
+                *self.fields.add(offset + 2),
+                *self.fields.add(offset + 3),
+                *self.fields.add(offset + 4),
+                *self.fields.add(offset + 5),
+                *self.fields.add(offset + 6),
+                *self.fields.add(offset + 7),
             ])))
         }
     }
@@ -297,18 +358,22 @@ impl Fields {
             JvmType::Reference => JvmValue {
                 reference: self.get_reference(offset).to_heap_index(),
             },
-            JvmType::Byte => todo!(),
-            JvmType::Char => todo!(),
-            JvmType::Short => todo!(),
-            JvmType::Boolean => todo!(),
+            // Narrow integral types are widened to int: bytes and shorts are sign-extended, chars zero-extended
+            JvmType::Byte | JvmType::Boolean => JvmValue {
+                int: i8::from_be_bytes(unsafe { [self.bytes(offset, 1)[0]] }) as i32,
+            },
+            JvmType::Char => JvmValue {
+                int: u16::from_be_bytes(unsafe { self.bytes(offset, 2).try_into().unwrap() })
+                    as i32,
+            },
+            JvmType::Short => JvmValue {
+                int: i16::from_be_bytes(unsafe { self.bytes(offset, 2).try_into().unwrap() })
+                    as i32,
+            },
         }
     }
 }
 
-pub unsafe fn init_fields_at(position: *mut u8, layout: FieldLayout, fields: &[FieldDescriptor]) {
-    for field in fields {}
-}
-
 #[derive(thiserror::Error, Debug)]
 pub enum FieldError {
     #[error("No field with name {0}")]
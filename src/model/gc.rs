@@ -0,0 +1,33 @@
+use std::fmt::Display;
+
+/// The garbage collectors the heap can be configured with (selected via -XX:+Use<Name>GC)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GarbageCollector {
+    /// Never reclaims memory; allocation fails once the heap is exhausted.
+    /// Useful for benchmarking pure allocation performance and for isolating GC bugs.
+    Epsilon,
+}
+
+impl GarbageCollector {
+    pub const ALL: &'static [GarbageCollector] = &[Self::Epsilon];
+
+    /// Maps the part of the selector between "-XX:+Use" and the end of the flag, e.g. "EpsilonGC"
+    pub fn from_flag_name(name: &str) -> Option<Self> {
+        match name {
+            "EpsilonGC" => Some(Self::Epsilon),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Epsilon => "Epsilon",
+        }
+    }
+}
+
+impl Display for GarbageCollector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
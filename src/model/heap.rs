@@ -1,23 +1,147 @@
+use std::collections::HashMap;
+
 use crate::list::NativeList;
 
 use super::{
+    array::{Array, ArrayType},
     class::{Class, VirtualMethodIndex},
     class_library::{ClassIndex, ClassLibrary},
     field::{FieldInfo, Fields},
+    gc::GarbageCollector,
     method::MethodIndex,
+    types::JvmType,
     value::JvmValue,
 };
 
+// Every object starts with an 8 byte header. The lower bits hold the index of the class of the object, arrays are
+// marked by the highest bit and store their element type right below it.
+const ARRAY_FLAG: u64 = 1 << 63;
+const ARRAY_TYPE_SHIFT: u64 = 56;
+const CLASS_INDEX_MASK: u64 = (1 << ARRAY_TYPE_SHIFT) - 1;
+const ARRAY_HEADER_SIZE: usize = 16; // Object header + length + component class
+
 pub struct Heap {
     content: NativeList<u8>,
     tail: usize,
+    size: usize,
+    gc: GarbageCollector,
+    strings: HashMap<String, HeapIndex>, // The interned strings by their content, see model::string
+    cleanups: Vec<(HeapIndex, Box<dyn FnOnce()>)>, // Cleaning actions of the host by their object, see model::cleaner
 }
 
 impl Heap {
-    pub fn new(size: usize) -> Self {
+    pub fn new(size: usize, gc: GarbageCollector) -> Self {
+        log::info!("Using the {} garbage collector", gc);
         Self {
             content: NativeList::alloc(size, 16),
-            tail: 0,
+            tail: 8, // The first slot is never allocated so that no object is located at NULL_POINTER
+            size,
+            gc,
+            strings: HashMap::new(),
+            cleanups: Vec::new(),
+        }
+    }
+
+    pub fn gc(&self) -> GarbageCollector {
+        self.gc
+    }
+
+    /// In bytes
+    pub fn used(&self) -> usize {
+        self.tail
+    }
+
+    /// In bytes
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+
+    pub fn interned_string(&self, value: &str) -> Option<HeapIndex> {
+        self.strings.get(value).copied()
+    }
+
+    pub fn intern_string(&mut self, value: &str, string: HeapIndex) {
+        self.strings.insert(value.to_string(), string);
+    }
+
+    pub fn interned_strings(&self) -> impl Iterator<Item = HeapIndex> + '_ {
+        self.strings.values().copied()
+    }
+
+    /// Runs the action once the object has become phantom reachable, see model::cleaner
+    pub fn register_cleanup(&mut self, object: HeapIndex, action: Box<dyn FnOnce()>) {
+        self.cleanups.push((object, action));
+    }
+
+    pub(super) fn take_cleanups(&mut self) -> Vec<(HeapIndex, Box<dyn FnOnce()>)> {
+        std::mem::take(&mut self.cleanups)
+    }
+
+    /// All objects in the order of their allocation. Objects are allocated back to back, so the heap can be walked
+    /// by the sizes of the objects.
+    pub fn objects(&self, classes: &ClassLibrary) -> Vec<HeapIndex> {
+        let mut objects = Vec::new();
+        let mut index = 8;
+        while index < self.tail {
+            objects.push(HeapIndex(index as u64));
+            index += self.object_size(HeapIndex(index as u64), classes);
+        }
+        objects
+    }
+
+    /// The number of bytes that the object occupies, including its header and padding
+    pub fn object_size(&self, object: HeapIndex, classes: &ClassLibrary) -> usize {
+        let index = object.0 as usize;
+        let header = unsafe { self.get_header(index) };
+        if header & ARRAY_FLAG != 0 {
+            let ty = ArrayType::from_raw((header >> ARRAY_TYPE_SHIFT) as u8 & 0x7f)
+                .expect("Invalid array type in the heap");
+            let length = unsafe {
+                u32::from_be_bytes([
+                    self.content.get(index + 8),
+                    self.content.get(index + 9),
+                    self.content.get(index + 10),
+                    self.content.get(index + 11),
+                ])
+            } as usize;
+            (ARRAY_HEADER_SIZE + length * ty.element_size()).next_multiple_of(8)
+        } else {
+            let class = unsafe { self.get_class_index(index) };
+            8 + classes.resolve(class).field_layout().byte_length()
+        }
+    }
+
+    /// Visits the non-null references of the object: the reference fields of an instance (as given by the reference
+    /// map of its class) with their offset, or the elements of a reference array without an offset
+    pub fn for_each_reference<F>(
+        &mut self,
+        object: HeapIndex,
+        classes: &ClassLibrary,
+        mut visitor: F,
+    ) where
+        F: FnMut(Option<usize>, HeapIndex),
+    {
+        if let Some(array) = self.resolve_array(object) {
+            if array.ty() == ArrayType::Reference {
+                for index in 0..array.length() {
+                    let element = array.get(index).reference().to_heap_index();
+                    if element != NULL_POINTER {
+                        visitor(None, element);
+                    }
+                }
+            }
+            return;
+        }
+        let instance = self.resolve(object);
+        for &offset in classes.resolve(instance.class()).reference_map().offsets() {
+            let field = FieldInfo {
+                offset,
+                ty: JvmType::Reference,
+            };
+            let target = instance.get_field(field).reference().to_heap_index();
+            if target != NULL_POINTER {
+                visitor(Some(offset), target);
+            }
         }
     }
 
@@ -30,23 +154,106 @@ impl Heap {
         }
     }
 
-    pub fn instantiate(&mut self, class: &Class) -> HeapIndex {
+    pub fn instantiate(&mut self, class: &Class) -> Result<HeapIndex, HeapError> {
+        let size = 8 + class.field_layout().byte_length();
+        let index = self.allocate(size)?;
+
         unsafe {
-            let index = self.tail;
-            self.set_class_index(index, class.index());
+            self.set_header(index, class.index().0 as u64);
             let _ = Fields::init_from_layout_at(
                 self.content.get_pointer().offset(index as isize + 8),
                 class.field_layout(),
                 class.field_descriptors(),
             );
-            self.tail += 8 + class.field_layout().byte_length();
-            HeapIndex(index as u64)
+            Ok(HeapIndex(index as u64))
+        }
+    }
+
+    /// Allocates a zeroed array. Arrays are instances of java/lang/Object as far as method dispatch is concerned, so
+    /// the class index of Object has to be passed. The component class should be given for reference arrays.
+    pub fn instantiate_array(
+        &mut self,
+        ty: ArrayType,
+        length: usize,
+        component_class: Option<ClassIndex>,
+        object_class: ClassIndex,
+    ) -> Result<HeapIndex, HeapError> {
+        // Round up to keep the following objects aligned
+        let size = (ARRAY_HEADER_SIZE + length * ty.element_size()).next_multiple_of(8);
+        let index = self.allocate(size)?;
+
+        unsafe {
+            self.set_header(
+                index,
+                ARRAY_FLAG | (ty as u64) << ARRAY_TYPE_SHIFT | object_class.0 as u64,
+            );
+            let mut fields = Fields::at(self.content.get_pointer().offset(index as isize + 8));
+            fields.set_bytes(0, &(length as u32).to_be_bytes());
+            let component = component_class.map(|class| class.0 as u32 + 1).unwrap_or(0);
+            fields.set_bytes(4, &component.to_be_bytes());
+            std::ptr::write_bytes(
+                self.content.get_pointer().add(index + ARRAY_HEADER_SIZE),
+                0,
+                size - ARRAY_HEADER_SIZE,
+            );
         }
+        Ok(HeapIndex(index as u64))
+    }
+
+    /// Returns None if the object is not an array
+    pub fn resolve_array(&mut self, index: HeapIndex) -> Option<Array> {
+        unsafe {
+            let header = self.get_header(index.0 as usize);
+            if header & ARRAY_FLAG == 0 {
+                return None;
+            }
+            let ty = ArrayType::from_raw((header >> ARRAY_TYPE_SHIFT) as u8 & 0x7f)?;
+            let fields = Fields::at(self.content.get_pointer().offset(index.0 as isize + 8));
+            let length = u32::from_be_bytes(fields.bytes(0, 4).try_into().unwrap()) as usize;
+            let component = u32::from_be_bytes(fields.bytes(4, 4).try_into().unwrap());
+            let component_class = component
+                .checked_sub(1)
+                .map(|class| ClassIndex(class as usize));
+            Some(Array::at(
+                ty,
+                length,
+                component_class,
+                self.content
+                    .get_pointer()
+                    .offset(index.0 as isize + ARRAY_HEADER_SIZE as isize),
+            ))
+        }
+    }
+
+    pub fn is_array(&self, index: HeapIndex) -> bool {
+        unsafe { self.get_header(index.0 as usize) & ARRAY_FLAG != 0 }
+    }
+
+    /// Reserves the given number of bytes and returns the index of the first one
+    fn allocate(&mut self, size: usize) -> Result<usize, HeapError> {
+        if self.tail + size > self.size {
+            match self.gc {
+                // Epsilon never reclaims memory
+                GarbageCollector::Epsilon => {
+                    return Err(HeapError::OutOfMemory {
+                        requested: size,
+                        available: self.size - self.tail,
+                    })
+                }
+            }
+        }
+        let index = self.tail;
+        self.tail += size;
+        Ok(index)
     }
 
     unsafe fn get_class_index(&self, index: usize) -> ClassIndex {
-        ClassIndex(u64::from_be_bytes([
-            self.content.get(index + 0),
+        ClassIndex((self.get_header(index) & CLASS_INDEX_MASK) as usize)
+    }
+
+    unsafe fn get_header(&self, index: usize) -> u64 {
+        u64::from_be_bytes([
+            self.content.get(index),
             self.content.get(index + 1),
             self.content.get(index + 2),
             self.content.get(index + 3),
@@ -54,12 +261,12 @@ impl Heap {
             self.content.get(index + 5),
             self.content.get(index + 6),
             self.content.get(index + 7),
-        ]) as usize)
+        ])
     }
 
-    unsafe fn set_class_index(&mut self, index: usize, class_index: ClassIndex) {
-        let bytes = class_index.0.to_be_bytes();
-        self.content.set(index + 0, bytes[0]);
+    unsafe fn set_header(&mut self, index: usize, header: u64) {
+        let bytes = header.to_be_bytes();
+        self.content.set(index, bytes[0]);
         self.content.set(index + 1, bytes[1]);
         self.content.set(index + 2, bytes[2]);
         self.content.set(index + 3, bytes[3]);
@@ -97,7 +304,7 @@ impl Instance {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 pub struct HeapIndex(u64);
 
@@ -110,13 +317,23 @@ impl HeapIndex {
         Self(value as u64)
     }
 
+    /// # Safety
+    /// The raw value is not tracked by the garbage collector
     pub unsafe fn into_raw(self) -> u64 {
         self.0
     }
 
+    /// # Safety
+    /// The value must have been returned by into_raw for an object that is still alive
     pub unsafe fn from_raw(value: u64) -> Self {
         Self(value)
     }
 }
 
 pub const NULL_POINTER: HeapIndex = HeapIndex(0);
+
+#[derive(thiserror::Error, Debug)]
+pub enum HeapError {
+    #[error("the heap is exhausted: requested {requested} bytes, but only {available} bytes are available")]
+    OutOfMemory { requested: usize, available: usize },
+}
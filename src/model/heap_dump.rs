@@ -0,0 +1,86 @@
+use std::{fmt::Write, fs, io, path::Path};
+
+use super::{
+    array::ArrayType,
+    class_library::ClassLibrary,
+    heap::{Heap, HeapIndex},
+};
+
+// A textual dump of the object graph (-XX:HeapDumpPath). Every object is listed with its type, its size and the
+// objects it references, which are found via the reference maps of the classes (see Heap::for_each_reference).
+// The roots are the static fields, the interned strings and the mirrors. Epsilon never reclaims memory, so the dump
+// also contains the objects that have become unreachable.
+
+/// Writes the dump of the heap to the given file
+pub fn write(path: &Path, heap: &mut Heap, classes: &ClassLibrary) -> io::Result<()> {
+    fs::write(path, dump(heap, classes))
+}
+
+fn dump(heap: &mut Heap, classes: &ClassLibrary) -> String {
+    let objects = heap.objects(classes);
+    let mut output = String::new();
+    writeln!(
+        output,
+        "Heap dump: {} objects, {} of {} bytes used",
+        objects.len(),
+        heap.used(),
+        heap.capacity()
+    )
+    .unwrap();
+
+    writeln!(output, "\nRoots:").unwrap();
+    for root in classes.static_roots() {
+        writeln!(output, "  static field -> {}", address(root)).unwrap();
+    }
+    for string in heap.interned_strings().collect::<Vec<_>>() {
+        writeln!(output, "  interned string -> {}", address(string)).unwrap();
+    }
+    for mirror in classes.mirrors().read().unwrap().roots() {
+        writeln!(output, "  mirror -> {}", address(mirror)).unwrap();
+    }
+    for loader in classes.loaders().read().unwrap().roots() {
+        writeln!(output, "  class loader -> {}", address(loader)).unwrap();
+    }
+
+    writeln!(output, "\nObjects:").unwrap();
+    for object in objects {
+        writeln!(
+            output,
+            "{} {} ({} bytes)",
+            address(object),
+            type_name(object, heap, classes),
+            heap.object_size(object, classes)
+        )
+        .unwrap();
+        heap.for_each_reference(object, classes, |offset, target| {
+            match offset {
+                Some(offset) => writeln!(output, "  +{} -> {}", offset, address(target)),
+                None => writeln!(output, "  [] -> {}", address(target)),
+            }
+            .unwrap()
+        });
+    }
+    output
+}
+
+fn type_name(object: HeapIndex, heap: &mut Heap, classes: &ClassLibrary) -> String {
+    match heap.resolve_array(object) {
+        Some(array) if array.ty() == ArrayType::Reference => {
+            let component = array
+                .component_class()
+                .and_then(|class| classes.resolve(class).name().ok())
+                .unwrap_or("<unknown>");
+            format!("{}[]", component)
+        }
+        Some(array) => format!("{:?}[]", array.ty()).to_lowercase(),
+        None => classes
+            .resolve(heap.resolve(object).class())
+            .name()
+            .unwrap_or("<unknown>")
+            .to_string(),
+    }
+}
+
+fn address(object: HeapIndex) -> String {
+    format!("{:#x}", object.as_u32())
+}
@@ -1,40 +1,126 @@
-use core::fmt::Debug;
-use std::{cell::{RefCell, Ref}, borrow::Borrow};
+use core::fmt::{Debug, Display};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
 
 use appendlist::AppendList;
 
 use crate::{
-    interpreter::{self},
-    jit::CodeBuffer,
+    interpreter::{self, quickening::QuickenedCode},
+    intrinsics,
+    jit::{
+        call_site,
+        compile_command::CompilationFilter,
+        runtime::{RuntimeFunctions, RUNTIME_FUNCTIONS},
+        CodeBuffer,
+    },
     list::NativeList,
+    native::{self, NativeFunction},
+    options::ExecutionMode,
 };
 
 use super::{
+    access_flags::MethodFlags,
+    annotation::Annotations,
     class_library::{ClassIndex, ClassLibrary},
+    constant_pool::ConstantPoolIndex,
     heap::Heap,
+    signature::MethodSignature,
     stack::StackPointer,
     types::JvmType,
-    value::JvmValue,
     visibility::Visibility,
 };
 
 #[derive(Debug)]
 pub struct MethodDescriptor {
     pub name: String,
+    pub descriptor: String, // e.g. (IJ)V
     pub parameters: Vec<JvmType>,
     pub return_type: JvmType,
     pub visibility: Visibility,
+    pub flags: MethodFlags,
     pub code: MethodCode,
     pub max_stack: usize,
     pub max_locals: usize,
+    pub exception_table: Vec<ExceptionTableEntry>,
+    pub line_numbers: Vec<LineNumber>,
+    pub local_variables: Vec<LocalVariable>,
+    pub signature: Option<MethodSignature>, // Only present for methods with generic types
+    pub annotations: Annotations,
     pub is_virtual: bool,
 }
 
 impl MethodDescriptor {
     pub fn parameter_count(&self) -> usize {
-        self.parameters.iter().map(|p| p.size()).sum::<usize>() / 4
+        self.parameters.iter().map(|p| p.slots()).sum::<usize>()
             + if self.is_virtual { 1 } else { 0 }
     }
+
+    /// Private methods and constructors are invoked without dynamic dispatch
+    pub fn is_direct(&self) -> bool {
+        matches!(self.visibility, Visibility::Private) || self.name == "<init>"
+    }
+
+    pub fn key(&self) -> MethodKey {
+        MethodKey::new(&self.name, &self.descriptor)
+    }
+}
+
+/// Identifies a method of a class. Overloaded methods share their name, but differ in their descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodKey {
+    pub name: String,
+    pub descriptor: String,
+}
+
+impl MethodKey {
+    pub fn new(name: &str, descriptor: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+        }
+    }
+}
+
+impl Display for MethodKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.name, self.descriptor)
+    }
+}
+
+/// An entry of the LineNumberTable of a method: the code starting at start_pc belongs to the source line
+#[derive(Debug, Clone, Copy)]
+pub struct LineNumber {
+    pub start_pc: usize,
+    pub line: usize,
+}
+
+/// An entry of the LocalVariableTable of a method: while the pc is in start_pc..start_pc + length, the local in slot
+/// holds the variable. Variables of generic types also have the signature of their LocalVariableTypeTable entry.
+#[derive(Debug, Clone)]
+pub struct LocalVariable {
+    pub start_pc: usize,
+    pub length: usize,
+    pub name: String,
+    pub descriptor: String,
+    pub signature: Option<String>,
+    pub slot: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExceptionTableEntry {
+    pub start_pc: usize, // Inclusive
+    pub end_pc: usize,   // Exclusive
+    pub handler_pc: usize,
+    pub catch_type: ConstantPoolIndex, // Zero (i.e. invalid) if the handler catches all exceptions
+}
+
+impl ExceptionTableEntry {
+    pub fn covers(&self, pc: usize) -> bool {
+        self.start_pc <= pc && pc < self.end_pc
+    }
 }
 
 pub enum MethodCode {
@@ -53,13 +139,25 @@ impl Debug for MethodCode {
     }
 }
 
+#[cfg(asm_entry)]
 pub type NativeMethod = extern "sysv64" fn(
     MethodIndex,
     StackPointer,
     *mut Heap,
     *const ClassLibrary,
     *const MethodTable,
-) -> JvmValue;
+) -> super::value::JvmValue;
+
+/// Without the assembly entry, the call table contains ordinary functions that get the VM state as explicit
+/// arguments and return the raw value
+#[cfg(not(asm_entry))]
+pub type NativeMethod = extern "C" fn(
+    MethodIndex,
+    StackPointer,
+    *mut Heap,
+    *const ClassLibrary,
+    *const MethodTable,
+) -> i64;
 
 pub enum MethodImplementation {
     Native(Box<NativeMethod>, Box<dyn CodeBuffer>),
@@ -69,14 +167,46 @@ pub enum MethodImplementation {
 #[repr(C)]
 pub struct MethodTable {
     call_table: NativeList<u64>,
-    methods: RefCell<Vec<MethodEntry>>,
+    methods: AppendList<MethodEntry>, // Entries are never moved, so references to their data stay valid while methods are added
+    execution_mode: ExecutionMode,
+    native_functions: RefCell<HashMap<(String, String), Rc<NativeFunction>>>, // (class name, method name) -> function
+    thresholds: CompilationThresholds,
+    filter: CompilationFilter,
+    code_cache_size: Option<usize>, // In bytes (-XX:ReservedCodeCacheSize), None is unlimited
+    code_cache_used: Cell<usize>,
+    code_cache_full: Cell<bool>, // Set once the compiler has been disabled because the code cache is full
+    runtime_functions: &'static RuntimeFunctions, // Compiled code finds the runtime functions through the method table
+}
+
+/// The offset of the pointer to the runtime functions in the method table (see jit::runtime)
+pub const RUNTIME_FUNCTIONS_OFFSET: usize = std::mem::offset_of!(MethodTable, runtime_functions);
+
+/// Decides when an interpreted method is hot enough to be compiled in the mixed execution mode
+#[derive(Debug, Clone, Copy)]
+pub struct CompilationThresholds {
+    pub invocations: u32, // -XX:CompileThreshold
+    pub back_edges: u32,  // -XX:BackEdgeThreshold, taken backward branches (i.e. loop iterations)
 }
 
 impl MethodTable {
-    pub fn new(length: usize) -> Self {
+    pub fn new(
+        length: usize,
+        execution_mode: ExecutionMode,
+        thresholds: CompilationThresholds,
+        filter: CompilationFilter,
+        code_cache_size: Option<usize>,
+    ) -> Self {
         Self {
             call_table: NativeList::alloc(length, 8),
-            methods: RefCell::new(Vec::new()),
+            methods: AppendList::new(),
+            execution_mode,
+            native_functions: RefCell::new(HashMap::new()),
+            thresholds,
+            filter,
+            code_cache_size,
+            code_cache_used: Cell::new(0),
+            code_cache_full: Cell::new(false),
+            runtime_functions: &RUNTIME_FUNCTIONS,
         }
     }
 
@@ -84,11 +214,27 @@ impl MethodTable {
         &self,
         implementation: MethodImplementation,
         data: MethodData,
+        class_name: &str,
     ) -> MethodIndex {
-        let index = self.methods.borrow().len();
+        let index = self.methods.len();
         self.patch_call_table(index, &implementation);
-        self.methods.borrow_mut().push(MethodEntry {
-            implementation,
+        self.code_cache_used
+            .set(self.code_cache_used.get() + code_size(&implementation));
+        // Methods that are excluded by the compile commands count as already attempted
+        let excluded = !self.filter.allows(class_name, &data.name, &data.descriptor);
+        if excluded {
+            log::info!(
+                "Excluding {}.{}{} from compilation",
+                class_name,
+                data.name,
+                data.descriptor
+            );
+        }
+        self.methods.push(MethodEntry {
+            implementation: RefCell::new(implementation),
+            compilation_attempted: Cell::new(excluded),
+            invocations: Cell::new(0),
+            back_edges: Cell::new(0),
             data,
         });
         index.into()
@@ -96,29 +242,193 @@ impl MethodTable {
 
     pub fn update_method(&self, index: MethodIndex, implementation: MethodImplementation) {
         self.patch_call_table(index.into(), &implementation);
-        self.methods.borrow_mut()[index.0 as usize].implementation = implementation;
+        // Compiled callers that have bound their call sites to the method don't use the call table
+        call_site::rebind(index, unsafe { self.resolve(index) });
+        let used = self.code_cache_used.get() + code_size(&implementation);
+        let previous = self.methods[index.0 as usize]
+            .implementation
+            .replace(implementation);
+        self.code_cache_used.set(used - code_size(&previous));
     }
 
+    /// The size of the machine code of all methods in bytes
+    pub fn code_cache_used(&self) -> usize {
+        self.code_cache_used.get()
+    }
+
+    /// # Safety
+    /// The method index must belong to this table
     pub unsafe fn resolve(&self, method_index: MethodIndex) -> u64 {
         self.call_table.get(method_index.0 as usize)
     }
 
+    /// # Safety
+    /// The call table must only be written through patch_call_table
     pub unsafe fn call_table_pointer(&self) -> *mut u64 {
         self.call_table.get_pointer()
     }
 
-    pub fn get_data(&self, method_index: MethodIndex) -> Ref<'_, MethodData> {
-        Ref::map(self.methods.borrow(), |m| &m[method_index.0 as usize].data)
+    pub fn get_data(&self, method_index: MethodIndex) -> &MethodData {
+        &self.methods[method_index.0 as usize].data
     }
 
     pub fn method_count(&self) -> usize {
-        self.methods.borrow().len()
+        self.methods.len()
+    }
+
+    /// The number of methods that fit into the call table
+    pub fn capacity(&self) -> usize {
+        self.call_table.capacity()
+    }
+
+    pub fn is_compiled(&self, method_index: MethodIndex) -> bool {
+        matches!(
+            *self.methods[method_index.0 as usize]
+                .implementation
+                .borrow(),
+            MethodImplementation::Native(..)
+        )
+    }
+
+    pub fn execution_mode(&self) -> ExecutionMode {
+        self.execution_mode
+    }
+
+    /// Registers the implementation of a native method. Classes that are loaded afterwards bind the method
+    /// automatically, see `bind_native_method` for classes that have already been loaded.
+    /// The method name may be followed by a descriptor (e.g. "sleep(J)V") to implement a single overload, which takes
+    /// precedence over a function that has been registered for all overloads.
+    pub fn register_native_function(
+        &self,
+        class_name: &str,
+        method_name: &str,
+        function: Rc<NativeFunction>,
+    ) {
+        self.native_functions
+            .borrow_mut()
+            .insert((class_name.to_string(), method_name.to_string()), function);
+    }
+
+    /// Returns the implementation of a native method that is being loaded. Methods without a registered
+    /// function are interpreted, which raises an UnsatisfiedLink error.
+    pub fn native_implementation(
+        &self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+    ) -> MethodImplementation {
+        match self.native_function(class_name, method_name, descriptor) {
+            Some(function) => native::bind(function, &format!("{}::{}", class_name, method_name)),
+            None => MethodImplementation::Interpreted,
+        }
+    }
+
+    /// Returns the implementation of a method with bytecode that is being loaded, which is bound to its intrinsic
+    /// if there is one
+    pub fn bytecode_implementation(
+        &self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+    ) -> MethodImplementation {
+        match intrinsics::lookup(class_name, method_name, descriptor) {
+            Some(function) => native::bind(
+                function,
+                &format!("intrinsic {}::{}", class_name, method_name),
+            ),
+            None => MethodImplementation::Interpreted,
+        }
+    }
+
+    /// The function of a bound method, i.e. a native method or an intrinsified method with bytecode
+    pub fn native_function(
+        &self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+    ) -> Option<Rc<NativeFunction>> {
+        let functions = self.native_functions.borrow();
+        functions
+            .get(&(
+                class_name.to_string(),
+                format!("{}{}", method_name, descriptor),
+            ))
+            .or_else(|| functions.get(&(class_name.to_string(), method_name.to_string())))
+            .cloned()
+            .or_else(|| intrinsics::lookup(class_name, method_name, descriptor))
+    }
+
+    /// Binds a native method of an already loaded class to the function that has been registered for it
+    pub fn bind_native_method(&self, method_index: MethodIndex, class_name: &str) {
+        let data = self.get_data(method_index);
+        if data.is_native {
+            self.update_method(
+                method_index,
+                self.native_implementation(class_name, &data.name, &data.descriptor),
+            );
+        }
+    }
+
+    /// Returns true exactly once per method, so that every method is only handed to the JIT once
+    pub fn should_attempt_compilation(&self, method_index: MethodIndex) -> bool {
+        if self.is_code_cache_full() {
+            return false;
+        }
+        !self.methods[method_index.0 as usize]
+            .compilation_attempted
+            .replace(true)
+    }
+
+    /// Like in HotSpot, the compiler is disabled for good once the code cache is full, the methods that have already
+    /// been compiled keep their code
+    fn is_code_cache_full(&self) -> bool {
+        match self.code_cache_size {
+            Some(size) if self.code_cache_used.get() >= size => {
+                if !self.code_cache_full.replace(true) {
+                    log::warn!(
+                        "The code cache is full ({} of {} bytes used), the compiler has been disabled",
+                        self.code_cache_used.get(),
+                        size
+                    );
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Counts an interpreted invocation of the method. Returns true when the method reaches the invocation
+    /// threshold, which only happens in the mixed execution mode.
+    pub fn count_invocation(&self, method_index: MethodIndex) -> bool {
+        self.execution_mode == ExecutionMode::Mixed
+            && Self::count(
+                &self.methods[method_index.0 as usize].invocations,
+                self.thresholds.invocations,
+            )
+    }
+
+    /// Counts a taken backward branch in the interpreted method. Returns true when the method reaches the back-edge
+    /// threshold, which only happens in the mixed execution mode.
+    pub fn count_back_edge(&self, method_index: MethodIndex) -> bool {
+        self.execution_mode == ExecutionMode::Mixed
+            && Self::count(
+                &self.methods[method_index.0 as usize].back_edges,
+                self.thresholds.back_edges,
+            )
+    }
+
+    fn count(counter: &Cell<u32>, threshold: u32) -> bool {
+        let count = counter.get().saturating_add(1);
+        counter.set(count);
+        count == threshold
     }
 
     fn patch_call_table(&self, index: usize, implementation: &MethodImplementation) {
         let ptr = match &implementation {
-            MethodImplementation::Native(code, _) => **code as u64,
-            MethodImplementation::Interpreted => interpreter::interpreter_trampoline as u64,
+            MethodImplementation::Native(code, _) => **code as usize as u64,
+            MethodImplementation::Interpreted => {
+                interpreter::interpreter_trampoline as *const () as u64
+            }
         };
         unsafe {
             self.call_table.set(index, ptr);
@@ -128,18 +438,30 @@ impl MethodTable {
 
 #[repr(C)]
 pub struct MethodEntry {
-    pub implementation: MethodImplementation,
+    pub implementation: RefCell<MethodImplementation>,
+    pub compilation_attempted: Cell<bool>,
+    pub invocations: Cell<u32>, // Interpreted invocations
+    pub back_edges: Cell<u32>,  // Taken backward branches in the interpreter
     pub data: MethodData,
 }
 
 pub struct MethodData {
     pub name: String,
+    pub descriptor: String,
     pub code: Vec<u8>,
     pub max_stack: usize,
     pub max_locals: usize,
+    pub exception_table: Vec<ExceptionTableEntry>,
+    pub line_numbers: Vec<LineNumber>, // Ordered by their start pc
+    pub local_variables: Vec<LocalVariable>,
     pub owning_class: ClassIndex,
     pub argument_count: usize,
+    pub parameters: Vec<JvmType>, // Without this
     pub return_type: JvmType,
+    pub is_native: bool, // Native methods have no code
+    pub visibility: Visibility,
+    pub flags: MethodFlags,
+    pub quickened_code: QuickenedCode, // The code that is executed by the interpreter
 }
 
 impl MethodData {
@@ -153,28 +475,89 @@ impl MethodData {
 
             Some(Self {
                 name: desc.name.clone(),
+                descriptor: desc.descriptor.clone(),
                 code: code.clone(),
                 max_stack: desc.max_stack,
                 max_locals: desc.max_locals,
+                exception_table: desc.exception_table.clone(),
+                line_numbers: desc.line_numbers.clone(),
+                local_variables: desc.local_variables.clone(),
+                owning_class,
+                argument_count: parameter_count,
+                parameters: desc.parameters.clone(),
+                return_type: desc.return_type,
+                is_native: false,
+                visibility: desc.visibility,
+                flags: desc.flags,
+                quickened_code: QuickenedCode::new(code, &desc.exception_table),
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn from_native_descriptor(
+        desc: &MethodDescriptor,
+        owning_class: ClassIndex,
+    ) -> Option<Self> {
+        if let MethodCode::Native = &desc.code {
+            let parameter_count = desc.parameter_count();
+
+            Some(Self {
+                name: desc.name.clone(),
+                descriptor: desc.descriptor.clone(),
+                code: Vec::new(),
+                max_stack: 0,
+                max_locals: parameter_count,
+                exception_table: Vec::new(),
+                line_numbers: Vec::new(),
+                local_variables: Vec::new(),
                 owning_class,
                 argument_count: parameter_count,
+                parameters: desc.parameters.clone(),
                 return_type: desc.return_type,
+                is_native: true,
+                visibility: desc.visibility,
+                flags: desc.flags,
+                quickened_code: QuickenedCode::new(&[], &[]),
             })
         } else {
             None
         }
     }
+
+    /// The variable that the local in the slot holds at pc, None if the class file doesn't describe it
+    pub fn local_variable(&self, slot: usize, pc: usize) -> Option<&LocalVariable> {
+        self.local_variables.iter().find(|variable| {
+            variable.slot == slot
+                && (variable.start_pc..variable.start_pc + variable.length).contains(&pc)
+        })
+    }
+
+    /// The source line of the instruction at pc, None if the class file has no line numbers for it
+    pub fn line_for_pc(&self, pc: usize) -> Option<usize> {
+        let entries = self
+            .line_numbers
+            .partition_point(|entry| entry.start_pc <= pc);
+        entries
+            .checked_sub(1)
+            .map(|entry| self.line_numbers[entry].line)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 pub struct MethodIndex(u32);
 
 impl MethodIndex {
+    /// # Safety
+    /// The raw value must only be passed back through from_raw
     pub unsafe fn into_raw(self) -> u32 {
         self.0
     }
 
+    /// # Safety
+    /// The value must have been returned by into_raw
     pub unsafe fn from_raw(value: u32) -> Self {
         Self(value)
     }
@@ -191,3 +574,10 @@ impl From<usize> for MethodIndex {
         MethodIndex(index as u32)
     }
 }
+
+fn code_size(implementation: &MethodImplementation) -> usize {
+    match implementation {
+        MethodImplementation::Native(_, code) => code.code_size(),
+        MethodImplementation::Interpreted => 0,
+    }
+}
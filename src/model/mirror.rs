@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+
+use super::{
+    array::ArrayType,
+    class_library::{ClassIndex, ClassLibrary, ClassResolveError},
+    heap::{Heap, HeapError, HeapIndex},
+    method::{MethodIndex, MethodTable},
+    stack::StackPointer,
+    string::{self, StringError},
+    value::JvmValue,
+    visibility::Visibility,
+};
+
+// Every type that is named by a class literal (e.g. Foo.class or int[].class) is represented by exactly one instance
+// of java/lang/Class, its mirror. The same holds for the java/lang/reflect/Field and java/lang/reflect/Method objects
+// of the declared members, because frameworks use them as keys of their caches. Mirrors are created on first use and
+// kept in the mirror table of the class library by the index of what they reflect (array types have no class index
+// and are kept by their descriptor). The table is a root of the heap.
+// Mirrors are never initialized by a constructor, like in HotSpot. Only the fields that the Java code can't compute
+// without calling into the VM are set (e.g. the name, the declaring class and the modifiers), if the loaded class
+// library declares them.
+
+pub const CLASS_CLASS: &str = "java/lang/Class";
+pub const FIELD_CLASS: &str = "java/lang/reflect/Field";
+pub const METHOD_CLASS: &str = "java/lang/reflect/Method";
+
+/// The mirrors that have been created so far
+#[derive(Default)]
+pub struct MirrorTable {
+    classes: HashMap<ClassIndex, HeapIndex>,
+    arrays: HashMap<String, HeapIndex>, // By the descriptor of the array type
+    fields: HashMap<(ClassIndex, usize), HeapIndex>, // By the declaring class and the position in its declared fields
+    methods: HashMap<MethodIndex, HeapIndex>,
+}
+
+impl MirrorTable {
+    pub fn roots(&self) -> impl Iterator<Item = HeapIndex> + '_ {
+        self.classes
+            .values()
+            .chain(self.arrays.values())
+            .chain(self.fields.values())
+            .chain(self.methods.values())
+            .copied()
+    }
+
+    /// The class that is reflected by a mirror, None for mirrors of array types and for other objects
+    pub fn reflected_class(&self, mirror: HeapIndex) -> Option<ClassIndex> {
+        self.classes
+            .iter()
+            .find(|(_, candidate)| **candidate == mirror)
+            .map(|(class, _)| *class)
+    }
+}
+
+/// Returns the mirror of the class or array type with the given name, loading the class if necessary
+pub fn mirror(
+    name: &str,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, MirrorError> {
+    if !name.starts_with('[') {
+        let class = classes.try_resolve_by_name(name, methods, heap, stack)?;
+        return class_mirror(class.index(), classes, heap, methods, stack);
+    }
+    if let Some(mirror) = classes.mirrors().read().unwrap().arrays.get(name) {
+        return Ok(*mirror);
+    }
+
+    // The element class of an array type must exist, although arrays don't have a class of their own
+    let element = name.trim_start_matches('[');
+    if let Some(element) = element.strip_prefix('L').and_then(|e| e.strip_suffix(';')) {
+        classes.try_resolve_by_name(element, methods, heap, stack)?;
+    }
+    let mirror = new_class_mirror(name, None, classes, heap, methods, stack)?;
+    classes
+        .mirrors()
+        .write()
+        .unwrap()
+        .arrays
+        .insert(name.to_string(), mirror);
+    Ok(mirror)
+}
+
+/// Returns the mirror of a loaded class
+pub fn class_mirror(
+    class: ClassIndex,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, MirrorError> {
+    if let Some(mirror) = classes.mirrors().read().unwrap().classes.get(&class) {
+        return Ok(*mirror);
+    }
+    let class_data = classes.resolve(class);
+    let name = class_data.name().unwrap_or("<unknown>");
+    let loader = classes
+        .loaders()
+        .read()
+        .unwrap()
+        .object(class_data.loader());
+    let mirror = new_class_mirror(name, loader, classes, heap, methods, stack)?;
+    classes
+        .mirrors()
+        .write()
+        .unwrap()
+        .classes
+        .insert(class, mirror);
+    Ok(mirror)
+}
+
+/// Returns the java/lang/reflect/Field of a field by its position in the declared fields of its class
+pub fn field_mirror(
+    class: ClassIndex,
+    field: usize,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, MirrorError> {
+    if let Some(mirror) = classes
+        .mirrors()
+        .read()
+        .unwrap()
+        .fields
+        .get(&(class, field))
+    {
+        return Ok(*mirror);
+    }
+    let descriptor = classes
+        .resolve(class)
+        .declared_fields()
+        .nth(field)
+        .expect("Field mirror of a field that the class doesn't declare");
+    let (name, modifiers) = (descriptor.name.clone(), descriptor.flags.bits());
+    let declaring_class = class_mirror(class, classes, heap, methods, stack)?;
+    let mirror = new_member_mirror(
+        FIELD_CLASS,
+        declaring_class,
+        &name,
+        modifiers,
+        field,
+        classes,
+        heap,
+        methods,
+        stack,
+    )?;
+    classes
+        .mirrors()
+        .write()
+        .unwrap()
+        .fields
+        .insert((class, field), mirror);
+    Ok(mirror)
+}
+
+/// Returns the java/lang/reflect/Method of a method
+pub fn method_mirror(
+    method: MethodIndex,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, MirrorError> {
+    if let Some(mirror) = classes.mirrors().read().unwrap().methods.get(&method) {
+        return Ok(*mirror);
+    }
+    let data = methods.get_data(method);
+    let declaring_class = class_mirror(data.owning_class, classes, heap, methods, stack)?;
+    let mirror = new_member_mirror(
+        METHOD_CLASS,
+        declaring_class,
+        &data.name,
+        data.flags.bits(),
+        method.into(),
+        classes,
+        heap,
+        methods,
+        stack,
+    )?;
+    classes
+        .mirrors()
+        .write()
+        .unwrap()
+        .methods
+        .insert(method, mirror);
+    Ok(mirror)
+}
+
+/// Returns a new array with the mirrors of the fields that the class declares (Class.getDeclaredFields0). Array types
+/// (None) don't declare any members.
+pub fn declared_fields(
+    class: Option<ClassIndex>,
+    public_only: bool,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, MirrorError> {
+    let fields = class.map_or_else(Vec::new, |class| {
+        classes
+            .resolve(class)
+            .declared_fields()
+            .enumerate()
+            .filter(|(_, field)| !public_only || field.visibility == Visibility::Public)
+            .map(|(position, _)| (class, position))
+            .collect()
+    });
+    let mut mirrors = Vec::with_capacity(fields.len());
+    for (class, field) in fields {
+        mirrors.push(field_mirror(class, field, classes, heap, methods, stack)?);
+    }
+    new_mirror_array(FIELD_CLASS, &mirrors, classes, heap, methods, stack)
+}
+
+/// Returns a new array with the mirrors of the methods that the class declares, without constructors and static
+/// initializers (Class.getDeclaredMethods0)
+pub fn declared_methods(
+    class: Option<ClassIndex>,
+    public_only: bool,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, MirrorError> {
+    let declared = class.map_or_else(Vec::new, |class| {
+        let class = classes.resolve(class);
+        class
+            .declared_methods()
+            .filter(|method| !method.name.starts_with('<'))
+            .filter(|method| !public_only || method.visibility == Visibility::Public)
+            .filter_map(|method| class.find_own_method(&method.name, &method.descriptor))
+            .collect()
+    });
+    let mut mirrors = Vec::with_capacity(declared.len());
+    for method in declared {
+        mirrors.push(method_mirror(method, classes, heap, methods, stack)?);
+    }
+    new_mirror_array(METHOD_CLASS, &mirrors, classes, heap, methods, stack)
+}
+
+/// The loader is the object of the defining loader, None for the bootstrap loader (Class.getClassLoader returns null)
+fn new_class_mirror(
+    name: &str,
+    loader: Option<HeapIndex>,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, MirrorError> {
+    let class_class = classes.try_resolve_by_name(CLASS_CLASS, methods, heap, stack)?;
+    let name_field = class_class.field_layout().resolve("name").ok();
+    let loader_field = class_class.field_layout().resolve("classLoader").ok();
+    let mirror = heap.instantiate(class_class)?;
+    if let (Some(loader_field), Some(loader)) = (loader_field, loader) {
+        heap.resolve(mirror)
+            .set_field(loader_field, JvmValue { reference: loader });
+    }
+    if let Some(name_field) = name_field {
+        // Class.getName returns the binary name, but keeps the descriptors of arrays (JLS §13.1)
+        let binary_name =
+            string::new_string(&name.replace('/', "."), classes, heap, methods, stack)?;
+        heap.resolve(mirror).set_field(
+            name_field,
+            JvmValue {
+                reference: binary_name,
+            },
+        );
+    }
+    Ok(mirror)
+}
+
+/// Creates a java/lang/reflect/Field or java/lang/reflect/Method. The slot identifies the member within the VM.
+#[allow(clippy::too_many_arguments)]
+fn new_member_mirror(
+    class_name: &str,
+    declaring_class: HeapIndex,
+    name: &str,
+    modifiers: u16,
+    slot: usize,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, MirrorError> {
+    // The names of members are interned, like in HotSpot
+    let name = string::intern(name, classes, heap, methods, stack)?;
+    let class = classes.try_resolve_by_name(class_name, methods, heap, stack)?;
+    let values = [
+        (
+            "clazz",
+            JvmValue {
+                reference: declaring_class,
+            },
+        ),
+        ("name", JvmValue { reference: name }),
+        (
+            "modifiers",
+            JvmValue {
+                int: modifiers as i32,
+            },
+        ),
+        ("slot", JvmValue { int: slot as i32 }),
+    ]
+    .into_iter()
+    .filter_map(|(field, value)| Some((class.field_layout().resolve(field).ok()?, value)))
+    .collect::<Vec<_>>();
+    let mirror = heap.instantiate(class)?;
+    for (field, value) in values {
+        heap.resolve(mirror).set_field(field, value);
+    }
+    Ok(mirror)
+}
+
+fn new_mirror_array(
+    component: &str,
+    mirrors: &[HeapIndex],
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, MirrorError> {
+    let component_class = classes
+        .try_resolve_by_name(component, methods, heap, stack)?
+        .index();
+    let object_class = classes
+        .try_resolve_by_name("java/lang/Object", methods, heap, stack)?
+        .index();
+    let array = heap.instantiate_array(
+        ArrayType::Reference,
+        mirrors.len(),
+        Some(component_class),
+        object_class,
+    )?;
+    let mut resolved = heap.resolve_array(array).unwrap();
+    for (index, mirror) in mirrors.iter().enumerate() {
+        resolved.set(index, JvmValue { reference: *mirror });
+    }
+    Ok(array)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MirrorError {
+    // Boxed, because the failed initialization of a class contains an ExecutionError, which may be a MirrorError
+    #[error(transparent)]
+    ClassResolve(Box<ClassResolveError>),
+
+    #[error(transparent)]
+    Heap(#[from] HeapError),
+
+    #[error(transparent)]
+    String(#[from] StringError),
+}
+
+impl From<ClassResolveError> for MirrorError {
+    fn from(err: ClassResolveError) -> Self {
+        Self::ClassResolve(Box::new(err))
+    }
+}
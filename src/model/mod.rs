@@ -1,4 +1,10 @@
+pub mod access_flags;
+pub mod array;
+pub mod boxing;
+pub mod cleaner;
 pub mod class;
+pub mod class_graph;
+pub mod class_list;
 pub mod field;
 pub mod method;
 pub mod class_file;
@@ -6,6 +12,15 @@ pub mod visibility;
 pub mod value;
 pub mod types;
 pub mod heap;
+pub mod heap_dump;
+pub mod mirror;
+pub mod gc;
 pub mod constant_pool;
 pub mod class_library;
-pub mod stack;
\ No newline at end of file
+pub mod stack;
+pub mod string;
+pub mod resolution_statistics;
+pub mod package;
+pub mod module;
+pub mod signature;
+pub mod annotation;
\ No newline at end of file
@@ -0,0 +1,84 @@
+use std::collections::HashSet;
+
+// Modules as described by module-info.class (JVMS §4.7.25). Module names are in dotted form (e.g. java.base),
+// package and class names in internal form (e.g. java/lang).
+
+/// The content of the Module, ModulePackages and ModuleMainClass attributes of a module-info.class
+#[derive(Debug, Clone)]
+pub struct ModuleDescriptor {
+    pub name: String,
+    pub flags: u16,
+    pub version: Option<String>,
+    pub requires: Vec<ModuleRequires>,
+    pub exports: Vec<PackageGrant>,
+    pub opens: Vec<PackageGrant>,
+    pub uses: Vec<String>,
+    pub provides: Vec<(String, Vec<String>)>, // (service interface, implementations)
+    pub packages: Vec<String>,                // Empty if the ModulePackages attribute is missing
+    pub main_class: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModuleRequires {
+    pub module: String,
+    pub flags: u16,
+    pub version: Option<String>,
+}
+
+/// An exported or opened package
+#[derive(Debug, Clone)]
+pub struct PackageGrant {
+    pub package: String,
+    pub flags: u16,
+    pub to: Vec<String>, // The modules the package is exported or opened to, empty if it is exported to all modules
+}
+
+/// A named module that has been defined in the class library
+#[derive(Debug)]
+pub struct Module {
+    descriptor: ModuleDescriptor,
+    packages: HashSet<String>,
+}
+
+impl Module {
+    /// The packages are all packages of the module, not only the ones listed in the descriptor
+    pub fn new(descriptor: ModuleDescriptor, packages: HashSet<String>) -> Self {
+        Self {
+            descriptor,
+            packages,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.descriptor.name
+    }
+
+    pub fn descriptor(&self) -> &ModuleDescriptor {
+        &self.descriptor
+    }
+
+    pub fn packages(&self) -> &HashSet<String> {
+        &self.packages
+    }
+
+    /// Whether this module reads the other one (without considering transitive dependencies).
+    /// Every module reads itself and java.base.
+    pub fn reads(&self, other: &str) -> bool {
+        other == self.name()
+            || other == "java.base"
+            || self
+                .descriptor
+                .requires
+                .iter()
+                .any(|requires| requires.module == other)
+    }
+
+    /// Whether the package is exported to the given module, or to all modules if None is given
+    pub fn exports(&self, package: &str, to: Option<&str>) -> bool {
+        self.descriptor.exports.iter().any(|export| {
+            export.package == package
+                && (export.to.is_empty()
+                    || to.is_some_and(|to| export.to.iter().any(|module| module == to)))
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use super::heap::HeapIndex;
+
+/// Identifies the defining class loader of a class: the bootstrap loader of the VM or an instance of a subclass of
+/// java/lang/ClassLoader, by its position in the LoaderTable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClassLoaderId {
+    Bootstrap,
+    User(u32),
+}
+
+/// The user-defined class loaders that have defined or initiated the loading of a class. Loaders are never unloaded,
+/// so the table is a root of the heap.
+#[derive(Default)]
+pub struct LoaderTable {
+    loaders: Vec<HeapIndex>,
+}
+
+impl LoaderTable {
+    /// The id of the class loader object, which is registered on first use
+    pub fn id_of(&mut self, loader: HeapIndex) -> ClassLoaderId {
+        let position = match self.loaders.iter().position(|known| *known == loader) {
+            Some(position) => position,
+            None => {
+                self.loaders.push(loader);
+                self.loaders.len() - 1
+            }
+        };
+        ClassLoaderId::User(position as u32)
+    }
+
+    /// The class loader object, None for the bootstrap loader
+    pub fn object(&self, id: ClassLoaderId) -> Option<HeapIndex> {
+        match id {
+            ClassLoaderId::Bootstrap => None,
+            ClassLoaderId::User(position) => Some(self.loaders[position as usize]),
+        }
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = HeapIndex> + '_ {
+        self.loaders.iter().copied()
+    }
+}
+
+/// A runtime package (JVMS §5.3): classes are only in the same runtime package if they have the same package
+/// name and were defined by the same class loader.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RuntimePackage {
+    name: String, // In internal form (e.g. java/lang), empty for the unnamed package
+    loader: ClassLoaderId,
+}
+
+impl RuntimePackage {
+    /// Determines the package of the class with the given internal name
+    pub fn of_class(class_name: &str, loader: ClassLoaderId) -> Self {
+        let name = match class_name.rfind('/') {
+            Some(position) => &class_name[..position],
+            None => "",
+        };
+        Self {
+            name: name.to_string(),
+            loader,
+        }
+    }
+
+    /// In internal form
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The package name in binary form, like Class.getPackageName (e.g. java.lang, empty for the unnamed package)
+    pub fn binary_name(&self) -> String {
+        self.name.replace('/', ".")
+    }
+
+    pub fn loader(&self) -> ClassLoaderId {
+        self.loader
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use super::class_library::{ClassIndex, ClassLibrary};
+
+// Symbolic references are resolved once and then served from the resolved state: resolved field and method
+// references are cached in the constant pool, and the interpreter quickens the instruction, so that later executions
+// don't look at the constant pool at all. Class references are looked up by name every time, which is a hit if the
+// class has already been loaded.
+// The counters (-XX:+PrintResolutionStatistics) are kept per class whose constant pool holds the reference and show
+// how much work the caches and the quickening save. A hit is an access to an already resolved field or method
+// reference (in the constant pool or through a quickened instruction) or to a loaded class; a resolution is the
+// first access. References that are resolved by the JIT while compiling aren't counted.
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SymbolicReference {
+    Field,
+    Method,
+    Class,
+}
+
+impl SymbolicReference {
+    const ALL: [SymbolicReference; 3] = [Self::Field, Self::Method, Self::Class];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Field => "fields",
+            Self::Method => "methods",
+            Self::Class => "classes",
+        }
+    }
+}
+
+/// The counters of one class as (resolutions, hits) per kind of reference
+#[derive(Default)]
+pub struct ResolutionStatistics {
+    counters: [(AtomicU64, AtomicU64); 3],
+}
+
+impl ResolutionStatistics {
+    /// Counts the resolution of an unresolved reference
+    #[inline(always)]
+    pub fn record_resolution(&self, kind: SymbolicReference) {
+        if ENABLED.load(Ordering::Relaxed) {
+            self.counters[kind as usize]
+                .0
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts an access to a reference that has already been resolved
+    #[inline(always)]
+    pub fn record_hit(&self, kind: SymbolicReference) {
+        if ENABLED.load(Ordering::Relaxed) {
+            self.counters[kind as usize]
+                .1
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a lookup of the class with the given name, which is a hit if the class has already been loaded. Array
+    /// types aren't classes of their own and aren't counted.
+    #[inline(always)]
+    pub fn record_class_lookup(&self, name: &str, classes: &ClassLibrary) {
+        if ENABLED.load(Ordering::Relaxed) && !name.starts_with('[') {
+            if classes.find_loaded(name).is_some() {
+                self.record_hit(SymbolicReference::Class);
+            } else {
+                self.record_resolution(SymbolicReference::Class);
+            }
+        }
+    }
+
+    fn get(&self, kind: SymbolicReference) -> (u64, u64) {
+        let (resolutions, hits) = &self.counters[kind as usize];
+        (
+            resolutions.load(Ordering::Relaxed),
+            hits.load(Ordering::Relaxed),
+        )
+    }
+
+    fn is_empty(&self) -> bool {
+        SymbolicReference::ALL
+            .iter()
+            .all(|kind| self.get(*kind) == (0, 0))
+    }
+}
+
+/// Logs the counters of every class that has resolved a reference and the totals
+pub fn print(classes: &ClassLibrary) {
+    let mut totals = [(0, 0); 3];
+    for index in 0..classes.class_count() {
+        let class = classes.resolve(ClassIndex(index));
+        let statistics = class.resolution_statistics();
+        if statistics.is_empty() {
+            continue;
+        }
+        log::info!(
+            "Resolution statistics of {}: {}",
+            class.name().unwrap_or("<unknown>"),
+            format_counters(SymbolicReference::ALL.map(|kind| statistics.get(kind)))
+        );
+        for kind in SymbolicReference::ALL {
+            let (resolutions, hits) = statistics.get(kind);
+            totals[kind as usize].0 += resolutions;
+            totals[kind as usize].1 += hits;
+        }
+    }
+    log::info!(
+        "Resolution statistics in total: {}",
+        format_counters(totals)
+    );
+}
+
+fn format_counters(counters: [(u64, u64); 3]) -> String {
+    SymbolicReference::ALL
+        .iter()
+        .map(|kind| {
+            let (resolutions, hits) = counters[*kind as usize];
+            let total = resolutions + hits;
+            let hit_rate = if total == 0 {
+                0.0
+            } else {
+                100.0 * hits as f64 / total as f64
+            };
+            format!(
+                "{} {} resolved / {} hits ({:.1}%)",
+                kind.name(),
+                resolutions,
+                hits,
+                hit_rate
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
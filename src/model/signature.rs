@@ -0,0 +1,539 @@
+use std::fmt::{self, Display};
+
+use super::class_library::{ClassIndex, ClassLibrary};
+
+// Generic signatures of classes, fields and methods as stored in Signature attributes (JVMS §4.7.9.1). The VM
+// executes the erased descriptors, the signatures are only kept for reflection and -XX:+PrintGenericSignatures, which
+// prints the generic declarations of the loaded classes like javap. Class names are in internal form
+// (e.g. java/util/Map) and printed in binary form.
+
+/// The signature of a generic class or of a class that extends or implements a parameterized type
+#[derive(Debug, Clone)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub super_class: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+/// The signature of a generic method or of a method that has parameters or a result of a parameterized type
+#[derive(Debug, Clone)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<JavaTypeSignature>,
+    pub result: Option<JavaTypeSignature>,   // None for void
+    pub throws: Vec<ReferenceTypeSignature>, // Only present if a type variable is thrown
+}
+
+impl MethodSignature {
+    /// The declaration of the method with the name like in Java, e.g. "<T> T max(java.util.List<? extends T>)"
+    pub fn declaration(&self, name: &str) -> String {
+        let mut declaration = String::new();
+        if !self.type_parameters.is_empty() {
+            declaration.push_str(&format!("<{}> ", join(&self.type_parameters)));
+        }
+        match &self.result {
+            Some(result) => declaration.push_str(&result.to_string()),
+            None => declaration.push_str("void"),
+        }
+        declaration.push_str(&format!(" {}({})", name, join(&self.parameters)));
+        if !self.throws.is_empty() {
+            declaration.push_str(&format!(" throws {}", join(&self.throws)));
+        }
+        declaration
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeParameter {
+    pub name: String,
+    pub class_bound: Option<ReferenceTypeSignature>, // Missing if the only bounds are interfaces
+    pub interface_bounds: Vec<ReferenceTypeSignature>,
+}
+
+#[derive(Debug, Clone)]
+pub enum JavaTypeSignature {
+    Base(char), // The descriptor of the primitive type, e.g. I
+    Reference(ReferenceTypeSignature),
+}
+
+/// The signature of a field is always a reference type
+#[derive(Debug, Clone)]
+pub enum ReferenceTypeSignature {
+    Class(ClassTypeSignature),
+    TypeVariable(String),
+    Array(Box<JavaTypeSignature>), // The component type
+}
+
+/// A class type, every nested class is a segment after its enclosing class (e.g. java/util/Map<K, V>.Entry)
+#[derive(Debug, Clone)]
+pub struct ClassTypeSignature {
+    pub segments: Vec<SimpleClassTypeSignature>, // The package is part of the first segment
+}
+
+#[derive(Debug, Clone)]
+pub struct SimpleClassTypeSignature {
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeArgument {
+    Wildcard, // ?
+    Exact(ReferenceTypeSignature),
+    Extends(ReferenceTypeSignature), // ? extends
+    Super(ReferenceTypeSignature),   // ? super
+}
+
+pub fn parse_class(signature: &str) -> Result<ClassSignature, SignatureError> {
+    let mut parser = Parser::new(signature);
+    let type_parameters = parser.type_parameters()?;
+    let super_class = parser.class_type()?;
+    let mut interfaces = Vec::new();
+    while !parser.is_at_end() {
+        interfaces.push(parser.class_type()?);
+    }
+    Ok(ClassSignature {
+        type_parameters,
+        super_class,
+        interfaces,
+    })
+}
+
+pub fn parse_method(signature: &str) -> Result<MethodSignature, SignatureError> {
+    let mut parser = Parser::new(signature);
+    let type_parameters = parser.type_parameters()?;
+    parser.expect('(')?;
+    let mut parameters = Vec::new();
+    while !parser.eat(')') {
+        parameters.push(parser.java_type()?);
+    }
+    let result = if parser.eat('V') {
+        None
+    } else {
+        Some(parser.java_type()?)
+    };
+    let mut throws = Vec::new();
+    while parser.eat('^') {
+        throws.push(parser.reference_type()?);
+    }
+    parser.finish()?;
+    Ok(MethodSignature {
+        type_parameters,
+        parameters,
+        result,
+        throws,
+    })
+}
+
+pub fn parse_field(signature: &str) -> Result<ReferenceTypeSignature, SignatureError> {
+    let mut parser = Parser::new(signature);
+    let ty = parser.reference_type()?;
+    parser.finish()?;
+    Ok(ty)
+}
+
+struct Parser<'a> {
+    signature: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(signature: &'a str) -> Self {
+        Self {
+            signature,
+            position: 0,
+        }
+    }
+
+    fn error(&self) -> SignatureError {
+        SignatureError::Invalid(self.signature.to_string())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.signature[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, SignatureError> {
+        let next = self.peek().ok_or_else(|| self.error())?;
+        self.position += next.len_utf8();
+        Ok(next)
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.position == self.signature.len()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SignatureError> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn finish(&self) -> Result<(), SignatureError> {
+        if self.is_at_end() {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// An identifier up to (and excluding) one of the terminators
+    fn identifier(&mut self, terminators: &[char]) -> Result<String, SignatureError> {
+        let start = self.position;
+        while let Some(next) = self.peek() {
+            if terminators.contains(&next) {
+                break;
+            }
+            self.position += next.len_utf8();
+        }
+        if self.position == start {
+            return Err(self.error());
+        }
+        Ok(self.signature[start..self.position].to_string())
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>, SignatureError> {
+        let mut parameters = Vec::new();
+        if !self.eat('<') {
+            return Ok(parameters);
+        }
+        while !self.eat('>') {
+            let name = self.identifier(&[':'])?;
+            self.expect(':')?;
+            // The class bound is empty if the type variable is only bounded by interfaces
+            let class_bound = match self.peek() {
+                Some(':') => None,
+                _ => Some(self.reference_type()?),
+            };
+            let mut interface_bounds = Vec::new();
+            while self.eat(':') {
+                interface_bounds.push(self.reference_type()?);
+            }
+            parameters.push(TypeParameter {
+                name,
+                class_bound,
+                interface_bounds,
+            });
+        }
+        Ok(parameters)
+    }
+
+    fn java_type(&mut self) -> Result<JavaTypeSignature, SignatureError> {
+        match self.peek() {
+            Some(base @ ('B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z')) => {
+                self.position += 1;
+                Ok(JavaTypeSignature::Base(base))
+            }
+            _ => Ok(JavaTypeSignature::Reference(self.reference_type()?)),
+        }
+    }
+
+    fn reference_type(&mut self) -> Result<ReferenceTypeSignature, SignatureError> {
+        match self.peek() {
+            Some('L') => Ok(ReferenceTypeSignature::Class(self.class_type()?)),
+            Some('T') => {
+                self.position += 1;
+                let name = self.identifier(&[';'])?;
+                self.expect(';')?;
+                Ok(ReferenceTypeSignature::TypeVariable(name))
+            }
+            Some('[') => {
+                self.position += 1;
+                Ok(ReferenceTypeSignature::Array(Box::new(self.java_type()?)))
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature, SignatureError> {
+        self.expect('L')?;
+        let mut segments = Vec::new();
+        loop {
+            let name = self.identifier(&['<', '.', ';'])?;
+            let mut type_arguments = Vec::new();
+            if self.eat('<') {
+                while !self.eat('>') {
+                    type_arguments.push(self.type_argument()?);
+                }
+            }
+            segments.push(SimpleClassTypeSignature {
+                name,
+                type_arguments,
+            });
+            match self.next()? {
+                '.' => continue,
+                ';' => break,
+                _ => return Err(self.error()),
+            }
+        }
+        Ok(ClassTypeSignature { segments })
+    }
+
+    fn type_argument(&mut self) -> Result<TypeArgument, SignatureError> {
+        if self.eat('*') {
+            Ok(TypeArgument::Wildcard)
+        } else if self.eat('+') {
+            Ok(TypeArgument::Extends(self.reference_type()?))
+        } else if self.eat('-') {
+            Ok(TypeArgument::Super(self.reference_type()?))
+        } else {
+            Ok(TypeArgument::Exact(self.reference_type()?))
+        }
+    }
+}
+
+/// Joins the displayed values with ", "
+fn join<T: Display>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Display for ClassSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.type_parameters.is_empty() {
+            write!(f, "<{}> ", join(&self.type_parameters))?;
+        }
+        write!(f, "extends {}", self.super_class)?;
+        if !self.interfaces.is_empty() {
+            write!(f, " implements {}", join(&self.interfaces))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for TypeParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        let bounds = self
+            .class_bound
+            .iter()
+            .chain(&self.interface_bounds)
+            .map(|bound| bound.to_string())
+            .collect::<Vec<_>>();
+        if !bounds.is_empty() {
+            write!(f, " extends {}", bounds.join(" & "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for JavaTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base(base) => write!(
+                f,
+                "{}",
+                match base {
+                    'B' => "byte",
+                    'C' => "char",
+                    'D' => "double",
+                    'F' => "float",
+                    'I' => "int",
+                    'J' => "long",
+                    'S' => "short",
+                    _ => "boolean",
+                }
+            ),
+            Self::Reference(reference) => write!(f, "{}", reference),
+        }
+    }
+}
+
+impl Display for ReferenceTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Class(class) => write!(f, "{}", class),
+            Self::TypeVariable(name) => write!(f, "{}", name),
+            Self::Array(component) => write!(f, "{}[]", component),
+        }
+    }
+}
+
+impl Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| segment.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", segments.join("."))
+    }
+}
+
+impl Display for SimpleClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name.replace('/', "."))?;
+        if !self.type_arguments.is_empty() {
+            write!(f, "<{}>", join(&self.type_arguments))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for TypeArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wildcard => write!(f, "?"),
+            Self::Exact(ty) => write!(f, "{}", ty),
+            Self::Extends(bound) => write!(f, "? extends {}", bound),
+            Self::Super(bound) => write!(f, "? super {}", bound),
+        }
+    }
+}
+
+/// Prints the generic declarations of the loaded classes and their members (-XX:+PrintGenericSignatures)
+pub fn print_loaded(classes: &ClassLibrary) {
+    for index in 0..classes.class_count() {
+        let class = classes.resolve(ClassIndex(index));
+        let fields = class
+            .declared_fields()
+            .filter_map(|field| Some((&field.name, field.signature.as_ref()?)))
+            .collect::<Vec<_>>();
+        let methods = class
+            .declared_methods()
+            .filter_map(|method| Some((&method.name, method.signature.as_ref()?)))
+            .collect::<Vec<_>>();
+        if class.generic_signature().is_none() && fields.is_empty() && methods.is_empty() {
+            continue;
+        }
+        let name = class.name().unwrap_or("<unknown>").replace('/', ".");
+        match class.generic_signature() {
+            Some(signature) => println!("class {} {}", name, signature),
+            None => println!("class {}", name),
+        }
+        for (name, signature) in fields {
+            println!("  {} {}", signature, name);
+        }
+        for (name, signature) in methods {
+            println!("  {}", signature.declaration(name));
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SignatureError {
+    #[error("invalid generic signature '{0}'")]
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_class, parse_field, parse_method, JavaTypeSignature, ReferenceTypeSignature,
+        TypeArgument,
+    };
+
+    #[test]
+    fn parses_nested_type_arguments() {
+        let ty = parse_field("Ljava/util/Map<Ljava/lang/String;Ljava/util/List<[I>;>;").unwrap();
+        assert_eq!(
+            ty.to_string(),
+            "java.util.Map<java.lang.String, java.util.List<int[]>>"
+        );
+        let ReferenceTypeSignature::Class(class) = &ty else {
+            panic!("not a class type: {:?}", ty);
+        };
+        let arguments = &class.segments[0].type_arguments;
+        assert_eq!(arguments.len(), 2);
+        let TypeArgument::Exact(ReferenceTypeSignature::Class(list)) = &arguments[1] else {
+            panic!("not an exact class type argument: {:?}", arguments[1]);
+        };
+        assert!(matches!(
+            &list.segments[0].type_arguments[..],
+            [TypeArgument::Exact(ReferenceTypeSignature::Array(component))]
+                if matches!(**component, JavaTypeSignature::Base('I'))
+        ));
+    }
+
+    #[test]
+    fn parses_inner_classes_of_parameterized_types() {
+        let ty = parse_field("Ljava/util/Map<TK;TV;>.Entry<TK;TV;>;").unwrap();
+        assert_eq!(ty.to_string(), "java.util.Map<K, V>.Entry<K, V>");
+    }
+
+    #[test]
+    fn parses_wildcards() {
+        let ty = parse_field("Ljava/util/Map<*+Ljava/lang/Number;-Ljava/lang/Integer;>;").unwrap();
+        assert_eq!(
+            ty.to_string(),
+            "java.util.Map<?, ? extends java.lang.Number, ? super java.lang.Integer>"
+        );
+        let ReferenceTypeSignature::Class(class) = &ty else {
+            panic!("not a class type: {:?}", ty);
+        };
+        assert!(matches!(
+            &class.segments[0].type_arguments[..],
+            [
+                TypeArgument::Wildcard,
+                TypeArgument::Extends(_),
+                TypeArgument::Super(_)
+            ]
+        ));
+    }
+
+    #[test]
+    fn parses_type_variables() {
+        let method = parse_method(
+            "<T:Ljava/lang/Object;:Ljava/lang/Comparable<-TT;>;E:Ljava/lang/Exception;>\
+             (Ljava/util/List<+TT;>;[TT;)TT;^TE;",
+        )
+        .unwrap();
+        assert_eq!(
+            method.declaration("max"),
+            "<T extends java.lang.Object & java.lang.Comparable<? super T>, \
+             E extends java.lang.Exception> T max(java.util.List<? extends T>, T[]) throws E"
+        );
+        assert!(matches!(
+            &method.result,
+            Some(JavaTypeSignature::Reference(ReferenceTypeSignature::TypeVariable(name)))
+                if name == "T"
+        ));
+    }
+
+    #[test]
+    fn parses_type_parameters_bounded_only_by_interfaces() {
+        let class = parse_class("<T::Ljava/lang/Runnable;>Ljava/lang/Object;Ljava/util/List<TT;>;")
+            .unwrap();
+        assert!(class.type_parameters[0].class_bound.is_none());
+        assert_eq!(
+            class.to_string(),
+            "<T extends java.lang.Runnable> extends java.lang.Object implements java.util.List<T>"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        for signature in [
+            "",
+            "I",                    // Fields have reference types
+            "Ljava/lang/String",    // Missing ;
+            "Ljava/util/List<TT;;", // Unterminated type arguments
+            "Ljava/util/List<>;x",  // Trailing characters
+            "T;",                   // Type variable without a name
+            "L;",                   // Class without a name
+            "Ljava/util/List<+>;",  // Wildcard without a bound
+        ] {
+            assert!(parse_field(signature).is_err(), "{:?}", signature);
+        }
+        for signature in ["()", "(I", "()X", "<T>()V", "()V^I", "()VV"] {
+            assert!(parse_method(signature).is_err(), "{:?}", signature);
+        }
+        for signature in ["", "<>", "<T:>Ljava/lang/Object;", "Ljava/lang/Object;I"] {
+            assert!(parse_class(signature).is_err(), "{:?}", signature);
+        }
+    }
+}
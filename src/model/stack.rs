@@ -1,10 +1,149 @@
-use std::alloc::Layout;
+use std::{
+    alloc::Layout,
+    cell::Cell,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use super::{
     types::JvmType,
     value::{JvmDouble, JvmFloat, JvmInt, JvmLong, JvmReference, JvmValue},
 };
 
+// In the checked mode, every slot of the Java stack is shadowed by a tag that describes the type of its value.
+// The interpreter sets the tags whenever it writes a slot and asserts that they match the expected type whenever it
+// reads a slot with a typed access, so that interpreter bugs (e.g. a wrong slot index or a missed pc advance) fail
+// right at the faulty instruction instead of corrupting values silently. Compiled and native code don't maintain
+// the tags.
+static TAGS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // The end of the Java stack of the current thread, interpreted frames must not grow beyond it
+    static STACK_LIMIT: Cell<*mut u32> = const { Cell::new(std::ptr::null_mut()) };
+    // The start of the Java stack of the current thread
+    static STACK_BASE: Cell<*mut u32> = const { Cell::new(std::ptr::null_mut()) };
+    // The end of the innermost stack frame of the current thread, restored when the frame is dropped
+    static STACK_TOP: Cell<*mut u32> = const { Cell::new(std::ptr::null_mut()) };
+    // (start of the Java stack, tags), the tags are only allocated in the checked mode
+    static TAGS: Cell<(*mut u32, *mut SlotTag)> = const { Cell::new((std::ptr::null_mut(), std::ptr::null_mut())) };
+    // The lowest address of the native stack of the current thread that interpreted frames may be entered at, None
+    // until it has been determined (see native_stack_limit)
+    static NATIVE_STACK_LIMIT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+// The interpreter recurses on the native stack for every call, so deep recursion would exhaust the native stack long
+// before the Java stack. The part of the native stack that is kept free is large enough for the VM to load, create
+// and throw the StackOverflowError.
+const NATIVE_STACK_RESERVE: usize = 512 * 1024;
+
+/// Tags the stack slots of all stacks that are created afterwards (-XX:+CheckStackTags)
+pub fn enable_tags() {
+    TAGS_ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// The type of the value in a slot of the Java stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotTag {
+    Unknown,       // Written by compiled or native code, matches every type
+    Uninitialized, // A local that hasn't been written yet
+    Int,
+    Float,
+    Reference,
+    LongHigh,
+    LongLow,
+    DoubleHigh,
+    DoubleLow,
+}
+
+impl SlotTag {
+    /// The tag of the second slot of a category 2 value
+    fn low_half(self) -> Self {
+        match self {
+            Self::LongHigh => Self::LongLow,
+            Self::DoubleHigh => Self::DoubleLow,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+#[inline(always)]
+fn tag_of(slot: *mut u32) -> Option<*mut SlotTag> {
+    if !TAGS_ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let (start, tags) = TAGS.with(|tags| tags.get());
+    if tags.is_null() {
+        return None;
+    }
+    Some(unsafe { tags.offset(slot.offset_from(start)) })
+}
+
+#[inline(always)]
+fn set_tag(slot: *mut u32, tag: SlotTag) {
+    if let Some(tag_slot) = tag_of(slot) {
+        unsafe { *tag_slot = tag };
+    }
+}
+
+#[inline(always)]
+fn get_tag(slot: *mut u32) -> SlotTag {
+    match tag_of(slot) {
+        Some(tag_slot) => unsafe { *tag_slot },
+        None => SlotTag::Unknown,
+    }
+}
+
+#[inline(always)]
+fn check_tag(slot: *mut u32, expected: SlotTag, location: impl FnOnce() -> String) {
+    let actual = get_tag(slot);
+    assert!(
+        actual == expected || actual == SlotTag::Unknown,
+        "Stack tag mismatch: expected {:?}, found {:?} in {}",
+        expected,
+        actual,
+        location()
+    );
+}
+
+/// Whether the native stack of the current thread has grown into its reserve (see NATIVE_STACK_RESERVE)
+#[inline(always)]
+fn is_native_stack_exhausted() -> bool {
+    let marker = 0u8;
+    let position = std::hint::black_box(&marker) as *const u8 as usize;
+    let limit = NATIVE_STACK_LIMIT.with(|limit| match limit.get() {
+        Some(limit) => limit,
+        None => {
+            let native_limit = native_stack_limit();
+            limit.set(Some(native_limit));
+            native_limit
+        }
+    });
+    position < limit
+}
+
+/// The lowest address of the native stack of the current thread plus the reserve, 0 if the stack can't be determined
+#[cfg(target_os = "linux")]
+fn native_stack_limit() -> usize {
+    unsafe {
+        let mut attributes: libc::pthread_attr_t = std::mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attributes) != 0 {
+            return 0;
+        }
+        let mut start = std::ptr::null_mut();
+        let mut size = 0;
+        let result = libc::pthread_attr_getstack(&attributes, &mut start, &mut size);
+        libc::pthread_attr_destroy(&mut attributes);
+        if result != 0 {
+            return 0;
+        }
+        start as usize + NATIVE_STACK_RESERVE.min(size / 2)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn native_stack_limit() -> usize {
+    0
+}
+
 /// Points to the first empty slot (a slot is 4 bytes wide)
 #[derive(Debug, Clone, Copy)]
 #[repr(transparent)]
@@ -14,6 +153,13 @@ impl StackPointer {
     pub fn with_size(slots: usize) -> Self {
         let layout = Layout::from_size_align(slots * 4, 4).unwrap();
         let stack = unsafe { std::alloc::alloc(layout) as *mut u32 };
+        STACK_LIMIT.with(|limit| limit.set(unsafe { stack.add(slots) }));
+        STACK_BASE.with(|base| base.set(stack));
+        if TAGS_ENABLED.load(Ordering::Relaxed) {
+            // Like the stack itself, the tags live as long as the thread
+            let tags = Box::leak(vec![SlotTag::Unknown; slots].into_boxed_slice());
+            TAGS.with(|cell| cell.set((stack, tags.as_mut_ptr())));
+        }
 
         Self(stack)
     }
@@ -22,8 +168,30 @@ impl StackPointer {
         self.0
     }
 
+    /// The raw values of all slots of the current thread's Java stack below this pointer, e.g. to find the objects
+    /// that are referenced by the active frames
+    pub fn used_slots(self) -> &'static [u32] {
+        let base = STACK_BASE.with(|base| base.get());
+        if base.is_null() {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(base, self.0.offset_from(base) as usize) }
+    }
+
+    /// Whether the pointer points into the Java stack of the current thread (or to its end)
+    pub fn is_on_stack(self) -> bool {
+        let base = STACK_BASE.with(|base| base.get());
+        let limit = STACK_LIMIT.with(|limit| limit.get());
+        base <= self.0 && self.0 <= limit
+    }
+
+    /// The value that is depth slots below this pointer, i.e. peek(0) is the value on top of the stack
+    pub fn peek(self, depth: usize) -> StackValue {
+        unsafe { StackValue(*self.0.sub(depth + 1)) }
+    }
+
     pub fn reserve_slots(self, slots: usize) -> Self {
-        Self(unsafe { self.0.offset(slots as isize) })
+        Self(unsafe { self.0.add(slots) })
     }
 
     pub fn into_raw(self) -> *mut u32 {
@@ -31,52 +199,217 @@ impl StackPointer {
     }
 }
 
+/// The locals and the operand stack of a method on the Java stack. The frame is removed from the stack when it is
+/// dropped, so that it also goes away if the method is left with an error.
 pub struct StackFrame {
     frame_base: StackPointer,
+    operands_base: StackPointer,
     stack_end: StackPointer,
+    operands_limit: StackPointer, // operands_base + max_stack
+    previous_top: *mut u32,       // The end of the enclosing frame
+    overflowed: bool,
 }
 
 impl StackFrame {
-    pub fn prepare(stack: StackPointer, parameters: usize, locals: usize) -> Self {
+    /// Fails if the locals and the operand stack of the frame don't fit onto the Java stack
+    pub fn prepare(
+        stack: StackPointer,
+        parameters: usize,
+        locals: usize,
+        max_stack: usize,
+    ) -> Result<Self, StackError> {
         let frame_base = stack;
         let stack_end = stack.reserve_slots(locals);
+        let operands_limit = stack_end.reserve_slots(max_stack);
+        let stack_limit = STACK_LIMIT.with(|limit| limit.get());
+        if !stack_limit.is_null() && operands_limit.0 > stack_limit {
+            return Err(StackError::StackOverflow);
+        }
+        if is_native_stack_exhausted() {
+            return Err(StackError::NativeStackOverflow);
+        }
 
         for i in 0..parameters {
             unsafe {
-                *frame_base.0.offset(i as isize) =
-                    *frame_base.0.offset(-(parameters as isize) + i as isize);
+                *frame_base.0.add(i) = *frame_base.0.offset(-(parameters as isize) + i as isize);
             }
         }
+        for i in 0..locals {
+            // The tags of the parameters are set by tag_parameters, the caller might not maintain them
+            set_tag(unsafe { frame_base.0.add(i) }, SlotTag::Uninitialized);
+        }
 
-        Self {
+        Ok(Self {
             frame_base,
+            operands_base: stack_end,
             stack_end,
+            operands_limit,
+            previous_top: STACK_TOP.with(|top| top.replace(operands_limit.0)),
+            overflowed: false,
+        })
+    }
+
+    /// Removes all values from the operand stack (but keeps the locals)
+    pub fn clear_operands(&mut self) {
+        self.stack_end = self.operands_base;
+        self.overflowed = false;
+    }
+
+    /// Whether a value has been pushed onto the full operand stack. Such values are dropped instead of overwriting
+    /// the memory behind the frame, so the frame must not be used any more.
+    pub fn has_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    /// Moves the arguments of a call from the top of the operand stack into the first locals and clears the operand
+    /// stack, so that the frame can be reused for a call of its own method
+    pub fn replace_locals(&mut self, argument_slots: usize) {
+        for index in (0..argument_slots).rev() {
+            let TaggedSlot(value, tag) = self.pop_slot();
+            self.set_local_tagged(index, value, tag);
         }
+        self.clear_operands();
     }
 
     pub fn get_stack_for_call(&mut self) -> StackPointer {
         self.stack_end
     }
 
-    pub fn clear(self) -> StackPointer {
-        self.frame_base
+    /// Tags the parameters in the locals according to the descriptor of the method (only in the checked mode)
+    pub fn tag_parameters(&self, argument_slots: usize, parameters: &[JvmType]) {
+        if !TAGS_ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut tags = Vec::with_capacity(argument_slots);
+        if argument_slots > parameters.iter().map(|ty| ty.slots()).sum() {
+            // this
+            tags.push(SlotTag::Reference);
+        }
+        for ty in parameters {
+            match ty {
+                JvmType::Void => {}
+                JvmType::Long => tags.extend([SlotTag::LongHigh, SlotTag::LongLow]),
+                JvmType::Double => tags.extend([SlotTag::DoubleHigh, SlotTag::DoubleLow]),
+                JvmType::Float => tags.push(SlotTag::Float),
+                JvmType::Reference => tags.push(SlotTag::Reference),
+                _ => tags.push(SlotTag::Int),
+            }
+        }
+        for (index, tag) in tags.into_iter().enumerate() {
+            set_tag(self.local_slot(index), tag);
+        }
+    }
+
+    fn local_slot(&self, index: usize) -> *mut u32 {
+        unsafe { self.frame_base.0.add(index) }
     }
 
     pub fn get_local(&self, index: usize) -> StackValue {
-        unsafe { StackValue(*self.frame_base.0.offset(index as isize)) }
+        unsafe { StackValue(*self.frame_base.0.add(index)) }
     }
 
     pub fn set_local(&self, index: usize, value: StackValue) {
-        unsafe { *self.frame_base.0.offset(index as isize) = value.0 }
+        self.set_local_tagged(index, value, SlotTag::Unknown);
+    }
+
+    fn get_local_tagged(&self, index: usize, expected: SlotTag) -> StackValue {
+        check_tag(self.local_slot(index), expected, || {
+            format!("local {}", index)
+        });
+        self.get_local(index)
+    }
+
+    fn set_local_tagged(&self, index: usize, value: StackValue, tag: SlotTag) {
+        unsafe { *self.local_slot(index) = value.0 }
+        set_tag(self.local_slot(index), tag);
+    }
+
+    pub fn get_local_int(&self, index: usize) -> JvmInt {
+        self.get_local_tagged(index, SlotTag::Int).as_int()
+    }
+
+    pub fn set_local_int(&self, index: usize, value: JvmInt) {
+        self.set_local_tagged(index, StackValue::from_int(value), SlotTag::Int);
+    }
+
+    pub fn get_local_reference(&self, index: usize) -> JvmReference {
+        self.get_local_tagged(index, SlotTag::Reference)
+            .as_reference()
+    }
+
+    /// Pushes the local, which must be a category 1 value of the given type
+    pub fn load_local(&mut self, index: usize, tag: SlotTag) {
+        let value = self.get_local_tagged(index, tag);
+        self.push_tagged(value, tag);
+    }
+
+    /// Pushes the category 2 value that starts at the local, high_tag is the tag of its first slot
+    pub fn load_local_wide(&mut self, index: usize, high_tag: SlotTag) {
+        let high = self.get_local_tagged(index, high_tag);
+        let low = self.get_local_tagged(index + 1, high_tag.low_half());
+        self.push_tagged(high, high_tag);
+        self.push_tagged(low, high_tag.low_half());
+    }
+
+    /// Pops a category 1 value of the given type into the local
+    pub fn store_local(&mut self, index: usize, tag: SlotTag) {
+        let value = self.pop_tagged(tag);
+        self.set_local_tagged(index, value, tag);
+    }
+
+    /// Pops a category 2 value into the local and the following one, high_tag is the tag of its first slot
+    pub fn store_local_wide(&mut self, index: usize, high_tag: SlotTag) {
+        let low = self.pop_tagged(high_tag.low_half());
+        let high = self.pop_tagged(high_tag);
+        self.set_local_tagged(index, high, high_tag);
+        self.set_local_tagged(index + 1, low, high_tag.low_half());
     }
 
     pub fn push(&mut self, value: StackValue) {
+        self.push_tagged(value, SlotTag::Unknown);
+    }
+
+    fn push_tagged(&mut self, value: StackValue, tag: SlotTag) {
+        if self.stack_end.0 >= self.operands_limit.0 {
+            self.overflowed = true;
+            return;
+        }
         unsafe {
             *self.stack_end.0 = value.0;
+            set_tag(self.stack_end.0, tag);
             self.stack_end.0 = self.stack_end.0.offset(1);
         }
     }
 
+    pub fn push_int(&mut self, value: JvmInt) {
+        self.push_tagged(StackValue::from_int(value), SlotTag::Int);
+    }
+
+    pub fn push_float(&mut self, value: JvmFloat) {
+        self.push_tagged(StackValue::from_float(value), SlotTag::Float);
+    }
+
+    pub fn push_reference(&mut self, value: JvmReference) {
+        self.push_tagged(StackValue::from_reference(value), SlotTag::Reference);
+    }
+
+    pub fn push_long(&mut self, value: JvmLong) {
+        let (high, low) = StackValue::from_long(value);
+        self.push_tagged(high, SlotTag::LongHigh);
+        self.push_tagged(low, SlotTag::LongLow);
+    }
+
+    pub fn push_double(&mut self, value: JvmDouble) {
+        let (high, low) = StackValue::from_double(value);
+        self.push_tagged(high, SlotTag::DoubleHigh);
+        self.push_tagged(low, SlotTag::DoubleLow);
+    }
+
+    /// Pushes a slot that has been popped by pop_slot, keeping its tag
+    pub fn push_slot(&mut self, slot: TaggedSlot) {
+        self.push_tagged(slot.0, slot.1);
+    }
+
     pub fn push_wide(&mut self, values: (StackValue, StackValue)) {
         self.push(values.0);
         self.push(values.1);
@@ -85,12 +418,16 @@ impl StackFrame {
     pub fn push_value(&mut self, value: JvmValue, ty: JvmType) {
         match ty {
             JvmType::Void => {}
-            JvmType::Integer => self.push(StackValue::from_int(value.int())),
-            JvmType::Long => self.push_wide(StackValue::from_long(value.long())),
-            JvmType::Float => self.push(StackValue::from_float(value.float())),
-            JvmType::Double => self.push_wide(StackValue::from_double(value.double())),
-            JvmType::Reference => self.push(StackValue::from_reference(value.reference())),
-            _ => todo!(),
+            // Narrow integral types are widened to int on the operand stack
+            JvmType::Integer
+            | JvmType::Byte
+            | JvmType::Char
+            | JvmType::Short
+            | JvmType::Boolean => self.push_int(value.int()),
+            JvmType::Long => self.push_long(value.long()),
+            JvmType::Float => self.push_float(value.float()),
+            JvmType::Double => self.push_double(value.double()),
+            JvmType::Reference => self.push_reference(value.reference()),
         }
     }
 
@@ -101,6 +438,52 @@ impl StackFrame {
         }
     }
 
+    fn pop_tagged(&mut self, expected: SlotTag) -> StackValue {
+        let value = self.pop();
+        check_tag(self.stack_end.0, expected, || {
+            format!("operand stack slot {}", self.operands().len())
+        });
+        value
+    }
+
+    pub fn pop_int(&mut self) -> JvmInt {
+        self.pop_tagged(SlotTag::Int).as_int()
+    }
+
+    pub fn pop_float(&mut self) -> JvmFloat {
+        self.pop_tagged(SlotTag::Float).as_float()
+    }
+
+    pub fn pop_reference(&mut self) -> JvmReference {
+        self.pop_tagged(SlotTag::Reference).as_reference()
+    }
+
+    pub fn pop_long(&mut self) -> JvmLong {
+        let low = self.pop_tagged(SlotTag::LongLow);
+        let high = self.pop_tagged(SlotTag::LongHigh);
+        (high, low).as_long()
+    }
+
+    pub fn pop_double(&mut self) -> JvmDouble {
+        let low = self.pop_tagged(SlotTag::DoubleLow);
+        let high = self.pop_tagged(SlotTag::DoubleHigh);
+        (high, low).as_double()
+    }
+
+    /// Pops a slot of any type, e.g. to rearrange the operand stack (dup, swap, ...)
+    pub fn pop_slot(&mut self) -> TaggedSlot {
+        let value = self.pop();
+        TaggedSlot(value, get_tag(self.stack_end.0))
+    }
+
+    /// Removes the given number of slots from the operand stack, e.g. the arguments after a call
+    pub fn discard(&mut self, slots: usize) {
+        unsafe {
+            self.stack_end.0 = self.stack_end.0.offset(-(slots as isize));
+        }
+    }
+
+    /// Pops a category 2 value, returned as (high, low) like it has been pushed by push_wide
     pub fn pop_wide(&mut self) -> (StackValue, StackValue) {
         let top = self.pop();
         let second = self.pop();
@@ -110,25 +493,33 @@ impl StackFrame {
     pub fn pop_type(&mut self, ty: JvmType) -> JvmValue {
         match ty {
             JvmType::Void => JvmValue::VOID,
-            JvmType::Byte => todo!(),
-            JvmType::Char => todo!(),
-            JvmType::Integer => JvmValue {
-                int: self.pop().as_int().into(),
+            JvmType::Integer
+            | JvmType::Byte
+            | JvmType::Char
+            | JvmType::Short
+            | JvmType::Boolean => JvmValue {
+                int: self.pop_int().into(),
             },
             JvmType::Long => JvmValue {
-                long: self.pop_wide().as_long().into(),
+                long: self.pop_long().into(),
             },
             JvmType::Float => JvmValue {
-                float: self.pop().as_float().into(),
+                float: self.pop_float().into(),
             },
             JvmType::Double => JvmValue {
-                double: self.pop_wide().as_double().into(),
+                double: self.pop_double().into(),
             },
             JvmType::Reference => JvmValue {
-                reference: self.pop().as_reference().to_heap_index(),
+                reference: self.pop_reference().to_heap_index(),
             },
-            JvmType::Short => todo!(),
-            JvmType::Boolean => todo!(),
+        }
+    }
+
+    /// The current content of the operand stack, the top value is the last element
+    pub fn operands(&self) -> &[StackValue] {
+        unsafe {
+            let length = self.stack_end.0.offset_from(self.operands_base.0) as usize;
+            std::slice::from_raw_parts(self.operands_base.0 as *const StackValue, length)
         }
     }
 
@@ -140,17 +531,47 @@ impl StackFrame {
     }
 }
 
+impl Drop for StackFrame {
+    fn drop(&mut self) {
+        STACK_TOP.with(|top| {
+            // Frames are nested, so the innermost frame is always dropped first
+            debug_assert_eq!(
+                top.get(),
+                self.operands_limit.0,
+                "A stack frame outlived its enclosing frame"
+            );
+            top.set(self.previous_top);
+        });
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StackError {
+    #[error("the Java stack is exhausted")]
+    StackOverflow,
+
+    #[error("the native stack is exhausted")]
+    NativeStackOverflow,
+
+    #[error("the operand stack exceeds its maximum size of {0} slots")]
+    OperandStackOverflow(usize),
+}
+
 #[repr(transparent)]
 #[derive(Debug, Default, Clone, Copy)]
 pub struct StackValue(u32);
 
+/// A slot of the operand stack together with its tag (which is Unknown unless the checked mode is enabled)
+#[derive(Debug, Clone, Copy)]
+pub struct TaggedSlot(StackValue, SlotTag);
+
 impl StackValue {
     pub fn as_int(self) -> JvmInt {
-        unsafe { std::mem::transmute::<u32, i32>(self.0) }.into()
+        self.0.cast_signed().into()
     }
 
     pub fn as_float(self) -> JvmFloat {
-        unsafe { std::mem::transmute::<u32, f32>(self.0) }.into()
+        f32::from_bits(self.0).into()
     }
 
     pub fn as_reference(self) -> JvmReference {
@@ -158,22 +579,22 @@ impl StackValue {
     }
 
     pub fn from_int(value: JvmInt) -> Self {
-        Self(unsafe { std::mem::transmute(value.0) })
+        Self(value.0.cast_unsigned())
     }
 
     pub fn from_float(value: JvmFloat) -> Self {
-        Self(unsafe { std::mem::transmute(value.0) })
+        Self(value.0.to_bits())
     }
 
     pub fn from_long(value: JvmLong) -> (Self, Self) {
-        let value: u64 = unsafe { std::mem::transmute(value.0) };
+        let value = value.0.cast_unsigned();
         let high = ((value & 0xffffffff00000000) >> 32) as u32;
         let low = (value & 0xffffffff) as u32;
         (Self(high), Self(low))
     }
 
     pub fn from_double(value: JvmDouble) -> (Self, Self) {
-        let value: u64 = unsafe { std::mem::transmute(value.0) };
+        let value = value.0.to_bits();
         let high = ((value & 0xffffffff00000000) >> 32) as u32;
         let low = (value & 0xffffffff) as u32;
         (Self(high), Self(low))
@@ -184,27 +605,27 @@ impl StackValue {
     }
 
     pub fn to_raw(self) -> i32 {
-        unsafe { std::mem::transmute::<u32, i32>(self.0) }
+        self.0.cast_signed()
     }
 
     pub fn from_raw(value: i32) -> Self {
-        unsafe { Self(std::mem::transmute::<i32, u32>(value)) }
+        Self(value.cast_unsigned())
     }
 }
 
 pub trait StackValueWide {
-    fn as_long(self) -> JvmLong;
-    fn as_double(self) -> JvmDouble;
+    fn as_long(&self) -> JvmLong;
+    fn as_double(&self) -> JvmDouble;
 }
 
 impl StackValueWide for (StackValue, StackValue) {
-    fn as_long(self) -> JvmLong {
-        unsafe { std::mem::transmute::<u64, i64>((self.0 .0 as u64) << 32 | (self.1 .0 as u64)) }
+    fn as_long(&self) -> JvmLong {
+        ((self.0 .0 as u64) << 32 | (self.1 .0 as u64))
+            .cast_signed()
             .into()
     }
 
-    fn as_double(self) -> JvmDouble {
-        unsafe { std::mem::transmute::<u64, f64>((self.0 .0 as u64) << 32 | (self.1 .0 as u64)) }
-            .into()
+    fn as_double(&self) -> JvmDouble {
+        f64::from_bits((self.0 .0 as u64) << 32 | (self.1 .0 as u64)).into()
     }
 }
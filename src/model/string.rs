@@ -0,0 +1,93 @@
+use super::{
+    array::ArrayType,
+    class_library::{ClassLibrary, ClassResolveError},
+    field::FieldError,
+    heap::{Heap, HeapError, HeapIndex},
+    method::MethodTable,
+    stack::StackPointer,
+    value::JvmValue,
+};
+
+// Strings use the layout of the JDK 8 class library, i.e. their characters are stored as char[] in the field value.
+// String constants are interned in the string table of the heap, so that all literals with the same content
+// evaluate to the same object (JLS §3.10.5).
+
+pub const STRING_CLASS: &str = "java/lang/String";
+pub const STRING_VALUE_FIELD: &str = "value";
+
+/// Creates a java/lang/String with the given content
+pub fn new_string(
+    value: &str,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, StringError> {
+    let characters = value.encode_utf16().collect::<Vec<_>>();
+    let object_class = classes
+        .try_resolve_by_name("java/lang/Object", methods, heap, stack)?
+        .index();
+    let array = heap.instantiate_array(ArrayType::Char, characters.len(), None, object_class)?;
+    let mut resolved = heap.resolve_array(array).unwrap();
+    for (index, character) in characters.into_iter().enumerate() {
+        resolved.set(index, character.into());
+    }
+
+    let class = classes.try_resolve_by_name(STRING_CLASS, methods, heap, stack)?;
+    let value_field = class.field_layout().resolve(STRING_VALUE_FIELD)?;
+    let string = heap.instantiate(class)?;
+    heap.resolve(string)
+        .set_field(value_field, JvmValue { reference: array });
+    Ok(string)
+}
+
+/// Returns the interned java/lang/String with the given content, creating it on first use
+pub fn intern(
+    value: &str,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+    methods: &MethodTable,
+    stack: StackPointer,
+) -> Result<HeapIndex, StringError> {
+    if let Some(string) = heap.interned_string(value) {
+        return Ok(string);
+    }
+    let string = new_string(value, classes, heap, methods, stack)?;
+    heap.intern_string(value, string);
+    Ok(string)
+}
+
+/// Reads the content of a java/lang/String, unpaired surrogates are replaced
+pub fn read_string(
+    string: HeapIndex,
+    classes: &ClassLibrary,
+    heap: &mut Heap,
+) -> Result<String, StringError> {
+    let class = classes.resolve(heap.resolve(string).class());
+    let value_field = class.field_layout().resolve(STRING_VALUE_FIELD)?;
+    let array = heap.resolve(string).get_field(value_field).reference();
+    let array = heap.resolve_array(array.to_heap_index()).unwrap();
+    let characters = (0..array.length())
+        .map(|index| array.get(index).int().0 as u16)
+        .collect::<Vec<_>>();
+    Ok(String::from_utf16_lossy(&characters))
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StringError {
+    // Boxed, because the failed initialization of a class contains an ExecutionError, which may be a StringError
+    #[error(transparent)]
+    ClassResolve(Box<ClassResolveError>),
+
+    #[error(transparent)]
+    Field(#[from] FieldError),
+
+    #[error(transparent)]
+    Heap(#[from] HeapError),
+}
+
+impl From<ClassResolveError> for StringError {
+    fn from(err: ClassResolveError) -> Self {
+        Self::ClassResolve(Box::new(err))
+    }
+}
@@ -21,54 +21,58 @@ pub enum JvmType {
 
 impl JvmType {
     pub fn matches(self, other: &Self) -> bool {
-        match (self, other) {
-            (JvmType::Void, JvmType::Void) => true,
-            (JvmType::Byte, JvmType::Byte) => true,
-            (JvmType::Char, JvmType::Char) => true,
-            (JvmType::Integer, JvmType::Integer) => true,
-            (JvmType::Long, JvmType::Long) => true,
-            (JvmType::Float, JvmType::Float) => true,
-            (JvmType::Double, JvmType::Double) => true,
-            (JvmType::Reference, JvmType::Reference) => true,
-            (JvmType::Short, JvmType::Short) => true,
-            (JvmType::Boolean, JvmType::Boolean) => true,
-            _ => false,
-        }
+        matches!(
+            (self, other),
+            (JvmType::Void, JvmType::Void)
+                | (JvmType::Byte, JvmType::Byte)
+                | (JvmType::Char, JvmType::Char)
+                | (JvmType::Integer, JvmType::Integer)
+                | (JvmType::Long, JvmType::Long)
+                | (JvmType::Float, JvmType::Float)
+                | (JvmType::Double, JvmType::Double)
+                | (JvmType::Reference, JvmType::Reference)
+                | (JvmType::Short, JvmType::Short)
+                | (JvmType::Boolean, JvmType::Boolean)
+        )
     }
 
     pub fn size(&self) -> usize {
         match self {
             JvmType::Void => 0,
             JvmType::Byte => 1,
-            JvmType::Char => 1,
+            JvmType::Char => 2,
             JvmType::Integer => 4,
             JvmType::Long => 8,
             JvmType::Float => 4,
             JvmType::Double => 8,
-            JvmType::Reference => 2, // TODO
+            JvmType::Reference => 8, // Fields::set_reference stores 8 bytes
             JvmType::Short => 2,
             JvmType::Boolean => 1,
         }
     }
 
+    /// The number of 4-byte slots that a value of this type takes on the operand stack or in the locals
+    pub fn slots(&self) -> usize {
+        match self {
+            JvmType::Void => 0,
+            JvmType::Long | JvmType::Double => 2,
+            _ => 1,
+        }
+    }
+
     pub fn alignment(&self) -> usize {
         self.size()
     }
 
     pub fn parse(graphemes: &mut Peekable<Graphemes>) -> Option<JvmType> {
-        let tag = graphemes.next();
-        if tag.is_none() {
-            return None;
-        }
-
-        match tag.unwrap() {
+        match graphemes.next()? {
             "B" => Some(JvmType::Byte),
             "C" => Some(JvmType::Char),
             "D" => Some(JvmType::Double),
             "F" => Some(JvmType::Float),
             "I" => Some(JvmType::Integer),
             "J" => Some(JvmType::Long),
-            "S" => Some(JvmType::Long),
+            "S" => Some(JvmType::Short),
             "Z" => Some(JvmType::Boolean),
             "V" => Some(JvmType::Void),
             "L" => {
@@ -78,7 +82,11 @@ impl JvmType {
                 //Some(JvmType::Reference(TypeReference::Unresolved(class)))
                 Some(JvmType::Reference)
             }
-            "[" => unimplemented!("Arrays are not implemented"),
+            "[" => {
+                // Arrays are references, the component type only has to be skipped
+                JvmType::parse(graphemes)?;
+                Some(JvmType::Reference)
+            }
             _ => None,
         }
     }
@@ -28,6 +28,13 @@ impl From<i16> for JvmInt {
     }
 }
 
+// Java's char, which is zero-extended
+impl From<u16> for JvmInt {
+    fn from(value: u16) -> Self {
+        Self(value as i32)
+    }
+}
+
 impl From<JvmInt> for i32 {
     fn from(value: JvmInt) -> Self {
         value.0
@@ -50,6 +57,20 @@ impl From<JvmFloat> for f32 {
     }
 }
 
+impl JvmFloat {
+    /// The remainder of frem (JVMS §6.5.frem), see JvmDouble::remainder
+    pub fn remainder(self, divisor: Self) -> Self {
+        let (dividend, divisor) = (self.0, divisor.0);
+        if dividend.is_nan() || divisor.is_nan() || dividend.is_infinite() || divisor == 0.0 {
+            Self(f32::NAN)
+        } else if divisor.is_infinite() || dividend == 0.0 {
+            Self(dividend)
+        } else {
+            Self(dividend % divisor)
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 #[repr(transparent)]
 pub struct JvmLong(pub i64);
@@ -82,6 +103,23 @@ impl From<JvmDouble> for f64 {
     }
 }
 
+impl JvmDouble {
+    /// The remainder of drem (JVMS §6.5.drem). Unlike IEEE 754 remainder, the quotient is truncated (like C's fmod),
+    /// so the result has the sign of the dividend. NaN operands, an infinite dividend and a zero divisor result in
+    /// NaN, an infinite divisor or a zero dividend result in the dividend.
+    pub fn remainder(self, divisor: Self) -> Self {
+        let (dividend, divisor) = (self.0, divisor.0);
+        if dividend.is_nan() || divisor.is_nan() || dividend.is_infinite() || divisor == 0.0 {
+            Self(f64::NAN)
+        } else if divisor.is_infinite() || dividend == 0.0 {
+            Self(dividend)
+        } else {
+            // Exact for finite operands, so no rounding mode is involved
+            Self(dividend % divisor)
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[repr(transparent)]
 pub struct JvmReference(pub HeapIndex);
@@ -142,15 +180,46 @@ impl JvmValue {
         unsafe { JvmReference(self.reference) }
     }
 
+    /// # Safety
+    /// Only the field that matches the type of the value may be read from the result
     pub unsafe fn from_native(value: i64) -> Self {
         Self { void: value }
     }
 
+    /// # Safety
+    /// The value must have been written with a type that fills all 64 bits or come from from_native
     pub unsafe fn to_native(self) -> i64 {
         self.void
     }
 }
 
+macro_rules! value_from {
+    ($ty:ty, $field:ident, $target:ty) => {
+        impl From<$ty> for JvmValue {
+            fn from(value: $ty) -> Self {
+                JvmValue {
+                    $field: value as $target,
+                }
+            }
+        }
+    };
+}
+
+value_from!(i32, int, i32);
+value_from!(i64, long, i64);
+value_from!(f32, float, f32);
+value_from!(f64, double, f64);
+value_from!(i8, int, i32);
+value_from!(i16, int, i32);
+value_from!(u16, int, i32); // Java's char
+value_from!(bool, int, i32);
+
+impl From<HeapIndex> for JvmValue {
+    fn from(value: HeapIndex) -> Self {
+        JvmValue { reference: value }
+    }
+}
+
 impl Default for JvmValue {
     fn default() -> Self {
         JvmValue::VOID
@@ -1,4 +1,4 @@
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Visibility {
     Public,
     PackagePrivate,
@@ -18,4 +18,14 @@ impl Visibility {
             Self::PackagePrivate
         }
     }
-}
\ No newline at end of file
+
+    /// The flag of the visibility (ACC_PUBLIC, ACC_PRIVATE or ACC_PROTECTED), zero for package-private
+    pub fn access_flags(self) -> u16 {
+        match self {
+            Self::Public => 0x0001,
+            Self::Private => 0x0002,
+            Self::Protected => 0x0004,
+            Self::PackagePrivate => 0,
+        }
+    }
+}
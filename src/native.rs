@@ -0,0 +1,223 @@
+use std::rc::Rc;
+
+#[cfg(asm_entry)]
+use dynasmrt::{dynasm, DynasmApi, ExecutableBuffer};
+
+#[cfg(asm_entry)]
+use crate::jit::{convention, perf_map};
+use crate::{
+    interpreter::{self, frame::FrameRecord, ExecutionError},
+    jit::CodeBuffer,
+    model::{
+        class_library::ClassLibrary,
+        heap::Heap,
+        method::{MethodImplementation, MethodIndex, MethodTable, NativeMethod},
+        stack::{StackFrame, StackPointer, StackValueWide},
+        types::JvmType,
+        value::JvmValue,
+    },
+};
+
+// Native methods that are implemented by Rust closures of the host.
+// Every bound method gets a small trampoline that translates the internal calling convention to sysv64
+// (like interpreter_trampoline) and additionally passes the closure to call_native_function.
+// Without the assembly entry, all bound methods share call_native_method, which looks the closure up by name.
+
+/// A host function that implements a native Java method. The arguments are unmarshaled according to the
+/// descriptor of the method, instance methods get `this` as their first argument.
+/// The returned value must match the return type of the method (JvmValue::VOID for void methods).
+/// A returned error terminates the method like an uncaught exception.
+pub type NativeFunction =
+    dyn Fn(&mut NativeContext, &[JvmValue]) -> Result<JvmValue, ExecutionError>;
+
+/// The VM state that is available to a native function while it runs
+pub struct NativeContext<'a> {
+    pub method: MethodIndex,
+    pub heap: &'a mut Heap,
+    pub classes: &'a ClassLibrary,
+    pub methods: &'a MethodTable,
+    pub stack: StackPointer, // The top of the Java stack, e.g. to call back into Java code
+}
+
+#[cfg(asm_entry)]
+struct NativeTrampoline {
+    code: ExecutableBuffer,
+    _function: Box<Rc<NativeFunction>>, // The trampoline passes a pointer to this box to call_native_function
+}
+
+#[cfg(asm_entry)]
+impl CodeBuffer for NativeTrampoline {
+    fn code_size(&self) -> usize {
+        self.code.len()
+    }
+}
+
+/// Creates the implementation of a native method that calls the function
+#[cfg(asm_entry)]
+pub fn bind(function: Rc<NativeFunction>, name: &str) -> MethodImplementation {
+    let function = Box::new(function);
+    let function_pointer = &*function as *const Rc<NativeFunction>;
+
+    let mut ops = dynasmrt::x64::Assembler::new().unwrap();
+    let start_offset = ops.offset();
+    dynasm!(ops
+        ; .arch x64
+        ; push rbp      // Aligns the stack to 16B and links the frame into the native frame chain
+        ; mov rbp, rsp
+    );
+    // The method_index is already placed in rdi
+    convention::emit_pass_context(&mut ops);
+    dynasm!(ops
+        ; .arch x64
+        ; mov r9, QWORD function_pointer as i64
+        ; mov rax, QWORD call_native_function as *const () as i64
+        ; call rax
+        ; pop rbp
+        ; ret
+    );
+    let code = ops
+        .finalize()
+        .expect("Failed to create the executable buffer");
+
+    perf_map::register_code(
+        code.ptr(start_offset) as usize,
+        code.len() - start_offset.0,
+        &format!("native {}", name),
+    );
+
+    let entry: NativeMethod = unsafe { std::mem::transmute(code.ptr(start_offset)) };
+    MethodImplementation::Native(
+        Box::new(entry),
+        Box::new(NativeTrampoline {
+            code,
+            _function: function,
+        }),
+    )
+}
+
+#[cfg(not(asm_entry))]
+struct BoundFunction {
+    _function: Rc<NativeFunction>, // Only kept for the lifetime of the implementation, see call_native_method
+}
+
+#[cfg(not(asm_entry))]
+impl CodeBuffer for BoundFunction {}
+
+/// Creates the implementation of a native method that calls the function
+#[cfg(not(asm_entry))]
+pub fn bind(function: Rc<NativeFunction>, _name: &str) -> MethodImplementation {
+    MethodImplementation::Native(
+        Box::new(call_native_method as NativeMethod),
+        Box::new(BoundFunction {
+            _function: function,
+        }),
+    )
+}
+
+#[cfg(asm_entry)]
+extern "sysv64" fn call_native_function(
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+    function: *const Rc<NativeFunction>,
+) -> i64 {
+    let (heap, classes, methods, function) =
+        unsafe { (&mut *heap, &*classes, &*methods, &*function) };
+    run_native_function(method_index, stack, heap, classes, methods, function)
+}
+
+/// The shared entry of all native methods without the assembly entry
+#[cfg(not(asm_entry))]
+extern "C" fn call_native_method(
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: *mut Heap,
+    classes: *const ClassLibrary,
+    methods: *const MethodTable,
+) -> i64 {
+    let (heap, classes, methods) = unsafe { (&mut *heap, &*classes, &*methods) };
+    let method = methods.get_data(method_index);
+    let class_name = classes
+        .resolve(method.owning_class)
+        .name()
+        .unwrap_or("<unknown>");
+    // Only methods with a registered function are bound
+    let function = methods
+        .native_function(class_name, &method.name, &method.descriptor)
+        .expect("No function registered for a bound native method");
+    run_native_function(method_index, stack, heap, classes, methods, &function)
+}
+
+fn run_native_function(
+    method_index: MethodIndex,
+    stack: StackPointer,
+    heap: &mut Heap,
+    classes: &ClassLibrary,
+    methods: &MethodTable,
+    function: &Rc<NativeFunction>,
+) -> i64 {
+    let method = methods.get_data(method_index);
+    let record = FrameRecord::new(method_index);
+    let _active_frame = record.enter();
+
+    let frame = match StackFrame::prepare(stack, method.argument_count, method.argument_count, 0) {
+        Ok(frame) => frame,
+        Err(err) => {
+            interpreter::raise_error(err.into(), classes, methods);
+            return 0;
+        }
+    };
+    let mut arguments = Vec::with_capacity(method.parameters.len() + 1);
+    let mut slot = 0;
+    if method.argument_count > method.parameters.iter().map(|p| p.slots()).sum() {
+        arguments.push(JvmValue {
+            reference: frame.get_local(0).as_reference().to_heap_index(),
+        });
+        slot += 1;
+    }
+    for parameter in &method.parameters {
+        arguments.push(argument(&frame, slot, *parameter));
+        slot += parameter.slots();
+    }
+    // The native function may call Java methods, whose frames start at our arguments
+    drop(frame);
+
+    let mut context = NativeContext {
+        method: method_index,
+        heap,
+        classes,
+        methods,
+        stack,
+    };
+    match function(&mut context, &arguments) {
+        Ok(value) => unsafe { value.to_native() },
+        Err(err) => {
+            interpreter::raise_error(err, classes, methods);
+            0
+        }
+    }
+}
+
+fn argument(frame: &StackFrame, slot: usize, ty: JvmType) -> JvmValue {
+    let value = frame.get_local(slot);
+    match ty {
+        JvmType::Long => JvmValue {
+            long: (value, frame.get_local(slot + 1)).as_long().into(),
+        },
+        JvmType::Double => JvmValue {
+            double: (value, frame.get_local(slot + 1)).as_double().into(),
+        },
+        JvmType::Float => JvmValue {
+            float: value.as_float().into(),
+        },
+        JvmType::Reference => JvmValue {
+            reference: value.as_reference().to_heap_index(),
+        },
+        // Narrow integral types are passed as int
+        _ => JvmValue {
+            int: value.as_int().into(),
+        },
+    }
+}
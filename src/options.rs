@@ -0,0 +1,446 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    class_loader::ModulePatch,
+    interpreter::{breakpoint::Breakpoint, watchpoint::Watchpoint},
+    jit::compile_command::CompileCommand,
+    model::{class_library::ClassLimits, gc::GarbageCollector, method::CompilationThresholds},
+};
+
+// References are stored as u32 in the stack slots (HeapIndex::as_u32), so every offset into the heap must fit into one
+const MAX_HEAP_SIZE: usize = u32::MAX as usize;
+
+/// Configuration of the VM, usually parsed from the command line
+#[derive(Debug, Clone)]
+pub struct VmOptions {
+    pub gc: GarbageCollector,
+    pub heap_size: usize, // In bytes (-Xmx), at most MAX_HEAP_SIZE
+    pub execution_mode: ExecutionMode,
+    pub compilation_thresholds: CompilationThresholds, // Only used in the mixed execution mode
+    pub compile_commands: Vec<CompileCommand>, // Restrict which methods may be compiled (-XX:CompileCommand)
+    pub code_cache_size: Option<usize>, // In bytes, the compiler is disabled once the compiled code exceeds it
+    pub class_limits: ClassLimits,
+    pub call_graph_dump: Option<PathBuf>, // Written as JSON if the file name ends with .json, as DOT otherwise
+    pub class_graph_dump: Option<PathBuf>, // Written like the call graph
+    pub heap_dump: Option<PathBuf>,       // Written when the main method has returned
+    pub dump_loaded_class_list: Option<PathBuf>, // Written like the heap dump, see model::class_list
+    pub replay_loaded_class_list: Option<PathBuf>, // Loaded before the main class
+    pub classpath: Vec<PathBuf>,
+    pub boot_classpath_append: Vec<PathBuf>, // Searched after the classpath (-Xbootclasspath/a:)
+    pub module_patches: Vec<ModulePatch>,    // Searched before the classpath (--patch-module)
+    pub boot_jdk: Option<PathBuf>, // The modules of the JDK are searched after the appended boot classpath
+    pub trace_methods: bool, // Print every method that is entered or exited by the interpreter
+    pub watchpoints: Vec<Watchpoint>, // Report the accesses of fields by the interpreter (-XX:WatchField)
+    pub breakpoints: Vec<Breakpoint>, // Stop the interpreter in the console debugger (-XX:Breakpoint)
+    pub check_module_access: bool,    // Enforce the readability and exports of named modules
+    pub enable_preview: bool, // Load classes that depend on the preview features of the latest supported release
+    pub fuse_instructions: bool, // Replace frequent instruction sequences by superinstructions in the interpreter
+    pub print_fusion_statistics: bool,
+    pub print_resolution_statistics: bool, // Per class, see model::resolution_statistics
+    pub eliminate_tail_calls: bool, // Reuse the frame for static calls of a method by itself in tail position
+    pub print_generic_signatures: bool, // Print the generic signatures of the loaded classes when the VM exits
+    pub print_annotations: bool,
+    pub check_stack_tags: bool, // Assert that the interpreter accesses every stack slot and local with its type
+    pub checkpoint_to: Option<PathBuf>, // Written right before the main method is called
+    pub restore_from: Option<PathBuf>,
+    pub list_unsupported: bool, // Print the opcodes that can't be executed and exit
+    pub print_version: bool,    // Print the version and the capabilities of the VM and exit
+    pub print_class_file: Option<PathBuf>, // Print the structure of the class file like javap -v and exit
+    pub fuzz_method: Option<PathBuf>, // Run the fuzzing harness on the file and exit (requires the feature fuzzing)
+    pub embedded_boot_classes: bool, // Search the bootstrap classes that are compiled into the binary last
+    pub verify: bool,                // Verify the code of every loaded class (-Xverify:all)
+    pub verification_cache: Option<PathBuf>, // Class files that have already been verified
+    pub main_class: Option<String>,  // In internal form, the first argument that isn't an option
+    pub jar: Option<PathBuf>,        // Names the main class in its manifest instead (-jar)
+    pub arguments: Vec<String>, // Passed to the main method, everything after the main class or the jar
+}
+
+/// Decides which methods are run by the interpreter and which are compiled by the JIT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// Only selected methods are compiled, everything else is interpreted
+    Mixed,
+    /// Never compile anything (-Xint)
+    Interpreted,
+    /// Compile every method on its first call (-Xcomp). Methods containing opcodes that the JIT doesn't
+    /// support yet are still interpreted.
+    Compiled,
+    /// Interpret everything, but additionally run every method without side effects that the JIT can compile as
+    /// compiled code and report differences in the results (-XX:+CrossCheckTiers)
+    CrossCheck,
+}
+
+impl Default for VmOptions {
+    fn default() -> Self {
+        Self {
+            gc: GarbageCollector::Epsilon,
+            heap_size: 64 << 20,
+            execution_mode: ExecutionMode::Mixed,
+            compilation_thresholds: CompilationThresholds {
+                invocations: 1500,
+                back_edges: 100000,
+            },
+            compile_commands: Vec::new(),
+            code_cache_size: None,
+            class_limits: ClassLimits::default(),
+            call_graph_dump: None,
+            class_graph_dump: None,
+            heap_dump: None,
+            dump_loaded_class_list: None,
+            replay_loaded_class_list: None,
+            classpath: vec![PathBuf::from(".")],
+            boot_classpath_append: Vec::new(),
+            module_patches: Vec::new(),
+            boot_jdk: None,
+            trace_methods: false,
+            watchpoints: Vec::new(),
+            breakpoints: Vec::new(),
+            check_module_access: false,
+            enable_preview: false,
+            fuse_instructions: true,
+            print_fusion_statistics: false,
+            print_resolution_statistics: false,
+            eliminate_tail_calls: true,
+            print_generic_signatures: false,
+            print_annotations: false,
+            check_stack_tags: false,
+            checkpoint_to: None,
+            restore_from: None,
+            list_unsupported: false,
+            print_version: false,
+            print_class_file: None,
+            fuzz_method: None,
+            embedded_boot_classes: true,
+            verify: false,
+            verification_cache: None,
+            main_class: None,
+            jar: None,
+            arguments: Vec::new(),
+        }
+    }
+}
+
+impl VmOptions {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, OptionsError> {
+        let mut options = Self::default();
+        let mut selected_gc = None;
+        let mut explicit_classpath = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "-cp" || arg == "-classpath" || arg == "--class-path" {
+                let classpath = args
+                    .next()
+                    .ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                options.classpath = parse_classpath(&classpath);
+                explicit_classpath = true;
+            } else if arg == "-jar" {
+                // The main class and further classpath entries are taken from the manifest of the jar
+                let jar = PathBuf::from(
+                    args.next()
+                        .ok_or_else(|| OptionsError::MissingValue(arg.clone()))?,
+                );
+                options.classpath = vec![jar.clone()];
+                options.jar = Some(jar);
+                explicit_classpath = true;
+                // Like in the java launcher, the remaining arguments belong to the program
+                options.arguments = args.by_ref().collect();
+            } else if let Some(paths) = arg.strip_prefix("-Xbootclasspath/a:") {
+                options
+                    .boot_classpath_append
+                    .extend(std::env::split_paths(paths));
+            } else if arg == "--patch-module" || arg.starts_with("--patch-module=") {
+                let patch = match arg.strip_prefix("--patch-module=") {
+                    Some(patch) => patch.to_string(),
+                    None => args
+                        .next()
+                        .ok_or_else(|| OptionsError::MissingValue(arg.clone()))?,
+                };
+                // <module>=<path>(:<path>)*
+                let (module, paths) = patch
+                    .split_once('=')
+                    .ok_or_else(|| OptionsError::InvalidValue(arg.clone(), patch.clone()))?;
+                options.module_patches.push(ModulePatch {
+                    module: module.to_string(),
+                    paths: std::env::split_paths(paths).collect(),
+                });
+            } else if arg == "--boot-jdk" || arg.starts_with("--boot-jdk=") {
+                let jdk = match arg.strip_prefix("--boot-jdk=") {
+                    Some(jdk) => jdk.to_string(),
+                    None => args
+                        .next()
+                        .ok_or_else(|| OptionsError::MissingValue(arg.clone()))?,
+                };
+                options.boot_jdk = Some(PathBuf::from(jdk));
+            } else if arg == "--enable-preview" {
+                options.enable_preview = true;
+            } else if arg == "--version" {
+                options.print_version = true;
+            } else if arg == "--list-unsupported" {
+                options.list_unsupported = true;
+            } else if arg == "-XX:+EmbeddedBootClasses" || arg == "-XX:-EmbeddedBootClasses" {
+                options.embedded_boot_classes = arg.starts_with("-XX:+");
+            } else if arg == "-Xverify:all" || arg == "-Xverify:none" {
+                options.verify = arg == "-Xverify:all";
+            } else if arg == "-Xint" {
+                // Like in HotSpot, the last of -Xint, -Xcomp and -XX:+CrossCheckTiers wins
+                options.execution_mode = ExecutionMode::Interpreted;
+            } else if arg == "-Xcomp" {
+                options.execution_mode = ExecutionMode::Compiled;
+            } else if arg == "-XX:+CrossCheckTiers" {
+                options.execution_mode = ExecutionMode::CrossCheck;
+            } else if arg == "-XX:+TraceMethods" {
+                options.trace_methods = true;
+            } else if arg == "-XX:+CheckModuleAccess" {
+                options.check_module_access = true;
+            } else if arg == "-XX:+FuseInstructions" || arg == "-XX:-FuseInstructions" {
+                options.fuse_instructions = arg.starts_with("-XX:+");
+            } else if arg == "-XX:+PrintFusionStatistics" {
+                options.print_fusion_statistics = true;
+            } else if arg == "-XX:+PrintResolutionStatistics" {
+                options.print_resolution_statistics = true;
+            } else if arg == "-XX:+EliminateTailCalls" || arg == "-XX:-EliminateTailCalls" {
+                options.eliminate_tail_calls = arg.starts_with("-XX:+");
+            } else if arg == "-XX:+PrintGenericSignatures" {
+                options.print_generic_signatures = true;
+            } else if arg == "-XX:+PrintAnnotations" {
+                options.print_annotations = true;
+            } else if arg == "-XX:+CheckStackTags" {
+                options.check_stack_tags = true;
+            } else if let Some(threshold) = arg.strip_prefix("-XX:CompileThreshold=") {
+                options.compilation_thresholds.invocations = threshold
+                    .parse()
+                    .map_err(|_| OptionsError::InvalidValue(arg.clone(), threshold.to_string()))?;
+            } else if let Some(threshold) = arg.strip_prefix("-XX:BackEdgeThreshold=") {
+                options.compilation_thresholds.back_edges = threshold
+                    .parse()
+                    .map_err(|_| OptionsError::InvalidValue(arg.clone(), threshold.to_string()))?;
+            } else if let Some(command) = arg.strip_prefix("-XX:CompileCommand=") {
+                options.compile_commands.push(
+                    CompileCommand::parse(command).ok_or_else(|| {
+                        OptionsError::InvalidValue(arg.clone(), command.to_string())
+                    })?,
+                );
+            } else if let Some(watchpoint) = arg.strip_prefix("-XX:WatchField=") {
+                options
+                    .watchpoints
+                    .push(Watchpoint::parse(watchpoint).ok_or_else(|| {
+                        OptionsError::InvalidValue(arg.clone(), watchpoint.to_string())
+                    })?);
+            } else if let Some(breakpoint) = arg.strip_prefix("-XX:Breakpoint=") {
+                options
+                    .breakpoints
+                    .push(Breakpoint::parse(breakpoint).ok_or_else(|| {
+                        OptionsError::InvalidValue(arg.clone(), breakpoint.to_string())
+                    })?);
+            } else if let Some(size) = arg
+                .strip_prefix("-Xmx")
+                .or_else(|| arg.strip_prefix("-XX:MaxHeapSize="))
+            {
+                options.heap_size = parse_size(size)
+                    .filter(|size| (1..=MAX_HEAP_SIZE).contains(size))
+                    .ok_or_else(|| OptionsError::InvalidValue(arg.clone(), size.to_string()))?;
+            } else if let Some(size) = arg.strip_prefix("-XX:ReservedCodeCacheSize=") {
+                options.code_cache_size =
+                    Some(parse_size(size).ok_or_else(|| {
+                        OptionsError::InvalidValue(arg.clone(), size.to_string())
+                    })?);
+            } else if let Some(count) = arg.strip_prefix("-XX:MaxLoadedClasses=") {
+                options.class_limits.classes = Some(
+                    count
+                        .parse()
+                        .map_err(|_| OptionsError::InvalidValue(arg.clone(), count.to_string()))?,
+                );
+            } else if let Some(size) = arg.strip_prefix("-XX:MaxConstantPoolSize=") {
+                options.class_limits.constant_pool_bytes =
+                    Some(parse_size(size).ok_or_else(|| {
+                        OptionsError::InvalidValue(arg.clone(), size.to_string())
+                    })?);
+            } else if let Some(path) = arg.strip_prefix("-XX:VerificationCache=") {
+                options.verification_cache = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("-XX:CheckpointTo=") {
+                options.checkpoint_to = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("-XX:RestoreFrom=") {
+                options.restore_from = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("-XX:PrintClassFile=") {
+                options.print_class_file = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("-XX:FuzzMethod=") {
+                options.fuzz_method = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("-XX:CallGraphDump=") {
+                options.call_graph_dump = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("-XX:ClassGraphDump=") {
+                options.class_graph_dump = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("-XX:HeapDumpPath=") {
+                options.heap_dump = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("-XX:DumpLoadedClassList=") {
+                options.dump_loaded_class_list = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("-XX:ReplayLoadedClassList=") {
+                options.replay_loaded_class_list = Some(PathBuf::from(path));
+            } else if let Some(flag) = arg.strip_prefix("-XX:+Use") {
+                let gc = GarbageCollector::from_flag_name(flag)
+                    .ok_or_else(|| OptionsError::UnknownOption(arg.clone()))?;
+                if let Some(previous) = selected_gc.replace(gc) {
+                    if previous != gc {
+                        return Err(OptionsError::ConflictingCollectors(previous, gc));
+                    }
+                }
+            } else if !arg.starts_with('-') {
+                options.main_class = Some(arg.replace('.', "/"));
+                options.arguments = args.by_ref().collect();
+            } else {
+                return Err(OptionsError::UnknownOption(arg));
+            }
+        }
+
+        if let Some(gc) = selected_gc {
+            options.gc = gc;
+        }
+
+        // Like the java launcher, the CLASSPATH environment variable replaces the default classpath (the working
+        // directory), but not the one that is given on the command line
+        if !explicit_classpath {
+            if let Some(classpath) = std::env::var_os("CLASSPATH") {
+                options.classpath = parse_classpath(&classpath.to_string_lossy());
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Splits a classpath at the separator of the platform (':' or ';'). Empty entries stand for the working directory,
+/// and an entry whose last component is * stands for the jar files in that directory (in the order of their names).
+fn parse_classpath(classpath: &str) -> Vec<PathBuf> {
+    let mut entries = Vec::new();
+    for path in std::env::split_paths(classpath) {
+        if path.as_os_str().is_empty() {
+            entries.push(PathBuf::from("."));
+        } else if path.file_name().is_some_and(|name| name == "*") {
+            let directory = path.parent().unwrap_or(Path::new(""));
+            let directory = if directory.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                directory
+            };
+            let mut jars = std::fs::read_dir(directory)
+                .map(|files| {
+                    files
+                        .filter_map(|file| Some(file.ok()?.path()))
+                        .filter(|file| {
+                            file.extension()
+                                .is_some_and(|extension| extension.eq_ignore_ascii_case("jar"))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            jars.sort();
+            entries.extend(jars);
+        } else {
+            entries.push(path);
+        }
+    }
+    entries
+}
+
+/// Parses a size in bytes with an optional unit like HotSpot does, e.g. "512", "64k", "48M" or "1g"
+fn parse_size(size: &str) -> Option<usize> {
+    let (number, factor) = match size.chars().last()? {
+        'k' | 'K' => (&size[..size.len() - 1], 1 << 10),
+        'm' | 'M' => (&size[..size.len() - 1], 1 << 20),
+        'g' | 'G' => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1),
+    };
+    number.parse::<usize>().ok()?.checked_mul(factor)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum OptionsError {
+    #[error("unrecognized option '{0}'")]
+    UnknownOption(String),
+
+    #[error("option '{0}' requires a value")]
+    MissingValue(String),
+
+    #[error("invalid value '{1}' for option '{0}'")]
+    InvalidValue(String, String),
+
+    #[error("conflicting garbage collectors selected: {0} and {1}")]
+    ConflictingCollectors(GarbageCollector, GarbageCollector),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{parse_classpath, ExecutionMode, VmOptions};
+
+    fn parse(args: &[&str]) -> VmOptions {
+        VmOptions::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn passes_the_arguments_after_the_main_class_to_the_program() {
+        let options = parse(&["-Xint", "com.example.Main", "-Xcomp", "b"]);
+        assert_eq!(options.main_class.as_deref(), Some("com/example/Main"));
+        assert_eq!(options.arguments, ["-Xcomp", "b"]);
+        assert_eq!(options.execution_mode, ExecutionMode::Interpreted);
+        assert_eq!(options.jar, None);
+    }
+
+    #[test]
+    fn passes_the_arguments_after_the_jar_to_the_program() {
+        let options = parse(&["-jar", "app.jar", "Main", "-cp"]);
+        assert_eq!(options.jar, Some(PathBuf::from("app.jar")));
+        assert_eq!(options.classpath, [PathBuf::from("app.jar")]);
+        assert_eq!(options.main_class, None);
+        assert_eq!(options.arguments, ["Main", "-cp"]);
+    }
+
+    #[test]
+    fn last_execution_mode_flag_wins() {
+        let options = parse(&["-Xcomp", "-XX:+CrossCheckTiers", "-Xint"]);
+        assert_eq!(options.execution_mode, ExecutionMode::Interpreted);
+        let options = parse(&["-Xint", "-Xcomp"]);
+        assert_eq!(options.execution_mode, ExecutionMode::Compiled);
+    }
+
+    #[test]
+    fn parses_the_maximum_heap_size() {
+        assert_eq!(parse(&["-Xmx512k"]).heap_size, 512 << 10);
+        assert_eq!(parse(&["-XX:MaxHeapSize=2g"]).heap_size, 2 << 30);
+        // The last one wins
+        assert_eq!(parse(&["-Xmx1g", "-Xmx48M"]).heap_size, 48 << 20);
+        for size in ["-Xmx4g", "-Xmx0", "-Xmx", "-XX:MaxHeapSize=big"] {
+            assert!(
+                VmOptions::parse([size.to_string()]).is_err(),
+                "{} has been accepted",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn expands_wildcards_to_the_jars_in_the_directory() {
+        let directory = std::env::temp_dir().join(format!("jvm-classpath-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        for file in ["b.jar", "a.JAR", "c.zip", "notes.txt"] {
+            std::fs::write(directory.join(file), b"").unwrap();
+        }
+
+        let wildcard = directory.join("*");
+        let classpath =
+            std::env::join_paths([wildcard.as_path(), "lib".as_ref(), "".as_ref()]).unwrap();
+        let entries = parse_classpath(&classpath.to_string_lossy());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Only the jars, sorted by name
+        let mut expected: Vec<_> = ["a.JAR", "b.jar"]
+            .iter()
+            .map(|jar| directory.join(jar))
+            .collect();
+        expected.extend([PathBuf::from("lib"), PathBuf::from(".")]);
+        assert_eq!(entries, expected);
+        // A missing directory contributes no entries
+        assert!(parse_classpath(&wildcard.to_string_lossy()).is_empty());
+    }
+}
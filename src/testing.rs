@@ -0,0 +1,32 @@
+//! Runs the classes of tests/classes, which are compiled from the Java sources next to them with
+//! `javac --release 8`.
+
+use std::path::PathBuf;
+
+use crate::{jvm::Jvm, options::VmOptions};
+
+/// A VM whose classpath holds the test classes, with the embedded bootstrap classes as the class library
+pub fn jvm() -> Jvm {
+    jvm_with(VmOptions::default())
+}
+
+/// Like jvm, but with the given options besides the classpath
+pub fn jvm_with(options: VmOptions) -> Jvm {
+    let mut jvm = Jvm::new(VmOptions {
+        classpath: vec![PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/classes"
+        ))],
+        ..options
+    });
+    jvm.load_class("classes/Object").unwrap();
+    jvm
+}
+
+/// Calls the static method of the class that takes no arguments and returns an int
+pub fn call_int(jvm: &mut Jvm, class: &str, method: &str) -> i32 {
+    match jvm.call_static(class, method, "()I", &[]) {
+        Ok(value) => unsafe { value.int },
+        Err(exception) => panic!("{}.{} failed: {}", class, method, exception),
+    }
+}
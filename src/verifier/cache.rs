@@ -0,0 +1,92 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// The cache (-XX:VerificationCache) is a text file that lists the hashes of the class files that have passed the
+// verifier, one per line. Class files are looked up by the hash of their contents, so a class file that has changed
+// since the last run is verified again, no matter where it has been loaded from. The first line names the version
+// of the verifier, a cache of another version is discarded because its checks may differ.
+// FNV-1a is used as the hash because it is stable across runs and Rust versions, unlike the hasher of the standard
+// library.
+
+const HEADER: &str = "jvm verification cache 1";
+
+pub struct VerificationCache {
+    path: PathBuf,
+    verified: HashSet<u64>,
+    modified: bool,
+}
+
+impl VerificationCache {
+    /// Reads the cache from the file, starts with an empty cache if the file doesn't exist or is invalid
+    pub fn open(path: &Path) -> Self {
+        let verified = match fs::read_to_string(path) {
+            Ok(contents) => parse(&contents).unwrap_or_else(|| {
+                log::warn!(
+                    "Discarding the verification cache {} of another version",
+                    path.display()
+                );
+                HashSet::new()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(err) => {
+                log::warn!(
+                    "Failed to read the verification cache {}: {}",
+                    path.display(),
+                    err
+                );
+                HashSet::new()
+            }
+        };
+        log::debug!(
+            "Loaded {} verified class files from {}",
+            verified.len(),
+            path.display()
+        );
+        Self {
+            path: path.to_path_buf(),
+            verified,
+            modified: false,
+        }
+    }
+
+    /// Whether the class file has already passed the verifier
+    pub fn contains(&self, class_file: &[u8]) -> bool {
+        self.verified.contains(&hash(class_file))
+    }
+
+    pub fn insert(&mut self, class_file: &[u8]) {
+        self.modified |= self.verified.insert(hash(class_file));
+    }
+
+    /// Writes the cache back to its file if classes have been added
+    pub fn save(&self) -> io::Result<()> {
+        if !self.modified {
+            return Ok(());
+        }
+        let mut contents = format!("{}\n", HEADER);
+        for hash in &self.verified {
+            contents.push_str(&format!("{:016x}\n", hash));
+        }
+        fs::write(&self.path, contents)
+    }
+}
+
+fn parse(contents: &str) -> Option<HashSet<u64>> {
+    let mut lines = contents.lines();
+    if lines.next()? != HEADER {
+        return None;
+    }
+    lines
+        .filter(|line| !line.is_empty())
+        .map(|line| u64::from_str_radix(line, 16).ok())
+        .collect()
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
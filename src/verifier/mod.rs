@@ -0,0 +1,293 @@
+pub mod cache;
+#[cfg_attr(not(feature = "fuzzing"), allow(dead_code))] // Only the fuzzing harness infers types so far
+pub mod types;
+
+use crate::{
+    bytecode,
+    class_parser::ClassData,
+    interpreter,
+    model::method::{MethodCode, MethodDescriptor},
+};
+
+use self::cache::VerificationCache;
+
+// The verifier checks the structure of the code of every method when its class is loaded (-Xverify:all): the code
+// consists of complete instructions with opcodes from the specification, branches and exception handlers point to
+// the start of an instruction, loads and stores stay within max_locals, return instructions match the return type
+// and the code can't fall off its end. Its type inference (see types) only models a part of the instructions, so it
+// isn't applied to loaded classes and inconsistent stack types are still only caught by the checked mode
+// (-XX:+CheckStackTags).
+// Verifying big applications on every start is expensive, so the results can be kept in a cache that is keyed by
+// the hash of the class file (see cache).
+
+/// Verifies the loaded classes, skipping the class files that the cache knows to be valid
+pub struct Verifier {
+    cache: Option<VerificationCache>,
+    verified: usize, // Classes that have actually been verified
+    cached: usize,   // Classes that have been skipped because of the cache
+}
+
+impl Verifier {
+    pub fn new(cache: Option<VerificationCache>) -> Self {
+        Self {
+            cache,
+            verified: 0,
+            cached: 0,
+        }
+    }
+
+    /// Verifies the methods of the class that has been parsed from the given class file
+    pub fn verify_class(
+        &mut self,
+        name: &str,
+        class_file: &[u8],
+        data: &ClassData,
+    ) -> Result<(), VerifyError> {
+        if let Some(cache) = &self.cache {
+            if cache.contains(class_file) {
+                self.cached += 1;
+                return Ok(());
+            }
+        }
+        for method in data.methods.iter().chain(&data.static_methods) {
+            verify_method(name, method)?;
+        }
+        self.verified += 1;
+        if let Some(cache) = &mut self.cache {
+            cache.insert(class_file);
+        }
+        Ok(())
+    }
+
+    /// Writes the cache (if any) back to its file, so that the next run can skip the classes verified in this run
+    pub fn save_cache(&self) {
+        log::info!(
+            "Verified {} classes, skipped {} verified classes of the cache",
+            self.verified,
+            self.cached
+        );
+        if let Some(cache) = &self.cache {
+            if let Err(err) = cache.save() {
+                log::warn!("Failed to write the verification cache: {}", err);
+            }
+        }
+    }
+}
+
+fn verify_method(class: &str, method: &MethodDescriptor) -> Result<(), VerifyError> {
+    let MethodCode::Bytecode(code) = &method.code else {
+        return Ok(());
+    };
+    let name = || format!("{}.{}{}", class, method.name, method.descriptor);
+    if code.is_empty() {
+        return Err(VerifyError::EmptyCode(name()));
+    }
+    if code.len() > u16::MAX as usize {
+        return Err(VerifyError::CodeTooLong(name()));
+    }
+
+    // Decode all instructions first, so that branch targets can be checked against the instruction starts
+    let mut starts = vec![false; code.len()];
+    let mut pc = 0;
+    let mut last = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        if !bytecode::is_verifiable(opcode) {
+            return Err(VerifyError::IllegalOpcode {
+                method: name(),
+                pc,
+                opcode,
+            });
+        }
+        let length = bytecode::instruction_length(code, pc)
+            .ok_or_else(|| VerifyError::TruncatedInstruction { method: name(), pc })?;
+        starts[pc] = true;
+        last = pc;
+        pc += length;
+    }
+    let is_start =
+        |target: i64| target >= 0 && (target as usize) < code.len() && starts[target as usize];
+
+    pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        for target in branch_targets(code, pc) {
+            if !is_start(target) {
+                return Err(VerifyError::InvalidBranchTarget { method: name(), pc });
+            }
+        }
+        if let Some((index, slots)) = local_access(code, pc) {
+            if index + slots > method.max_locals {
+                return Err(VerifyError::InvalidLocal {
+                    method: name(),
+                    pc,
+                    index,
+                    max_locals: method.max_locals,
+                });
+            }
+        }
+        if (bytecode::IRETURN..=bytecode::RETURN).contains(&opcode)
+            && !interpreter::is_return_for(opcode, method.return_type)
+        {
+            return Err(VerifyError::ReturnTypeMismatch { method: name(), pc });
+        }
+        pc += bytecode::instruction_length(code, pc).unwrap();
+    }
+
+    // Every path has to end in an unconditional transfer of control
+    if falls_through(code[last]) {
+        return Err(VerifyError::FallsOffEnd(name()));
+    }
+
+    for entry in &method.exception_table {
+        let end_is_valid = entry.end_pc == code.len() || is_start(entry.end_pc as i64);
+        if entry.start_pc >= entry.end_pc
+            || !is_start(entry.start_pc as i64)
+            || !end_is_valid
+            || !is_start(entry.handler_pc as i64)
+        {
+            return Err(VerifyError::InvalidExceptionHandler(name()));
+        }
+    }
+    Ok(())
+}
+
+/// Whether execution can continue with the next instruction, i.e. the instruction isn't an unconditional transfer of
+/// control
+pub fn falls_through(opcode: u8) -> bool {
+    !matches!(
+        opcode,
+        bytecode::IRETURN
+            ..=bytecode::RETURN
+                | bytecode::GOTO
+                | bytecode::GOTO_W
+                | bytecode::ATHROW
+                | bytecode::TABLESWITCH
+                | bytecode::LOOKUPSWITCH
+                | bytecode::RET
+    )
+}
+
+/// The targets of the branch at pc, empty if the instruction isn't a branch
+pub fn branch_targets(code: &[u8], pc: usize) -> Vec<i64> {
+    let read_i16 =
+        |position: usize| i16::from_be_bytes([code[position], code[position + 1]]) as i64;
+    let read_i32 = |position: usize| {
+        i32::from_be_bytes([
+            code[position],
+            code[position + 1],
+            code[position + 2],
+            code[position + 3],
+        ]) as i64
+    };
+    let base = pc as i64;
+    match code[pc] {
+        bytecode::IFEQ..=bytecode::JSR | bytecode::IFNULL | bytecode::IFNONNULL => {
+            vec![base + read_i16(pc + 1)]
+        }
+        bytecode::GOTO_W | bytecode::JSR_W => vec![base + read_i32(pc + 1)],
+        // The operands of the switches are aligned to four bytes, relative to the start of the code
+        bytecode::TABLESWITCH => {
+            let operands = pc + 1 + (3 - pc % 4);
+            let low = read_i32(operands + 4);
+            let high = read_i32(operands + 8);
+            let mut targets = vec![base + read_i32(operands)];
+            for i in 0..(high - low + 1).max(0) as usize {
+                targets.push(base + read_i32(operands + 12 + 4 * i));
+            }
+            targets
+        }
+        bytecode::LOOKUPSWITCH => {
+            let operands = pc + 1 + (3 - pc % 4);
+            let pairs = read_i32(operands + 4);
+            let mut targets = vec![base + read_i32(operands)];
+            for i in 0..pairs.max(0) as usize {
+                targets.push(base + read_i32(operands + 8 + 8 * i + 4));
+            }
+            targets
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Returns (index, slots) of the local that the instruction at pc reads or writes
+fn local_access(code: &[u8], pc: usize) -> Option<(usize, usize)> {
+    // The typed variants are ordered int, long, float, double, reference
+    let slots = |kind: u8| if kind == 1 || kind == 3 { 2 } else { 1 };
+    match code[pc] {
+        opcode @ bytecode::ILOAD..=bytecode::ALOAD => {
+            Some((code[pc + 1] as usize, slots(opcode - bytecode::ILOAD)))
+        }
+        opcode @ bytecode::ISTORE..=bytecode::ASTORE => {
+            Some((code[pc + 1] as usize, slots(opcode - bytecode::ISTORE)))
+        }
+        opcode @ bytecode::ILOAD_0..=bytecode::ALOAD_3 => {
+            let offset = opcode - bytecode::ILOAD_0;
+            Some(((offset % 4) as usize, slots(offset / 4)))
+        }
+        opcode @ bytecode::ISTORE_0..=bytecode::ASTORE_3 => {
+            let offset = opcode - bytecode::ISTORE_0;
+            Some(((offset % 4) as usize, slots(offset / 4)))
+        }
+        bytecode::IINC | bytecode::RET => Some((code[pc + 1] as usize, 1)),
+        bytecode::WIDE => {
+            let index = u16::from_be_bytes([code[pc + 2], code[pc + 3]]) as usize;
+            match code[pc + 1] {
+                opcode @ bytecode::ILOAD..=bytecode::ALOAD => {
+                    Some((index, slots(opcode - bytecode::ILOAD)))
+                }
+                opcode @ bytecode::ISTORE..=bytecode::ASTORE => {
+                    Some((index, slots(opcode - bytecode::ISTORE)))
+                }
+                _ => Some((index, 1)),
+            }
+        }
+        _ => None,
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum VerifyError {
+    #[error("the code of {0} is empty")]
+    EmptyCode(String),
+
+    #[error("the code of {0} is longer than 65535 bytes")]
+    CodeTooLong(String),
+
+    #[error("{method} contains the illegal opcode {opcode:#04x} at pc {pc}")]
+    IllegalOpcode {
+        method: String,
+        pc: usize,
+        opcode: u8,
+    },
+
+    #[error("the instruction at pc {pc} of {method} is truncated")]
+    TruncatedInstruction { method: String, pc: usize },
+
+    #[error("the branch at pc {pc} of {method} doesn't jump to the start of an instruction")]
+    InvalidBranchTarget { method: String, pc: usize },
+
+    #[error("the instruction at pc {pc} of {method} accesses the local {index}, but the method has only {max_locals} locals")]
+    InvalidLocal {
+        method: String,
+        pc: usize,
+        index: usize,
+        max_locals: usize,
+    },
+
+    #[error("the return instruction at pc {pc} of {method} doesn't match the return type")]
+    ReturnTypeMismatch { method: String, pc: usize },
+
+    #[error("the code of {0} can fall off its end")]
+    FallsOffEnd(String),
+
+    #[error("an exception handler of {0} covers an invalid range of the code")]
+    InvalidExceptionHandler(String),
+
+    #[error("the types of {method} are inconsistent at pc {pc}: {reason}")]
+    InconsistentTypes {
+        method: String,
+        pc: usize,
+        reason: String,
+    },
+}
@@ -0,0 +1,597 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    bytecode,
+    model::{
+        access_flags::MethodFlags,
+        class::{Class, LoadableConstant},
+        constant_pool::ConstantPoolIndex,
+        method::MethodData,
+        types::JvmType,
+    },
+};
+
+use super::{branch_targets, VerifyError};
+
+// The type inference of the verifier, which computes the types of the operand stack and the locals at every
+// instruction like the type checker of the specification (JVMS §4.10). It only models the instructions that don't
+// depend on the class library: constants, locals, arithmetic, comparisons, branches, stack manipulation, primitive
+// arrays and static fields of primitive types. References are either null or primitive arrays, so code that creates
+// or uses objects, calls methods or has exception handlers is rejected. Code that passes the inference can't make
+// the interpreter misinterpret its operand stack.
+
+/// The type of a slot of the operand stack or of a local
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Int,
+    Float,
+    Long,   // The first slot of a long
+    Double, // The first slot of a double
+    Top,    // The second slot of a long or double
+    Null,
+    Array(u8), // A primitive array (or null) with the atype of newarray
+}
+
+impl Slot {
+    fn of(ty: JvmType) -> &'static [Slot] {
+        match ty {
+            JvmType::Void => &[],
+            JvmType::Long => &[Slot::Long, Slot::Top],
+            JvmType::Float => &[Slot::Float],
+            JvmType::Double => &[Slot::Double, Slot::Top],
+            JvmType::Reference => &[Slot::Null],
+            _ => &[Slot::Int],
+        }
+    }
+
+    /// The type of both slots, None if they can't be merged. Null can be merged with any array.
+    fn merge(self, other: Self) -> Option<Self> {
+        match (self, other) {
+            _ if self == other => Some(self),
+            (Slot::Null, Slot::Array(_)) => Some(other),
+            (Slot::Array(_), Slot::Null) => Some(self),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Slot::Int => write!(f, "int"),
+            Slot::Float => write!(f, "float"),
+            Slot::Long => write!(f, "long"),
+            Slot::Double => write!(f, "double"),
+            Slot::Top => write!(f, "the second half of a long or double"),
+            Slot::Null => write!(f, "null"),
+            Slot::Array(atype) => write!(f, "{}[]", element_type(*atype)),
+        }
+    }
+}
+
+/// The element type of an array by the atype of newarray (JVMS §6.5.newarray)
+fn element_type(atype: u8) -> &'static str {
+    match atype {
+        4 => "boolean",
+        5 => "char",
+        6 => "float",
+        7 => "double",
+        8 => "byte",
+        9 => "short",
+        10 => "int",
+        _ => "long",
+    }
+}
+
+/// The types of the operand stack and the locals before an instruction, None for locals that can't be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+    stack: Vec<Slot>,
+    locals: Vec<Option<Slot>>,
+}
+
+impl Frame {
+    fn pop(&mut self, expected: Slot) -> Result<(), String> {
+        match self.stack.pop() {
+            Some(actual) if actual == expected => Ok(()),
+            Some(actual) => Err(format!("expected {}, found {}", expected, actual)),
+            None => Err(format!(
+                "expected {}, found an empty operand stack",
+                expected
+            )),
+        }
+    }
+
+    fn pop_type(&mut self, ty: JvmType) -> Result<(), String> {
+        for slot in Slot::of(ty).iter().rev() {
+            self.pop(*slot)?;
+        }
+        Ok(())
+    }
+
+    fn push_type(&mut self, ty: JvmType) {
+        self.stack.extend(Slot::of(ty));
+    }
+
+    /// Pops an array of one of the given atypes (or null)
+    fn pop_array(&mut self, atypes: &[u8]) -> Result<(), String> {
+        match self.stack.pop() {
+            Some(Slot::Null) => Ok(()),
+            Some(Slot::Array(atype)) if atypes.is_empty() || atypes.contains(&atype) => Ok(()),
+            actual => {
+                let expected = match atypes {
+                    [] => "an array".to_string(),
+                    _ => atypes
+                        .iter()
+                        .map(|atype| format!("{}[]", element_type(*atype)))
+                        .collect::<Vec<_>>()
+                        .join(" or "),
+                };
+                match actual {
+                    Some(actual) => Err(format!("expected {}, found {}", expected, actual)),
+                    None => Err(format!(
+                        "expected {}, found an empty operand stack",
+                        expected
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Describes why the local can't be loaded as the expected type
+    fn unexpected_local(&self, index: usize) -> String {
+        match self.locals.get(index) {
+            Some(Some(local)) => format!("local {} is {}", index, local),
+            Some(None) => format!("local {} has no value that can be read", index),
+            None => format!("local {} is out of range", index),
+        }
+    }
+
+    /// Removes the given number of slots from the top of the stack, which must not split a long or double
+    fn take(&mut self, slots: usize) -> Result<Vec<Slot>, String> {
+        if self.stack.len() < slots {
+            return Err("operand stack underflow".to_string());
+        }
+        let taken = self.stack.split_off(self.stack.len() - slots);
+        if taken.first() == Some(&Slot::Top) {
+            return Err("splits a long or double".to_string());
+        }
+        Ok(taken)
+    }
+
+    /// Pushes the local, which must be of the type
+    fn load(&mut self, index: usize, ty: JvmType) -> Result<(), String> {
+        if matches!(ty, JvmType::Reference) {
+            return match self.locals.get(index) {
+                Some(Some(local @ (Slot::Null | Slot::Array(_)))) => {
+                    self.stack.push(*local);
+                    Ok(())
+                }
+                _ => Err(self.unexpected_local(index)),
+            };
+        }
+        for (offset, slot) in Slot::of(ty).iter().enumerate() {
+            match self.locals.get(index + offset) {
+                Some(Some(local)) if local == slot => self.stack.push(*local),
+                _ => return Err(self.unexpected_local(index + offset)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Pops a value of the type into the local
+    fn store(&mut self, index: usize, ty: JvmType) -> Result<(), String> {
+        let slots = Slot::of(ty).len();
+        let value = self.take(slots)?;
+        let matches = match ty {
+            JvmType::Reference => matches!(value[0], Slot::Null | Slot::Array(_)),
+            _ => value == Slot::of(ty),
+        };
+        if !matches {
+            let value = value
+                .iter()
+                .map(|slot| slot.to_string())
+                .collect::<Vec<_>>()
+                .join(" and ");
+            return Err(format!("expected {}, found {}", ty, value));
+        }
+        if index + slots > self.locals.len() {
+            return Err(format!("local {} is out of range", index));
+        }
+        // Overwriting a half of a long or double invalidates the other half
+        if self.locals[index] == Some(Slot::Top) {
+            self.locals[index - 1] = None;
+        }
+        if matches!(
+            self.locals[index + slots - 1],
+            Some(Slot::Long | Slot::Double)
+        ) {
+            self.locals[index + slots] = None;
+        }
+        for (offset, slot) in value.into_iter().enumerate() {
+            self.locals[index + offset] = Some(slot);
+        }
+        Ok(())
+    }
+
+    /// Merges the other frame into this one, returns whether this frame has changed
+    fn merge(&mut self, other: &Frame) -> Result<bool, String> {
+        if self.stack.len() != other.stack.len() {
+            return Err("the operand stack has different depths on two paths".to_string());
+        }
+        let mut changed = false;
+        for (slot, other) in self.stack.iter_mut().zip(&other.stack) {
+            let merged = slot
+                .merge(*other)
+                .ok_or_else(|| format!("merges {} and {} on the operand stack", slot, other))?;
+            changed |= merged != *slot;
+            *slot = merged;
+        }
+        for (local, other) in self.locals.iter_mut().zip(&other.locals) {
+            let merged = match (*local, *other) {
+                (Some(local), Some(other)) => local.merge(other),
+                _ => None,
+            };
+            changed |= merged != *local;
+            *local = merged;
+        }
+        Ok(changed)
+    }
+}
+
+/// The array loads and stores as (opcode, atypes, element type)
+const ARRAY_ACCESSES: &[(u8, u8, &[u8], JvmType)] = &[
+    (bytecode::IALOAD, bytecode::IASTORE, &[10], JvmType::Integer),
+    (bytecode::LALOAD, bytecode::LASTORE, &[11], JvmType::Long),
+    (bytecode::FALOAD, bytecode::FASTORE, &[6], JvmType::Float),
+    (bytecode::DALOAD, bytecode::DASTORE, &[7], JvmType::Double),
+    (
+        bytecode::BALOAD,
+        bytecode::BASTORE,
+        &[4, 8],
+        JvmType::Integer,
+    ),
+    (bytecode::CALOAD, bytecode::CASTORE, &[5], JvmType::Integer),
+    (bytecode::SALOAD, bytecode::SASTORE, &[9], JvmType::Integer),
+];
+
+/// Infers the types of the operand stack and the locals at every instruction of the static method of the class.
+/// The code must have passed the structural checks of the verifier (see verify_class). Fails if the types are
+/// inconsistent or the code uses an instruction that the inference doesn't model.
+pub fn infer_types(class: &Class, method: &MethodData) -> Result<(), VerifyError> {
+    let name = || {
+        format!(
+            "{}.{}{}",
+            class.name().unwrap_or_default(),
+            method.name,
+            method.descriptor
+        )
+    };
+    let fail = |pc: usize, reason: String| VerifyError::InconsistentTypes {
+        method: name(),
+        pc,
+        reason,
+    };
+    if method.is_native || method.code.is_empty() {
+        return Err(fail(0, "the method has no code".to_string()));
+    }
+    if !method.flags.contains(MethodFlags::STATIC) {
+        return Err(fail(
+            0,
+            "the receiver of instance methods isn't modeled".to_string(),
+        ));
+    }
+    if !method.exception_table.is_empty() {
+        return Err(fail(0, "exception handlers aren't modeled".to_string()));
+    }
+    let code = &method.code;
+    let mut locals = method
+        .parameters
+        .iter()
+        .flat_map(|ty| Slot::of(*ty).iter().copied().map(Some))
+        .collect::<Vec<_>>();
+    if locals.len() > method.max_locals {
+        return Err(fail(
+            0,
+            "the parameters don't fit into max_locals".to_string(),
+        ));
+    }
+    locals.resize(method.max_locals, None);
+    let mut frames: Vec<Option<Frame>> = vec![None; code.len()];
+    frames[0] = Some(Frame {
+        stack: Vec::new(),
+        locals,
+    });
+    let mut worklist = vec![0];
+
+    while let Some(pc) = worklist.pop() {
+        let mut frame = frames[pc].clone().unwrap();
+        let next_pc = pc + bytecode::instruction_length(code, pc).unwrap();
+        let falls_through = execute(&mut frame, class, code, pc, method.return_type)
+            .map_err(|reason| fail(pc, reason))?;
+        if frame.stack.len() > method.max_stack {
+            return Err(fail(pc, "exceeds max_stack".to_string()));
+        }
+        let mut successors = branch_targets(code, pc)
+            .into_iter()
+            .map(|target| target as usize)
+            .collect::<Vec<_>>();
+        if falls_through {
+            successors.push(next_pc);
+        }
+        for successor in successors {
+            // The verifier has already checked the targets, this only guards the indexing
+            if successor >= code.len() {
+                return Err(fail(pc, "branches out of the code".to_string()));
+            }
+            let changed = match &mut frames[successor] {
+                Some(existing) => existing
+                    .merge(&frame)
+                    .map_err(|reason| fail(successor, reason))?,
+                empty => {
+                    *empty = Some(frame.clone());
+                    true
+                }
+            };
+            if changed {
+                worklist.push(successor);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies the instruction at pc to the frame, returns whether the execution may continue at the next instruction
+fn execute(
+    frame: &mut Frame,
+    class: &Class,
+    code: &[u8],
+    pc: usize,
+    return_type: JvmType,
+) -> Result<bool, String> {
+    use JvmType::{Double, Float, Integer, Long, Reference};
+
+    let opcode = code[pc];
+    if !bytecode::is_interpreted(opcode) {
+        return Err(format!(
+            "{} isn't implemented by the interpreter",
+            mnemonic(opcode)
+        ));
+    }
+    let u16_operand = || u16::from_be_bytes([code[pc + 1], code[pc + 2]]);
+    match opcode {
+        bytecode::NOP => {}
+        bytecode::ACONST_NULL => frame.stack.push(Slot::Null),
+        bytecode::ICONST_M1..=bytecode::ICONST_5 | bytecode::BIPUSH | bytecode::SIPUSH => {
+            frame.push_type(Integer)
+        }
+        bytecode::LCONST_0 | bytecode::LCONST_1 => frame.push_type(Long),
+        bytecode::FCONST_0..=bytecode::FCONST_2 => frame.push_type(Float),
+        bytecode::DCONST_0 | bytecode::DCONST_1 => frame.push_type(Double),
+        bytecode::LDC | bytecode::LDC_W | bytecode::LDC2_W => {
+            let index = match opcode {
+                bytecode::LDC => code[pc + 1] as u16,
+                _ => u16_operand(),
+            };
+            // Strings, mirrors and dynamic constants are objects, which aren't modeled
+            match (opcode, class.get_loadable(ConstantPoolIndex::from(index))) {
+                (
+                    bytecode::LDC | bytecode::LDC_W,
+                    Ok(LoadableConstant::Value(ty @ (Integer | Float), _)),
+                )
+                | (bytecode::LDC2_W, Ok(LoadableConstant::Value(ty @ (Long | Double), _))) => {
+                    frame.push_type(ty)
+                }
+                _ => return Err(format!("unsupported constant #{}", index)),
+            }
+        }
+        bytecode::ILOAD..=bytecode::ALOAD => {
+            frame.load(code[pc + 1] as usize, local_type(opcode - bytecode::ILOAD))?
+        }
+        bytecode::ILOAD_0..=bytecode::ALOAD_3 => {
+            let offset = opcode - bytecode::ILOAD_0;
+            frame.load((offset % 4) as usize, local_type(offset / 4))?
+        }
+        bytecode::ISTORE..=bytecode::ASTORE => {
+            frame.store(code[pc + 1] as usize, local_type(opcode - bytecode::ISTORE))?
+        }
+        bytecode::ISTORE_0..=bytecode::ASTORE_3 => {
+            let offset = opcode - bytecode::ISTORE_0;
+            frame.store((offset % 4) as usize, local_type(offset / 4))?
+        }
+        bytecode::IINC => {
+            let index = code[pc + 1] as usize;
+            if frame.locals.get(index) != Some(&Some(Slot::Int)) {
+                return Err(format!("local {} isn't an int", index));
+            }
+        }
+        _ if ARRAY_ACCESSES.iter().any(|(load, _, _, _)| *load == opcode) => {
+            let (_, _, atypes, ty) = ARRAY_ACCESSES
+                .iter()
+                .find(|(load, _, _, _)| *load == opcode)
+                .unwrap();
+            frame.pop_type(Integer)?;
+            frame.pop_array(atypes)?;
+            frame.push_type(*ty);
+        }
+        _ if ARRAY_ACCESSES
+            .iter()
+            .any(|(_, store, _, _)| *store == opcode) =>
+        {
+            let (_, _, atypes, ty) = ARRAY_ACCESSES
+                .iter()
+                .find(|(_, store, _, _)| *store == opcode)
+                .unwrap();
+            frame.pop_type(*ty)?;
+            frame.pop_type(Integer)?;
+            frame.pop_array(atypes)?;
+        }
+        bytecode::NEWARRAY => {
+            let atype = code[pc + 1];
+            if !(4..=11).contains(&atype) {
+                return Err(format!("invalid atype {}", atype));
+            }
+            frame.pop_type(Integer)?;
+            frame.stack.push(Slot::Array(atype));
+        }
+        bytecode::ARRAYLENGTH => {
+            frame.pop_array(&[])?;
+            frame.push_type(Integer);
+        }
+        bytecode::POP => drop(frame.take(1)?),
+        bytecode::POP2 => drop(frame.take(2)?),
+        bytecode::DUP | bytecode::DUP2 => {
+            let top = frame.take(if opcode == bytecode::DUP { 1 } else { 2 })?;
+            frame.stack.extend(&top);
+            frame.stack.extend(&top);
+        }
+        bytecode::DUP_X1 | bytecode::DUP_X2 | bytecode::DUP2_X1 | bytecode::DUP2_X2 => {
+            let (top, below) = match opcode {
+                bytecode::DUP_X1 => (1, 1),
+                bytecode::DUP_X2 => (1, 2),
+                bytecode::DUP2_X1 => (2, 1),
+                _ => (2, 2),
+            };
+            let top = frame.take(top)?;
+            let below = frame.take(below)?;
+            frame.stack.extend(&top);
+            frame.stack.extend(&below);
+            frame.stack.extend(&top);
+        }
+        bytecode::SWAP => {
+            let top = frame.take(1)?;
+            let below = frame.take(1)?;
+            frame.stack.extend(&top);
+            frame.stack.extend(&below);
+        }
+        bytecode::IADD..=bytecode::DREM => {
+            let ty = arithmetic_type(opcode - bytecode::IADD);
+            frame.pop_type(ty)?;
+            frame.pop_type(ty)?;
+            frame.push_type(ty);
+        }
+        bytecode::INEG..=bytecode::DNEG => {
+            let ty = arithmetic_type(opcode - bytecode::INEG);
+            frame.pop_type(ty)?;
+            frame.push_type(ty);
+        }
+        bytecode::ISHL..=bytecode::LUSHR => {
+            // The shift distance is always an int
+            let ty = arithmetic_type((opcode - bytecode::ISHL) % 2);
+            frame.pop_type(Integer)?;
+            frame.pop_type(ty)?;
+            frame.push_type(ty);
+        }
+        bytecode::IAND..=bytecode::LXOR => {
+            let ty = arithmetic_type((opcode - bytecode::IAND) % 2);
+            frame.pop_type(ty)?;
+            frame.pop_type(ty)?;
+            frame.push_type(ty);
+        }
+        bytecode::I2L..=bytecode::I2S => {
+            let (from, to) = match opcode {
+                bytecode::I2L => (Integer, Long),
+                bytecode::I2F => (Integer, Float),
+                bytecode::I2D => (Integer, Double),
+                bytecode::L2I => (Long, Integer),
+                bytecode::L2F => (Long, Float),
+                bytecode::L2D => (Long, Double),
+                bytecode::F2I => (Float, Integer),
+                bytecode::F2L => (Float, Long),
+                bytecode::F2D => (Float, Double),
+                bytecode::D2I => (Double, Integer),
+                bytecode::D2L => (Double, Long),
+                bytecode::D2F => (Double, Float),
+                _ => (Integer, Integer), // i2b, i2c, i2s
+            };
+            frame.pop_type(from)?;
+            frame.push_type(to);
+        }
+        bytecode::LCMP..=bytecode::DCMPG => {
+            let ty = match opcode {
+                bytecode::LCMP => Long,
+                bytecode::FCMPL | bytecode::FCMPG => Float,
+                _ => Double,
+            };
+            frame.pop_type(ty)?;
+            frame.pop_type(ty)?;
+            frame.push_type(Integer);
+        }
+        bytecode::IFEQ..=bytecode::IFLE | bytecode::TABLESWITCH | bytecode::LOOKUPSWITCH => {
+            frame.pop_type(Integer)?;
+            return Ok(!matches!(
+                opcode,
+                bytecode::TABLESWITCH | bytecode::LOOKUPSWITCH
+            ));
+        }
+        bytecode::IF_ICMPEQ..=bytecode::IF_ICMPLE => {
+            frame.pop_type(Integer)?;
+            frame.pop_type(Integer)?;
+            return Ok(true);
+        }
+        bytecode::IF_ACMPEQ | bytecode::IF_ACMPNE => {
+            frame.pop_array(&[])?;
+            frame.pop_array(&[])?;
+            return Ok(true);
+        }
+        bytecode::IFNULL | bytecode::IFNONNULL => {
+            frame.pop_array(&[])?;
+            return Ok(true);
+        }
+        bytecode::GOTO | bytecode::GOTO_W => return Ok(false),
+        bytecode::IRETURN..=bytecode::RETURN => {
+            // The verifier has already checked that the instruction matches the return type
+            match return_type {
+                Reference => frame.pop_array(&[])?,
+                ty => frame.pop_type(ty)?,
+            }
+            return Ok(false);
+        }
+        bytecode::GETSTATIC | bytecode::PUTSTATIC => {
+            // The resolution by the interpreter checks that the field exists and is static
+            let ty = match class.field_reference_type(ConstantPoolIndex::from(u16_operand())) {
+                Ok(Reference) | Err(_) => {
+                    return Err(format!("unsupported field #{}", u16_operand()))
+                }
+                Ok(ty) => ty,
+            };
+            if opcode == bytecode::GETSTATIC {
+                frame.push_type(ty);
+            } else {
+                frame.pop_type(ty)?;
+            }
+        }
+        _ => return Err(format!("unsupported instruction {}", mnemonic(opcode))),
+    }
+    Ok(true)
+}
+
+fn mnemonic(opcode: u8) -> String {
+    bytecode::OPCODES
+        .iter()
+        .find(|(defined, _)| *defined == opcode)
+        .map_or("unknown".to_string(), |(_, mnemonic)| {
+            mnemonic.to_ascii_lowercase()
+        })
+}
+
+/// The type of the loads and stores of locals, which are ordered int, long, float, double, reference
+fn local_type(offset: u8) -> JvmType {
+    [
+        JvmType::Integer,
+        JvmType::Long,
+        JvmType::Float,
+        JvmType::Double,
+        JvmType::Reference,
+    ][offset as usize]
+}
+
+/// The type of the arithmetic instructions, which are ordered int, long, float, double
+fn arithmetic_type(offset: u8) -> JvmType {
+    [
+        JvmType::Integer,
+        JvmType::Long,
+        JvmType::Float,
+        JvmType::Double,
+    ][(offset % 4) as usize]
+}
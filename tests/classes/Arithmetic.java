@@ -0,0 +1,30 @@
+// Computes with the operands that the tests pass, so that javac can't fold the operations into constants
+public class Arithmetic {
+    static float frem(float dividend, float divisor) {
+        return dividend % divisor;
+    }
+
+    static double drem(double dividend, double divisor) {
+        return dividend % divisor;
+    }
+
+    static int i2c(int value) {
+        return (char) value;
+    }
+
+    static int storeChar(int value) {
+        char[] chars = { (char) value };
+        return chars[0];
+    }
+
+    // Like Character.highSurrogate and Character.lowSurrogate, but without shifts, which the interpreter doesn't
+    // support yet
+    static int highSurrogate(int codePoint) {
+        int offset = Character.MIN_HIGH_SURROGATE - Character.MIN_SUPPLEMENTARY_CODE_POINT / 0x400;
+        return (char) (codePoint / 0x400 + offset);
+    }
+
+    static int lowSurrogate(int codePoint) {
+        return (char) ((codePoint & 0x3ff) + Character.MIN_LOW_SURROGATE);
+    }
+}
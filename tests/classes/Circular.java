@@ -0,0 +1,6 @@
+// CircularBase.class is compiled from a version that extends Circular, so each class is the superclass of the other
+public class Circular extends CircularBase {
+}
+
+class CircularBase {
+}
@@ -0,0 +1,8 @@
+// javac inlines the constant into greeting, so the tests read the static field to see its ConstantValue attribute
+public class Constants {
+    static final String GREETING = "Hello";
+
+    static String greeting() {
+        return GREETING;
+    }
+}
@@ -0,0 +1,18 @@
+// Runs with -XX:+CrossCheckTiers, which executes the methods without side effects twice
+public class CrossChecks {
+    static int counter;
+
+    static int answer() {
+        return 42;
+    }
+
+    static int increment() {
+        counter++;
+        return counter;
+    }
+
+    // The JIT can compile the call, but not the access of the static field
+    static int callIncrement() {
+        return increment();
+    }
+}
@@ -0,0 +1,88 @@
+// Virtual calls of overloaded and covariantly overridden methods. A method only overrides the methods with the same
+// name and descriptor, so every overload has its own slot in the dispatch table.
+public class Dispatch {
+    static int overloadedOverride() {
+        Shape shape = new Square();
+        return shape.size(0) * 10 + shape.size(0L); // Square only overrides size(int)
+    }
+
+    static int inheritedOverload() {
+        return new Square().size(0L);
+    }
+
+    static int covariantOverride() {
+        Shape shape = new Square();
+        Square square = new Square();
+        return shape.copy().kind() * 10 + square.copy().kind(); // Through the bridge method Shape copy()
+    }
+
+    static int privateMethodNotOverridden() {
+        Square square = new Square();
+        return square.callSecret() * 10 + square.secret();
+    }
+
+    // Dispatch.class is compiled against a version of Stale in which value returned an int and count took an int,
+    // so the calls refer to methods whose names exist, but whose descriptors don't
+    static int staleReturnType() {
+        try {
+            return (int) new Stale().value(0);
+        } catch (NoSuchMethodError e) {
+            return -1;
+        }
+    }
+
+    static int staleParameterType() {
+        try {
+            return new Stale().count(0);
+        } catch (NoSuchMethodError e) {
+            return -1;
+        }
+    }
+}
+
+class Shape {
+    int size(int scale) {
+        return 1;
+    }
+
+    int size(long scale) {
+        return 2;
+    }
+
+    Shape copy() {
+        return new Shape();
+    }
+
+    int kind() {
+        return 1;
+    }
+
+    private int secret() {
+        return 1;
+    }
+
+    int callSecret() {
+        return secret();
+    }
+}
+
+class Square extends Shape {
+    @Override
+    int size(int scale) {
+        return 3;
+    }
+
+    @Override
+    Square copy() {
+        return new Square();
+    }
+
+    @Override
+    int kind() {
+        return 4;
+    }
+
+    int secret() {
+        return 5;
+    }
+}
@@ -0,0 +1,13 @@
+// The static initializer divides by zero, so the first use of the class throws an ExceptionInInitializerError and the
+// later uses throw a NoClassDefFoundError
+public class FailingInitializer {
+    static int value = 1 / zero();
+
+    static int zero() {
+        return 0;
+    }
+
+    static int value() {
+        return value;
+    }
+}
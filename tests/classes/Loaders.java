@@ -0,0 +1,36 @@
+// A class loader written in Java, which defines one class from the class file that it has been given and leaves the
+// other classes to the bootstrap loader
+public class Loaders extends ClassLoader {
+    static int requests; // The calls of loadClass
+
+    private final String definedName;
+    private final byte[] classFile;
+    private boolean defined;
+
+    Loaders(String definedName, byte[] classFile) {
+        this.definedName = definedName;
+        this.classFile = classFile;
+    }
+
+    // The interpreter doesn't support ifnull yet, so the loader remembers whether it has defined the class instead of
+    // checking the result of findLoadedClass0
+    public Class<?> loadClass(String name) {
+        requests++;
+        if (!name.equals(definedName)) {
+            return findBootstrapClass(name);
+        }
+        if (defined) {
+            return findLoadedClass0(name);
+        }
+        defined = true;
+        return defineClass1(this, name, classFile, 0, classFile.length, null, null);
+    }
+
+    static Loaders create(String definedName, byte[] classFile) {
+        return new Loaders(definedName, classFile);
+    }
+
+    static int requests() {
+        return requests;
+    }
+}
@@ -0,0 +1,4 @@
+// Declares a native method that is never bound
+public class NativeFrames {
+    static native int unbound();
+}
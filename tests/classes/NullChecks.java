@@ -0,0 +1,138 @@
+// Dereferences null with each instruction that dereferences an object. Every test method returns 1 if the
+// instruction threw a NullPointerException that could be caught. The called methods don't access their receiver, so
+// only the invoke instructions can throw.
+public class NullChecks implements HasValue {
+    int field;
+
+    public int value() {
+        return 2;
+    }
+
+    public final int finalValue() {
+        return 2;
+    }
+
+    private int privateValue() {
+        return 2;
+    }
+
+    private static NullChecks nothing() {
+        return null;
+    }
+
+    private static int fieldOf(NullChecks checks) {
+        return checks.field; // aload_0 getfield, which is fused into a superinstruction
+    }
+
+    // Compiled by the tests, the JIT doesn't support exception handlers
+    static int compiledInvokeSpecial() {
+        return nothing().privateValue();
+    }
+
+    static int getField() {
+        try {
+            return nothing().field;
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int putField() {
+        try {
+            nothing().field = 1;
+            return 0;
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int loadFieldOfLocal() {
+        try {
+            return fieldOf(null);
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int invokeVirtual() {
+        try {
+            return nothing().value();
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int invokeFinal() {
+        try {
+            return nothing().finalValue();
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int invokeSpecial() {
+        try {
+            return nothing().privateValue();
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int invokeInterface() {
+        HasValue value = nothing();
+        try {
+            return value.value();
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int invokeCompiled() {
+        try {
+            return compiledInvokeSpecial();
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int arrayLength() {
+        int[] array = null;
+        try {
+            return array.length;
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int arrayLoad() {
+        int[] array = null;
+        try {
+            return array[0];
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int arrayStore() {
+        int[] array = null;
+        try {
+            array[0] = 1;
+            return 0;
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int throwNull() {
+        NullPointerException exception = null;
+        try {
+            throw exception;
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+}
+
+interface HasValue {
+    int value();
+}
@@ -0,0 +1,18 @@
+// Recurses until the stack is exhausted, the recursion isn't a tail call
+public class Recursion {
+    static int depth;
+
+    static int recurse(int n) {
+        depth = n;
+        return recurse(n + 1) + 1;
+    }
+
+    // Returns the depth of the recursion when the StackOverflowError was thrown
+    static int overflow() {
+        try {
+            return recurse(0);
+        } catch (StackOverflowError e) {
+            return depth;
+        }
+    }
+}
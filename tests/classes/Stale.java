@@ -0,0 +1,10 @@
+// Changed after Dispatch has been compiled, see Dispatch.staleReturnType and Dispatch.staleParameterType
+public class Stale {
+    long value(int x) {
+        return 1;
+    }
+
+    int count(long x) {
+        return 1;
+    }
+}
@@ -0,0 +1,14 @@
+// Uses opcodes that the interpreter doesn't implement yet (see --list-unsupported), which throw an InternalError
+public class UnsupportedOpcodes {
+    static int shiftLeft(int value) {
+        return value << 1;
+    }
+
+    static int catchInternalError() {
+        try {
+            return shiftLeft(1);
+        } catch (InternalError e) {
+            return -1;
+        }
+    }
+}
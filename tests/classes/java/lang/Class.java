@@ -0,0 +1,7 @@
+package java.lang;
+
+// The fields of java.lang.Class that the VM fills in when it creates a mirror
+public final class Class<T> {
+    private String name;
+    private ClassLoader classLoader;
+}
@@ -0,0 +1,14 @@
+package java.lang;
+
+// The part of java.lang.ClassLoader that the VM implements, so that the tests can write class loaders without a class
+// library. The natives have the signatures of JDK 9 and later, but are visible to subclasses.
+public abstract class ClassLoader {
+    public abstract Class<?> loadClass(String name);
+
+    protected static native Class<?> defineClass1(ClassLoader loader, String name, byte[] b, int off, int len,
+            Object protectionDomain, String source);
+
+    protected final native Class<?> findLoadedClass0(String name);
+
+    protected static native Class<?> findBootstrapClass(String name);
+}